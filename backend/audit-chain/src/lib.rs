@@ -0,0 +1,360 @@
+//! Hashing and verification of the tamper-evident `audit_log` chain.
+//!
+//! Every entry stores the hash of the one before it (`prev_hash`) and its
+//! own (`entry_hash`), SHA-256 over the previous hash and the entry's
+//! fields. Editing, deleting or inserting a row breaks the chain from that
+//! row on, which [`ChainReport::check`] reports. The first chained entry
+//! follows [`GENESIS_HASH`]; rows written before chaining existed have no
+//! hashes.
+//!
+//! The backend's `shared::audit` and the domain model's
+//! `albergue_persistence::audit` append to the same chain through this
+//! crate; `tests/fixtures/audit_vectors.json` holds entries both must keep
+//! hashing the same way.
+
+#![deny(warnings)]
+#![warn(clippy::all, clippy::pedantic)]
+
+use std::fmt::Write;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use ring::digest;
+use serde::Serialize;
+use serde_json::Value;
+
+/// `prev_hash` of the first chained entry.
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+const HASH_DOMAIN: &[u8] = b"albergue.audit.v1\0";
+
+/// The hashed fields of an entry, borrowed from whichever type holds it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EntryFields<'a> {
+    pub table_name: &'a str,
+    pub record_id: &'a str,
+    pub action: &'a str,
+    pub old_values: Option<&'a Value>,
+    pub new_values: Option<&'a Value>,
+    pub user_id: Option<i64>,
+    pub ip_address: Option<&'a str>,
+    pub user_agent: Option<&'a str>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl EntryFields<'_> {
+    /// The hash of the entry when it follows `prev_hash`.
+    #[must_use]
+    pub fn hash(&self, prev_hash: &str) -> String {
+        let old_values = self.old_values.map(canonical_json);
+        let new_values = self.new_values.map(canonical_json);
+        let user_id = self.user_id.map(|id| id.to_string());
+        let created_at = timestamp(self.created_at);
+        let fields = [
+            Some(prev_hash),
+            Some(self.table_name),
+            Some(self.record_id),
+            Some(self.action),
+            old_values.as_deref(),
+            new_values.as_deref(),
+            user_id.as_deref(),
+            self.ip_address,
+            self.user_agent,
+            Some(created_at.as_str()),
+        ];
+        let mut ctx = digest::Context::new(&digest::SHA256);
+        ctx.update(HASH_DOMAIN);
+        for field in fields {
+            match field {
+                None => ctx.update(&[0]),
+                Some(value) => {
+                    ctx.update(&[1]);
+                    ctx.update(&u32::try_from(value.len()).unwrap_or(u32::MAX).to_be_bytes());
+                    ctx.update(value.as_bytes());
+                }
+            }
+        }
+        ctx.finish()
+            .as_ref()
+            .iter()
+            .fold(String::with_capacity(64), |mut out, b| {
+                let _ = write!(out, "{b:02x}");
+                out
+            })
+    }
+}
+
+/// How timestamps are written to `created_at` and hashed: UTC, microseconds.
+#[must_use]
+pub fn timestamp(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+/// JSON with object keys sorted and no whitespace, so a value hashes the
+/// same whatever order it was stored in.
+#[must_use]
+pub fn canonical_json(value: &Value) -> String {
+    fn write_value(out: &mut String, value: &Value) {
+        match value {
+            Value::Object(map) => {
+                let mut keys: Vec<&String> = map.keys().collect();
+                keys.sort();
+                out.push('{');
+                for (i, key) in keys.into_iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    out.push_str(&Value::String(key.clone()).to_string());
+                    out.push(':');
+                    write_value(out, &map[key]);
+                }
+                out.push('}');
+            }
+            Value::Array(items) => {
+                out.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    write_value(out, item);
+                }
+                out.push(']');
+            }
+            scalar => out.push_str(&scalar.to_string()),
+        }
+    }
+    let mut out = String::new();
+    write_value(&mut out, value);
+    out
+}
+
+/// A stored `audit_log` row, however it was loaded.
+pub trait ChainedRow {
+    fn id(&self) -> i64;
+    fn prev_hash(&self) -> Option<&str>;
+    fn entry_hash(&self) -> Option<&str>;
+    fn fields(&self) -> EntryFields<'_>;
+}
+
+impl<T: ChainedRow + ?Sized> ChainedRow for &T {
+    fn id(&self) -> i64 {
+        (**self).id()
+    }
+
+    fn prev_hash(&self) -> Option<&str> {
+        (**self).prev_hash()
+    }
+
+    fn entry_hash(&self) -> Option<&str> {
+        (**self).entry_hash()
+    }
+
+    fn fields(&self) -> EntryFields<'_> {
+        (**self).fields()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChainBreak {
+    pub id: i64,
+    pub reason: String,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct ChainReport {
+    /// Chained entries whose hashes check out.
+    pub verified: u64,
+    /// Rows from before chaining started.
+    pub unchained: u64,
+    /// Hash of the last verified entry. Keep a copy elsewhere: it is what
+    /// shows the newest entries were not removed.
+    pub head: Option<String>,
+    pub broken: Option<ChainBreak>,
+}
+
+impl ChainReport {
+    #[must_use]
+    pub fn is_intact(&self) -> bool {
+        self.broken.is_none()
+    }
+
+    /// Checks the next row in `id` order against the chain so far. Returns
+    /// `false` once the chain is broken; later rows are not checked.
+    pub fn check(&mut self, row: &impl ChainedRow) -> bool {
+        if self.broken.is_some() {
+            return false;
+        }
+        let (Some(prev_hash), Some(entry_hash)) = (row.prev_hash(), row.entry_hash()) else {
+            if self.head.is_none() {
+                self.unchained += 1;
+                return true;
+            }
+            return self.break_at(row.id(), "entry is not chained");
+        };
+        if prev_hash != self.head.as_deref().unwrap_or(GENESIS_HASH) {
+            return self.break_at(
+                row.id(),
+                "previous hash does not match: an entry was removed or inserted",
+            );
+        }
+        if row.fields().hash(prev_hash) != entry_hash {
+            return self.break_at(row.id(), "entry hash does not match: the entry was altered");
+        }
+        self.verified += 1;
+        self.head = Some(entry_hash.to_string());
+        true
+    }
+
+    fn break_at(&mut self, id: i64, reason: &str) -> bool {
+        self.broken = Some(ChainBreak {
+            id,
+            reason: reason.to_string(),
+        });
+        false
+    }
+}
+
+/// Checks rows in `id` order, stopping at the first that does not follow
+/// from the one before.
+pub fn verify_chain<R: ChainedRow>(rows: impl IntoIterator<Item = R>) -> ChainReport {
+    let mut report = ChainReport::default();
+    for row in rows {
+        if !report.check(&row) {
+            break;
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    struct Row {
+        id: i64,
+        fields: EntryFields<'static>,
+        prev_hash: Option<String>,
+        entry_hash: Option<String>,
+    }
+
+    impl ChainedRow for Row {
+        fn id(&self) -> i64 {
+            self.id
+        }
+
+        fn prev_hash(&self) -> Option<&str> {
+            self.prev_hash.as_deref()
+        }
+
+        fn entry_hash(&self) -> Option<&str> {
+            self.entry_hash.as_deref()
+        }
+
+        fn fields(&self) -> EntryFields<'_> {
+            self.fields
+        }
+    }
+
+    fn fields(record_id: &'static str) -> EntryFields<'static> {
+        EntryFields {
+            table_name: "pilgrims",
+            record_id,
+            action: "read",
+            old_values: None,
+            new_values: None,
+            user_id: Some(3),
+            ip_address: None,
+            user_agent: None,
+            created_at: DateTime::parse_from_rfc3339("2026-10-18T09:30:00Z")
+                .unwrap()
+                .with_timezone(&Utc),
+        }
+    }
+
+    fn chain(entries: &[EntryFields<'static>]) -> Vec<Row> {
+        let mut prev = GENESIS_HASH.to_string();
+        (1..)
+            .zip(entries)
+            .map(|(id, fields)| {
+                let hash = fields.hash(&prev);
+                let row = Row {
+                    id,
+                    fields: *fields,
+                    prev_hash: Some(prev.clone()),
+                    entry_hash: Some(hash.clone()),
+                };
+                prev = hash;
+                row
+            })
+            .collect()
+    }
+
+    #[test]
+    fn detects_tampering() {
+        let entries = [fields("42"), fields("43"), fields("42")];
+        let report = verify_chain(chain(&entries));
+        assert!(report.is_intact());
+        assert_eq!(report.verified, 3);
+        assert_eq!(report.head, chain(&entries)[2].entry_hash);
+
+        let mut altered = chain(&entries);
+        altered[1].fields.user_id = None;
+        assert_eq!(verify_chain(altered).broken.unwrap().id, 2);
+
+        let mut removed = chain(&entries);
+        removed.remove(1);
+        let report = verify_chain(removed);
+        assert_eq!(report.broken.unwrap().id, 3);
+        assert_eq!(report.verified, 1);
+
+        let legacy = || Row {
+            id: 0,
+            fields: fields("42"),
+            prev_hash: None,
+            entry_hash: None,
+        };
+        let mut with_legacy = vec![legacy()];
+        with_legacy.extend(chain(&entries));
+        let report = verify_chain(with_legacy);
+        assert_eq!((report.unchained, report.verified), (1, 3));
+
+        let mut with_legacy = chain(&entries);
+        with_legacy.push(Row { id: 9, ..legacy() });
+        assert_eq!(verify_chain(with_legacy).broken.unwrap().id, 9);
+    }
+
+    /// Entries `shared::audit` and `albergue_persistence::audit` hash too.
+    #[test]
+    fn hashes_the_shared_vectors() {
+        let vectors: Value =
+            serde_json::from_str(include_str!("../tests/fixtures/audit_vectors.json")).unwrap();
+        for vector in vectors["entries"].as_array().unwrap() {
+            let text = |key: &str| vector[key].as_str();
+            let values = |key: &str| Some(&vector[key]).filter(|v| !v.is_null());
+            let fields = EntryFields {
+                table_name: text("table_name").unwrap(),
+                record_id: text("record_id").unwrap(),
+                action: text("action").unwrap(),
+                old_values: values("old_values"),
+                new_values: values("new_values"),
+                user_id: vector["user_id"].as_i64(),
+                ip_address: text("ip_address"),
+                user_agent: text("user_agent"),
+                created_at: DateTime::parse_from_rfc3339(text("created_at").unwrap())
+                    .unwrap()
+                    .with_timezone(&Utc),
+            };
+            assert_eq!(
+                fields.hash(text("prev_hash").unwrap()),
+                text("entry_hash").unwrap()
+            );
+        }
+        for vector in vectors["canonical_json"].as_array().unwrap() {
+            assert_eq!(
+                canonical_json(&vector["value"]),
+                vector["canonical"].as_str().unwrap()
+            );
+        }
+        assert_eq!(canonical_json(&json!("é\n")), r#""é\n""#);
+    }
+}
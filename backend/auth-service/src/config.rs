@@ -1,31 +1,72 @@
 use async_trait::async_trait;
 use chrono::Duration;
-use openidconnect::{core::CoreProviderMetadata, ClientId, ClientSecret, IssuerUrl, RedirectUrl};
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
+use spin_sdk::{key_value::Store, variables};
 use std::{env, sync::Arc};
 
-use crate::providers::logto::LogtoProvider;
-use crate::providers::zitadel::ZitadelProvider;
-use crate::providers::github::GitHubProvider;
+use crate::identity::Identity;
+use crate::login_transaction::AUTH_STORE;
+use crate::registry::{self, ProviderHealth, DEFAULT_DISCOVERY_TTL_SECONDS};
+use crate::signing::SigningKey;
 
 #[async_trait(?Send)]
 pub trait IdentityProvider: Sync + 'static {
-    fn name(&self) -> &'static str {
-        "identity_provider"
+    fn name(&self) -> &str;
+
+    /// OIDC providers return an ID token carrying the login's `nonce`; plain
+    /// OAuth 2.0 providers such as GitHub do not.
+    fn issues_id_tokens(&self) -> bool {
+        true
     }
-    fn authorization_url(&self, state: &str) -> String;
-    async fn exchange_code(&self, code: &str, redirect_uri: &str) -> anyhow::Result<TokenResponse>;
+    /// Authorization request carrying `state`, the S256 PKCE `code_challenge`
+    /// and, for OIDC providers, the `nonce`.
+    fn authorization_url(&self, state: &str, code_challenge: &str, nonce: &str) -> String;
+    async fn exchange_code(
+        &self,
+        code: &str,
+        redirect_uri: &str,
+        code_verifier: &str,
+    ) -> anyhow::Result<TokenResponse>;
     async fn refresh_token(&self, refresh_token: &str) -> anyhow::Result<TokenResponse>;
+    /// Who the tokens were issued to, from the ID token or the provider's
+    /// user API.
+    async fn identity(&self, token: &TokenResponse) -> anyhow::Result<Identity>;
+    /// Where to send the browser to end the user's session at the provider
+    /// (RP-initiated logout), when the provider supports it.
+    async fn end_session_url(
+        &self,
+        _id_token_hint: Option<&str>,
+        _post_logout_redirect_uri: &str,
+    ) -> anyhow::Result<Option<String>> {
+        Ok(None)
+    }
     fn jwks_uri(&self) -> String;   
 }
 
 #[derive(Clone)]
 pub struct AppConfig {
+    /// Providers that can be signed in with, in declaration order.
     pub providers: Vec<Arc<dyn IdentityProvider>>,
-    pub jwt_secret: Vec<u8>,        
+    /// Health of every declared provider, usable or not.
+    pub provider_health: Vec<ProviderHealth>,
+    /// Current signing key first, then keys being rotated out, which are
+    /// still published so tokens they signed stay verifiable.
+    pub signing_keys: Arc<Vec<SigningKey>>,
     pub token_ttl: Duration,        
+    /// How long a sign-in can be refreshed before the user signs in again.
+    pub session_ttl: Duration,
     pub redirect_uri: String,       
-    pub client_id: String,
+    pub post_logout_redirect_uri: String,
+    /// `iss` of the tokens this service mints; discovery lives under it.
+    pub issuer: String,
+    /// `aud` of the tokens this service mints.
+    pub audience: String,
+}
+
+impl AppConfig {
+    pub fn signing_key(&self) -> &SigningKey {
+        &self.signing_keys[0]
+    }
 }
 
 unsafe impl Send for AppConfig {}
@@ -40,112 +81,73 @@ pub struct TokenResponse {
     pub token_type: String,
 }
 
-#[derive(Serialize)]
-pub struct Claims {
-    pub sub: String,
-    pub exp: usize,
-    pub aud: String,
-    pub iss: String,
+pub const DEFAULT_ISSUER: &str = "https://alberguecarrascalejo.fermyon.app/api/auth";
+pub const DEFAULT_AUDIENCE: &str = "albergue-api";
+/// Thirty days.
+pub const DEFAULT_SESSION_TTL_SECONDS: i64 = 2_592_000;
+
+/// A Spin variable, falling back to the upper-cased environment variable.
+pub(crate) fn setting(name: &str) -> Option<String> {
+    variables::get(name)
+        .ok()
+        .or_else(|| env::var(name.to_uppercase()).ok())
+        .filter(|v| !v.trim().is_empty())
 }
 
-pub async fn load_config() -> anyhow::Result<AppConfig> {
-    let mut providers: Vec<Arc<dyn IdentityProvider>> = Vec::new();     
-
-    let redirect_uri = format!("{}/sign-in-callback", env::var("LOGTO_ORIGIN_URL")?);
-    let jwt_secret = env::var("JWT_SECRET").unwrap_or_default().into_bytes();
-    let ttl_secs: i64 = env::var("TOKEN_TTL").unwrap_or_else(|_| "3600".into()).parse()?;
-    let app_client_id = env::var("LOGTO_APP_ID").unwrap_or_else(|_| "spin-auth".into());
-
-    if let (Ok(issuer), Ok(client_id), Ok(client_secret)) = (
-        env::var("LOGTO_ISSUER_ENDPOINT"),
-        env::var("LOGTO_APP_ID"),   
-        env::var("LOGTO_APP_SECRET"),
-    ) {
-        if let Ok(meta) = discover_oidc(&issuer).await {
-            providers.push(Arc::new(LogtoProvider {
-                metadata: meta,     
-                client_id: ClientId::new(client_id),
-                client_secret: ClientSecret::new(client_secret),        
-                redirect: RedirectUrl::new(redirect_uri.clone())?,      
-            }));
-        }
+fn load_signing_keys() -> anyhow::Result<Vec<SigningKey>> {
+    let current = setting("jwt_signing_key")
+        .ok_or_else(|| anyhow::anyhow!("jwt_signing_key is not configured"))?;
+    let mut keys = vec![SigningKey::from_pem(&current)?];
+    if let Some(previous) = setting("jwt_previous_signing_key") {
+        keys.push(SigningKey::from_pem(&previous)?);
     }
+    Ok(keys)
+}
 
-    if let (Ok(domain), Ok(client_id), Ok(client_secret)) = (
-        env::var("ZITADEL_DOMAIN"), 
-        env::var("LOGTO_APP_ID"),   
-        env::var("LOGTO_APP_SECRET"),
-    ) {
-         let issuer = format!("https://{}/oidc", domain);
-         if let Ok(meta) = discover_oidc(&issuer).await {
-            providers.push(Arc::new(ZitadelProvider {
-                metadata: meta,     
-                client_id: ClientId::new(client_id),
-                client_secret: ClientSecret::new(client_secret),        
-                redirect: RedirectUrl::new(redirect_uri.clone())?,      
-            }));
-         }
+/// Providers declared in the `auth_providers` setting or, when it is unset,
+/// by the original per-provider variables.
+fn declared_providers() -> anyhow::Result<Vec<registry::ProviderConfig>> {
+    match setting("auth_providers") {
+        Some(json) => registry::parse_providers(&json)
+            .map_err(|e| anyhow::anyhow!("auth_providers is invalid: {e}")),
+        None => Ok(registry::legacy_providers(setting)),
     }
+}
 
-    if let (Ok(client_id), Ok(client_secret)) = (
-        env::var("GITHUB_CLIENT_ID"),
-        env::var("GITHUB_CLIENT_SECRET"),
-    ) {
-        providers.push(Arc::new(GitHubProvider {
-            client_id,
-            client_secret,
-            redirect_uri: redirect_uri.clone(),
-        }));
-    }
+/// Runs on every request, as Spin keeps no state between them; provider
+/// discovery comes from the key-value store rather than the network.
+pub async fn load_config() -> anyhow::Result<AppConfig> {
+    let origin = env::var("LOGTO_ORIGIN_URL")?;
+    let redirect_uri = format!("{origin}/sign-in-callback");
+    let signing_keys = load_signing_keys()?;
+    let ttl_secs: i64 = env::var("TOKEN_TTL").unwrap_or_else(|_| "3600".into()).parse()?;
+    let session_ttl_secs: i64 = env::var("SESSION_TTL")
+        .unwrap_or_else(|_| DEFAULT_SESSION_TTL_SECONDS.to_string())
+        .parse()?;
+    let discovery_ttl_secs: i64 = env::var("DISCOVERY_TTL")
+        .unwrap_or_else(|_| DEFAULT_DISCOVERY_TTL_SECONDS.to_string())
+        .parse()?;
+
+    let declared = declared_providers()?;
+    let store = Store::open(AUTH_STORE)?;
+    let (providers, provider_health) = registry::load(
+        &store,
+        &declared,
+        &redirect_uri,
+        setting,
+        Duration::seconds(discovery_ttl_secs),
+    )
+    .await;
 
     Ok(AppConfig {
         providers,
-        client_id: app_client_id,   
+        provider_health,
+        signing_keys: Arc::new(signing_keys),
         redirect_uri,
-        jwt_secret,
+        post_logout_redirect_uri: format!("{}/", origin.trim_end_matches('/')),
+        issuer: setting("auth_issuer").unwrap_or_else(|| DEFAULT_ISSUER.to_string()),
+        audience: setting("token_audience").unwrap_or_else(|| DEFAULT_AUDIENCE.to_string()),
         token_ttl: Duration::seconds(ttl_secs),
+        session_ttl: Duration::seconds(session_ttl_secs),
     })
 }
-
-async fn discover_oidc(issuer: &str) -> anyhow::Result<CoreProviderMetadata> {
-    let http = |req: openidconnect::HttpRequest| {
-        async move {
-            let mut builder = http::Request::builder()
-                .method(req.method())     
-                .uri(req.uri());
-
-            for (key, value) in req.headers() {
-                builder = builder.header(key.as_str(), value.as_bytes());
-            }
-
-            let body = if req.body().is_empty() {
-                vec![]
-            } else {
-                req.body().clone()
-            };
-
-            let request = builder.body(body)
-                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
-
-            let response: http::Response<Vec<u8>> = spin_sdk::http::send(request).await
-                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
-
-            let status_code = openidconnect::http::StatusCode::from_u16(response.status().as_u16())
-                 .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
-
-            let mut builder02 = openidconnect::http::Response::builder()
-                .status(status_code);
-
-            for (key, value) in response.headers() {
-                builder02 = builder02.header(key.as_str(), value.as_bytes());
-            }
-
-            let body_bytes = response.body().clone();
-
-            builder02.body(body_bytes).map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))       
-        }
-    };
-
-    let meta = CoreProviderMetadata::discover_async(IssuerUrl::new(issuer.to_string())?, &http).await?;      
-    Ok(meta)
-}
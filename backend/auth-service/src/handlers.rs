@@ -1,107 +1,238 @@
-use spin_sdk::http::{Request, Response};
+use spin_sdk::{
+    http::{Request, Response},
+    key_value::Store,
+};
 use chrono::Utc;
-use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
 use serde_json::json;
 use std::collections::HashMap;
 use http::StatusCode;
 
-use crate::config::{AppConfig, Claims};
-
-pub async fn login_handler(_req: Request, cfg: &AppConfig) -> anyhow::Result<Response> {
-    let state = uuid::Uuid::new_v4().to_string();
-    if let Some(provider) = cfg.providers.first() {     
-        let url = provider.authorization_url(&state);   
-        
-        Ok(Response::builder()
-            .status(StatusCode::TEMPORARY_REDIRECT)
-            .header("Location", url)
-            .body(vec![])
-            .build())
-    } else {
-        Ok(Response::builder()
-            .status(StatusCode::INTERNAL_SERVER_ERROR)
-            .body("No auth providers configured")
-            .build())
-    }
+use crate::config::{AppConfig, IdentityProvider, TokenResponse};
+use crate::sessions::{self, SessionError};
+use crate::signing::{jwks, SigningKey};
+use crate::staff::{self, NewStaffAccount, StaffError};
+use crate::tokens::{self, Caller, Role};
+use crate::totp;
+use crate::users;
+use crate::login_transaction::{self, LoginError, LoginTransaction, AUTH_STORE};
+
+fn query_params(req: &Request) -> HashMap<String, String> {
+    let query = req.uri().split_once('?').map_or("", |(_, q)| q);
+    serde_urlencoded::from_str(query).unwrap_or_default()
 }
 
-pub async fn callback_handler(req: Request, cfg: &AppConfig) -> anyhow::Result<Response> {
-    let uri = req.uri();
-    let query = uri.split_once('?').map(|(_, q)| q).unwrap_or("");
-    let params: HashMap<String, String> = serde_urlencoded::from_str(query).unwrap_or_default();
-    
-    let code = match params.get("code") {
-        Some(c) => c,
-        None => {
+pub async fn login_handler(req: Request, cfg: &AppConfig) -> anyhow::Result<Response> {
+    let params = query_params(&req);
+    let provider = match params.get("provider") {
+        Some(name) => cfg.providers.iter().find(|p| p.name() == name.as_str()),
+        None => cfg.providers.first(),
+    };
+    let Some(provider) = provider else {
+        // Declared but failing discovery or configuration.
+        if let Some(health) = params
+            .get("provider")
+            .and_then(|name| cfg.provider_health.iter().find(|h| &h.name == name))
+        {
             return Ok(Response::builder()
-                .status(StatusCode::BAD_REQUEST)
-                .body("Missing code")
+                .status(StatusCode::SERVICE_UNAVAILABLE)
+                .header("Retry-After", "60")
+                .body(format!(
+                    "Auth provider {} is unavailable: {}",
+                    health.name,
+                    health.last_error.as_deref().unwrap_or("not reachable")
+                ))
                 .build());
-        },
+        }
+        return Ok(Response::builder()
+            .status(StatusCode::BAD_REQUEST)
+            .body("Unknown or unconfigured auth provider")
+            .build());
     };
 
-    let mut token = None;
-    let mut last_error = String::new();
-
-    for provider in &cfg.providers {
-        match provider.exchange_code(code, &cfg.redirect_uri).await {
-            Ok(t) => {
-                token = Some(t);
-                break;
-            }
-            Err(e) => {
-                last_error = e.to_string();
-            }
+    let store = Store::open(AUTH_STORE)?;
+    let begun = LoginTransaction::new(
+        provider.name(),
+        params.get("return_to").map(String::as_str),
+        Utc::now(),
+    )
+    .and_then(|transaction| {
+        let state = login_transaction::begin(&store, &transaction)?;
+        Ok((transaction, state))
+    });
+    let (transaction, state) = match begun {
+        Ok(begun) => begun,
+        Err(e) => {
+            return Ok(Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(e.to_string())
+                .build());
         }
-    }
+    };
+    let url = provider.authorization_url(&state, &transaction.code_challenge(), &transaction.nonce);
 
-    let token = match token {
-        Some(t) => t,
-        None => {
+    Ok(Response::builder()
+        .status(StatusCode::TEMPORARY_REDIRECT)
+        .header("Location", url)
+        .header("Set-Cookie", login_transaction::state_cookie(&state))
+        .header("Cache-Control", "no-store")
+        .body(vec![])
+        .build())
+}
+
+pub async fn callback_handler(req: Request, cfg: &AppConfig) -> anyhow::Result<Response> {
+    let params = query_params(&req);
+
+    let Some(state) = params.get("state") else {
+        return Ok(Response::builder()
+            .status(StatusCode::BAD_REQUEST)
+            .body("Missing state")
+            .build());
+    };
+    // A `state` is only honoured in the browser that started the login.
+    if let Err(e) =
+        login_transaction::check_state_cookie(req.header("cookie").and_then(|h| h.as_str()), state)
+    {
+        return Ok(Response::builder()
+            .status(StatusCode::BAD_REQUEST)
+            .body(e.to_string())
+            .build());
+    }
+    let transaction = match login_transaction::take(state) {
+        Ok(t) => t,
+        Err(e @ LoginError::Storage(_)) => {
             return Ok(Response::builder()
-                .status(StatusCode::UNAUTHORIZED)
-                .body(format!("Auth failed: {}", last_error))
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(e.to_string())
                 .build());
-        },
+        }
+        Err(e) => {
+            return Ok(Response::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .body(e.to_string())
+                .build());
+        }
     };
 
-    let claims = Claims {
-        sub: token.access_token.clone(),
-        exp: (Utc::now() + cfg.token_ttl).timestamp() as usize,
-        aud: cfg.client_id.clone(),
-        iss: "spin-auth-service".into(),
+    if let Some(error) = params.get("error") {
+        return Ok(Response::builder()
+            .status(StatusCode::UNAUTHORIZED)
+            .body(format!("Auth failed: {error}"))
+            .build());
+    }
+    let Some(code) = params.get("code") else {
+        return Ok(Response::builder()
+            .status(StatusCode::BAD_REQUEST)
+            .body("Missing code")
+            .build());
+    };
+
+    // The code is only ever sent back to the provider that started the flow.
+    let Some(provider) = cfg
+        .providers
+        .iter()
+        .find(|p| p.name() == transaction.provider)
+    else {
+        return Ok(Response::builder()
+            .status(StatusCode::BAD_REQUEST)
+            .body(format!("Auth provider {} is no longer configured", transaction.provider))
+            .build());
     };
-    let header = Header::new(Algorithm::HS256);
-    let jwt = match encode(&header, &claims, &EncodingKey::from_secret(&cfg.jwt_secret)) {
+
+    let token = match provider
+        .exchange_code(code, &cfg.redirect_uri, &transaction.code_verifier)
+        .await
+    {
         Ok(t) => t,
         Err(e) => {
-             return Ok(Response::builder()
-                .status(StatusCode::INTERNAL_SERVER_ERROR)
-                .body(e.to_string())
+            return Ok(Response::builder()
+                .status(StatusCode::UNAUTHORIZED)
+                .body(format!("Auth failed: {e}"))
                 .build());
-        },
+        }
+    };
+
+    if provider.issues_id_tokens() {
+        let checked = match token.id_token.as_deref() {
+            Some(id_token) => transaction.check_id_token(id_token),
+            None => Err(LoginError::NonceMismatch),
+        };
+        if let Err(e) = checked {
+            return Ok(Response::builder()
+                .status(StatusCode::UNAUTHORIZED)
+                .body(format!("Auth failed: {e}"))
+                .build());
+        }
+    }
+
+    let user_agent = req.header("user-agent").and_then(|h| h.as_str());
+    let (jwt, refresh_token) = match sign_in(cfg, &**provider, &token, user_agent).await {
+        Ok(issued) => issued,
+        Err(resp) => return Ok(resp),
     };
 
     let body = json!({
         "jwt": jwt,
-        "refresh_token": token.refresh_token,
+        "token_type": "Bearer",
+        "expires_in": cfg.token_ttl.num_seconds(),
+        "refresh_token": refresh_token,
+        "return_to": transaction.return_to,
     });
 
     Ok(Response::builder()
         .status(StatusCode::OK)
         .header("Content-Type", "application/json")
+        .header("Set-Cookie", login_transaction::clear_state_cookie())
         .body(serde_json::to_vec(&body)?)
         .build())
 }
 
-pub async fn logout_handler(_req: Request, _cfg: &AppConfig) -> anyhow::Result<Response> {       
-    Ok(Response::builder()
-        .status(StatusCode::TEMPORARY_REDIRECT)
-        .header("Location", "/")
-        .body(vec![])
-        .build())
+/// Signs the caller out: revokes the session of the bearer token or, when the
+/// access token has already expired, of `refresh_token` in the body. The
+/// provider's session lives in its own cookies, so its end-session URL is
+/// returned for the browser to visit rather than called from here.
+pub async fn logout_handler(req: Request, cfg: &AppConfig) -> anyhow::Result<Response> {
+    let store = Store::open(AUTH_STORE)?;
+    let session = if bearer_token(&req).is_some() {
+        let caller = match authenticate(&req, cfg, &store) {
+            Ok(caller) => caller,
+            Err(resp) => return Ok(resp),
+        };
+        sessions::find(&caller.session_id)?
+    } else {
+        let payload: HashMap<String, String> =
+            serde_json::from_slice(req.body()).unwrap_or_default();
+        let Some(refresh) = payload.get("refresh_token") else {
+            return Ok(Response::builder()
+                .status(StatusCode::UNAUTHORIZED)
+                .body("Missing bearer token or refresh_token")
+                .build());
+        };
+        sessions::find_by_refresh_token(refresh)?
+    };
+
+    // Signing out of a session that is already gone is not an error.
+    let Some(session) = session.filter(|s| s.revoked_at.is_none()) else {
+        let body = json!({ "revoked": false, "end_session_url": null });
+        return json_response(StatusCode::OK, &body);
+    };
+    sessions::revoke(&store, session.user_id, &session.id, cfg.token_ttl)?;
+
+    let end_session_url = match cfg.providers.iter().find(|p| p.name() == session.provider) {
+        Some(provider) => provider
+            .end_session_url(session.id_token.as_deref(), &cfg.post_logout_redirect_uri)
+            .await
+            .ok()
+            .flatten(),
+        None => None,
+    };
+    json_response(
+        StatusCode::OK,
+        &json!({ "revoked": true, "end_session_url": end_session_url }),
+    )
 }
 
+/// Trades a refresh token for a new access token and the next refresh token
+/// of the session.
 pub async fn refresh_handler(req: Request, cfg: &AppConfig) -> anyhow::Result<Response> {
     let body = req.into_body();
     let payload: HashMap<String, String> = serde_json::from_slice(&body).unwrap_or_default();
@@ -116,74 +247,381 @@ pub async fn refresh_handler(req: Request, cfg: &AppConfig) -> anyhow::Result<Re
         },
     };
 
-    let mut token = None;
-    let mut last_error = String::new();
-
-    for provider in &cfg.providers {
-        match provider.refresh_token(refresh).await {   
-            Ok(t) => {
-                token = Some(t);
-                break;
-            }
-            Err(e) => {
-                last_error = e.to_string();
-            }
+    let store = Store::open(AUTH_STORE)?;
+    let refreshed = match sessions::rotate(&store, refresh, cfg.token_ttl) {
+        Ok(r) => r,
+        Err(e @ SessionError::Storage(_)) => {
+            return Ok(Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(e.to_string())
+                .build());
         }
-    }
-
-    let token = match token {
-        Some(t) => t,
-        None => {
+        Err(e) => {
             return Ok(Response::builder()
                 .status(StatusCode::UNAUTHORIZED)
-                .body(format!("Refresh failed: {}", last_error))
+                .body(format!("Refresh failed: {e}"))
                 .build());
-        },
+        }
     };
 
-    let claims = Claims {
-        sub: token.access_token.clone(),
-        exp: (Utc::now() + cfg.token_ttl).timestamp() as usize,
-        aud: cfg.client_id.clone(),
-        iss: "spin-auth-service".into(),
+    let jwt = tokens::issue(
+        cfg,
+        &refreshed.user,
+        &refreshed.identity,
+        &refreshed.session_id,
+    )?;
+    let body = json!({
+        "jwt": jwt,
+        "token_type": "Bearer",
+        "expires_in": cfg.token_ttl.num_seconds(),
+        "refresh_token": refreshed.refresh_token,
+    });
+    json_response(StatusCode::OK, &body)
+}
+
+/// The caller's active sessions, flagging the one the request came from.
+pub async fn list_sessions_handler(req: Request, cfg: &AppConfig) -> anyhow::Result<Response> {
+    let store = Store::open(AUTH_STORE)?;
+    let caller = match authenticate(&req, cfg, &store) {
+        Ok(caller) => caller,
+        Err(resp) => return Ok(resp),
     };
-    let jwt = match encode(
-        &Header::new(Algorithm::HS256),
-        &claims,
-        &EncodingKey::from_secret(&cfg.jwt_secret),     
-    ) {
-        Ok(t) => t,
+    let listed: Vec<serde_json::Value> = sessions::list(caller.user_id)?
+        .into_iter()
+        .map(|session| {
+            let current = session.id == caller.session_id;
+            let mut entry = json!(session);
+            entry["current"] = json!(current);
+            entry
+        })
+        .collect();
+    json_response(StatusCode::OK, &json!({ "sessions": listed }))
+}
+
+/// `DELETE /api/auth/sessions/{id}`: revokes one of the caller's sessions.
+pub async fn revoke_session_handler(req: Request, cfg: &AppConfig) -> anyhow::Result<Response> {
+    let store = Store::open(AUTH_STORE)?;
+    let caller = match authenticate(&req, cfg, &store) {
+        Ok(caller) => caller,
+        Err(resp) => return Ok(resp),
+    };
+    let session_id = req.path().trim_start_matches("/api/auth/sessions/");
+    if !sessions::revoke(&store, caller.user_id, session_id, cfg.token_ttl)? {
+        return Ok(Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body("No such session")
+            .build());
+    }
+    Ok(Response::builder()
+        .status(StatusCode::NO_CONTENT)
+        .body(vec![])
+        .build())
+}
+
+/// `DELETE /api/auth/sessions`: signs the caller out everywhere.
+pub async fn revoke_all_sessions_handler(
+    req: Request,
+    cfg: &AppConfig,
+) -> anyhow::Result<Response> {
+    let store = Store::open(AUTH_STORE)?;
+    let caller = match authenticate(&req, cfg, &store) {
+        Ok(caller) => caller,
+        Err(resp) => return Ok(resp),
+    };
+    let revoked = sessions::revoke_all(&store, caller.user_id, cfg.token_ttl)?;
+    json_response(StatusCode::OK, &json!({ "revoked": revoked }))
+}
+
+#[derive(serde::Deserialize)]
+struct StaffLogin {
+    username: String,
+    password: String,
+    totp_code: Option<String>,
+}
+
+/// `POST /api/auth/staff/login`: hospitaleros and admins sign in with their
+/// username, password and, once enrolled, a TOTP code.
+pub async fn staff_login_handler(req: Request, cfg: &AppConfig) -> anyhow::Result<Response> {
+    let Ok(login) = serde_json::from_slice::<StaffLogin>(req.body()) else {
+        return Ok(Response::builder()
+            .status(StatusCode::BAD_REQUEST)
+            .body("Expected username and password")
+            .build());
+    };
+    let signed_in = match staff::sign_in(
+        &login.username,
+        &login.password,
+        login.totp_code.as_deref(),
+        cfg.session_ttl,
+    )
+    .await
+    {
+        Ok(signed_in) => signed_in,
+        Err(e) => return Ok(staff_error_response(&e)),
+    };
+
+    let user_agent = req.header("user-agent").and_then(|h| h.as_str());
+    let session = sessions::start(
+        &signed_in.user,
+        &signed_in.identity,
+        None,
+        user_agent,
+        signed_in.session_ttl,
+    )?;
+    let jwt = tokens::issue(cfg, &signed_in.user, &signed_in.identity, &session.session_id)?;
+    let body = json!({
+        "jwt": jwt,
+        "token_type": "Bearer",
+        "expires_in": cfg.token_ttl.num_seconds(),
+        "refresh_token": session.refresh_token,
+    });
+    json_response(StatusCode::OK, &body)
+}
+
+/// `POST /api/auth/staff/totp/enrol`: a new TOTP secret for the caller's
+/// authenticator app, in use once confirmed.
+pub async fn totp_enrol_handler(req: Request, cfg: &AppConfig) -> anyhow::Result<Response> {
+    let caller = match authenticate_staff(&req, cfg, None) {
+        Ok(caller) => caller,
+        Err(resp) => return Ok(resp),
+    };
+    match staff::begin_totp(caller.user_id).await {
+        Ok(enrolment) => json_response(
+            StatusCode::OK,
+            &json!({
+                "secret": enrolment.secret,
+                "otpauth_uri": totp::provisioning_uri(
+                    staff::TOTP_ISSUER,
+                    &enrolment.username,
+                    &enrolment.secret,
+                ),
+            }),
+        ),
+        Err(e) => Ok(staff_error_response(&e)),
+    }
+}
+
+/// `POST /api/auth/staff/totp/confirm`: turns TOTP on with a first code.
+pub async fn totp_confirm_handler(req: Request, cfg: &AppConfig) -> anyhow::Result<Response> {
+    let caller = match authenticate_staff(&req, cfg, None) {
+        Ok(caller) => caller,
+        Err(resp) => return Ok(resp),
+    };
+    let payload: HashMap<String, String> = serde_json::from_slice(req.body()).unwrap_or_default();
+    let Some(code) = payload.get("code") else {
+        return Ok(Response::builder()
+            .status(StatusCode::BAD_REQUEST)
+            .body("Missing code")
+            .build());
+    };
+    match staff::confirm_totp(caller.user_id, code).await {
+        Ok(()) => json_response(StatusCode::OK, &json!({ "totp_enabled": true })),
+        Err(e) => Ok(staff_error_response(&e)),
+    }
+}
+
+/// `POST /api/auth/staff/accounts`: an admin opens an account for a
+/// volunteer, valid for their rota.
+pub async fn create_staff_account_handler(
+    req: Request,
+    cfg: &AppConfig,
+) -> anyhow::Result<Response> {
+    if let Err(resp) = authenticate_staff(&req, cfg, Some(Role::Admin)) {
+        return Ok(resp);
+    }
+    let account: NewStaffAccount = match serde_json::from_slice(req.body()) {
+        Ok(account) => account,
         Err(e) => {
             return Ok(Response::builder()
-                .status(StatusCode::INTERNAL_SERVER_ERROR)
-                .body(e.to_string())
+                .status(StatusCode::BAD_REQUEST)
+                .body(format!("Invalid staff account: {e}"))
                 .build());
-        },
+        }
     };
+    match staff::create(&account) {
+        Ok(id) => json_response(
+            StatusCode::CREATED,
+            &json!({ "id": id, "username": account.username.trim() }),
+        ),
+        Err(e) => Ok(staff_error_response(&e)),
+    }
+}
 
-    let body = json!({ "jwt": jwt });
-    Ok(Response::builder()
-        .status(StatusCode::OK)
+fn staff_error_response(e: &StaffError) -> Response {
+    let status = match e {
+        StaffError::InvalidCredentials | StaffError::TotpRequired | StaffError::InvalidCode => {
+            StatusCode::UNAUTHORIZED
+        }
+        StaffError::Locked { .. } => StatusCode::TOO_MANY_REQUESTS,
+        StaffError::Inactive => StatusCode::FORBIDDEN,
+        StaffError::TotpAlreadyEnabled | StaffError::NoPendingTotp | StaffError::UsernameTaken => {
+            StatusCode::CONFLICT
+        }
+        StaffError::Invalid(_) => StatusCode::BAD_REQUEST,
+        StaffError::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    let mut builder = Response::builder();
+    builder
+        .status(status)
         .header("Content-Type", "application/json")
-        .body(serde_json::to_vec(&body)?)
-        .build())
+        .header("Cache-Control", "no-store");
+    if let StaffError::Locked { until } = e {
+        let wait = (*until - Utc::now()).num_seconds().max(1);
+        builder.header("Retry-After", wait.to_string());
+    }
+    let body = json!({
+        "error": e.to_string(),
+        "totp_required": *e == StaffError::TotpRequired,
+    });
+    builder.body(serde_json::to_vec(&body).unwrap_or_default()).build()
+}
+
+/// [`authenticate`], for hospitaleros and admins only, or only `role`.
+fn authenticate_staff(
+    req: &Request,
+    cfg: &AppConfig,
+    role: Option<Role>,
+) -> Result<Caller, Response> {
+    let store = Store::open(AUTH_STORE).map_err(|e| {
+        Response::builder()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .body(e.to_string())
+            .build()
+    })?;
+    let caller = authenticate(req, cfg, &store)?;
+    let allowed = match role {
+        Some(role) => caller.has_role(role),
+        None => caller.has_role(Role::Hospitalero) || caller.has_role(Role::Admin),
+    };
+    if !allowed {
+        return Err(Response::builder()
+            .status(StatusCode::FORBIDDEN)
+            .body("Staff accounts only")
+            .build());
+    }
+    Ok(caller)
 }
 
-pub async fn well_known_handler(_req: Request, _cfg: &AppConfig) -> anyhow::Result<Response> {
-    let issuer = "https://alberguecarrascalejo.fermyon.app/api/auth";
+/// Declared providers and their health, for sign-in buttons and operators.
+pub async fn providers_handler(_req: Request, cfg: &AppConfig) -> anyhow::Result<Response> {
+    let listed: Vec<serde_json::Value> = cfg
+        .provider_health
+        .iter()
+        .map(|health| {
+            let mut entry = json!(health);
+            entry["available"] = json!(cfg.providers.iter().any(|p| p.name() == health.name));
+            entry
+        })
+        .collect();
+    json_response(StatusCode::OK, &json!({ "providers": listed }))
+}
+
+pub async fn well_known_handler(_req: Request, cfg: &AppConfig) -> anyhow::Result<Response> {
+    let issuer = cfg.issuer.trim_end_matches('/');
+    let algs: Vec<&str> = cfg.signing_keys.iter().map(SigningKey::alg).collect();
     let config = json!({
         "issuer": issuer,
         "authorization_endpoint": format!("{}/login", issuer),
         "token_endpoint": format!("{}/callback", issuer),  
-        "jwks_uri": format!("{}/.well-known/jwks.json", issuer),
+        "jwks_uri": format!("{}/jwks.json", issuer),
         "response_types_supported": ["code"],
         "subject_types_supported": ["public"],
-        "id_token_signing_alg_values_supported": ["RS256", "HS256"],
+        "id_token_signing_alg_values_supported": algs,
+        "scopes_supported": ["openid", "profile", "email"],
+        "claims_supported": [
+            "iss", "sub", "aud", "iat", "exp", "jti", "sid", "roles", "scope", "idp", "email",
+            "name",
+        ],
     });
 
     Ok(Response::builder()
         .status(StatusCode::OK)
         .header("Content-Type", "application/json")
+        .header("Cache-Control", "public, max-age=3600")
         .body(serde_json::to_vec(&config)?)
         .build())
 }
+
+pub async fn jwks_handler(_req: Request, cfg: &AppConfig) -> anyhow::Result<Response> {
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/jwk-set+json")
+        .header("Cache-Control", "public, max-age=300")
+        .body(serde_json::to_vec(&jwks(&cfg.signing_keys))?)
+        .build())
+}
+
+fn json_response(status: StatusCode, body: &serde_json::Value) -> anyhow::Result<Response> {
+    Ok(Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .header("Cache-Control", "no-store")
+        .body(serde_json::to_vec(body)?)
+        .build())
+}
+
+fn bearer_token(req: &Request) -> Option<&str> {
+    req.header("authorization")
+        .and_then(|h| h.as_str())
+        .and_then(|h| h.strip_prefix("Bearer ").or_else(|| h.strip_prefix("bearer ")))
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+}
+
+/// The gateway lets `/api/auth/*` through unauthenticated, so routes acting
+/// for a signed-in user check this service's access token themselves,
+/// including whether its session has been revoked.
+fn authenticate(req: &Request, cfg: &AppConfig, store: &Store) -> Result<Caller, Response> {
+    let unauthorized = |message: String| {
+        Response::builder()
+            .status(StatusCode::UNAUTHORIZED)
+            .header("WWW-Authenticate", "Bearer")
+            .body(message)
+            .build()
+    };
+    let token = bearer_token(req).ok_or_else(|| unauthorized("Missing bearer token".into()))?;
+    let caller = tokens::authenticate(cfg, token, Utc::now().timestamp())
+        .map_err(|e| unauthorized(e.to_string()))?;
+    match sessions::is_revoked(store, &caller.session_id) {
+        Ok(false) => Ok(caller),
+        Ok(true) => Err(unauthorized(SessionError::Revoked.to_string())),
+        Err(e) => Err(Response::builder()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .body(e.to_string())
+            .build()),
+    }
+}
+
+/// Resolves the local user behind a provider's tokens, opens a session for
+/// the sign-in and mints this service's access token and the session's first
+/// refresh token.
+async fn sign_in(
+    cfg: &AppConfig,
+    provider: &dyn IdentityProvider,
+    token: &TokenResponse,
+    user_agent: Option<&str>,
+) -> Result<(String, String), Response> {
+    let identity = provider.identity(token).await.map_err(|e| {
+        Response::builder()
+            .status(StatusCode::UNAUTHORIZED)
+            .body(format!("Auth failed: {e}"))
+            .build()
+    })?;
+    let server_error = |e: anyhow::Error| {
+        Response::builder()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .body(e.to_string())
+            .build()
+    };
+    let user = users::resolve(&identity).map_err(server_error)?;
+    let session = sessions::start(
+        &user,
+        &identity,
+        token.id_token.as_deref(),
+        user_agent,
+        cfg.session_ttl,
+    )
+    .map_err(server_error)?;
+    let jwt = tokens::issue(cfg, &user, &identity, &session.session_id).map_err(server_error)?;
+    Ok((jwt, session.refresh_token))
+}
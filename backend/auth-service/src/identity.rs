@@ -0,0 +1,161 @@
+use anyhow::{anyhow, bail, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde_json::Value;
+
+/// Tolerated clock difference with the providers when checking `exp`/`iat`.
+const CLOCK_SKEW_SECONDS: i64 = 60;
+
+/// Who signed in, as the provider knows them. `(provider, subject)` is stable
+/// for a person and is what links them to a local user.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Identity {
+    pub provider: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub name: Option<String>,
+}
+
+impl Identity {
+    /// Reads an ID token's or a userinfo response's claims.
+    pub fn from_claims(provider: &str, claims: &Value) -> Result<Self> {
+        let subject = match claims.get("sub") {
+            Some(Value::String(sub)) if !sub.is_empty() => sub.clone(),
+            Some(Value::Number(sub)) => sub.to_string(),
+            _ => bail!("{provider} did not say who signed in (no sub claim)"),
+        };
+        let text = |name: &str| {
+            claims
+                .get(name)
+                .and_then(Value::as_str)
+                .filter(|v| !v.is_empty())
+                .map(str::to_string)
+        };
+        // An address the provider has not verified says nothing about who
+        // owns it.
+        let email = text("email").filter(|_| {
+            claims
+                .get("email_verified")
+                .and_then(Value::as_bool)
+                .unwrap_or(true)
+        });
+        Ok(Self {
+            provider: provider.to_string(),
+            subject,
+            email,
+            name: text("name").or_else(|| text("preferred_username")),
+        })
+    }
+}
+
+/// Payload of a compact JWT, read without checking its signature.
+pub fn jwt_claims(token: &str) -> Option<Value> {
+    let payload = token.split('.').nth(1)?;
+    let bytes = URL_SAFE_NO_PAD.decode(payload).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+/// Checks the registered claims of an ID token the provider returned from its
+/// token endpoint: issued by `issuer`, for `client_id`, and still current at
+/// `now`.
+pub fn check_id_token_claims(
+    claims: &Value,
+    issuer: &str,
+    client_id: &str,
+    now: i64,
+) -> Result<()> {
+    let iss = claims
+        .get("iss")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    if iss.trim_end_matches('/') != issuer.trim_end_matches('/') {
+        bail!("ID token was issued by {iss:?}, not {issuer:?}");
+    }
+    let for_us = match claims.get("aud") {
+        Some(Value::String(aud)) => aud == client_id,
+        Some(Value::Array(auds)) => auds.iter().any(|a| a.as_str() == Some(client_id)),
+        _ => false,
+    };
+    if !for_us {
+        bail!("ID token is not addressed to this client");
+    }
+    let exp = claims
+        .get("exp")
+        .and_then(Value::as_i64)
+        .ok_or_else(|| anyhow!("ID token has no exp claim"))?;
+    if exp + CLOCK_SKEW_SECONDS < now {
+        bail!("ID token has expired");
+    }
+    if claims
+        .get("iat")
+        .and_then(Value::as_i64)
+        .is_some_and(|iat| iat > now + CLOCK_SKEW_SECONDS)
+    {
+        bail!("ID token was issued in the future");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn id_token(claims: &Value) -> String {
+        format!("e30.{}.c2ln", URL_SAFE_NO_PAD.encode(claims.to_string()))
+    }
+
+    #[test]
+    fn reads_identity_from_claims() {
+        let claims = jwt_claims(&id_token(&json!({
+            "sub": "abc",
+            "email": "ana@example.org",
+            "email_verified": true,
+            "preferred_username": "ana"
+        })))
+        .unwrap();
+        assert_eq!(
+            Identity::from_claims("logto", &claims).unwrap(),
+            Identity {
+                provider: "logto".to_string(),
+                subject: "abc".to_string(),
+                email: Some("ana@example.org".to_string()),
+                name: Some("ana".to_string()),
+            }
+        );
+
+        let github = Identity::from_claims("github", &json!({ "sub": 583_231 })).unwrap();
+        assert_eq!(github.subject, "583231");
+        assert!(Identity::from_claims("logto", &json!({ "email": "a@b.c" })).is_err());
+    }
+
+    #[test]
+    fn ignores_unverified_email() {
+        let identity = Identity::from_claims(
+            "zitadel",
+            &json!({ "sub": "1", "email": "x@example.org", "email_verified": false }),
+        )
+        .unwrap();
+        assert_eq!(identity.email, None);
+    }
+
+    #[test]
+    fn checks_issuer_audience_and_lifetime() {
+        let issuer = "https://id.example/oidc";
+        let good = json!({ "iss": issuer, "aud": ["other", "app"], "exp": 2_000, "iat": 1_000 });
+        assert!(check_id_token_claims(&good, "https://id.example/oidc/", "app", 1_500).is_ok());
+
+        let cases = [
+            json!({ "iss": "https://evil.example", "aud": "app", "exp": 2_000 }),
+            json!({ "iss": issuer, "aud": "other", "exp": 2_000 }),
+            json!({ "iss": issuer, "aud": "app" }),
+            json!({ "iss": issuer, "aud": "app", "exp": 1_000 }),
+            json!({ "iss": issuer, "aud": "app", "exp": 3_000, "iat": 1_700 }),
+        ];
+        for claims in cases {
+            assert!(
+                check_id_token_claims(&claims, issuer, "app", 1_500).is_err(),
+                "{claims}"
+            );
+        }
+    }
+}
@@ -10,23 +10,51 @@ use http::StatusCode;
 
 mod config;
 mod handlers;
+mod identity;
+mod login_transaction;
+mod passwords;
 mod providers;
+mod registry;
+mod secrets;
+mod sessions;
+mod signing;
+mod staff;
+mod tokens;
+mod totp;
+mod users;
 
 use config::load_config;
 use handlers::{
-    callback_handler, login_handler, logout_handler, refresh_handler, well_known_handler,
+    callback_handler, create_staff_account_handler, jwks_handler, list_sessions_handler,
+    login_handler, logout_handler, providers_handler, refresh_handler,
+    revoke_all_sessions_handler, revoke_session_handler, staff_login_handler,
+    totp_confirm_handler, totp_enrol_handler, well_known_handler,
 };
 
 #[http_component]
 async fn handle_auth_service(req: Request) -> anyhow::Result<Response> {
     let config = load_config().await?;
     
-    match (req.method(), req.uri()) {
+    // `uri()` carries the query string, which login and callback both use.
+    match (req.method(), req.path()) {
         (&Method::Get, "/api/auth/login") => login_handler(req, &config).await,
         (&Method::Get, "/api/auth/callback") => callback_handler(req, &config).await,
-        (&Method::Get, "/api/auth/logout") => logout_handler(req, &config).await,
+        (&Method::Post, "/api/auth/logout") => logout_handler(req, &config).await,
         (&Method::Post, "/api/auth/refresh") => refresh_handler(req, &config).await,
+        (&Method::Get, "/api/auth/providers") => providers_handler(req, &config).await,
+        (&Method::Post, "/api/auth/staff/login") => staff_login_handler(req, &config).await,
+        (&Method::Post, "/api/auth/staff/totp/enrol") => totp_enrol_handler(req, &config).await,
+        (&Method::Post, "/api/auth/staff/totp/confirm") => totp_confirm_handler(req, &config).await,
+        (&Method::Post, "/api/auth/staff/accounts") => {
+            create_staff_account_handler(req, &config).await
+        }
+        (&Method::Get, "/api/auth/sessions") => list_sessions_handler(req, &config).await,
+        (&Method::Delete, "/api/auth/sessions") => revoke_all_sessions_handler(req, &config).await,
+        (&Method::Delete, path) if path.starts_with("/api/auth/sessions/") => {
+            revoke_session_handler(req, &config).await
+        }
         (&Method::Get, "/api/auth/.well-known/openid-configuration") => well_known_handler(req, &config).await,
+        (&Method::Get, "/api/auth/jwks.json") => jwks_handler(req, &config).await,
         _ => Ok(Response::builder()
             .status(StatusCode::NOT_FOUND)
             .body("Not Found")
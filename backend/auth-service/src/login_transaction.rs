@@ -0,0 +1,312 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Duration, Utc};
+use ring::{
+    digest::{digest, SHA256},
+    rand::{SecureRandom, SystemRandom},
+};
+use serde::{Deserialize, Serialize};
+use spin_sdk::key_value::Store;
+use spin_sdk::wit::wasi::keyvalue::{atomics, store as wasi_store};
+
+use crate::identity::jwt_claims;
+
+pub const AUTH_STORE: &str = "default";
+
+/// Long enough to sign in at the provider, short enough that an abandoned
+/// `state` is useless soon after.
+pub const LOGIN_TRANSACTION_TTL_MINUTES: i64 = 10;
+
+/// Cookie tying the `state` of a login to the browser that started it, so a
+/// callback carrying someone else's `state` is refused.
+pub const STATE_COOKIE: &str = "login_state";
+
+/// Where the frontend lands when the login did not ask for anywhere else.
+pub const DEFAULT_RETURN_TO: &str = "/";
+
+/// Value a taken transaction's record is swapped for before it is deleted.
+const TAKEN: &[u8] = b"";
+
+/// What `login_handler` remembers about a login until the provider redirects
+/// back with its `state`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LoginTransaction {
+    /// Name of the provider the flow was started with; the code is only ever
+    /// exchanged there.
+    pub provider: String,
+    /// PKCE verifier whose S256 challenge went out with the authorization
+    /// request.
+    pub code_verifier: String,
+    /// Expected `nonce` claim of the provider's ID token.
+    pub nonce: String,
+    pub return_to: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl LoginTransaction {
+    pub fn new(
+        provider: &str,
+        return_to: Option<&str>,
+        created_at: DateTime<Utc>,
+    ) -> Result<Self, LoginError> {
+        Ok(Self {
+            provider: provider.to_string(),
+            code_verifier: random_token()?,
+            nonce: random_token()?,
+            return_to: safe_return_to(return_to),
+            created_at,
+            expires_at: created_at + Duration::minutes(LOGIN_TRANSACTION_TTL_MINUTES),
+        })
+    }
+
+    pub fn code_challenge(&self) -> String {
+        pkce_challenge(&self.code_verifier)
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        now >= self.expires_at
+    }
+
+    /// Checks the `nonce` claim of an ID token received straight from the
+    /// provider's token endpoint over TLS, which OIDC Core 3.1.3.7 accepts
+    /// in place of checking its signature.
+    pub fn check_id_token(&self, id_token: &str) -> Result<(), LoginError> {
+        match id_token_nonce(id_token) {
+            Some(nonce) if nonce == self.nonce => Ok(()),
+            _ => Err(LoginError::NonceMismatch),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum LoginError {
+    Unknown,
+    Expired,
+    NonceMismatch,
+    StateMismatch,
+    Random,
+    Storage(String),
+}
+
+impl std::fmt::Display for LoginError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unknown => write!(f, "Login state is invalid or has already been used"),
+            Self::Expired => write!(f, "Login took too long, please sign in again"),
+            Self::NonceMismatch => write!(f, "ID token nonce does not match the login"),
+            Self::StateMismatch => write!(f, "Login was not started from this browser"),
+            Self::Random => write!(f, "System random source is unavailable"),
+            Self::Storage(e) => write!(f, "Login transaction storage error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for LoginError {}
+
+fn transaction_key(state: &str) -> String {
+    format!("login_transaction:{state}")
+}
+
+/// Stores a new transaction and returns the `state` it is filed under.
+pub fn begin(store: &Store, transaction: &LoginTransaction) -> Result<String, LoginError> {
+    let state = random_token()?;
+    let bytes = serde_json::to_vec(transaction).map_err(|e| LoginError::Storage(e.to_string()))?;
+    store
+        .set(&transaction_key(&state), &bytes)
+        .map_err(|e| LoginError::Storage(e.to_string()))?;
+    Ok(state)
+}
+
+/// Consumes the transaction filed under `state`. The record is swapped for a
+/// tombstone in a single compare-and-swap before it is checked, so of two
+/// callbacks racing with the same `state` only one gets it, and a `state` is
+/// good for one callback even when that one fails.
+pub fn take(state: &str) -> Result<LoginTransaction, LoginError> {
+    let bucket = wasi_store::open(AUTH_STORE).map_err(|e| storage(&e))?;
+    let key = transaction_key(state);
+    let cas = atomics::Cas::new(&bucket, &key).map_err(|e| storage(&e))?;
+    let bytes = cas
+        .current()
+        .map_err(|e| storage(&e))?
+        .filter(|bytes| bytes != TAKEN)
+        .ok_or(LoginError::Unknown)?;
+    match atomics::swap(cas, TAKEN) {
+        Ok(()) => {}
+        Err(atomics::CasError::CasFailed(_)) => return Err(LoginError::Unknown),
+        Err(atomics::CasError::StoreError(e)) => return Err(storage(&e)),
+    }
+    // The transaction is spent either way; a tombstone left behind reads as
+    // unknown.
+    let _ = bucket.delete(&key);
+
+    let transaction: LoginTransaction =
+        serde_json::from_slice(&bytes).map_err(|e| LoginError::Storage(e.to_string()))?;
+    if transaction.is_expired(Utc::now()) {
+        return Err(LoginError::Expired);
+    }
+    Ok(transaction)
+}
+
+fn storage(e: &wasi_store::Error) -> LoginError {
+    LoginError::Storage(format!("{e:?}"))
+}
+
+/// `Set-Cookie` value binding `state` to the browser. Only its hash is kept
+/// there; it is only sent back to the callback and only for as long as the
+/// transaction lives.
+pub fn state_cookie(state: &str) -> String {
+    format!(
+        "{STATE_COOKIE}={}; Path=/api/auth/callback; Max-Age={}; HttpOnly; Secure; SameSite=Lax",
+        state_hash(state),
+        LOGIN_TRANSACTION_TTL_MINUTES * 60
+    )
+}
+
+/// `Set-Cookie` value removing the state cookie once the callback used it.
+pub fn clear_state_cookie() -> String {
+    format!("{STATE_COOKIE}=; Path=/api/auth/callback; Max-Age=0; HttpOnly; Secure; SameSite=Lax")
+}
+
+/// Checks the `Cookie` header of a callback against the `state` it carries.
+pub fn check_state_cookie(cookie_header: Option<&str>, state: &str) -> Result<(), LoginError> {
+    let expected = state_hash(state);
+    let matches = cookie_header
+        .into_iter()
+        .flat_map(|header| header.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .any(|(name, value)| name == STATE_COOKIE && value == expected);
+    if matches {
+        Ok(())
+    } else {
+        Err(LoginError::StateMismatch)
+    }
+}
+
+fn state_hash(state: &str) -> String {
+    URL_SAFE_NO_PAD.encode(digest(&SHA256, state.as_bytes()))
+}
+
+/// 32 random bytes, base64url-encoded: 43 characters, the shortest valid PKCE
+/// verifier.
+pub fn random_token() -> Result<String, LoginError> {
+    let mut bytes = [0u8; 32];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| LoginError::Random)?;
+    Ok(URL_SAFE_NO_PAD.encode(bytes))
+}
+
+/// RFC 7636 S256 code challenge.
+pub fn pkce_challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(digest(&SHA256, verifier.as_bytes()))
+}
+
+/// Only same-site paths are accepted, so the login cannot be turned into an
+/// open redirect.
+pub fn safe_return_to(return_to: Option<&str>) -> String {
+    match return_to {
+        Some(path)
+            if path.starts_with('/')
+                && !path.starts_with("//")
+                && !path.contains('\\')
+                && !path.chars().any(char::is_control) =>
+        {
+            path.to_string()
+        }
+        _ => DEFAULT_RETURN_TO.to_string(),
+    }
+}
+
+fn id_token_nonce(id_token: &str) -> Option<String> {
+    jwt_claims(id_token)?
+        .get("nonce")?
+        .as_str()
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id_token(claims: &serde_json::Value) -> String {
+        format!(
+            "eyJhbGciOiJSUzI1NiJ9.{}.c2ln",
+            URL_SAFE_NO_PAD.encode(claims.to_string())
+        )
+    }
+
+    #[test]
+    fn pkce_challenge_matches_rfc_7636_example() {
+        assert_eq!(
+            pkce_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+
+    #[test]
+    fn random_tokens_are_valid_verifiers() {
+        let token = random_token().unwrap();
+        assert_eq!(token.len(), 43);
+        assert_ne!(token, random_token().unwrap());
+        assert!(token
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+    }
+
+    #[test]
+    fn return_to_must_stay_on_site() {
+        assert_eq!(safe_return_to(Some("/bookings?x=1")), "/bookings?x=1");
+        for bad in [
+            "https://evil.example",
+            "//evil.example",
+            "/\\evil.example",
+            "/a\nb",
+            "",
+        ] {
+            assert_eq!(safe_return_to(Some(bad)), DEFAULT_RETURN_TO, "{bad:?}");
+        }
+        assert_eq!(safe_return_to(None), DEFAULT_RETURN_TO);
+    }
+
+    #[test]
+    fn checks_the_id_token_nonce() {
+        let now = Utc::now();
+        let transaction = LoginTransaction::new("logto", None, now).unwrap();
+        assert!(!transaction.is_expired(now));
+        assert!(transaction.is_expired(now + Duration::minutes(LOGIN_TRANSACTION_TTL_MINUTES)));
+
+        let good = id_token(&serde_json::json!({ "sub": "u1", "nonce": transaction.nonce }));
+        assert_eq!(transaction.check_id_token(&good), Ok(()));
+
+        let other = id_token(&serde_json::json!({ "sub": "u1", "nonce": random_token().unwrap() }));
+        let missing = id_token(&serde_json::json!({ "sub": "u1" }));
+        for token in [other.as_str(), missing.as_str(), "not-a-jwt"] {
+            assert_eq!(
+                transaction.check_id_token(token),
+                Err(LoginError::NonceMismatch)
+            );
+        }
+    }
+
+    #[test]
+    fn state_cookie_binds_the_state_to_the_browser() {
+        let state = random_token().unwrap();
+        let cookie = state_cookie(&state);
+        assert!(cookie.contains("HttpOnly; Secure; SameSite=Lax"));
+        assert!(!cookie.contains(&state));
+
+        let sent = cookie.split(';').next().unwrap();
+        let header = format!("theme=dark; {sent}");
+        assert_eq!(check_state_cookie(Some(&header), &state), Ok(()));
+        for header in [None, Some("theme=dark"), Some(sent)] {
+            assert_eq!(
+                check_state_cookie(header, &random_token().unwrap()),
+                Err(LoginError::StateMismatch)
+            );
+        }
+        assert_eq!(
+            check_state_cookie(None, &state),
+            Err(LoginError::StateMismatch)
+        );
+    }
+}
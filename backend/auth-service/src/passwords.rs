@@ -0,0 +1,33 @@
+use anyhow::{anyhow, Result};
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+
+/// Shortest password accepted for a staff account.
+pub const MIN_PASSWORD_CHARS: usize = 12;
+
+/// Argon2id hash of `password` in PHC string format, with a fresh salt.
+pub fn hash(password: &str) -> Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| anyhow!("Hashing the password failed: {e}"))
+}
+
+/// Whether `password` matches `stored`. Anything that is not a PHC string,
+/// such as the `!` of provider-only accounts, matches nothing.
+pub fn verify(stored: &str, password: &str) -> bool {
+    PasswordHash::new(stored).is_ok_and(|parsed| {
+        Argon2::default()
+            .verify_password(password.as_bytes(), &parsed)
+            .is_ok()
+    })
+}
+
+/// Spends the time a verification would on a username that does not exist,
+/// so response times do not reveal which usernames do.
+pub fn verify_nothing(password: &str) {
+    let _ = hash(password);
+}
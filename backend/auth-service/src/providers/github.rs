@@ -1,8 +1,12 @@
 use async_trait::async_trait;
 use url::form_urlencoded;
-use crate::config::{IdentityProvider, TokenResponse};      
+use crate::config::{IdentityProvider, TokenResponse};
+use crate::identity::Identity;
+use crate::providers::get_json;
+use serde_json::json;      
 
 pub struct GitHubProvider {
+    pub name: String,
     pub client_id: String,
     pub client_secret: String,
     pub redirect_uri: String,
@@ -10,21 +14,32 @@ pub struct GitHubProvider {
 
 #[async_trait(?Send)]
 impl IdentityProvider for GitHubProvider {
-    fn name(&self) -> &'static str {
-        "github"
+    fn name(&self) -> &str {
+        &self.name
     }
 
-    fn authorization_url(&self, state: &str) -> String {   
+    fn issues_id_tokens(&self) -> bool {
+        false
+    }
+
+    fn authorization_url(&self, state: &str, code_challenge: &str, _nonce: &str) -> String {   
         let mut url = url::Url::parse("https://github.com/login/oauth/authorize").unwrap();
         url.query_pairs_mut()
             .append_pair("client_id", &self.client_id)     
             .append_pair("redirect_uri", &self.redirect_uri)
             .append_pair("scope", "read:user user:email")  
-            .append_pair("state", state);
+            .append_pair("state", state)
+            .append_pair("code_challenge", code_challenge)
+            .append_pair("code_challenge_method", "S256");
         url.to_string()
     }
 
-    async fn exchange_code(&self, code: &str, redirect_uri: &str) -> anyhow::Result<TokenResponse> {
+    async fn exchange_code(
+        &self,
+        code: &str,
+        redirect_uri: &str,
+        code_verifier: &str,
+    ) -> anyhow::Result<TokenResponse> {
         let token_url = "https://github.com/login/oauth/access_token";
 
         let body_str = form_urlencoded::Serializer::new(String::new())
@@ -32,6 +47,7 @@ impl IdentityProvider for GitHubProvider {
             .append_pair("client_secret", &self.client_secret)
             .append_pair("code", code)
             .append_pair("redirect_uri", redirect_uri)     
+            .append_pair("code_verifier", code_verifier)
             .finish();
 
         let req = http::Request::builder()
@@ -59,6 +75,17 @@ impl IdentityProvider for GitHubProvider {
         Err(anyhow::anyhow!("GitHub refresh token not implemented"))
     }
 
+    async fn identity(&self, token: &TokenResponse) -> anyhow::Result<Identity> {
+        let user = get_json("https://api.github.com/user", Some(&token.access_token)).await?;
+        // The numeric id survives renames of the GitHub login.
+        let claims = json!({
+            "sub": user["id"],
+            "email": user["email"],
+            "name": user["name"].as_str().or_else(|| user["login"].as_str()),
+        });
+        Identity::from_claims(self.name(), &claims)
+    }
+
     fn jwks_uri(&self) -> String {
         "".to_string()
     }
@@ -1,3 +1,58 @@
-﻿pub mod logto;
-pub mod zitadel;
+﻿pub mod oidc;
 pub mod github;
+
+use anyhow::{anyhow, bail, Context};
+use openidconnect::core::CoreProviderMetadata;
+
+use crate::config::TokenResponse;
+use crate::identity::{check_id_token_claims, jwt_claims, Identity};
+
+/// Identity from an OIDC provider's ID token or, when the token
+/// response carries none, its userinfo endpoint.
+pub(crate) async fn oidc_identity(
+    provider: &str,
+    metadata: &CoreProviderMetadata,
+    client_id: &str,
+    token: &TokenResponse,
+) -> anyhow::Result<Identity> {
+    if let Some(id_token) = token.id_token.as_deref() {
+        let claims = jwt_claims(id_token).context("ID token is not a JWT")?;
+        check_id_token_claims(
+            &claims,
+            metadata.issuer().as_str(),
+            client_id,
+            chrono::Utc::now().timestamp(),
+        )?;
+        return Identity::from_claims(provider, &claims);
+    }
+    let userinfo = metadata
+        .userinfo_endpoint()
+        .ok_or_else(|| anyhow!("{provider} returned no ID token and has no userinfo endpoint"))?;
+    let claims = get_json(userinfo.url().as_str(), Some(&token.access_token)).await?;
+    Identity::from_claims(provider, &claims)
+}
+
+/// GETs a JSON document, on behalf of the user holding `access_token` when
+/// one is given.
+pub(crate) async fn get_json(
+    url: &str,
+    access_token: Option<&str>,
+) -> anyhow::Result<serde_json::Value> {
+    let mut builder = http::Request::builder()
+        .method(http::Method::GET)
+        .uri(url)
+        .header("Accept", "application/json")
+        .header("User-Agent", "albergue-auth-service");
+    if let Some(access_token) = access_token {
+        builder = builder.header("Authorization", format!("Bearer {access_token}"));
+    }
+    let req = builder.body(Vec::new())?;
+
+    let resp: http::Response<Vec<u8>> = spin_sdk::http::send(req).await
+        .map_err(|e| anyhow!("Spin HTTP error: {e:?}"))?;
+
+    if resp.status() != 200 {
+        bail!("{url} answered with status: {}", resp.status());
+    }
+    Ok(serde_json::from_slice(resp.body())?)
+}
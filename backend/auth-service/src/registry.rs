@@ -0,0 +1,470 @@
+use anyhow::{bail, Result};
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use spin_sdk::key_value::Store;
+use std::sync::Arc;
+
+use crate::config::IdentityProvider;
+use crate::providers::get_json;
+use crate::providers::github::GitHubProvider;
+use crate::providers::oidc::OidcProvider;
+
+/// Discovery documents are cached in the key-value store under
+/// `oidc_discovery:{issuer}`, so sign-in does not wait on the providers.
+const DISCOVERY_KEY_PREFIX: &str = "oidc_discovery:";
+/// Last known health of each declared provider, `provider_health:{name}`.
+const HEALTH_KEY_PREFIX: &str = "provider_health:";
+
+pub const DEFAULT_DISCOVERY_TTL_SECONDS: i64 = 3600;
+/// How long a provider whose discovery failed is left alone before it is
+/// tried again.
+const RETRY_SECONDS: i64 = 60;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderKind {
+    /// Any OIDC issuer with a discovery document.
+    Oidc,
+    /// GitHub OAuth apps, which have no discovery document or ID tokens.
+    Github,
+}
+
+/// One entry of the `auth_providers` setting.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProviderConfig {
+    /// What `?provider=` selects it by and what `user_identities.provider`
+    /// records. Renaming a provider orphans the identities linked under the
+    /// old name.
+    pub name: String,
+    pub kind: ProviderKind,
+    #[serde(default)]
+    pub issuer: Option<String>,
+    pub client_id: String,
+    /// Spin variable (or upper-cased environment variable) holding the
+    /// client secret.
+    #[serde(default)]
+    pub client_secret_variable: Option<String>,
+    /// The secret itself; for local development only.
+    #[serde(default)]
+    pub client_secret: Option<String>,
+    /// Space-separated scopes to request; `openid profile email` by default.
+    #[serde(default)]
+    pub scopes: Option<String>,
+}
+
+impl ProviderConfig {
+    fn client_secret(&self, setting: &impl Fn(&str) -> Option<String>) -> Option<String> {
+        self.client_secret
+            .clone()
+            .or_else(|| self.client_secret_variable.as_deref().and_then(setting))
+    }
+}
+
+/// Reads and checks the `auth_providers` setting: a JSON array of
+/// [`ProviderConfig`].
+pub fn parse_providers(json: &str) -> Result<Vec<ProviderConfig>> {
+    let providers: Vec<ProviderConfig> = serde_json::from_str(json)?;
+    let mut names = std::collections::HashSet::new();
+    for provider in &providers {
+        let name = provider.name.as_str();
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+        {
+            bail!("Provider name {name:?} must be lowercase letters, digits, '-' or '_'");
+        }
+        if !names.insert(name) {
+            bail!("Provider {name} is declared twice");
+        }
+        if provider.kind == ProviderKind::Oidc && provider.issuer.is_none() {
+            bail!("OIDC provider {name} has no issuer");
+        }
+    }
+    Ok(providers)
+}
+
+/// Providers as the service was configured before `auth_providers` existed:
+/// Logto, Zitadel (sharing Logto's client) and GitHub, each present when its
+/// variables are set.
+pub fn legacy_providers(setting: impl Fn(&str) -> Option<String>) -> Vec<ProviderConfig> {
+    let mut providers = Vec::new();
+    if let (Some(issuer), Some(client_id)) =
+        (setting("logto_issuer_endpoint"), setting("logto_app_id"))
+    {
+        providers.push(ProviderConfig {
+            name: "logto".to_string(),
+            kind: ProviderKind::Oidc,
+            issuer: Some(issuer),
+            client_id,
+            client_secret_variable: Some("logto_app_secret".to_string()),
+            client_secret: None,
+            scopes: None,
+        });
+    }
+    if let (Some(domain), Some(client_id)) = (setting("zitadel_domain"), setting("logto_app_id")) {
+        providers.push(ProviderConfig {
+            name: "zitadel".to_string(),
+            kind: ProviderKind::Oidc,
+            issuer: Some(format!("https://{domain}/oidc")),
+            client_id,
+            client_secret_variable: Some("logto_app_secret".to_string()),
+            client_secret: None,
+            scopes: Some("openid profile email offline_access".to_string()),
+        });
+    }
+    if let Some(client_id) = setting("github_client_id") {
+        providers.push(ProviderConfig {
+            name: "github".to_string(),
+            kind: ProviderKind::Github,
+            issuer: None,
+            client_id,
+            client_secret_variable: Some("github_client_secret".to_string()),
+            client_secret: None,
+            scopes: None,
+        });
+    }
+    providers
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedDiscovery {
+    fetched_at: i64,
+    document: Value,
+}
+
+impl CachedDiscovery {
+    fn is_fresh(&self, now: i64, ttl: Duration) -> bool {
+        now < self.fetched_at + ttl.num_seconds()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Up,
+    /// Discovery failed but a stale document is still being used.
+    Degraded,
+    /// Not usable: no discovery document or no client secret.
+    Down,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProviderHealth {
+    pub name: String,
+    pub kind: ProviderKind,
+    pub status: HealthStatus,
+    /// Unix time of the last discovery attempt or configuration check.
+    pub checked_at: i64,
+    pub last_error: Option<String>,
+    pub consecutive_failures: u32,
+}
+
+impl ProviderHealth {
+    /// Health after a check that ended in `status`, counting failures since
+    /// the last success.
+    fn observe(
+        previous: Option<&ProviderHealth>,
+        config: &ProviderConfig,
+        status: HealthStatus,
+        error: Option<String>,
+        now: i64,
+    ) -> Self {
+        let consecutive_failures = match status {
+            HealthStatus::Up => 0,
+            _ => previous.map_or(0, |p| p.consecutive_failures) + 1,
+        };
+        Self {
+            name: config.name.clone(),
+            kind: config.kind,
+            status,
+            checked_at: now,
+            last_error: error,
+            consecutive_failures,
+        }
+    }
+
+    /// Failing providers are retried at most every [`RETRY_SECONDS`].
+    fn retry_due(&self, now: i64) -> bool {
+        self.status == HealthStatus::Up || now >= self.checked_at + RETRY_SECONDS
+    }
+}
+
+fn read_json<T: for<'de> Deserialize<'de>>(store: &Store, key: &str) -> Option<T> {
+    let bytes = store.get(key).ok().flatten()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+fn write_json<T: Serialize>(store: &Store, key: &str, value: &T) {
+    // The cache is an optimisation; a failed write is retried next request.
+    if let Ok(bytes) = serde_json::to_vec(value) {
+        let _ = store.set(key, &bytes);
+    }
+}
+
+/// What checking a provider's discovery document came to.
+enum Check {
+    /// A fresh cached document; health is whatever the last check found.
+    Cached(Value),
+    /// Recently failed: not retried yet, any stale document is kept.
+    Skipped(Option<Value>),
+    Checked {
+        document: Option<Value>,
+        status: HealthStatus,
+        error: Option<String>,
+    },
+}
+
+impl Check {
+    fn failed(error: &str) -> Self {
+        Self::Checked {
+            document: None,
+            status: HealthStatus::Down,
+            error: Some(error.to_string()),
+        }
+    }
+}
+
+/// Usable providers, in declaration order, and the health of every declared
+/// one. Discovery is served from the key-value store while fresh; when a
+/// refresh fails the stale document is kept (the provider is `degraded`)
+/// rather than the provider disappearing.
+pub async fn load(
+    store: &Store,
+    declared: &[ProviderConfig],
+    redirect_uri: &str,
+    setting: impl Fn(&str) -> Option<String>,
+    discovery_ttl: Duration,
+) -> (Vec<Arc<dyn IdentityProvider>>, Vec<ProviderHealth>) {
+    let now = Utc::now().timestamp();
+    let mut providers = Vec::new();
+    let mut health = Vec::new();
+
+    for config in declared {
+        let health_key = format!("{HEALTH_KEY_PREFIX}{}", config.name);
+        let previous: Option<ProviderHealth> = read_json(store, &health_key);
+        let secret = config.client_secret(&setting);
+
+        let check = match (&secret, config.kind, config.issuer.as_deref()) {
+            (None, _, _) => Check::failed("Client secret is not configured"),
+            (Some(_), ProviderKind::Github, _) => Check::Checked {
+                document: None,
+                status: HealthStatus::Up,
+                error: None,
+            },
+            (Some(_), ProviderKind::Oidc, None) => Check::failed("No issuer configured"),
+            (Some(_), ProviderKind::Oidc, Some(issuer)) => {
+                discover(store, issuer, previous.as_ref(), now, discovery_ttl).await
+            }
+        };
+        let observe =
+            |status, error| ProviderHealth::observe(previous.as_ref(), config, status, error, now);
+        let (document, mut current) = match check {
+            Check::Cached(document) | Check::Skipped(Some(document)) => (
+                Some(document),
+                previous
+                    .clone()
+                    .unwrap_or_else(|| observe(HealthStatus::Up, None)),
+            ),
+            Check::Skipped(None) => (
+                None,
+                previous
+                    .clone()
+                    .unwrap_or_else(|| observe(HealthStatus::Down, None)),
+            ),
+            Check::Checked {
+                document,
+                status,
+                error,
+            } => (document, observe(status, error)),
+        };
+
+        if let Some(secret) = secret {
+            match build(config, document.as_ref(), &secret, redirect_uri) {
+                Ok(Some(provider)) => providers.push(provider),
+                Ok(None) => {}
+                Err(e) => current = observe(HealthStatus::Down, Some(e.to_string())),
+            }
+        }
+        if previous.as_ref() != Some(&current) {
+            write_json(store, &health_key, &current);
+        }
+        health.push(current);
+    }
+
+    (providers, health)
+}
+
+fn build(
+    config: &ProviderConfig,
+    document: Option<&Value>,
+    secret: &str,
+    redirect_uri: &str,
+) -> Result<Option<Arc<dyn IdentityProvider>>> {
+    match (config.kind, document) {
+        (ProviderKind::Github, _) => Ok(Some(Arc::new(GitHubProvider {
+            name: config.name.clone(),
+            client_id: config.client_id.clone(),
+            client_secret: secret.to_string(),
+            redirect_uri: redirect_uri.to_string(),
+        }))),
+        (ProviderKind::Oidc, Some(document)) => Ok(Some(Arc::new(OidcProvider::from_discovery(
+            &config.name,
+            config.issuer.as_deref().unwrap_or_default(),
+            document,
+            &config.client_id,
+            secret,
+            redirect_uri,
+            config.scopes.as_deref(),
+        )?))),
+        (ProviderKind::Oidc, None) => Ok(None),
+    }
+}
+
+async fn discover(
+    store: &Store,
+    issuer: &str,
+    previous: Option<&ProviderHealth>,
+    now: i64,
+    ttl: Duration,
+) -> Check {
+    let cache_key = format!("{DISCOVERY_KEY_PREFIX}{issuer}");
+    let cached: Option<CachedDiscovery> = read_json(store, &cache_key);
+    if let Some(fresh) = cached.as_ref().filter(|c| c.is_fresh(now, ttl)) {
+        return Check::Cached(fresh.document.clone());
+    }
+    if previous.is_some_and(|p| !p.retry_due(now)) {
+        return Check::Skipped(cached.map(|c| c.document));
+    }
+    match fetch_discovery(issuer).await {
+        Ok(document) => {
+            let entry = CachedDiscovery {
+                fetched_at: now,
+                document,
+            };
+            write_json(store, &cache_key, &entry);
+            Check::Checked {
+                document: Some(entry.document),
+                status: HealthStatus::Up,
+                error: None,
+            }
+        }
+        Err(e) => Check::Checked {
+            status: if cached.is_some() {
+                HealthStatus::Degraded
+            } else {
+                HealthStatus::Down
+            },
+            document: cached.map(|c| c.document),
+            error: Some(e.to_string()),
+        },
+    }
+}
+
+/// Fetches the discovery document of `issuer`, which must name that issuer.
+async fn fetch_discovery(issuer: &str) -> Result<Value> {
+    let issuer = issuer.trim_end_matches('/');
+    let document = get_json(&format!("{issuer}/.well-known/openid-configuration"), None).await?;
+    let named = document["issuer"].as_str().unwrap_or_default();
+    if named.trim_end_matches('/') != issuer {
+        bail!("Discovery document of {issuer} names another issuer: {named:?}");
+    }
+    Ok(document)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(pairs: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let pairs: Vec<(String, String)> = pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |name| {
+            pairs
+                .iter()
+                .find(|(k, _)| k == name)
+                .map(|(_, v)| v.clone())
+        }
+    }
+
+    #[test]
+    fn parses_declared_providers() {
+        let providers = parse_providers(
+            r#"[
+                {"name": "google", "kind": "oidc", "issuer": "https://accounts.google.com",
+                 "client_id": "g", "client_secret_variable": "google_client_secret"},
+                {"name": "github", "kind": "github", "client_id": "h", "client_secret": "dev"}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(providers.len(), 2);
+        assert_eq!(providers[0].kind, ProviderKind::Oidc);
+        assert_eq!(
+            providers[0].client_secret(&lookup(&[("google_client_secret", "s")])),
+            Some("s".to_string())
+        );
+        assert_eq!(
+            providers[1].client_secret(&lookup(&[])),
+            Some("dev".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_ambiguous_or_incomplete_providers() {
+        for json in [
+            r#"[{"name": "a", "kind": "oidc", "client_id": "x"}]"#,
+            r#"[{"name": "Google", "kind": "github", "client_id": "x"}]"#,
+            r#"[{"name": "a", "kind": "github", "client_id": "x"},
+                {"name": "a", "kind": "github", "client_id": "y"}]"#,
+            r#"[{"name": "a", "kind": "saml", "client_id": "x"}]"#,
+            r#"[{"name": "a", "kind": "github", "client_id": "x", "secret": "typo"}]"#,
+        ] {
+            assert!(parse_providers(json).is_err(), "{json}");
+        }
+    }
+
+    #[test]
+    fn legacy_variables_declare_the_original_providers() {
+        let providers = legacy_providers(lookup(&[
+            ("logto_issuer_endpoint", "https://x.logto.app/oidc"),
+            ("logto_app_id", "app"),
+            ("zitadel_domain", "x.zitadel.cloud"),
+            ("github_client_id", "gh"),
+        ]));
+        let names: Vec<&str> = providers.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["logto", "zitadel", "github"]);
+        assert_eq!(
+            providers[1].issuer.as_deref(),
+            Some("https://x.zitadel.cloud/oidc")
+        );
+        assert!(legacy_providers(lookup(&[("logto_app_id", "app")])).is_empty());
+    }
+
+    #[test]
+    fn discovery_is_cached_for_its_ttl() {
+        let cached = CachedDiscovery {
+            fetched_at: 1_000,
+            document: Value::Null,
+        };
+        assert!(cached.is_fresh(1_000 + 3_599, Duration::seconds(3_600)));
+        assert!(!cached.is_fresh(1_000 + 3_600, Duration::seconds(3_600)));
+    }
+
+    #[test]
+    fn failures_are_counted_and_retried_later() {
+        let config = &legacy_providers(lookup(&[("github_client_id", "gh")]))[0];
+        let down = ProviderHealth::observe(None, config, HealthStatus::Down, None, 100);
+        let again = ProviderHealth::observe(Some(&down), config, HealthStatus::Degraded, None, 200);
+        assert_eq!(again.consecutive_failures, 2);
+        assert!(!again.retry_due(200 + RETRY_SECONDS - 1));
+        assert!(again.retry_due(200 + RETRY_SECONDS));
+
+        let up = ProviderHealth::observe(Some(&again), config, HealthStatus::Up, None, 300);
+        assert_eq!(up.consecutive_failures, 0);
+        assert!(up.retry_due(300));
+    }
+}
@@ -0,0 +1,66 @@
+//! Seals and opens stored secrets through security-service, so the keys to
+//! them are held there and not next to the `users` table.
+
+use anyhow::{anyhow, bail, Result};
+use serde_json::{json, Value};
+use shared::gateway_token::{
+    sign_service_token, AUTH_ISSUER, GATEWAY_TOKEN_HEADER, INTERNAL_TOKEN_SECRET_VAR,
+};
+use spin_sdk::http::{Method, Request, Response};
+
+const SECURITY_SERVICE: &str = "security-service";
+const ENCRYPT_URL: &str = "http://security-service.spin.internal/security/encrypt";
+const DECRYPT_URL: &str = "http://security-service.spin.internal/security/decrypt";
+
+/// Envelope context of `users.totp_secret`.
+pub const TOTP_SECRET_CONTEXT: &str = "users.totp_secret";
+
+/// Encrypts `value` for storage in the `context` column.
+pub async fn seal(value: &str, context: &str) -> Result<String> {
+    let sealed = call(ENCRYPT_URL, &json!({ "data": value, "context": context })).await?;
+    sealed["encrypted_data"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("security-service returned no encrypted_data"))
+}
+
+/// Decrypts a value [`seal`]ed for the `context` column.
+pub async fn open(sealed: &str, context: &str) -> Result<String> {
+    let opened = call(
+        DECRYPT_URL,
+        &json!({ "encrypted_data": sealed, "context": context }),
+    )
+    .await?;
+    opened["data"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("security-service returned no data"))
+}
+
+async fn call(url: &str, body: &Value) -> Result<Value> {
+    let secret = spin_sdk::variables::get(INTERNAL_TOKEN_SECRET_VAR)
+        .ok()
+        .filter(|secret| !secret.is_empty())
+        .ok_or_else(|| anyhow!("internal token secret not configured"))?;
+    let token = sign_service_token(
+        secret.as_bytes(),
+        AUTH_ISSUER,
+        SECURITY_SERVICE,
+        &uuid::Uuid::new_v4().to_string(),
+        u64::try_from(chrono::Utc::now().timestamp()).unwrap_or(0),
+    );
+    let request = Request::builder()
+        .method(Method::Post)
+        .uri(url)
+        .header("content-type", "application/json")
+        .header(GATEWAY_TOKEN_HEADER, token)
+        .body(serde_json::to_vec(body)?)
+        .build();
+    let response: Response = spin_sdk::http::send(request)
+        .await
+        .map_err(|e| anyhow!("security-service unreachable: {e}"))?;
+    if *response.status() != 200 {
+        bail!("security-service returned status {}", response.status());
+    }
+    Ok(serde_json::from_slice(response.body())?)
+}
@@ -0,0 +1,479 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Duration, Utc};
+use ring::digest::{digest, SHA256};
+use serde::Serialize;
+use spin_sdk::{
+    key_value::Store,
+    sqlite::{Connection, Row, Value},
+};
+
+use crate::identity::Identity;
+use crate::login_transaction::random_token;
+use crate::tokens::{parse_roles, DEFAULT_ROLES};
+use crate::users::LocalUser;
+
+/// Revoked session ids are listed in the key-value store the gateway shares,
+/// as `revoked_session:{sid}` holding the unix time after which no access
+/// token of the session is valid anyway.
+pub const REVOKED_SESSION_PREFIX: &str = "revoked_session:";
+
+/// Longest `User-Agent` kept to tell sessions apart in the session list.
+const USER_AGENT_MAX_CHARS: usize = 200;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SessionError {
+    Unknown,
+    Reused,
+    Revoked,
+    Expired,
+    Storage(String),
+}
+
+impl std::fmt::Display for SessionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unknown => write!(f, "Refresh token is invalid"),
+            Self::Reused => write!(
+                f,
+                "Refresh token was already used; the session has been revoked"
+            ),
+            Self::Revoked => write!(f, "Session has been signed out"),
+            Self::Expired => write!(f, "Session has expired, please sign in again"),
+            Self::Storage(e) => write!(f, "Session storage error: {e}"),
+        }
+    }
+}
+
+impl From<anyhow::Error> for SessionError {
+    fn from(e: anyhow::Error) -> Self {
+        Self::Storage(e.to_string())
+    }
+}
+
+fn storage(e: impl std::fmt::Display) -> SessionError {
+    SessionError::Storage(e.to_string())
+}
+
+/// A sign-in, as the refresh token presented for it leads back to.
+#[derive(Debug)]
+pub struct Session {
+    pub id: String,
+    pub user_id: i64,
+    pub provider: String,
+    pub subject: String,
+    pub id_token: Option<String>,
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl Session {
+    pub fn check(&self, now: DateTime<Utc>) -> Result<(), SessionError> {
+        if self.revoked_at.is_some() {
+            return Err(SessionError::Revoked);
+        }
+        if now >= self.expires_at {
+            return Err(SessionError::Expired);
+        }
+        Ok(())
+    }
+}
+
+/// What the user is shown in their session list.
+#[derive(Debug, Serialize)]
+pub struct SessionInfo {
+    pub id: String,
+    pub provider: String,
+    pub user_agent: Option<String>,
+    pub created_at: String,
+    pub last_used_at: Option<String>,
+    pub expires_at: String,
+}
+
+/// A refresh token handed to the client, with the session it belongs to.
+pub struct Issued {
+    pub session_id: String,
+    pub refresh_token: String,
+}
+
+/// A rotated refresh token, with who its session belongs to.
+pub struct Refreshed {
+    pub user: LocalUser,
+    pub identity: Identity,
+    pub session_id: String,
+    pub refresh_token: String,
+}
+
+/// Refresh tokens are 256 random bits, so an unsalted SHA-256 is enough to
+/// make a leaked table useless.
+pub fn hash_token(token: &str) -> String {
+    URL_SAFE_NO_PAD.encode(digest(&SHA256, token.as_bytes()))
+}
+
+fn text(value: &str) -> Value {
+    Value::Text(value.to_string())
+}
+
+fn optional_text(value: Option<&str>) -> Value {
+    value.map_or(Value::Null, text)
+}
+
+fn timestamp(row: &Row, column: &str) -> Option<DateTime<Utc>> {
+    row.get::<&str>(column)
+        .and_then(|v| DateTime::parse_from_rfc3339(v).ok())
+        .map(|v| v.with_timezone(&Utc))
+}
+
+fn session_from_row(row: &Row) -> Result<Session, SessionError> {
+    let column = |name: &str| {
+        row.get::<&str>(name)
+            .map(str::to_string)
+            .ok_or_else(|| storage(format!("auth_sessions.{name} is missing")))
+    };
+    Ok(Session {
+        id: column("id")?,
+        user_id: row
+            .get::<i64>("user_id")
+            .ok_or_else(|| storage("auth_sessions.user_id is missing"))?,
+        provider: column("provider")?,
+        subject: column("subject")?,
+        id_token: row.get::<&str>("id_token").map(str::to_string),
+        expires_at: timestamp(row, "expires_at")
+            .ok_or_else(|| storage("auth_sessions.expires_at is invalid"))?,
+        revoked_at: timestamp(row, "revoked_at"),
+    })
+}
+
+const SESSION_COLUMNS: &str = "id, user_id, provider, subject, id_token, expires_at, revoked_at";
+
+fn insert_refresh_token(
+    conn: &Connection,
+    session_id: &str,
+    now: DateTime<Utc>,
+) -> anyhow::Result<String> {
+    let token = random_token()?;
+    conn.execute(
+        "INSERT INTO refresh_tokens (token_hash, session_id, created_at) VALUES (?, ?, ?)",
+        &[
+            text(&hash_token(&token)),
+            text(session_id),
+            text(&now.to_rfc3339()),
+        ],
+    )?;
+    Ok(token)
+}
+
+/// Opens a session for a sign-in and hands out its first refresh token.
+pub fn start(
+    user: &LocalUser,
+    identity: &Identity,
+    id_token: Option<&str>,
+    user_agent: Option<&str>,
+    ttl: Duration,
+) -> anyhow::Result<Issued> {
+    let conn = Connection::open_default()?;
+    let now = Utc::now();
+    let session_id = random_token()?;
+    let user_agent: Option<String> =
+        user_agent.map(|ua| ua.chars().take(USER_AGENT_MAX_CHARS).collect());
+    conn.execute(
+        "INSERT INTO auth_sessions \
+         (id, user_id, provider, subject, id_token, user_agent, created_at, last_used_at, \
+         expires_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        &[
+            text(&session_id),
+            Value::Integer(user.id),
+            text(&identity.provider),
+            text(&identity.subject),
+            optional_text(id_token),
+            optional_text(user_agent.as_deref()),
+            text(&now.to_rfc3339()),
+            text(&now.to_rfc3339()),
+            text(&(now + ttl).to_rfc3339()),
+        ],
+    )?;
+    let refresh_token = insert_refresh_token(&conn, &session_id, now)?;
+    Ok(Issued {
+        session_id,
+        refresh_token,
+    })
+}
+
+/// Trades a refresh token for the next one of its session. A token is good
+/// for one rotation: presenting it again means it was copied, and the whole
+/// session is revoked, cutting off whoever holds its latest token too.
+pub fn rotate(
+    store: &Store,
+    refresh_token: &str,
+    token_ttl: Duration,
+) -> Result<Refreshed, SessionError> {
+    let conn = Connection::open_default().map_err(storage)?;
+    let now = Utc::now();
+    let hash = hash_token(refresh_token);
+
+    // Marking the token used and learning whether it was unused is a single
+    // statement, so two concurrent refreshes cannot both rotate it.
+    let claimed = conn
+        .execute(
+            "UPDATE refresh_tokens SET used_at = ? \
+             WHERE token_hash = ? AND used_at IS NULL RETURNING session_id",
+            &[text(&now.to_rfc3339()), text(&hash)],
+        )
+        .map_err(storage)?;
+    let Some(session_id) = claimed
+        .rows()
+        .next()
+        .and_then(|row| row.get::<&str>("session_id").map(str::to_string))
+    else {
+        let reused = conn
+            .execute(
+                "SELECT session_id FROM refresh_tokens WHERE token_hash = ?",
+                &[text(&hash)],
+            )
+            .map_err(storage)?;
+        if let Some(session_id) = reused
+            .rows()
+            .next()
+            .and_then(|row| row.get::<&str>("session_id").map(str::to_string))
+        {
+            revoke_rows(&conn, store, &[session_id], now, token_ttl)?;
+            return Err(SessionError::Reused);
+        }
+        return Err(SessionError::Unknown);
+    };
+
+    let found = conn
+        .execute(
+            &format!(
+                "SELECT {SESSION_COLUMNS}, \
+                 (SELECT roles FROM users WHERE users.id = auth_sessions.user_id) AS roles, \
+                 (SELECT expires_at FROM users WHERE users.id = auth_sessions.user_id) \
+                 AS account_expires_at, \
+                 (SELECT email FROM user_identities i WHERE i.provider = auth_sessions.provider \
+                 AND i.subject = auth_sessions.subject) AS email \
+                 FROM auth_sessions WHERE id = ?"
+            ),
+            &[text(&session_id)],
+        )
+        .map_err(storage)?;
+    let row = found.rows().next().ok_or(SessionError::Unknown)?;
+    let session = session_from_row(&row)?;
+    session.check(now)?;
+    // A staff account whose rota was cut short ends its sessions with it.
+    if timestamp(&row, "account_expires_at").is_some_and(|end| now >= end) {
+        return Err(SessionError::Expired);
+    }
+
+    conn.execute(
+        "UPDATE auth_sessions SET last_used_at = ? WHERE id = ?",
+        &[text(&now.to_rfc3339()), text(&session.id)],
+    )
+    .map_err(storage)?;
+    let refresh_token = insert_refresh_token(&conn, &session.id, now)?;
+
+    Ok(Refreshed {
+        user: LocalUser {
+            id: session.user_id,
+            roles: parse_roles(row.get::<&str>("roles").unwrap_or(DEFAULT_ROLES)),
+        },
+        identity: Identity {
+            provider: session.provider,
+            subject: session.subject,
+            email: row.get::<&str>("email").map(str::to_string),
+            name: None,
+        },
+        session_id: session.id,
+        refresh_token,
+    })
+}
+
+/// The session a refresh token was issued for, used or not.
+pub fn find_by_refresh_token(refresh_token: &str) -> anyhow::Result<Option<Session>> {
+    let conn = Connection::open_default()?;
+    let found = conn.execute(
+        &format!(
+            "SELECT {SESSION_COLUMNS} FROM auth_sessions WHERE id = \
+             (SELECT session_id FROM refresh_tokens WHERE token_hash = ?)"
+        ),
+        &[text(&hash_token(refresh_token))],
+    )?;
+    let row = found.rows().next();
+    row.map(|row| session_from_row(&row).map_err(|e| anyhow::anyhow!("{e}")))
+        .transpose()
+}
+
+pub fn find(session_id: &str) -> anyhow::Result<Option<Session>> {
+    let conn = Connection::open_default()?;
+    let found = conn.execute(
+        &format!("SELECT {SESSION_COLUMNS} FROM auth_sessions WHERE id = ?"),
+        &[text(session_id)],
+    )?;
+    let row = found.rows().next();
+    row.map(|row| session_from_row(&row).map_err(|e| anyhow::anyhow!("{e}")))
+        .transpose()
+}
+
+/// The user's sessions that can still be refreshed, most recently used first.
+pub fn list(user_id: i64) -> anyhow::Result<Vec<SessionInfo>> {
+    let conn = Connection::open_default()?;
+    let now = Utc::now();
+    let found = conn.execute(
+        "SELECT id, provider, user_agent, created_at, last_used_at, expires_at \
+         FROM auth_sessions WHERE user_id = ? AND revoked_at IS NULL \
+         ORDER BY last_used_at DESC",
+        &[Value::Integer(user_id)],
+    )?;
+    Ok(found
+        .rows()
+        .filter(|row| timestamp(row, "expires_at").is_some_and(|exp| exp > now))
+        .map(|row| {
+            let column = |name: &str| row.get::<&str>(name).map(str::to_string);
+            SessionInfo {
+                id: column("id").unwrap_or_default(),
+                provider: column("provider").unwrap_or_default(),
+                user_agent: column("user_agent"),
+                created_at: column("created_at").unwrap_or_default(),
+                last_used_at: column("last_used_at"),
+                expires_at: column("expires_at").unwrap_or_default(),
+            }
+        })
+        .collect())
+}
+
+/// Revokes one of the user's sessions. Returns `false` when the user has no
+/// such session.
+pub fn revoke(
+    store: &Store,
+    user_id: i64,
+    session_id: &str,
+    token_ttl: Duration,
+) -> anyhow::Result<bool> {
+    let conn = Connection::open_default()?;
+    let owned = conn.execute(
+        "SELECT id FROM auth_sessions WHERE id = ? AND user_id = ?",
+        &[text(session_id), Value::Integer(user_id)],
+    )?;
+    if owned.rows().next().is_none() {
+        return Ok(false);
+    }
+    revoke_rows(
+        &conn,
+        store,
+        &[session_id.to_string()],
+        Utc::now(),
+        token_ttl,
+    )?;
+    Ok(true)
+}
+
+/// Revokes every session of the user ("sign out everywhere"). Returns how
+/// many were still active.
+pub fn revoke_all(store: &Store, user_id: i64, token_ttl: Duration) -> anyhow::Result<usize> {
+    let conn = Connection::open_default()?;
+    let active = conn.execute(
+        "SELECT id FROM auth_sessions WHERE user_id = ? AND revoked_at IS NULL",
+        &[Value::Integer(user_id)],
+    )?;
+    let ids: Vec<String> = active
+        .rows()
+        .filter_map(|row| row.get::<&str>("id").map(str::to_string))
+        .collect();
+    revoke_rows(&conn, store, &ids, Utc::now(), token_ttl)?;
+    Ok(ids.len())
+}
+
+/// Marks sessions revoked and lists them for the gateway until the last
+/// access token they could have been issued has expired.
+fn revoke_rows(
+    conn: &Connection,
+    store: &Store,
+    session_ids: &[String],
+    now: DateTime<Utc>,
+    token_ttl: Duration,
+) -> anyhow::Result<()> {
+    let until = (now + token_ttl).timestamp().to_string();
+    for id in session_ids {
+        conn.execute(
+            "UPDATE auth_sessions SET revoked_at = ? WHERE id = ? AND revoked_at IS NULL",
+            &[text(&now.to_rfc3339()), text(id)],
+        )?;
+        store.set(&format!("{REVOKED_SESSION_PREFIX}{id}"), until.as_bytes())?;
+    }
+    prune_revocations(store, now);
+    Ok(())
+}
+
+/// Drops revocation entries whose access tokens have all expired. Best
+/// effort: a stale entry only costs a key.
+fn prune_revocations(store: &Store, now: DateTime<Utc>) {
+    let Ok(keys) = store.get_keys() else {
+        return;
+    };
+    for key in keys
+        .iter()
+        .filter(|k| k.starts_with(REVOKED_SESSION_PREFIX))
+    {
+        let until = store.get(key).ok().flatten();
+        if until.is_some_and(|v| !revocation_current(&v, now)) {
+            let _ = store.delete(key);
+        }
+    }
+}
+
+/// Whether a revocation entry still covers unexpired access tokens.
+/// Unreadable entries are kept.
+fn revocation_current(until: &[u8], now: DateTime<Utc>) -> bool {
+    std::str::from_utf8(until)
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .is_none_or(|until| until > now.timestamp())
+}
+
+/// Whether access tokens carrying `sid` must be refused.
+pub fn is_revoked(store: &Store, session_id: &str) -> anyhow::Result<bool> {
+    Ok(store.exists(&format!("{REVOKED_SESSION_PREFIX}{session_id}"))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(expires_in: i64, revoked: bool) -> Session {
+        let now = Utc::now();
+        Session {
+            id: "s".to_string(),
+            user_id: 1,
+            provider: "logto".to_string(),
+            subject: "abc".to_string(),
+            id_token: None,
+            expires_at: now + Duration::seconds(expires_in),
+            revoked_at: revoked.then_some(now),
+        }
+    }
+
+    #[test]
+    fn hashes_are_stable_and_do_not_leak_the_token() {
+        let token = random_token().unwrap();
+        assert_eq!(hash_token(&token), hash_token(&token));
+        assert_ne!(hash_token(&token), hash_token(&random_token().unwrap()));
+        assert_eq!(hash_token(&token).len(), 43);
+        assert!(!hash_token(&token).contains(&token));
+    }
+
+    #[test]
+    fn only_live_sessions_refresh() {
+        let now = Utc::now();
+        assert_eq!(session(60, false).check(now), Ok(()));
+        assert_eq!(session(60, true).check(now), Err(SessionError::Revoked));
+        assert_eq!(session(-1, false).check(now), Err(SessionError::Expired));
+    }
+
+    #[test]
+    fn revocations_lapse_with_the_last_access_token() {
+        let now = Utc::now();
+        let later = (now.timestamp() + 60).to_string();
+        let earlier = (now.timestamp() - 60).to_string();
+        assert!(revocation_current(later.as_bytes(), now));
+        assert!(!revocation_current(earlier.as_bytes(), now));
+        assert!(revocation_current(b"garbage", now));
+    }
+}
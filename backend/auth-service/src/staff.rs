@@ -0,0 +1,533 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use spin_sdk::sqlite::{Connection, Row, Value};
+
+use crate::identity::Identity;
+use crate::passwords::{self, MIN_PASSWORD_CHARS};
+use crate::secrets::{self, TOTP_SECRET_CONTEXT};
+use crate::tokens::{parse_roles, Role, DEFAULT_ROLES};
+use crate::totp;
+use crate::users::LocalUser;
+
+/// `provider` of sessions opened with a username and password; the subject
+/// is the local user id.
+pub const LOCAL_PROVIDER: &str = "local";
+/// Issuer shown next to the account in authenticator apps.
+pub const TOTP_ISSUER: &str = "Albergue Carrascalejo";
+
+/// Failed sign-ins, wrong codes included, before the account is locked.
+const MAX_FAILED_ATTEMPTS: i64 = 5;
+const LOCKOUT_MINUTES: i64 = 15;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum StaffError {
+    /// Unknown username, wrong password or not a staff account; deliberately
+    /// not told apart.
+    InvalidCredentials,
+    TotpRequired,
+    InvalidCode,
+    Locked {
+        until: DateTime<Utc>,
+    },
+    /// Outside the account's shift rota.
+    Inactive,
+    TotpAlreadyEnabled,
+    NoPendingTotp,
+    UsernameTaken,
+    Invalid(String),
+    Storage(String),
+}
+
+impl std::fmt::Display for StaffError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidCredentials => write!(f, "Invalid username or password"),
+            Self::TotpRequired => write!(f, "An authentication code is required"),
+            Self::InvalidCode => write!(f, "Invalid authentication code"),
+            Self::Locked { until } => write!(
+                f,
+                "Too many failed sign-ins; try again after {}",
+                until.to_rfc3339()
+            ),
+            Self::Inactive => write!(f, "This account is not active at the moment"),
+            Self::TotpAlreadyEnabled => {
+                write!(f, "Two-factor authentication is already enabled")
+            }
+            Self::NoPendingTotp => write!(f, "Start two-factor enrolment first"),
+            Self::UsernameTaken => write!(f, "Username is already taken"),
+            Self::Invalid(e) => write!(f, "{e}"),
+            Self::Storage(e) => write!(f, "Staff account storage error: {e}"),
+        }
+    }
+}
+
+fn storage(e: impl std::fmt::Display) -> StaffError {
+    StaffError::Storage(e.to_string())
+}
+
+/// A `users` row, as signing in with a password needs it.
+struct StaffAccount {
+    id: i64,
+    username: String,
+    password: String,
+    roles: Vec<Role>,
+    /// Sealed by security-service; see [`open_totp_secret`].
+    totp_secret: Option<String>,
+    totp_enabled: bool,
+    totp_last_step: Option<i64>,
+    failed_login_attempts: i64,
+    locked_until: Option<DateTime<Utc>>,
+    active_from: Option<DateTime<Utc>>,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+impl StaffAccount {
+    fn is_staff(&self) -> bool {
+        self.roles.contains(&Role::Hospitalero) || self.roles.contains(&Role::Admin)
+    }
+
+    fn locked(&self, now: DateTime<Utc>) -> Result<(), StaffError> {
+        match self.locked_until {
+            Some(until) if until > now => Err(StaffError::Locked { until }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Volunteers' accounts only work during their rota.
+    fn active(&self, now: DateTime<Utc>) -> Result<(), StaffError> {
+        let started = self.active_from.is_none_or(|from| from <= now);
+        let ended = self.expires_at.is_some_and(|end| end <= now);
+        if started && !ended {
+            Ok(())
+        } else {
+            Err(StaffError::Inactive)
+        }
+    }
+
+    /// Sessions do not outlive the account.
+    fn session_ttl(&self, ttl: Duration, now: DateTime<Utc>) -> Duration {
+        self.expires_at.map_or(ttl, |end| ttl.min(end - now))
+    }
+}
+
+/// Lock to set once `failed` attempts have been counted.
+fn lock_after(failed: i64, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    (failed >= MAX_FAILED_ATTEMPTS).then(|| now + Duration::minutes(LOCKOUT_MINUTES))
+}
+
+fn text(value: &str) -> Value {
+    Value::Text(value.to_string())
+}
+
+fn optional_timestamp(value: Option<DateTime<Utc>>) -> Value {
+    value.map_or(Value::Null, |v| text(&v.to_rfc3339()))
+}
+
+fn timestamp(row: &Row, column: &str) -> Option<DateTime<Utc>> {
+    row.get::<&str>(column)
+        .and_then(|v| DateTime::parse_from_rfc3339(v).ok())
+        .map(|v| v.with_timezone(&Utc))
+}
+
+const ACCOUNT_COLUMNS: &str = "id, username, password, roles, totp_secret, totp_enabled_at, \
+                               totp_last_step, failed_login_attempts, locked_until, active_from, \
+                               expires_at";
+
+fn account_from_row(row: &Row) -> Result<StaffAccount, StaffError> {
+    let column = |name: &str| row.get::<&str>(name).map(str::to_string);
+    Ok(StaffAccount {
+        id: row
+            .get::<i64>("id")
+            .ok_or_else(|| storage("users.id is missing"))?,
+        username: column("username").unwrap_or_default(),
+        password: column("password").unwrap_or_default(),
+        roles: parse_roles(row.get::<&str>("roles").unwrap_or(DEFAULT_ROLES)),
+        totp_secret: column("totp_secret"),
+        totp_enabled: column("totp_enabled_at").is_some(),
+        totp_last_step: row.get::<i64>("totp_last_step"),
+        failed_login_attempts: row.get::<i64>("failed_login_attempts").unwrap_or(0),
+        locked_until: timestamp(row, "locked_until"),
+        active_from: timestamp(row, "active_from"),
+        expires_at: timestamp(row, "expires_at"),
+    })
+}
+
+fn find(conn: &Connection, column: &str, value: Value) -> Result<Option<StaffAccount>, StaffError> {
+    let found = conn
+        .execute(
+            &format!("SELECT {ACCOUNT_COLUMNS} FROM users WHERE {column} = ?"),
+            &[value],
+        )
+        .map_err(storage)?;
+    let row = found.rows().next();
+    row.map(|row| account_from_row(&row)).transpose()
+}
+
+fn record_failure(
+    conn: &Connection,
+    account_id: i64,
+    now: DateTime<Utc>,
+) -> Result<(), StaffError> {
+    // Counting in the statement itself, so concurrent failures are all
+    // counted and the lock is decided on the count they produced.
+    let counted = conn
+        .execute(
+            "UPDATE users SET failed_login_attempts = failed_login_attempts + 1 \
+             WHERE id = ? RETURNING failed_login_attempts",
+            &[Value::Integer(account_id)],
+        )
+        .map_err(storage)?;
+    let failed = counted
+        .rows()
+        .next()
+        .and_then(|row| row.get::<i64>("failed_login_attempts"))
+        .unwrap_or(0);
+    if let Some(locked_until) = lock_after(failed, now) {
+        // Locking starts the count afresh for when the lock lapses.
+        conn.execute(
+            "UPDATE users SET failed_login_attempts = 0, locked_until = ? WHERE id = ?",
+            &[
+                optional_timestamp(Some(locked_until)),
+                Value::Integer(account_id),
+            ],
+        )
+        .map_err(storage)?;
+    }
+    Ok(())
+}
+
+/// Moves `totp_last_step` forward to `step`, unless a concurrent sign-in
+/// already used this or a later step. `false` means the code was replayed.
+fn claim_totp_step(conn: &Connection, account_id: i64, step: i64) -> Result<bool, StaffError> {
+    let claimed = conn
+        .execute(
+            "UPDATE users SET totp_last_step = ? \
+             WHERE id = ? AND (totp_last_step IS NULL OR totp_last_step < ?) RETURNING id",
+            &[
+                Value::Integer(step),
+                Value::Integer(account_id),
+                Value::Integer(step),
+            ],
+        )
+        .map_err(storage)?;
+    let replayed = claimed.rows().next().is_none();
+    Ok(!replayed)
+}
+
+async fn open_totp_secret(sealed: &str) -> Result<String, StaffError> {
+    secrets::open(sealed, TOTP_SECRET_CONTEXT)
+        .await
+        .map_err(storage)
+}
+
+/// A successful staff sign-in, ready to open a session for.
+pub struct SignedIn {
+    pub user: LocalUser,
+    pub identity: Identity,
+    /// The configured session lifetime, cut to the end of the rota.
+    pub session_ttl: Duration,
+}
+
+/// Checks a hospitalero's or admin's username, password and, once enrolled,
+/// TOTP code. Repeated failures lock the account for a while; the rota is
+/// only checked once the password is right, so it is not told to strangers.
+pub async fn sign_in(
+    username: &str,
+    password: &str,
+    totp_code: Option<&str>,
+    session_ttl: Duration,
+) -> Result<SignedIn, StaffError> {
+    let conn = Connection::open_default().map_err(storage)?;
+    let now = Utc::now();
+
+    let Some(account) = find(&conn, "username", text(username))? else {
+        passwords::verify_nothing(password);
+        return Err(StaffError::InvalidCredentials);
+    };
+    account.locked(now)?;
+    if !passwords::verify(&account.password, password) || !account.is_staff() {
+        record_failure(&conn, account.id, now)?;
+        return Err(StaffError::InvalidCredentials);
+    }
+    account.active(now)?;
+
+    if account.totp_enabled {
+        let Some(code) = totp_code else {
+            return Err(StaffError::TotpRequired);
+        };
+        let secret = match account.totp_secret.as_deref() {
+            Some(sealed) => open_totp_secret(sealed).await?,
+            None => String::new(),
+        };
+        let Some(step) = totp::verify(&secret, code, now.timestamp(), account.totp_last_step)
+        else {
+            record_failure(&conn, account.id, now)?;
+            return Err(StaffError::InvalidCode);
+        };
+        if !claim_totp_step(&conn, account.id, step)? {
+            return Err(StaffError::InvalidCode);
+        }
+    }
+
+    conn.execute(
+        "UPDATE users SET failed_login_attempts = 0, locked_until = NULL WHERE id = ?",
+        &[Value::Integer(account.id)],
+    )
+    .map_err(storage)?;
+
+    Ok(SignedIn {
+        session_ttl: account.session_ttl(session_ttl, now),
+        identity: Identity {
+            provider: LOCAL_PROVIDER.to_string(),
+            subject: account.id.to_string(),
+            email: None,
+            name: Some(account.username),
+        },
+        user: LocalUser {
+            id: account.id,
+            roles: account.roles,
+        },
+    })
+}
+
+/// A TOTP secret waiting for its first code.
+pub struct TotpEnrolment {
+    pub username: String,
+    pub secret: String,
+}
+
+/// Gives the user a new TOTP secret. It is only required at sign-in once a
+/// code from it has been confirmed, so an abandoned enrolment locks no one
+/// out.
+pub async fn begin_totp(user_id: i64) -> Result<TotpEnrolment, StaffError> {
+    let conn = Connection::open_default().map_err(storage)?;
+    let account =
+        find(&conn, "id", Value::Integer(user_id))?.ok_or(StaffError::InvalidCredentials)?;
+    if account.totp_enabled {
+        return Err(StaffError::TotpAlreadyEnabled);
+    }
+    let secret = totp::generate_secret().map_err(storage)?;
+    let sealed = secrets::seal(&secret, TOTP_SECRET_CONTEXT)
+        .await
+        .map_err(storage)?;
+    conn.execute(
+        "UPDATE users SET totp_secret = ?, totp_enabled_at = NULL, totp_last_step = NULL \
+         WHERE id = ?",
+        &[text(&sealed), Value::Integer(user_id)],
+    )
+    .map_err(storage)?;
+    Ok(TotpEnrolment {
+        username: account.username,
+        secret,
+    })
+}
+
+/// Turns TOTP on once the user proves their authenticator has the secret.
+/// Wrong codes count towards the lockout, as they do at sign-in.
+pub async fn confirm_totp(user_id: i64, code: &str) -> Result<(), StaffError> {
+    let conn = Connection::open_default().map_err(storage)?;
+    let now = Utc::now();
+    let account =
+        find(&conn, "id", Value::Integer(user_id))?.ok_or(StaffError::InvalidCredentials)?;
+    if account.totp_enabled {
+        return Err(StaffError::TotpAlreadyEnabled);
+    }
+    account.locked(now)?;
+    let sealed = account.totp_secret.ok_or(StaffError::NoPendingTotp)?;
+    let secret = open_totp_secret(&sealed).await?;
+    let Some(step) = totp::verify(&secret, code, now.timestamp(), None) else {
+        record_failure(&conn, account.id, now)?;
+        return Err(StaffError::InvalidCode);
+    };
+    conn.execute(
+        "UPDATE users SET totp_enabled_at = ?, totp_last_step = ? WHERE id = ?",
+        &[
+            text(&now.to_rfc3339()),
+            Value::Integer(step),
+            Value::Integer(user_id),
+        ],
+    )
+    .map_err(storage)?;
+    Ok(())
+}
+
+/// Body of `POST /api/auth/staff/accounts`.
+#[derive(Debug, Deserialize)]
+pub struct NewStaffAccount {
+    pub username: String,
+    pub password: String,
+    pub roles: Vec<String>,
+    pub active_from: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl NewStaffAccount {
+    /// Roles to store: the staff roles asked for, on top of pilgrim.
+    fn validate(&self, now: DateTime<Utc>) -> Result<Vec<Role>, StaffError> {
+        let invalid = |message: &str| Err(StaffError::Invalid(message.to_string()));
+        if self.username.trim().is_empty() || self.username.contains(':') {
+            // Provider-created users are named `provider:subject`.
+            return invalid("Username must be non-empty and must not contain ':'");
+        }
+        if self.password.chars().count() < MIN_PASSWORD_CHARS {
+            return invalid(&format!(
+                "Password must have at least {MIN_PASSWORD_CHARS} characters"
+            ));
+        }
+        let mut roles = Vec::new();
+        for role in &self.roles {
+            match Role::parse(role) {
+                Some(role @ (Role::Hospitalero | Role::Admin)) => roles.push(role),
+                _ => return invalid(&format!("{role} is not a staff role")),
+            }
+        }
+        if roles.is_empty() {
+            return invalid("A staff account needs the hospitalero or admin role");
+        }
+        // Volunteers rotate; only admin accounts may be open-ended.
+        if self.expires_at.is_none() && !roles.contains(&Role::Admin) {
+            return invalid("Hospitalero accounts need expires_at, the end of their rota");
+        }
+        if self
+            .expires_at
+            .is_some_and(|end| end <= now || self.active_from.is_some_and(|from| end <= from))
+        {
+            return invalid("expires_at must be in the future and after active_from");
+        }
+        roles.push(Role::Pilgrim);
+        roles.sort();
+        roles.dedup();
+        Ok(roles)
+    }
+}
+
+/// Creates a staff account with an Argon2id-hashed password. Returns its id.
+pub fn create(account: &NewStaffAccount) -> Result<i64, StaffError> {
+    let now = Utc::now();
+    let roles = account.validate(now)?;
+    let conn = Connection::open_default().map_err(storage)?;
+    let username = account.username.trim();
+    if find(&conn, "username", text(username))?.is_some() {
+        return Err(StaffError::UsernameTaken);
+    }
+    let hash = passwords::hash(&account.password).map_err(storage)?;
+    let roles: Vec<&str> = roles.iter().map(|r| r.as_str()).collect();
+    let created = conn
+        .execute(
+            "INSERT INTO users (username, password, roles, created_at, active_from, expires_at) \
+             VALUES (?, ?, ?, ?, ?, ?) RETURNING id",
+            &[
+                text(username),
+                text(&hash),
+                text(&roles.join(" ")),
+                text(&now.to_rfc3339()),
+                optional_timestamp(account.active_from),
+                optional_timestamp(account.expires_at),
+            ],
+        )
+        .map_err(storage)?;
+    let id = created.rows().next().and_then(|row| row.get::<i64>("id"));
+    id.ok_or_else(|| storage("Creating the user returned no id"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account(now: DateTime<Utc>) -> StaffAccount {
+        StaffAccount {
+            id: 1,
+            username: "ana".to_string(),
+            password: "!".to_string(),
+            roles: vec![Role::Pilgrim, Role::Hospitalero],
+            totp_secret: None,
+            totp_enabled: false,
+            totp_last_step: None,
+            failed_login_attempts: 0,
+            locked_until: None,
+            active_from: Some(now - Duration::days(1)),
+            expires_at: Some(now + Duration::days(13)),
+        }
+    }
+
+    fn new_account(roles: &[&str], expires_in_days: Option<i64>) -> NewStaffAccount {
+        NewStaffAccount {
+            username: "ana".to_string(),
+            password: "correct horse battery".to_string(),
+            roles: roles.iter().map(|r| (*r).to_string()).collect(),
+            active_from: None,
+            expires_at: expires_in_days.map(|d| Utc::now() + Duration::days(d)),
+        }
+    }
+
+    #[test]
+    fn locks_after_repeated_failures() {
+        let now = Utc::now();
+        for failed in 1..MAX_FAILED_ATTEMPTS {
+            assert_eq!(lock_after(failed, now), None);
+        }
+        let locked = lock_after(MAX_FAILED_ATTEMPTS, now);
+        assert_eq!(locked, Some(now + Duration::minutes(LOCKOUT_MINUTES)));
+
+        let mut locked_account = account(now);
+        locked_account.locked_until = locked;
+        assert!(matches!(
+            locked_account.locked(now),
+            Err(StaffError::Locked { .. })
+        ));
+        assert_eq!(locked_account.locked(now + Duration::hours(1)), Ok(()));
+    }
+
+    #[test]
+    fn accounts_work_only_during_their_rota() {
+        let now = Utc::now();
+        let staff = account(now);
+        assert_eq!(staff.active(now), Ok(()));
+        assert_eq!(
+            staff.active(now - Duration::days(2)),
+            Err(StaffError::Inactive)
+        );
+        assert_eq!(
+            staff.active(now + Duration::days(13)),
+            Err(StaffError::Inactive)
+        );
+        assert_eq!(
+            staff.session_ttl(Duration::days(30), now),
+            Duration::days(13)
+        );
+        assert!(!StaffAccount {
+            roles: vec![Role::Pilgrim],
+            ..account(now)
+        }
+        .is_staff());
+    }
+
+    #[test]
+    fn new_accounts_are_staff_with_a_rota() {
+        let now = Utc::now();
+        assert_eq!(
+            new_account(&["hospitalero"], Some(14)).validate(now),
+            Ok(vec![Role::Pilgrim, Role::Hospitalero])
+        );
+        assert!(new_account(&["admin"], None).validate(now).is_ok());
+        for bad in [
+            new_account(&["hospitalero"], None),
+            new_account(&["hospitalero"], Some(-1)),
+            new_account(&["pilgrim"], Some(14)),
+            new_account(&[], Some(14)),
+            NewStaffAccount {
+                password: "short".to_string(),
+                ..new_account(&["hospitalero"], Some(14))
+            },
+            NewStaffAccount {
+                username: "github:42".to_string(),
+                ..new_account(&["hospitalero"], Some(14))
+            },
+        ] {
+            assert!(
+                matches!(bad.validate(now), Err(StaffError::Invalid(_))),
+                "{bad:?}"
+            );
+        }
+    }
+}
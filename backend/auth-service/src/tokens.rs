@@ -0,0 +1,235 @@
+use anyhow::{anyhow, bail, Result};
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::config::AppConfig;
+use crate::identity::Identity;
+use crate::users::LocalUser;
+
+/// What a local user may do at the albergue. Stored space-separated in
+/// `users.roles`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    Pilgrim,
+    Hospitalero,
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Pilgrim => "pilgrim",
+            Self::Hospitalero => "hospitalero",
+            Self::Admin => "admin",
+        }
+    }
+
+    pub fn parse(role: &str) -> Option<Self> {
+        match role {
+            "pilgrim" => Some(Self::Pilgrim),
+            "hospitalero" => Some(Self::Hospitalero),
+            "admin" => Some(Self::Admin),
+            _ => None,
+        }
+    }
+
+    /// Scopes the role grants. Admins can do everything hospitaleros can.
+    fn scopes(self) -> &'static [&'static str] {
+        match self {
+            Self::Pilgrim => &["profile", "bookings:own", "reviews:write"],
+            Self::Hospitalero => &[
+                "profile",
+                "bookings:read",
+                "bookings:write",
+                "pilgrims:read",
+                "documents:verify",
+                "reviews:moderate",
+            ],
+            Self::Admin => &[
+                "profile",
+                "bookings:read",
+                "bookings:write",
+                "pilgrims:read",
+                "documents:verify",
+                "reviews:moderate",
+                "admin",
+            ],
+        }
+    }
+}
+
+/// Every user is at least a pilgrim; staff roles are granted in `users.roles`.
+pub const DEFAULT_ROLES: &str = "pilgrim";
+
+/// Reads a `users.roles` value, ignoring roles this service does not know.
+pub fn parse_roles(roles: &str) -> Vec<Role> {
+    let mut parsed: Vec<Role> = roles.split_whitespace().filter_map(Role::parse).collect();
+    parsed.sort();
+    parsed.dedup();
+    parsed
+}
+
+/// Space-separated union of the scopes `roles` grant.
+pub fn scope_for(roles: &[Role]) -> String {
+    let mut scopes: Vec<&str> = roles.iter().flat_map(|r| r.scopes()).copied().collect();
+    scopes.sort_unstable();
+    scopes.dedup();
+    scopes.join(" ")
+}
+
+#[derive(Serialize)]
+pub struct Claims {
+    pub iss: String,
+    /// Local user id, the same whichever provider the user signed in with.
+    pub sub: String,
+    pub aud: String,
+    pub iat: i64,
+    pub exp: i64,
+    pub jti: String,
+    /// Session the token was issued for; revoking it revokes the token.
+    pub sid: String,
+    pub roles: Vec<&'static str>,
+    pub scope: String,
+    /// Provider the user signed in with this time.
+    pub idp: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+impl Claims {
+    pub fn new(cfg: &AppConfig, user: &LocalUser, identity: &Identity, session_id: &str) -> Self {
+        let now = Utc::now();
+        Self {
+            iss: cfg.issuer.clone(),
+            sub: user.id.to_string(),
+            aud: cfg.audience.clone(),
+            iat: now.timestamp(),
+            exp: (now + cfg.token_ttl).timestamp(),
+            jti: uuid::Uuid::new_v4().to_string(),
+            sid: session_id.to_string(),
+            roles: user.roles.iter().map(|r| r.as_str()).collect(),
+            scope: scope_for(&user.roles),
+            idp: identity.provider.clone(),
+            email: identity.email.clone(),
+            name: identity.name.clone(),
+        }
+    }
+}
+
+/// Mints the access token for `user` with the current signing key.
+pub fn issue(
+    cfg: &AppConfig,
+    user: &LocalUser,
+    identity: &Identity,
+    session_id: &str,
+) -> Result<String> {
+    let claims = serde_json::to_value(Claims::new(cfg, user, identity, session_id))?;
+    cfg.signing_key().sign(&claims)
+}
+
+/// Who presented one of this service's access tokens.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Caller {
+    pub user_id: i64,
+    pub session_id: String,
+    pub roles: Vec<Role>,
+}
+
+impl Caller {
+    pub fn has_role(&self, role: Role) -> bool {
+        self.roles.contains(&role)
+    }
+}
+
+/// Checks an access token this service issued: signed by one of its keys,
+/// for its issuer and audience, and not expired at `now`. Whether its session
+/// has been revoked is left to the caller.
+pub fn authenticate(cfg: &AppConfig, token: &str, now: i64) -> Result<Caller> {
+    let claims = crate::signing::verify(&cfg.signing_keys, token)?;
+    check_claims(&claims, &cfg.issuer, &cfg.audience, now)
+}
+
+fn check_claims(claims: &Value, issuer: &str, audience: &str, now: i64) -> Result<Caller> {
+    if claims["iss"].as_str() != Some(issuer) {
+        bail!("Token was not issued by this service");
+    }
+    if claims["aud"].as_str() != Some(audience) {
+        bail!("Token is not addressed to this API");
+    }
+    if claims["exp"].as_i64().is_none_or(|exp| exp <= now) {
+        bail!("Token has expired");
+    }
+    let user_id = claims["sub"]
+        .as_str()
+        .and_then(|sub| sub.parse().ok())
+        .ok_or_else(|| anyhow!("Token subject is not a local user"))?;
+    let session_id = claims["sid"]
+        .as_str()
+        .ok_or_else(|| anyhow!("Token has no session"))?
+        .to_string();
+    let roles = claims["roles"]
+        .as_array()
+        .map(|roles| roles.iter().filter_map(Value::as_str).collect::<Vec<_>>().join(" "))
+        .unwrap_or_default();
+    Ok(Caller {
+        user_id,
+        session_id,
+        roles: parse_roles(&roles),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_known_roles_once() {
+        assert_eq!(
+            parse_roles("hospitalero pilgrim  root hospitalero"),
+            vec![Role::Pilgrim, Role::Hospitalero]
+        );
+        assert!(parse_roles("").is_empty());
+    }
+
+    #[test]
+    fn accepts_only_current_tokens_for_this_api() {
+        let claims = |iss: &str, aud: &str, exp: i64| {
+            serde_json::json!({
+                "iss": iss, "aud": aud, "exp": exp, "sub": "7", "sid": "s1",
+                "roles": ["pilgrim", "hospitalero"],
+            })
+        };
+        assert_eq!(
+            check_claims(&claims("me", "api", 200), "me", "api", 100).unwrap(),
+            Caller {
+                user_id: 7,
+                session_id: "s1".to_string(),
+                roles: vec![Role::Pilgrim, Role::Hospitalero],
+            }
+        );
+        for bad in [
+            claims("them", "api", 200),
+            claims("me", "other", 200),
+            claims("me", "api", 100),
+            serde_json::json!({ "iss": "me", "aud": "api", "exp": 200, "sub": "7" }),
+        ] {
+            assert!(check_claims(&bad, "me", "api", 100).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn scopes_are_the_union_of_the_roles() {
+        assert_eq!(
+            scope_for(&[Role::Pilgrim]),
+            "bookings:own profile reviews:write"
+        );
+        let staff = scope_for(&[Role::Pilgrim, Role::Admin]);
+        for scope in ["admin", "bookings:own", "reviews:moderate"] {
+            assert!(staff.split(' ').any(|s| s == scope), "{staff}");
+        }
+        assert_eq!(staff.matches("profile").count(), 1);
+    }
+}
@@ -0,0 +1,88 @@
+use anyhow::{Context, Result};
+use chrono::Utc;
+use spin_sdk::sqlite::{Connection, Value};
+
+use crate::identity::Identity;
+use crate::tokens::{parse_roles, Role, DEFAULT_ROLES};
+
+/// Password of users who only sign in through a provider; it matches no
+/// password hash.
+const NO_PASSWORD: &str = "!";
+
+/// Row of the `users` table a provider identity is linked to.
+pub struct LocalUser {
+    pub id: i64,
+    pub roles: Vec<Role>,
+}
+
+fn text(value: &str) -> Value {
+    Value::Text(value.to_string())
+}
+
+fn optional_text(value: Option<&str>) -> Value {
+    value.map_or(Value::Null, text)
+}
+
+/// Finds the local user linked to `identity`, creating a pilgrim account on
+/// first sign-in. Identities are never linked by email: an address asserted
+/// by one provider does not prove ownership of an account created through
+/// another.
+pub fn resolve(identity: &Identity) -> Result<LocalUser> {
+    let conn = Connection::open_default()?;
+    let now = Utc::now().to_rfc3339();
+
+    let found = conn.execute(
+        "SELECT u.id AS id, u.roles AS roles FROM user_identities i \
+         JOIN users u ON u.id = i.user_id \
+         WHERE i.provider = ? AND i.subject = ?",
+        &[text(&identity.provider), text(&identity.subject)],
+    )?;
+    if let Some(row) = found.rows().next() {
+        let id = row.get::<i64>("id").context("users.id is not an integer")?;
+        let roles = parse_roles(row.get::<&str>("roles").unwrap_or(DEFAULT_ROLES));
+        conn.execute(
+            "UPDATE user_identities SET email = ?, last_login_at = ? \
+             WHERE provider = ? AND subject = ?",
+            &[
+                optional_text(identity.email.as_deref()),
+                text(&now),
+                text(&identity.provider),
+                text(&identity.subject),
+            ],
+        )?;
+        return Ok(LocalUser { id, roles });
+    }
+
+    let created = conn.execute(
+        "INSERT INTO users (username, password, roles, created_at) \
+         VALUES (?, ?, ?, ?) RETURNING id",
+        &[
+            text(&format!("{}:{}", identity.provider, identity.subject)),
+            text(NO_PASSWORD),
+            text(DEFAULT_ROLES),
+            text(&now),
+        ],
+    )?;
+    let id = created
+        .rows()
+        .next()
+        .and_then(|row| row.get::<i64>("id"))
+        .context("Creating the user returned no id")?;
+    conn.execute(
+        "INSERT INTO user_identities \
+         (user_id, provider, subject, email, created_at, last_login_at) \
+         VALUES (?, ?, ?, ?, ?, ?)",
+        &[
+            Value::Integer(id),
+            text(&identity.provider),
+            text(&identity.subject),
+            optional_text(identity.email.as_deref()),
+            text(&now),
+            text(&now),
+        ],
+    )?;
+    Ok(LocalUser {
+        id,
+        roles: parse_roles(DEFAULT_ROLES),
+    })
+}
@@ -0,0 +1,49 @@
+//! Records booking changes in security-service's audit log.
+
+use shared::audit::AuditEntry;
+use shared::gateway_token::{
+    sign_service_token, BOOKING_ISSUER, GATEWAY_TOKEN_HEADER, INTERNAL_TOKEN_SECRET_VAR,
+};
+use spin_sdk::http::{Method, Request, Response};
+
+const AUDIT_URL: &str = "http://security-service.spin.internal/security/audit";
+const AUDIT_SERVICE: &str = "security-service";
+
+pub const BOOKINGS_TABLE: &str = "bookings";
+
+/// The client behind the gateway, for the entry's `ip_address` and
+/// `user_agent`.
+pub fn from_client(entry: AuditEntry, req: &Request) -> AuditEntry {
+    let header = |name: &str| req.header(name).and_then(|h| h.as_str());
+    let ip = header("x-forwarded-for").and_then(|hops| hops.split(',').next().map(str::trim));
+    entry.from_client(ip, header("user-agent"))
+}
+
+/// Appends `entry` to the chain. Callers make the change only once this
+/// succeeds, so nothing is changed off the record.
+pub async fn record(entry: &AuditEntry) -> Result<(), String> {
+    let secret = spin_sdk::variables::get(INTERNAL_TOKEN_SECRET_VAR)
+        .ok()
+        .filter(|secret| !secret.is_empty())
+        .ok_or("internal token secret not configured")?;
+    let token = sign_service_token(
+        secret.as_bytes(),
+        BOOKING_ISSUER,
+        AUDIT_SERVICE,
+        &uuid::Uuid::new_v4().to_string(),
+        u64::try_from(chrono::Utc::now().timestamp()).unwrap_or(0),
+    );
+    let body = serde_json::to_vec(entry).map_err(|e| e.to_string())?;
+    let request = Request::builder()
+        .method(Method::Post)
+        .uri(AUDIT_URL)
+        .header("content-type", "application/json")
+        .header(GATEWAY_TOKEN_HEADER, token)
+        .body(body)
+        .build();
+    match spin_sdk::http::send::<_, Response>(request).await {
+        Ok(response) if *response.status() == 201 => Ok(()),
+        Ok(response) => Err(format!("audit log returned status {}", response.status())),
+        Err(e) => Err(format!("audit log unreachable: {e}")),
+    }
+}
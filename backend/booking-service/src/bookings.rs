@@ -0,0 +1,96 @@
+//! Bookings as stored in the albergue's database, the `bookings` table of
+//! the domain model's migrations.
+
+use spin_sdk::sqlite::{Connection, Value};
+
+pub const CHECKED_IN: &str = "checked_in";
+pub const CHECKED_OUT: &str = "checked_out";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CheckOutError {
+    NotFound,
+    /// Only a checked-in booking can check out; carries its status.
+    NotCheckedIn(String),
+    Storage(String),
+}
+
+impl std::fmt::Display for CheckOutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotFound => write!(f, "booking not found"),
+            Self::NotCheckedIn(status) => {
+                write!(f, "booking is {status}, only checked-in bookings can check out")
+            }
+            Self::Storage(e) => write!(f, "booking storage failed: {e}"),
+        }
+    }
+}
+
+/// The pilgrim of a booking that can check out.
+pub fn checked_in_pilgrim(booking_id: i64) -> Result<i64, CheckOutError> {
+    let conn = open()?;
+    let found = conn
+        .execute(
+            "SELECT pilgrim_id, status FROM bookings WHERE id = ?",
+            &[Value::Integer(booking_id)],
+        )
+        .map_err(storage)?;
+    let row = found.rows().next().ok_or(CheckOutError::NotFound)?;
+    let status = row.get::<&str>("status").unwrap_or_default();
+    can_check_out(status)?;
+    row.get::<i64>("pilgrim_id").ok_or(CheckOutError::NotFound)
+}
+
+/// Moves the booking from checked in to checked out. Fails if it is no
+/// longer checked in, e.g. another check-out got there first.
+pub fn check_out(booking_id: i64, at: &str) -> Result<(), CheckOutError> {
+    let conn = open()?;
+    let updated = conn
+        .execute(
+            "UPDATE bookings SET status = ?, updated_at = ? \
+             WHERE id = ? AND status = ? RETURNING id",
+            &[
+                Value::Text(CHECKED_OUT.to_string()),
+                Value::Text(at.to_string()),
+                Value::Integer(booking_id),
+                Value::Text(CHECKED_IN.to_string()),
+            ],
+        )
+        .map_err(storage)?;
+    if updated.rows().next().is_none() {
+        return Err(CheckOutError::NotCheckedIn("no longer checked in".to_string()));
+    }
+    Ok(())
+}
+
+fn can_check_out(status: &str) -> Result<(), CheckOutError> {
+    if status == CHECKED_IN {
+        Ok(())
+    } else {
+        Err(CheckOutError::NotCheckedIn(status.to_string()))
+    }
+}
+
+fn open() -> Result<Connection, CheckOutError> {
+    Connection::open_default().map_err(storage)
+}
+
+fn storage(e: impl std::fmt::Display) -> CheckOutError {
+    CheckOutError::Storage(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_checked_in_bookings_check_out() {
+        assert_eq!(can_check_out(CHECKED_IN), Ok(()));
+        for status in ["reserved", "confirmed", CHECKED_OUT, "cancelled", ""] {
+            assert_eq!(
+                can_check_out(status),
+                Err(CheckOutError::NotCheckedIn(status.to_string()))
+            );
+        }
+    }
+}
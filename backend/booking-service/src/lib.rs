@@ -8,12 +8,28 @@
     clippy::cast_sign_loss,
     clippy::cast_precision_loss,
     // Spin's http component executor is not Send; allow this lint for WASM components.
-    clippy::future_not_send
+    clippy::future_not_send,
+    // Raised inside the bindings `http_component` generates.
+    clippy::same_length_and_capacity
 )]
 
+mod audit;
+mod bookings;
+
+use chrono::Utc;
 use serde::{Deserialize, Serialize};
+use shared::audit::{actions, AuditEntry};
+use shared::event_publisher::create_publisher;
+use shared::events::{topics, BookingCheckedOut, CloudEvent};
+use shared::gateway_token::{
+    verified_user_claims, GATEWAY_TOKEN_HEADER, INTERNAL_TOKEN_SECRET_VAR, USER_CLAIMS_HEADER,
+};
 use spin_sdk::http::{Method, Request, Response, ResponseBuilder};
 use spin_sdk::http_component;
+use std::collections::HashMap;
+
+const SERVICE_NAME: &str = "booking-service";
+const STAFF_ROLES: [&str; 2] = ["admin", "hospitalero"];
 
 #[derive(Serialize, Deserialize)]
 pub struct Booking {
@@ -61,20 +77,122 @@ pub struct Pricing {
 }
 
 #[http_component]
-fn handle_request(req: Request) -> Response {
-    let method = req.method();
-    let path = req.uri();
-
-    match (method, path) {
-        (&Method::Get, "/bookings") => get_bookings(),
-        (&Method::Post, "/bookings") => create_booking(req),
-        (&Method::Get, "/rooms") => get_rooms(),
-        (&Method::Get, "/dashboard/stats") => get_dashboard_stats(),
-        (&Method::Get, "/pricing") => get_pricing(),
+async fn handle_request(req: Request) -> Response {
+    let method = req.method().clone();
+    let path = req.uri().to_string();
+
+    if method == Method::Post {
+        if let Some(booking_id) = check_out_target(&path) {
+            let booking_id = booking_id.to_string();
+            return check_out_booking(req, &booking_id).await;
+        }
+    }
+
+    match (&method, path.as_str()) {
+        (Method::Get, "/bookings") => get_bookings(),
+        (Method::Post, "/bookings") => create_booking(req).await,
+        (Method::Get, "/rooms") => get_rooms(),
+        (Method::Get, "/dashboard/stats") => get_dashboard_stats(),
+        (Method::Get, "/pricing") => get_pricing(),
         _ => error_response(404, "Not found"),
     }
 }
 
+/// The booking id in `.../bookings/{id}/check-out`.
+fn check_out_target(path: &str) -> Option<&str> {
+    let rest = path.strip_suffix("/check-out")?;
+    let (prefix, id) = rest.rsplit_once('/')?;
+    (prefix.ends_with("/bookings") && !id.is_empty()).then_some(id)
+}
+
+/// `POST /bookings/{id}/check-out` — reception staff only. Moves a
+/// checked-in booking to checked out and publishes `BookingCheckedOut`,
+/// which reviews-service answers with a review token for the booking's
+/// pilgrim.
+async fn check_out_booking(req: Request, booking_id: &str) -> Response {
+    let Some(staff) = staff_member(&req) else {
+        return error_response(403, "Staff role required");
+    };
+    let Ok(id) = booking_id.parse::<i64>() else {
+        return error_response(404, "Booking not found");
+    };
+    let pilgrim_id = match bookings::checked_in_pilgrim(id) {
+        Ok(pilgrim_id) => pilgrim_id,
+        Err(err) => return check_out_error(&err),
+    };
+
+    let checked_out = BookingCheckedOut {
+        booking_id: booking_id.to_string(),
+        pilgrim_id: pilgrim_id.to_string(),
+        checked_out_at: Utc::now(),
+    };
+    let mut entry = AuditEntry::new(audit::BOOKINGS_TABLE, booking_id, actions::UPDATE)
+        .by(staff.parse().ok());
+    entry.old_values = Some(serde_json::json!({ "status": bookings::CHECKED_IN }));
+    entry.new_values = Some(serde_json::json!({
+        "status": bookings::CHECKED_OUT,
+        "pilgrim_id": checked_out.pilgrim_id,
+        "checked_out_at": checked_out.checked_out_at,
+    }));
+    if let Err(err) = audit::record(&audit::from_client(entry, &req)).await {
+        return error_response(502, &format!("Failed to record check-out: {err}"));
+    }
+    if let Err(err) = bookings::check_out(id, &checked_out.checked_out_at.to_rfc3339()) {
+        return check_out_error(&err);
+    }
+    let event = CloudEvent::new(
+        topics::BOOKING_CHECKED_OUT.to_string(),
+        SERVICE_NAME.to_string(),
+        checked_out.clone(),
+    );
+    if let Err(err) = create_publisher().publish(&event).await {
+        return error_response(502, &format!("Failed to publish check-out: {err}"));
+    }
+
+    json_response(
+        200,
+        &serde_json::json!({
+            "id": checked_out.booking_id,
+            "status": bookings::CHECKED_OUT,
+            "checked_out_at": checked_out.checked_out_at,
+        }),
+    )
+}
+
+fn check_out_error(err: &bookings::CheckOutError) -> Response {
+    let status = match err {
+        bookings::CheckOutError::NotFound => 404,
+        bookings::CheckOutError::NotCheckedIn(_) => 409,
+        bookings::CheckOutError::Storage(_) => 500,
+    };
+    error_response(status, &err.to_string())
+}
+
+/// The caller's claims, as verified by the gateway.
+fn user_claims(req: &Request) -> Option<HashMap<String, String>> {
+    let secret = spin_sdk::variables::get(INTERNAL_TOKEN_SECRET_VAR).ok()?;
+    let header = |name: &str| req.header(name).and_then(|h| h.as_str());
+    verified_user_claims(
+        header(GATEWAY_TOKEN_HEADER),
+        header(USER_CLAIMS_HEADER),
+        secret.as_bytes(),
+        SERVICE_NAME,
+        u64::try_from(Utc::now().timestamp()).unwrap_or(0),
+    )
+    .ok()
+}
+
+/// The caller's subject when the gateway-verified claims carry a staff role.
+fn staff_member(req: &Request) -> Option<String> {
+    let claims = user_claims(req)?;
+    let is_staff = claims.get("roles").is_some_and(|roles| {
+        roles
+            .split(',')
+            .any(|role| STAFF_ROLES.contains(&role.trim()))
+    });
+    is_staff.then(|| claims.get("sub").cloned().unwrap_or_default())
+}
+
 use serde_json::Value;
 use std::env;
 
@@ -83,7 +201,7 @@ fn register_whatsapp_client(client_phone: &str, business_phone: &str) {
     println!("Registering WhatsApp client {client_phone} with business phone {business_phone}");
 }
 
-fn create_booking(req: Request) -> Response {
+async fn create_booking(req: Request) -> Response {
     // Parse request body with error handling
     let body_bytes = req.body();
     let body_json: Value = match serde_json::from_slice(body_bytes) {
@@ -148,6 +266,19 @@ fn create_booking(req: Request) -> Response {
         payment_status: "pending".to_string(),
     };
 
+    let user_id = user_claims(&req)
+        .and_then(|claims| claims.get("sub").and_then(|sub| sub.parse().ok()));
+    let entry = AuditEntry::change(
+        audit::BOOKINGS_TABLE,
+        &new_booking.id,
+        None,
+        serde_json::to_value(&new_booking).ok().as_ref(),
+    )
+    .by(user_id);
+    if let Err(err) = audit::record(&audit::from_client(entry, &req)).await {
+        return error_response(502, &format!("Failed to record booking: {err}"));
+    }
+
     json_response(201, &new_booking)
 }
 
@@ -0,0 +1,418 @@
+#!/usr/bin/env python3
+"""Add continent, capital, currency, languages and INE codes to data/countries.json.
+
+Usage:
+    import-country-details.py [PAISES.csv]
+
+Continents follow the UN M49 regions, with the Caribbean and Central America
+counted as North America. Capitals, ISO 4217 currencies and official languages
+(English names) are curated below; continents and currencies are checked
+against tzdata and the glibc locales when those are installed, and mismatches
+are printed for review.
+
+PAISES.csv is INE's "Relación de países" saved as CSV, with the three-digit
+INE code (CODIGO) and the Spanish name (NOMBRE), and optionally the ISO alpha-3
+code (ISO3). Rows are matched on alpha-3 when present, otherwise on the
+Spanish name. Countries it does not cover keep no `ine` code.
+"""
+
+import csv
+import json
+import sys
+import unicodedata
+from pathlib import Path
+
+DATASET = Path(__file__).resolve().parent.parent / "data" / "countries.json"
+
+AF, AN, AS, EU, NA, OC, SA = (
+    "Africa",
+    "Antarctica",
+    "Asia",
+    "Europe",
+    "North America",
+    "Oceania",
+    "South America",
+)
+
+# alpha-2: (continent, capital, currency, official languages)
+DETAILS = {
+    "AD": (EU, "Andorra la Vella", "EUR", ["Catalan"]),
+    "AE": (AS, "Abu Dhabi", "AED", ["Arabic"]),
+    "AF": (AS, "Kabul", "AFN", ["Pashto", "Dari"]),
+    "AG": (NA, "St. John's", "XCD", ["English"]),
+    "AI": (NA, "The Valley", "XCD", ["English"]),
+    "AL": (EU, "Tirana", "ALL", ["Albanian"]),
+    "AM": (AS, "Yerevan", "AMD", ["Armenian"]),
+    "AO": (AF, "Luanda", "AOA", ["Portuguese"]),
+    "AQ": (AN, None, None, []),
+    "AR": (SA, "Buenos Aires", "ARS", ["Spanish"]),
+    "AS": (OC, "Pago Pago", "USD", ["English", "Samoan"]),
+    "AT": (EU, "Vienna", "EUR", ["German"]),
+    "AU": (OC, "Canberra", "AUD", ["English"]),
+    "AW": (NA, "Oranjestad", "AWG", ["Dutch", "Papiamento"]),
+    "AX": (EU, "Mariehamn", "EUR", ["Swedish"]),
+    "AZ": (AS, "Baku", "AZN", ["Azerbaijani"]),
+    "BA": (EU, "Sarajevo", "BAM", ["Bosnian", "Croatian", "Serbian"]),
+    "BB": (NA, "Bridgetown", "BBD", ["English"]),
+    "BD": (AS, "Dhaka", "BDT", ["Bengali"]),
+    "BE": (EU, "Brussels", "EUR", ["Dutch", "French", "German"]),
+    "BF": (AF, "Ouagadougou", "XOF", ["French"]),
+    "BG": (EU, "Sofia", "EUR", ["Bulgarian"]),
+    "BH": (AS, "Manama", "BHD", ["Arabic"]),
+    "BI": (AF, "Gitega", "BIF", ["Kirundi", "French", "English"]),
+    "BJ": (AF, "Porto-Novo", "XOF", ["French"]),
+    "BL": (NA, "Gustavia", "EUR", ["French"]),
+    "BM": (NA, "Hamilton", "BMD", ["English"]),
+    "BN": (AS, "Bandar Seri Begawan", "BND", ["Malay"]),
+    "BO": (SA, "Sucre", "BOB", ["Spanish", "Quechua", "Aymara", "Guarani"]),
+    "BQ": (NA, "Kralendijk", "USD", ["Dutch", "Papiamento", "English"]),
+    "BR": (SA, "Brasília", "BRL", ["Portuguese"]),
+    "BS": (NA, "Nassau", "BSD", ["English"]),
+    "BT": (AS, "Thimphu", "BTN", ["Dzongkha"]),
+    "BV": (SA, None, "NOK", []),
+    "BW": (AF, "Gaborone", "BWP", ["English", "Tswana"]),
+    "BY": (EU, "Minsk", "BYN", ["Belarusian", "Russian"]),
+    "BZ": (NA, "Belmopan", "BZD", ["English"]),
+    "CA": (NA, "Ottawa", "CAD", ["English", "French"]),
+    "CC": (OC, "West Island", "AUD", ["English"]),
+    "CD": (AF, "Kinshasa", "CDF", ["French"]),
+    "CF": (AF, "Bangui", "XAF", ["French", "Sango"]),
+    "CG": (AF, "Brazzaville", "XAF", ["French"]),
+    "CH": (EU, "Bern", "CHF", ["German", "French", "Italian", "Romansh"]),
+    "CI": (AF, "Yamoussoukro", "XOF", ["French"]),
+    "CK": (OC, "Avarua", "NZD", ["English", "Cook Islands Māori"]),
+    "CL": (SA, "Santiago", "CLP", ["Spanish"]),
+    "CM": (AF, "Yaoundé", "XAF", ["French", "English"]),
+    "CN": (AS, "Beijing", "CNY", ["Chinese"]),
+    "CO": (SA, "Bogotá", "COP", ["Spanish"]),
+    "CR": (NA, "San José", "CRC", ["Spanish"]),
+    "CU": (NA, "Havana", "CUP", ["Spanish"]),
+    "CV": (AF, "Praia", "CVE", ["Portuguese"]),
+    "CW": (NA, "Willemstad", "XCG", ["Dutch", "Papiamento", "English"]),
+    "CX": (OC, "Flying Fish Cove", "AUD", ["English"]),
+    "CY": (AS, "Nicosia", "EUR", ["Greek", "Turkish"]),
+    "CZ": (EU, "Prague", "CZK", ["Czech"]),
+    "DE": (EU, "Berlin", "EUR", ["German"]),
+    "DJ": (AF, "Djibouti", "DJF", ["French", "Arabic"]),
+    "DK": (EU, "Copenhagen", "DKK", ["Danish"]),
+    "DM": (NA, "Roseau", "XCD", ["English"]),
+    "DO": (NA, "Santo Domingo", "DOP", ["Spanish"]),
+    "DZ": (AF, "Algiers", "DZD", ["Arabic", "Tamazight"]),
+    "EC": (SA, "Quito", "USD", ["Spanish"]),
+    "EE": (EU, "Tallinn", "EUR", ["Estonian"]),
+    "EG": (AF, "Cairo", "EGP", ["Arabic"]),
+    "EH": (AF, "Laayoune", "MAD", ["Arabic"]),
+    "ER": (AF, "Asmara", "ERN", ["Tigrinya", "Arabic", "English"]),
+    "ES": (EU, "Madrid", "EUR", ["Spanish"]),
+    "ET": (AF, "Addis Ababa", "ETB", ["Amharic"]),
+    "FI": (EU, "Helsinki", "EUR", ["Finnish", "Swedish"]),
+    "FJ": (OC, "Suva", "FJD", ["English", "Fijian", "Fiji Hindi"]),
+    "FK": (SA, "Stanley", "FKP", ["English"]),
+    "FM": (OC, "Palikir", "USD", ["English"]),
+    "FO": (EU, "Tórshavn", "DKK", ["Faroese", "Danish"]),
+    "FR": (EU, "Paris", "EUR", ["French"]),
+    "GA": (AF, "Libreville", "XAF", ["French"]),
+    "GB": (EU, "London", "GBP", ["English"]),
+    "GD": (NA, "St. George's", "XCD", ["English"]),
+    "GE": (AS, "Tbilisi", "GEL", ["Georgian"]),
+    "GF": (SA, "Cayenne", "EUR", ["French"]),
+    "GG": (EU, "St Peter Port", "GBP", ["English"]),
+    "GH": (AF, "Accra", "GHS", ["English"]),
+    "GI": (EU, "Gibraltar", "GIP", ["English"]),
+    "GL": (NA, "Nuuk", "DKK", ["Greenlandic"]),
+    "GM": (AF, "Banjul", "GMD", ["English"]),
+    "GN": (AF, "Conakry", "GNF", ["French"]),
+    "GP": (NA, "Basse-Terre", "EUR", ["French"]),
+    "GQ": (AF, "Malabo", "XAF", ["Spanish", "French", "Portuguese"]),
+    "GR": (EU, "Athens", "EUR", ["Greek"]),
+    "GS": (SA, "King Edward Point", "GBP", ["English"]),
+    "GT": (NA, "Guatemala City", "GTQ", ["Spanish"]),
+    "GU": (OC, "Hagåtña", "USD", ["English", "Chamorro"]),
+    "GW": (AF, "Bissau", "XOF", ["Portuguese"]),
+    "GY": (SA, "Georgetown", "GYD", ["English"]),
+    "HK": (AS, None, "HKD", ["Chinese", "English"]),
+    "HM": (OC, None, "AUD", []),
+    "HN": (NA, "Tegucigalpa", "HNL", ["Spanish"]),
+    "HR": (EU, "Zagreb", "EUR", ["Croatian"]),
+    "HT": (NA, "Port-au-Prince", "HTG", ["French", "Haitian Creole"]),
+    "HU": (EU, "Budapest", "HUF", ["Hungarian"]),
+    "ID": (AS, "Jakarta", "IDR", ["Indonesian"]),
+    "IE": (EU, "Dublin", "EUR", ["Irish", "English"]),
+    "IL": (AS, "Jerusalem", "ILS", ["Hebrew"]),
+    "IM": (EU, "Douglas", "GBP", ["English", "Manx"]),
+    "IN": (AS, "New Delhi", "INR", ["Hindi", "English"]),
+    "IO": (AF, None, "USD", ["English"]),
+    "IQ": (AS, "Baghdad", "IQD", ["Arabic", "Kurdish"]),
+    "IR": (AS, "Tehran", "IRR", ["Persian"]),
+    "IS": (EU, "Reykjavík", "ISK", ["Icelandic"]),
+    "IT": (EU, "Rome", "EUR", ["Italian"]),
+    "JE": (EU, "Saint Helier", "GBP", ["English", "French"]),
+    "JM": (NA, "Kingston", "JMD", ["English"]),
+    "JO": (AS, "Amman", "JOD", ["Arabic"]),
+    "JP": (AS, "Tokyo", "JPY", ["Japanese"]),
+    "KE": (AF, "Nairobi", "KES", ["Swahili", "English"]),
+    "KG": (AS, "Bishkek", "KGS", ["Kyrgyz", "Russian"]),
+    "KH": (AS, "Phnom Penh", "KHR", ["Khmer"]),
+    "KI": (OC, "South Tarawa", "AUD", ["English", "Gilbertese"]),
+    "KM": (AF, "Moroni", "KMF", ["Comorian", "Arabic", "French"]),
+    "KN": (NA, "Basseterre", "XCD", ["English"]),
+    "KP": (AS, "Pyongyang", "KPW", ["Korean"]),
+    "KR": (AS, "Seoul", "KRW", ["Korean"]),
+    "KW": (AS, "Kuwait City", "KWD", ["Arabic"]),
+    "KY": (NA, "George Town", "KYD", ["English"]),
+    "KZ": (AS, "Astana", "KZT", ["Kazakh", "Russian"]),
+    "LA": (AS, "Vientiane", "LAK", ["Lao"]),
+    "LB": (AS, "Beirut", "LBP", ["Arabic"]),
+    "LC": (NA, "Castries", "XCD", ["English"]),
+    "LI": (EU, "Vaduz", "CHF", ["German"]),
+    "LK": (AS, "Sri Jayawardenepura Kotte", "LKR", ["Sinhala", "Tamil"]),
+    "LR": (AF, "Monrovia", "LRD", ["English"]),
+    "LS": (AF, "Maseru", "LSL", ["Sotho", "English"]),
+    "LT": (EU, "Vilnius", "EUR", ["Lithuanian"]),
+    "LU": (EU, "Luxembourg", "EUR", ["Luxembourgish", "French", "German"]),
+    "LV": (EU, "Riga", "EUR", ["Latvian"]),
+    "LY": (AF, "Tripoli", "LYD", ["Arabic"]),
+    "MA": (AF, "Rabat", "MAD", ["Arabic", "Tamazight"]),
+    "MC": (EU, "Monaco", "EUR", ["French"]),
+    "MD": (EU, "Chișinău", "MDL", ["Romanian"]),
+    "ME": (EU, "Podgorica", "EUR", ["Montenegrin"]),
+    "MF": (NA, "Marigot", "EUR", ["French"]),
+    "MG": (AF, "Antananarivo", "MGA", ["Malagasy", "French"]),
+    "MH": (OC, "Majuro", "USD", ["Marshallese", "English"]),
+    "MK": (EU, "Skopje", "MKD", ["Macedonian", "Albanian"]),
+    "ML": (AF, "Bamako", "XOF", ["Bambara", "French"]),
+    "MM": (AS, "Naypyidaw", "MMK", ["Burmese"]),
+    "MN": (AS, "Ulaanbaatar", "MNT", ["Mongolian"]),
+    "MO": (AS, None, "MOP", ["Chinese", "Portuguese"]),
+    "MP": (OC, "Saipan", "USD", ["English", "Chamorro", "Carolinian"]),
+    "MQ": (NA, "Fort-de-France", "EUR", ["French"]),
+    "MR": (AF, "Nouakchott", "MRU", ["Arabic"]),
+    "MS": (NA, "Plymouth", "XCD", ["English"]),
+    "MT": (EU, "Valletta", "EUR", ["Maltese", "English"]),
+    "MU": (AF, "Port Louis", "MUR", ["English", "French"]),
+    "MV": (AS, "Malé", "MVR", ["Dhivehi"]),
+    "MW": (AF, "Lilongwe", "MWK", ["English", "Chichewa"]),
+    "MX": (NA, "Mexico City", "MXN", ["Spanish"]),
+    "MY": (AS, "Kuala Lumpur", "MYR", ["Malay"]),
+    "MZ": (AF, "Maputo", "MZN", ["Portuguese"]),
+    "NA": (AF, "Windhoek", "NAD", ["English"]),
+    "NC": (OC, "Nouméa", "XPF", ["French"]),
+    "NE": (AF, "Niamey", "XOF", ["Hausa", "French"]),
+    "NF": (OC, "Kingston", "AUD", ["English", "Norfuk"]),
+    "NG": (AF, "Abuja", "NGN", ["English"]),
+    "NI": (NA, "Managua", "NIO", ["Spanish"]),
+    "NL": (EU, "Amsterdam", "EUR", ["Dutch"]),
+    "NO": (EU, "Oslo", "NOK", ["Norwegian"]),
+    "NP": (AS, "Kathmandu", "NPR", ["Nepali"]),
+    "NR": (OC, "Yaren", "AUD", ["Nauruan", "English"]),
+    "NU": (OC, "Alofi", "NZD", ["Niuean", "English"]),
+    "NZ": (OC, "Wellington", "NZD", ["English", "Māori"]),
+    "OM": (AS, "Muscat", "OMR", ["Arabic"]),
+    "PA": (NA, "Panama City", "PAB", ["Spanish"]),
+    "PE": (SA, "Lima", "PEN", ["Spanish", "Quechua", "Aymara"]),
+    "PF": (OC, "Papeete", "XPF", ["French"]),
+    "PG": (OC, "Port Moresby", "PGK", ["English", "Tok Pisin", "Hiri Motu"]),
+    "PH": (AS, "Manila", "PHP", ["Filipino", "English"]),
+    "PK": (AS, "Islamabad", "PKR", ["Urdu", "English"]),
+    "PL": (EU, "Warsaw", "PLN", ["Polish"]),
+    "PM": (NA, "Saint-Pierre", "EUR", ["French"]),
+    "PN": (OC, "Adamstown", "NZD", ["English"]),
+    "PR": (NA, "San Juan", "USD", ["Spanish", "English"]),
+    "PS": (AS, "Ramallah", "ILS", ["Arabic"]),
+    "PT": (EU, "Lisbon", "EUR", ["Portuguese"]),
+    "PW": (OC, "Ngerulmud", "USD", ["Palauan", "English"]),
+    "PY": (SA, "Asunción", "PYG", ["Spanish", "Guarani"]),
+    "QA": (AS, "Doha", "QAR", ["Arabic"]),
+    "RE": (AF, "Saint-Denis", "EUR", ["French"]),
+    "RO": (EU, "Bucharest", "RON", ["Romanian"]),
+    "RS": (EU, "Belgrade", "RSD", ["Serbian"]),
+    "RU": (EU, "Moscow", "RUB", ["Russian"]),
+    "RW": (AF, "Kigali", "RWF", ["Kinyarwanda", "English", "French", "Swahili"]),
+    "SA": (AS, "Riyadh", "SAR", ["Arabic"]),
+    "SB": (OC, "Honiara", "SBD", ["English"]),
+    "SC": (AF, "Victoria", "SCR", ["Seychellois Creole", "English", "French"]),
+    "SD": (AF, "Khartoum", "SDG", ["Arabic", "English"]),
+    "SE": (EU, "Stockholm", "SEK", ["Swedish"]),
+    "SG": (AS, "Singapore", "SGD", ["English", "Malay", "Chinese", "Tamil"]),
+    "SH": (AF, "Jamestown", "SHP", ["English"]),
+    "SI": (EU, "Ljubljana", "EUR", ["Slovenian"]),
+    "SJ": (EU, "Longyearbyen", "NOK", ["Norwegian"]),
+    "SK": (EU, "Bratislava", "EUR", ["Slovak"]),
+    "SL": (AF, "Freetown", "SLE", ["English"]),
+    "SM": (EU, "San Marino", "EUR", ["Italian"]),
+    "SN": (AF, "Dakar", "XOF", ["French"]),
+    "SO": (AF, "Mogadishu", "SOS", ["Somali", "Arabic"]),
+    "SR": (SA, "Paramaribo", "SRD", ["Dutch"]),
+    "SS": (AF, "Juba", "SSP", ["English"]),
+    "ST": (AF, "São Tomé", "STN", ["Portuguese"]),
+    "SV": (NA, "San Salvador", "USD", ["Spanish"]),
+    "SX": (NA, "Philipsburg", "XCG", ["Dutch", "English"]),
+    "SY": (AS, "Damascus", "SYP", ["Arabic"]),
+    "SZ": (AF, "Mbabane", "SZL", ["Swati", "English"]),
+    "TC": (NA, "Cockburn Town", "USD", ["English"]),
+    "TD": (AF, "N'Djamena", "XAF", ["French", "Arabic"]),
+    "TF": (AF, "Port-aux-Français", "EUR", ["French"]),
+    "TG": (AF, "Lomé", "XOF", ["French"]),
+    "TH": (AS, "Bangkok", "THB", ["Thai"]),
+    "TJ": (AS, "Dushanbe", "TJS", ["Tajik"]),
+    "TK": (OC, None, "NZD", ["Tokelauan", "English"]),
+    "TL": (AS, "Dili", "USD", ["Tetum", "Portuguese"]),
+    "TM": (AS, "Ashgabat", "TMT", ["Turkmen"]),
+    "TN": (AF, "Tunis", "TND", ["Arabic"]),
+    "TO": (OC, "Nuku'alofa", "TOP", ["Tongan", "English"]),
+    "TR": (AS, "Ankara", "TRY", ["Turkish"]),
+    "TT": (NA, "Port of Spain", "TTD", ["English"]),
+    "TV": (OC, "Funafuti", "AUD", ["Tuvaluan", "English"]),
+    "TW": (AS, "Taipei", "TWD", ["Chinese"]),
+    "TZ": (AF, "Dodoma", "TZS", ["Swahili", "English"]),
+    "UA": (EU, "Kyiv", "UAH", ["Ukrainian"]),
+    "UG": (AF, "Kampala", "UGX", ["English", "Swahili"]),
+    "UM": (OC, None, "USD", ["English"]),
+    "US": (NA, "Washington, D.C.", "USD", ["English"]),
+    "UY": (SA, "Montevideo", "UYU", ["Spanish"]),
+    "UZ": (AS, "Tashkent", "UZS", ["Uzbek"]),
+    "VA": (EU, "Vatican City", "EUR", ["Italian", "Latin"]),
+    "VC": (NA, "Kingstown", "XCD", ["English"]),
+    "VE": (SA, "Caracas", "VES", ["Spanish"]),
+    "VG": (NA, "Road Town", "USD", ["English"]),
+    "VI": (NA, "Charlotte Amalie", "USD", ["English"]),
+    "VN": (AS, "Hanoi", "VND", ["Vietnamese"]),
+    "VU": (OC, "Port Vila", "VUV", ["Bislama", "English", "French"]),
+    "WF": (OC, "Mata-Utu", "XPF", ["French"]),
+    "WS": (OC, "Apia", "WST", ["Samoan", "English"]),
+    "YE": (AS, "Sana'a", "YER", ["Arabic"]),
+    "YT": (AF, "Mamoudzou", "EUR", ["French"]),
+    "ZA": (
+        AF,
+        "Pretoria",
+        "ZAR",
+        [
+            "Afrikaans",
+            "English",
+            "Ndebele",
+            "Northern Sotho",
+            "Sotho",
+            "Swati",
+            "Tsonga",
+            "Tswana",
+            "Venda",
+            "Xhosa",
+            "Zulu",
+            "South African Sign Language",
+        ],
+    ),
+    "ZM": (AF, "Lusaka", "ZMW", ["English"]),
+    "ZW": (AF, "Harare", "ZWG", ["English", "Shona", "Ndebele"]),
+}
+
+# tzdata areas that name a single continent.
+TZ_AREAS = {"Africa": AF, "Antarctica": AN, "Asia": AS, "Europe": EU, "Australia": OC}
+
+
+def normalise(name):
+    name = unicodedata.normalize("NFKD", name).encode("ascii", "ignore").decode()
+    return " ".join(name.upper().replace(",", " ").replace(".", " ").split())
+
+
+def read_rows(path):
+    text = Path(path).read_text(encoding="utf-8-sig")
+    delimiter = ";" if text.count(";") > text.count(",") else ","
+    reader = csv.DictReader(text.splitlines(), delimiter=delimiter)
+    return [{k.strip().upper(): (v or "").strip() for k, v in row.items() if k} for row in reader]
+
+
+def column(row, *names):
+    for name in names:
+        if row.get(name):
+            return row[name]
+    return ""
+
+
+def ine_codes(path, countries):
+    by_alpha3 = {c["alpha3"]: c["alpha2"] for c in countries}
+    by_name = {}
+    for c in countries:
+        for name in (c["names"].get("es"), c["names"]["en"]):
+            if name:
+                by_name.setdefault(normalise(name), c["alpha2"])
+    codes = {}
+    for row in read_rows(path):
+        code = column(row, "CODIGO", "COD_PAIS", "CPAIS").zfill(3)
+        alpha2 = by_alpha3.get(column(row, "ISO3", "ISO", "ALFA3").upper()) or by_name.get(
+            normalise(column(row, "NOMBRE", "PAIS", "DENOMINACION"))
+        )
+        if code.isdigit() and alpha2:
+            codes[alpha2] = code
+        elif code.isdigit():
+            print(f"INE country {code} {column(row, 'NOMBRE', 'PAIS')!r} not matched")
+    return codes
+
+
+def tz_continents():
+    path = Path("/usr/share/zoneinfo/zone.tab")
+    if not path.exists():
+        return {}
+    continents = {}
+    for line in path.read_text(encoding="utf-8").splitlines():
+        if line.startswith("#"):
+            continue
+        alpha2, _, zone = line.split("\t")[:3]
+        area = TZ_AREAS.get(zone.split("/")[0])
+        if area:
+            continents.setdefault(alpha2, set()).add(area)
+    return continents
+
+
+def locale_currencies():
+    root = Path("/usr/share/i18n/locales")
+    if not root.exists():
+        return {}
+    currencies = {}
+    for path in root.glob("*_*"):
+        territory = path.name.split("_")[1].split("@")[0]
+        for line in path.read_text(encoding="utf-8", errors="ignore").splitlines():
+            if line.startswith("int_curr_symbol"):
+                symbol = line.split('"')[1].strip() if '"' in line else ""
+                if len(symbol) == 3 and symbol.isalpha():
+                    currencies.setdefault(territory, set()).add(symbol)
+    return currencies
+
+
+def main():
+    dataset = json.loads(DATASET.read_text(encoding="utf-8"))
+    countries = dataset["countries"]
+    ine = ine_codes(sys.argv[1], countries) if len(sys.argv) > 1 else {}
+    continents, currencies = tz_continents(), locale_currencies()
+
+    for country in countries:
+        alpha2 = country["alpha2"]
+        continent, capital, currency, languages = DETAILS[alpha2]
+        if continents.get(alpha2) and continent not in continents[alpha2]:
+            print(f"{alpha2}: {continent}, tzdata says {sorted(continents[alpha2])}")
+        if currencies.get(alpha2) and currency not in currencies[alpha2]:
+            print(f"{alpha2}: {currency}, glibc says {sorted(currencies[alpha2])}")
+        country.update(continent=continent, languages=languages)
+        for key, value in (("capital", capital), ("currency", currency), ("ine", ine.get(alpha2))):
+            if value:
+                country[key] = value
+            else:
+                country.pop(key, None)
+    missing = set(DETAILS) - {c["alpha2"] for c in countries}
+    if missing:
+        sys.exit(f"Details for unknown countries: {sorted(missing)}")
+
+    source = dataset["source"].split("; details")[0]
+    source += "; details (continent, capital, currency, languages) curated in scripts/import-country-details.py"
+    if ine:
+        source += "; INE country codes from INE Relación de países"
+    lines = ",\n".join("  " + json.dumps(c, ensure_ascii=False) for c in countries)
+    DATASET.write_text(
+        '{\n "source": ' + json.dumps(source, ensure_ascii=False)
+        + ',\n "countries": [\n' + lines + "\n ]\n}\n",
+        encoding="utf-8",
+    )
+    print(f"Wrote details for {len(countries)} countries, {len(ine)} with INE codes, to {DATASET}")
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,71 @@
+#!/usr/bin/env python3
+"""Regenerate data/municipalities.json from the official INE and CartoCiudad files.
+
+Usage:
+    import-ine-municipalities.py MUNICIPIOS.csv POSTALES.csv [YEAR]
+
+MUNICIPIOS.csv is INE's "Relación de municipios y sus códigos por provincias"
+saved as CSV (columns CPRO, CMUN, DC, NOMBRE). POSTALES.csv is CartoCiudad's
+postal-code/municipality relation (columns with the postal code and the
+five-digit INE code, e.g. COD_POSTAL and INE_MUN). Both are read as UTF-8 with
+`;` or `,` delimiters.
+"""
+
+import csv
+import json
+import sys
+from collections import defaultdict
+from pathlib import Path
+
+OUTPUT = Path(__file__).resolve().parent.parent / "data" / "municipalities.json"
+
+
+def read_rows(path):
+    text = Path(path).read_text(encoding="utf-8-sig")
+    delimiter = ";" if text.count(";") > text.count(",") else ","
+    reader = csv.DictReader(text.splitlines(), delimiter=delimiter)
+    return [{k.strip().upper(): (v or "").strip() for k, v in row.items() if k} for row in reader]
+
+
+def column(row, *names):
+    for name in names:
+        if row.get(name):
+            return row[name]
+    return ""
+
+
+def main():
+    if len(sys.argv) < 3:
+        sys.exit(__doc__)
+    year = sys.argv[3] if len(sys.argv) > 3 else ""
+
+    postal_codes = defaultdict(set)
+    for row in read_rows(sys.argv[2]):
+        code = column(row, "INE_MUN", "CODIGO_INE", "COD_INE", "CODMUN").zfill(5)
+        postal = column(row, "COD_POSTAL", "CODIGO_POSTAL", "CP").zfill(5)
+        if code.isdigit() and postal.isdigit():
+            postal_codes[code[:5]].add(postal)
+
+    municipalities = []
+    for row in read_rows(sys.argv[1]):
+        cpro, cmun = column(row, "CPRO").zfill(2), column(row, "CMUN").zfill(3)
+        if not (cpro + cmun).isdigit():
+            continue
+        code = cpro + cmun
+        municipalities.append(
+            {"code": code, "name": column(row, "NOMBRE"), "postal_codes": sorted(postal_codes[code])}
+        )
+    municipalities.sort(key=lambda m: m["code"])
+
+    source = f"INE Relación de municipios {year}".strip() + "; postal codes from CartoCiudad"
+    lines = ",\n".join("  " + json.dumps(m, ensure_ascii=False) for m in municipalities)
+    OUTPUT.write_text(
+        '{\n "source": ' + json.dumps(source, ensure_ascii=False)
+        + ',\n "municipalities": [\n' + lines + "\n ]\n}\n",
+        encoding="utf-8",
+    )
+    print(f"Wrote {len(municipalities)} municipalities to {OUTPUT}")
+
+
+if __name__ == "__main__":
+    main()
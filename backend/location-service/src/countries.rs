@@ -0,0 +1,305 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::models::CountryData;
+
+/// ISO 3166-1 with localised names, generated from Debian's iso-codes package,
+/// plus the details added by `scripts/import-country-details.py`.
+const DATASET: &str = include_str!("../data/countries.json");
+
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+/// Languages offered by the gateway's language picker; every one of them has
+/// country names in the bundled dataset.
+pub const SUPPORTED_LANGUAGES: [&str; 19] = [
+    "es", "en", "fr", "de", "it", "pt", "nl", "pl", "ko", "ja", "zh", "ru", "cs", "sk", "hu", "ca",
+    "eu", "gl", "oc",
+];
+
+#[derive(Deserialize, Debug)]
+pub struct CountryRecord {
+    pub alpha2: String,
+    pub alpha3: String,
+    pub numeric: String,
+    pub flag: String,
+    pub calling_code: Option<String>,
+    pub eu: bool,
+    pub schengen: bool,
+    pub continent: Option<String>,
+    pub capital: Option<String>,
+    pub currency: Option<String>,
+    #[serde(default)]
+    pub languages: Vec<String>,
+    /// INE country code, when the INE table has been imported.
+    pub ine: Option<String>,
+    /// Localised names; languages whose name matches English are omitted.
+    names: HashMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct Dataset {
+    countries: Vec<CountryRecord>,
+}
+
+impl CountryRecord {
+    pub fn name(&self, lang: &str) -> &str {
+        self.names
+            .get(lang)
+            .or_else(|| self.names.get(DEFAULT_LANGUAGE))
+            .map_or(self.alpha2.as_str(), String::as_str)
+    }
+
+    pub fn to_country_data(&self, lang: &str) -> CountryData {
+        CountryData {
+            code: self.alpha2.clone(),
+            alpha3: self.alpha3.clone(),
+            numeric: self.numeric.clone(),
+            name: self.name(lang).to_string(),
+            flag: Some(self.flag.clone()),
+            phone_prefix: self.calling_code.clone(),
+            continent: self.continent.clone(),
+            capital: self.capital.clone(),
+            currency: self.currency.clone(),
+            languages: self.languages.clone(),
+            ine_code: self.ine.clone(),
+            calling_code: self.calling_code.clone(),
+            eu_member: self.eu,
+            schengen: self.schengen,
+        }
+    }
+}
+
+pub fn all() -> &'static [CountryRecord] {
+    static COUNTRIES: OnceLock<Vec<CountryRecord>> = OnceLock::new();
+    COUNTRIES.get_or_init(|| {
+        serde_json::from_str::<Dataset>(DATASET).map_or_else(
+            |e| {
+                log::error!("Bundled country dataset is invalid: {e}");
+                Vec::new()
+            },
+            |d| d.countries,
+        )
+    })
+}
+
+/// Look a country up by alpha-2, alpha-3 or numeric code, case-insensitively.
+pub fn find(code: &str) -> Option<&'static CountryRecord> {
+    let code = code.trim().to_uppercase();
+    all().iter().find(|c| match code.len() {
+        2 => c.alpha2 == code,
+        3 if code.bytes().all(|b| b.is_ascii_digit()) => c.numeric == code,
+        3 => c.alpha3 == code,
+        _ => false,
+    })
+}
+
+/// Resolve a requested language (`es`, `pt-BR`, `zh_CN`) to a supported one.
+pub fn resolve_language(requested: Option<&str>) -> &'static str {
+    requested
+        .and_then(|lang| lang.split(['-', '_']).next())
+        .map(str::to_lowercase)
+        .and_then(|lang| SUPPORTED_LANGUAGES.iter().find(|l| **l == lang).copied())
+        .unwrap_or(DEFAULT_LANGUAGE)
+}
+
+/// Every country, ordered by its name in `lang`.
+pub fn sorted(lang: &str) -> Vec<&'static CountryRecord> {
+    let mut countries: Vec<_> = all().iter().collect();
+    countries.sort_by_cached_key(|c| fold(c.name(lang)));
+    countries
+}
+
+/// Prefix and typo-tolerant search over codes and names.
+///
+/// Names are matched in the requested language and in English, so a pilgrim
+/// typing "Germany" on the Spanish site still finds "Alemania".
+pub fn search(query: &str, lang: &str) -> Vec<&'static CountryRecord> {
+    let query = fold(query.trim());
+    if query.is_empty() {
+        return sorted(lang);
+    }
+
+    let mut matches: Vec<(u8, String, &CountryRecord)> = all()
+        .iter()
+        .filter_map(|c| {
+            let rank = rank(c, &query, lang)?;
+            Some((rank, fold(c.name(lang)), c))
+        })
+        .collect();
+    matches.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.cmp(&b.1)));
+    matches.into_iter().map(|(_, _, c)| c).collect()
+}
+
+/// Lower is better; `None` when the country doesn't match at all.
+fn rank(country: &CountryRecord, query: &str, lang: &str) -> Option<u8> {
+    let upper = query.to_uppercase();
+    if country.alpha2 == upper || country.alpha3 == upper {
+        return Some(0);
+    }
+
+    let mut names = vec![fold(country.name(lang))];
+    if lang != DEFAULT_LANGUAGE {
+        names.push(fold(country.name(DEFAULT_LANGUAGE)));
+    }
+
+    names
+        .iter()
+        .filter_map(|name| {
+            if name == query {
+                Some(0)
+            } else if name.starts_with(query) {
+                Some(1)
+            } else if name
+                .split([' ', '-', ',', '('])
+                .any(|w| w.starts_with(query))
+            {
+                Some(2)
+            } else if name.contains(query) {
+                Some(3)
+            } else if is_fuzzy_prefix(name, query) {
+                Some(4)
+            } else {
+                None
+            }
+        })
+        .min()
+}
+
+/// Whether `query` is within a small edit distance of the start of `name`.
+/// The first letter must match; typos there are rare and matching without it
+/// mostly surfaces noise ("Mongolia" for "congo").
+fn is_fuzzy_prefix(name: &str, query: &str) -> bool {
+    if name.chars().next() != query.chars().next() {
+        return false;
+    }
+    let query_len = query.chars().count();
+    let max_distance = match query_len {
+        0..=3 => return false,
+        4..=6 => 1,
+        _ => 2,
+    };
+    let prefix: String = name.chars().take(query_len).collect();
+    levenshtein(&prefix, query) <= max_distance
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut curr = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != *cb);
+            curr[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        prev = curr;
+    }
+    prev[b.len()]
+}
+
+/// Lowercase and strip Latin diacritics, so "espana" finds "España".
+pub fn fold(text: &str) -> String {
+    text.chars()
+        .flat_map(char::to_lowercase)
+        .map(|c| match c {
+            'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => 'a',
+            'ç' | 'ć' | 'č' => 'c',
+            'ď' => 'd',
+            'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ė' | 'ę' | 'ě' => 'e',
+            'ì' | 'í' | 'î' | 'ï' | 'ī' | 'į' => 'i',
+            'ĺ' | 'ľ' | 'ł' => 'l',
+            'ñ' | 'ń' | 'ň' => 'n',
+            'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ő' => 'o',
+            'ŕ' | 'ř' => 'r',
+            'ś' | 'š' | 'ş' => 's',
+            'ť' | 'ţ' => 't',
+            'ù' | 'ú' | 'û' | 'ü' | 'ū' | 'ů' | 'ű' => 'u',
+            'ý' | 'ÿ' => 'y',
+            'ź' | 'ż' | 'ž' => 'z',
+            _ => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dataset_is_complete() {
+        assert_eq!(all().len(), 249);
+        assert_eq!(all().iter().filter(|c| c.eu).count(), 27);
+        assert_eq!(all().iter().filter(|c| c.schengen).count(), 29);
+        for lang in SUPPORTED_LANGUAGES {
+            assert!(!find("ES").unwrap().name(lang).is_empty(), "{lang}");
+        }
+    }
+
+    #[test]
+    fn test_find_by_any_code() {
+        assert_eq!(find("es").unwrap().alpha3, "ESP");
+        assert_eq!(find("PRT").unwrap().alpha2, "PT");
+        assert_eq!(find("250").unwrap().alpha2, "FR");
+        assert!(find("XX").is_none());
+        assert!(find("").is_none());
+    }
+
+    #[test]
+    fn test_localised_names() {
+        let de = find("DE").unwrap();
+        assert_eq!(de.name("es"), "Alemania");
+        assert_eq!(de.name("en"), "Germany");
+        assert_eq!(de.name("ja"), "ドイツ");
+        assert_eq!(find("CA").unwrap().name("fr"), "Canada");
+    }
+
+    #[test]
+    fn test_resolve_language() {
+        assert_eq!(resolve_language(Some("pt-BR")), "pt");
+        assert_eq!(resolve_language(Some("zh_CN")), "zh");
+        assert_eq!(resolve_language(Some("xx")), "en");
+        assert_eq!(resolve_language(None), "en");
+    }
+
+    #[test]
+    fn test_search_prefix_and_accents() {
+        let results = search("espa", "es");
+        assert_eq!(results[0].alpha2, "ES");
+
+        let results = search("germany", "es");
+        assert_eq!(results[0].alpha2, "DE");
+
+        let results = search("PRT", "en");
+        assert_eq!(results[0].alpha2, "PT");
+    }
+
+    #[test]
+    fn test_search_tolerates_typos() {
+        let results = search("Portgual", "en");
+        assert_eq!(results[0].alpha2, "PT");
+
+        assert!(search("zzzz", "en").is_empty());
+    }
+
+    #[test]
+    fn test_sorted_by_localised_name() {
+        let countries = sorted("es");
+        assert_eq!(countries.len(), 249);
+        let names: Vec<_> = countries.iter().map(|c| fold(c.name("es"))).collect();
+        assert!(names.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    /// Holds once `scripts/import-country-details.py` has been run with
+    /// INE's country table; no codes are bundled yet.
+    #[test]
+    #[ignore = "needs the INE country table import"]
+    fn test_ine_codes() {
+        for alpha3 in ["ESP", "PRT", "FRA"] {
+            let ine = find(alpha3).and_then(|c| c.ine.as_deref());
+            assert!(
+                ine.is_some_and(|code| code.len() == 3 && code.bytes().all(|b| b.is_ascii_digit())),
+                "{alpha3} has no INE code"
+            );
+        }
+    }
+}
@@ -2,15 +2,25 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::Result;
-use http::{Method, Request, StatusCode};
-use spin_sdk::http::{IntoResponse, ResponseBuilder};
+use http::{Request, StatusCode};
+use spin_sdk::http::{Response, ResponseBuilder};
 
-use crate::models::{ApiResponse, CacheConfig};
+use crate::models::{
+    AddressValidationRequest, AddressValidationResult, ApiResponse, CacheConfig,
+    PhoneValidationRequest, PhoneValidationResult,
+};
+use crate::municipalities::{self, AddressError};
 use crate::service::CountryService;
-use redis_service::RedisService;
+use crate::{countries, phones};
 
 pub struct RequestHandler {
-    service: Arc<tokio::sync::Mutex<CountryService>>,
+    pub(crate) service: Arc<tokio::sync::Mutex<CountryService>>,
+}
+
+impl Default for RequestHandler {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl RequestHandler {
@@ -21,25 +31,27 @@ impl RequestHandler {
                 .ok()
                 .and_then(|url| if url.is_empty() { None } else { Some(url) });
 
-        let service = if let Some(redis_url) = redis_url {
-            log::info!("Initializing with Redis cache");
-            let redis = RedisService::new(&redis_url);
-            let config = CacheConfig {
-                enabled: true,
-                ttl: Duration::from_secs(3600), // 1 hour TTL
-            };
-            CountryService::with_redis(redis, Some(config))
-        } else {
-            log::warn!("Redis not configured, using in-memory cache only");
-            CountryService::with_memory_cache(Some(CacheConfig::default()))
-        };
+        let service = redis_url.map_or_else(
+            || {
+                log::warn!("Redis not configured, using in-memory cache only");
+                CountryService::with_memory_cache(Some(CacheConfig::default()))
+            },
+            |redis_url| {
+                log::info!("Initializing with Redis cache");
+                let config = CacheConfig {
+                    enabled: true,
+                    ttl: Duration::from_hours(1), // 1 hour TTL
+                };
+                CountryService::with_redis(&redis_url, Some(config))
+            },
+        );
 
         Self {
             service: Arc::new(tokio::sync::Mutex::new(service)),
         }
     }
 
-    pub async fn handle_request(&self, req: Request<Vec<u8>>) -> Result<impl IntoResponse> {
+    pub async fn handle_request(&self, req: Request<Vec<u8>>) -> Result<Response> {
         let method = req.method();
         let path = req.uri().path();
 
@@ -53,29 +65,38 @@ impl RequestHandler {
                 self.handle_get_country(req).await
             }
             ("POST", "/api/countries/warm-cache") => self.handle_warm_cache().await,
-            ("GET", "/api/countries") => self.handle_list_countries().await,
+            ("GET", "/api/countries") => Ok(Self::handle_list_countries(&req)),
             ("DELETE", "/api/countries/cache") => self.handle_clear_cache().await,
             ("DELETE", path) if path.starts_with("/api/countries/") => {
                 self.handle_clear_country_cache(req).await
             }
+            ("POST", "/api/phones/validate") => Ok(Self::handle_validate_phone(&req)),
+            ("GET", "/api/addresses/provinces") => Ok(Self::handle_list_provinces()),
+            ("GET", path) if path.starts_with("/api/addresses/postal-codes/") => {
+                Ok(Self::handle_lookup_postal_code(path))
+            }
+            ("GET", path) if path.starts_with("/api/addresses/municipalities/") => {
+                Ok(Self::handle_get_municipality(path))
+            }
+            ("POST", "/api/addresses/validate") => Ok(Self::handle_validate_address(&req)),
             _ => Ok(Self::handle_not_found()),
         }
     }
 
-    async fn handle_get_country(&self, req: Request<Vec<u8>>) -> Result<impl IntoResponse> {
+    async fn handle_get_country(&self, req: Request<Vec<u8>>) -> Result<Response> {
         let path = req.uri().path();
         let code = path.strip_prefix("/api/countries/").unwrap_or("");
 
         if code.is_empty() {
-            return Ok(ResponseBuilder::new(StatusCode::BAD_REQUEST)
-                .header("content-type", "application/json")
-                .header("Access-Control-Allow-Origin", "*")
-                .body(r#"{"error":"Country code is required"}"#)
-                .build());
+            return Ok(json_response(
+                StatusCode::BAD_REQUEST,
+                &ApiResponse::<()>::error("Country code is required".to_string()),
+            ));
         }
 
+        let lang = request_language(&req);
         let mut service = self.service.lock().await;
-        match service.get_country_data(code).await {
+        match service.get_country_data(code, lang) {
             Ok(Some(country)) => {
                 let response = ApiResponse::success(country);
                 let body = serde_json::to_string(&response).unwrap_or_else(|_| {
@@ -101,7 +122,7 @@ impl RequestHandler {
                     .build())
             }
             Err(e) => {
-                log::error!("Error getting country data: {}", e);
+                log::error!("Error getting country data: {e}");
                 let response = ApiResponse::<()>::error("Internal server error".to_string());
                 let body = serde_json::to_string(&response).unwrap_or_else(|_| {
                     r#"{"success":false,"message":"Internal server error"}"#.to_string()
@@ -116,11 +137,11 @@ impl RequestHandler {
         }
     }
 
-    async fn handle_warm_cache(&self) -> Result<impl IntoResponse> {
+    async fn handle_warm_cache(&self) -> Result<Response> {
         let common_countries = ["ES", "FR", "PT", "IT", "DE", "GB"];
         let mut service = self.service.lock().await;
-        match service.warm_cache(&common_countries).await {
-            Ok(_) => {
+        match service.warm_cache(&common_countries) {
+            Ok(()) => {
                 let response = ApiResponse::success("Cache warmed successfully");
                 let body = serde_json::to_string(&response).unwrap_or_else(|_| {
                     r#"{"success":true,"message":"Cache warmed successfully"}"#.to_string()
@@ -133,8 +154,8 @@ impl RequestHandler {
                     .build())
             }
             Err(e) => {
-                log::error!("Failed to warm cache: {}", e);
-                let response = ApiResponse::<()>::error(format!("Failed to warm cache: {}", e));
+                log::error!("Failed to warm cache: {e}");
+                let response = ApiResponse::<()>::error(format!("Failed to warm cache: {e}"));
                 let body = serde_json::to_string(&response).unwrap_or_else(|_| {
                     r#"{"success":false,"message":"Failed to warm cache"}"#.to_string()
                 });
@@ -148,25 +169,27 @@ impl RequestHandler {
         }
     }
 
-    async fn handle_list_countries(&self) -> Result<impl IntoResponse> {
-        // In a real app, you would return the list of supported countries
-        let countries = vec!["ES", "FR", "DE", "IT", "PT"];
+    fn handle_list_countries(req: &Request<Vec<u8>>) -> Response {
+        let search = query_param(req, "search").filter(|q| !q.trim().is_empty());
+        let lang = request_language(req);
+        let countries = CountryService::list_countries(search.as_deref(), lang);
 
         let response = ApiResponse::success(countries);
         let body = serde_json::to_string(&response)
             .unwrap_or_else(|_| r#"{"success":true,"data":[]}"#.to_string());
 
-        Ok(ResponseBuilder::new(StatusCode::OK)
+        ResponseBuilder::new(StatusCode::OK)
             .header("content-type", "application/json")
             .header("Access-Control-Allow-Origin", "*")
+            .header("Content-Language", lang)
             .body(body)
-            .build())
+            .build()
     }
 
-    async fn handle_clear_cache(&self) -> Result<impl IntoResponse> {
+    async fn handle_clear_cache(&self) -> Result<Response> {
         let mut service = self.service.lock().await;
-        match service.clear_cache().await {
-            Ok(_) => {
+        match service.clear_cache() {
+            Ok(()) => {
                 let response = ApiResponse::success("Cache cleared successfully");
                 let body = serde_json::to_string(&response).unwrap_or_else(|_| {
                     r#"{"success":true,"message":"Cache cleared successfully"}"#.to_string()
@@ -179,8 +202,8 @@ impl RequestHandler {
                     .build())
             }
             Err(e) => {
-                log::error!("Failed to clear cache: {}", e);
-                let response = ApiResponse::<()>::error(format!("Failed to clear cache: {}", e));
+                log::error!("Failed to clear cache: {e}");
+                let response = ApiResponse::<()>::error(format!("Failed to clear cache: {e}"));
                 let body = serde_json::to_string(&response).unwrap_or_else(|_| {
                     r#"{"success":false,"message":"Failed to clear cache"}"#.to_string()
                 });
@@ -194,21 +217,20 @@ impl RequestHandler {
         }
     }
 
-    async fn handle_clear_country_cache(&self, req: Request<Vec<u8>>) -> Result<impl IntoResponse> {
+    async fn handle_clear_country_cache(&self, req: Request<Vec<u8>>) -> Result<Response> {
         let path = req.uri().path();
         let code = path.strip_prefix("/api/countries/").unwrap_or("");
 
         if code.is_empty() {
-            return Ok(ResponseBuilder::new(StatusCode::BAD_REQUEST)
-                .header("content-type", "application/json")
-                .header("Access-Control-Allow-Origin", "*")
-                .body(r#"{"error":"Country code is required"}"#)
-                .build());
+            return Ok(json_response(
+                StatusCode::BAD_REQUEST,
+                &ApiResponse::<()>::error("Country code is required".to_string()),
+            ));
         }
 
         let mut service = self.service.lock().await;
-        match service.clear_country_cache(code).await {
-            Ok(_) => {
+        match service.clear_country_cache(code) {
+            Ok(()) => {
                 let response = ApiResponse::success("Country cache cleared successfully");
                 let body = serde_json::to_string(&response).unwrap_or_else(|_| {
                     r#"{"success":true,"message":"Country cache cleared successfully"}"#.to_string()
@@ -221,9 +243,9 @@ impl RequestHandler {
                     .build())
             }
             Err(e) => {
-                log::error!("Failed to clear country cache: {}", e);
+                log::error!("Failed to clear country cache: {e}");
                 let response =
-                    ApiResponse::<()>::error(format!("Failed to clear country cache: {}", e));
+                    ApiResponse::<()>::error(format!("Failed to clear country cache: {e}"));
                 let body = serde_json::to_string(&response).unwrap_or_else(|_| {
                     r#"{"success":false,"message":"Failed to clear country cache"}"#.to_string()
                 });
@@ -237,7 +259,111 @@ impl RequestHandler {
         }
     }
 
-    fn handle_cors_preflight() -> impl IntoResponse {
+    fn handle_validate_phone(req: &Request<Vec<u8>>) -> Response {
+        let Ok(request) = serde_json::from_slice::<PhoneValidationRequest>(req.body()) else {
+            let response = ApiResponse::<()>::error(
+                "Body must be JSON with a `phone` and optional `country`".to_string(),
+            );
+            return ResponseBuilder::new(StatusCode::BAD_REQUEST)
+                .header("content-type", "application/json")
+                .header("Access-Control-Allow-Origin", "*")
+                .body(serde_json::to_string(&response).unwrap_or_default())
+                .build();
+        };
+
+        let result = match phones::parse(&request.phone, request.country.as_deref()) {
+            Ok(number) => PhoneValidationResult {
+                valid: true,
+                number: Some(number),
+                error: None,
+                message: None,
+            },
+            Err(e) => PhoneValidationResult {
+                valid: false,
+                number: None,
+                error: Some(e.code().to_string()),
+                message: Some(e.to_string()),
+            },
+        };
+
+        let response = ApiResponse::success(result);
+        let body = serde_json::to_string(&response).unwrap_or_else(|_| {
+            r#"{"success":false,"message":"Failed to serialize response"}"#.to_string()
+        });
+
+        ResponseBuilder::new(StatusCode::OK)
+            .header("content-type", "application/json")
+            .header("Access-Control-Allow-Origin", "*")
+            .body(body)
+            .build()
+    }
+
+    fn handle_list_provinces() -> Response {
+        let response = ApiResponse::success(municipalities::provinces());
+        json_response(StatusCode::OK, &response)
+    }
+
+    fn handle_lookup_postal_code(path: &str) -> Response {
+        let postal_code = path
+            .strip_prefix("/api/addresses/postal-codes/")
+            .unwrap_or("");
+
+        match municipalities::lookup_postal_code(postal_code) {
+            Ok(lookup) => json_response(StatusCode::OK, &ApiResponse::success(lookup)),
+            Err(e @ AddressError::UnknownPostalArea(_)) => json_response(
+                StatusCode::NOT_FOUND,
+                &ApiResponse::<()>::error(e.to_string()),
+            ),
+            Err(e) => json_response(
+                StatusCode::BAD_REQUEST,
+                &ApiResponse::<()>::error(e.to_string()),
+            ),
+        }
+    }
+
+    fn handle_get_municipality(path: &str) -> Response {
+        let code = path
+            .strip_prefix("/api/addresses/municipalities/")
+            .unwrap_or("");
+
+        match municipalities::find_municipality(code) {
+            Ok(Some(municipality)) => {
+                json_response(StatusCode::OK, &ApiResponse::success(municipality))
+            }
+            Ok(None) => json_response(
+                StatusCode::NOT_FOUND,
+                &ApiResponse::<()>::error("Municipality not found".to_string()),
+            ),
+            Err(e) => json_response(
+                StatusCode::BAD_REQUEST,
+                &ApiResponse::<()>::error(e.to_string()),
+            ),
+        }
+    }
+
+    fn handle_validate_address(req: &Request<Vec<u8>>) -> Response {
+        let Ok(request) = serde_json::from_slice::<AddressValidationRequest>(req.body()) else {
+            return json_response(
+                StatusCode::BAD_REQUEST,
+                &ApiResponse::<()>::error(
+                    "Body must be JSON with a `postal_code` and optional `province` and `municipality_code`"
+                        .to_string(),
+                ),
+            );
+        };
+
+        let check = municipalities::validate(
+            &request.postal_code,
+            request.province.as_deref(),
+            request.municipality_code.as_deref(),
+        );
+        json_response(
+            StatusCode::OK,
+            &ApiResponse::success(AddressValidationResult::from(check)),
+        )
+    }
+
+    fn handle_cors_preflight() -> Response {
         ResponseBuilder::new(StatusCode::OK)
             .header("Access-Control-Allow-Origin", "*")
             .header(
@@ -252,7 +378,7 @@ impl RequestHandler {
             .build()
     }
 
-    fn handle_not_found() -> impl IntoResponse {
+    fn handle_not_found() -> Response {
         let response = ApiResponse::<()>::error("Endpoint not found".to_string());
         ResponseBuilder::new(StatusCode::NOT_FOUND)
             .header("content-type", "application/json")
@@ -261,3 +387,59 @@ impl RequestHandler {
             .build()
     }
 }
+
+fn json_response<T: serde::Serialize>(status: StatusCode, response: &ApiResponse<T>) -> Response {
+    let body = serde_json::to_string(response).unwrap_or_else(|_| {
+        r#"{"success":false,"message":"Failed to serialize response"}"#.to_string()
+    });
+
+    ResponseBuilder::new(status)
+        .header("content-type", "application/json")
+        .header("Access-Control-Allow-Origin", "*")
+        .body(body)
+        .build()
+}
+
+/// Language for country names: `?lang=` first, then the browser's preferred
+/// `Accept-Language`, then English.
+fn request_language(req: &Request<Vec<u8>>) -> &'static str {
+    let requested = query_param(req, "lang").or_else(|| {
+        req.headers()
+            .get("accept-language")
+            .and_then(|h| h.to_str().ok())
+            .and_then(|h| h.split([',', ';']).next())
+            .map(|lang| lang.trim().to_string())
+    });
+    countries::resolve_language(requested.as_deref())
+}
+
+fn query_param(req: &Request<Vec<u8>>, name: &str) -> Option<String> {
+    req.uri().query()?.split('&').find_map(|pair| {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        (key == name).then(|| percent_decode(value))
+    })
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+                match hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                    Some(b) => {
+                        decoded.push(b);
+                        i += 2;
+                    }
+                    None => decoded.push(b'%'),
+                }
+            }
+            b => decoded.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
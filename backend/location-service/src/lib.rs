@@ -2,20 +2,35 @@
 #![warn(clippy::all, clippy::pedantic, clippy::nursery)]
 #![allow(
     clippy::module_name_repetitions,
+    clippy::must_use_candidate,
     clippy::missing_errors_doc,
-    clippy::missing_panics_doc
+    clippy::missing_panics_doc,
+    // Spin's http component executor is not Send; allow this lint for WASM components.
+    clippy::future_not_send,
+    // Raised inside the bindings `http_component` generates.
+    clippy::same_length_and_capacity
 )]
 
+mod countries;
 mod handlers;
 mod models;
+mod municipalities;
+mod phones;
 mod service;
 
 pub use handlers::RequestHandler;
-pub use models::{ApiResponse, CacheConfig, CacheEntry, CountryData, LocationServiceError};
+pub use models::{
+    AddressIssue, AddressValidationRequest, AddressValidationResult, ApiResponse, CacheConfig,
+    CacheEntry, CountryData, CountryResponse, LocationServiceError, PhoneValidationRequest,
+    PhoneValidationResult,
+};
+pub use municipalities::{AddressError, Municipality, PostalCodeLookup, Province};
+pub use phones::{PhoneError, PhoneNumber, PhoneNumberType};
 pub use service::{CountryCache, CountryService};
 
 use anyhow::Result;
-use spin_sdk::http::{Request, Response};
+use http::Request;
+use spin_sdk::http::Response;
 use spin_sdk::http_component;
 
 static REQUEST_HANDLER: std::sync::OnceLock<RequestHandler> = std::sync::OnceLock::new();
@@ -28,14 +43,14 @@ async fn handle_request(req: Request<Vec<u8>>) -> Result<Response> {
         RequestHandler::new()
     });
 
-    let response = handler.handle_request(req).await?;
-    Ok(response.into_response())
+    handler.handle_request(req).await
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use http::{Method, Request, StatusCode};
+    use std::sync::Arc;
     use std::time::Duration;
 
     #[tokio::test]
@@ -51,7 +66,7 @@ mod tests {
             service: Arc::new(tokio::sync::Mutex::new(CountryService::with_memory_cache(
                 Some(CacheConfig {
                     enabled: true,
-                    ttl: Duration::from_secs(60),
+                    ttl: Duration::from_mins(1),
                 }),
             ))),
         };
@@ -64,7 +79,7 @@ mod tests {
             .unwrap();
 
         let response = handler.handle_request(request).await.unwrap();
-        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(*response.status(), StatusCode::OK);
 
         // Test GET /api/countries (list countries)
         let request = Request::builder()
@@ -74,7 +89,7 @@ mod tests {
             .unwrap();
 
         let response = handler.handle_request(request).await.unwrap();
-        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(*response.status(), StatusCode::OK);
 
         // Test DELETE /api/countries/cache
         let request = Request::builder()
@@ -84,7 +99,7 @@ mod tests {
             .unwrap();
 
         let response = handler.handle_request(request).await.unwrap();
-        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(*response.status(), StatusCode::OK);
 
         // Test OPTIONS (CORS preflight)
         let request = Request::builder()
@@ -94,7 +109,7 @@ mod tests {
             .unwrap();
 
         let response = handler.handle_request(request).await.unwrap();
-        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(*response.status(), StatusCode::OK);
 
         // Test 404
         let request = Request::builder()
@@ -104,6 +119,6 @@ mod tests {
             .unwrap();
 
         let response = handler.handle_request(request).await.unwrap();
-        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(*response.status(), StatusCode::NOT_FOUND);
     }
 }
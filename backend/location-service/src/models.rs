@@ -2,6 +2,9 @@ use serde::{Deserialize, Serialize};
 use std::time::Duration;
 use thiserror::Error;
 
+use crate::municipalities::{AddressCheck, AddressError, Municipality, Province};
+use crate::phones::PhoneNumber;
+
 #[derive(Error, Debug)]
 pub enum LocationServiceError {
     #[error("Redis error: {0}")]
@@ -14,21 +17,39 @@ pub enum LocationServiceError {
     NotFound(String),
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
 pub struct CountryData {
+    /// ISO 3166-1 alpha-2.
     pub code: String,
+    /// ISO 3166-1 alpha-3. This is the code the Ministry's SES.HOSPEDAJES
+    /// traveller report expects for `nacionalidad` and `pais`.
+    pub alpha3: String,
+    /// ISO 3166-1 numeric, zero-padded.
+    pub numeric: String,
+    /// Name in the requested language.
     pub name: String,
     pub flag: Option<String>,
+    /// Dialling prefix, e.g. `+34`; `None` for uninhabited territories.
     pub phone_prefix: Option<String>,
+    /// UN M49 continent, with the Caribbean and Central America in North America.
     pub continent: Option<String>,
     pub capital: Option<String>,
+    /// ISO 4217 code.
     pub currency: Option<String>,
+    /// Official languages, by English name.
     pub languages: Vec<String>,
+    /// Three-digit code from INE's country table, used in the padrón and
+    /// other INE statistics; `None` until the table has been imported.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ine_code: Option<String>,
+    /// Same as `phone_prefix`; kept for clients of the older response.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub calling_code: Option<String>,
+    pub eu_member: bool,
+    pub schengen: bool,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct CountryResponse {
     pub country: String,
     pub country_code: String,
@@ -36,7 +57,79 @@ pub struct CountryResponse {
     pub flag: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PhoneValidationRequest {
+    pub phone: String,
+    /// Alpha-2 or alpha-3 of the guest's country; needed for national-format numbers.
+    #[serde(default)]
+    pub country: Option<String>,
+}
+
+/// An invalid number is a normal outcome, not a failed request, so it is
+/// reported with `valid: false` and a machine-readable `error` code.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PhoneValidationResult {
+    pub valid: bool,
+    #[serde(flatten)]
+    pub number: Option<PhoneNumber>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AddressValidationRequest {
+    pub postal_code: String,
+    /// Province name or two-digit INE code.
+    #[serde(default)]
+    pub province: Option<String>,
+    /// Five-digit INE municipality code, or six with the control digit.
+    #[serde(default)]
+    pub municipality_code: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct AddressIssue {
+    pub field: &'static str,
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl From<&AddressError> for AddressIssue {
+    fn from(error: &AddressError) -> Self {
+        Self {
+            field: error.field(),
+            code: error.code(),
+            message: error.to_string(),
+        }
+    }
+}
+
+/// Like phone validation, an inconsistent address is reported with
+/// `valid: false` rather than as a failed request.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct AddressValidationResult {
+    pub valid: bool,
+    pub province: Option<&'static Province>,
+    pub municipality: Option<&'static Municipality>,
+    pub errors: Vec<AddressIssue>,
+    pub warnings: Vec<AddressIssue>,
+}
+
+impl From<AddressCheck> for AddressValidationResult {
+    fn from(check: AddressCheck) -> Self {
+        Self {
+            valid: check.is_valid(),
+            province: check.province,
+            municipality: check.municipality,
+            errors: check.errors.iter().map(AddressIssue::from).collect(),
+            warnings: check.warnings.iter().map(AddressIssue::from).collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct CacheEntry {
     pub data: CountryData,
     pub timestamp: u64,
@@ -50,7 +143,7 @@ pub struct ApiResponse<T> {
 }
 
 impl<T> ApiResponse<T> {
-    pub fn success(data: T) -> Self {
+    pub const fn success(data: T) -> Self {
         Self {
             success: true,
             data: Some(data),
@@ -58,7 +151,7 @@ impl<T> ApiResponse<T> {
         }
     }
 
-    pub fn error(message: String) -> Self {
+    pub const fn error(message: String) -> Self {
         Self {
             success: false,
             data: None,
@@ -77,7 +170,7 @@ impl Default for CacheConfig {
     fn default() -> Self {
         Self {
             enabled: true,
-            ttl: Duration::from_secs(3600), // 1 hour default
+            ttl: Duration::from_hours(1), // 1 hour default
         }
     }
 }
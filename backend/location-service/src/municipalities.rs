@@ -0,0 +1,442 @@
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+use thiserror::Error;
+
+use crate::countries::fold;
+
+const PROVINCES: &str = include_str!("../data/provinces.json");
+/// INE municipality codes with their postal codes. Regenerate the full list
+/// with `scripts/import-ine-municipalities.py`.
+const MUNICIPALITIES: &str = include_str!("../data/municipalities.json");
+
+/// INE's weight tables for the municipality control digit.
+const CONTROL_DIGIT_TABLES: [[u32; 10]; 3] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9],
+    [0, 3, 8, 2, 7, 4, 1, 5, 9, 6],
+    [0, 2, 4, 6, 8, 1, 3, 5, 7, 9],
+];
+/// Which table applies to each digit of the five-digit code.
+const CONTROL_DIGIT_WEIGHTS: [usize; 5] = [2, 1, 0, 2, 1];
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Province {
+    /// Two-digit INE code; also the first two digits of its postal codes.
+    pub code: String,
+    pub name: String,
+    /// Autonomous community.
+    pub community: String,
+    /// Castilian, co-official and older spellings accepted on input.
+    #[serde(default, skip_serializing)]
+    aliases: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct ProvinceDataset {
+    provinces: Vec<Province>,
+}
+
+#[derive(Deserialize)]
+struct MunicipalityRecord {
+    code: String,
+    name: String,
+    #[serde(default)]
+    postal_codes: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct MunicipalityDataset {
+    municipalities: Vec<MunicipalityRecord>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Municipality {
+    /// Five-digit INE code (province + municipality), as stored in
+    /// `pilgrims.address_municipality_code` and sent to the traveller registry.
+    pub code: String,
+    /// Six-digit form with INE's control digit.
+    pub full_code: String,
+    pub name: String,
+    pub province_code: String,
+    pub postal_codes: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct PostalCodeLookup {
+    pub postal_code: String,
+    pub province: &'static Province,
+    /// Every municipality the postal code is known to cover; a postal code can
+    /// span several small municipalities.
+    pub municipalities: Vec<&'static Municipality>,
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum AddressError {
+    #[error("Postal code must be five digits")]
+    InvalidPostalCode,
+    #[error("No Spanish province uses postal codes starting with {0}")]
+    UnknownPostalArea(String),
+    #[error("Unknown province: {0}")]
+    UnknownProvince(String),
+    #[error("Municipality code must be five digits, or six with the control digit")]
+    InvalidMunicipalityCode,
+    #[error("Control digit does not match municipality code {0}")]
+    ControlDigitMismatch(String),
+    #[error("Municipality {0} is not in the bundled INE dataset")]
+    UnknownMunicipality(String),
+    #[error("Province {province} does not match postal code {postal_code}")]
+    ProvinceMismatch {
+        province: String,
+        postal_code: String,
+    },
+    #[error("Municipality {municipality} is not in province {province}")]
+    MunicipalityProvinceMismatch {
+        municipality: String,
+        province: String,
+    },
+    #[error("Postal code {postal_code} does not belong to municipality {municipality}")]
+    PostalCodeMismatch {
+        postal_code: String,
+        municipality: String,
+    },
+}
+
+impl AddressError {
+    pub const fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidPostalCode => "invalid_postal_code",
+            Self::UnknownPostalArea(_) => "unknown_postal_area",
+            Self::UnknownProvince(_) => "unknown_province",
+            Self::InvalidMunicipalityCode => "invalid_municipality_code",
+            Self::ControlDigitMismatch(_) => "control_digit_mismatch",
+            Self::UnknownMunicipality(_) => "unknown_municipality",
+            Self::ProvinceMismatch { .. } => "province_mismatch",
+            Self::MunicipalityProvinceMismatch { .. } => "municipality_province_mismatch",
+            Self::PostalCodeMismatch { .. } => "postal_code_mismatch",
+        }
+    }
+
+    /// The address field the problem should be shown against.
+    pub const fn field(&self) -> &'static str {
+        match self {
+            Self::InvalidPostalCode | Self::UnknownPostalArea(_) => "postal_code",
+            Self::UnknownProvince(_) | Self::ProvinceMismatch { .. } => "province",
+            Self::InvalidMunicipalityCode
+            | Self::ControlDigitMismatch(_)
+            | Self::UnknownMunicipality(_)
+            | Self::MunicipalityProvinceMismatch { .. }
+            | Self::PostalCodeMismatch { .. } => "municipality_code",
+        }
+    }
+}
+
+/// Outcome of checking a Spanish address. Errors are inconsistencies;
+/// warnings are things the bundled data can't confirm either way.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AddressCheck {
+    pub province: Option<&'static Province>,
+    pub municipality: Option<&'static Municipality>,
+    pub errors: Vec<AddressError>,
+    pub warnings: Vec<AddressError>,
+}
+
+impl AddressCheck {
+    pub const fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+pub fn provinces() -> &'static [Province] {
+    static ALL: OnceLock<Vec<Province>> = OnceLock::new();
+    ALL.get_or_init(|| {
+        serde_json::from_str::<ProvinceDataset>(PROVINCES).map_or_else(
+            |e| {
+                log::error!("Bundled province dataset is invalid: {e}");
+                Vec::new()
+            },
+            |d| d.provinces,
+        )
+    })
+}
+
+pub fn municipalities() -> &'static [Municipality] {
+    static ALL: OnceLock<Vec<Municipality>> = OnceLock::new();
+    ALL.get_or_init(|| {
+        serde_json::from_str::<MunicipalityDataset>(MUNICIPALITIES).map_or_else(
+            |e| {
+                log::error!("Bundled municipality dataset is invalid: {e}");
+                Vec::new()
+            },
+            |d| {
+                d.municipalities
+                    .into_iter()
+                    .map(|m| Municipality {
+                        full_code: format!("{}{}", m.code, control_digit(&m.code)),
+                        province_code: m.code[..2].to_string(),
+                        code: m.code,
+                        name: m.name,
+                        postal_codes: m.postal_codes,
+                    })
+                    .collect()
+            },
+        )
+    })
+}
+
+/// Find a province by INE code ("6" or "06") or by name, ignoring case and
+/// accents and accepting co-official spellings ("Ourense", "Orense").
+pub fn find_province(input: &str) -> Option<&'static Province> {
+    let input = input.trim();
+    if !input.is_empty() && input.len() <= 2 && input.bytes().all(|b| b.is_ascii_digit()) {
+        let code = format!("{input:0>2}");
+        return provinces().iter().find(|p| p.code == code);
+    }
+
+    let folded = fold(input);
+    provinces().iter().find(|p| {
+        fold(&p.name) == folded
+            || p.name.split('/').any(|n| fold(n) == folded)
+            || p.aliases.iter().any(|a| fold(a) == folded)
+    })
+}
+
+/// Province a postal code belongs to, from its first two digits.
+pub fn province_for_postal_code(postal_code: &str) -> Result<&'static Province, AddressError> {
+    let postal_code = normalize_postal_code(postal_code)?;
+    let prefix = &postal_code[..2];
+    provinces()
+        .iter()
+        .find(|p| p.code == prefix)
+        .ok_or_else(|| AddressError::UnknownPostalArea(prefix.to_string()))
+}
+
+pub fn lookup_postal_code(postal_code: &str) -> Result<PostalCodeLookup, AddressError> {
+    let postal_code = normalize_postal_code(postal_code)?;
+    let province = province_for_postal_code(&postal_code)?;
+    Ok(PostalCodeLookup {
+        municipalities: municipalities_for_postal_code(&postal_code),
+        postal_code,
+        province,
+    })
+}
+
+/// Look a municipality up by five-digit INE code, or six with control digit.
+pub fn find_municipality(code: &str) -> Result<Option<&'static Municipality>, AddressError> {
+    let code = normalize_municipality_code(code)?;
+    Ok(municipalities().iter().find(|m| m.code == code))
+}
+
+/// Check that a postal code, province and municipality code describe the same
+/// place. Province and municipality are optional since the form collects
+/// them only for Spanish residents.
+pub fn validate(
+    postal_code: &str,
+    province: Option<&str>,
+    municipality_code: Option<&str>,
+) -> AddressCheck {
+    let mut check = AddressCheck::default();
+
+    let postal_province = province_for_postal_code(postal_code)
+        .map_err(|e| check.errors.push(e))
+        .ok();
+
+    let given_province = province.filter(|p| !p.trim().is_empty()).and_then(|input| {
+        let found = find_province(input);
+        if found.is_none() {
+            check
+                .errors
+                .push(AddressError::UnknownProvince(input.trim().to_string()));
+        }
+        found
+    });
+    if let (Some(given), Some(expected)) = (given_province, postal_province) {
+        if given.code != expected.code {
+            check.errors.push(AddressError::ProvinceMismatch {
+                province: given.name.clone(),
+                postal_code: postal_code.trim().to_string(),
+            });
+        }
+    }
+    check.province = postal_province.or(given_province);
+
+    let Some(code) = municipality_code.filter(|c| !c.trim().is_empty()) else {
+        return check;
+    };
+    let code = match normalize_municipality_code(code) {
+        Ok(code) => code,
+        Err(e) => {
+            check.errors.push(e);
+            return check;
+        }
+    };
+
+    if let Some(province) = check.province {
+        if !code.starts_with(&province.code) {
+            check
+                .errors
+                .push(AddressError::MunicipalityProvinceMismatch {
+                    municipality: code,
+                    province: province.name.clone(),
+                });
+            return check;
+        }
+    }
+
+    let Some(municipality) = municipalities().iter().find(|m| m.code == code) else {
+        check.warnings.push(AddressError::UnknownMunicipality(code));
+        return check;
+    };
+    check.municipality = Some(municipality);
+
+    // Only a postal code the dataset assigns elsewhere is a mismatch; one it
+    // doesn't know about can't be judged.
+    if let Ok(postal_code) = normalize_postal_code(postal_code) {
+        let candidates = municipalities_for_postal_code(&postal_code);
+        if !candidates.is_empty() && !candidates.iter().any(|m| m.code == municipality.code) {
+            check.errors.push(AddressError::PostalCodeMismatch {
+                postal_code,
+                municipality: municipality.name.clone(),
+            });
+        }
+    }
+    check
+}
+
+/// INE control digit for a five-digit municipality code.
+pub fn control_digit(code: &str) -> u32 {
+    let sum: u32 = code
+        .chars()
+        .filter_map(|c| c.to_digit(10))
+        .zip(CONTROL_DIGIT_WEIGHTS)
+        .map(|(digit, table)| CONTROL_DIGIT_TABLES[table][digit as usize])
+        .sum();
+    (10 - sum % 10) % 10
+}
+
+fn municipalities_for_postal_code(postal_code: &str) -> Vec<&'static Municipality> {
+    municipalities()
+        .iter()
+        .filter(|m| m.postal_codes.iter().any(|p| p == postal_code))
+        .collect()
+}
+
+/// Accepts "06800" and "06 800"; leading zeros are often lost in spreadsheets,
+/// so four digits are padded back.
+fn normalize_postal_code(postal_code: &str) -> Result<String, AddressError> {
+    let digits: String = postal_code.chars().filter(|c| !c.is_whitespace()).collect();
+    if !(4..=5).contains(&digits.len()) || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(AddressError::InvalidPostalCode);
+    }
+    Ok(format!("{digits:0>5}"))
+}
+
+fn normalize_municipality_code(code: &str) -> Result<String, AddressError> {
+    let code = code.trim();
+    if !code.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(AddressError::InvalidMunicipalityCode);
+    }
+    match code.len() {
+        5 => Ok(code.to_string()),
+        6 => {
+            let (base, dc) = code.split_at(5);
+            if dc.parse::<u32>().ok() == Some(control_digit(base)) {
+                Ok(base.to_string())
+            } else {
+                Err(AddressError::ControlDigitMismatch(base.to_string()))
+            }
+        }
+        _ => Err(AddressError::InvalidMunicipalityCode),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_datasets_load() {
+        assert_eq!(provinces().len(), 52);
+        assert!(municipalities()
+            .iter()
+            .all(|m| provinces().iter().any(|p| p.code == m.province_code)));
+    }
+
+    #[test]
+    fn test_control_digit() {
+        assert_eq!(control_digit("28079"), 6);
+        assert_eq!(control_digit("08019"), 3);
+        assert_eq!(control_digit("41091"), 7);
+        assert_eq!(find_municipality("280796").unwrap().unwrap().name, "Madrid");
+        assert_eq!(
+            find_municipality("280791"),
+            Err(AddressError::ControlDigitMismatch("28079".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_find_province_by_code_and_name() {
+        assert_eq!(find_province("6").unwrap().name, "Badajoz");
+        assert_eq!(find_province("caceres").unwrap().code, "10");
+        assert_eq!(find_province("Orense").unwrap().code, "32");
+        assert_eq!(find_province("València").unwrap().code, "46");
+        assert!(find_province("Atlantis").is_none());
+    }
+
+    #[test]
+    fn test_lookup_postal_code() {
+        let lookup = lookup_postal_code("06800").unwrap();
+        assert_eq!(lookup.province.name, "Badajoz");
+        assert_eq!(lookup.municipalities[0].name, "Mérida");
+
+        let lookup = lookup_postal_code("6300").unwrap();
+        assert_eq!(lookup.postal_code, "06300");
+
+        assert_eq!(
+            lookup_postal_code("53000").unwrap_err(),
+            AddressError::UnknownPostalArea("53".to_string())
+        );
+        assert_eq!(
+            lookup_postal_code("ABCDE").unwrap_err(),
+            AddressError::InvalidPostalCode
+        );
+    }
+
+    #[test]
+    fn test_validate_consistent_address() {
+        let check = validate("06800", Some("Badajoz"), Some("06083"));
+        assert!(check.is_valid(), "{:?}", check.errors);
+        assert_eq!(check.municipality.unwrap().name, "Mérida");
+
+        let check = validate("28013", None, None);
+        assert!(check.is_valid());
+        assert_eq!(check.province.unwrap().code, "28");
+    }
+
+    #[test]
+    fn test_validate_inconsistent_address() {
+        let check = validate("06800", Some("Cáceres"), Some("10037"));
+        let codes: Vec<_> = check.errors.iter().map(AddressError::code).collect();
+        assert_eq!(
+            codes,
+            ["province_mismatch", "municipality_province_mismatch"]
+        );
+
+        let check = validate("06800", None, Some("06015"));
+        assert_eq!(check.errors[0].code(), "postal_code_mismatch");
+
+        let check = validate("06820", None, Some("06099"));
+        assert!(check.is_valid());
+        assert_eq!(check.warnings[0].code(), "unknown_municipality");
+    }
+
+    /// Holds once `scripts/import-ine-municipalities.py` has been run on the
+    /// official INE and postal-code files; the bundled list is still partial.
+    #[test]
+    #[ignore = "needs the full INE import"]
+    fn test_full_ine_dataset() {
+        assert!(municipalities().len() > 8000);
+        assert!(municipalities().iter().all(|m| !m.postal_codes.is_empty()));
+        assert!(municipalities()
+            .iter()
+            .any(|m| m.province_code == "06" && fold(&m.name).contains("carrascalejo")));
+    }
+}
@@ -0,0 +1,399 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use thiserror::Error;
+
+use crate::countries;
+
+/// Per-region numbering plans from libphonenumber's metadata. Calling codes are
+/// not repeated here; they come from the country dataset.
+const NUMBERING: &str = include_str!("../data/phone_numbering.json");
+
+/// E.164 caps the whole number, calling code included, at 15 digits.
+const MAX_E164_DIGITS: usize = 15;
+/// Prefix used to dial out of most countries; accepted everywhere since
+/// pilgrims often type numbers the way they dial them at home.
+const COMMON_INTERNATIONAL_PREFIX: &str = "00";
+
+#[derive(Deserialize, Debug)]
+struct NumberingPlan {
+    general: String,
+    lengths: Vec<usize>,
+    #[serde(default)]
+    international_prefix: Option<String>,
+    #[serde(default)]
+    national_prefix: Option<String>,
+    /// For regions sharing a calling code, the leading digits that pick them out.
+    #[serde(default)]
+    leading_digits: Option<String>,
+    /// The region a shared calling code defaults to (US for +1, GB for +44).
+    #[serde(default)]
+    main: bool,
+    #[serde(default)]
+    fixed_line: Option<String>,
+    #[serde(default)]
+    mobile: Option<String>,
+    #[serde(default)]
+    other: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Numbering {
+    regions: HashMap<String, NumberingPlan>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PhoneNumberType {
+    Mobile,
+    Landline,
+    /// The numbering plan doesn't distinguish, as in the US and Canada.
+    FixedOrMobile,
+    /// Toll-free, premium-rate, internet telephony, shared-cost and similar services.
+    Other,
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum PhoneError {
+    #[error("Phone number is empty")]
+    Empty,
+    #[error("Phone number contains invalid characters")]
+    InvalidCharacters,
+    #[error("A country is required for numbers in national format")]
+    MissingCountry,
+    #[error("Unknown country: {0}")]
+    UnknownCountry(String),
+    #[error("No numbering plan for {0}")]
+    NoNumberingPlan(String),
+    #[error("Unknown international calling code")]
+    UnknownCallingCode,
+    #[error("Phone number is too short for {0}")]
+    TooShort(String),
+    #[error("Phone number is too long for {0}")]
+    TooLong(String),
+    #[error("Phone number is not valid in {0}")]
+    InvalidNumber(String),
+}
+
+impl PhoneError {
+    /// Stable machine-readable code for API clients.
+    pub const fn code(&self) -> &'static str {
+        match self {
+            Self::Empty => "empty",
+            Self::InvalidCharacters => "invalid_characters",
+            Self::MissingCountry => "missing_country",
+            Self::UnknownCountry(_) => "unknown_country",
+            Self::NoNumberingPlan(_) => "no_numbering_plan",
+            Self::UnknownCallingCode => "unknown_calling_code",
+            Self::TooShort(_) => "too_short",
+            Self::TooLong(_) => "too_long",
+            Self::InvalidNumber(_) => "invalid_number",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PhoneNumber {
+    /// `+34612345678`
+    pub e164: String,
+    /// ISO 3166-1 alpha-2 of the region the number belongs to.
+    pub country: String,
+    /// `+34`
+    pub calling_code: String,
+    pub national_number: String,
+    pub number_type: PhoneNumberType,
+}
+
+fn plans() -> &'static HashMap<String, NumberingPlan> {
+    static PLANS: OnceLock<HashMap<String, NumberingPlan>> = OnceLock::new();
+    PLANS.get_or_init(|| {
+        serde_json::from_str::<Numbering>(NUMBERING).map_or_else(
+            |e| {
+                log::error!("Bundled phone numbering data is invalid: {e}");
+                HashMap::new()
+            },
+            |n| n.regions,
+        )
+    })
+}
+
+/// Parse a phone number typed in national or international form.
+///
+/// `country` (any ISO 3166-1 code) is required for national numbers and is
+/// used to recognise that country's own international dialling prefix.
+pub fn parse(input: &str, country: Option<&str>) -> Result<PhoneNumber, PhoneError> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err(PhoneError::Empty);
+    }
+
+    let (plus, digits) = extract_digits(input)?;
+    let default_region = country
+        .map(|c| countries::find(c).ok_or_else(|| PhoneError::UnknownCountry(c.to_string())))
+        .transpose()?;
+
+    let international = if plus {
+        Some(digits.as_str())
+    } else {
+        let own_prefix = default_region
+            .and_then(|c| plans().get(&c.alpha2))
+            .and_then(|p| p.international_prefix.as_deref());
+        [own_prefix, Some(COMMON_INTERNATIONAL_PREFIX)]
+            .into_iter()
+            .flatten()
+            .find_map(|prefix| digits.strip_prefix(prefix))
+    };
+
+    if let Some(rest) = international {
+        return parse_international(rest);
+    }
+    let region = default_region.ok_or(PhoneError::MissingCountry)?;
+    parse_national(&digits, &region.alpha2)
+}
+
+fn parse_international(digits: &str) -> Result<PhoneNumber, PhoneError> {
+    // Calling codes are prefix-free, so the first length that names one is it.
+    for len in 1..=3 {
+        let Some(code) = digits.get(..len) else {
+            break;
+        };
+        let calling_code = format!("+{code}");
+        let regions: Vec<&str> = countries::all()
+            .iter()
+            .filter(|c| c.calling_code.as_deref() == Some(calling_code.as_str()))
+            .map(|c| c.alpha2.as_str())
+            .filter(|r| plans().contains_key(*r))
+            .collect();
+        if regions.is_empty() {
+            continue;
+        }
+
+        let national = &digits[len..];
+        if let Some(region) = select_region(&regions, national) {
+            return validate(national, region);
+        }
+
+        // No region accepted it; report why against the code's main region.
+        let main = regions
+            .iter()
+            .copied()
+            .find(|r| plans()[*r].main)
+            .unwrap_or(regions[0]);
+        return Err(validate(national, main)
+            .err()
+            .unwrap_or_else(|| PhoneError::InvalidNumber(main.to_string())));
+    }
+    Err(PhoneError::UnknownCallingCode)
+}
+
+/// Among regions sharing a calling code, the one whose plan accepts the number.
+fn select_region<'a>(regions: &[&'a str], national: &str) -> Option<&'a str> {
+    if regions.len() == 1 {
+        return Some(regions[0]);
+    }
+    let mut candidates: Vec<&str> = regions
+        .iter()
+        .copied()
+        .filter(|r| validate(national, r).is_ok())
+        .collect();
+    // Prefer regions identified by their leading digits (Guernsey within +44),
+    // then the main region for the code.
+    candidates.sort_by_key(|r| {
+        let plan = &plans()[*r];
+        let by_leading_digits = plan
+            .leading_digits
+            .as_deref()
+            .is_some_and(|ld| full_match(&format!("(?:{ld}).*"), national));
+        (!by_leading_digits, !plan.main)
+    });
+    candidates.first().copied()
+}
+
+fn parse_national(digits: &str, region: &str) -> Result<PhoneNumber, PhoneError> {
+    let plan = plans()
+        .get(region)
+        .ok_or_else(|| PhoneError::NoNumberingPlan(region.to_string()))?;
+
+    // Try without the trunk prefix first ("0" in France), falling back to the
+    // digits as typed for countries whose numbers legitimately start with it.
+    if let Some(stripped) = plan
+        .national_prefix
+        .as_deref()
+        .and_then(|p| digits.strip_prefix(p))
+    {
+        if let Ok(number) = validate(stripped, region) {
+            return Ok(number);
+        }
+    }
+    validate(digits, region)
+}
+
+fn validate(national: &str, region: &str) -> Result<PhoneNumber, PhoneError> {
+    let plan = plans()
+        .get(region)
+        .ok_or_else(|| PhoneError::NoNumberingPlan(region.to_string()))?;
+    let calling_code = countries::find(region)
+        .and_then(|c| c.calling_code.clone())
+        .ok_or_else(|| PhoneError::NoNumberingPlan(region.to_string()))?;
+
+    let len = national.len();
+    let (min, max) = (
+        plan.lengths.iter().copied().min().unwrap_or(0),
+        plan.lengths
+            .iter()
+            .copied()
+            .max()
+            .unwrap_or(MAX_E164_DIGITS),
+    );
+    if len < min {
+        return Err(PhoneError::TooShort(region.to_string()));
+    }
+    if len > max || calling_code.len() - 1 + len > MAX_E164_DIGITS {
+        return Err(PhoneError::TooLong(region.to_string()));
+    }
+    if !plan.lengths.is_empty() && !plan.lengths.contains(&len) {
+        return Err(PhoneError::InvalidNumber(region.to_string()));
+    }
+    if !full_match(&plan.general, national) {
+        return Err(PhoneError::InvalidNumber(region.to_string()));
+    }
+
+    let matches = |pattern: &Option<String>| {
+        pattern
+            .as_deref()
+            .is_some_and(|pattern| full_match(pattern, national))
+    };
+    let number_type = match (matches(&plan.mobile), matches(&plan.fixed_line)) {
+        (true, true) => PhoneNumberType::FixedOrMobile,
+        (true, false) => PhoneNumberType::Mobile,
+        (false, true) => PhoneNumberType::Landline,
+        (false, false) if matches(&plan.other) => PhoneNumberType::Other,
+        (false, false) => return Err(PhoneError::InvalidNumber(region.to_string())),
+    };
+
+    Ok(PhoneNumber {
+        e164: format!("{calling_code}{national}"),
+        country: region.to_string(),
+        national_number: national.to_string(),
+        calling_code,
+        number_type,
+    })
+}
+
+/// Anchored match, compiling each pattern once; resolving a shared calling
+/// code like +1 tries the plans of two dozen regions.
+fn full_match(pattern: &str, text: &str) -> bool {
+    static COMPILED: OnceLock<Mutex<HashMap<String, Option<Regex>>>> = OnceLock::new();
+    let mut compiled = COMPILED
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    compiled
+        .entry(pattern.to_string())
+        .or_insert_with(|| Regex::new(&format!("^(?:{pattern})$")).ok())
+        .as_ref()
+        .is_some_and(|re| re.is_match(text))
+}
+
+/// Strip formatting, keeping digits and noting a leading `+`.
+fn extract_digits(input: &str) -> Result<(bool, String), PhoneError> {
+    let plus = input.starts_with('+');
+    let rest = input.strip_prefix('+').unwrap_or(input);
+    let mut digits = String::with_capacity(rest.len());
+    for c in rest.chars() {
+        match c {
+            '0'..='9' => digits.push(c),
+            ' ' | '-' | '.' | '(' | ')' | '/' | '\u{a0}' => {}
+            _ => return Err(PhoneError::InvalidCharacters),
+        }
+    }
+    if digits.is_empty() {
+        return Err(PhoneError::Empty);
+    }
+    Ok((plus, digits))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spanish_mobile_national_and_international() {
+        let national = parse("612 34 56 78", Some("ES")).unwrap();
+        assert_eq!(national.e164, "+34612345678");
+        assert_eq!(national.number_type, PhoneNumberType::Mobile);
+
+        for input in ["+34 612 345 678", "0034612345678", "+34-612-345-678"] {
+            assert_eq!(parse(input, None).unwrap(), national, "{input}");
+        }
+    }
+
+    #[test]
+    fn test_spanish_landline() {
+        let number = parse("924 12 34 56", Some("ESP")).unwrap();
+        assert_eq!(number.e164, "+34924123456");
+        assert_eq!(number.number_type, PhoneNumberType::Landline);
+    }
+
+    #[test]
+    fn test_national_prefix_is_stripped() {
+        let number = parse("06 12 34 56 78", Some("FR")).unwrap();
+        assert_eq!(number.e164, "+33612345678");
+        assert_eq!(number.country, "FR");
+        assert_eq!(number.number_type, PhoneNumberType::Mobile);
+
+        let number = parse("07400 123456", Some("GB")).unwrap();
+        assert_eq!(number.e164, "+447400123456");
+    }
+
+    #[test]
+    fn test_italian_leading_zero_is_kept() {
+        let number = parse("06 1234 5678", Some("IT")).unwrap();
+        assert_eq!(number.e164, "+390612345678");
+        assert_eq!(number.number_type, PhoneNumberType::Landline);
+    }
+
+    #[test]
+    fn test_shared_calling_code_picks_region() {
+        let us = parse("+1 201 555 0123", None).unwrap();
+        assert_eq!(us.country, "US");
+        assert_eq!(us.number_type, PhoneNumberType::FixedOrMobile);
+
+        let ca = parse("+1 604 555 0123", None).unwrap();
+        assert_eq!(ca.country, "CA");
+
+        let us_dialled = parse("011 34 612 345 678", Some("US")).unwrap();
+        assert_eq!(us_dialled.e164, "+34612345678");
+    }
+
+    #[test]
+    fn test_invalid_numbers() {
+        assert_eq!(parse("", Some("ES")), Err(PhoneError::Empty));
+        assert_eq!(
+            parse("612abc", Some("ES")),
+            Err(PhoneError::InvalidCharacters)
+        );
+        assert_eq!(parse("612345678", None), Err(PhoneError::MissingCountry));
+        assert_eq!(
+            parse("61234", Some("ES")),
+            Err(PhoneError::TooShort("ES".to_string()))
+        );
+        assert_eq!(
+            parse("6123456789", Some("ES")),
+            Err(PhoneError::TooLong("ES".to_string()))
+        );
+        assert_eq!(
+            parse("112345678", Some("ES")),
+            Err(PhoneError::InvalidNumber("ES".to_string()))
+        );
+        assert_eq!(
+            parse("612345678", Some("XX")),
+            Err(PhoneError::UnknownCountry("XX".to_string()))
+        );
+        assert!(matches!(
+            parse("+999 1234", None),
+            Err(PhoneError::UnknownCallingCode)
+        ));
+    }
+}
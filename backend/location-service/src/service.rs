@@ -1,35 +1,40 @@
 use std::collections::HashMap;
-use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use redis_service::{RedisService, RedisServiceError};
+use spin_sdk::redis::{Connection, RedisParameter};
 
+use crate::countries::{self, DEFAULT_LANGUAGE, SUPPORTED_LANGUAGES};
 use crate::models::{CacheConfig, CacheEntry, CountryData, LocationServiceError};
 
 #[derive(Clone)]
 pub struct CountryCache {
-    redis: Arc<RedisService>,
+    address: String,
     cache_ttl: Duration,
 }
 
 impl CountryCache {
-    pub fn new(redis: RedisService, cache_ttl: Duration) -> Self {
+    pub fn new(address: &str, cache_ttl: Duration) -> Self {
         Self {
-            redis: Arc::new(redis),
+            address: address.to_string(),
             cache_ttl,
         }
     }
 
-    pub async fn get_country(
+    fn connection(&self) -> Result<Connection, LocationServiceError> {
+        Connection::open(&self.address)
+            .map_err(|e| LocationServiceError::Redis(format!("Failed to connect: {e:?}")))
+    }
+
+    pub fn get_country(
         &self,
         country_code: &str,
     ) -> Result<Option<CountryData>, LocationServiceError> {
-        let key = format!("country:{}", country_code);
+        let key = format!("country:{country_code}");
 
-        match self.redis.get(&key).await {
+        match self.connection()?.get(&key) {
             Ok(Some(data)) => {
-                let entry: CacheEntry = serde_json::from_str(&data).map_err(|e| {
-                    LocationServiceError::Cache(format!("Failed to deserialize cache entry: {}", e))
+                let entry: CacheEntry = serde_json::from_slice(&data).map_err(|e| {
+                    LocationServiceError::Cache(format!("Failed to deserialize cache entry: {e}"))
                 })?;
 
                 let now = SystemTime::now().duration_since(UNIX_EPOCH).map_err(|_| {
@@ -42,7 +47,7 @@ impl CountryCache {
                 // Cache entry expired, fall through to return None
             }
             Err(e) => {
-                log::warn!("Failed to get country from cache: {}", e);
+                log::warn!("Failed to get country from cache: {e:?}");
                 // Continue to return None on cache miss
             }
             _ => {}
@@ -51,12 +56,12 @@ impl CountryCache {
         Ok(None)
     }
 
-    pub async fn set_country(
+    pub fn set_country(
         &self,
         country_code: &str,
         country: &CountryData,
     ) -> Result<(), LocationServiceError> {
-        let key = format!("country:{}", country_code);
+        let key = format!("country:{country_code}");
         let entry = CacheEntry {
             data: country.clone(),
             timestamp: SystemTime::now()
@@ -67,39 +72,42 @@ impl CountryCache {
                 .as_secs(),
         };
 
-        let serialized = serde_json::to_string(&entry).map_err(|e| {
-            LocationServiceError::Cache(format!("Failed to serialize cache entry: {}", e))
+        let serialized = serde_json::to_vec(&entry).map_err(|e| {
+            LocationServiceError::Cache(format!("Failed to serialize cache entry: {e}"))
         })?;
 
-        self.redis
-            .set_with_ttl(&key, &serialized, self.cache_ttl.as_secs() as u64)
-            .await
-            .map_err(|e| LocationServiceError::Redis(e.to_string()))?;
+        self.connection()?
+            .execute(
+                "SET",
+                &[
+                    RedisParameter::Binary(key.into_bytes()),
+                    RedisParameter::Binary(serialized),
+                    RedisParameter::Binary(b"EX".to_vec()),
+                    RedisParameter::Binary(self.cache_ttl.as_secs().to_string().into_bytes()),
+                ],
+            )
+            .map_err(|e| LocationServiceError::Redis(format!("{e:?}")))?;
 
         Ok(())
     }
 
-    pub async fn clear_country_cache(
-        &self,
-        country_code: &str,
-    ) -> Result<(), LocationServiceError> {
-        let key = format!("country:{}", country_code);
-        self.redis
-            .delete_key(&key)
-            .await
-            .map_err(|e| LocationServiceError::Redis(e.to_string()))?;
+    pub fn clear_country_cache(&self, country_code: &str) -> Result<(), LocationServiceError> {
+        let key = format!("country:{country_code}");
+        self.connection()?
+            .del(&[key])
+            .map_err(|e| LocationServiceError::Redis(format!("{e:?}")))?;
         Ok(())
     }
 }
 
-pub struct LocationService {
+pub struct CountryService {
     memory_cache: HashMap<String, CacheEntry>,
     redis_cache: Option<CountryCache>,
     cache_config: CacheConfig,
 }
 
-impl LocationService {
-    /// Create a new LocationService with default configuration
+impl CountryService {
+    /// Create a new `CountryService` with default configuration
     pub fn new() -> Self {
         Self {
             memory_cache: HashMap::new(),
@@ -108,17 +116,22 @@ impl LocationService {
         }
     }
 
-    /// Create a new LocationService with Redis caching
-    pub fn with_redis(redis: RedisService, cache_config: Option<CacheConfig>) -> Self {
+    /// Create a new `CountryService` with Redis caching
+    pub fn with_redis(redis_url: &str, cache_config: Option<CacheConfig>) -> Self {
         let config = cache_config.unwrap_or_default();
         Self {
             memory_cache: HashMap::new(),
-            redis_cache: Some(CountryCache::new(redis, config.ttl)),
+            redis_cache: Some(CountryCache::new(redis_url, config.ttl)),
             cache_config: config,
         }
     }
 
-    /// Create a new LocationService with memory caching only
+    /// Create a new `CountryService` caching in memory for `ttl`
+    pub fn with_cache_ttl(ttl: Duration) -> Self {
+        Self::with_memory_cache(Some(CacheConfig { enabled: true, ttl }))
+    }
+
+    /// Create a new `CountryService` with memory caching only
     pub fn with_memory_cache(cache_config: Option<CacheConfig>) -> Self {
         let config = cache_config.unwrap_or_default();
         Self {
@@ -128,24 +141,25 @@ impl LocationService {
         }
     }
 
-    /// Get country data by country code
-    pub async fn get_country_data(
+    /// Get country data by alpha-2, alpha-3 or numeric code, named in `lang`
+    pub fn get_country_data(
         &mut self,
         code: &str,
+        lang: &str,
     ) -> Result<Option<CountryData>, LocationServiceError> {
-        let code = code.to_uppercase();
+        let key = cache_key(code, lang);
 
         // Check Redis cache first if enabled
         if let Some(redis_cache) = &self.redis_cache {
-            match redis_cache.get_country(&code).await {
+            match redis_cache.get_country(&key) {
                 Ok(Some(cached)) => return Ok(Some(cached)),
-                Err(e) => log::warn!("Redis cache error: {}", e),
+                Err(e) => log::warn!("Redis cache error: {e}"),
                 _ => {}
             }
         }
 
         // Check in-memory cache if Redis is not available or cache miss
-        if let Some(entry) = self.memory_cache.get(&code) {
+        if let Some(entry) = self.memory_cache.get(&key) {
             let now = SystemTime::now()
                 .duration_since(UNIX_EPOCH)
                 .map_err(|_| {
@@ -159,39 +173,35 @@ impl LocationService {
         }
 
         // Get country data from the data source
-        let country_data = self.get_country_from_source(&code).await?;
+        let Some(country_data) = Self::get_country_from_source(code, lang) else {
+            return Ok(None);
+        };
 
         // Update all caches with new data
-        self.update_caches(&code, &country_data).await?;
+        self.update_caches(&key, &country_data)?;
 
-        Ok(country_data)
+        Ok(Some(country_data))
     }
 
-    /// Get country data from the primary data source
-    async fn get_country_from_source(
-        &self,
-        code: &str,
-    ) -> Result<Option<CountryData>, LocationServiceError> {
-        // This is a simplified example - in a real application, you would fetch from a database or API
-        match code.as_str() {
-            "ES" => Ok(Some(CountryData {
-                code: "ES".to_string(),
-                name: "Spain".to_string(),
-                flag: Some("🇪🇸".to_string()),
-                phone_prefix: Some("+34".to_string()),
-                calling_code: Some("+34".to_string()),
-                continent: Some("Europe".to_string()),
-                capital: Some("Madrid".to_string()),
-                currency: Some("EUR".to_string()),
-                languages: vec!["Spanish".to_string()],
-            })),
-            // Add more countries as needed
-            _ => Ok(None),
-        }
+    /// Every country, ordered by name in `lang`, optionally filtered by a search query
+    pub fn list_countries(search: Option<&str>, lang: &str) -> Vec<CountryData> {
+        let countries = search.map_or_else(
+            || countries::sorted(lang),
+            |query| countries::search(query, lang),
+        );
+        countries
+            .into_iter()
+            .map(|c| c.to_country_data(lang))
+            .collect()
+    }
+
+    /// Get country data from the bundled ISO 3166-1 dataset
+    fn get_country_from_source(code: &str, lang: &str) -> Option<CountryData> {
+        countries::find(code).map(|c| c.to_country_data(lang))
     }
 
     /// Update all caches with new data
-    async fn update_caches(
+    fn update_caches(
         &mut self,
         code: &str,
         data: &CountryData,
@@ -209,12 +219,12 @@ impl LocationService {
         };
 
         // Update in-memory cache
-        self.memory_cache.insert(code.to_string(), entry.clone());
+        self.memory_cache.insert(code.to_string(), entry);
 
         // Update Redis cache if enabled
         if let Some(redis_cache) = &self.redis_cache {
-            if let Err(e) = redis_cache.set_country(code, data).await {
-                log::error!("Failed to update Redis cache: {}", e);
+            if let Err(e) = redis_cache.set_country(code, data) {
+                log::error!("Failed to update Redis cache: {e}");
             }
         }
 
@@ -222,18 +232,18 @@ impl LocationService {
     }
 
     /// Warm up the cache with the given country codes
-    pub async fn warm_cache(&mut self, codes: &[&str]) -> Result<(), LocationServiceError> {
+    pub fn warm_cache(&mut self, codes: &[&str]) -> Result<(), LocationServiceError> {
         for code in codes {
-            self.get_country_data(code).await?;
+            self.get_country_data(code, DEFAULT_LANGUAGE)?;
         }
         Ok(())
     }
 
     /// Clear all caches
-    pub async fn clear_cache(&mut self) -> Result<(), LocationServiceError> {
+    pub fn clear_cache(&mut self) -> Result<(), LocationServiceError> {
         self.memory_cache.clear();
 
-        if let Some(redis_cache) = &self.redis_cache {
+        if self.redis_cache.is_some() {
             // In a real implementation, you might want to clear all country keys
             // This is a simplified version that doesn't clear the entire Redis cache
             log::info!("Memory cache cleared. Note: Redis cache was not cleared to avoid affecting other services.");
@@ -244,46 +254,54 @@ impl LocationService {
         Ok(())
     }
 
-    /// Clear cache for a specific country
-    pub async fn clear_country_cache(
-        &self,
-        country_code: &str,
-    ) -> Result<(), LocationServiceError> {
-        let code = country_code.to_uppercase();
+    /// Clear cache for a specific country, in every language
+    pub fn clear_country_cache(&mut self, country_code: &str) -> Result<(), LocationServiceError> {
+        for lang in SUPPORTED_LANGUAGES {
+            let key = cache_key(country_code, lang);
 
-        // Clear from memory cache
-        self.memory_cache.remove(&code);
+            // Clear from memory cache
+            self.memory_cache.remove(&key);
 
-        // Clear from Redis if enabled
-        if let Some(redis_cache) = &self.redis_cache {
-            redis_cache.clear_country_cache(&code).await?;
+            // Clear from Redis if enabled
+            if let Some(redis_cache) = &self.redis_cache {
+                redis_cache.clear_country_cache(&key)?;
+            }
         }
 
         Ok(())
     }
 
+    /// How long cached countries are served
+    pub const fn cache_ttl(&self) -> Duration {
+        self.cache_config.ttl
+    }
+
     /// Get the number of items in the in-memory cache
     pub fn cache_size(&self) -> usize {
         self.memory_cache.len()
     }
 
-    /// Check if a country is cached (in-memory only)
+    /// Check if a country is cached in the default language (in-memory only)
     pub fn is_cached(&self, code: &str) -> bool {
-        if let Some(entry) = self.memory_cache.get(&code.to_uppercase()) {
-            let now = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .ok()
-                .map(|d| d.as_secs())
-                .unwrap_or(0);
-            now - entry.timestamp < self.cache_config.ttl.as_secs()
-        } else {
-            false
-        }
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .ok()
+            .map_or(0, |d| d.as_secs());
+        self.memory_cache
+            .get(&cache_key(code, DEFAULT_LANGUAGE))
+            .is_some_and(|entry| now - entry.timestamp < self.cache_config.ttl.as_secs())
     }
 }
 
-#[cfg(test)]
-impl Default for LocationService {
+/// Cache key for a country in one language, normalised to its alpha-2 code so
+/// that `ES`, `ESP` and `724` share an entry.
+fn cache_key(code: &str, lang: &str) -> String {
+    let code =
+        countries::find(code).map_or_else(|| code.trim().to_uppercase(), |c| c.alpha2.clone());
+    format!("{code}:{lang}")
+}
+
+impl Default for CountryService {
     fn default() -> Self {
         Self::new()
     }
@@ -1,7 +1,15 @@
 use http::{Method, Request, StatusCode};
-use location_service::handlers::RequestHandler;
-use location_service::models::CountryData;
-use serde_json;
+use location_service::RequestHandler;
+use spin_sdk::http::Response;
+
+/// Runs a request through a fresh handler, which caches in memory only.
+async fn send(request: Request<Vec<u8>>) -> Response {
+    RequestHandler::new().handle_request(request).await.unwrap()
+}
+
+fn header<'a>(response: &'a Response, name: &str) -> &'a str {
+    response.header(name).and_then(|v| v.as_str()).unwrap()
+}
 
 #[cfg(test)]
 mod integration_tests {
@@ -16,10 +24,9 @@ mod integration_tests {
             .body(vec![])
             .unwrap();
 
-        let response = RequestHandler::handle_request(request).await.unwrap();
-        let http_response = response.into_response();
+        let http_response = send(request).await;
 
-        assert_eq!(http_response.status(), StatusCode::OK);
+        assert_eq!(*http_response.status(), StatusCode::OK);
 
         let body = String::from_utf8(http_response.into_body()).unwrap();
         let json_response: serde_json::Value = serde_json::from_str(&body).unwrap();
@@ -36,17 +43,10 @@ mod integration_tests {
             .body(vec![])
             .unwrap();
 
-        let response = RequestHandler::handle_request(request).await.unwrap();
-        let http_response = response.into_response();
-
-        let headers = http_response.headers();
-        assert_eq!(headers.get("Access-Control-Allow-Origin").unwrap(), "*");
-        assert!(headers
-            .get("content-type")
-            .unwrap()
-            .to_str()
-            .unwrap()
-            .contains("application/json"));
+        let http_response = send(request).await;
+
+        assert_eq!(header(&http_response, "Access-Control-Allow-Origin"), "*");
+        assert!(header(&http_response, "content-type").contains("application/json"));
     }
 
     #[tokio::test]
@@ -59,9 +59,8 @@ mod integration_tests {
                 .body(vec![])
                 .unwrap();
 
-            let response = RequestHandler::handle_request(request).await.unwrap();
-            let http_response = response.into_response();
-            assert_eq!(http_response.status(), StatusCode::OK);
+            let http_response = send(request).await;
+            assert_eq!(*http_response.status(), StatusCode::OK);
 
             let body = String::from_utf8(http_response.into_body()).unwrap();
             let json_response: serde_json::Value = serde_json::from_str(&body).unwrap();
@@ -77,9 +76,8 @@ mod integration_tests {
             .body(vec![])
             .unwrap();
 
-        let response = RequestHandler::handle_request(request).await.unwrap();
-        let http_response = response.into_response();
-        assert_eq!(http_response.status(), StatusCode::NOT_FOUND);
+        let http_response = send(request).await;
+        assert_eq!(*http_response.status(), StatusCode::NOT_FOUND);
 
         let body = String::from_utf8(http_response.into_body()).unwrap();
         let json_response: serde_json::Value = serde_json::from_str(&body).unwrap();
@@ -95,14 +93,12 @@ mod integration_tests {
             .body(vec![])
             .unwrap();
 
-        let response = RequestHandler::handle_request(request).await.unwrap();
-        let http_response = response.into_response();
-        assert_eq!(http_response.status(), StatusCode::OK);
+        let http_response = send(request).await;
+        assert_eq!(*http_response.status(), StatusCode::OK);
 
-        let headers = http_response.headers();
-        assert_eq!(headers.get("Access-Control-Allow-Origin").unwrap(), "*");
+        assert_eq!(header(&http_response, "Access-Control-Allow-Origin"), "*");
         assert_eq!(
-            headers.get("Access-Control-Allow-Methods").unwrap(),
+            header(&http_response, "Access-Control-Allow-Methods"),
             "GET, POST, PUT, DELETE, OPTIONS"
         );
     }
@@ -115,11 +111,9 @@ mod integration_tests {
             .body(vec![])
             .unwrap();
 
-        let response = RequestHandler::handle_request(request).await.unwrap();
-        let http_response = response.into_response();
+        let http_response = send(request).await;
 
-        let headers = http_response.headers();
-        let content_type = headers.get("content-type").unwrap().to_str().unwrap();
+        let content_type = header(&http_response, "content-type");
         assert!(content_type.contains("application/json"));
     }
 
@@ -134,9 +128,8 @@ mod integration_tests {
                 .body(vec![])
                 .unwrap();
 
-            let response = RequestHandler::handle_request(request).await.unwrap();
-            let http_response = response.into_response();
-            assert_eq!(http_response.status(), StatusCode::OK);
+            let http_response = send(request).await;
+            assert_eq!(*http_response.status(), StatusCode::OK);
 
             let body = String::from_utf8(http_response.into_body()).unwrap();
             let json_response: serde_json::Value = serde_json::from_str(&body).unwrap();
@@ -152,9 +145,8 @@ mod integration_tests {
             .body(vec![])
             .unwrap();
 
-        let response = RequestHandler::handle_request(request).await.unwrap();
-        let http_response = response.into_response();
-        assert_eq!(http_response.status(), StatusCode::OK);
+        let http_response = send(request).await;
+        assert_eq!(*http_response.status(), StatusCode::OK);
 
         let body = String::from_utf8(http_response.into_body()).unwrap();
         let json_response: serde_json::Value = serde_json::from_str(&body).unwrap();
@@ -170,9 +162,8 @@ mod integration_tests {
             .body(vec![])
             .unwrap();
 
-        let response = RequestHandler::handle_request(request).await.unwrap();
-        let http_response = response.into_response();
-        assert_eq!(http_response.status(), StatusCode::OK);
+        let http_response = send(request).await;
+        assert_eq!(*http_response.status(), StatusCode::OK);
 
         let body = String::from_utf8(http_response.into_body()).unwrap();
         let json_response: serde_json::Value = serde_json::from_str(&body).unwrap();
@@ -180,6 +171,161 @@ mod integration_tests {
         assert!(json_response["data"].is_array());
     }
 
+    #[tokio::test]
+    async fn test_list_countries_is_complete_and_localised() {
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("/api/countries?lang=es")
+            .body(vec![])
+            .unwrap();
+
+        let http_response = send(request).await;
+        assert_eq!(*http_response.status(), StatusCode::OK);
+
+        let body = String::from_utf8(http_response.into_body()).unwrap();
+        let json_response: serde_json::Value = serde_json::from_str(&body).unwrap();
+        let countries = json_response["data"].as_array().unwrap();
+        assert_eq!(countries.len(), 249);
+
+        let spain = countries.iter().find(|c| c["code"] == "ES").unwrap();
+        assert_eq!(spain["name"], "España");
+        assert_eq!(spain["alpha3"], "ESP");
+        assert_eq!(spain["calling_code"], "+34");
+    }
+
+    #[tokio::test]
+    async fn test_search_countries_endpoint() {
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("/api/countries?search=alem&lang=es")
+            .body(vec![])
+            .unwrap();
+
+        let http_response = send(request).await;
+        assert_eq!(*http_response.status(), StatusCode::OK);
+
+        let body = String::from_utf8(http_response.into_body()).unwrap();
+        let json_response: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(json_response["data"][0]["code"], "DE");
+        assert_eq!(json_response["data"][0]["name"], "Alemania");
+    }
+
+    #[tokio::test]
+    async fn test_get_country_by_alpha3_with_accept_language() {
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("/api/countries/PRT")
+            .header("Accept-Language", "fr-FR,fr;q=0.9,en;q=0.8")
+            .body(vec![])
+            .unwrap();
+
+        let http_response = send(request).await;
+        assert_eq!(*http_response.status(), StatusCode::OK);
+
+        let body = String::from_utf8(http_response.into_body()).unwrap();
+        let json_response: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(json_response["data"]["code"], "PT");
+        assert_eq!(json_response["data"]["name"], "Portugal");
+        assert_eq!(json_response["data"]["schengen"], true);
+    }
+
+    #[tokio::test]
+    async fn test_validate_phone_endpoint() {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/api/phones/validate")
+            .header("content-type", "application/json")
+            .body(br#"{"phone":"612 34 56 78","country":"ES"}"#.to_vec())
+            .unwrap();
+
+        let http_response = send(request).await;
+        assert_eq!(*http_response.status(), StatusCode::OK);
+
+        let body = String::from_utf8(http_response.into_body()).unwrap();
+        let json_response: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(json_response["data"]["valid"], true);
+        assert_eq!(json_response["data"]["e164"], "+34612345678");
+        assert_eq!(json_response["data"]["number_type"], "mobile");
+    }
+
+    #[tokio::test]
+    async fn test_validate_phone_rejects_invalid_number() {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/api/phones/validate")
+            .body(br#"{"phone":"61234","country":"ES"}"#.to_vec())
+            .unwrap();
+
+        let http_response = send(request).await;
+        assert_eq!(*http_response.status(), StatusCode::OK);
+
+        let body = String::from_utf8(http_response.into_body()).unwrap();
+        let json_response: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(json_response["data"]["valid"], false);
+        assert_eq!(json_response["data"]["error"], "too_short");
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/api/phones/validate")
+            .body(b"not json".to_vec())
+            .unwrap();
+
+        let response = send(request).await;
+        assert_eq!(*response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_postal_code_lookup_endpoint() {
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("/api/addresses/postal-codes/06800")
+            .body(vec![])
+            .unwrap();
+
+        let http_response = send(request).await;
+        assert_eq!(*http_response.status(), StatusCode::OK);
+
+        let body = String::from_utf8(http_response.into_body()).unwrap();
+        let json_response: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(json_response["data"]["province"]["name"], "Badajoz");
+        assert_eq!(json_response["data"]["municipalities"][0]["code"], "06083");
+
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("/api/addresses/postal-codes/99000")
+            .body(vec![])
+            .unwrap();
+
+        let response = send(request).await;
+        assert_eq!(*response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_validate_address_endpoint() {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/api/addresses/validate")
+            .header("content-type", "application/json")
+            .body(
+                r#"{"postal_code":"06800","province":"Cáceres","municipality_code":"06083"}"#
+                    .as_bytes()
+                    .to_vec(),
+            )
+            .unwrap();
+
+        let http_response = send(request).await;
+        assert_eq!(*http_response.status(), StatusCode::OK);
+
+        let body = String::from_utf8(http_response.into_body()).unwrap();
+        let json_response: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(json_response["data"]["valid"], false);
+        assert_eq!(json_response["data"]["errors"][0]["field"], "province");
+        assert_eq!(
+            json_response["data"]["errors"][0]["code"],
+            "province_mismatch"
+        );
+    }
+
     #[tokio::test]
     async fn test_clear_cache_endpoint() {
         let request = Request::builder()
@@ -188,9 +334,8 @@ mod integration_tests {
             .body(vec![])
             .unwrap();
 
-        let response = RequestHandler::handle_request(request).await.unwrap();
-        let http_response = response.into_response();
-        assert_eq!(http_response.status(), StatusCode::OK);
+        let http_response = send(request).await;
+        assert_eq!(*http_response.status(), StatusCode::OK);
 
         let body = String::from_utf8(http_response.into_body()).unwrap();
         let json_response: serde_json::Value = serde_json::from_str(&body).unwrap();
@@ -205,9 +350,8 @@ mod integration_tests {
             .body(vec![])
             .unwrap();
 
-        let response = RequestHandler::handle_request(request).await.unwrap();
-        let http_response = response.into_response();
-        assert_eq!(http_response.status(), StatusCode::BAD_REQUEST);
+        let http_response = send(request).await;
+        assert_eq!(*http_response.status(), StatusCode::BAD_REQUEST);
 
         let body = String::from_utf8(http_response.into_body()).unwrap();
         let json_response: serde_json::Value = serde_json::from_str(&body).unwrap();
@@ -222,9 +366,8 @@ mod integration_tests {
             .body(vec![])
             .unwrap();
 
-        let response = RequestHandler::handle_request(request).await.unwrap();
-        let http_response = response.into_response();
-        assert_eq!(http_response.status(), StatusCode::NOT_FOUND);
+        let http_response = send(request).await;
+        assert_eq!(*http_response.status(), StatusCode::NOT_FOUND);
 
         let body = String::from_utf8(http_response.into_body()).unwrap();
         let json_response: serde_json::Value = serde_json::from_str(&body).unwrap();
@@ -239,9 +382,8 @@ mod integration_tests {
             .body(vec![])
             .unwrap();
 
-        let response = RequestHandler::handle_request(request).await.unwrap();
-        let http_response = response.into_response();
-        assert_eq!(http_response.status(), StatusCode::NOT_FOUND);
+        let http_response = send(request).await;
+        assert_eq!(*http_response.status(), StatusCode::NOT_FOUND);
     }
 
     #[tokio::test]
@@ -253,9 +395,8 @@ mod integration_tests {
             .body(vec![])
             .unwrap();
 
-        let warm_response = RequestHandler::handle_request(warm_request).await.unwrap();
-        let warm_http_response = warm_response.into_response();
-        assert_eq!(warm_http_response.status(), StatusCode::OK);
+        let warm_http_response = send(warm_request).await;
+        assert_eq!(*warm_http_response.status(), StatusCode::OK);
 
         // Now test that countries are accessible
         for country_code in ["ES", "FR", "PT", "IT"] {
@@ -265,9 +406,8 @@ mod integration_tests {
                 .body(vec![])
                 .unwrap();
 
-            let response = RequestHandler::handle_request(request).await.unwrap();
-            let http_response = response.into_response();
-            assert_eq!(http_response.status(), StatusCode::OK);
+            let http_response = send(request).await;
+            assert_eq!(*http_response.status(), StatusCode::OK);
         }
     }
 
@@ -284,9 +424,8 @@ mod integration_tests {
                 .body(vec![])
                 .unwrap();
 
-            let response = RequestHandler::handle_request(request).await.unwrap();
-            let http_response = response.into_response();
-            assert_eq!(http_response.status(), StatusCode::OK);
+            let http_response = send(request).await;
+            assert_eq!(*http_response.status(), StatusCode::OK);
         }
 
         let duration = start.elapsed();
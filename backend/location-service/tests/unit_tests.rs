@@ -1,6 +1,4 @@
 use location_service::*;
-use models::{ApiResponse, CacheEntry, CountryData};
-use service::LocationService;
 
 #[cfg(test)]
 mod country_data_tests {
@@ -17,6 +15,7 @@ mod country_data_tests {
             capital: Some("Madrid".to_string()),
             currency: Some("EUR".to_string()),
             languages: vec!["Spanish".to_string()],
+            ..Default::default()
         };
 
         let serialized = serde_json::to_string(&country).unwrap();
@@ -43,6 +42,7 @@ mod country_data_tests {
             capital: None,
             currency: None,
             languages: vec![],
+            ..Default::default()
         };
 
         let serialized = serde_json::to_string(&country).unwrap();
@@ -97,6 +97,7 @@ mod cache_entry_tests {
             capital: Some("Paris".to_string()),
             currency: Some("EUR".to_string()),
             languages: vec!["French".to_string()],
+            ..Default::default()
         };
 
         let timestamp = SystemTime::now()
@@ -120,27 +121,27 @@ mod cache_entry_tests {
 #[cfg(test)]
 mod location_service_tests {
     use super::*;
-    use std::time::{SystemTime, UNIX_EPOCH};
+    use std::time::Duration;
 
     #[tokio::test]
     async fn test_location_service_new() {
-        let service = LocationService::new();
+        let service = CountryService::new();
         assert_eq!(service.cache_size(), 0);
-        assert_eq!(service.cache_ttl, 3600);
+        assert_eq!(service.cache_ttl(), Duration::from_hours(1));
     }
 
     #[tokio::test]
     async fn test_location_service_with_custom_ttl() {
-        let service = LocationService::with_cache_ttl(7200);
-        assert_eq!(service.cache_ttl, 7200);
+        let service = CountryService::with_cache_ttl(Duration::from_hours(2));
+        assert_eq!(service.cache_ttl(), Duration::from_hours(2));
     }
 
     #[tokio::test]
     async fn test_get_country_data_known_country() {
-        let mut service = LocationService::new();
+        let mut service = CountryService::new();
 
         // Test Spain
-        let result = service.get_country_data("ES").await.unwrap();
+        let result = service.get_country_data("ES", "en").unwrap();
         assert!(result.is_some());
         let country = result.unwrap();
         assert_eq!(country.code, "ES");
@@ -151,50 +152,62 @@ mod location_service_tests {
         assert_eq!(country.capital.unwrap(), "Madrid");
         assert_eq!(country.currency.unwrap(), "EUR");
         assert_eq!(country.languages, vec!["Spanish"]);
+        assert_eq!(country.alpha3, "ESP");
+        assert!(country.eu_member && country.schengen);
+    }
+
+    #[tokio::test]
+    async fn test_get_country_data_localised() {
+        let mut service = CountryService::new();
+
+        let country = service.get_country_data("DEU", "es").unwrap().unwrap();
+        assert_eq!(country.code, "DE");
+        assert_eq!(country.name, "Alemania");
+        assert_eq!(country.capital.unwrap(), "Berlin");
     }
 
     #[tokio::test]
     async fn test_get_country_data_unknown_country() {
-        let mut service = LocationService::new();
+        let mut service = CountryService::new();
 
-        let result = service.get_country_data("XX").await.unwrap();
+        let result = service.get_country_data("XX", "en").unwrap();
         assert!(result.is_none());
     }
 
     #[tokio::test]
     async fn test_get_country_data_case_insensitive() {
-        let mut service = LocationService::new();
+        let mut service = CountryService::new();
 
         // Test lowercase
-        let result = service.get_country_data("es").await.unwrap();
+        let result = service.get_country_data("es", "en").unwrap();
         assert!(result.is_some());
         assert_eq!(result.unwrap().code, "ES");
 
         // Test mixed case
-        let result = service.get_country_data("Fr").await.unwrap();
+        let result = service.get_country_data("Fr", "en").unwrap();
         assert!(result.is_some());
         assert_eq!(result.unwrap().code, "FR");
     }
 
     #[tokio::test]
     async fn test_get_country_data_empty_code() {
-        let mut service = LocationService::new();
+        let mut service = CountryService::new();
 
-        let result = service.get_country_data("").await.unwrap();
+        let result = service.get_country_data("", "en").unwrap();
         assert!(result.is_none());
     }
 
     #[tokio::test]
     async fn test_cache_functionality() {
-        let mut service = LocationService::new();
+        let mut service = CountryService::new();
 
         // First call should populate cache
-        let result1 = service.get_country_data("PT").await.unwrap();
+        let result1 = service.get_country_data("PT", "en").unwrap();
         assert!(result1.is_some());
         assert_eq!(service.cache_size(), 1);
 
         // Second call should use cache
-        let result2 = service.get_country_data("PT").await.unwrap();
+        let result2 = service.get_country_data("PT", "en").unwrap();
         assert!(result2.is_some());
         assert_eq!(service.cache_size(), 1);
 
@@ -204,10 +217,10 @@ mod location_service_tests {
 
     #[tokio::test]
     async fn test_cache_expiration() {
-        let mut service = LocationService::with_cache_ttl(1); // 1 second TTL
+        let mut service = CountryService::with_cache_ttl(Duration::from_secs(1)); // 1 second TTL
 
         // Populate cache
-        service.get_country_data("IT").await.unwrap();
+        service.get_country_data("IT", "en").unwrap();
         assert!(service.is_cached("IT"));
 
         // Wait for expiration (in test, we'll simulate)
@@ -217,24 +230,24 @@ mod location_service_tests {
 
     #[tokio::test]
     async fn test_clear_cache() {
-        let mut service = LocationService::new();
+        let mut service = CountryService::new();
 
-        service.get_country_data("ES").await.unwrap();
+        service.get_country_data("ES", "en").unwrap();
         assert_eq!(service.cache_size(), 1);
 
-        service.clear_cache();
+        service.clear_cache().unwrap();
         assert_eq!(service.cache_size(), 0);
         assert!(!service.is_cached("ES"));
     }
 
     #[tokio::test]
     async fn test_warm_cache() {
-        let mut service = LocationService::new();
+        let mut service = CountryService::new();
 
         assert_eq!(service.cache_size(), 0);
 
         let countries = ["ES", "FR", "PT", "IT"];
-        let result = service.warm_cache(&countries).await;
+        let result = service.warm_cache(&countries);
 
         assert!(result.is_ok());
         assert_eq!(service.cache_size(), 4);
@@ -247,10 +260,10 @@ mod location_service_tests {
 
     #[tokio::test]
     async fn test_warm_cache_with_unknown_countries() {
-        let mut service = LocationService::new();
+        let mut service = CountryService::new();
 
         let countries = ["ES", "XX", "FR", "YY"];
-        let result = service.warm_cache(&countries).await;
+        let result = service.warm_cache(&countries);
 
         assert!(result.is_ok());
         assert_eq!(service.cache_size(), 2); // Only ES and FR should be cached
@@ -262,10 +275,10 @@ mod location_service_tests {
 
     #[tokio::test]
     async fn test_warm_cache_empty_list() {
-        let mut service = LocationService::new();
+        let mut service = CountryService::new();
 
         let countries: &[&str] = &[];
-        let result = service.warm_cache(countries).await;
+        let result = service.warm_cache(countries);
 
         assert!(result.is_ok());
         assert_eq!(service.cache_size(), 0);
@@ -273,51 +286,35 @@ mod location_service_tests {
 
     #[tokio::test]
     async fn test_is_cached_function() {
-        let mut service = LocationService::new();
+        let mut service = CountryService::new();
 
         assert!(!service.is_cached("ES"));
 
-        service.get_country_data("ES").await.unwrap();
+        service.get_country_data("ES", "en").unwrap();
         assert!(service.is_cached("ES"));
         assert!(!service.is_cached("XX"));
     }
 
     #[tokio::test]
     async fn test_cache_size_tracking() {
-        let mut service = LocationService::new();
+        let mut service = CountryService::new();
 
         assert_eq!(service.cache_size(), 0);
 
-        service.get_country_data("ES").await.unwrap();
+        service.get_country_data("ES", "en").unwrap();
         assert_eq!(service.cache_size(), 1);
 
-        service.get_country_data("FR").await.unwrap();
+        service.get_country_data("FR", "en").unwrap();
         assert_eq!(service.cache_size(), 2);
 
-        service.clear_cache();
+        service.clear_cache().unwrap();
         assert_eq!(service.cache_size(), 0);
     }
 
     #[tokio::test]
     async fn test_default_implementation() {
-        let service = LocationService::default();
+        let service = CountryService::default();
         assert_eq!(service.cache_size(), 0);
-        assert_eq!(service.cache_ttl, 3600);
-    }
-}
-
-// Implement Default for CountryData for testing
-impl Default for CountryData {
-    fn default() -> Self {
-        Self {
-            code: String::new(),
-            name: String::new(),
-            flag: None,
-            phone_prefix: None,
-            continent: None,
-            capital: None,
-            currency: None,
-            languages: Vec::new(),
-        }
+        assert_eq!(service.cache_ttl(), Duration::from_hours(1));
     }
 }
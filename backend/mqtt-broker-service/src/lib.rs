@@ -10,8 +10,14 @@ use spin_sdk::{
     http::{IntoResponse, Method, Params, Request, Response, ResponseBuilder, Router},
     http_component, redis, variables,
 };
+use shared::gateway_token::{
+    sign_service_token, BROKER_ISSUER, GATEWAY_TOKEN_HEADER, INTERNAL_TOKEN_SECRET_VAR,
+};
 use std::collections::HashMap;
 
+/// Spin variable listing the webhooks configured at deploy time.
+const WEBHOOK_SUBSCRIPTIONS_VAR: &str = "webhook_subscriptions";
+
 /// Lightweight MQTT Broker for Spin
 /// Handles pub/sub messaging between microservices
 /// Uses Redis for message persistence and routing
@@ -185,38 +191,47 @@ async fn handle_register_webhook(req: Request, _params: Params) -> Result<impl I
 }
 
 /// Deliver message to registered webhooks (fire-and-forget)
+///
+/// Each delivery carries a service token addressed to the subscriber, so
+/// webhook endpoints can tell the broker from any other caller. Without the
+/// secret to sign it nothing is delivered.
 async fn deliver_to_webhooks(redis_address: &str, topic: &str, message_json: &[u8]) {
-    // Get all webhook subscriptions
-    let webhooks_index =
-        match redis::execute(redis_address, "SMEMBERS", &[b"mqtt:webhooks:index"]).await {
-            Ok(keys) => keys,
-            Err(_) => return, // No webhooks registered
-        };
+    let secret = match variables::get(INTERNAL_TOKEN_SECRET_VAR) {
+        Ok(secret) if !secret.is_empty() => secret,
+        _ => {
+            println!(
+                "[MQTT Broker] Not delivering {topic} to webhooks: {INTERNAL_TOKEN_SECRET_VAR} is not configured"
+            );
+            return;
+        }
+    };
+    let now = chrono::Utc::now();
+    let delivery_id = format!("{topic}:{}", now.timestamp_micros());
 
-    for webhook_key in webhooks_index {
-        // Get webhook subscription
-        let subscription_data =
-            match redis::get(redis_address, &String::from_utf8_lossy(&webhook_key)).await {
-                Ok(data) => data,
-                Err(_) => continue,
-            };
-
-        let subscription: WebhookSubscription = match serde_json::from_slice(&subscription_data) {
-            Ok(sub) => sub,
-            Err(_) => continue,
-        };
+    let mut subscriptions = configured_webhooks();
+    subscriptions.extend(registered_webhooks(redis_address).await);
 
+    for subscription in subscriptions {
         // Check if topic matches any filter
         if !topic_matches_filters(topic, &subscription.topic_filters) {
             continue;
         }
 
+        let token = sign_service_token(
+            secret.as_bytes(),
+            BROKER_ISSUER,
+            &subscription.service_id,
+            &delivery_id,
+            u64::try_from(now.timestamp()).unwrap_or(0),
+        );
+
         // Fire-and-forget POST to webhook URL
         let webhook_request = match spin_sdk::http::Request::builder()
             .method(Method::Post)
             .uri(&subscription.webhook_url)
             .header("Content-Type", "application/json")
             .header("X-MQTT-Topic", topic)
+            .header(GATEWAY_TOKEN_HEADER, token)
             .body(message_json)
             .build()
         {
@@ -234,6 +249,45 @@ async fn deliver_to_webhooks(redis_address: &str, topic: &str, message_json: &[u
     }
 }
 
+/// Webhooks declared in the `webhook_subscriptions` variable, a JSON list
+/// of registrations, so subscribers need not register at runtime.
+fn configured_webhooks() -> Vec<WebhookRegistration> {
+    let configured = variables::get(WEBHOOK_SUBSCRIPTIONS_VAR).unwrap_or_default();
+    if configured.trim().is_empty() {
+        return Vec::new();
+    }
+    serde_json::from_str(&configured).unwrap_or_else(|e| {
+        println!("[MQTT Broker] Ignoring invalid {WEBHOOK_SUBSCRIPTIONS_VAR}: {e}");
+        Vec::new()
+    })
+}
+
+/// Webhooks registered through `/api/mqtt/register-webhook`.
+async fn registered_webhooks(redis_address: &str) -> Vec<WebhookRegistration> {
+    let Ok(webhooks_index) =
+        redis::execute(redis_address, "SMEMBERS", &[b"mqtt:webhooks:index"]).await
+    else {
+        return Vec::new(); // No webhooks registered
+    };
+
+    let mut registrations = Vec::new();
+    for webhook_key in webhooks_index {
+        let Ok(subscription_data) =
+            redis::get(redis_address, &String::from_utf8_lossy(&webhook_key)).await
+        else {
+            continue;
+        };
+        if let Ok(subscription) = serde_json::from_slice::<WebhookSubscription>(&subscription_data) {
+            registrations.push(WebhookRegistration {
+                service_id: subscription.service_id,
+                webhook_url: subscription.webhook_url,
+                topic_filters: subscription.topic_filters,
+            });
+        }
+    }
+    registrations
+}
+
 /// Check if topic matches any of the filter patterns
 /// Supports wildcard matching: albergue.v1.booking.* matches albergue.v1.booking.reserved
 fn topic_matches_filters(topic: &str, filters: &[String]) -> bool {
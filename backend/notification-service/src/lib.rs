@@ -7,17 +7,25 @@ use spin_sdk::{
     http_component,
 };
 use http::StatusCode;
+use shared::gateway_token::{
+    verify_service_token, GATEWAY_TOKEN_HEADER, INTERNAL_TOKEN_SECRET_VAR, REVIEWS_ISSUER,
+};
 use std::collections::HashMap;
 
 mod adapters;
 mod application;
 mod domain;
 mod infrastructure;
+mod pilgrims;
 mod ports;
 
 use application::notification_service::NotificationService;
 use domain::notification::{Notification, NotificationChannel, NotificationStatus, NotificationType};
 
+/// Our name in service tokens addressed to us.
+const SERVICE_NAME: &str = "notification-service";
+const DEFAULT_REVIEW_LINK_BASE: &str = "https://alberguecarrascalejo.fermyon.app/reviews/new";
+
 #[http_component]
 async fn handle_request(req: Request) -> anyhow::Result<Response> {
     let service = NotificationService::new();
@@ -30,6 +38,7 @@ async fn handle_request(req: Request) -> anyhow::Result<Response> {
         (&Method::Post, "/send/whatsapp") => handle_send_whatsapp(req, &service).await,
         (&Method::Post, "/send/telegram") => handle_send_telegram(req, &service).await,
         (&Method::Post, "/send/booking-confirmation") => handle_booking_confirmation(req, &service).await,
+        (&Method::Post, "/send/review-invitation") => handle_review_invitation(req, &service).await,
         _ => Ok(Response::new(StatusCode::NOT_FOUND, "Not Found"))
     }
 }
@@ -152,3 +161,64 @@ async fn handle_booking_confirmation(req: Request, service: &NotificationService
     
     Ok(Response::new(StatusCode::OK, serde_json::to_vec(&results)?))
 }
+
+#[derive(serde::Deserialize)]
+struct ReviewInvitationRequest {
+    booking_id: String,
+    pilgrim_id: String,
+    token: String,
+    expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Sends a guest the link to review their stay. Only reviews-service may ask,
+/// as the body carries the single-use review token.
+async fn handle_review_invitation(req: Request, service: &NotificationService) -> anyhow::Result<Response> {
+    let secret = spin_sdk::variables::get(INTERNAL_TOKEN_SECRET_VAR).unwrap_or_default();
+    if secret.is_empty() {
+        return Ok(Response::new(StatusCode::SERVICE_UNAVAILABLE, "Internal token secret not configured"));
+    }
+    let token = req.header(GATEWAY_TOKEN_HEADER).and_then(|h| h.as_str());
+    let now = u64::try_from(chrono::Utc::now().timestamp()).unwrap_or(0);
+    if let Err(e) = verify_service_token(token, secret.as_bytes(), SERVICE_NAME, &[REVIEWS_ISSUER], now) {
+        return Ok(Response::new(StatusCode::FORBIDDEN, e.to_string()));
+    }
+
+    let payload: ReviewInvitationRequest = serde_json::from_slice(req.body())?;
+    let Some(recipient) = pilgrims::email(&payload.pilgrim_id).await? else {
+        return Ok(Response::new(StatusCode::UNPROCESSABLE_ENTITY, "Pilgrim has no email address"));
+    };
+    let link_base = spin_sdk::variables::get("review_link_base")
+        .ok()
+        .filter(|base| !base.is_empty())
+        .unwrap_or_else(|| DEFAULT_REVIEW_LINK_BASE.to_string());
+
+    let notification = Notification {
+        id: uuid::Uuid::new_v4(),
+        notification_type: NotificationType::ReviewInvitation,
+        recipient,
+        subject: Some("¿Qué tal tu estancia en el Albergue del Carrascalejo?".to_string()),
+        message: format!(
+            "Gracias por alojarte con nosotros. Cuéntanos qué te pareció: {link_base}?token={}\n\nEl enlace caduca el {}.",
+            payload.token,
+            payload.expires_at.format("%d/%m/%Y"),
+        ),
+        channel: NotificationChannel::Email,
+        status: NotificationStatus::Pending,
+        created_at: chrono::Utc::now(),
+        sent_at: None,
+        delivered_at: None,
+        error_message: None,
+        template_data: HashMap::from([("booking_id".to_string(), payload.booking_id)]),
+    };
+
+    let result = service.send_with_fallback(notification, vec![NotificationChannel::Email]).await?;
+    let status = match result.status {
+        NotificationStatus::Sent => StatusCode::OK,
+        _ => StatusCode::BAD_GATEWAY,
+    };
+
+    Ok(Response::new(status, serde_json::to_vec(&serde_json::json!({
+        "id": result.id,
+        "status": result.status,
+    }))?))
+}
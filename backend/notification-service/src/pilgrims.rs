@@ -0,0 +1,69 @@
+//! Contact details of pilgrims, for messages addressed to one by id.
+//!
+//! They are stored encrypted, so each lookup goes through security-service's
+//! decrypt endpoint, which records the read in the audit log.
+
+use anyhow::{anyhow, bail, Result};
+use shared::gateway_token::{
+    sign_service_token, GATEWAY_TOKEN_HEADER, INTERNAL_TOKEN_SECRET_VAR, NOTIFICATION_ISSUER,
+};
+use spin_sdk::http::{Method, Request, Response};
+use spin_sdk::sqlite::{Connection, Value};
+
+const DECRYPT_URL: &str = "http://security-service.spin.internal/security/decrypt";
+const SECURITY_SERVICE: &str = "security-service";
+const EMAIL_CONTEXT: &str = "pilgrims.email_encrypted";
+
+/// The pilgrim's email address, `None` when they did not give one.
+pub async fn email(pilgrim_id: &str) -> Result<Option<String>> {
+    let id: i64 = pilgrim_id
+        .parse()
+        .map_err(|_| anyhow!("invalid pilgrim id {pilgrim_id:?}"))?;
+    let conn = Connection::open_default()?;
+    let found = conn.execute(
+        "SELECT email_encrypted FROM pilgrims WHERE id = ?",
+        &[Value::Integer(id)],
+    )?;
+    let Some(row) = found.rows().next() else {
+        bail!("pilgrim {pilgrim_id} not found");
+    };
+    let Some(sealed) = row.get::<&str>("email_encrypted") else {
+        return Ok(None);
+    };
+    decrypt(sealed, pilgrim_id).await.map(Some)
+}
+
+async fn decrypt(sealed: &str, pilgrim_id: &str) -> Result<String> {
+    let secret = spin_sdk::variables::get(INTERNAL_TOKEN_SECRET_VAR)
+        .ok()
+        .filter(|secret| !secret.is_empty())
+        .ok_or_else(|| anyhow!("internal token secret not configured"))?;
+    let token = sign_service_token(
+        secret.as_bytes(),
+        NOTIFICATION_ISSUER,
+        SECURITY_SERVICE,
+        &uuid::Uuid::new_v4().to_string(),
+        u64::try_from(chrono::Utc::now().timestamp()).unwrap_or(0),
+    );
+    let body = serde_json::json!({
+        "encrypted_data": sealed,
+        "context": EMAIL_CONTEXT,
+        "record_id": pilgrim_id,
+    });
+    let request = Request::builder()
+        .method(Method::Post)
+        .uri(DECRYPT_URL)
+        .header("content-type", "application/json")
+        .header(GATEWAY_TOKEN_HEADER, token)
+        .body(body.to_string())
+        .build();
+    let response: Response = spin_sdk::http::send(request).await?;
+    if *response.status() != 200 {
+        bail!("security-service returned status {}", response.status());
+    }
+    let decrypted: serde_json::Value = serde_json::from_slice(response.body())?;
+    decrypted["data"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("security-service returned no data"))
+}
@@ -0,0 +1,501 @@
+//! AES-256-GCM envelope encryption of personal data.
+//!
+//! Every value gets its own data key. The data key encrypts the value and is
+//! itself wrapped by a master key, so rotating the master key only re-wraps
+//! data keys; the values themselves are never re-encrypted. An envelope is a
+//! single text column value:
+//!
+//! ```text
+//! v1:{master key id}:{wrapped data key}:{ciphertext}
+//! ```
+//!
+//! Both parts are unpadded base64url of `nonce || ciphertext || tag`. The
+//! caller's context (e.g. `pilgrims.phone_encrypted`) is the additional
+//! authenticated data of the value, so an envelope copied into another
+//! column fails to decrypt.
+//!
+//! security-service and albergue-persistence both encrypt through this
+//! crate; `tests/fixtures/envelope_vectors.json` holds values each of them
+//! wrote, which every reader must keep opening.
+
+#![deny(warnings)]
+#![warn(clippy::all, clippy::pedantic)]
+#![allow(clippy::missing_errors_doc)]
+
+use std::fmt;
+
+use base64::{
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    Engine,
+};
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN},
+    hmac,
+    rand::{SecureRandom, SystemRandom},
+};
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+pub const ALGORITHM: &str = "AES-256-GCM";
+const VERSION: &str = "v1";
+pub const KEY_BYTES: usize = 32;
+/// Blind-index keys shorter than this would be guessable.
+pub const MIN_BLIND_INDEX_KEY_BYTES: usize = 32;
+const LEGACY_PREFIX: &[u8] = b"encrypted:";
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CryptoError {
+    #[error("not an encrypted value")]
+    Malformed,
+    #[error("master key {0} is not configured")]
+    UnknownKey(String),
+    #[error("decryption failed: wrong key or context, or the value was altered")]
+    Decryption,
+    #[error("invalid key: {0}")]
+    InvalidKey(String),
+    #[error("no randomness available")]
+    Randomness,
+}
+
+/// A master key and the id envelopes refer to it by.
+#[derive(Clone)]
+pub struct MasterKey {
+    id: String,
+    key: LessSafeKey,
+}
+
+impl MasterKey {
+    pub fn new(id: &str, key: &[u8]) -> Result<Self, CryptoError> {
+        let valid_id = !id.is_empty()
+            && id
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.'));
+        if !valid_id {
+            return Err(CryptoError::InvalidKey(format!(
+                "key id {id:?} must be letters, digits, '-', '_' or '.'"
+            )));
+        }
+        Ok(Self {
+            id: id.to_string(),
+            key: aes_key(key)?,
+        })
+    }
+
+    #[must_use]
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+impl fmt::Debug for MasterKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MasterKey")
+            .field("id", &self.id)
+            .finish_non_exhaustive()
+    }
+}
+
+fn aes_key(key: &[u8]) -> Result<LessSafeKey, CryptoError> {
+    if key.len() != KEY_BYTES {
+        return Err(CryptoError::InvalidKey(format!(
+            "AES-256 keys are {KEY_BYTES} bytes, got {}",
+            key.len()
+        )));
+    }
+    UnboundKey::new(&AES_256_GCM, key)
+        .map(LessSafeKey::new)
+        .map_err(|_| CryptoError::InvalidKey("rejected by AES-256-GCM".to_string()))
+}
+
+/// Key of blind indexes, independent of the master keys so rotating those
+/// leaves every index valid.
+pub struct BlindIndexKey(hmac::Key);
+
+impl BlindIndexKey {
+    pub fn new(key: &[u8]) -> Result<Self, CryptoError> {
+        if key.len() < MIN_BLIND_INDEX_KEY_BYTES {
+            return Err(CryptoError::InvalidKey(format!(
+                "the blind-index key needs at least {MIN_BLIND_INDEX_KEY_BYTES} bytes"
+            )));
+        }
+        Ok(Self(hmac::Key::new(hmac::HMAC_SHA256, key)))
+    }
+
+    /// Keyed HMAC-SHA256 of the normalised `value`, so equal values can be
+    /// found without decrypting anything. Identifiers are compared without
+    /// case, spaces or hyphens: "12345678-z" and "12345678Z" match.
+    #[must_use]
+    pub fn index(&self, value: &str, context: &str) -> String {
+        let normalised: Zeroizing<String> = Zeroizing::new(
+            value
+                .chars()
+                .filter(|c| !c.is_whitespace() && *c != '-')
+                .flat_map(char::to_uppercase)
+                .collect(),
+        );
+        let mut ctx = hmac::Context::with_key(&self.0);
+        ctx.update(context.as_bytes());
+        ctx.update(&[0]);
+        ctx.update(normalised.as_bytes());
+        URL_SAFE_NO_PAD.encode(ctx.sign())
+    }
+}
+
+/// Encrypts `plaintext` under a fresh data key wrapped by `master`.
+pub fn seal(master: &MasterKey, plaintext: &[u8], context: &str) -> Result<String, CryptoError> {
+    let mut data_key = Zeroizing::new([0u8; KEY_BYTES]);
+    SystemRandom::new()
+        .fill(data_key.as_mut())
+        .map_err(|_| CryptoError::Randomness)?;
+    let ciphertext = seal_bytes(&aes_key(data_key.as_ref())?, plaintext, context.as_bytes())?;
+    format_envelope(master, data_key.as_ref(), &ciphertext)
+}
+
+fn format_envelope(
+    master: &MasterKey,
+    data_key: &[u8],
+    ciphertext: &[u8],
+) -> Result<String, CryptoError> {
+    let wrapped = seal_bytes(&master.key, data_key, wrap_aad(&master.id).as_bytes())?;
+    Ok(format!(
+        "{VERSION}:{}:{}:{}",
+        master.id,
+        URL_SAFE_NO_PAD.encode(wrapped),
+        URL_SAFE_NO_PAD.encode(ciphertext)
+    ))
+}
+
+/// A data key is bound to the master key id it is wrapped under.
+fn wrap_aad(key_id: &str) -> String {
+    format!("{VERSION}:dek:{key_id}")
+}
+
+fn seal_bytes(key: &LessSafeKey, plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    let mut nonce = [0u8; NONCE_LEN];
+    SystemRandom::new()
+        .fill(&mut nonce)
+        .map_err(|_| CryptoError::Randomness)?;
+    let mut sealed = plaintext.to_vec();
+    key.seal_in_place_append_tag(
+        Nonce::assume_unique_for_key(nonce),
+        Aad::from(aad),
+        &mut sealed,
+    )
+    .map_err(|_| CryptoError::Randomness)?;
+    let mut out = nonce.to_vec();
+    out.append(&mut sealed);
+    Ok(out)
+}
+
+fn open_bytes(
+    key: &LessSafeKey,
+    sealed: &[u8],
+    aad: &[u8],
+) -> Result<Zeroizing<Vec<u8>>, CryptoError> {
+    if sealed.len() < NONCE_LEN {
+        return Err(CryptoError::Malformed);
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| CryptoError::Malformed)?;
+    let mut in_out = Zeroizing::new(ciphertext.to_vec());
+    let len = key
+        .open_in_place(nonce, Aad::from(aad), &mut in_out)
+        .map_err(|_| CryptoError::Decryption)?
+        .len();
+    in_out.truncate(len);
+    Ok(in_out)
+}
+
+/// A parsed envelope.
+pub struct Envelope<'a> {
+    key_id: &'a str,
+    wrapped_key: Vec<u8>,
+    ciphertext: Vec<u8>,
+}
+
+impl<'a> Envelope<'a> {
+    pub fn parse(envelope: &'a str) -> Result<Self, CryptoError> {
+        let mut parts = envelope.trim().split(':');
+        let (Some(VERSION), Some(key_id), Some(wrapped), Some(ciphertext), None) = (
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+        ) else {
+            return Err(CryptoError::Malformed);
+        };
+        let decode = |part: &str| {
+            URL_SAFE_NO_PAD
+                .decode(part)
+                .map_err(|_| CryptoError::Malformed)
+        };
+        Ok(Self {
+            key_id,
+            wrapped_key: decode(wrapped)?,
+            ciphertext: decode(ciphertext)?,
+        })
+    }
+
+    /// The master key the data key is wrapped under.
+    #[must_use]
+    pub fn key_id(&self) -> &'a str {
+        self.key_id
+    }
+
+    /// Decrypts the value with `master`, the key [`Self::key_id`] names.
+    pub fn open(
+        &self,
+        master: &MasterKey,
+        context: &str,
+    ) -> Result<Zeroizing<Vec<u8>>, CryptoError> {
+        let data_key = self.data_key(master)?;
+        open_bytes(&aes_key(&data_key)?, &self.ciphertext, context.as_bytes())
+    }
+
+    /// The same value with its data key wrapped under `to` instead.
+    pub fn rewrap(&self, master: &MasterKey, to: &MasterKey) -> Result<String, CryptoError> {
+        format_envelope(to, &self.data_key(master)?, &self.ciphertext)
+    }
+
+    fn data_key(&self, master: &MasterKey) -> Result<Zeroizing<Vec<u8>>, CryptoError> {
+        open_bytes(
+            &master.key,
+            &self.wrapped_key,
+            wrap_aad(self.key_id).as_bytes(),
+        )
+    }
+}
+
+/// The master key id an envelope names, or `None` for anything else.
+#[must_use]
+pub fn key_id(envelope: &str) -> Option<&str> {
+    Envelope::parse(envelope).ok().map(|e| e.key_id)
+}
+
+/// Values written before envelopes existed were only base64 of
+/// `"encrypted:" + plaintext`. Returns that plaintext, so it can be read
+/// until the re-encryption job encrypts it for real.
+#[must_use]
+pub fn legacy_plaintext(value: &str) -> Option<Zeroizing<Vec<u8>>> {
+    let decoded = Zeroizing::new(STANDARD.decode(value.trim()).ok()?);
+    decoded
+        .strip_prefix(LEGACY_PREFIX)
+        .map(|plaintext| Zeroizing::new(plaintext.to_vec()))
+}
+
+/// The current master key, the retired ones envelopes may still name, and
+/// the blind-index key.
+pub struct Keyring {
+    current: MasterKey,
+    previous: Vec<MasterKey>,
+    blind_index_key: BlindIndexKey,
+}
+
+/// A decrypted value and the master key it was wrapped under.
+pub struct Decrypted {
+    pub plaintext: Vec<u8>,
+    pub key_id: String,
+}
+
+impl Keyring {
+    pub fn new(
+        current: MasterKey,
+        previous: Vec<MasterKey>,
+        blind_index_key: &[u8],
+    ) -> Result<Self, CryptoError> {
+        Ok(Self {
+            current,
+            previous,
+            blind_index_key: BlindIndexKey::new(blind_index_key)?,
+        })
+    }
+
+    #[must_use]
+    pub fn current_key_id(&self) -> &str {
+        self.current.id()
+    }
+
+    /// The current key or a retired one, by id.
+    pub fn master(&self, id: &str) -> Result<&MasterKey, CryptoError> {
+        std::iter::once(&self.current)
+            .chain(&self.previous)
+            .find(|k| k.id == id)
+            .ok_or_else(|| CryptoError::UnknownKey(id.to_string()))
+    }
+
+    /// Encrypts `plaintext` under a fresh data key wrapped by the current
+    /// master key.
+    pub fn encrypt(&self, plaintext: &[u8], context: &str) -> Result<String, CryptoError> {
+        seal(&self.current, plaintext, context)
+    }
+
+    pub fn decrypt(&self, envelope: &str, context: &str) -> Result<Decrypted, CryptoError> {
+        let parts = Envelope::parse(envelope)?;
+        let mut plaintext = parts.open(self.master(parts.key_id)?, context)?;
+        Ok(Decrypted {
+            plaintext: std::mem::take(&mut *plaintext),
+            key_id: parts.key_id.to_string(),
+        })
+    }
+
+    /// Re-wraps the envelope's data key under the current master key.
+    /// Returns `None` when it already uses the current key.
+    pub fn rewrap(&self, envelope: &str) -> Result<Option<String>, CryptoError> {
+        let parts = Envelope::parse(envelope)?;
+        if parts.key_id == self.current.id {
+            return Ok(None);
+        }
+        parts
+            .rewrap(self.master(parts.key_id)?, &self.current)
+            .map(Some)
+    }
+
+    #[must_use]
+    pub fn blind_index(&self, value: &str, context: &str) -> String {
+        self.blind_index_key.index(value, context)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn master(id: &str, byte: u8) -> MasterKey {
+        MasterKey::new(id, &[byte; KEY_BYTES]).unwrap()
+    }
+
+    fn keyring(current: MasterKey, previous: Vec<MasterKey>) -> Keyring {
+        Keyring::new(current, previous, &[9; 32]).unwrap()
+    }
+
+    #[test]
+    fn round_trips_within_its_context() {
+        let keys = keyring(master("k2", 2), vec![]);
+        let sealed = keys
+            .encrypt(b"Carrascalejo 1", "pilgrims.address_street_encrypted")
+            .unwrap();
+        assert!(sealed.starts_with("v1:k2:"));
+        assert!(!sealed.contains("Carrascalejo"));
+        let opened = keys
+            .decrypt(&sealed, "pilgrims.address_street_encrypted")
+            .unwrap();
+        assert_eq!(opened.plaintext, b"Carrascalejo 1");
+        assert_eq!(opened.key_id, "k2");
+        assert_eq!(
+            keys.decrypt(&sealed, "pilgrims.phone_encrypted").err(),
+            Some(CryptoError::Decryption)
+        );
+        // Fresh data key and nonces every time.
+        assert_ne!(
+            sealed,
+            keys.encrypt(b"Carrascalejo 1", "pilgrims.address_street_encrypted")
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn rotation_rewraps_without_touching_the_ciphertext() {
+        let old = keyring(master("k1", 1), vec![]);
+        let sealed = old.encrypt(b"X1234567L", "ctx").unwrap();
+
+        let rotated = keyring(master("k2", 2), vec![master("k1", 1)]);
+        let rewrapped = rotated.rewrap(&sealed).unwrap().unwrap();
+        assert!(rewrapped.starts_with("v1:k2:"));
+        assert_eq!(sealed.rsplit(':').next(), rewrapped.rsplit(':').next());
+        assert_eq!(
+            rotated.decrypt(&rewrapped, "ctx").unwrap().plaintext,
+            b"X1234567L"
+        );
+        assert_eq!(rotated.rewrap(&rewrapped).unwrap(), None);
+
+        let forgotten = keyring(master("k3", 3), vec![]);
+        assert_eq!(
+            forgotten.decrypt(&sealed, "ctx").err(),
+            Some(CryptoError::UnknownKey("k1".to_string()))
+        );
+    }
+
+    #[test]
+    fn tampering_is_detected() {
+        let keys = keyring(master("k1", 1), vec![]);
+        let sealed = keys.encrypt(b"secret", "ctx").unwrap();
+        let (head, tail) = sealed.split_at(sealed.len() - 2);
+        let flipped = if tail.starts_with('A') { "B" } else { "A" };
+        let altered = format!("{head}{flipped}{}", &tail[1..]);
+        assert!(keys.decrypt(&altered, "ctx").is_err());
+        for bad in ["", "v1:k1", "v2:k1:AA:AA", "v1:k1:!!:AA", "v1:k1:AA:AA:AA"] {
+            assert!(keys.decrypt(bad, "ctx").is_err(), "{bad}");
+        }
+        assert!(MasterKey::new("k:1", &[0; KEY_BYTES]).is_err());
+        assert!(MasterKey::new("k1", &[0; 16]).is_err());
+        assert!(BlindIndexKey::new(&[0; 16]).is_err());
+    }
+
+    #[test]
+    fn blind_indexes_match_normalised_identifiers_per_context() {
+        let keys = keyring(master("k1", 1), vec![]);
+        let index = keys.blind_index("12345678-z", "pilgrims.document_number");
+        assert_eq!(
+            index,
+            keys.blind_index(" 12345678Z", "pilgrims.document_number")
+        );
+        assert_ne!(
+            index,
+            keys.blind_index("12345679Z", "pilgrims.document_number")
+        );
+        assert_ne!(index, keys.blind_index("12345678Z", "pilgrims.phone"));
+        // Rotating master keys does not change blind indexes.
+        assert_eq!(
+            index,
+            keyring(master("k9", 9), vec![]).blind_index("12345678Z", "pilgrims.document_number")
+        );
+    }
+
+    #[test]
+    fn reads_legacy_values() {
+        let legacy = STANDARD.encode("encrypted:Ana");
+        assert_eq!(*legacy_plaintext(&legacy).unwrap(), b"Ana");
+        assert_eq!(legacy_plaintext("QW5h"), None);
+        assert_eq!(key_id(&legacy), None);
+    }
+
+    #[test]
+    fn debug_output_hides_the_key() {
+        assert_eq!(
+            format!("{:?}", master("k1", 1)),
+            "MasterKey { id: \"k1\", .. }"
+        );
+    }
+
+    /// Values written by security-service and albergue-persistence; both
+    /// go through this crate, and each must keep reading the other's.
+    #[test]
+    fn reads_the_shared_vectors() {
+        let vectors: serde_json::Value =
+            serde_json::from_str(include_str!("../tests/fixtures/envelope_vectors.json")).unwrap();
+        let text = |value: &serde_json::Value| value.as_str().unwrap().to_string();
+        let decode = |value: &serde_json::Value| STANDARD.decode(text(value)).unwrap();
+        let current = MasterKey::new("k1", &decode(&vectors["master_keys"]["k1"])).unwrap();
+        let keys = Keyring::new(current, vec![], &decode(&vectors["blind_index_key"])).unwrap();
+
+        for vector in vectors["envelopes"].as_array().unwrap() {
+            let opened = keys
+                .decrypt(&text(&vector["value"]), &text(&vector["context"]))
+                .unwrap();
+            assert_eq!(opened.plaintext, text(&vector["plaintext"]).as_bytes());
+        }
+        for vector in vectors["blind_indexes"].as_array().unwrap() {
+            assert_eq!(
+                keys.blind_index(&text(&vector["value"]), &text(&vector["context"])),
+                text(&vector["index"])
+            );
+        }
+        for vector in vectors["legacy"].as_array().unwrap() {
+            assert_eq!(
+                *legacy_plaintext(&text(&vector["value"])).unwrap(),
+                text(&vector["plaintext"]).as_bytes()
+            );
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use anyhow::Result;
+use spin_sdk::http::{Request, Response};
+use spin_sdk::variables;
+
+use crate::guest_reviews::{self, error, moderator};
+use crate::importers::export_file::{parse_export, ExportFormat, ExportSource, ParsedExport};
+use crate::importers::{google_places, ImportError, ImportReport, ImportedReview, GOOGLE_SOURCE};
+use crate::store::ReviewStore;
+use crate::{dedup, json_response, seed, Review};
+
+/// Every publicly listed review, newest first: imported platform reviews (or
+/// the seed set until a first import has run) plus approved guest reviews.
+pub fn published_reviews() -> Vec<Review> {
+    let imported = ReviewStore::open()
+        .and_then(|store| store.list_imported())
+        .unwrap_or_else(|e| {
+            log::warn!("Failed to load imported reviews: {e}");
+            Vec::new()
+        });
+
+    let mut reviews = if imported.is_empty() {
+        seed::seed_reviews()
+    } else {
+        imported
+    };
+    reviews.extend(guest_reviews::approved_reviews());
+    reviews.sort_by(|a, b| b.date.cmp(&a.date));
+    reviews
+}
+
+/// `POST /reviews/import/{google|booking|gronze}` — staff only.
+///
+/// Google is pulled from the Places API; Booking.com and Gronze take the
+/// extranet export as the request body, CSV or JSON by `Content-Type`.
+pub async fn handle_import(req: Request, source: &str) -> Result<Response> {
+    if moderator(&req).is_none() {
+        return error(403, "Forbidden", "Moderator role required");
+    }
+
+    let parsed = if source == "google" {
+        let api_key = variables::get("google_places_api_key").unwrap_or_default();
+        let place_id = variables::get("google_place_id").unwrap_or_default();
+        google_places::fetch_reviews(&api_key, &place_id)
+            .await
+            .map(|reviews| ParsedExport {
+                reviews,
+                skipped: 0,
+            })
+    } else if let Some(export_source) = ExportSource::from_path_segment(source) {
+        let content_type = req.header("content-type").and_then(|h| h.as_str());
+        parse_export(
+            export_source,
+            ExportFormat::from_content_type(content_type),
+            req.body(),
+        )
+    } else {
+        return error(404, "Not Found", "Unknown review source");
+    };
+
+    let parsed = match parsed {
+        Ok(p) => p,
+        Err(e @ ImportError::Config(_)) => {
+            return error(503, "Service Unavailable", &e.to_string())
+        }
+        Err(e @ ImportError::Http(_)) => return error(502, "Bad Gateway", &e.to_string()),
+        Err(e @ ImportError::Parse(_)) => {
+            return error(422, "Unprocessable Entity", &e.to_string())
+        }
+    };
+
+    let store = ReviewStore::open()?;
+    let mut existing = store.list_imported()?;
+    existing.extend(guest_reviews::approved_reviews());
+
+    let fetched = u32::try_from(parsed.reviews.len()).unwrap_or(u32::MAX);
+    let incoming: Vec<Review> = parsed
+        .reviews
+        .into_iter()
+        .map(ImportedReview::into_review)
+        .collect();
+    let outcome = dedup::merge(&existing, incoming);
+    store.save_imported(&outcome.unique)?;
+
+    json_response(
+        200,
+        &ImportReport {
+            source: ExportSource::from_path_segment(source)
+                .map_or(GOOGLE_SOURCE, ExportSource::label)
+                .to_string(),
+            fetched,
+            imported: u32::try_from(outcome.unique.len()).unwrap_or(u32::MAX),
+            duplicates: outcome.duplicates,
+            skipped: parsed.skipped,
+        },
+    )
+}
@@ -0,0 +1,224 @@
+use chrono::NaiveDate;
+use std::collections::HashSet;
+
+use crate::Review;
+
+/// Word-set similarity above which two reviews are the same text, whoever posted it.
+const SAME_TEXT_THRESHOLD: f64 = 0.9;
+/// Looser similarity accepted when author and rating also match.
+const SAME_AUTHOR_THRESHOLD: f64 = 0.5;
+/// Pilgrims who cross-post usually do so within a few weeks of their stay.
+const CROSS_POST_WINDOW_DAYS: i64 = 60;
+/// Texts shorter than this ("Muy bien", "Great!") are too generic to match on.
+const MIN_TOKENS: usize = 4;
+
+#[derive(Debug, Default)]
+pub struct DedupOutcome {
+    pub unique: Vec<Review>,
+    pub duplicates: u32,
+}
+
+/// Drop incoming reviews that are already stored or repeated within the batch.
+///
+/// A re-import of the same platform review is matched by id; the same pilgrim
+/// posting on several platforms is matched on author, rating, date and text.
+pub fn merge(existing: &[Review], incoming: Vec<Review>) -> DedupOutcome {
+    let mut outcome = DedupOutcome::default();
+    for review in incoming {
+        let seen = existing
+            .iter()
+            .chain(outcome.unique.iter())
+            .any(|other| is_duplicate(&review, other));
+        if seen {
+            outcome.duplicates += 1;
+        } else {
+            outcome.unique.push(review);
+        }
+    }
+    outcome
+}
+
+pub fn is_duplicate(a: &Review, b: &Review) -> bool {
+    if a.id == b.id {
+        return true;
+    }
+
+    let (ta, tb) = (tokens(&a.text), tokens(&b.text));
+    if ta.len() < MIN_TOKENS || tb.len() < MIN_TOKENS {
+        return false;
+    }
+
+    let similarity = jaccard(&ta, &tb);
+    if similarity >= SAME_TEXT_THRESHOLD {
+        return true;
+    }
+
+    a.rating == b.rating
+        && same_author(&a.author_name, &b.author_name)
+        && within_window(&a.date, &b.date)
+        && similarity >= SAME_AUTHOR_THRESHOLD
+}
+
+/// Lowercase, strip accents and punctuation so "Muy limpio." matches "muy limpio".
+pub fn normalize(text: &str) -> String {
+    text.chars()
+        .flat_map(char::to_lowercase)
+        .map(fold_accent)
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect()
+}
+
+fn tokens(text: &str) -> HashSet<String> {
+    normalize(text)
+        .split_whitespace()
+        .map(str::to_string)
+        .collect()
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn jaccard(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    let intersection = a.intersection(b).count();
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    intersection as f64 / union as f64
+}
+
+/// Platforms abbreviate differently ("Marta R." vs "Marta Ruiz"), so compare
+/// the first name and, when both carry one, the surname's initial.
+fn same_author(a: &str, b: &str) -> bool {
+    let (a, b) = (normalize(a), normalize(b));
+    let mut wa = a.split_whitespace();
+    let mut wb = b.split_whitespace();
+    match (wa.next(), wb.next()) {
+        (Some(fa), Some(fb)) if fa == fb => match (wa.next(), wb.next()) {
+            (Some(sa), Some(sb)) => sa.chars().next() == sb.chars().next(),
+            _ => true,
+        },
+        _ => false,
+    }
+}
+
+fn within_window(a: &str, b: &str) -> bool {
+    match (
+        NaiveDate::parse_from_str(a, "%Y-%m-%d"),
+        NaiveDate::parse_from_str(b, "%Y-%m-%d"),
+    ) {
+        (Ok(a), Ok(b)) => (a - b).num_days().abs() <= CROSS_POST_WINDOW_DAYS,
+        _ => false,
+    }
+}
+
+const fn fold_accent(c: char) -> char {
+    match c {
+        'á' | 'à' | 'â' | 'ä' | 'ã' | 'å' => 'a',
+        'é' | 'è' | 'ê' | 'ë' => 'e',
+        'í' | 'ì' | 'î' | 'ï' => 'i',
+        'ó' | 'ò' | 'ô' | 'ö' | 'õ' => 'o',
+        'ú' | 'ù' | 'û' | 'ü' => 'u',
+        'ñ' => 'n',
+        'ç' => 'c',
+        _ => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn review(id: &str, author: &str, rating: u8, date: &str, text: &str, source: &str) -> Review {
+        Review {
+            id: id.to_string(),
+            author_name: author.to_string(),
+            rating,
+            text: text.to_string(),
+            date: date.to_string(),
+            source: source.to_string(),
+            verified: false,
+            helpful_count: 0,
+            language: None,
+        }
+    }
+
+    #[test]
+    fn test_reimport_matches_by_id() {
+        let stored = review(
+            "booking_1",
+            "Sarah",
+            5,
+            "2024-06-10",
+            "Great",
+            "Booking.com",
+        );
+        let again = review(
+            "booking_1",
+            "Sarah",
+            5,
+            "2024-06-10",
+            "Great",
+            "Booking.com",
+        );
+
+        let outcome = merge(&[stored], vec![again]);
+        assert!(outcome.unique.is_empty());
+        assert_eq!(outcome.duplicates, 1);
+    }
+
+    #[test]
+    fn test_cross_posted_review_is_duplicate() {
+        let google = review(
+            "google_a",
+            "Marta Ruiz",
+            4,
+            "2024-03-05",
+            "Limpio y tranquilo, aunque la cocina tiene poco menaje.",
+            "Google",
+        );
+        let gronze = review(
+            "gronze_b",
+            "Marta R.",
+            4,
+            "2024-03-02",
+            "Buena parada. Limpio y tranquilo aunque la cocina tiene poco menaje",
+            "Gronze",
+        );
+        assert!(is_duplicate(&google, &gronze));
+    }
+
+    #[test]
+    fn test_different_pilgrims_are_kept() {
+        let a = review(
+            "a",
+            "Sarah Mitchell",
+            5,
+            "2024-06-10",
+            "Clean facilities, comfortable beds, welcoming host.",
+            "Booking.com",
+        );
+        let b = review(
+            "b",
+            "Emma Johnson",
+            5,
+            "2024-06-11",
+            "Exceptional hospitality, exceeded my expectations.",
+            "Booking.com",
+        );
+        let short_a = review("c", "Ana", 5, "2024-06-10", "Muy bien", "Google");
+        let short_b = review("d", "Ana", 5, "2024-06-10", "Muy bien", "Gronze");
+
+        let outcome = merge(&[a], vec![b, short_b]);
+        assert_eq!(outcome.unique.len(), 2);
+        assert!(!is_duplicate(&short_a, &outcome.unique[1]));
+    }
+
+    #[test]
+    fn test_normalize_folds_accents() {
+        assert_eq!(
+            normalize("Ópera, ¡Niño!")
+                .split_whitespace()
+                .collect::<Vec<_>>(),
+            ["opera", "nino"]
+        );
+    }
+}
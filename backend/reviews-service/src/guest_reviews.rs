@@ -0,0 +1,293 @@
+use anyhow::Result;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use shared::event_publisher::create_publisher;
+use shared::events::{
+    topics, BookingCheckedOut, CloudEvent, ReviewModerated, ReviewSubmitted, ReviewTokenIssued,
+};
+use shared::gateway_token::{
+    verified_user_claims, verify_gateway_token, BROKER_ISSUER, GATEWAY_TOKEN_HEADER,
+    INTERNAL_TOKEN_SECRET_VAR, USER_CLAIMS_HEADER,
+};
+use shared::webhook_handler::parse_cloud_event;
+use spin_sdk::http::{Request, Response};
+
+use crate::moderation::{GuestReview, ModerationAction, ModerationStatus, ReviewSubmission};
+use crate::scan::{scan_review_text, verdict, ScanVerdict};
+use crate::store::ReviewStore;
+use crate::invitations;
+use crate::tokens::{self, TokenError};
+use crate::{json_response, ErrorResponse, Review};
+
+const SERVICE_NAME: &str = "reviews-service";
+const MODERATOR_ROLES: [&str; 2] = ["admin", "hospitalero"];
+
+/// Body `mqtt-broker-service` posts to registered webhooks.
+#[derive(Deserialize)]
+struct BrokerMessage {
+    topic: String,
+    payload: String,
+}
+
+#[derive(Serialize)]
+struct SubmissionAccepted {
+    id: String,
+    status: ModerationStatus,
+    flagged: bool,
+}
+
+#[derive(Serialize)]
+struct PendingReviewsResponse {
+    reviews: Vec<GuestReview>,
+    total_count: u32,
+}
+
+/// Approved first-party reviews, for merging into the public listings.
+pub fn approved_reviews() -> Vec<Review> {
+    ReviewStore::open()
+        .and_then(|store| store.list(ModerationStatus::Approved))
+        .map_or_else(
+            |e| {
+                log::warn!("Failed to load approved guest reviews: {e}");
+                Vec::new()
+            },
+            |reviews| reviews.into_iter().map(|r| r.review).collect(),
+        )
+}
+
+/// `POST /reviews` — guest submission redeemed with a one-time review token.
+pub async fn handle_submit(req: Request) -> Result<Response> {
+    let Ok(submission) = serde_json::from_slice::<ReviewSubmission>(req.body()) else {
+        return error(400, "Bad Request", "Invalid review payload");
+    };
+
+    if let Err(message) = submission.validate() {
+        return error(422, "Unprocessable Entity", &message);
+    }
+
+    let store = ReviewStore::open()?;
+
+    // Check the token before scanning, but only burn it once the review is accepted,
+    // so a guest whose text is refused can edit it and try again.
+    if let Err(e) = tokens::peek(store.kv(), &submission.token) {
+        return token_error(&e);
+    }
+
+    let scan = scan_review_text(&submission.text).await;
+    let scan_verdict = verdict(&scan);
+    if scan_verdict == ScanVerdict::Blocked {
+        return error(
+            422,
+            "Unprocessable Entity",
+            "Review text was rejected by content scanning",
+        );
+    }
+
+    // Redeeming is the one atomic step; the review only exists if it succeeds.
+    let token = match tokens::redeem(&submission.token) {
+        Ok(t) => t,
+        Err(e) => return token_error(&e),
+    };
+
+    let review = GuestReview::from_submission(
+        &submission,
+        &token.booking_id,
+        &token.pilgrim_id,
+        scan,
+        Utc::now(),
+    );
+    store.save(&review)?;
+
+    let event = CloudEvent::new(
+        topics::REVIEW_SUBMITTED.to_string(),
+        SERVICE_NAME.to_string(),
+        ReviewSubmitted {
+            review_id: review.review.id.clone(),
+            booking_id: review.booking_id.clone(),
+            rating: review.review.rating,
+            risk_level: review.scan.risk_level.clone(),
+        },
+    );
+    let _ = create_publisher().publish(&event).await;
+
+    json_response(
+        202,
+        &SubmissionAccepted {
+            id: review.review.id,
+            status: review.status,
+            flagged: scan_verdict == ScanVerdict::Flagged,
+        },
+    )
+}
+
+/// `GET /reviews/pending` — moderation queue, staff only.
+pub fn handle_pending(req: &Request) -> Result<Response> {
+    if moderator(req).is_none() {
+        return error(403, "Forbidden", "Moderator role required");
+    }
+
+    let reviews = ReviewStore::open()?.list(ModerationStatus::Pending)?;
+    json_response(
+        200,
+        &PendingReviewsResponse {
+            total_count: u32::try_from(reviews.len()).unwrap_or(u32::MAX),
+            reviews,
+        },
+    )
+}
+
+/// `POST /reviews/{id}/moderation` — approve, reject or reply, staff only.
+pub async fn handle_moderation(req: Request, review_id: &str) -> Result<Response> {
+    let Some(moderator) = moderator(&req) else {
+        return error(403, "Forbidden", "Moderator role required");
+    };
+
+    let Ok(action) = serde_json::from_slice::<ModerationAction>(req.body()) else {
+        return error(400, "Bad Request", "Invalid moderation action");
+    };
+
+    let store = ReviewStore::open()?;
+    let Some(mut review) = store.get(review_id)? else {
+        return error(404, "Not Found", "Review not found");
+    };
+
+    let action_name = action.as_str();
+    let moderated_by = (!moderator.is_empty()).then_some(moderator);
+    if let Err(message) = review.apply(action, moderated_by.clone(), Utc::now()) {
+        return error(409, "Conflict", &message);
+    }
+    store.save(&review)?;
+
+    let event = CloudEvent::new(
+        topics::REVIEW_MODERATED.to_string(),
+        SERVICE_NAME.to_string(),
+        ReviewModerated {
+            review_id: review.review.id.clone(),
+            action: action_name.to_string(),
+            moderated_by,
+        },
+    );
+    let _ = create_publisher().publish(&event).await;
+
+    json_response(200, &review)
+}
+
+/// `POST /reviews/events` — broker webhook; issues a review token on check-out.
+/// The subscription is part of the broker's `webhook_subscriptions` config.
+/// Only the broker, or staff going through the gateway, may call it.
+pub async fn handle_booking_event(req: Request) -> Result<Response> {
+    if !from_broker(&req) && moderator(&req).is_none() {
+        return error(
+            401,
+            "Unauthorized",
+            "Broker or staff gateway token required",
+        );
+    }
+
+    let Ok(message) = serde_json::from_slice::<BrokerMessage>(req.body()) else {
+        return error(400, "Bad Request", "Invalid broker message");
+    };
+
+    if message.topic != topics::BOOKING_CHECKED_OUT {
+        return json_response(202, &serde_json::json!({ "status": "ignored" }));
+    }
+
+    let event = parse_cloud_event(message.payload.as_bytes())?;
+    let checked_out: BookingCheckedOut = serde_json::from_value(event.data)?;
+
+    let store = ReviewStore::open()?;
+    let token = match tokens::issue(store.kv(), &checked_out.booking_id, &checked_out.pilgrim_id) {
+        Ok(t) => t,
+        Err(e) => return error(500, "Internal Server Error", &e.to_string()),
+    };
+
+    let issued = CloudEvent::new(
+        topics::REVIEW_TOKEN_ISSUED.to_string(),
+        SERVICE_NAME.to_string(),
+        ReviewTokenIssued {
+            booking_id: token.booking_id.clone(),
+            pilgrim_id: token.pilgrim_id.clone(),
+            token_sha256: token.sha256(),
+            expires_at: token.expires_at,
+        },
+    );
+    let _ = create_publisher().publish(&issued).await;
+
+    // Redelivered check-outs get the same token back, so a failed invitation
+    // is retried with the next delivery.
+    if let Err(e) = invitations::send(&token).await {
+        return error(502, "Bad Gateway", &format!("Failed to send review invitation: {e}"));
+    }
+
+    json_response(
+        201,
+        &serde_json::json!({
+            "status": "issued",
+            "booking_id": token.booking_id,
+            "expires_at": token.expires_at,
+        }),
+    )
+}
+
+/// Return the moderator's subject when the gateway-forwarded claims carry a
+/// staff role, `None` otherwise. Claims only count when the gateway token
+/// sent with them verifies.
+pub fn moderator(req: &Request) -> Option<String> {
+    let secret = spin_sdk::variables::get(INTERNAL_TOKEN_SECRET_VAR).ok()?;
+    let header = |name: &str| req.header(name).and_then(|h| h.as_str());
+    let claims = match verified_user_claims(
+        header(GATEWAY_TOKEN_HEADER),
+        header(USER_CLAIMS_HEADER),
+        secret.as_bytes(),
+        SERVICE_NAME,
+        u64::try_from(Utc::now().timestamp()).unwrap_or(0),
+    ) {
+        Ok(claims) => claims,
+        Err(e) => {
+            log::warn!("Ignoring gateway claims: {e}");
+            return None;
+        }
+    };
+    let is_staff = claims.get("roles").is_some_and(|roles| {
+        roles
+            .split(',')
+            .any(|role| MODERATOR_ROLES.contains(&role.trim()))
+    });
+    is_staff.then(|| claims.get("sub").cloned().unwrap_or_default())
+}
+
+/// Whether the request carries a token the broker signed for this service.
+fn from_broker(req: &Request) -> bool {
+    let Ok(secret) = spin_sdk::variables::get(INTERNAL_TOKEN_SECRET_VAR) else {
+        return false;
+    };
+    let Some(token) = req.header(GATEWAY_TOKEN_HEADER).and_then(|h| h.as_str()) else {
+        return false;
+    };
+    let now = u64::try_from(Utc::now().timestamp()).unwrap_or(0);
+    match verify_gateway_token(token, secret.as_bytes(), SERVICE_NAME, now) {
+        Ok(claims) => claims.iss == BROKER_ISSUER,
+        Err(e) => {
+            log::warn!("Rejecting event delivery: {e}");
+            false
+        }
+    }
+}
+
+fn token_error(e: &TokenError) -> Result<Response> {
+    match e {
+        TokenError::Unknown => error(403, "Forbidden", &e.to_string()),
+        TokenError::Expired => error(410, "Gone", &e.to_string()),
+        TokenError::Storage(_) => error(500, "Internal Server Error", &e.to_string()),
+    }
+}
+
+pub fn error(status: u16, error: &str, message: &str) -> Result<Response> {
+    json_response(
+        status,
+        &ErrorResponse {
+            error: error.to_string(),
+            message: message.to_string(),
+        },
+    )
+}
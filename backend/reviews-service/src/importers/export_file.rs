@@ -0,0 +1,327 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use std::collections::HashMap;
+
+use super::{scale_rating, stable_id, ImportError, ImportedReview, BOOKING_SOURCE, GRONZE_SOURCE};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportSource {
+    Booking,
+    Gronze,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+/// Header aliases for one platform's review export. Each field lists the
+/// column names seen across export versions and languages, lowercase.
+struct ColumnMap {
+    id: &'static [&'static str],
+    author: &'static [&'static str],
+    date: &'static [&'static str],
+    rating: &'static [&'static str],
+    /// Text columns concatenated, in order, to build the review body.
+    text: &'static [&'static [&'static str]],
+    rating_scale: f64,
+}
+
+const BOOKING_COLUMNS: ColumnMap = ColumnMap {
+    id: &[
+        "review id",
+        "review_id",
+        "reservation number",
+        "reservation_number",
+    ],
+    author: &["guest name", "guest_name", "reviewer name", "name"],
+    date: &["review date", "review_date", "date"],
+    rating: &["review score", "review_score", "score", "average score"],
+    text: &[
+        &["review title", "review_title", "title"],
+        &["positive review", "positive_review", "positive"],
+        &["negative review", "negative_review", "negative"],
+    ],
+    rating_scale: 10.0,
+};
+
+const GRONZE_COLUMNS: ColumnMap = ColumnMap {
+    id: &["id", "review_id", "id_opinion"],
+    author: &["autor", "author", "nombre", "usuario"],
+    date: &["fecha", "date"],
+    rating: &[
+        "nota",
+        "puntuacion",
+        "puntuación",
+        "valoracion",
+        "valoración",
+        "rating",
+    ],
+    text: &[
+        &["titulo", "título", "title"],
+        &["comentario", "opinion", "opinión", "text", "comment"],
+    ],
+    rating_scale: 10.0,
+};
+
+/// Placeholders Booking.com writes into empty positive/negative fields.
+const EMPTY_TEXT_MARKERS: [&str; 3] = [
+    "there are no comments available for this review",
+    "no hay comentarios disponibles para esta opinión",
+    "n/a",
+];
+
+const DATE_FORMATS: [&str; 4] = ["%Y-%m-%d", "%d/%m/%Y", "%d-%m-%Y", "%Y/%m/%d"];
+const DATETIME_FORMATS: [&str; 2] = ["%Y-%m-%d %H:%M:%S", "%d/%m/%Y %H:%M"];
+
+impl ExportSource {
+    pub fn from_path_segment(segment: &str) -> Option<Self> {
+        match segment {
+            "booking" => Some(Self::Booking),
+            "gronze" => Some(Self::Gronze),
+            _ => None,
+        }
+    }
+
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Booking => BOOKING_SOURCE,
+            Self::Gronze => GRONZE_SOURCE,
+        }
+    }
+
+    const fn columns(self) -> &'static ColumnMap {
+        match self {
+            Self::Booking => &BOOKING_COLUMNS,
+            Self::Gronze => &GRONZE_COLUMNS,
+        }
+    }
+}
+
+impl ExportFormat {
+    /// Pick the format from the upload's `Content-Type`, defaulting to JSON.
+    pub fn from_content_type(content_type: Option<&str>) -> Self {
+        match content_type {
+            Some(ct) if ct.contains("csv") => Self::Csv,
+            _ => Self::Json,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ParsedExport {
+    pub reviews: Vec<ImportedReview>,
+    /// Rows without a usable date or score.
+    pub skipped: u32,
+}
+
+/// Parse a review export downloaded from the platform's partner extranet.
+pub fn parse_export(
+    source: ExportSource,
+    format: ExportFormat,
+    body: &[u8],
+) -> Result<ParsedExport, ImportError> {
+    let rows = match format {
+        ExportFormat::Csv => csv_rows(body)?,
+        ExportFormat::Json => json_rows(body)?,
+    };
+
+    let columns = source.columns();
+    let mut parsed = ParsedExport::default();
+    for row in &rows {
+        match map_row(source, columns, row) {
+            Some(review) => parsed.reviews.push(review),
+            None => parsed.skipped += 1,
+        }
+    }
+    Ok(parsed)
+}
+
+fn map_row(
+    source: ExportSource,
+    columns: &ColumnMap,
+    row: &HashMap<String, String>,
+) -> Option<ImportedReview> {
+    let date = parse_date(field(row, columns.date)?)?;
+    let score: f64 = field(row, columns.rating)?.replace(',', ".").parse().ok()?;
+    let rating = scale_rating(score, columns.rating_scale)?;
+    let author_name = field(row, columns.author)
+        .unwrap_or("Anonymous")
+        .to_string();
+
+    let text = columns
+        .text
+        .iter()
+        .filter_map(|aliases| field(row, aliases))
+        .filter(|t| !EMPTY_TEXT_MARKERS.contains(&t.to_lowercase().as_str()))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let date_str = date.to_string();
+    let external_id = field(row, columns.id).map_or_else(
+        || stable_id(&[&author_name, &date_str, &text]),
+        ToString::to_string,
+    );
+
+    Some(ImportedReview {
+        source: source.label(),
+        external_id,
+        author_name,
+        rating,
+        text,
+        date,
+        language_hint: None,
+    })
+}
+
+/// First non-empty value among a field's aliases.
+fn field<'a>(row: &'a HashMap<String, String>, aliases: &[&str]) -> Option<&'a str> {
+    aliases
+        .iter()
+        .filter_map(|alias| row.get(*alias))
+        .map(|v| v.trim())
+        .find(|v| !v.is_empty())
+}
+
+fn parse_date(value: &str) -> Option<NaiveDate> {
+    DATE_FORMATS
+        .iter()
+        .find_map(|f| NaiveDate::parse_from_str(value, f).ok())
+        .or_else(|| {
+            DATETIME_FORMATS
+                .iter()
+                .find_map(|f| NaiveDateTime::parse_from_str(value, f).ok())
+                .map(|dt| dt.date())
+        })
+        .or_else(|| {
+            DateTime::parse_from_rfc3339(value)
+                .ok()
+                .map(|dt| dt.date_naive())
+        })
+}
+
+fn csv_rows(body: &[u8]) -> Result<Vec<HashMap<String, String>>, ImportError> {
+    // Extranet exports are opened and re-saved in spreadsheet tools often enough
+    // that both separators show up; sniff it from the header line.
+    let header_line =
+        String::from_utf8_lossy(body.split(|b| *b == b'\n').next().unwrap_or_default());
+    let delimiter = if header_line.matches(';').count() > header_line.matches(',').count() {
+        b';'
+    } else {
+        b','
+    };
+
+    let body = body.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(body);
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .from_reader(body);
+
+    let headers: Vec<String> = reader
+        .headers()
+        .map_err(|e| ImportError::Parse(e.to_string()))?
+        .iter()
+        .map(|h| h.trim().to_lowercase())
+        .collect();
+
+    reader
+        .records()
+        .map(|record| {
+            let record = record.map_err(|e| ImportError::Parse(e.to_string()))?;
+            Ok(headers
+                .iter()
+                .cloned()
+                .zip(record.iter().map(str::to_string))
+                .collect())
+        })
+        .collect()
+}
+
+fn json_rows(body: &[u8]) -> Result<Vec<HashMap<String, String>>, ImportError> {
+    let value: serde_json::Value =
+        serde_json::from_slice(body).map_err(|e| ImportError::Parse(e.to_string()))?;
+
+    // Accept a bare array or an object wrapping it under `reviews`.
+    let items = match value {
+        serde_json::Value::Array(items) => items,
+        serde_json::Value::Object(mut obj) => match obj.remove("reviews") {
+            Some(serde_json::Value::Array(items)) => items,
+            _ => return Err(ImportError::Parse("Expected a `reviews` array".to_string())),
+        },
+        _ => {
+            return Err(ImportError::Parse(
+                "Expected a JSON array of reviews".to_string(),
+            ))
+        }
+    };
+
+    Ok(items
+        .into_iter()
+        .filter_map(|item| match item {
+            serde_json::Value::Object(obj) => Some(
+                obj.into_iter()
+                    .filter_map(|(k, v)| {
+                        let v = match v {
+                            serde_json::Value::String(s) => s,
+                            serde_json::Value::Number(n) => n.to_string(),
+                            serde_json::Value::Bool(b) => b.to_string(),
+                            _ => return None,
+                        };
+                        Some((k.trim().to_lowercase(), v))
+                    })
+                    .collect(),
+            ),
+            _ => None,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_booking_csv_fixture() {
+        let body = include_bytes!("../../tests/fixtures/booking_reviews.csv");
+        let parsed = parse_export(ExportSource::Booking, ExportFormat::Csv, body).unwrap();
+
+        assert_eq!(parsed.reviews.len(), 3);
+        assert_eq!(parsed.skipped, 1);
+
+        let first = &parsed.reviews[0];
+        assert_eq!(first.source, BOOKING_SOURCE);
+        assert_eq!(first.external_id, "4012345678");
+        assert_eq!(first.rating, 5);
+        assert_eq!(first.date.to_string(), "2024-06-10");
+        assert!(!first.text.contains("no comments available"));
+    }
+
+    #[test]
+    fn test_parse_gronze_json_fixture() {
+        let body = include_bytes!("../../tests/fixtures/gronze_reviews.json");
+        let parsed = parse_export(ExportSource::Gronze, ExportFormat::Json, body).unwrap();
+
+        assert_eq!(parsed.reviews.len(), 2);
+        assert_eq!(parsed.reviews[0].source, GRONZE_SOURCE);
+        assert_eq!(parsed.reviews[0].rating, 5);
+        assert_eq!(parsed.reviews[1].rating, 4);
+        assert_eq!(parsed.reviews[1].date.to_string(), "2024-03-02");
+    }
+
+    #[test]
+    fn test_semicolon_csv_without_ids() {
+        let body = "Fecha;Autor;Nota;Comentario\n02/03/2024;Lucía;8;Muy buen sitio\n";
+        let parsed =
+            parse_export(ExportSource::Gronze, ExportFormat::Csv, body.as_bytes()).unwrap();
+
+        assert_eq!(parsed.reviews.len(), 1);
+        assert_eq!(parsed.reviews[0].external_id.len(), 16);
+        assert_eq!(parsed.reviews[0].rating, 4);
+    }
+
+    #[test]
+    fn test_rejects_malformed_json() {
+        let result = parse_export(ExportSource::Booking, ExportFormat::Json, b"{\"foo\": 1}");
+        assert!(matches!(result, Err(ImportError::Parse(_))));
+    }
+}
@@ -0,0 +1,170 @@
+use chrono::DateTime;
+use serde::Deserialize;
+use spin_sdk::http::{Method, Request, Response};
+use std::fmt::Write;
+
+use super::{ImportError, ImportedReview, GOOGLE_SOURCE};
+
+const PLACE_DETAILS_URL: &str = "https://maps.googleapis.com/maps/api/place/details/json";
+
+#[derive(Deserialize)]
+struct PlaceDetailsResponse {
+    status: String,
+    #[serde(default)]
+    error_message: Option<String>,
+    #[serde(default)]
+    result: Option<PlaceResult>,
+}
+
+#[derive(Deserialize)]
+struct PlaceResult {
+    #[serde(default)]
+    reviews: Vec<PlaceReview>,
+}
+
+#[derive(Deserialize)]
+struct PlaceReview {
+    author_name: String,
+    #[serde(default)]
+    author_url: Option<String>,
+    rating: u8,
+    #[serde(default)]
+    text: String,
+    time: i64,
+    #[serde(default)]
+    language: Option<String>,
+    #[serde(default)]
+    original_language: Option<String>,
+}
+
+/// Fetch the reviews Google exposes for a place through the Place Details API.
+///
+/// Google returns at most five reviews per call; repeated imports accumulate
+/// them over time since stored reviews are keyed by a stable external id.
+pub async fn fetch_reviews(
+    api_key: &str,
+    place_id: &str,
+) -> Result<Vec<ImportedReview>, ImportError> {
+    if api_key.is_empty() || place_id.is_empty() {
+        return Err(ImportError::Config(
+            "google_places_api_key and google_place_id must be set".to_string(),
+        ));
+    }
+
+    let url = place_details_url(api_key, place_id);
+    let request = Request::builder().method(Method::Get).uri(url).build();
+
+    let response: Response = spin_sdk::http::send(request)
+        .await
+        .map_err(|e| ImportError::Http(e.to_string()))?;
+    if *response.status() != 200 {
+        return Err(ImportError::Http(format!(
+            "Place Details returned status {}",
+            response.status()
+        )));
+    }
+
+    parse_place_details(response.body())
+}
+
+fn place_details_url(api_key: &str, place_id: &str) -> String {
+    format!(
+        "{PLACE_DETAILS_URL}?place_id={}&fields=reviews&reviews_no_translations=true&key={}",
+        encode_query_value(place_id),
+        encode_query_value(api_key)
+    )
+}
+
+/// Percent-encodes everything but RFC 3986 unreserved characters.
+fn encode_query_value(value: &str) -> String {
+    value.bytes().fold(String::with_capacity(value.len()), |mut out, b| {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~') {
+            out.push(char::from(b));
+        } else {
+            let _ = write!(out, "%{b:02X}");
+        }
+        out
+    })
+}
+
+/// Parse a Place Details response body into importable reviews.
+pub fn parse_place_details(body: &[u8]) -> Result<Vec<ImportedReview>, ImportError> {
+    let details: PlaceDetailsResponse =
+        serde_json::from_slice(body).map_err(|e| ImportError::Parse(e.to_string()))?;
+
+    match details.status.as_str() {
+        "OK" => {}
+        "ZERO_RESULTS" => return Ok(Vec::new()),
+        status => {
+            return Err(ImportError::Http(format!(
+                "Place Details status {status}: {}",
+                details.error_message.unwrap_or_default()
+            )))
+        }
+    }
+
+    let reviews = details.result.map(|r| r.reviews).unwrap_or_default();
+    Ok(reviews
+        .into_iter()
+        .filter(|r| (1..=5).contains(&r.rating))
+        .filter_map(|r| {
+            let date = DateTime::from_timestamp(r.time, 0)?.date_naive();
+            // Google has no review id in this API; author URL plus timestamp is stable.
+            let external_id = super::stable_id(&[
+                r.author_url.as_deref().unwrap_or(&r.author_name),
+                &r.time.to_string(),
+            ]);
+            Some(ImportedReview {
+                source: GOOGLE_SOURCE,
+                external_id,
+                author_name: r.author_name,
+                rating: r.rating,
+                text: r.text.trim().to_string(),
+                date,
+                language_hint: r.original_language.or(r.language),
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_fixture() {
+        let body = include_bytes!("../../tests/fixtures/google_place_details.json");
+        let reviews = parse_place_details(body).unwrap();
+
+        assert_eq!(reviews.len(), 3);
+        assert_eq!(reviews[0].author_name, "María González");
+        assert_eq!(reviews[0].rating, 5);
+        assert_eq!(reviews[0].date.to_string(), "2024-06-15");
+        assert_eq!(reviews[0].language_hint.as_deref(), Some("es"));
+        assert_eq!(
+            reviews[0].external_id,
+            parse_place_details(body).unwrap()[0].external_id
+        );
+    }
+
+    #[test]
+    fn test_place_details_url_encodes_values() {
+        assert_eq!(
+            place_details_url("k+y/=", "ChIJ a&b=c"),
+            format!(
+                "{PLACE_DETAILS_URL}?place_id=ChIJ%20a%26b%3Dc&fields=reviews\
+                 &reviews_no_translations=true&key=k%2By%2F%3D"
+            )
+        );
+    }
+
+    #[test]
+    fn test_api_error_status() {
+        let body =
+            br#"{"status":"REQUEST_DENIED","error_message":"The provided API key is invalid."}"#;
+        assert!(matches!(
+            parse_place_details(body),
+            Err(ImportError::Http(_))
+        ));
+    }
+}
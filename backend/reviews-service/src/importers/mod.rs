@@ -0,0 +1,121 @@
+pub mod export_file;
+pub mod google_places;
+
+use chrono::NaiveDate;
+use serde::Serialize;
+
+use crate::language::detect_language_or;
+use crate::Review;
+
+pub const GOOGLE_SOURCE: &str = "Google";
+pub const BOOKING_SOURCE: &str = "Booking.com";
+pub const GRONZE_SOURCE: &str = "Gronze";
+
+#[derive(Debug)]
+pub enum ImportError {
+    Config(String),
+    Http(String),
+    Parse(String),
+}
+
+impl std::fmt::Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Config(e) => write!(f, "Import configuration error: {e}"),
+            Self::Http(e) => write!(f, "Import request failed: {e}"),
+            Self::Parse(e) => write!(f, "Import parse error: {e}"),
+        }
+    }
+}
+
+/// A review as read from an external platform, before it is stored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportedReview {
+    pub source: &'static str,
+    pub external_id: String,
+    pub author_name: String,
+    pub rating: u8,
+    pub text: String,
+    pub date: NaiveDate,
+    pub language_hint: Option<String>,
+}
+
+impl ImportedReview {
+    pub fn into_review(self) -> Review {
+        let language = detect_language_or(&self.text, self.language_hint.as_deref());
+        Review {
+            id: format!("{}_{}", id_prefix(self.source), self.external_id),
+            author_name: self.author_name,
+            rating: self.rating,
+            text: self.text,
+            date: self.date.format("%Y-%m-%d").to_string(),
+            // Booking.com only accepts reviews from guests with a completed reservation.
+            verified: self.source == BOOKING_SOURCE,
+            source: self.source.to_string(),
+            helpful_count: 0,
+            language,
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ImportReport {
+    pub source: String,
+    pub fetched: u32,
+    pub imported: u32,
+    pub duplicates: u32,
+    pub skipped: u32,
+}
+
+fn id_prefix(source: &str) -> &'static str {
+    match source {
+        GOOGLE_SOURCE => "google",
+        BOOKING_SOURCE => "booking",
+        GRONZE_SOURCE => "gronze",
+        _ => "external",
+    }
+}
+
+/// Convert a score on a `0..=scale_max` scale to the 1–5 stars used on the site.
+pub fn scale_rating(score: f64, scale_max: f64) -> Option<u8> {
+    if !score.is_finite() || score < 0.0 || score > scale_max || scale_max <= 0.0 {
+        return None;
+    }
+    let stars = (score / scale_max * 5.0).round().clamp(1.0, 5.0);
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    Some(stars as u8)
+}
+
+/// Stable FNV-1a hash, used to derive ids for export rows that don't carry one.
+pub fn stable_id(parts: &[&str]) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for part in parts {
+        for byte in part.bytes().chain(std::iter::once(0x1f)) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    format!("{hash:016x}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scale_rating() {
+        assert_eq!(scale_rating(10.0, 10.0), Some(5));
+        assert_eq!(scale_rating(8.8, 10.0), Some(4));
+        assert_eq!(scale_rating(9.0, 10.0), Some(5));
+        assert_eq!(scale_rating(1.0, 10.0), Some(1));
+        assert_eq!(scale_rating(4.0, 5.0), Some(4));
+        assert_eq!(scale_rating(11.0, 10.0), None);
+        assert_eq!(scale_rating(f64::NAN, 10.0), None);
+    }
+
+    #[test]
+    fn test_stable_id_is_deterministic() {
+        assert_eq!(stable_id(&["a", "b"]), stable_id(&["a", "b"]));
+        assert_ne!(stable_id(&["ab", ""]), stable_id(&["a", "b"]));
+    }
+}
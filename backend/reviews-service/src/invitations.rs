@@ -0,0 +1,51 @@
+//! Delivery of review tokens to guests.
+//!
+//! The token is a credential, so it only travels to notification-service,
+//! which looks up the pilgrim's contact details and sends the review link.
+//! Events about it carry [`ReviewToken::sha256`] instead.
+
+use shared::gateway_token::{
+    sign_service_token, GATEWAY_TOKEN_HEADER, INTERNAL_TOKEN_SECRET_VAR, REVIEWS_ISSUER,
+};
+use spin_sdk::http::{Method, Request, Response};
+
+use crate::tokens::ReviewToken;
+
+const INVITATION_URL: &str = "http://notification-service.spin.internal/send/review-invitation";
+const NOTIFICATION_SERVICE: &str = "notification-service";
+
+/// Asks notification-service to send the guest their review link.
+pub async fn send(token: &ReviewToken) -> Result<(), String> {
+    let secret = spin_sdk::variables::get(INTERNAL_TOKEN_SECRET_VAR)
+        .ok()
+        .filter(|secret| !secret.is_empty())
+        .ok_or("internal token secret not configured")?;
+    let service_token = sign_service_token(
+        secret.as_bytes(),
+        REVIEWS_ISSUER,
+        NOTIFICATION_SERVICE,
+        &uuid::Uuid::new_v4().to_string(),
+        u64::try_from(chrono::Utc::now().timestamp()).unwrap_or(0),
+    );
+    let body = serde_json::json!({
+        "booking_id": token.booking_id,
+        "pilgrim_id": token.pilgrim_id,
+        "token": token.token,
+        "expires_at": token.expires_at,
+    });
+    let request = Request::builder()
+        .method(Method::Post)
+        .uri(INVITATION_URL)
+        .header("content-type", "application/json")
+        .header(GATEWAY_TOKEN_HEADER, service_token)
+        .body(body.to_string())
+        .build();
+    match spin_sdk::http::send::<_, Response>(request).await {
+        Ok(response) if *response.status() == 200 => Ok(()),
+        Ok(response) => Err(format!(
+            "notification-service returned status {}",
+            response.status()
+        )),
+        Err(e) => Err(format!("notification-service unreachable: {e}")),
+    }
+}
@@ -0,0 +1,75 @@
+use whatlang::{Detector, Lang};
+
+/// Languages offered by the gateway's language picker, as ISO 639-1 codes.
+const SUPPORTED_LANGUAGES: [(Lang, &str); 12] = [
+    (Lang::Spa, "es"),
+    (Lang::Eng, "en"),
+    (Lang::Fra, "fr"),
+    (Lang::Deu, "de"),
+    (Lang::Ita, "it"),
+    (Lang::Por, "pt"),
+    (Lang::Nld, "nl"),
+    (Lang::Pol, "pl"),
+    (Lang::Jpn, "ja"),
+    (Lang::Kor, "ko"),
+    (Lang::Cmn, "zh"),
+    (Lang::Rus, "ru"),
+];
+
+/// Below this confidence the provider's own language hint, if any, wins.
+const MIN_CONFIDENCE: f64 = 0.3;
+
+/// Detect the language of a review, returning an ISO 639-1 code.
+///
+/// Detection is restricted to the languages the site is translated into, which
+/// keeps short pilgrim reviews from being classified as a close neighbour
+/// (e.g. Galician or Catalan for Spanish).
+pub fn detect_language(text: &str) -> Option<String> {
+    let detector = Detector::with_allowlist(SUPPORTED_LANGUAGES.iter().map(|(l, _)| *l).collect());
+    let info = detector.detect(text)?;
+    if info.confidence() < MIN_CONFIDENCE {
+        return None;
+    }
+    SUPPORTED_LANGUAGES
+        .iter()
+        .find(|(lang, _)| *lang == info.lang())
+        .map(|(_, code)| (*code).to_string())
+}
+
+/// Detect the language, falling back to a hint supplied by the source platform.
+pub fn detect_language_or(text: &str, hint: Option<&str>) -> Option<String> {
+    detect_language(text).or_else(|| {
+        let hint = hint?.split(['-', '_']).next()?.to_ascii_lowercase();
+        SUPPORTED_LANGUAGES
+            .iter()
+            .any(|(_, code)| *code == hint)
+            .then_some(hint)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detects_pilgrim_languages() {
+        let cases = [
+            ("Excelente albergue en El Carrascalejo. Muy limpio, camas cómodas y el hospitalero muy amable.", "es"),
+            ("Perfect stop on the Camino! Clean facilities, comfortable beds, and the host was incredibly welcoming.", "en"),
+            ("Bon accueil, équipements corrects. Village tranquille pour se reposer.", "fr"),
+            ("Wunderbare Herberge! Sehr sauber, gute Ausstattung und herzlicher Empfang.", "de"),
+            ("Bom albergue para peregrinos. Quartos limpos, boa localização no Carrascalejo.", "pt"),
+        ];
+
+        for (text, expected) in cases {
+            assert_eq!(detect_language(text).as_deref(), Some(expected), "{text}");
+        }
+    }
+
+    #[test]
+    fn test_falls_back_to_hint() {
+        assert_eq!(detect_language_or("", Some("it-IT")).as_deref(), Some("it"));
+        assert_eq!(detect_language_or("", Some("gl")), None);
+        assert_eq!(detect_language_or("", None), None);
+    }
+}
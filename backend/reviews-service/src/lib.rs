@@ -4,18 +4,36 @@
     clippy::module_name_repetitions,
     clippy::must_use_candidate,
     clippy::missing_errors_doc,
-    clippy::missing_panics_doc
+    clippy::missing_panics_doc,
+    // Spin's http component executor is not Send; allow this lint for WASM components.
+    clippy::future_not_send,
+    // Raised inside the bindings `http_component` generates.
+    clippy::same_length_and_capacity
 )]
 
+mod aggregation;
+mod dedup;
+mod guest_reviews;
+mod importers;
+mod invitations;
+mod language;
+mod moderation;
+mod scan;
+mod seed;
+mod stats;
+mod store;
+mod tokens;
+
 use anyhow::Result;
+use chrono::Utc;
 use serde::{Deserialize, Serialize};
 use spin_sdk::{
-    http::{Request, Response},
+    http::{Method, Request, Response},
     http_component,
 };
 use std::collections::HashMap;
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Review {
     pub id: String,
     pub author_name: String,
@@ -25,6 +43,9 @@ pub struct Review {
     pub source: String,
     pub verified: bool,
     pub helpful_count: u32,
+    /// ISO 639-1 code, detected on import or submission.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -43,14 +64,14 @@ pub struct ErrorResponse {
 
 #[http_component]
 async fn handle_request(req: Request) -> Result<Response, anyhow::Error> {
-    let _uri = req.uri().to_string();
-    let path = req.uri();
+    let method = req.method().clone();
+    let path = req.path().to_string();
 
     // Enable CORS
     let mut response_builder = Response::builder();
     response_builder
         .header("Access-Control-Allow-Origin", "*")
-        .header("Access-Control-Allow-Methods", "GET, OPTIONS")
+        .header("Access-Control-Allow-Methods", "GET, POST, OPTIONS")
         .header(
             "Access-Control-Allow-Headers",
             "Content-Type, Authorization",
@@ -60,11 +81,26 @@ async fn handle_request(req: Request) -> Result<Response, anyhow::Error> {
         return Ok(response_builder.status(200).body(()).build());
     }
 
-    let response = match path {
-        "/reviews/google" => handle_google_reviews().await?,
-        "/reviews/booking" => handle_booking_reviews().await?,
-        "/reviews/all" => handle_all_reviews().await?,
-        "/reviews/stats" => handle_review_stats().await?,
+    let response = match (&method, path.as_str()) {
+        (Method::Get, "/reviews/google") => handle_google_reviews()?,
+        (Method::Get, "/reviews/booking") => handle_booking_reviews()?,
+        (Method::Get, "/reviews/gronze") => handle_source_reviews(importers::GRONZE_SOURCE)?,
+        (Method::Get, "/reviews/all") => handle_all_reviews()?,
+        (Method::Get, "/reviews/stats") => handle_review_stats()?,
+        (Method::Get, "/reviews/pending") => guest_reviews::handle_pending(&req)?,
+        (Method::Post, "/reviews") => guest_reviews::handle_submit(req).await?,
+        (Method::Post, "/reviews/events") => guest_reviews::handle_booking_event(req).await?,
+        (Method::Post, p) if p.starts_with("/reviews/import/") => {
+            let source = p.trim_start_matches("/reviews/import/").to_string();
+            aggregation::handle_import(req, &source).await?
+        }
+        (Method::Post, p) if p.starts_with("/reviews/") && p.ends_with("/moderation") => {
+            let id = p
+                .trim_start_matches("/reviews/")
+                .trim_end_matches("/moderation")
+                .to_string();
+            guest_reviews::handle_moderation(req, &id).await?
+        }
         _ => Response::builder()
             .status(404)
             .header("Content-Type", "application/json")
@@ -78,226 +114,55 @@ async fn handle_request(req: Request) -> Result<Response, anyhow::Error> {
     Ok(response)
 }
 
-async fn handle_google_reviews() -> Result<Response> {
-    let google_reviews = vec![
-        Review {
-            id: "google_1".to_string(),
-            author_name: "María González".to_string(),
-            rating: 5,
-            text: "Excelente albergue en El Carrascalejo. Muy limpio, camas cómodas y el hospitalero muy amable. Perfecto para peregrinos del Camino de Santiago.".to_string(),
-            date: "2024-06-15".to_string(),
-            source: "Google".to_string(),
-            verified: true,
-            helpful_count: 12,
-        },
-        Review {
-            id: "google_2".to_string(),
-            author_name: "Jean-Pierre Dubois".to_string(),
-            rating: 4,
-            text: "Bon accueil, équipements corrects. Village tranquille pour se reposer. Je recommande pour une étape sur le Camino.".to_string(),
-            date: "2024-05-28".to_string(),
-            source: "Google".to_string(),
-            verified: true,
-            helpful_count: 8,
-        },
-        Review {
-            id: "google_3".to_string(),
-            author_name: "Klaus Weber".to_string(),
-            rating: 5,
-            text: "Wunderbare Herberge! Sehr sauber, gute Ausstattung und herzlicher Empfang. El Carrascalejo ist ein perfekter Zwischenstopp.".to_string(),
-            date: "2024-04-20".to_string(),
-            source: "Google".to_string(),
-            verified: true,
-            helpful_count: 15,
-        }
-    ];
-
-    let response = ReviewsResponse {
-        reviews: google_reviews.clone(),
-        total_count: google_reviews.len() as u32,
-        average_rating: calculate_average_rating(&google_reviews),
-        source_breakdown: create_source_breakdown(&google_reviews),
-    };
-
-    Ok(Response::builder()
-        .status(200)
-        .header("Content-Type", "application/json")
-        .body(serde_json::to_string(&response)?)
-        .build())
+fn handle_google_reviews() -> Result<Response> {
+    handle_source_reviews(importers::GOOGLE_SOURCE)
 }
 
-async fn handle_booking_reviews() -> Result<Response> {
-    let booking_reviews = vec![
-        Review {
-            id: "booking_1".to_string(),
-            author_name: "Sarah Mitchell".to_string(),
-            rating: 5,
-            text: "Perfect stop on the Camino! Clean facilities, comfortable beds, and the host was incredibly welcoming. Highly recommend this albergue.".to_string(),
-            date: "2024-06-10".to_string(),
-            source: "Booking.com".to_string(),
-            verified: true,
-            helpful_count: 9,
-        },
-        Review {
-            id: "booking_2".to_string(),
-            author_name: "Antonio Silva".to_string(),
-            rating: 4,
-            text: "Bom albergue para peregrinos. Quartos limpos, boa localização no Carrascalejo. Staff simpático e prestável.".to_string(),
-            date: "2024-05-15".to_string(),
-            source: "Booking.com".to_string(),
-            verified: true,
-            helpful_count: 6,
-        },
-        Review {
-            id: "booking_3".to_string(),
-            author_name: "Emma Johnson".to_string(),
-            rating: 5,
-            text: "Exceptional hospitality! The albergue exceeded my expectations. Clean, comfortable, and the perfect place to rest during the pilgrimage.".to_string(),
-            date: "2024-04-05".to_string(),
-            source: "Booking.com".to_string(),
-            verified: true,
-            helpful_count: 11,
-        }
-    ];
-
-    let response = ReviewsResponse {
-        reviews: booking_reviews.clone(),
-        total_count: booking_reviews.len() as u32,
-        average_rating: calculate_average_rating(&booking_reviews),
-        source_breakdown: create_source_breakdown(&booking_reviews),
-    };
-
-    Ok(Response::builder()
-        .status(200)
-        .header("Content-Type", "application/json")
-        .body(serde_json::to_string(&response)?)
-        .build())
+fn handle_booking_reviews() -> Result<Response> {
+    handle_source_reviews(importers::BOOKING_SOURCE)
 }
 
-async fn handle_all_reviews() -> Result<Response> {
-    // Combine Google and Booking.com reviews
-    let mut all_reviews = Vec::new();
-
-    // Add Google reviews
-    let google_reviews = vec![
-        Review {
-            id: "google_1".to_string(),
-            author_name: "María González".to_string(),
-            rating: 5,
-            text: "Excelente albergue en El Carrascalejo. Muy limpio, camas cómodas y el hospitalero muy amable.".to_string(),
-            date: "2024-06-15".to_string(),
-            source: "Google".to_string(),
-            verified: true,
-            helpful_count: 12,
-        },
-        Review {
-            id: "google_2".to_string(),
-            author_name: "Jean-Pierre Dubois".to_string(),
-            rating: 4,
-            text: "Bon accueil, équipements corrects. Village tranquille pour se reposer.".to_string(),
-            date: "2024-05-28".to_string(),
-            source: "Google".to_string(),
-            verified: true,
-            helpful_count: 8,
-        },
-        Review {
-            id: "google_3".to_string(),
-            author_name: "Klaus Weber".to_string(),
-            rating: 5,
-            text: "Wunderbare Herberge! Sehr sauber, gute Ausstattung und herzlicher Empfang.".to_string(),
-            date: "2024-04-20".to_string(),
-            source: "Google".to_string(),
-            verified: true,
-            helpful_count: 15,
-        }
-    ];
-
-    // Add Booking.com reviews
-    let booking_reviews = vec![
-        Review {
-            id: "booking_1".to_string(),
-            author_name: "Sarah Mitchell".to_string(),
-            rating: 5,
-            text: "Perfect stop on the Camino! Clean facilities, comfortable beds, and the host was incredibly welcoming.".to_string(),
-            date: "2024-06-10".to_string(),
-            source: "Booking.com".to_string(),
-            verified: true,
-            helpful_count: 9,
-        },
-        Review {
-            id: "booking_2".to_string(),
-            author_name: "Antonio Silva".to_string(),
-            rating: 4,
-            text: "Bom albergue para peregrinos. Quartos limpos, boa localização no Carrascalejo.".to_string(),
-            date: "2024-05-15".to_string(),
-            source: "Booking.com".to_string(),
-            verified: true,
-            helpful_count: 6,
-        },
-        Review {
-            id: "booking_3".to_string(),
-            author_name: "Emma Johnson".to_string(),
-            rating: 5,
-            text: "Exceptional hospitality! The albergue exceeded my expectations. Clean, comfortable, and perfect for pilgrims.".to_string(),
-            date: "2024-04-05".to_string(),
-            source: "Booking.com".to_string(),
-            verified: true,
-            helpful_count: 11,
-        }
-    ];
-
-    all_reviews.extend(google_reviews);
-    all_reviews.extend(booking_reviews);
-
-    // Sort by date (most recent first)
-    all_reviews.sort_by(|a, b| b.date.cmp(&a.date));
+fn handle_source_reviews(source: &str) -> Result<Response> {
+    let reviews: Vec<Review> = aggregation::published_reviews()
+        .into_iter()
+        .filter(|r| r.source == source)
+        .collect();
+    json_response(200, &reviews_response(reviews))
+}
 
-    let response = ReviewsResponse {
-        reviews: all_reviews.clone(),
-        total_count: all_reviews.len() as u32,
-        average_rating: calculate_average_rating(&all_reviews),
-        source_breakdown: create_source_breakdown(&all_reviews),
-    };
+fn handle_all_reviews() -> Result<Response> {
+    json_response(200, &reviews_response(aggregation::published_reviews()))
+}
 
-    Ok(Response::builder()
-        .status(200)
-        .header("Content-Type", "application/json")
-        .body(serde_json::to_string(&response)?)
-        .build())
+fn handle_review_stats() -> Result<Response> {
+    let reviews = aggregation::published_reviews();
+    json_response(200, &stats::compute(&reviews, Utc::now().date_naive()))
 }
 
-async fn handle_review_stats() -> Result<Response> {
-    let stats = serde_json::json!({
-        "total_reviews": 6,
-        "average_rating": 4.7,
-        "rating_distribution": {
-            "5": 4,
-            "4": 2,
-            "3": 0,
-            "2": 0,
-            "1": 0
-        },
-        "sources": {
-            "Google": 3,
-            "Booking.com": 3
-        },
-        "verified_percentage": 100.0,
-        "recent_reviews": 3
-    });
+fn reviews_response(reviews: Vec<Review>) -> ReviewsResponse {
+    ReviewsResponse {
+        total_count: u32::try_from(reviews.len()).unwrap_or(u32::MAX),
+        average_rating: calculate_average_rating(&reviews),
+        source_breakdown: create_source_breakdown(&reviews),
+        reviews,
+    }
+}
 
+fn json_response<T: Serialize>(status: u16, body: &T) -> Result<Response> {
     Ok(Response::builder()
-        .status(200)
+        .status(status)
         .header("Content-Type", "application/json")
-        .body(stats.to_string())
+        .body(serde_json::to_string(body)?)
         .build())
 }
 
+#[allow(clippy::cast_precision_loss)]
 fn calculate_average_rating(reviews: &[Review]) -> f32 {
     if reviews.is_empty() {
         return 0.0;
     }
 
-    let total: u32 = reviews.iter().map(|r| r.rating as u32).sum();
+    let total: u32 = reviews.iter().map(|r| u32::from(r.rating)).sum();
     total as f32 / reviews.len() as f32
 }
 
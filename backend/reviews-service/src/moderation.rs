@@ -0,0 +1,302 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::language::detect_language;
+use crate::Review;
+
+/// Source label for reviews written by our own guests.
+pub const FIRST_PARTY_SOURCE: &str = "Albergue";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ModerationStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+impl ModerationStatus {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Approved => "approved",
+            Self::Rejected => "rejected",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OwnerReply {
+    pub text: String,
+    pub replied_by: Option<String>,
+    pub replied_at: DateTime<Utc>,
+}
+
+/// Outcome of the content scan run against the review text at submission time.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScanSummary {
+    pub risk_level: String,
+    pub threats_detected: u32,
+}
+
+/// A review submitted by a guest, together with its moderation state.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GuestReview {
+    pub review: Review,
+    pub booking_id: String,
+    pub pilgrim_id: String,
+    pub status: ModerationStatus,
+    pub scan: ScanSummary,
+    pub submitted_at: DateTime<Utc>,
+    pub moderated_at: Option<DateTime<Utc>>,
+    pub moderated_by: Option<String>,
+    pub rejection_reason: Option<String>,
+    pub reply: Option<OwnerReply>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ReviewSubmission {
+    pub token: String,
+    pub author_name: String,
+    pub rating: u8,
+    pub text: String,
+}
+
+pub const MIN_REVIEW_LENGTH: usize = 10;
+pub const MAX_REVIEW_LENGTH: usize = 2000;
+pub const MAX_AUTHOR_LENGTH: usize = 80;
+pub const MAX_REPLY_LENGTH: usize = 1000;
+
+impl ReviewSubmission {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=5).contains(&self.rating) {
+            return Err("Rating must be between 1 and 5".to_string());
+        }
+
+        let author = self.author_name.trim();
+        if author.is_empty() || author.chars().count() > MAX_AUTHOR_LENGTH {
+            return Err(format!(
+                "Author name must be between 1 and {MAX_AUTHOR_LENGTH} characters"
+            ));
+        }
+
+        let length = self.text.trim().chars().count();
+        if !(MIN_REVIEW_LENGTH..=MAX_REVIEW_LENGTH).contains(&length) {
+            return Err(format!(
+                "Review text must be between {MIN_REVIEW_LENGTH} and {MAX_REVIEW_LENGTH} characters"
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl GuestReview {
+    pub fn from_submission(
+        submission: &ReviewSubmission,
+        booking_id: &str,
+        pilgrim_id: &str,
+        scan: ScanSummary,
+        now: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            review: Review {
+                id: format!("direct_{}", uuid::Uuid::new_v4().simple()),
+                author_name: submission.author_name.trim().to_string(),
+                rating: submission.rating,
+                text: submission.text.trim().to_string(),
+                date: now.format("%Y-%m-%d").to_string(),
+                source: FIRST_PARTY_SOURCE.to_string(),
+                verified: true,
+                helpful_count: 0,
+                language: detect_language(&submission.text),
+            },
+            booking_id: booking_id.to_string(),
+            pilgrim_id: pilgrim_id.to_string(),
+            status: ModerationStatus::Pending,
+            scan,
+            submitted_at: now,
+            moderated_at: None,
+            moderated_by: None,
+            rejection_reason: None,
+            reply: None,
+        }
+    }
+
+    /// Apply a moderation action, enforcing the allowed state transitions:
+    /// only pending reviews can be approved or rejected, and only approved
+    /// reviews can receive a public reply.
+    pub fn apply(
+        &mut self,
+        action: ModerationAction,
+        moderator: Option<String>,
+        now: DateTime<Utc>,
+    ) -> Result<(), String> {
+        match action {
+            ModerationAction::Approve => {
+                if self.status != ModerationStatus::Pending {
+                    return Err(format!("Review is already {}", self.status.as_str()));
+                }
+                self.status = ModerationStatus::Approved;
+            }
+            ModerationAction::Reject { reason } => {
+                if self.status != ModerationStatus::Pending {
+                    return Err(format!("Review is already {}", self.status.as_str()));
+                }
+                self.status = ModerationStatus::Rejected;
+                self.rejection_reason = reason;
+            }
+            ModerationAction::Reply { text } => {
+                if self.status != ModerationStatus::Approved {
+                    return Err("Only approved reviews can be replied to".to_string());
+                }
+                let text = text.trim().to_string();
+                if text.is_empty() || text.chars().count() > MAX_REPLY_LENGTH {
+                    return Err(format!(
+                        "Reply must be between 1 and {MAX_REPLY_LENGTH} characters"
+                    ));
+                }
+                self.reply = Some(OwnerReply {
+                    text,
+                    replied_by: moderator,
+                    replied_at: now,
+                });
+                return Ok(());
+            }
+        }
+
+        self.moderated_at = Some(now);
+        self.moderated_by = moderator;
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ModerationAction {
+    Approve,
+    Reject {
+        #[serde(default)]
+        reason: Option<String>,
+    },
+    Reply {
+        text: String,
+    },
+}
+
+impl ModerationAction {
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Approve => "approve",
+            Self::Reject { .. } => "reject",
+            Self::Reply { .. } => "reply",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn submission(rating: u8, text: &str) -> ReviewSubmission {
+        ReviewSubmission {
+            token: "token".to_string(),
+            author_name: "Peregrina".to_string(),
+            rating,
+            text: text.to_string(),
+        }
+    }
+
+    fn pending_review() -> GuestReview {
+        GuestReview::from_submission(
+            &submission(5, "Muy buena acogida, volveremos."),
+            "booking-1",
+            "pilgrim-1",
+            ScanSummary {
+                risk_level: "clean".to_string(),
+                threats_detected: 0,
+            },
+            Utc::now(),
+        )
+    }
+
+    #[test]
+    fn test_submission_validation() {
+        assert!(submission(5, "Muy buena acogida").validate().is_ok());
+        assert!(submission(0, "Muy buena acogida").validate().is_err());
+        assert!(submission(6, "Muy buena acogida").validate().is_err());
+        assert!(submission(4, "corto").validate().is_err());
+        assert!(submission(4, &"a".repeat(MAX_REVIEW_LENGTH + 1))
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn test_new_review_is_verified_and_pending() {
+        let review = pending_review();
+        assert!(review.review.verified);
+        assert_eq!(review.review.source, FIRST_PARTY_SOURCE);
+        assert_eq!(review.status, ModerationStatus::Pending);
+    }
+
+    #[test]
+    fn test_approve_then_reply() {
+        let mut review = pending_review();
+        let now = Utc::now();
+
+        assert!(review
+            .apply(
+                ModerationAction::Reply {
+                    text: "Gracias".to_string()
+                },
+                None,
+                now
+            )
+            .is_err());
+
+        review
+            .apply(ModerationAction::Approve, Some("admin".to_string()), now)
+            .unwrap();
+        assert_eq!(review.status, ModerationStatus::Approved);
+        assert_eq!(review.moderated_by.as_deref(), Some("admin"));
+
+        review
+            .apply(
+                ModerationAction::Reply {
+                    text: "¡Gracias, buen camino!".to_string(),
+                },
+                Some("admin".to_string()),
+                now,
+            )
+            .unwrap();
+        assert!(review.reply.is_some());
+    }
+
+    #[test]
+    fn test_rejected_review_cannot_be_approved() {
+        let mut review = pending_review();
+        let now = Utc::now();
+
+        review
+            .apply(
+                ModerationAction::Reject {
+                    reason: Some("spam".to_string()),
+                },
+                None,
+                now,
+            )
+            .unwrap();
+        assert_eq!(review.rejection_reason.as_deref(), Some("spam"));
+        assert!(review.apply(ModerationAction::Approve, None, now).is_err());
+    }
+
+    #[test]
+    fn test_moderation_action_parsing() {
+        let action: ModerationAction =
+            serde_json::from_str(r#"{"action":"reject","reason":"off-topic"}"#).unwrap();
+        assert_eq!(action.as_str(), "reject");
+
+        let action: ModerationAction = serde_json::from_str(r#"{"action":"approve"}"#).unwrap();
+        assert_eq!(action.as_str(), "approve");
+    }
+}
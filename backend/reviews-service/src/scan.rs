@@ -0,0 +1,95 @@
+use serde::Deserialize;
+use spin_sdk::http::{Method, Request, Response};
+
+use crate::moderation::ScanSummary;
+
+const SECURITY_SCAN_URL: &str = "http://security-service.spin.internal/security/scan";
+
+#[derive(Deserialize)]
+struct SecurityScanResult {
+    threats_detected: u32,
+    risk_level: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanVerdict {
+    /// Nothing suspicious; the review still waits for a human moderator.
+    Clean,
+    /// Suspicious but not conclusive, e.g. the scanner was unreachable.
+    Flagged,
+    /// Refused outright; the guest can edit the text and resubmit.
+    Blocked,
+}
+
+pub fn verdict(summary: &ScanSummary) -> ScanVerdict {
+    match summary.risk_level.as_str() {
+        "clean" | "low" => ScanVerdict::Clean,
+        "high" | "critical" => ScanVerdict::Blocked,
+        _ => ScanVerdict::Flagged,
+    }
+}
+
+/// Run the review text through security-service's content scanner.
+///
+/// A scanner outage must not lose the review, so failures are reported as
+/// `unavailable` and left for the moderator to judge.
+pub async fn scan_review_text(text: &str) -> ScanSummary {
+    let body = serde_json::json!({
+        "content": text,
+        "scan_type": "review",
+    });
+
+    let request = Request::builder()
+        .method(Method::Post)
+        .uri(SECURITY_SCAN_URL)
+        .header("content-type", "application/json")
+        .body(serde_json::to_vec(&body).unwrap_or_default())
+        .build();
+
+    let result = match spin_sdk::http::send::<_, Response>(request).await {
+        Ok(response) if *response.status() == 200 => {
+            serde_json::from_slice::<SecurityScanResult>(response.body()).ok()
+        }
+        Ok(response) => {
+            log::warn!("Security scan returned status {}", response.status());
+            None
+        }
+        Err(e) => {
+            log::warn!("Security scan failed: {e}");
+            None
+        }
+    };
+
+    result.map_or_else(
+        || ScanSummary {
+            risk_level: "unavailable".to_string(),
+            threats_detected: 0,
+        },
+        |r| ScanSummary {
+            risk_level: r.risk_level,
+            threats_detected: r.threats_detected,
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(risk_level: &str) -> ScanSummary {
+        ScanSummary {
+            risk_level: risk_level.to_string(),
+            threats_detected: 0,
+        }
+    }
+
+    #[test]
+    fn test_verdict_mapping() {
+        assert_eq!(verdict(&summary("clean")), ScanVerdict::Clean);
+        assert_eq!(verdict(&summary("low")), ScanVerdict::Clean);
+        assert_eq!(verdict(&summary("medium")), ScanVerdict::Flagged);
+        assert_eq!(verdict(&summary("unavailable")), ScanVerdict::Flagged);
+        assert_eq!(verdict(&summary("high")), ScanVerdict::Blocked);
+        assert_eq!(verdict(&summary("critical")), ScanVerdict::Blocked);
+    }
+}
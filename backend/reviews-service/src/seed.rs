@@ -0,0 +1,91 @@
+use crate::Review;
+
+/// Reviews shown until a first import from the external platforms has run.
+pub fn seed_reviews() -> Vec<Review> {
+    vec![
+        seed(
+            "google_1",
+            "María González",
+            5,
+            "Excelente albergue en El Carrascalejo. Muy limpio, camas cómodas y el hospitalero muy amable. Perfecto para peregrinos del Camino de Santiago.",
+            "2024-06-15",
+            "Google",
+            12,
+            "es",
+        ),
+        seed(
+            "google_2",
+            "Jean-Pierre Dubois",
+            4,
+            "Bon accueil, équipements corrects. Village tranquille pour se reposer. Je recommande pour une étape sur le Camino.",
+            "2024-05-28",
+            "Google",
+            8,
+            "fr",
+        ),
+        seed(
+            "google_3",
+            "Klaus Weber",
+            5,
+            "Wunderbare Herberge! Sehr sauber, gute Ausstattung und herzlicher Empfang. El Carrascalejo ist ein perfekter Zwischenstopp.",
+            "2024-04-20",
+            "Google",
+            15,
+            "de",
+        ),
+        seed(
+            "booking_1",
+            "Sarah Mitchell",
+            5,
+            "Perfect stop on the Camino! Clean facilities, comfortable beds, and the host was incredibly welcoming. Highly recommend this albergue.",
+            "2024-06-10",
+            "Booking.com",
+            9,
+            "en",
+        ),
+        seed(
+            "booking_2",
+            "Antonio Silva",
+            4,
+            "Bom albergue para peregrinos. Quartos limpos, boa localização no Carrascalejo. Staff simpático e prestável.",
+            "2024-05-15",
+            "Booking.com",
+            6,
+            "pt",
+        ),
+        seed(
+            "booking_3",
+            "Emma Johnson",
+            5,
+            "Exceptional hospitality! The albergue exceeded my expectations. Clean, comfortable, and the perfect place to rest during the pilgrimage.",
+            "2024-04-05",
+            "Booking.com",
+            11,
+            "en",
+        ),
+    ]
+}
+
+#[allow(clippy::too_many_arguments)]
+fn seed(
+    id: &str,
+    author_name: &str,
+    rating: u8,
+    text: &str,
+    date: &str,
+    source: &str,
+    helpful_count: u32,
+    language: &str,
+) -> Review {
+    Review {
+        id: id.to_string(),
+        author_name: author_name.to_string(),
+        rating,
+        text: text.to_string(),
+        date: date.to_string(),
+        source: source.to_string(),
+        verified: true,
+        helpful_count,
+        language: Some(language.to_string()),
+    }
+}
@@ -0,0 +1,168 @@
+use chrono::{Duration, NaiveDate};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::Review;
+
+/// Reviews newer than this count towards `recent_reviews`.
+const RECENT_WINDOW_DAYS: i64 = 90;
+
+#[derive(Serialize, Debug)]
+pub struct ReviewStats {
+    /// Same as `total_count`, `rating_histogram` and `source_breakdown`;
+    /// the names the endpoint has always had.
+    pub total_reviews: u32,
+    pub rating_distribution: BTreeMap<u8, u32>,
+    pub sources: HashMap<String, u32>,
+    pub total_count: u32,
+    pub average_rating: f32,
+    /// Count per star rating, always keyed 1 through 5.
+    pub rating_histogram: BTreeMap<u8, u32>,
+    pub source_breakdown: HashMap<String, u32>,
+    pub language_breakdown: BTreeMap<String, u32>,
+    pub verified_percentage: f32,
+    pub recent_reviews: u32,
+    /// Oldest month first.
+    pub monthly_trend: Vec<MonthlyTrend>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct MonthlyTrend {
+    /// `YYYY-MM`
+    pub month: String,
+    pub count: u32,
+    pub average_rating: f32,
+    /// Average of every review up to and including this month.
+    pub cumulative_average: f32,
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+pub fn compute(reviews: &[Review], today: NaiveDate) -> ReviewStats {
+    let total_count = reviews.len() as u32;
+
+    let mut rating_histogram: BTreeMap<u8, u32> = (1..=5).map(|r| (r, 0)).collect();
+    let mut source_breakdown = HashMap::new();
+    let mut language_breakdown = BTreeMap::new();
+    let mut by_month: BTreeMap<String, (u32, u32)> = BTreeMap::new();
+    let mut verified = 0;
+    let mut recent_reviews = 0;
+    let recent_since = today - Duration::days(RECENT_WINDOW_DAYS);
+
+    for review in reviews {
+        if let Some(count) = rating_histogram.get_mut(&review.rating) {
+            *count += 1;
+        }
+        *source_breakdown.entry(review.source.clone()).or_insert(0) += 1;
+        let language = review
+            .language
+            .clone()
+            .unwrap_or_else(|| "unknown".to_string());
+        *language_breakdown.entry(language).or_insert(0) += 1;
+        if review.verified {
+            verified += 1;
+        }
+
+        if let Ok(date) = NaiveDate::parse_from_str(&review.date, "%Y-%m-%d") {
+            if date >= recent_since {
+                recent_reviews += 1;
+            }
+            let month = by_month
+                .entry(date.format("%Y-%m").to_string())
+                .or_default();
+            month.0 += 1;
+            month.1 += u32::from(review.rating);
+        }
+    }
+
+    let mut running = (0u32, 0u32);
+    let monthly_trend = by_month
+        .into_iter()
+        .map(|(month, (count, sum))| {
+            running.0 += count;
+            running.1 += sum;
+            MonthlyTrend {
+                month,
+                count,
+                average_rating: sum as f32 / count as f32,
+                cumulative_average: running.1 as f32 / running.0 as f32,
+            }
+        })
+        .collect();
+
+    ReviewStats {
+        total_reviews: total_count,
+        rating_distribution: rating_histogram.clone(),
+        sources: source_breakdown.clone(),
+        total_count,
+        average_rating: crate::calculate_average_rating(reviews),
+        rating_histogram,
+        source_breakdown,
+        language_breakdown,
+        verified_percentage: if total_count == 0 {
+            0.0
+        } else {
+            verified as f32 * 100.0 / total_count as f32
+        },
+        recent_reviews,
+        monthly_trend,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn review(rating: u8, date: &str, source: &str, language: Option<&str>) -> Review {
+        Review {
+            id: format!("{source}_{date}"),
+            author_name: "Pilgrim".to_string(),
+            rating,
+            text: String::new(),
+            date: date.to_string(),
+            source: source.to_string(),
+            verified: source == "Booking.com",
+            helpful_count: 0,
+            language: language.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_compute_stats() {
+        let reviews = [
+            review(5, "2024-06-15", "Google", Some("es")),
+            review(3, "2024-06-02", "Booking.com", Some("en")),
+            review(4, "2024-04-20", "Google", None),
+            review(5, "2024-04-05", "Booking.com", Some("en")),
+        ];
+        let stats = compute(&reviews, NaiveDate::from_ymd_opt(2024, 7, 10).unwrap());
+
+        assert_eq!(stats.total_count, 4);
+        assert_eq!(stats.total_reviews, stats.total_count);
+        assert_eq!(stats.rating_distribution, stats.rating_histogram);
+        assert_eq!(stats.sources, stats.source_breakdown);
+        assert!((stats.average_rating - 4.25).abs() < f32::EPSILON);
+        assert_eq!(
+            stats.rating_histogram.values().copied().collect::<Vec<_>>(),
+            [0, 0, 1, 1, 2]
+        );
+        assert_eq!(stats.source_breakdown["Google"], 2);
+        assert_eq!(stats.language_breakdown["en"], 2);
+        assert_eq!(stats.language_breakdown["unknown"], 1);
+        assert!((stats.verified_percentage - 50.0).abs() < f32::EPSILON);
+        assert_eq!(stats.recent_reviews, 3);
+
+        assert_eq!(stats.monthly_trend.len(), 2);
+        assert_eq!(stats.monthly_trend[0].month, "2024-04");
+        assert!((stats.monthly_trend[0].average_rating - 4.5).abs() < f32::EPSILON);
+        assert!((stats.monthly_trend[1].average_rating - 4.0).abs() < f32::EPSILON);
+        assert!((stats.monthly_trend[1].cumulative_average - 4.25).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn test_empty_stats() {
+        let stats = compute(&[], NaiveDate::from_ymd_opt(2024, 7, 1).unwrap());
+        assert_eq!(stats.total_count, 0);
+        assert_eq!(stats.rating_histogram.len(), 5);
+        assert!(stats.monthly_trend.is_empty());
+    }
+}
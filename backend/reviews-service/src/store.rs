@@ -0,0 +1,129 @@
+use anyhow::Result;
+use spin_sdk::key_value::Store;
+
+use crate::moderation::{GuestReview, ModerationStatus};
+use crate::Review;
+
+pub const REVIEWS_STORE: &str = "default";
+const IMPORTED_INDEX_KEY: &str = "reviews:index:imported";
+
+/// Guest and imported reviews persisted in the Spin key-value store.
+///
+/// Each guest review lives under `review:{id}`; per-status indexes hold the ids
+/// so that the moderation queue and the public listing don't need a key scan.
+/// Reviews imported from external platforms live under `imported:{id}` with
+/// their own index.
+pub struct ReviewStore {
+    store: Store,
+}
+
+impl ReviewStore {
+    pub fn open() -> Result<Self> {
+        Ok(Self {
+            store: Store::open(REVIEWS_STORE)?,
+        })
+    }
+
+    pub const fn kv(&self) -> &Store {
+        &self.store
+    }
+
+    pub fn get(&self, id: &str) -> Result<Option<GuestReview>> {
+        match self.store.get(&review_key(id))? {
+            Some(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Persist a review and move its id into the index for its current status.
+    pub fn save(&self, review: &GuestReview) -> Result<()> {
+        self.store
+            .set(&review_key(&review.review.id), &serde_json::to_vec(review)?)?;
+
+        for status in [
+            ModerationStatus::Pending,
+            ModerationStatus::Approved,
+            ModerationStatus::Rejected,
+        ] {
+            let mut ids = self.index(status)?;
+            let present = ids.iter().any(|id| id == &review.review.id);
+            if status == review.status && !present {
+                ids.push(review.review.id.clone());
+                self.set_index(status, &ids)?;
+            } else if status != review.status && present {
+                ids.retain(|id| id != &review.review.id);
+                self.set_index(status, &ids)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn list(&self, status: ModerationStatus) -> Result<Vec<GuestReview>> {
+        let mut reviews = Vec::new();
+        for id in self.index(status)? {
+            if let Some(review) = self.get(&id)? {
+                reviews.push(review);
+            }
+        }
+        reviews.sort_by_key(|r| std::cmp::Reverse(r.submitted_at));
+        Ok(reviews)
+    }
+
+    /// Store newly imported reviews; ids already present are overwritten.
+    pub fn save_imported(&self, reviews: &[Review]) -> Result<()> {
+        let mut ids = self.imported_ids()?;
+        for review in reviews {
+            self.store
+                .set(&imported_key(&review.id), &serde_json::to_vec(review)?)?;
+            if !ids.contains(&review.id) {
+                ids.push(review.id.clone());
+            }
+        }
+        self.store
+            .set(IMPORTED_INDEX_KEY, &serde_json::to_vec(&ids)?)?;
+        Ok(())
+    }
+
+    pub fn list_imported(&self) -> Result<Vec<Review>> {
+        let mut reviews = Vec::new();
+        for id in self.imported_ids()? {
+            if let Some(bytes) = self.store.get(&imported_key(&id))? {
+                reviews.push(serde_json::from_slice(&bytes)?);
+            }
+        }
+        Ok(reviews)
+    }
+
+    fn imported_ids(&self) -> Result<Vec<String>> {
+        match self.store.get(IMPORTED_INDEX_KEY)? {
+            Some(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn index(&self, status: ModerationStatus) -> Result<Vec<String>> {
+        match self.store.get(&index_key(status))? {
+            Some(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn set_index(&self, status: ModerationStatus, ids: &[String]) -> Result<()> {
+        self.store
+            .set(&index_key(status), &serde_json::to_vec(ids)?)?;
+        Ok(())
+    }
+}
+
+fn review_key(id: &str) -> String {
+    format!("review:{id}")
+}
+
+fn imported_key(id: &str) -> String {
+    format!("imported:{id}")
+}
+
+fn index_key(status: ModerationStatus) -> String {
+    format!("reviews:index:{}", status.as_str())
+}
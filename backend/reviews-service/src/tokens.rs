@@ -0,0 +1,205 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use ring::digest::{digest, SHA256};
+use spin_sdk::key_value::Store;
+use std::fmt::Write;
+use spin_sdk::wit::wasi::keyvalue::{atomics, store as wasi_store};
+
+use crate::store::REVIEWS_STORE;
+
+/// Review links stay valid for a month after check-out.
+pub const REVIEW_TOKEN_TTL_DAYS: i64 = 30;
+
+/// Value a redeemed token's record is swapped for before it is deleted.
+const REDEEMED: &[u8] = b"";
+
+/// One-time token that entitles the guest of a completed stay to a single review.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReviewToken {
+    pub token: String,
+    pub booking_id: String,
+    pub pilgrim_id: String,
+    pub issued_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl ReviewToken {
+    pub fn new(booking_id: &str, pilgrim_id: &str, issued_at: DateTime<Utc>) -> Self {
+        Self {
+            token: uuid::Uuid::new_v4().simple().to_string(),
+            booking_id: booking_id.to_string(),
+            pilgrim_id: pilgrim_id.to_string(),
+            issued_at,
+            expires_at: issued_at + Duration::days(REVIEW_TOKEN_TTL_DAYS),
+        }
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        now >= self.expires_at
+    }
+
+    /// Hex SHA-256 of the token, safe to publish.
+    pub fn sha256(&self) -> String {
+        digest(&SHA256, self.token.as_bytes())
+            .as_ref()
+            .iter()
+            .fold(String::new(), |mut out, b| {
+                let _ = write!(out, "{b:02x}");
+                out
+            })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TokenError {
+    Unknown,
+    Expired,
+    Storage(String),
+}
+
+impl std::fmt::Display for TokenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unknown => write!(f, "Review token is invalid or has already been used"),
+            Self::Expired => write!(f, "Review token has expired"),
+            Self::Storage(e) => write!(f, "Review token storage error: {e}"),
+        }
+    }
+}
+
+fn token_key(token: &str) -> String {
+    format!("review_token:{token}")
+}
+
+fn booking_key(booking_id: &str) -> String {
+    format!("review_token:booking:{booking_id}")
+}
+
+/// Issue a token for a checked-out booking.
+///
+/// Check-out events can be delivered more than once, so an existing unexpired
+/// token for the same booking is returned instead of minting a second one.
+pub fn issue(store: &Store, booking_id: &str, pilgrim_id: &str) -> Result<ReviewToken, TokenError> {
+    let now = Utc::now();
+
+    if let Some(existing) = store
+        .get(&booking_key(booking_id))
+        .map_err(|e| TokenError::Storage(e.to_string()))?
+    {
+        let existing = String::from_utf8_lossy(&existing).to_string();
+        if let Some(token) = load(store, &existing)? {
+            if !token.is_expired(now) {
+                return Ok(token);
+            }
+        }
+    }
+
+    let token = ReviewToken::new(booking_id, pilgrim_id, now);
+    let bytes = serde_json::to_vec(&token).map_err(|e| TokenError::Storage(e.to_string()))?;
+    store
+        .set(&token_key(&token.token), &bytes)
+        .map_err(|e| TokenError::Storage(e.to_string()))?;
+    store
+        .set(&booking_key(booking_id), token.token.as_bytes())
+        .map_err(|e| TokenError::Storage(e.to_string()))?;
+
+    Ok(token)
+}
+
+/// Check that a token is usable without consuming it.
+pub fn peek(store: &Store, token: &str) -> Result<ReviewToken, TokenError> {
+    let stored = load(store, token)?.ok_or(TokenError::Unknown)?;
+    if stored.is_expired(Utc::now()) {
+        return Err(TokenError::Expired);
+    }
+    Ok(stored)
+}
+
+/// Consume a token. The record is swapped for a tombstone in a single
+/// compare-and-swap, so of two submissions racing with the same token only
+/// one gets it; the other, like any later redemption, fails with `Unknown`.
+pub fn redeem(token: &str) -> Result<ReviewToken, TokenError> {
+    let bucket = wasi_store::open(REVIEWS_STORE).map_err(|e| storage(&e))?;
+    let key = token_key(token);
+    let cas = atomics::Cas::new(&bucket, &key).map_err(|e| storage(&e))?;
+    let stored = decode(cas.current().map_err(|e| storage(&e))?)?.ok_or(TokenError::Unknown)?;
+    if stored.is_expired(Utc::now()) {
+        return Err(TokenError::Expired);
+    }
+    match atomics::swap(cas, REDEEMED) {
+        Ok(()) => {}
+        Err(atomics::CasError::CasFailed(_)) => return Err(TokenError::Unknown),
+        Err(atomics::CasError::StoreError(e)) => return Err(storage(&e)),
+    }
+
+    // The token is spent either way; a tombstone left behind reads as unknown.
+    for key in [key, booking_key(&stored.booking_id)] {
+        if let Err(e) = bucket.delete(&key) {
+            log::warn!("Failed to delete redeemed review token key {key}: {e:?}");
+        }
+    }
+    Ok(stored)
+}
+
+fn storage(e: &wasi_store::Error) -> TokenError {
+    TokenError::Storage(format!("{e:?}"))
+}
+
+fn load(store: &Store, token: &str) -> Result<Option<ReviewToken>, TokenError> {
+    decode(
+        store
+            .get(&token_key(token))
+            .map_err(|e| TokenError::Storage(e.to_string()))?,
+    )
+}
+
+/// A stored token record; `None` when missing or already redeemed.
+fn decode(bytes: Option<Vec<u8>>) -> Result<Option<ReviewToken>, TokenError> {
+    match bytes {
+        Some(bytes) if bytes != REDEEMED => serde_json::from_slice(&bytes)
+            .map(Some)
+            .map_err(|e| TokenError::Storage(e.to_string())),
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_expires_after_ttl() {
+        let issued = Utc::now();
+        let token = ReviewToken::new("booking-1", "pilgrim-1", issued);
+
+        assert_eq!(token.token.len(), 32);
+        assert!(!token.is_expired(issued + Duration::days(REVIEW_TOKEN_TTL_DAYS - 1)));
+        assert!(token.is_expired(issued + Duration::days(REVIEW_TOKEN_TTL_DAYS)));
+    }
+
+    #[test]
+    fn test_redeemed_tokens_read_as_unknown() {
+        assert!(matches!(decode(None), Ok(None)));
+        assert!(matches!(decode(Some(REDEEMED.to_vec())), Ok(None)));
+        let token = ReviewToken::new("booking-1", "pilgrim-1", Utc::now());
+        let stored = decode(Some(serde_json::to_vec(&token).unwrap())).unwrap();
+        assert_eq!(stored.map(|t| t.token), Some(token.token));
+    }
+
+    #[test]
+    fn test_published_hash_does_not_reveal_the_token() {
+        let token = ReviewToken::new("booking-1", "pilgrim-1", Utc::now());
+        let hash = token.sha256();
+        assert_eq!(hash.len(), 64);
+        assert!(!hash.contains(&token.token));
+        assert_eq!(hash, token.sha256());
+    }
+
+    #[test]
+    fn test_tokens_are_unique() {
+        let now = Utc::now();
+        let a = ReviewToken::new("booking-1", "pilgrim-1", now);
+        let b = ReviewToken::new("booking-1", "pilgrim-1", now);
+        assert_ne!(a.token, b.token);
+    }
+}
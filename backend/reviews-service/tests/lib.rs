@@ -186,7 +186,7 @@ async fn test_options_request() {
     assert_eq!(headers.get("Access-Control-Allow-Origin").unwrap(), "*");
     assert_eq!(
         headers.get("Access-Control-Allow-Methods").unwrap(),
-        "GET, OPTIONS"
+        "GET, POST, OPTIONS"
     );
     assert_eq!(
         headers.get("Access-Control-Allow-Headers").unwrap(),
@@ -0,0 +1,308 @@
+//! `audit_log` storage: appending chained entries, verifying the chain and
+//! querying it. Hashing lives in [`shared::audit`].
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::Serialize;
+use shared::audit::{self, AuditEntry, ChainReport, StoredEntry, GENESIS_HASH};
+use spin_sdk::sqlite::{Connection, Value};
+
+/// Concurrent writers can read the same head; the unique `prev_hash` index
+/// lets one of them win and the other retries on the new head.
+const MAX_ATTEMPTS: u32 = 5;
+const VERIFY_PAGE: i64 = 500;
+pub const DEFAULT_QUERY_LIMIT: u32 = 100;
+pub const MAX_QUERY_LIMIT: u32 = 1000;
+
+const COLUMNS: &str = "id, table_name, record_id, action, old_values, new_values, user_id, \
+                       ip_address, user_agent, created_at, prev_hash, entry_hash";
+
+#[derive(Debug, Serialize)]
+pub struct Recorded {
+    pub id: i64,
+    pub entry_hash: String,
+}
+
+#[derive(Debug, Default)]
+pub struct AuditQuery {
+    pub user_id: Option<i64>,
+    pub table_name: Option<String>,
+    pub record_id: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub limit: u32,
+}
+
+impl AuditQuery {
+    /// Reads `user_id`, `table`, `record_id`, `from`, `to` (RFC 3339) and
+    /// `limit` from a URL query string.
+    pub fn parse(query: &str) -> Result<Self> {
+        let mut parsed = Self {
+            limit: DEFAULT_QUERY_LIMIT,
+            ..Self::default()
+        };
+        for pair in query.split('&').filter(|p| !p.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let value = percent_decode(value)?;
+            let time = |v: &str| {
+                DateTime::parse_from_rfc3339(v)
+                    .map(|t| t.with_timezone(&Utc))
+                    .map_err(|_| anyhow!("{key} must be an RFC 3339 timestamp"))
+            };
+            match key {
+                "user_id" => parsed.user_id = Some(value.parse()?),
+                "table" => parsed.table_name = Some(value),
+                "record_id" => parsed.record_id = Some(value),
+                "from" => parsed.from = Some(time(&value)?),
+                "to" => parsed.to = Some(time(&value)?),
+                "limit" => parsed.limit = value.parse::<u32>()?.clamp(1, MAX_QUERY_LIMIT),
+                _ => return Err(anyhow!("unknown filter {key}")),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+fn percent_decode(value: &str) -> Result<String> {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' => {
+                let hex = value
+                    .get(i + 1..i + 3)
+                    .and_then(|h| u8::from_str_radix(h, 16).ok())
+                    .ok_or_else(|| anyhow!("malformed escape in query"))?;
+                out.push(hex);
+                i += 2;
+            }
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    Ok(String::from_utf8(out)?)
+}
+
+fn text(value: Option<&str>) -> Value {
+    value.map_or(Value::Null, |v| Value::Text(v.to_string()))
+}
+
+/// Appends `entry` to the chain.
+pub fn record(entry: &AuditEntry) -> Result<Recorded> {
+    let conn = Connection::open_default()?;
+    let mut last_error = None;
+    for _ in 0..MAX_ATTEMPTS {
+        let head = conn.execute(
+            "SELECT entry_hash FROM audit_log WHERE entry_hash IS NOT NULL \
+             ORDER BY id DESC LIMIT 1",
+            &[],
+        )?;
+        let prev_hash = head
+            .rows()
+            .next()
+            .and_then(|row| row.get::<&str>("entry_hash").map(str::to_string))
+            .unwrap_or_else(|| GENESIS_HASH.to_string());
+        let entry_hash = entry.hash(&prev_hash);
+        let inserted = conn.execute(
+            "INSERT INTO audit_log (table_name, record_id, action, old_values, new_values, \
+             user_id, ip_address, user_agent, created_at, prev_hash, entry_hash) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?) RETURNING id",
+            &[
+                Value::Text(entry.table_name.clone()),
+                Value::Text(entry.record_id.clone()),
+                Value::Text(entry.action.clone()),
+                text(
+                    entry
+                        .old_values
+                        .as_ref()
+                        .map(audit::canonical_json)
+                        .as_deref(),
+                ),
+                text(
+                    entry
+                        .new_values
+                        .as_ref()
+                        .map(audit::canonical_json)
+                        .as_deref(),
+                ),
+                entry.user_id.map_or(Value::Null, Value::Integer),
+                text(entry.ip_address.as_deref()),
+                text(entry.user_agent.as_deref()),
+                Value::Text(audit::timestamp(entry.created_at)),
+                Value::Text(prev_hash),
+                Value::Text(entry_hash.clone()),
+            ],
+        );
+        match inserted {
+            Ok(result) => {
+                let id = result
+                    .rows()
+                    .next()
+                    .and_then(|row| row.get::<i64>("id"))
+                    .ok_or_else(|| anyhow!("audit insert returned no id"))?;
+                return Ok(Recorded { id, entry_hash });
+            }
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(anyhow!(
+        "could not append to the audit log: {}",
+        last_error.map_or_else(String::new, |e| e.to_string())
+    ))
+}
+
+/// Walks the whole chain a page at a time, carrying the previous hash from
+/// one page to the next.
+pub fn verify() -> Result<ChainReport> {
+    let conn = Connection::open_default()?;
+    let mut report = ChainReport::default();
+    let mut after = 0;
+    loop {
+        let page = conn.execute(
+            &format!("SELECT {COLUMNS} FROM audit_log WHERE id > ? ORDER BY id LIMIT ?"),
+            &[Value::Integer(after), Value::Integer(VERIFY_PAGE)],
+        )?;
+        let mut rows = 0;
+        for row in page.rows() {
+            let stored = stored_entry(&row)?;
+            if !report.check(&stored) {
+                return Ok(report);
+            }
+            after = stored.id;
+            rows += 1;
+        }
+        if rows < VERIFY_PAGE {
+            return Ok(report);
+        }
+    }
+}
+
+/// Newest entries first.
+pub fn query(filter: &AuditQuery) -> Result<Vec<StoredEntry>> {
+    let conn = Connection::open_default()?;
+    let mut conditions = Vec::new();
+    let mut params = Vec::new();
+    if let Some(user_id) = filter.user_id {
+        conditions.push("user_id = ?");
+        params.push(Value::Integer(user_id));
+    }
+    if let Some(table) = &filter.table_name {
+        conditions.push("table_name = ?");
+        params.push(Value::Text(table.clone()));
+    }
+    if let Some(record_id) = &filter.record_id {
+        conditions.push("record_id = ?");
+        params.push(Value::Text(record_id.clone()));
+    }
+    if let Some(from) = filter.from {
+        conditions.push("created_at >= ?");
+        params.push(Value::Text(audit::timestamp(from)));
+    }
+    if let Some(to) = filter.to {
+        conditions.push("created_at < ?");
+        params.push(Value::Text(audit::timestamp(to)));
+    }
+    let filter_sql = if conditions.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", conditions.join(" AND "))
+    };
+    params.push(Value::Integer(i64::from(filter.limit)));
+    let result = conn.execute(
+        &format!("SELECT {COLUMNS} FROM audit_log {filter_sql} ORDER BY id DESC LIMIT ?"),
+        &params,
+    )?;
+    result.rows().map(|row| stored_entry(&row)).collect()
+}
+
+fn stored_entry(row: &spin_sdk::sqlite::Row<'_>) -> Result<StoredEntry> {
+    let id = row
+        .get::<i64>("id")
+        .ok_or_else(|| anyhow!("audit row without id"))?;
+    let string = |column: &str| row.get::<&str>(column).map(str::to_string);
+    let json = |column: &str| -> Result<Option<serde_json::Value>> {
+        row.get::<&str>(column)
+            .map(serde_json::from_str)
+            .transpose()
+            .map_err(|e| anyhow!("audit row {id}: {column}: {e}"))
+    };
+    let created_at = row
+        .get::<&str>("created_at")
+        .and_then(parse_timestamp)
+        .ok_or_else(|| anyhow!("audit row {id}: unreadable created_at"))?;
+    Ok(StoredEntry {
+        id,
+        entry: AuditEntry {
+            table_name: string("table_name").unwrap_or_default(),
+            record_id: string("record_id").unwrap_or_default(),
+            action: string("action").unwrap_or_default(),
+            old_values: json("old_values")?,
+            new_values: json("new_values")?,
+            user_id: row.get::<i64>("user_id"),
+            ip_address: string("ip_address"),
+            user_agent: string("user_agent"),
+            created_at,
+        },
+        prev_hash: string("prev_hash"),
+        entry_hash: string("entry_hash"),
+    })
+}
+
+/// Timestamps as written by this service, the domain model or the database.
+fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            DateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f%:z")
+                .map(|t| t.with_timezone(&Utc))
+                .ok()
+        })
+        .or_else(|| {
+            NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f")
+                .map(|t| t.and_utc())
+                .ok()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_query_filters() {
+        let q = AuditQuery::parse(
+            "user_id=3&table=pilgrims&record_id=42&from=2026-10-01T00%3A00%3A00%2B02%3A00&limit=5000",
+        )
+        .unwrap();
+        assert_eq!(q.user_id, Some(3));
+        assert_eq!(q.table_name.as_deref(), Some("pilgrims"));
+        assert_eq!(q.record_id.as_deref(), Some("42"));
+        assert_eq!(
+            q.from.map(audit::timestamp).as_deref(),
+            Some("2026-09-30T22:00:00.000000Z")
+        );
+        assert_eq!(q.limit, MAX_QUERY_LIMIT);
+        assert_eq!(AuditQuery::parse("").unwrap().limit, DEFAULT_QUERY_LIMIT);
+        assert!(AuditQuery::parse("user=3").is_err());
+        assert!(AuditQuery::parse("from=yesterday").is_err());
+    }
+
+    #[test]
+    fn reads_stored_timestamps() {
+        for stored in [
+            "2026-10-18T09:30:00.123456Z",
+            "2026-10-18T09:30:00.123456+00:00",
+            "2026-10-18 09:30:00.123456+00:00",
+            "2026-10-18 09:30:00.123456",
+        ] {
+            assert_eq!(
+                parse_timestamp(stored).map(audit::timestamp).as_deref(),
+                Some("2026-10-18T09:30:00.123456Z"),
+                "{stored}"
+            );
+        }
+    }
+}
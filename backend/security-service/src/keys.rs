@@ -0,0 +1,73 @@
+//! Master and blind-index keys from Spin variables.
+//!
+//! - `encryption_key`: base64 of the current 32-byte master key.
+//! - `encryption_key_id`: the id envelopes record for it (default `k1`).
+//! - `encryption_previous_keys`: retired master keys still named by stored
+//!   envelopes, as comma-separated `id:base64` pairs.
+//! - `blind_index_key`: base64 of at least 32 bytes. It is independent of the
+//!   master keys, so rotating them keeps blind indexes valid.
+
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use pii_envelope::{Keyring, MasterKey};
+use spin_sdk::variables;
+
+const DEFAULT_KEY_ID: &str = "k1";
+
+fn setting(name: &str) -> Option<String> {
+    variables::get(name)
+        .ok()
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+fn decode(name: &str, value: &str) -> Result<Vec<u8>> {
+    STANDARD
+        .decode(value.trim())
+        .with_context(|| format!("{name} is not valid base64"))
+}
+
+pub fn load() -> Result<Keyring> {
+    let current_key =
+        setting("encryption_key").ok_or_else(|| anyhow!("encryption_key is not configured"))?;
+    let current_id = setting("encryption_key_id").unwrap_or_else(|| DEFAULT_KEY_ID.to_string());
+    let current = MasterKey::new(&current_id, &decode("encryption_key", &current_key)?)?;
+    let previous = parse_previous(&setting("encryption_previous_keys").unwrap_or_default())?;
+    let blind_index_key =
+        setting("blind_index_key").ok_or_else(|| anyhow!("blind_index_key is not configured"))?;
+    Ok(Keyring::new(
+        current,
+        previous,
+        &decode("blind_index_key", &blind_index_key)?,
+    )?)
+}
+
+fn parse_previous(list: &str) -> Result<Vec<MasterKey>> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (id, key) = entry
+                .split_once(':')
+                .ok_or_else(|| anyhow!("encryption_previous_keys entries are id:base64"))?;
+            let key = decode(&format!("encryption_previous_keys[{id}]"), key)?;
+            Ok(MasterKey::new(id.trim(), &key)?)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_retired_keys() {
+        let key = STANDARD.encode([1u8; 32]);
+        let parsed = parse_previous(&format!("k1:{key}, k0:{key},")).unwrap();
+        let ids: Vec<&str> = parsed.iter().map(MasterKey::id).collect();
+        assert_eq!(ids, ["k1", "k0"]);
+        assert!(parse_previous("").unwrap().is_empty());
+        assert!(parse_previous(&key).is_err());
+        assert!(parse_previous("k1:c2hvcnQ=").is_err());
+    }
+}
@@ -3,18 +3,48 @@
 #![allow(
     clippy::module_name_repetitions,
     clippy::missing_errors_doc,
-    clippy::missing_panics_doc
+    clippy::missing_panics_doc,
+    // Raised inside the bindings `http_component` generates.
+    clippy::same_length_and_capacity
 )]
 
+mod audit;
+mod keys;
+mod rotation;
+mod scan;
+
 use anyhow::Result;
-use base64::Engine;
 use http::StatusCode;
 use serde::{Deserialize, Serialize};
+use shared::audit::AuditEntry;
+use shared::gateway_token::{
+    verified_user_claims, verify_service_token, AUTH_ISSUER, BOOKING_ISSUER, GATEWAY_TOKEN_HEADER,
+    INTERNAL_TOKEN_SECRET_VAR, NOTIFICATION_ISSUER, OPERATOR_ISSUER, USER_CLAIMS_HEADER,
+};
 use spin_sdk::http::{Request, Response, Method};
 use spin_sdk::http_component;
 use std::collections::HashMap;
 use tokio::task;
 
+use audit::AuditQuery;
+
+/// Our name in the gateway's configuration, the audience of its tokens.
+const SERVICE_NAME: &str = "security-service";
+const ADMIN_ROLE: &str = "admin";
+/// Who may call the plaintext, key and audit-append endpoints: the issuers
+/// of service tokens signed with the shared secret.
+const INTERNAL_CALLERS: [&str; 4] = [
+    AUTH_ISSUER,
+    BOOKING_ISSUER,
+    NOTIFICATION_ISSUER,
+    OPERATOR_ISSUER,
+];
+/// Tables whose values are only decrypted for a named row, so every read
+/// lands in the audit log.
+const AUDITED_TABLES: [&str; 1] = ["pilgrims"];
+
+use pii_envelope::{CryptoError, Keyring};
+
 #[derive(Serialize, Deserialize)]
 struct SecurityScanRequest {
     content: String,
@@ -30,6 +60,13 @@ struct SecurityScanResult {
     details: Vec<ThreatDetail>,
     scan_duration_ms: u64,
     confidence_score: f64,
+    /// The text with everything but safe markup removed, to render instead
+    /// of the original.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sanitized_content: Option<String>,
+    /// An upload's type as read from its content.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detected_type: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -44,7 +81,13 @@ struct ThreatDetail {
 #[derive(Serialize, Deserialize)]
 struct EncryptionRequest {
     data: String,
-    key_id: Option<String>,
+    /// Where the value will be stored, e.g. `pilgrims.phone_encrypted`. The
+    /// same context must be given to decrypt it.
+    #[serde(default)]
+    context: String,
+    /// Also return the value's blind index, computed in this context.
+    #[serde(default)]
+    blind_index_context: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -53,150 +96,70 @@ struct EncryptionResult {
     key_id: String,
     algorithm: String,
     timestamp: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    blind_index: Option<String>,
 }
 
-fn detect_xss_patterns(content: &str) -> Vec<ThreatDetail> {
-    let xss_patterns = [
-        (r"<script[^>]*>", "Script injection", "high"),
-        (r"javascript:", "JavaScript URL", "medium"),
-        (r"on\w+\s*=", "Event handler injection", "medium"),
-        (r"<iframe[^>]*>", "Iframe injection", "medium"),
-        (r"eval\s*\(", "Eval function", "high"),
-    ];
-
-    let mut threats = Vec::new();
-
-    for (pattern, threat_type, severity) in &xss_patterns {
-        if let Ok(regex) = regex::Regex::new(pattern) {
-            if regex.is_match(&content.to_lowercase()) {
-                threats.push(ThreatDetail {
-                    threat_type: format!("XSS: {}", threat_type),
-                    severity: severity.to_string(),
-                    description: format!("Detected potential XSS pattern: {}", threat_type),
-                    location: None,
-                    recommendation: "Sanitize input and encode output".to_string(),
-                });
-            }
-        }
-    }
+#[derive(Deserialize)]
+struct DecryptionRequest {
+    encrypted_data: String,
+    #[serde(default)]
+    context: String,
+    /// The row the value belongs to. When given, the read is recorded in
+    /// the audit log against the context's table; for [`AUDITED_TABLES`] it
+    /// is required.
+    #[serde(default)]
+    record_id: Option<String>,
+    /// `users.id` of the person the value is decrypted for.
+    #[serde(default)]
+    user_id: Option<i64>,
+}
 
-    threats
+#[derive(Deserialize)]
+struct BlindIndexRequest {
+    value: String,
+    context: String,
 }
 
-fn detect_sql_injection(content: &str) -> Vec<ThreatDetail> {
-    let sql_patterns = [
-        (r"(?i)union\s+select", "UNION SELECT injection", "high"),
-        (
-            r"(?i)'\s*or\s*'1'\s*=\s*'1",
-            "Boolean-based injection",
-            "high",
-        ),
-        (r"(?i);\s*drop\s+table", "DROP TABLE command", "critical"),
-        (r"(?i)'\s*;\s*exec", "Command execution", "critical"),
-        (r"(?i)load_file\s*\(", "File disclosure", "high"),
-    ];
-
-    let mut threats = Vec::new();
-
-    for (pattern, threat_type, severity) in &sql_patterns {
-        if let Ok(regex) = regex::Regex::new(pattern) {
-            if regex.is_match(content) {
-                threats.push(ThreatDetail {
-                    threat_type: format!("SQL Injection: {}", threat_type),
-                    severity: severity.to_string(),
-                    description: format!("Detected potential SQL injection: {}", threat_type),
-                    location: None,
-                    recommendation: "Use parameterized queries".to_string(),
-                });
-            }
-        }
-    }
+#[derive(Deserialize, Default)]
+struct RotationRequest {
+    batch_size: Option<u32>,
+}
 
-    threats
-}
-
-fn detect_malware_signatures(content: &str) -> Vec<ThreatDetail> {
-    let malware_patterns = [
-        (r"(?i)cmd\.exe", "Command execution", "high"),
-        (r"(?i)powershell", "PowerShell execution", "medium"),
-        (r"(?i)base64_decode", "Base64 obfuscation", "medium"),
-        (r"(?i)shell_exec", "Shell execution", "high"),
-        (r"(?i)system\s*\(", "System command", "high"),
-    ];
-
-    let mut threats = Vec::new();
-
-    for (pattern, threat_type, severity) in &malware_patterns {
-        if let Ok(regex) = regex::Regex::new(pattern) {
-            if regex.is_match(content) {
-                threats.push(ThreatDetail {
-                    threat_type: format!("Malware: {}", threat_type),
-                    severity: severity.to_string(),
-                    description: format!("Detected potential malware signature: {}", threat_type),
-                    location: None,
-                    recommendation: "Quarantine and analyze further".to_string(),
-                });
-            }
+impl From<scan::Finding> for ThreatDetail {
+    fn from(finding: scan::Finding) -> Self {
+        Self {
+            threat_type: finding.threat_type,
+            severity: finding.severity.as_str().to_string(),
+            description: finding.description,
+            location: finding.location,
+            recommendation: finding.recommendation.to_string(),
         }
     }
-
-    threats
 }
 
-async fn perform_comprehensive_scan(
-    content: String,
-    _scan_type: String,
-) -> Result<SecurityScanResult> {
+fn perform_scan(input: &scan::Input<'_>) -> SecurityScanResult {
     let start_time = std::time::Instant::now();
 
-    let content_arc = std::sync::Arc::new(content);
-
-    let xss_task = task::spawn({
-        let c = content_arc.clone();
-        async move { detect_xss_patterns(c.as_str()) }
-    });
-
-    let sql_task = task::spawn({
-        let c = content_arc.clone();
-        async move { detect_sql_injection(c.as_str()) }
-    });
-
-    let malware_task = task::spawn({
-        let c = content_arc.clone();
-        async move { detect_malware_signatures(c.as_str()) }
-    });
-
-    let entropy_task = task::spawn({
-        let c = content_arc.clone();
-        async move {
-            tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
-            calculate_entropy(c.as_str())
-        }
-    });
-
-    let (xss_threats, sql_threats, malware_threats, entropy_score) =
-        tokio::try_join!(xss_task, sql_task, malware_task, entropy_task)?;
-
-    let mut all_threats = Vec::new();
-    all_threats.extend(xss_threats);
-    all_threats.extend(sql_threats);
-    all_threats.extend(malware_threats);
-
-    if entropy_score > 7.5 {
-        all_threats.push(ThreatDetail {
-            threat_type: "High Entropy".to_string(),
-            severity: "medium".to_string(),
-            description: "Content has high entropy, possibly obfuscated".to_string(),
-            location: None,
-            recommendation: "Review for obfuscated code".to_string(),
-        });
-    }
+    let all_threats: Vec<ThreatDetail> = scan::Scanner::default()
+        .scan(input)
+        .into_iter()
+        .map(ThreatDetail::from)
+        .collect();
 
-    let threats_count = all_threats.len() as u32;
+    let threats_count = u32::try_from(all_threats.len()).unwrap_or(u32::MAX);
     let risk_level = determine_risk_level(&all_threats);
-    let confidence_score = calculate_confidence_score(&all_threats, content_arc.as_str());
+    let (sanitized_content, detected_type, length) = match input {
+        scan::Input::Text(text) => (Some(scan::sanitize(text)), None, text.len()),
+        scan::Input::Upload { bytes, .. } => (
+            None,
+            scan::FileType::sniff(bytes).map(|t| t.mime().to_string()),
+            bytes.len(),
+        ),
+    };
+    let confidence_score = calculate_confidence_score(&all_threats, length);
 
-    Ok(SecurityScanResult {
+    SecurityScanResult {
         status: if threats_count > 0 {
             "threats_detected"
         } else {
@@ -206,30 +169,11 @@ async fn perform_comprehensive_scan(
         threats_detected: threats_count,
         risk_level,
         details: all_threats,
-        scan_duration_ms: start_time.elapsed().as_millis() as u64,
+        scan_duration_ms: u64::try_from(start_time.elapsed().as_millis()).unwrap_or(u64::MAX),
         confidence_score,
-    })
-}
-
-fn calculate_entropy(content: &str) -> f64 {
-    if content.is_empty() {
-        return 0.0;
-    }
-
-    let mut char_counts = HashMap::new();
-    for c in content.chars() {
-        *char_counts.entry(c).or_insert(0) += 1;
-    }
-
-    let len = content.len() as f64;
-    let mut entropy = 0.0;
-
-    for count in char_counts.values() {
-        let frequency = *count as f64 / len;
-        entropy -= frequency * frequency.log2();
+        sanitized_content,
+        detected_type,
     }
-
-    entropy
 }
 
 fn determine_risk_level(threats: &[ThreatDetail]) -> String {
@@ -250,13 +194,14 @@ fn determine_risk_level(threats: &[ThreatDetail]) -> String {
     }
 }
 
-fn calculate_confidence_score(threats: &[ThreatDetail], content: &str) -> f64 {
+#[allow(clippy::cast_precision_loss)]
+fn calculate_confidence_score(threats: &[ThreatDetail], content_length: usize) -> f64 {
     if threats.is_empty() {
         return 0.95;
     }
 
     let base_confidence = 0.8;
-    let content_length_factor = (content.len() as f64 / 1000.0).min(1.0);
+    let content_length_factor = (content_length as f64 / 1000.0).min(1.0);
     let threat_diversity = threats
         .iter()
         .map(|t| &t.threat_type)
@@ -266,38 +211,43 @@ fn calculate_confidence_score(threats: &[ThreatDetail], content: &str) -> f64 {
     (base_confidence + content_length_factor * 0.1 + threat_diversity * 0.05).min(0.99)
 }
 
-async fn perform_encryption(data: String, key_id: Option<String>) -> Result<EncryptionResult> {
-    let actual_key_id = key_id.unwrap_or_else(|| "default-key-2024".to_string());
-
-    let encryption_task = task::spawn(async move {
-        tokio::time::sleep(tokio::time::Duration::from_millis(25)).await;
-
-        let encrypted = base64::engine::general_purpose::STANDARD.encode(format!("encrypted:{}", data));
-
-        EncryptionResult {
-            encrypted_data: encrypted,
-            key_id: actual_key_id,
-            algorithm: "AES-256-GCM".to_string(),
-            timestamp: {
-                let dur = std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap_or_else(|_| std::time::Duration::from_secs(0));
-                dur.as_secs()
-            },
-        }
-    });
-
-    encryption_task.await.map_err(|e| e.into())
+fn perform_encryption(
+    keyring: &Keyring,
+    req: &EncryptionRequest,
+) -> Result<EncryptionResult, CryptoError> {
+    let encrypted_data = keyring.encrypt(req.data.as_bytes(), &req.context)?;
+    Ok(EncryptionResult {
+        encrypted_data,
+        key_id: keyring.current_key_id().to_string(),
+        algorithm: pii_envelope::ALGORITHM.to_string(),
+        timestamp: {
+            let dur = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_else(|_| std::time::Duration::from_secs(0));
+            dur.as_secs()
+        },
+        blind_index: req
+            .blind_index_context
+            .as_deref()
+            .map(|context| keyring.blind_index(&req.data, context)),
+    })
 }
 
 #[http_component]
 async fn handle_request(req: Request) -> Result<Response> {
     let method = req.method();
-    let path = req.uri();
+    let path = req.path();
 
     match (method, path) {
-        (&Method::Post, "/security/scan") => handle_security_scan(req).await,
-        (&Method::Post, "/security/encrypt") => handle_encryption(req).await,
+        (&Method::Post, "/security/scan") => handle_security_scan(&req),
+        (&Method::Post, "/security/scan/upload") => handle_upload_scan(&req),
+        (&Method::Post, "/security/encrypt") => handle_encryption(&req),
+        (&Method::Post, "/security/decrypt") => handle_decryption(&req),
+        (&Method::Post, "/security/blind-index") => handle_blind_index(&req),
+        (&Method::Post, "/security/rotate") => handle_rotation(&req),
+        (&Method::Post, "/security/audit") => handle_audit_record(&req),
+        (&Method::Get, "/security/audit") => handle_audit_query(&req),
+        (&Method::Get, "/security/audit/verify") => handle_audit_verify(&req),
         (&Method::Post, "/security/validate") => handle_validation(req).await,
         (&Method::Get, "/security/status") => handle_security_status().await,
         _ => Ok(Response::builder()
@@ -308,9 +258,8 @@ async fn handle_request(req: Request) -> Result<Response> {
     }
 }
 
-async fn handle_security_scan(req: Request) -> Result<Response> {
-    let body_bytes = req.into_body();
-    let body = std::str::from_utf8(&body_bytes)?;
+fn handle_security_scan(req: &Request) -> Result<Response> {
+    let body = std::str::from_utf8(req.body())?;
     let scan_req: SecurityScanRequest =
         serde_json::from_str(body).unwrap_or_else(|_| SecurityScanRequest {
             content: String::new(),
@@ -318,7 +267,7 @@ async fn handle_security_scan(req: Request) -> Result<Response> {
             metadata: None,
         });
 
-    let result = perform_comprehensive_scan(scan_req.content, scan_req.scan_type).await?;
+    let result = perform_scan(&scan::Input::Text(&scan_req.content));
 
     Ok(Response::builder()
         .status(StatusCode::OK)
@@ -328,21 +277,267 @@ async fn handle_security_scan(req: Request) -> Result<Response> {
         .build())
 }
 
-async fn handle_encryption(req: Request) -> Result<Response> {
-    let body_bytes = req.into_body();
-    let body = std::str::from_utf8(&body_bytes)?;
-    let enc_req: EncryptionRequest = serde_json::from_str(body)?;
-
-    let result = perform_encryption(enc_req.data, enc_req.key_id).await?;
+/// The raw file as the body, its claimed type as `content-type`.
+fn handle_upload_scan(req: &Request) -> Result<Response> {
+    let declared_type = req.header("content-type").and_then(|v| v.as_str());
+    let result = perform_scan(&scan::Input::Upload {
+        bytes: req.body(),
+        declared_type,
+    });
+    json_response(StatusCode::OK, &result)
+}
 
+fn json_response(status: StatusCode, body: &impl Serialize) -> Result<Response> {
     Ok(Response::builder()
-        .status(StatusCode::OK)
+        .status(status)
         .header("content-type", "application/json")
-        .header("Access-Control-Allow-Origin", "*")
-        .body(serde_json::to_vec(&result)?)
+        .header("cache-control", "no-store")
+        .body(serde_json::to_vec(body)?)
         .build())
 }
 
+fn error_response(status: StatusCode, error: &str) -> Result<Response> {
+    json_response(status, &serde_json::json!({ "error": error }))
+}
+
+fn crypto_error_response(e: &CryptoError) -> Result<Response> {
+    let status = match e {
+        CryptoError::Malformed | CryptoError::Decryption | CryptoError::UnknownKey(_) => {
+            StatusCode::UNPROCESSABLE_ENTITY
+        }
+        CryptoError::InvalidKey(_) | CryptoError::Randomness => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    error_response(status, &e.to_string())
+}
+
+/// Keys come from Spin variables; without them nothing can be encrypted.
+fn load_keyring() -> Result<Keyring, Response> {
+    keys::load().map_err(|e| {
+        let body = serde_json::json!({ "error": format!("Encryption is not configured: {e}") });
+        Response::builder()
+            .status(StatusCode::SERVICE_UNAVAILABLE)
+            .header("content-type", "application/json")
+            .body(body.to_string())
+            .build()
+    })
+}
+
+/// Plaintext and key operations are for [`INTERNAL_CALLERS`] only, proven
+/// by a service token. Requests without one, or forwarded by the public
+/// gateway, are refused, as is everything while the secret is unset.
+fn require_internal(req: &Request) -> Option<Response> {
+    let secret = match spin_sdk::variables::get(INTERNAL_TOKEN_SECRET_VAR) {
+        Ok(secret) if !secret.is_empty() => secret,
+        _ => {
+            return error_response(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Gateway secret not configured",
+            )
+            .ok()
+        }
+    };
+    let token = req.header(GATEWAY_TOKEN_HEADER).and_then(|h| h.as_str());
+    let now = u64::try_from(chrono::Utc::now().timestamp()).unwrap_or(0);
+    match verify_service_token(token, secret.as_bytes(), SERVICE_NAME, &INTERNAL_CALLERS, now) {
+        Ok(_) => None,
+        Err(e) => error_response(
+            StatusCode::FORBIDDEN,
+            &format!("Only available to internal services: {e}"),
+        )
+        .ok(),
+    }
+}
+
+fn handle_encryption(req: &Request) -> Result<Response> {
+    if let Some(resp) = require_internal(req) {
+        return Ok(resp);
+    }
+    let enc_req: EncryptionRequest = match serde_json::from_slice(req.body()) {
+        Ok(r) => r,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, &e.to_string()),
+    };
+    let keyring = match load_keyring() {
+        Ok(k) => k,
+        Err(resp) => return Ok(resp),
+    };
+
+    match perform_encryption(&keyring, &enc_req) {
+        Ok(result) => json_response(StatusCode::OK, &result),
+        Err(e) => crypto_error_response(&e),
+    }
+}
+
+fn handle_decryption(req: &Request) -> Result<Response> {
+    if let Some(resp) = require_internal(req) {
+        return Ok(resp);
+    }
+    let dec_req: DecryptionRequest = match serde_json::from_slice(req.body()) {
+        Ok(r) => r,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, &e.to_string()),
+    };
+    let (table, _) = context_parts(&dec_req.context);
+    if dec_req.record_id.is_none() && AUDITED_TABLES.contains(&table) {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            &format!("record_id is required to decrypt {table} data"),
+        );
+    }
+    let keyring = match load_keyring() {
+        Ok(k) => k,
+        Err(resp) => return Ok(resp),
+    };
+
+    match keyring.decrypt(&dec_req.encrypted_data, &dec_req.context) {
+        Ok(decrypted) => match String::from_utf8(decrypted.plaintext) {
+            Ok(data) => {
+                if let Err(e) = audit_read(&dec_req) {
+                    return error_response(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string());
+                }
+                json_response(
+                    StatusCode::OK,
+                    &serde_json::json!({ "data": data, "key_id": decrypted.key_id }),
+                )
+            }
+            Err(_) => {
+                error_response(StatusCode::UNPROCESSABLE_ENTITY, "Decrypted value is not UTF-8")
+            }
+        },
+        Err(e) => crypto_error_response(&e),
+    }
+}
+
+/// Records who read which field of which row. Plaintext is only released
+/// once the read is on record.
+fn audit_read(req: &DecryptionRequest) -> Result<()> {
+    let Some(record_id) = &req.record_id else {
+        return Ok(());
+    };
+    let (table, field) = context_parts(&req.context);
+    let mut entry = AuditEntry::read(table, record_id).by(req.user_id);
+    entry.new_values = Some(serde_json::json!({ "fields": [field] }));
+    audit::record(&entry)?;
+    Ok(())
+}
+
+/// `pilgrims.phone_encrypted` as (`pilgrims`, `phone_encrypted`).
+fn context_parts(context: &str) -> (&str, &str) {
+    context.split_once('.').unwrap_or((context, ""))
+}
+
+/// Deterministic lookup key for an identifier, e.g. a document number in
+/// the `pilgrims.document_number` context, to find a returning pilgrim by
+/// `document_number_blind_index`.
+fn handle_blind_index(req: &Request) -> Result<Response> {
+    if let Some(resp) = require_internal(req) {
+        return Ok(resp);
+    }
+    let index_req: BlindIndexRequest = match serde_json::from_slice(req.body()) {
+        Ok(r) => r,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, &e.to_string()),
+    };
+    let keyring = match load_keyring() {
+        Ok(k) => k,
+        Err(resp) => return Ok(resp),
+    };
+    json_response(
+        StatusCode::OK,
+        &serde_json::json!({
+            "blind_index": keyring.blind_index(&index_req.value, &index_req.context),
+        }),
+    )
+}
+
+/// Runs the re-encryption job; see [`rotation`].
+fn handle_rotation(req: &Request) -> Result<Response> {
+    if let Some(resp) = require_internal(req) {
+        return Ok(resp);
+    }
+    let rotation_req: RotationRequest = serde_json::from_slice(req.body()).unwrap_or_default();
+    let keyring = match load_keyring() {
+        Ok(k) => k,
+        Err(resp) => return Ok(resp),
+    };
+    let batch_size = rotation_req
+        .batch_size
+        .unwrap_or(rotation::DEFAULT_BATCH_SIZE)
+        .clamp(1, 1000);
+    match rotation::run(&keyring, batch_size) {
+        Ok(report) => json_response(StatusCode::OK, &report),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+    }
+}
+
+/// Appends an entry for another service, e.g. after it changed a booking.
+fn handle_audit_record(req: &Request) -> Result<Response> {
+    if let Some(resp) = require_internal(req) {
+        return Ok(resp);
+    }
+    let entry: AuditEntry = match serde_json::from_slice(req.body()) {
+        Ok(e) => e,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, &e.to_string()),
+    };
+    match audit::record(&entry) {
+        Ok(recorded) => json_response(StatusCode::CREATED, &recorded),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+    }
+}
+
+/// `GET /security/audit?user_id=&table=&record_id=&from=&to=&limit=`, for
+/// admins through the gateway.
+fn handle_audit_query(req: &Request) -> Result<Response> {
+    if let Some(resp) = require_admin(req) {
+        return Ok(resp);
+    }
+    let filter = match AuditQuery::parse(req.query()) {
+        Ok(f) => f,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, &e.to_string()),
+    };
+    match audit::query(&filter) {
+        Ok(entries) => json_response(StatusCode::OK, &serde_json::json!({ "entries": entries })),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+    }
+}
+
+fn handle_audit_verify(req: &Request) -> Result<Response> {
+    if let Some(resp) = require_admin(req) {
+        return Ok(resp);
+    }
+    match audit::verify() {
+        Ok(report) => json_response(StatusCode::OK, &report),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+    }
+}
+
+/// The audit trail is for admins, whose role the gateway vouches for.
+fn require_admin(req: &Request) -> Option<Response> {
+    let Ok(secret) = spin_sdk::variables::get(INTERNAL_TOKEN_SECRET_VAR) else {
+        return error_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Gateway secret not configured",
+        )
+        .ok();
+    };
+    let header = |name: &str| req.header(name).and_then(|h| h.as_str());
+    let claims = verified_user_claims(
+        header(GATEWAY_TOKEN_HEADER),
+        header(USER_CLAIMS_HEADER),
+        secret.as_bytes(),
+        SERVICE_NAME,
+        u64::try_from(chrono::Utc::now().timestamp()).unwrap_or(0),
+    );
+    match claims {
+        Ok(claims)
+            if claims
+                .get("roles")
+                .is_some_and(|roles| roles.split(',').any(|r| r.trim() == ADMIN_ROLE)) =>
+        {
+            None
+        }
+        Ok(_) => error_response(StatusCode::FORBIDDEN, "Admins only").ok(),
+        Err(e) => error_response(StatusCode::UNAUTHORIZED, &e.to_string()).ok(),
+    }
+}
+
 async fn handle_validation(req: Request) -> Result<Response> {
     let body_bytes = req.into_body();
     let body = std::str::from_utf8(&body_bytes)?;
@@ -382,12 +577,14 @@ async fn handle_security_status() -> Result<Response> {
             "service_status": "healthy",
             "security_level": "high",
             "active_protections": [
-                "xss_detection",
-                "sql_injection_prevention",
-                "malware_scanning",
-                "entropy_analysis",
-                "rate_limiting"
+                "html_sanitization",
+                "sql_injection_tokenizer",
+                "upload_validation",
+                "rate_limiting",
+                "envelope_encryption",
+                "audit_hash_chain"
             ],
+            "scan_detectors": scan::Scanner::default().detectors(),
             "threat_intelligence": {
                 "last_update": "2024-01-20T10:00:00Z",
                 "signatures_count": 15420,
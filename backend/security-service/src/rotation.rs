@@ -0,0 +1,204 @@
+//! Re-encryption job run after a master key rotation.
+//!
+//! Envelopes still wrapped by a retired master key get their data key
+//! re-wrapped under the current one; values from before envelope encryption
+//! existed are encrypted for real. Missing document-number blind indexes are
+//! filled in on the way. A run walks each column in pages keyed on `id`, so
+//! rows that fail are passed over rather than fetched again. The job is
+//! called until `remaining` is zero; once `failed` is empty too, the retired
+//! key can be removed from `encryption_previous_keys`.
+
+use anyhow::Result;
+use pii_envelope::Keyring;
+use serde::Serialize;
+use spin_sdk::sqlite::{Connection, Value};
+
+/// Encrypted columns of `pilgrims`, as named in the database. Each value's
+/// encryption context is `pilgrims.{column}`.
+pub const PILGRIM_ENCRYPTED_COLUMNS: [&str; 10] = [
+    "first_name_encrypted",
+    "last_name1_encrypted",
+    "last_name2_encrypted",
+    "birth_date_encrypted",
+    "document_number_encrypted",
+    "phone_encrypted",
+    "email_encrypted",
+    "address_street_encrypted",
+    "address_street2_encrypted",
+    "address_city_encrypted",
+];
+
+/// Context of the document number's blind index.
+pub const DOCUMENT_NUMBER_INDEX_CONTEXT: &str = "pilgrims.document_number";
+
+pub const DEFAULT_BATCH_SIZE: u32 = 200;
+
+#[must_use]
+pub fn context(column: &str) -> String {
+    format!("pilgrims.{column}")
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct RotationReport {
+    pub key_id: String,
+    pub rewrapped: u32,
+    /// Legacy `encrypted:` values now properly encrypted.
+    pub upgraded: u32,
+    pub indexed: u32,
+    /// Values that could not be read, e.g. wrapped by a key no longer
+    /// configured. They are left as they are and not counted in
+    /// `remaining`.
+    pub failed: Vec<String>,
+    /// Values still not under the current key after this run, other than
+    /// the failed ones, e.g. written while it ran.
+    pub remaining: u32,
+}
+
+fn text(value: &str) -> Value {
+    Value::Text(value.to_string())
+}
+
+/// Rotates every column, reading `batch_size` rows at a time.
+pub fn run(keyring: &Keyring, batch_size: u32) -> Result<RotationReport> {
+    let conn = Connection::open_default()?;
+    let current = format!("v1:{}:", keyring.current_key_id());
+    let prefix_len = i64::try_from(current.len())?;
+    let mut report = RotationReport {
+        key_id: keyring.current_key_id().to_string(),
+        ..RotationReport::default()
+    };
+
+    for column in PILGRIM_ENCRYPTED_COLUMNS {
+        let failed_before = report.failed.len();
+        let mut last_id = 0;
+        loop {
+            let stale = conn.execute(
+                &format!(
+                    "SELECT id, {column} AS value FROM pilgrims \
+                     WHERE id > ? AND {column} IS NOT NULL AND substr({column}, 1, ?) <> ? \
+                     ORDER BY id LIMIT ?"
+                ),
+                &[
+                    Value::Integer(last_id),
+                    Value::Integer(prefix_len),
+                    text(&current),
+                    Value::Integer(i64::from(batch_size)),
+                ],
+            )?;
+            let mut fetched = 0;
+            for row in stale.rows() {
+                fetched += 1;
+                let Some(id) = row.get::<i64>("id") else {
+                    continue;
+                };
+                last_id = id;
+                let Some(value) = row.get::<&str>("value") else {
+                    continue;
+                };
+                let replaced = if pii_envelope::key_id(value).is_some() {
+                    keyring.rewrap(value).map(|r| r.map(|v| (v, false)))
+                } else if let Some(plaintext) = pii_envelope::legacy_plaintext(value) {
+                    keyring
+                        .encrypt(&plaintext, &context(column))
+                        .map(|v| Some((v, true)))
+                } else {
+                    Err(pii_envelope::CryptoError::Malformed)
+                };
+                match replaced {
+                    Ok(Some((sealed, upgraded))) => {
+                        conn.execute(
+                            &format!("UPDATE pilgrims SET {column} = ? WHERE id = ?"),
+                            &[text(&sealed), Value::Integer(id)],
+                        )?;
+                        if upgraded {
+                            report.upgraded += 1;
+                        } else {
+                            report.rewrapped += 1;
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(e) => report.failed.push(format!("pilgrims.{column}#{id}: {e}")),
+                }
+            }
+            if fetched < batch_size {
+                break;
+            }
+        }
+
+        let left = conn.execute(
+            &format!(
+                "SELECT COUNT(*) AS n FROM pilgrims \
+                 WHERE {column} IS NOT NULL AND substr({column}, 1, ?) <> ?"
+            ),
+            &[Value::Integer(prefix_len), text(&current)],
+        )?;
+        let left = left
+            .rows()
+            .next()
+            .and_then(|row| row.get::<i64>("n"))
+            .unwrap_or(0);
+        let failed = i64::try_from(report.failed.len() - failed_before)?;
+        report.remaining += u32::try_from((left - failed).max(0)).unwrap_or(u32::MAX);
+    }
+
+    report.indexed = backfill_document_index(&conn, keyring, batch_size, &mut report.failed)?;
+    Ok(report)
+}
+
+/// Computes the blind index of pilgrims registered before it existed.
+fn backfill_document_index(
+    conn: &Connection,
+    keyring: &Keyring,
+    batch_size: u32,
+    failed: &mut Vec<String>,
+) -> Result<u32> {
+    let context = context("document_number_encrypted");
+    let mut indexed = 0;
+    let mut last_id = 0;
+    loop {
+        let missing = conn.execute(
+            "SELECT id, document_number_encrypted AS value FROM pilgrims \
+             WHERE id > ? AND document_number_blind_index IS NULL \
+             AND document_number_encrypted IS NOT NULL \
+             ORDER BY id LIMIT ?",
+            &[
+                Value::Integer(last_id),
+                Value::Integer(i64::from(batch_size)),
+            ],
+        )?;
+        let mut fetched = 0;
+        for row in missing.rows() {
+            fetched += 1;
+            let Some(id) = row.get::<i64>("id") else {
+                continue;
+            };
+            last_id = id;
+            let Some(value) = row.get::<&str>("value") else {
+                continue;
+            };
+            let plaintext = match keyring.decrypt(value, &context) {
+                Ok(decrypted) => decrypted.plaintext,
+                Err(e) => {
+                    let Some(plaintext) = pii_envelope::legacy_plaintext(value) else {
+                        failed.push(format!("pilgrims.document_number_blind_index#{id}: {e}"));
+                        continue;
+                    };
+                    plaintext.to_vec()
+                }
+            };
+            let index = keyring.blind_index(
+                &String::from_utf8_lossy(&plaintext),
+                DOCUMENT_NUMBER_INDEX_CONTEXT,
+            );
+            conn.execute(
+                "UPDATE pilgrims SET document_number_blind_index = ? WHERE id = ?",
+                &[text(&index), Value::Integer(id)],
+            )?;
+            indexed += 1;
+        }
+        if fetched < batch_size {
+            break;
+        }
+    }
+    Ok(indexed)
+}
@@ -0,0 +1,280 @@
+//! Precision and recall of the default scanner over a labelled corpus.
+//!
+//! Benign samples are the kind of thing guests write in reviews and forms,
+//! chosen to contain what keyword scanners trip on. Malicious samples are
+//! common payloads and their obfuscated forms. When a detector changes, add
+//! the case that prompted it here.
+
+use super::upload::fixtures;
+use super::{Input, Scanner};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Label {
+    Benign,
+    Xss,
+    Sql,
+    BadUpload,
+}
+
+const TEXT: &[(&str, Label)] = &[
+    // Reviews and form fields.
+    ("Great albergue, 10/10 would stay again!", Label::Benign),
+    (
+        "It's the best hospitalero on the Vía de la Plata. Don't miss the paella",
+        Label::Benign,
+    ),
+    (
+        "Rock 'n' roll at night from the bar next door, bring earplugs",
+        Label::Benign,
+    ),
+    (
+        "The pilgrims' union meets here; select your bunk early, don't drop by after 10",
+        Label::Benign,
+    ),
+    ("Rooms 5 or 6 are quieter -- ask for them", Label::Benign),
+    (
+        "5 stars, 1 night, 2 showers; 3 euros for the washing machine",
+        Label::Benign,
+    ),
+    (
+        "I'd say 1=1 perfect, the system works and nobody's cmd.exe-ing you around",
+        Label::Benign,
+    ),
+    (
+        "My laptop needed powershell updates so I used the wifi, which is fast",
+        Label::Benign,
+    ),
+    (
+        "We slept (well) and could sleep(ish) in until 8",
+        Label::Benign,
+    ),
+    (
+        "Bunks < 1m apart but > 2 showers per floor. I <3 this place",
+        Label::Benign,
+    ),
+    ("Tom & Jerry & me: 3 friends, 1 room :-)", Label::Benign),
+    (
+        "<p>Lovely <b>garden</b>, see <a href=\"https://example.org/map\">map</a></p>",
+        Label::Benign,
+    ),
+    (
+        "Our grandmother's O'Brien-style 'order by' list was posted at reception",
+        Label::Benign,
+    ),
+    ("Price: 15€ -- or 12€ with the credencial", Label::Benign),
+    ("O'Connor", Label::Benign),
+    ("D'Artagnan y Pérez", Label::Benign),
+    ("50% off on Sundays, 100% recommended", Label::Benign),
+    (
+        "Check-in 13:00; check-out 8:00. Update: they set the heating on",
+        Label::Benign,
+    ),
+    (
+        "Calle Real 1, 10820 Carrascalejo; tel. 927 00 00 00",
+        Label::Benign,
+    ),
+    (
+        "base64_decode? No idea what that is, but the coffee is great",
+        Label::Benign,
+    ),
+    (
+        "javascript: the only thing I didn't need on the Camino",
+        Label::Benign,
+    ),
+    (
+        "e-mail: peregrino@example.org <peregrino@example.org>",
+        Label::Benign,
+    ),
+    ("1 or 2 nights max, then move on to Cáceres", Label::Benign),
+    // Cross-site scripting.
+    ("<script>alert(1)</script>", Label::Xss),
+    ("<ScRiPt src=//evil.example/x.js></sCrIpT>", Label::Xss),
+    ("<img src=x oNeRrOr=alert(document.cookie)>", Label::Xss),
+    ("<svg/onload=alert(1)>", Label::Xss),
+    (
+        "<a href=\"jav&#x09;ascript&colon;alert(1)\">click</a>",
+        Label::Xss,
+    ),
+    ("<a href=\"  JaVaScRiPt:alert(1)\">x</a>", Label::Xss),
+    (
+        "<iframe src=\"data:text/html;base64,PHNjcmlwdD5hbGVydCgxKTwvc2NyaXB0Pg==\">",
+        Label::Xss,
+    ),
+    ("<body onpageshow=alert(1)>", Label::Xss),
+    ("<details open ontoggle=alert(1)>", Label::Xss),
+    ("<object data=\"javascript:alert(1)\">", Label::Xss),
+    (
+        "<iframe srcdoc=\"&lt;script&gt;alert(1)&lt;/script&gt;\">",
+        Label::Xss,
+    ),
+    (
+        "Nice stay%3Cimg%20src%3Dx%20onerror%3Dalert(1)%3E",
+        Label::Xss,
+    ),
+    (
+        "<form action=\"vbscript:msgbox(1)\"><input type=submit>",
+        Label::Xss,
+    ),
+    // SQL injection.
+    ("' OR '1'='1", Label::Sql),
+    ("' or 1=1 --", Label::Sql),
+    ("admin'--", Label::Sql),
+    ("admin'#", Label::Sql),
+    ("') OR ('a'='a", Label::Sql),
+    ("1 OR 1=1", Label::Sql),
+    ("'/**/oR/**/1/**/=/**/1#", Label::Sql),
+    ("' UNION SELECT username, password FROM users--", Label::Sql),
+    ("-1 UnIoN/**/aLl/**/SeLeCt 1,2,3", Label::Sql),
+    ("1; DROP TABLE pilgrims", Label::Sql),
+    ("x'; DELETE FROM bookings WHERE '1'='1", Label::Sql),
+    ("1' AND SLEEP(5)#", Label::Sql),
+    ("1 AND (SELECT 1 FROM (SELECT SLEEP(5))a)", Label::Sql),
+    ("'||(SELECT password FROM users LIMIT 1)||'", Label::Sql),
+    ("1' ORDER BY 3--", Label::Sql),
+    ("\" or \"\"=\"", Label::Sql),
+    ("'; EXEC xp_cmdshell('dir')--", Label::Sql),
+    ("%27%20OR%201%3D1--%20", Label::Sql),
+    ("1'; WAITFOR DELAY '0:0:5'--", Label::Sql),
+];
+
+fn upload_corpus() -> Vec<(&'static str, Vec<u8>, Option<&'static str>, Label)> {
+    let mut with_trailer = fixtures::jpeg();
+    with_trailer.extend_from_slice(b"PK\x03\x04\x14\0\0\0\x08\0");
+    let mut with_script = fixtures::png();
+    with_script.splice(
+        33..33,
+        b"\0\0\0\x12tEXt<script>x</script>\0\0\0\0".iter().copied(),
+    );
+    let mut multi_picture = fixtures::jpeg();
+    multi_picture.extend_from_slice(&fixtures::jpeg());
+    let mut padded = fixtures::png();
+    padded.extend_from_slice(&[0; 16]);
+    vec![
+        ("jpeg", fixtures::jpeg(), Some("image/jpeg"), Label::Benign),
+        (
+            "multi-picture jpeg",
+            multi_picture,
+            Some("image/jpeg"),
+            Label::Benign,
+        ),
+        ("png", fixtures::png(), Some("image/png"), Label::Benign),
+        ("padded png", padded, None, Label::Benign),
+        ("webp", fixtures::webp(), Some("image/webp"), Label::Benign),
+        ("heic", fixtures::heic(), Some("image/heic"), Label::Benign),
+        (
+            "pdf",
+            fixtures::pdf("/Pages 2 0 R "),
+            Some("application/pdf"),
+            Label::Benign,
+        ),
+        (
+            "jpeg + zip",
+            with_trailer,
+            Some("image/jpeg"),
+            Label::BadUpload,
+        ),
+        (
+            "png with script chunk",
+            with_script,
+            Some("image/png"),
+            Label::BadUpload,
+        ),
+        (
+            "pdf with javascript",
+            fixtures::pdf("/OpenAction << /S /JavaScript /JS (app.alert(1)) >> "),
+            Some("application/pdf"),
+            Label::BadUpload,
+        ),
+        (
+            "pdf then html",
+            {
+                let mut pdf = fixtures::pdf("");
+                pdf.extend_from_slice(b"<html><body>hi</body></html>");
+                pdf
+            },
+            None,
+            Label::BadUpload,
+        ),
+        (
+            "html as png",
+            b"<!DOCTYPE html><script>x</script>".to_vec(),
+            Some("image/png"),
+            Label::BadUpload,
+        ),
+        (
+            "png labelled jpeg",
+            fixtures::png(),
+            Some("image/jpeg"),
+            Label::BadUpload,
+        ),
+        (
+            "exe",
+            b"MZ\x90\0\x03\0\0\0".to_vec(),
+            Some("image/jpeg"),
+            Label::BadUpload,
+        ),
+    ]
+}
+
+/// What the scanner made of a sample, by the prefix of its findings.
+fn verdict(findings: &[super::Finding]) -> Label {
+    let any = |prefix: &str| findings.iter().any(|f| f.threat_type.starts_with(prefix));
+    if any("Upload") {
+        Label::BadUpload
+    } else if any("XSS") {
+        Label::Xss
+    } else if any("SQL") {
+        Label::Sql
+    } else {
+        Label::Benign
+    }
+}
+
+/// Precision and recall for `label` over (expected, actual) pairs.
+fn score(results: &[(String, Label, Label)], label: Label) -> (f64, f64) {
+    let count = |f: &dyn Fn(&(String, Label, Label)) -> bool| {
+        f64::from(u32::try_from(results.iter().filter(|r| f(r)).count()).unwrap())
+    };
+    let true_positives = count(&|(_, expected, actual)| *expected == label && *actual == label);
+    let flagged = count(&|(_, _, actual)| *actual == label);
+    let labelled = count(&|(_, expected, _)| *expected == label);
+    (
+        true_positives / flagged.max(1.0),
+        true_positives / labelled.max(1.0),
+    )
+}
+
+#[test]
+fn scanner_is_precise_over_the_corpus() {
+    let scanner = Scanner::default();
+    let mut results: Vec<(String, Label, Label)> = TEXT
+        .iter()
+        .map(|(text, label)| {
+            let actual = verdict(&scanner.scan(&Input::Text(text)));
+            ((*text).to_string(), *label, actual)
+        })
+        .collect();
+    results.extend(
+        upload_corpus()
+            .into_iter()
+            .map(|(name, bytes, declared_type, label)| {
+                let actual = verdict(&scanner.scan(&Input::Upload {
+                    bytes: &bytes,
+                    declared_type,
+                }));
+                (name.to_string(), label, actual)
+            }),
+    );
+
+    let wrong: Vec<_> = results.iter().filter(|(_, e, a)| e != a).collect();
+    for label in [Label::Xss, Label::Sql, Label::BadUpload] {
+        let (precision, recall) = score(&results, label);
+        assert!(
+            precision >= 1.0,
+            "{label:?} precision {precision:.2}: {wrong:#?}"
+        );
+        assert!(recall >= 0.95, "{label:?} recall {recall:.2}: {wrong:#?}");
+    }
+    let (benign_precision, _) = score(&results, Label::Benign);
+    assert!(benign_precision >= 0.95, "{wrong:#?}");
+}
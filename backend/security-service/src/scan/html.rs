@@ -0,0 +1,220 @@
+//! Markup that would run script or load other documents when rendered.
+
+use std::cell::RefCell;
+
+use html5ever::tendril::StrTendril;
+use html5ever::tokenizer::{
+    BufferQueue, Tag, TagKind, Token, TokenSink, TokenSinkResult, Tokenizer, TokenizerOpts,
+};
+
+use super::{Detector, Finding, Input, Severity};
+
+const RECOMMENDATION: &str = "Render the sanitized content, or escape the input as text";
+
+/// Elements that execute script or embed another document.
+const ACTIVE_ELEMENTS: &[(&str, Severity)] = &[
+    ("script", Severity::High),
+    ("iframe", Severity::High),
+    ("frame", Severity::High),
+    ("frameset", Severity::High),
+    ("object", Severity::High),
+    ("embed", Severity::High),
+    ("applet", Severity::High),
+    ("base", Severity::Medium),
+    ("meta", Severity::Medium),
+    ("link", Severity::Medium),
+    ("form", Severity::Medium),
+    ("style", Severity::Low),
+];
+
+/// Attributes holding a URL the browser follows or loads.
+const URL_ATTRIBUTES: &[&str] = &[
+    "href",
+    "src",
+    "action",
+    "formaction",
+    "data",
+    "poster",
+    "background",
+    "xlink:href",
+];
+
+/// Removes everything but a safe subset of HTML, keeping the text.
+#[must_use]
+pub fn sanitize(content: &str) -> String {
+    ammonia::Builder::default()
+        .link_rel(Some("noopener noreferrer nofollow"))
+        .clean(content)
+        .to_string()
+}
+
+pub struct HtmlDetector;
+
+impl Detector for HtmlDetector {
+    fn name(&self) -> &'static str {
+        "html"
+    }
+
+    fn inspect(&self, input: &Input<'_>) -> Vec<Finding> {
+        let Input::Text(text) = input else {
+            return Vec::new();
+        };
+        if !text.contains('<') {
+            return Vec::new();
+        }
+        let tokenizer = Tokenizer::new(Sink::default(), TokenizerOpts::default());
+        let queue = BufferQueue::default();
+        queue.push_back(StrTendril::from_slice(text));
+        let _ = tokenizer.feed(&queue);
+        tokenizer.end();
+        tokenizer.sink.findings.into_inner()
+    }
+}
+
+#[derive(Default)]
+struct Sink {
+    findings: RefCell<Vec<Finding>>,
+}
+
+impl TokenSink for Sink {
+    type Handle = ();
+
+    fn process_token(&self, token: Token, _line_number: u64) -> TokenSinkResult<()> {
+        if let Token::TagToken(tag) = token {
+            if tag.kind == TagKind::StartTag {
+                self.inspect(&tag);
+            }
+        }
+        TokenSinkResult::Continue
+    }
+}
+
+impl Sink {
+    fn inspect(&self, tag: &Tag) {
+        let element: &str = &tag.name;
+        if let Some((_, severity)) = ACTIVE_ELEMENTS.iter().find(|(name, _)| *name == element) {
+            self.report(
+                &format!("<{element}> element"),
+                *severity,
+                format!("Markup contains a <{element}> element"),
+                element,
+            );
+        }
+        for attr in &tag.attrs {
+            let name = match &attr.name.prefix {
+                Some(prefix) => format!("{}:{}", &**prefix, &*attr.name.local),
+                None => attr.name.local.to_string(),
+            };
+            let location = format!("<{element} {name}>");
+            if name.starts_with("on") {
+                self.report(
+                    "event handler",
+                    Severity::High,
+                    format!("Markup sets the {name} event handler"),
+                    &location,
+                );
+            } else if name == "srcdoc" {
+                self.report(
+                    "inline document",
+                    Severity::High,
+                    "Markup embeds a document through srcdoc".to_string(),
+                    &location,
+                );
+            } else if URL_ATTRIBUTES.contains(&name.as_str()) {
+                if let Some(scheme) = active_scheme(&attr.value) {
+                    self.report(
+                        "script URL",
+                        Severity::High,
+                        format!("{name} points at a {scheme} URL"),
+                        &location,
+                    );
+                }
+            } else if name == "style" && attr.value.to_ascii_lowercase().contains("expression(") {
+                self.report(
+                    "style expression",
+                    Severity::Medium,
+                    "Inline style evaluates a CSS expression".to_string(),
+                    &location,
+                );
+            }
+        }
+    }
+
+    fn report(&self, what: &str, severity: Severity, description: String, location: &str) {
+        let finding = Finding {
+            threat_type: format!("XSS: {what}"),
+            severity,
+            description,
+            location: Some(location.to_string()),
+            recommendation: RECOMMENDATION,
+        };
+        let mut findings = self.findings.borrow_mut();
+        if !findings.contains(&finding) {
+            findings.push(finding);
+        }
+    }
+}
+
+/// The scheme of a URL that runs script when followed, read the way
+/// browsers do: ignoring case, whitespace and control characters.
+fn active_scheme(url: &str) -> Option<&'static str> {
+    let normalised: String = url
+        .chars()
+        .filter(|c| !c.is_ascii_whitespace() && !c.is_control())
+        .take(32)
+        .collect::<String>()
+        .to_ascii_lowercase();
+    if normalised.starts_with("javascript:") {
+        Some("javascript")
+    } else if normalised.starts_with("vbscript:") {
+        Some("vbscript")
+    } else if ["data:text/html", "data:image/svg", "data:application/xhtml"]
+        .iter()
+        .any(|prefix| normalised.starts_with(prefix))
+    {
+        Some("data")
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(text: &str) -> Vec<String> {
+        HtmlDetector
+            .inspect(&Input::Text(text))
+            .into_iter()
+            .map(|f| f.threat_type)
+            .collect()
+    }
+
+    #[test]
+    fn reads_markup_as_a_browser_would() {
+        assert_eq!(
+            kinds("<ScRiPt >alert(1)</script>"),
+            ["XSS: <script> element"]
+        );
+        assert_eq!(kinds("<svg/onload=alert(1)>"), ["XSS: event handler"]);
+        assert_eq!(
+            kinds("<a href=\"jav&#x09;ascript&colon;alert(1)\">x</a>"),
+            ["XSS: script URL"]
+        );
+        assert!(
+            kinds("Best stay ever <3 -- 5 > 4, system(s) & powershell fans welcome").is_empty()
+        );
+        assert!(
+            kinds("<p>Lovely <b>garden</b></p><a href=\"https://example.org\">map</a>").is_empty()
+        );
+    }
+
+    #[test]
+    fn sanitizing_keeps_the_text() {
+        assert_eq!(
+            sanitize("<p onclick=\"x()\">Hola <script>alert(1)</script>peregrino</p>"),
+            "<p>Hola peregrino</p>"
+        );
+        assert_eq!(sanitize("I <3 it"), "I &lt;3 it");
+    }
+}
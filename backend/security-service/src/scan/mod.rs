@@ -0,0 +1,189 @@
+//! Threat scanning of user-supplied content.
+//!
+//! Detectors look at the structure of what they are given rather than for
+//! keywords: markup is tokenized the way a browser reads it, text is
+//! tokenized as SQL in each context it could end up in, and uploads are
+//! walked segment by segment. A review that mentions `powershell` or
+//! `system(` is just text; an obfuscated `<img src=x oNeRrOr=…>` is still an
+//! event handler.
+//!
+//! Detectors only report findings. Turning them into a risk level is up to
+//! the caller.
+
+mod html;
+mod sql;
+mod upload;
+
+#[cfg(test)]
+mod corpus;
+
+use serde::Serialize;
+
+pub use html::{sanitize, HtmlDetector};
+pub use sql::SqlInjectionDetector;
+pub use upload::{FileType, UploadDetector};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl Severity {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Low => "low",
+            Self::Medium => "medium",
+            Self::High => "high",
+            Self::Critical => "critical",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Input<'a> {
+    /// Free text: reviews, names, notes, query parameters.
+    Text(&'a str),
+    /// An uploaded file and the type the client claimed for it.
+    Upload {
+        bytes: &'a [u8],
+        declared_type: Option<&'a str>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    /// `<category>: <what>`, e.g. `XSS: event handler`.
+    pub threat_type: String,
+    pub severity: Severity,
+    pub description: String,
+    /// Where in the input, e.g. the element or the quoting context.
+    pub location: Option<String>,
+    pub recommendation: &'static str,
+}
+
+/// One kind of structural check. Detectors ignore inputs they do not apply
+/// to.
+pub trait Detector: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn inspect(&self, input: &Input<'_>) -> Vec<Finding>;
+}
+
+pub struct Scanner {
+    detectors: Vec<Box<dyn Detector>>,
+}
+
+impl Default for Scanner {
+    fn default() -> Self {
+        Self::empty()
+            .with(HtmlDetector)
+            .with(SqlInjectionDetector)
+            .with(UploadDetector::default())
+    }
+}
+
+impl Scanner {
+    #[must_use]
+    pub fn empty() -> Self {
+        Self {
+            detectors: Vec::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn with(mut self, detector: impl Detector + 'static) -> Self {
+        self.detectors.push(Box::new(detector));
+        self
+    }
+
+    #[must_use]
+    pub fn detectors(&self) -> Vec<&'static str> {
+        self.detectors.iter().map(|d| d.name()).collect()
+    }
+
+    /// Runs every detector. Percent-encoded text is also checked decoded,
+    /// since that is how it reaches whatever parses it next.
+    #[must_use]
+    pub fn scan(&self, input: &Input<'_>) -> Vec<Finding> {
+        let decoded = match input {
+            Input::Text(text) => percent_decode(text).filter(|d| d != text),
+            Input::Upload { .. } => None,
+        };
+        let mut findings: Vec<Finding> = Vec::new();
+        for input in std::iter::once(*input).chain(decoded.as_deref().map(Input::Text)) {
+            for finding in self.detectors.iter().flat_map(|d| d.inspect(&input)) {
+                if !findings.contains(&finding) {
+                    findings.push(finding);
+                }
+            }
+        }
+        findings
+    }
+}
+
+/// `None` when there is nothing to decode or it does not decode to UTF-8.
+fn percent_decode(text: &str) -> Option<String> {
+    if !text.contains('%') {
+        return None;
+    }
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| text.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        if let Some(byte) = escaped {
+            out.push(byte);
+            i += 3;
+        } else {
+            out.push(if bytes[i] == b'+' { b' ' } else { bytes[i] });
+            i += 1;
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Always;
+
+    impl Detector for Always {
+        fn name(&self) -> &'static str {
+            "always"
+        }
+
+        fn inspect(&self, _input: &Input<'_>) -> Vec<Finding> {
+            vec![Finding {
+                threat_type: "Test: always".to_string(),
+                severity: Severity::Low,
+                description: String::new(),
+                location: None,
+                recommendation: "",
+            }]
+        }
+    }
+
+    #[test]
+    fn detectors_plug_in_and_findings_are_not_repeated() {
+        let scanner = Scanner::empty().with(Always);
+        assert_eq!(scanner.detectors(), ["always"]);
+        assert_eq!(scanner.scan(&Input::Text("100%25 free")).len(), 1);
+        assert!(Scanner::empty().scan(&Input::Text("x")).is_empty());
+    }
+
+    #[test]
+    fn checks_percent_encoded_text_decoded() {
+        let findings = Scanner::default().scan(&Input::Text("%3Cscript%3Ealert(1)%3C/script%3E"));
+        assert!(findings.iter().any(|f| f.threat_type.starts_with("XSS")));
+        assert_eq!(percent_decode("50%"), Some("50%".to_string()));
+        assert_eq!(percent_decode("a+b%20c"), Some("a b c".to_string()));
+    }
+}
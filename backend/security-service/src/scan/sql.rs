@@ -0,0 +1,360 @@
+//! SQL injection, found by tokenizing the input as SQL.
+//!
+//! Input ends up in a query either as a bare (numeric) value or inside a
+//! quoted string. For each of those contexts the input is tokenized as it
+//! would be there, and only what follows the value, i.e. what the input
+//! managed to add to the query, is looked at. Text with apostrophes or SQL
+//! words in it does not continue the query, so it is left alone.
+
+use sqlparser::dialect::MySqlDialect;
+use sqlparser::keywords::Keyword;
+use sqlparser::tokenizer::{Token, Tokenizer, Whitespace};
+
+use super::{Detector, Finding, Input, Severity};
+
+const RECOMMENDATION: &str = "Bind the value as a query parameter";
+
+/// Prefix and suffix placing the input in a query, and how to describe it.
+const CONTEXTS: &[(&str, &str, &str)] = &[
+    ("", "", "as a bare value"),
+    ("'", "", "after an opening single quote"),
+    ("'", "'", "inside single quotes"),
+    ("\"", "", "after an opening double quote"),
+    ("\"", "\"", "inside double quotes"),
+];
+
+/// Functions used for blind injection and file access.
+const DANGEROUS_FUNCTIONS: &[&str] = &[
+    "sleep",
+    "benchmark",
+    "pg_sleep",
+    "load_file",
+    "extractvalue",
+    "updatexml",
+    "randomblob",
+    "xp_cmdshell",
+    "dbms_pipe.receive_message",
+];
+
+pub struct SqlInjectionDetector;
+
+impl Detector for SqlInjectionDetector {
+    fn name(&self) -> &'static str {
+        "sql"
+    }
+
+    fn inspect(&self, input: &Input<'_>) -> Vec<Finding> {
+        let Input::Text(text) = input else {
+            return Vec::new();
+        };
+        let mut findings: Vec<Finding> = Vec::new();
+        for (prefix, suffix, context) in CONTEXTS {
+            let Some(tokens) = tokenize(&format!("{prefix}{text}{suffix}")) else {
+                continue;
+            };
+            let Some(rest) = after_value(&tokens, !prefix.is_empty()) else {
+                continue;
+            };
+            if let Some((what, severity)) = classify(rest, !prefix.is_empty()) {
+                if findings.iter().any(|f| f.threat_type.ends_with(what)) {
+                    continue;
+                }
+                findings.push(Finding {
+                    threat_type: format!("SQL Injection: {what}"),
+                    severity,
+                    description: format!("Input extends a query with {what} when used {context}"),
+                    location: Some((*context).to_string()),
+                    recommendation: RECOMMENDATION,
+                });
+            }
+        }
+        findings
+    }
+}
+
+/// Tokens without whitespace and inline comments, which SQL ignores.
+/// `None` when the text does not tokenize in this context.
+fn tokenize(sql: &str) -> Option<Vec<Token>> {
+    let tokens = Tokenizer::new(&MySqlDialect {}, sql).tokenize().ok()?;
+    Some(
+        tokens
+            .into_iter()
+            .filter(|t| {
+                !matches!(
+                    t,
+                    Token::Whitespace(
+                        Whitespace::Space
+                            | Whitespace::Tab
+                            | Whitespace::Newline
+                            | Whitespace::MultiLineComment(_)
+                    )
+                )
+            })
+            .collect(),
+    )
+}
+
+/// What follows the value the input was meant to be: the closed string in
+/// quoted contexts, a leading number otherwise.
+fn after_value(tokens: &[Token], quoted: bool) -> Option<&[Token]> {
+    let rest = if quoted {
+        match tokens.first()? {
+            Token::SingleQuotedString(_) | Token::DoubleQuotedString(_) => &tokens[1..],
+            _ => return None,
+        }
+    } else {
+        let start = usize::from(matches!(tokens.first()?, Token::Minus));
+        match tokens.get(start)? {
+            Token::Number(..) => &tokens[start + 1..],
+            _ => return None,
+        }
+    };
+    // Closing parentheses get out of an enclosing expression.
+    let skip = rest.iter().take_while(|t| **t == Token::RParen).count();
+    Some(&rest[skip..])
+}
+
+fn classify(rest: &[Token], quoted: bool) -> Option<(&'static str, Severity)> {
+    let first = rest.first()?;
+    if union_select(rest) {
+        return Some(("UNION SELECT", Severity::Critical));
+    }
+    if *first == Token::SemiColon && stacked_statement(&rest[1..]) {
+        return Some(("stacked query", Severity::Critical));
+    }
+    if quoted && rest.len() == 1 && is_line_comment(first) {
+        return Some(("comment truncation", Severity::High));
+    }
+    if is_boolean_operator(first) && boolean_condition(&rest[1..]) {
+        return Some(("boolean condition", Severity::High));
+    }
+    if matches!(
+        keyword(first),
+        Some(Keyword::ORDER | Keyword::GROUP | Keyword::HAVING | Keyword::LIMIT | Keyword::INTO)
+    ) && clause(rest)
+    {
+        return Some(("query clause", Severity::High));
+    }
+    if is_operator(first) && (subquery(&rest[1..]) || dangerous_call(&rest[1..])) {
+        return Some(("subquery", Severity::High));
+    }
+    if (is_boolean_operator(first) || *first == Token::SemiColon) && dangerous_call(rest) {
+        return Some(("time or file function", Severity::High));
+    }
+    None
+}
+
+fn keyword(token: &Token) -> Option<Keyword> {
+    match token {
+        Token::Word(w) if w.quote_style.is_none() && w.keyword != Keyword::NoKeyword => {
+            Some(w.keyword)
+        }
+        _ => None,
+    }
+}
+
+fn word(token: &Token) -> Option<String> {
+    match token {
+        Token::Word(w) if w.quote_style.is_none() => Some(w.value.to_ascii_lowercase()),
+        _ => None,
+    }
+}
+
+fn is_line_comment(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Whitespace(Whitespace::SingleLineComment { .. })
+    )
+}
+
+/// The end of the statement as far as the database is concerned.
+fn ends(rest: &[Token]) -> bool {
+    rest.first()
+        .is_none_or(|t| is_line_comment(t) || *t == Token::SemiColon)
+}
+
+fn starts_select(rest: &[Token]) -> bool {
+    rest.iter().find(|t| **t != Token::LParen).and_then(keyword) == Some(Keyword::SELECT)
+}
+
+fn union_select(rest: &[Token]) -> bool {
+    if rest.first().and_then(keyword) != Some(Keyword::UNION) {
+        return false;
+    }
+    let next = &rest[1..];
+    match next.first().and_then(keyword) {
+        Some(Keyword::ALL | Keyword::DISTINCT) => starts_select(&next[1..]),
+        _ => starts_select(next),
+    }
+}
+
+/// A second statement that reads like SQL rather than a sentence.
+fn stacked_statement(rest: &[Token]) -> bool {
+    let words: Vec<Option<Keyword>> = rest.iter().map(keyword).collect();
+    let has = |k: Keyword| words.iter().skip(1).any(|w| *w == Some(k));
+    match words.first().copied().flatten() {
+        Some(Keyword::SELECT) => has(Keyword::FROM) || dangerous_call(rest),
+        Some(Keyword::DROP | Keyword::ALTER | Keyword::CREATE | Keyword::TRUNCATE) => matches!(
+            words.get(1).copied().flatten(),
+            Some(
+                Keyword::TABLE
+                    | Keyword::DATABASE
+                    | Keyword::SCHEMA
+                    | Keyword::VIEW
+                    | Keyword::INDEX
+            )
+        ),
+        Some(Keyword::DELETE) => has(Keyword::FROM),
+        Some(Keyword::INSERT) => has(Keyword::INTO),
+        Some(Keyword::UPDATE) => has(Keyword::SET),
+        Some(Keyword::EXEC | Keyword::EXECUTE | Keyword::DECLARE) => true,
+        _ => matches!(
+            rest.first().and_then(word).as_deref(),
+            Some("shutdown" | "waitfor")
+        ),
+    }
+}
+
+fn is_boolean_operator(token: &Token) -> bool {
+    matches!(
+        keyword(token),
+        Some(Keyword::OR | Keyword::AND | Keyword::XOR)
+    ) || matches!(token, Token::StringConcat | Token::Ampersand)
+        || word(token).as_deref() == Some("&&")
+}
+
+fn is_operator(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Plus | Token::Minus | Token::Mul | Token::Div | Token::StringConcat | Token::Eq
+    )
+}
+
+fn is_comparison(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Eq
+            | Token::DoubleEq
+            | Token::Neq
+            | Token::Lt
+            | Token::Gt
+            | Token::LtEq
+            | Token::GtEq
+            | Token::Spaceship
+    ) || matches!(
+        keyword(token),
+        Some(Keyword::LIKE | Keyword::IS | Keyword::IN | Keyword::BETWEEN | Keyword::RLIKE)
+    )
+}
+
+fn is_operand(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Number(..)
+            | Token::SingleQuotedString(_)
+            | Token::DoubleQuotedString(_)
+            | Token::HexStringLiteral(_)
+            | Token::Word(_)
+    )
+}
+
+/// `1=1`, `'a'='a`, `x LIKE x`, a lone `1` or `TRUE` that ends the
+/// statement, a subquery or a function call.
+fn boolean_condition(rest: &[Token]) -> bool {
+    let rest = &rest[rest.iter().take_while(|t| **t == Token::LParen).count()..];
+    let Some(operand) = rest.first() else {
+        return false;
+    };
+    if subquery(rest) || dangerous_call(rest) {
+        return true;
+    }
+    if !is_operand(operand) {
+        return false;
+    }
+    if rest.get(1).is_some_and(is_comparison) {
+        return rest
+            .get(2)
+            .is_some_and(|t| is_operand(t) || *t == Token::LParen);
+    }
+    let literal = !matches!(operand, Token::Word(_))
+        || matches!(
+            keyword(operand),
+            Some(Keyword::TRUE | Keyword::FALSE | Keyword::NULL)
+        );
+    literal && ends(&rest[1..])
+}
+
+/// `ORDER BY 3`, `GROUP BY 1`, `LIMIT 1`, `INTO OUTFILE`, `HAVING 1=1`.
+fn clause(rest: &[Token]) -> bool {
+    match keyword(&rest[0]) {
+        Some(Keyword::ORDER | Keyword::GROUP) => {
+            rest.get(1).and_then(keyword) == Some(Keyword::BY)
+                && rest.get(2).is_some_and(is_operand)
+                && ends(&rest[3..])
+        }
+        Some(Keyword::LIMIT) => {
+            matches!(rest.get(1), Some(Token::Number(..)))
+                && (ends(&rest[2..]) || rest.get(2) == Some(&Token::Comma))
+        }
+        Some(Keyword::HAVING) => boolean_condition(&rest[1..]),
+        Some(Keyword::INTO) => {
+            matches!(
+                rest.get(1).and_then(word).as_deref(),
+                Some("outfile" | "dumpfile")
+            )
+        }
+        _ => false,
+    }
+}
+
+fn subquery(rest: &[Token]) -> bool {
+    rest.first() == Some(&Token::LParen) && starts_select(rest)
+}
+
+fn dangerous_call(rest: &[Token]) -> bool {
+    rest.windows(2).any(|w| {
+        w[1] == Token::LParen
+            && word(&w[0]).is_some_and(|name| DANGEROUS_FUNCTIONS.contains(&name.as_str()))
+    }) || rest.windows(2).any(|w| {
+        word(&w[0]).as_deref() == Some("waitfor") && word(&w[1]).as_deref() == Some("delay")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(text: &str) -> Vec<String> {
+        SqlInjectionDetector
+            .inspect(&Input::Text(text))
+            .into_iter()
+            .map(|f| f.threat_type)
+            .collect()
+    }
+
+    #[test]
+    fn finds_what_the_input_adds_to_the_query() {
+        assert_eq!(kinds("' oR '1'='1"), ["SQL Injection: boolean condition"]);
+        assert_eq!(kinds("admin'--"), ["SQL Injection: comment truncation"]);
+        assert_eq!(
+            kinds("1;DROP/**/TABLE pilgrims"),
+            ["SQL Injection: stacked query"]
+        );
+        assert_eq!(
+            kinds("-1' UniOn/**/SeLeCt password FROM users#"),
+            ["SQL Injection: UNION SELECT"]
+        );
+    }
+
+    #[test]
+    fn leaves_prose_alone() {
+        for text in [
+            "It's the pilgrims' union; select your bunk early, don't drop by after 10",
+            "Rooms 5 or 6 are quieter -- ask for them",
+            "I'd say 1=1 perfect",
+            "We slept (well) and had to sleep(ish) in",
+        ] {
+            assert!(kinds(text).is_empty(), "{text}: {:?}", kinds(text));
+        }
+    }
+}
@@ -0,0 +1,430 @@
+//! Uploaded documents: identity document photos and scans.
+//!
+//! The type is taken from the file's magic number, never from its name or
+//! the client's say-so, and the file is walked to its end marker. Anything
+//! after that, or markup inside it, makes it a polyglot: a file that is an
+//! image to us and a page or an archive to something else.
+
+use super::{Detector, Finding, Input, Severity};
+
+/// Large enough for a phone photo of a passport page.
+pub const MAX_UPLOAD_BYTES: usize = 10 * 1024 * 1024;
+
+const RECOMMENDATION: &str = "Reject the upload and ask for a photo or PDF of the document";
+
+/// Case-insensitive byte strings that only belong in a web page or a script.
+const MARKUP_MARKERS: &[&[u8]] = &[
+    b"<script",
+    b"<?php",
+    b"<iframe",
+    b"<!doctype html",
+    b"<html>",
+    b"javascript:",
+];
+
+/// PDF names that run or launch something when the document is opened.
+const ACTIVE_PDF_NAMES: &[&[u8]] = &[
+    b"/JavaScript",
+    b"/JS ",
+    b"/JS(",
+    b"/JS<",
+    b"/Launch",
+    b"/EmbeddedFile",
+    b"/RichMedia",
+    b"/XFA",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileType {
+    Jpeg,
+    Png,
+    Webp,
+    Heic,
+    Pdf,
+}
+
+impl FileType {
+    #[must_use]
+    pub const fn mime(self) -> &'static str {
+        match self {
+            Self::Jpeg => "image/jpeg",
+            Self::Png => "image/png",
+            Self::Webp => "image/webp",
+            Self::Heic => "image/heic",
+            Self::Pdf => "application/pdf",
+        }
+    }
+
+    #[must_use]
+    pub fn sniff(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(Self::Jpeg)
+        } else if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(Self::Png)
+        } else if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WEBP") {
+            Some(Self::Webp)
+        } else if bytes.get(4..8) == Some(b"ftyp")
+            && matches!(
+                bytes.get(8..12),
+                Some(b"heic" | b"heix" | b"hevc" | b"mif1" | b"msf1")
+            )
+        {
+            Some(Self::Heic)
+        } else if bytes.starts_with(b"%PDF-") {
+            Some(Self::Pdf)
+        } else {
+            None
+        }
+    }
+
+    /// Where the file ends according to its own structure, or `None` if it
+    /// is cut short or inconsistent.
+    fn end(self, bytes: &[u8]) -> Option<usize> {
+        match self {
+            Self::Jpeg => jpeg_end(bytes),
+            Self::Png => png_end(bytes),
+            Self::Webp => {
+                let size = u32::from_le_bytes(bytes.get(4..8)?.try_into().ok()?) as usize;
+                let end = size.checked_add(8 + size % 2)?;
+                (end <= bytes.len()).then_some(end)
+            }
+            Self::Heic => bmff_end(bytes),
+            Self::Pdf => {
+                let eof = rfind(bytes, b"%%EOF")? + b"%%EOF".len();
+                Some(eof)
+            }
+        }
+    }
+
+    /// Bytes allowed after the end: padding, and for JPEG the extra images
+    /// of the multi-picture format some phones write.
+    fn allows_trailer(self, trailer: &[u8]) -> bool {
+        match self {
+            Self::Pdf => trailer.iter().all(u8::is_ascii_whitespace),
+            Self::Jpeg if trailer.starts_with(&[0xFF, 0xD8, 0xFF]) => {
+                jpeg_end(trailer).is_some_and(|end| self.allows_trailer(&trailer[end..]))
+            }
+            _ => trailer.iter().all(|b| *b == 0 || *b == 0xFF),
+        }
+    }
+}
+
+pub struct UploadDetector {
+    pub max_bytes: usize,
+}
+
+impl Default for UploadDetector {
+    fn default() -> Self {
+        Self {
+            max_bytes: MAX_UPLOAD_BYTES,
+        }
+    }
+}
+
+impl Detector for UploadDetector {
+    fn name(&self) -> &'static str {
+        "upload"
+    }
+
+    fn inspect(&self, input: &Input<'_>) -> Vec<Finding> {
+        let Input::Upload {
+            bytes,
+            declared_type,
+        } = *input
+        else {
+            return Vec::new();
+        };
+        let mut findings = Vec::new();
+        let mut report = |what: &str, severity, description: String| {
+            findings.push(Finding {
+                threat_type: format!("Upload: {what}"),
+                severity,
+                description,
+                location: None,
+                recommendation: RECOMMENDATION,
+            });
+        };
+
+        if bytes.len() > self.max_bytes {
+            report(
+                "too large",
+                Severity::Medium,
+                format!(
+                    "{} bytes is over the {} byte limit",
+                    bytes.len(),
+                    self.max_bytes
+                ),
+            );
+            return findings;
+        }
+        let Some(file_type) = FileType::sniff(bytes) else {
+            report(
+                "unrecognised type",
+                Severity::High,
+                "Not a JPEG, PNG, WebP, HEIC or PDF file".to_string(),
+            );
+            return findings;
+        };
+        let declared = declared_type
+            .map(|t| t.split(';').next().unwrap_or(t).trim().to_ascii_lowercase())
+            .filter(|t| !t.is_empty() && t != "application/octet-stream");
+        if let Some(declared) = declared {
+            let declared = if declared == "image/jpg" {
+                "image/jpeg".to_string()
+            } else {
+                declared
+            };
+            if declared != file_type.mime() {
+                report(
+                    "type mismatch",
+                    Severity::High,
+                    format!(
+                        "Declared as {declared} but the content is {}",
+                        file_type.mime()
+                    ),
+                );
+            }
+        }
+        match file_type.end(bytes) {
+            None => report(
+                "malformed file",
+                Severity::Medium,
+                format!(
+                    "The {} structure is truncated or inconsistent",
+                    file_type.mime()
+                ),
+            ),
+            Some(end) if !file_type.allows_trailer(&bytes[end..]) => report(
+                "polyglot",
+                Severity::High,
+                format!(
+                    "{} bytes follow the end of the {} data",
+                    bytes.len() - end,
+                    file_type.mime()
+                ),
+            ),
+            Some(_) => {}
+        }
+        if let Some(marker) = MARKUP_MARKERS
+            .iter()
+            .find(|m| find_ignore_case(bytes, m).is_some())
+        {
+            report(
+                "embedded markup",
+                Severity::High,
+                format!("Contains {}", String::from_utf8_lossy(marker)),
+            );
+        }
+        if file_type == FileType::Pdf {
+            if let Some(name) = ACTIVE_PDF_NAMES.iter().find(|n| find(bytes, n).is_some()) {
+                report(
+                    "active PDF content",
+                    Severity::High,
+                    format!(
+                        "The PDF declares {}",
+                        String::from_utf8_lossy(name).trim_end()
+                    ),
+                );
+            }
+        }
+        findings
+    }
+}
+
+/// Walks the marker segments, then the entropy-coded scan data, to EOI.
+fn jpeg_end(bytes: &[u8]) -> Option<usize> {
+    let mut i = 2;
+    loop {
+        if *bytes.get(i)? != 0xFF {
+            return None;
+        }
+        let marker = *bytes.get(i + 1)?;
+        match marker {
+            0xFF => i += 1,
+            0xD9 => return Some(i + 2),
+            0xD0..=0xD7 | 0x01 => i += 2,
+            _ => {
+                let length =
+                    usize::from(u16::from_be_bytes([*bytes.get(i + 2)?, *bytes.get(i + 3)?]));
+                if length < 2 {
+                    return None;
+                }
+                i += 2 + length;
+                if marker == 0xDA {
+                    // Scan data runs until a marker other than a restart or a
+                    // stuffed 0xFF00.
+                    while *bytes.get(i)? != 0xFF
+                        || matches!(bytes.get(i + 1)?, 0x00 | 0xD0..=0xD7 | 0xFF)
+                    {
+                        i += 1;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Walks the chunks, which must start with IHDR, to IEND.
+fn png_end(bytes: &[u8]) -> Option<usize> {
+    let mut i = 8;
+    let mut first = true;
+    loop {
+        let length = u32::from_be_bytes(bytes.get(i..i + 4)?.try_into().ok()?) as usize;
+        let kind = bytes.get(i + 4..i + 8)?;
+        if first && kind != b"IHDR" {
+            return None;
+        }
+        first = false;
+        let end = i.checked_add(12)?.checked_add(length)?;
+        if end > bytes.len() {
+            return None;
+        }
+        if kind == b"IEND" {
+            return Some(end);
+        }
+        i = end;
+    }
+}
+
+/// Walks the top-level ISO base media boxes to the end of the last one.
+fn bmff_end(bytes: &[u8]) -> Option<usize> {
+    let mut i = 0;
+    while i < bytes.len() {
+        let size = u32::from_be_bytes(bytes.get(i..i + 4)?.try_into().ok()?);
+        let kind = bytes.get(i + 4..i + 8)?;
+        if !kind.iter().all(|b| b.is_ascii_graphic() || *b == b' ') {
+            // Not a box: whatever follows is not part of the image.
+            return (i > 0).then_some(i);
+        }
+        let size = match size {
+            0 => bytes.len() - i,
+            1 => usize::try_from(u64::from_be_bytes(
+                bytes.get(i + 8..i + 16)?.try_into().ok()?,
+            ))
+            .ok()?,
+            n => n as usize,
+        };
+        if size < 8 {
+            return None;
+        }
+        i = i.checked_add(size)?;
+    }
+    (i == bytes.len()).then_some(i)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn rfind(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).rposition(|w| w == needle)
+}
+
+fn find_ignore_case(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|w| w.eq_ignore_ascii_case(needle))
+}
+
+#[cfg(test)]
+pub(super) mod fixtures {
+    //! Small but structurally complete files.
+
+    pub fn png() -> Vec<u8> {
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        for (kind, data) in [
+            (&b"IHDR"[..], &[0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0][..]),
+            (b"IDAT", &[0x78, 0x9C, 0x63, 0, 0, 0, 1, 0, 1]),
+            (b"IEND", &[]),
+        ] {
+            png.extend_from_slice(&u32::try_from(data.len()).unwrap().to_be_bytes());
+            png.extend_from_slice(kind);
+            png.extend_from_slice(data);
+            png.extend_from_slice(&[0; 4]);
+        }
+        png
+    }
+
+    pub fn jpeg() -> Vec<u8> {
+        let mut jpeg = vec![0xFF, 0xD8];
+        // APP0 (JFIF), then a scan whose data contains stuffed and restart
+        // markers.
+        jpeg.extend_from_slice(&[0xFF, 0xE0, 0, 16]);
+        jpeg.extend_from_slice(b"JFIF\0\x01\x01\0\0\x01\0\x01\0\0");
+        jpeg.extend_from_slice(&[0xFF, 0xDA, 0, 8, 1, 1, 0, 0, 0x3F, 0]);
+        jpeg.extend_from_slice(&[0x12, 0xFF, 0x00, 0x34, 0xFF, 0xD0, 0x56]);
+        jpeg.extend_from_slice(&[0xFF, 0xD9]);
+        jpeg
+    }
+
+    pub fn webp() -> Vec<u8> {
+        let mut webp = b"RIFF".to_vec();
+        webp.extend_from_slice(&12u32.to_le_bytes());
+        webp.extend_from_slice(b"WEBPVP8L");
+        webp.extend_from_slice(&[0; 4]);
+        webp
+    }
+
+    pub fn heic() -> Vec<u8> {
+        let mut heic = Vec::new();
+        heic.extend_from_slice(&16u32.to_be_bytes());
+        heic.extend_from_slice(b"ftypheic\0\0\0\0");
+        heic.extend_from_slice(&12u32.to_be_bytes());
+        heic.extend_from_slice(b"mdat\x01\x02\x03\x04");
+        heic
+    }
+
+    pub fn pdf(body: &str) -> Vec<u8> {
+        format!(
+            "%PDF-1.7\n1 0 obj\n<< /Type /Catalog {body}>>\nendobj\n\
+             trailer\n<< /Root 1 0 R >>\n%%EOF\n"
+        )
+        .into_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fixtures::*;
+    use super::*;
+
+    fn kinds(bytes: &[u8], declared_type: Option<&str>) -> Vec<String> {
+        UploadDetector::default()
+            .inspect(&Input::Upload {
+                bytes,
+                declared_type,
+            })
+            .into_iter()
+            .map(|f| f.threat_type)
+            .collect()
+    }
+
+    #[test]
+    fn accepts_well_formed_documents() {
+        for (bytes, mime) in [
+            (jpeg(), "image/jpg"),
+            (png(), "image/png"),
+            (webp(), "image/webp"),
+            (heic(), "image/heic"),
+            (pdf(""), "application/pdf; charset=binary"),
+        ] {
+            assert!(
+                kinds(&bytes, Some(mime)).is_empty(),
+                "{mime}: {:?}",
+                kinds(&bytes, Some(mime))
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_by_content_not_by_label() {
+        assert_eq!(kinds(&png(), Some("image/jpeg")), ["Upload: type mismatch"]);
+        assert_eq!(kinds(b"GIF89a", None), ["Upload: unrecognised type"]);
+        assert_eq!(kinds(&jpeg()[..12], None), ["Upload: malformed file"]);
+        let too_large = UploadDetector { max_bytes: 8 }.inspect(&Input::Upload {
+            bytes: &png(),
+            declared_type: None,
+        });
+        assert_eq!(too_large[0].threat_type, "Upload: too large");
+    }
+}
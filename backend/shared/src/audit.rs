@@ -0,0 +1,350 @@
+//! Entries of the tamper-evident `audit_log`.
+//!
+//! Every entry stores the hash of the one before it (`prev_hash`) and its
+//! own (`entry_hash`), SHA-256 over the previous hash and the entry's
+//! fields. Editing, deleting or inserting a row breaks the chain from that
+//! row on, which [`verify_chain`] (or [`ChainReport::check`], a row at a
+//! time) reports. The first chained entry follows
+//! [`GENESIS_HASH`]; rows written before chaining existed have no hashes.
+//!
+//! Hashing and checking come from the `audit-chain` crate, which the
+//! domain model's `albergue_persistence::audit` uses too when it writes
+//! entries for GDPR requests.
+
+use audit_chain::{ChainedRow, EntryFields};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::dto::{SecurityEvent, SecurityEventType};
+
+pub use audit_chain::{
+    canonical_json, timestamp, verify_chain, ChainBreak, ChainReport, GENESIS_HASH,
+};
+
+pub mod actions {
+    pub const READ: &str = "read";
+    pub const CREATE: &str = "create";
+    pub const UPDATE: &str = "update";
+    pub const DELETE: &str = "delete";
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub table_name: String,
+    pub record_id: String,
+    pub action: String,
+    #[serde(default)]
+    pub old_values: Option<Value>,
+    #[serde(default)]
+    pub new_values: Option<Value>,
+    /// `users.id` of whoever acted, if a person did.
+    #[serde(default)]
+    pub user_id: Option<i64>,
+    #[serde(default)]
+    pub ip_address: Option<String>,
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    #[serde(default = "now")]
+    pub created_at: DateTime<Utc>,
+}
+
+fn now() -> DateTime<Utc> {
+    Utc::now()
+}
+
+impl AuditEntry {
+    #[must_use]
+    pub fn new(table_name: &str, record_id: &str, action: &str) -> Self {
+        Self {
+            table_name: table_name.to_string(),
+            record_id: record_id.to_string(),
+            action: action.to_string(),
+            old_values: None,
+            new_values: None,
+            user_id: None,
+            ip_address: None,
+            user_agent: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[must_use]
+    pub fn read(table_name: &str, record_id: &str) -> Self {
+        Self::new(table_name, record_id, actions::READ)
+    }
+
+    /// A change from `old` to `new`, either of which is absent when the
+    /// record was created or deleted. Only the fields that changed are kept.
+    #[must_use]
+    pub fn change(
+        table_name: &str,
+        record_id: &str,
+        old: Option<&Value>,
+        new: Option<&Value>,
+    ) -> Self {
+        let (action, (old_values, new_values)) = match (old, new) {
+            (None, new) => (actions::CREATE, (None, new.cloned())),
+            (old, None) => (actions::DELETE, (old.cloned(), None)),
+            (Some(old), Some(new)) => (actions::UPDATE, diff(old, new)),
+        };
+        Self {
+            old_values,
+            new_values,
+            ..Self::new(table_name, record_id, action)
+        }
+    }
+
+    #[must_use]
+    pub fn by(mut self, user_id: Option<i64>) -> Self {
+        self.user_id = user_id;
+        self
+    }
+
+    #[must_use]
+    pub fn from_client(mut self, ip_address: Option<&str>, user_agent: Option<&str>) -> Self {
+        self.ip_address = ip_address.map(str::to_string);
+        self.user_agent = user_agent.map(str::to_string);
+        self
+    }
+
+    /// The entry a `DocumentAccess` or `DataModification` event stands for.
+    /// Its details name the record (`table_name`, `record_id`) and may carry
+    /// `old_values`, `new_values` and the acting `user_id`.
+    #[must_use]
+    pub fn from_security_event(event: &SecurityEvent) -> Option<Self> {
+        let action = match event.event_type {
+            SecurityEventType::DocumentAccess => actions::READ,
+            SecurityEventType::DataModification => actions::UPDATE,
+            SecurityEventType::Login
+            | SecurityEventType::Logout
+            | SecurityEventType::FailedLogin => return None,
+        };
+        let details = &event.details;
+        let text = |key: &str| details.get(key).and_then(Value::as_str);
+        let record_id = match details.get("record_id")? {
+            Value::String(id) => id.clone(),
+            Value::Number(id) => id.to_string(),
+            _ => return None,
+        };
+        let mut entry = Self::new(text("table_name")?, &record_id, action)
+            .by(details.get("user_id").and_then(Value::as_i64))
+            .from_client(Some(&event.ip_address), text("user_agent"));
+        entry.old_values = details.get("old_values").cloned();
+        entry.new_values = details.get("new_values").cloned();
+        entry.created_at = event.timestamp;
+        Some(entry)
+    }
+
+    /// The hash of this entry when it follows `prev_hash`.
+    #[must_use]
+    pub fn hash(&self, prev_hash: &str) -> String {
+        self.fields().hash(prev_hash)
+    }
+
+    fn fields(&self) -> EntryFields<'_> {
+        EntryFields {
+            table_name: &self.table_name,
+            record_id: &self.record_id,
+            action: &self.action,
+            old_values: self.old_values.as_ref(),
+            new_values: self.new_values.as_ref(),
+            user_id: self.user_id,
+            ip_address: self.ip_address.as_deref(),
+            user_agent: self.user_agent.as_deref(),
+            created_at: self.created_at,
+        }
+    }
+}
+
+/// Top-level fields of two JSON objects that differ, as (old, new). Values
+/// that are not objects are compared whole.
+#[must_use]
+pub fn diff(old: &Value, new: &Value) -> (Option<Value>, Option<Value>) {
+    let (Value::Object(old), Value::Object(new)) = (old, new) else {
+        return if old == new {
+            (None, None)
+        } else {
+            (Some(old.clone()), Some(new.clone()))
+        };
+    };
+    let mut before = serde_json::Map::new();
+    let mut after = serde_json::Map::new();
+    for key in old
+        .keys()
+        .chain(new.keys().filter(|k| !old.contains_key(*k)))
+    {
+        let (was, is) = (old.get(key), new.get(key));
+        if was != is {
+            before.insert(key.clone(), was.cloned().unwrap_or(Value::Null));
+            after.insert(key.clone(), is.cloned().unwrap_or(Value::Null));
+        }
+    }
+    if before.is_empty() {
+        (None, None)
+    } else {
+        (Some(Value::Object(before)), Some(Value::Object(after)))
+    }
+}
+
+/// A row of `audit_log` as stored.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StoredEntry {
+    pub id: i64,
+    #[serde(flatten)]
+    pub entry: AuditEntry,
+    pub prev_hash: Option<String>,
+    pub entry_hash: Option<String>,
+}
+
+impl ChainedRow for StoredEntry {
+    fn id(&self) -> i64 {
+        self.id
+    }
+
+    fn prev_hash(&self) -> Option<&str> {
+        self.prev_hash.as_deref()
+    }
+
+    fn entry_hash(&self) -> Option<&str> {
+        self.entry_hash.as_deref()
+    }
+
+    fn fields(&self) -> EntryFields<'_> {
+        self.entry.fields()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn entry() -> AuditEntry {
+        AuditEntry {
+            created_at: DateTime::parse_from_rfc3339("2026-10-18T09:30:00.123456Z")
+                .unwrap()
+                .with_timezone(&Utc),
+            ..AuditEntry::change(
+                "pilgrims",
+                "42",
+                Some(&json!({ "phone": "a", "language": "es" })),
+                Some(&json!({ "phone": "b", "language": "es" })),
+            )
+            .by(Some(3))
+            .from_client(Some("10.0.0.1"), None)
+        }
+    }
+
+    fn chain(entries: &[AuditEntry]) -> Vec<StoredEntry> {
+        let mut prev = GENESIS_HASH.to_string();
+        (1..)
+            .zip(entries)
+            .map(|(id, entry)| {
+                let hash = entry.hash(&prev);
+                let row = StoredEntry {
+                    id,
+                    entry: entry.clone(),
+                    prev_hash: Some(prev.clone()),
+                    entry_hash: Some(hash.clone()),
+                };
+                prev = hash;
+                row
+            })
+            .collect()
+    }
+
+    #[test]
+    fn keeps_only_changed_fields() {
+        let e = entry();
+        assert_eq!(e.action, actions::UPDATE);
+        assert_eq!(e.old_values, Some(json!({ "phone": "a" })));
+        assert_eq!(e.new_values, Some(json!({ "phone": "b" })));
+        let created = AuditEntry::change("bookings", "7", None, Some(&json!({ "a": 1 })));
+        assert_eq!(created.action, actions::CREATE);
+        assert_eq!(diff(&json!({ "a": 1 }), &json!({ "a": 1 })), (None, None));
+        assert_eq!(
+            diff(&json!({ "a": 1 }), &json!({ "b": 2 })),
+            (
+                Some(json!({ "a": 1, "b": null })),
+                Some(json!({ "a": null, "b": 2 }))
+            )
+        );
+    }
+
+    /// The vectors of the `audit-chain` crate, read as this crate stores
+    /// entries.
+    #[test]
+    fn hashes_the_shared_vectors() {
+        let vectors: Value = serde_json::from_str(include_str!(
+            "../../audit-chain/tests/fixtures/audit_vectors.json"
+        ))
+        .unwrap();
+        for vector in vectors["entries"].as_array().unwrap() {
+            let entry: AuditEntry = serde_json::from_value(vector.clone()).unwrap();
+            let prev_hash = vector["prev_hash"].as_str().unwrap();
+            assert_eq!(
+                entry.hash(prev_hash),
+                vector["entry_hash"].as_str().unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn detects_tampering() {
+        let mut second = entry();
+        second.record_id = "43".to_string();
+        let rows = chain(&[entry(), second, entry()]);
+        let report = verify_chain(rows.clone());
+        assert!(report.is_intact());
+        assert_eq!(report.verified, 3);
+        assert_eq!(report.head, rows[2].entry_hash);
+
+        let mut altered = rows.clone();
+        altered[1].entry.user_id = Some(1);
+        assert_eq!(verify_chain(altered).broken.unwrap().id, 2);
+
+        let mut removed = rows.clone();
+        removed.remove(1);
+        let report = verify_chain(removed);
+        assert_eq!(report.broken.unwrap().id, 3);
+        assert_eq!(report.verified, 1);
+
+        let legacy = StoredEntry {
+            id: 0,
+            entry: entry(),
+            prev_hash: None,
+            entry_hash: None,
+        };
+        let mut with_legacy = vec![legacy.clone()];
+        with_legacy.extend(rows);
+        assert_eq!(verify_chain(with_legacy.clone()).unchained, 1);
+        with_legacy.push(StoredEntry { id: 9, ..legacy });
+        assert_eq!(verify_chain(with_legacy).broken.unwrap().id, 9);
+    }
+
+    #[test]
+    fn maps_security_events() {
+        let event = SecurityEvent {
+            event_type: SecurityEventType::DocumentAccess,
+            user_id: None,
+            ip_address: "10.0.0.1".to_string(),
+            timestamp: Utc::now(),
+            details: json!({ "table_name": "pilgrims", "record_id": 42, "user_id": 3 }),
+        };
+        let entry = AuditEntry::from_security_event(&event).unwrap();
+        assert_eq!(
+            (
+                entry.action.as_str(),
+                entry.record_id.as_str(),
+                entry.user_id
+            ),
+            (actions::READ, "42", Some(3))
+        );
+        let login = SecurityEvent {
+            event_type: SecurityEventType::Login,
+            ..event
+        };
+        assert!(AuditEntry::from_security_event(&login).is_none());
+    }
+}
@@ -131,6 +131,14 @@ pub struct BookingCancelled {
     pub cancelled_at: DateTime<Utc>,
 }
 
+/// Topic: `albergue.v1.booking.checked_out`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BookingCheckedOut {
+    pub booking_id: String,
+    pub pilgrim_id: String,
+    pub checked_out_at: DateTime<Utc>,
+}
+
 /// Topic: `albergue.v1.booking.expired`
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BookingExpired {
@@ -207,6 +215,39 @@ pub struct BedStatusChanged {
     pub reason: Option<String>,
 }
 
+// ============================================================================
+// Review Aggregate Events (albergue.v1.review.*)
+// ============================================================================
+
+/// Topic: `albergue.v1.review.token_issued`
+///
+/// The token itself only goes to the guest, through notification-service.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReviewTokenIssued {
+    pub booking_id: String,
+    pub pilgrim_id: String,
+    /// Hex SHA-256 of the token, to match it up without being able to use it.
+    pub token_sha256: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Topic: `albergue.v1.review.submitted`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReviewSubmitted {
+    pub review_id: String,
+    pub booking_id: String,
+    pub rating: u8,
+    pub risk_level: String,
+}
+
+/// Topic: `albergue.v1.review.moderated`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReviewModerated {
+    pub review_id: String,
+    pub action: String,
+    pub moderated_by: Option<String>,
+}
+
 // ============================================================================
 // Event Type Constants
 // ============================================================================
@@ -222,6 +263,7 @@ pub mod topics {
     pub const BOOKING_BED_ASSIGNED: &str = "albergue.v1.booking.bed_assigned";
     pub const BOOKING_CONFIRMED: &str = "albergue.v1.booking.confirmed";
     pub const BOOKING_CANCELLED: &str = "albergue.v1.booking.cancelled";
+    pub const BOOKING_CHECKED_OUT: &str = "albergue.v1.booking.checked_out";
     pub const BOOKING_EXPIRED: &str = "albergue.v1.booking.expired";
 
     // Payment events
@@ -233,6 +275,11 @@ pub mod topics {
     pub const GOVERNMENT_SUBMISSION_SUCCEEDED: &str = "albergue.v1.government.submission_succeeded";
     pub const GOVERNMENT_SUBMISSION_FAILED: &str = "albergue.v1.government.submission_failed";
 
+    // Review events
+    pub const REVIEW_TOKEN_ISSUED: &str = "albergue.v1.review.token_issued";
+    pub const REVIEW_SUBMITTED: &str = "albergue.v1.review.submitted";
+    pub const REVIEW_MODERATED: &str = "albergue.v1.review.moderated";
+
     // Bed events
     pub const BED_STATUS_CHANGED: &str = "albergue.v1.bed.status_changed";
 }
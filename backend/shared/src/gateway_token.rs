@@ -0,0 +1,365 @@
+//! Verification of the `x-gateway-token` the API gateway signs for every
+//! upstream call. Services must not trust `x-user-claims` on its own: any
+//! caller that reaches them directly could set it.
+//!
+//! Backend components calling each other, like the broker delivering events
+//! to webhooks, sign the same kind of token with [`sign_service_token`] and
+//! the callee checks who sent it with [`verify_service_token`].
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ring::{digest, hmac};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt::Write;
+use thiserror::Error;
+
+pub const GATEWAY_TOKEN_HEADER: &str = "x-gateway-token";
+pub const USER_CLAIMS_HEADER: &str = "x-user-claims";
+
+/// Spin variable holding the secret shared with the gateway.
+pub const INTERNAL_TOKEN_SECRET_VAR: &str = "internal_token_secret";
+
+/// `iss` of the tokens the gateway mints for the requests it forwards.
+pub const GATEWAY_ISSUER: &str = "api-gateway";
+
+/// `iss` of the tokens mqtt-broker-service sends with webhook deliveries.
+pub const BROKER_ISSUER: &str = "mqtt-broker-service";
+
+/// `iss` of the tokens booking-service sends with its audit entries.
+pub const BOOKING_ISSUER: &str = "booking-service";
+
+/// `iss` of the tokens reviews-service sends with review invitations.
+pub const REVIEWS_ISSUER: &str = "reviews-service";
+
+/// `iss` of the tokens auth-service sends to seal and open TOTP secrets.
+pub const AUTH_ISSUER: &str = "auth-service";
+
+/// `iss` of the tokens notification-service sends to look up a recipient.
+pub const NOTIFICATION_ISSUER: &str = "notification-service";
+
+/// `iss` of the tokens operators sign for maintenance calls, such as
+/// security-service's key rotation.
+pub const OPERATOR_ISSUER: &str = "operator";
+
+/// Allowed clock difference between the gateway and the service.
+const LEEWAY_SECONDS: u64 = 30;
+
+/// Lifetime of tokens signed with [`sign_service_token`].
+const SERVICE_TOKEN_TTL_SECONDS: u64 = 60;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum GatewayTokenError {
+    #[error("missing gateway token")]
+    Missing,
+    #[error("malformed gateway token")]
+    Malformed,
+    #[error("invalid gateway token signature")]
+    BadSignature,
+    #[error("gateway token expired")]
+    Expired,
+    #[error("gateway token issued for another service")]
+    WrongAudience,
+    #[error("token issued by an unexpected caller")]
+    UnknownIssuer,
+    #[error("user claims do not match the gateway token")]
+    ClaimsMismatch,
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+pub struct GatewayTokenClaims {
+    pub iss: String,
+    pub aud: String,
+    #[serde(default)]
+    pub sub: Option<String>,
+    pub iat: u64,
+    pub exp: u64,
+    /// The request's correlation id.
+    pub jti: String,
+    /// Hex SHA-256 of the `x-user-claims` value sent with the token.
+    #[serde(default)]
+    pub claims_sha256: Option<String>,
+}
+
+/// Checks the token's HS256 signature, its expiry at `now` (Unix seconds)
+/// and that it was minted for `audience`, the service's gateway name.
+pub fn verify_gateway_token(
+    token: &str,
+    secret: &[u8],
+    audience: &str,
+    now: u64,
+) -> Result<GatewayTokenClaims, GatewayTokenError> {
+    let token = token.trim();
+    let Some((signing_input, signature)) = token.rsplit_once('.') else {
+        return Err(GatewayTokenError::Malformed);
+    };
+    let Some((header, payload)) = signing_input.split_once('.') else {
+        return Err(GatewayTokenError::Malformed);
+    };
+    if payload.contains('.') {
+        return Err(GatewayTokenError::Malformed);
+    }
+
+    let header: serde_json::Value = decode_json(header)?;
+    if header.get("alg").and_then(serde_json::Value::as_str) != Some("HS256") {
+        return Err(GatewayTokenError::Malformed);
+    }
+    let signature = URL_SAFE_NO_PAD
+        .decode(signature)
+        .map_err(|_| GatewayTokenError::Malformed)?;
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret);
+    hmac::verify(&key, signing_input.as_bytes(), &signature)
+        .map_err(|_| GatewayTokenError::BadSignature)?;
+
+    let claims: GatewayTokenClaims = decode_json(payload)?;
+    if claims.aud != audience {
+        return Err(GatewayTokenError::WrongAudience);
+    }
+    if now >= claims.exp + LEEWAY_SECONDS || claims.iat > now + LEEWAY_SECONDS {
+        return Err(GatewayTokenError::Expired);
+    }
+    Ok(claims)
+}
+
+/// Signs a token for a call from `issuer` to the service named `audience`,
+/// issued at `now` (Unix seconds). It carries no user claims.
+#[must_use]
+pub fn sign_service_token(
+    secret: &[u8],
+    issuer: &str,
+    audience: &str,
+    jti: &str,
+    now: u64,
+) -> String {
+    let payload = serde_json::json!({
+        "iss": issuer,
+        "aud": audience,
+        "iat": now,
+        "exp": now + SERVICE_TOKEN_TTL_SECONDS,
+        "jti": jti,
+    });
+    let header = URL_SAFE_NO_PAD.encode(br#"{"alg":"HS256","typ":"JWT"}"#);
+    let payload = URL_SAFE_NO_PAD.encode(payload.to_string());
+    let signing_input = format!("{header}.{payload}");
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret);
+    let signature = URL_SAFE_NO_PAD.encode(hmac::sign(&key, signing_input.as_bytes()));
+    format!("{signing_input}.{signature}")
+}
+
+/// Checks a token another backend component signed for `audience`, issued
+/// by one of `issuers`.
+///
+/// Tokens the gateway minted for public requests are not accepted unless
+/// [`GATEWAY_ISSUER`] is listed.
+pub fn verify_service_token(
+    token: Option<&str>,
+    secret: &[u8],
+    audience: &str,
+    issuers: &[&str],
+    now: u64,
+) -> Result<GatewayTokenClaims, GatewayTokenError> {
+    let token = token.ok_or(GatewayTokenError::Missing)?;
+    let claims = verify_gateway_token(token, secret, audience, now)?;
+    if !issuers.contains(&claims.iss.as_str()) {
+        return Err(GatewayTokenError::UnknownIssuer);
+    }
+    Ok(claims)
+}
+
+/// The caller's claims, once the token proves the gateway sent them.
+/// Requests without claims yield an empty map, but still need a valid token.
+pub fn verified_user_claims(
+    token: Option<&str>,
+    user_claims: Option<&str>,
+    secret: &[u8],
+    audience: &str,
+    now: u64,
+) -> Result<HashMap<String, String>, GatewayTokenError> {
+    let token = token.ok_or(GatewayTokenError::Missing)?;
+    let claims = verify_gateway_token(token, secret, audience, now)?;
+
+    let Some(user_claims) = user_claims else {
+        return match claims.claims_sha256 {
+            Some(_) => Err(GatewayTokenError::ClaimsMismatch),
+            None => Ok(HashMap::new()),
+        };
+    };
+    let hash = sha256_hex(user_claims);
+    if claims.claims_sha256.as_deref() != Some(hash.as_str()) {
+        return Err(GatewayTokenError::ClaimsMismatch);
+    }
+    serde_json::from_str(user_claims).map_err(|_| GatewayTokenError::Malformed)
+}
+
+fn sha256_hex(text: &str) -> String {
+    digest::digest(&digest::SHA256, text.as_bytes())
+        .as_ref()
+        .iter()
+        .fold(String::new(), |mut out, b| {
+            let _ = write!(out, "{b:02x}");
+            out
+        })
+}
+
+fn decode_json<T: serde::de::DeserializeOwned>(part: &str) -> Result<T, GatewayTokenError> {
+    let bytes = URL_SAFE_NO_PAD
+        .decode(part)
+        .map_err(|_| GatewayTokenError::Malformed)?;
+    serde_json::from_slice(&bytes).map_err(|_| GatewayTokenError::Malformed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &[u8] = b"test-secret";
+
+    fn sign(payload: &serde_json::Value, secret: &[u8]) -> String {
+        let header = URL_SAFE_NO_PAD.encode(br#"{"alg":"HS256","typ":"JWT"}"#);
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(payload).unwrap());
+        let input = format!("{header}.{payload}");
+        let key = hmac::Key::new(hmac::HMAC_SHA256, secret);
+        let sig = URL_SAFE_NO_PAD.encode(hmac::sign(&key, input.as_bytes()));
+        format!("{input}.{sig}")
+    }
+
+    fn token_for(user_claims: Option<&str>) -> String {
+        let mut payload = serde_json::json!({
+            "iss": "api-gateway",
+            "aud": "reviews-service",
+            "sub": "user-1",
+            "iat": 1_000,
+            "exp": 1_060,
+            "jti": "corr-1",
+        });
+        if let Some(claims) = user_claims {
+            payload["claims_sha256"] = serde_json::json!(sha256_hex(claims));
+        }
+        sign(&payload, SECRET)
+    }
+
+    #[test]
+    fn accepts_claims_signed_by_the_gateway() {
+        let claims = r#"{"sub":"user-1","roles":"hospitalero"}"#;
+        let token = token_for(Some(claims));
+        let verified =
+            verified_user_claims(Some(&token), Some(claims), SECRET, "reviews-service", 1_010)
+                .unwrap();
+        assert_eq!(
+            verified.get("roles").map(String::as_str),
+            Some("hospitalero")
+        );
+    }
+
+    #[test]
+    fn rejects_claims_the_token_does_not_cover() {
+        let token = token_for(Some(r#"{"sub":"user-1"}"#));
+        let forged = r#"{"sub":"user-1","roles":"admin"}"#;
+        assert_eq!(
+            verified_user_claims(Some(&token), Some(forged), SECRET, "reviews-service", 1_010),
+            Err(GatewayTokenError::ClaimsMismatch)
+        );
+        let anonymous = token_for(None);
+        assert_eq!(
+            verified_user_claims(
+                Some(&anonymous),
+                Some(forged),
+                SECRET,
+                "reviews-service",
+                1_010
+            ),
+            Err(GatewayTokenError::ClaimsMismatch)
+        );
+        assert_eq!(
+            verified_user_claims(None, Some(forged), SECRET, "reviews-service", 1_010),
+            Err(GatewayTokenError::Missing)
+        );
+    }
+
+    #[test]
+    fn rejects_bad_signature_audience_and_expiry() {
+        let token = token_for(None);
+        assert_eq!(
+            verify_gateway_token(&token, b"other", "reviews-service", 1_010),
+            Err(GatewayTokenError::BadSignature)
+        );
+        assert_eq!(
+            verify_gateway_token(&token, SECRET, "booking-service", 1_010),
+            Err(GatewayTokenError::WrongAudience)
+        );
+        assert!(verify_gateway_token(&token, SECRET, "reviews-service", 1_089).is_ok());
+        assert_eq!(
+            verify_gateway_token(&token, SECRET, "reviews-service", 1_090),
+            Err(GatewayTokenError::Expired)
+        );
+        assert_eq!(
+            verify_gateway_token("a.b", SECRET, "reviews-service", 1_010),
+            Err(GatewayTokenError::Malformed)
+        );
+    }
+
+    #[test]
+    fn service_tokens_verify_for_their_audience() {
+        let token = sign_service_token(SECRET, BROKER_ISSUER, "reviews-service", "msg-1", 1_000);
+        let claims = verify_gateway_token(&token, SECRET, "reviews-service", 1_010).unwrap();
+        assert_eq!(claims.iss, BROKER_ISSUER);
+        assert_eq!(claims.sub, None);
+        assert_eq!(
+            verify_gateway_token(&token, SECRET, "booking-service", 1_010),
+            Err(GatewayTokenError::WrongAudience)
+        );
+        assert_eq!(
+            verified_user_claims(Some(&token), None, SECRET, "reviews-service", 1_010),
+            Ok(HashMap::new())
+        );
+    }
+
+    #[test]
+    fn service_tokens_must_come_from_a_known_issuer() {
+        let token = sign_service_token(SECRET, BOOKING_ISSUER, "security-service", "m-1", 1_000);
+        let internal = [BOOKING_ISSUER, OPERATOR_ISSUER];
+        assert_eq!(
+            verify_service_token(Some(&token), SECRET, "security-service", &internal, 1_010)
+                .unwrap()
+                .iss,
+            BOOKING_ISSUER
+        );
+        assert_eq!(
+            verify_service_token(
+                Some(&token),
+                SECRET,
+                "security-service",
+                &[OPERATOR_ISSUER],
+                1_010
+            ),
+            Err(GatewayTokenError::UnknownIssuer)
+        );
+        let public = sign_service_token(SECRET, GATEWAY_ISSUER, "security-service", "m-2", 1_000);
+        assert_eq!(
+            verify_service_token(Some(&public), SECRET, "security-service", &internal, 1_010),
+            Err(GatewayTokenError::UnknownIssuer)
+        );
+        assert_eq!(
+            verify_service_token(None, SECRET, "security-service", &internal, 1_010),
+            Err(GatewayTokenError::Missing)
+        );
+        assert_eq!(
+            verify_service_token(Some(&token), b"", "security-service", &internal, 1_010),
+            Err(GatewayTokenError::BadSignature)
+        );
+    }
+
+    #[test]
+    fn rejects_other_algorithms() {
+        let header = URL_SAFE_NO_PAD.encode(br#"{"alg":"none"}"#);
+        let payload = token_for(None).split('.').nth(1).unwrap().to_string();
+        assert_eq!(
+            verify_gateway_token(
+                &format!("{header}.{payload}."),
+                SECRET,
+                "reviews-service",
+                1_010
+            ),
+            Err(GatewayTokenError::Malformed)
+        );
+    }
+}
@@ -10,12 +10,14 @@
 // Shared types and utilities for WASM microservices
 // Types are now generated from database/ folder schema definitions
 
+pub mod audit;
 pub mod config;
 pub mod db;
 pub mod dto;
 pub mod error;
 pub mod event_publisher;
 pub mod events;
+pub mod gateway_token;
 pub mod webhook_handler;
 
 use serde::{Deserialize, Serialize};
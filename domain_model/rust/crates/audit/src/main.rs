@@ -0,0 +1,65 @@
+#![deny(warnings)]
+#![warn(clippy::all, clippy::pedantic, clippy::nursery)]
+#![allow(clippy::missing_errors_doc)]
+
+//! Checks and searches the hash-chained audit log.
+//!
+//! ```text
+//! albergue-audit verify
+//! albergue-audit query [--user <id>] [--table <name>] [--record <id>]
+//!                      [--from <rfc3339>] [--to <rfc3339>] [--limit <n>]
+//! ```
+//!
+//! `verify` exits non-zero when the chain is broken. Results are printed as
+//! JSON; the database is read from `DATABASE_URL`.
+
+use albergue_persistence::audit::{self, AuditQuery};
+use albergue_persistence::db;
+use chrono::{DateTime, Utc};
+
+const USAGE: &str = "usage: albergue-audit verify | query [--user <id>] [--table <name>] \
+                     [--record <id>] [--from <rfc3339>] [--to <rfc3339>] [--limit <n>]";
+
+fn timestamp(value: &str) -> Result<DateTime<Utc>, Box<dyn std::error::Error>> {
+  Ok(DateTime::parse_from_rfc3339(value)?.with_timezone(&Utc))
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+  tracing_subscriber::fmt()
+    .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+    .init();
+
+  let mut args = std::env::args().skip(1);
+  let command = args.next().ok_or(USAGE)?;
+  let mut filter = AuditQuery::default();
+  while let Some(flag) = args.next() {
+    let value = args.next().ok_or(USAGE)?;
+    match flag.as_str() {
+      "--user" => filter.user_id = Some(value.parse()?),
+      "--table" => filter.table_name = Some(value),
+      "--record" => filter.record_id = Some(value),
+      "--from" => filter.from = Some(timestamp(&value)?),
+      "--to" => filter.to = Some(timestamp(&value)?),
+      "--limit" => filter.limit = Some(value.parse()?),
+      _ => return Err(USAGE.into()),
+    }
+  }
+
+  let conn = db::connect_auto().await?;
+  match command.as_str() {
+    "verify" => {
+      let report = audit::verify(&conn).await?;
+      println!("{}", serde_json::to_string_pretty(&report)?);
+      if !report.is_intact() {
+        std::process::exit(1);
+      }
+    }
+    "query" => {
+      let rows = audit::query(&conn, &filter).await?;
+      println!("{}", serde_json::to_string_pretty(&rows)?);
+    }
+    _ => return Err(USAGE.into()),
+  }
+  Ok(())
+}
@@ -14,6 +14,11 @@ mod m20260111_000007_government_submissions;
 mod m20260111_000008_notifications;
 mod m20260111_000009_audit_log;
 mod m20260111_000010_seed_synthetic_data;
+mod m20261018_000011_user_identities;
+mod m20261018_000012_auth_sessions;
+mod m20261018_000013_staff_accounts;
+mod m20261018_000014_pilgrim_document_index;
+mod m20261018_000015_audit_chain;
 pub struct Migrator;
 
 #[async_trait::async_trait]
@@ -30,6 +35,11 @@ impl MigratorTrait for Migrator {
             Box::new(m20260111_000008_notifications::Migration),
             Box::new(m20260111_000009_audit_log::Migration),
             Box::new(m20260111_000010_seed_synthetic_data::Migration),
+            Box::new(m20261018_000011_user_identities::Migration),
+            Box::new(m20261018_000012_auth_sessions::Migration),
+            Box::new(m20261018_000013_staff_accounts::Migration),
+            Box::new(m20261018_000014_pilgrim_document_index::Migration),
+            Box::new(m20261018_000015_audit_chain::Migration),
         ]
     }
 }
@@ -0,0 +1,109 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    // Space-separated roles (pilgrim, hospitalero, admin) the auth-service
+    // puts in the tokens it issues.
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Users::Table)
+          .add_column(
+            ColumnDef::new(Users::Roles)
+              .string()
+              .not_null()
+              .default("pilgrim"),
+          )
+          .to_owned(),
+      )
+      .await?;
+
+    // Accounts at an identity provider (Logto, Zitadel, GitHub...) that sign
+    // in as a local user.
+    manager
+      .create_table(
+        Table::create()
+          .table(UserIdentities::Table)
+          .if_not_exists()
+          .col(
+            ColumnDef::new(UserIdentities::Id)
+              .integer()
+              .not_null()
+              .primary_key()
+              .auto_increment(),
+          )
+          .col(ColumnDef::new(UserIdentities::UserId).integer().not_null())
+          .col(ColumnDef::new(UserIdentities::Provider).string().not_null())
+          .col(ColumnDef::new(UserIdentities::Subject).string().not_null())
+          .col(ColumnDef::new(UserIdentities::Email).string().null())
+          .col(ColumnDef::new(UserIdentities::CreatedAt).timestamp().null())
+          .col(ColumnDef::new(UserIdentities::LastLoginAt).timestamp().null())
+          .foreign_key(
+            ForeignKey::create()
+              .name("fk_user_identities_user")
+              .from(UserIdentities::Table, UserIdentities::UserId)
+              .to(Users::Table, Users::Id)
+              .on_delete(ForeignKeyAction::Cascade),
+          )
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_user_identities_provider_subject")
+          .table(UserIdentities::Table)
+          .col(UserIdentities::Provider)
+          .col(UserIdentities::Subject)
+          .unique()
+          .to_owned(),
+      )
+      .await?;
+
+    Ok(())
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(
+        Table::drop()
+          .table(UserIdentities::Table)
+          .if_exists()
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Users::Table)
+          .drop_column(Users::Roles)
+          .to_owned(),
+      )
+      .await
+  }
+}
+
+#[derive(DeriveIden)]
+enum Users {
+  Table,
+  Id,
+  Roles,
+}
+
+#[derive(DeriveIden)]
+enum UserIdentities {
+  Table,
+  Id,
+  UserId,
+  Provider,
+  Subject,
+  Email,
+  CreatedAt,
+  LastLoginAt,
+}
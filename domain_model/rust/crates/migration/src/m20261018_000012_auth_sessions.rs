@@ -0,0 +1,142 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    // One row per sign-in: the family every refresh token rotated from that
+    // sign-in belongs to, and the `sid` claim of its access tokens.
+    manager
+      .create_table(
+        Table::create()
+          .table(AuthSessions::Table)
+          .if_not_exists()
+          .col(
+            ColumnDef::new(AuthSessions::Id)
+              .string()
+              .not_null()
+              .primary_key(),
+          )
+          .col(ColumnDef::new(AuthSessions::UserId).integer().not_null())
+          .col(ColumnDef::new(AuthSessions::Provider).string().not_null())
+          .col(ColumnDef::new(AuthSessions::Subject).string().not_null())
+          .col(ColumnDef::new(AuthSessions::IdToken).text().null())
+          .col(ColumnDef::new(AuthSessions::UserAgent).string().null())
+          .col(ColumnDef::new(AuthSessions::CreatedAt).timestamp().not_null())
+          .col(ColumnDef::new(AuthSessions::LastUsedAt).timestamp().null())
+          .col(ColumnDef::new(AuthSessions::ExpiresAt).timestamp().not_null())
+          .col(ColumnDef::new(AuthSessions::RevokedAt).timestamp().null())
+          .foreign_key(
+            ForeignKey::create()
+              .name("fk_auth_sessions_user")
+              .from(AuthSessions::Table, AuthSessions::UserId)
+              .to(Users::Table, Users::Id)
+              .on_delete(ForeignKeyAction::Cascade),
+          )
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_auth_sessions_user")
+          .table(AuthSessions::Table)
+          .col(AuthSessions::UserId)
+          .to_owned(),
+      )
+      .await?;
+
+    // Refresh tokens are only kept as SHA-256 hashes. A token that has been
+    // rotated keeps its row, with `used_at` set, so presenting it again is
+    // recognised as reuse.
+    manager
+      .create_table(
+        Table::create()
+          .table(RefreshTokens::Table)
+          .if_not_exists()
+          .col(
+            ColumnDef::new(RefreshTokens::TokenHash)
+              .string()
+              .not_null()
+              .primary_key(),
+          )
+          .col(ColumnDef::new(RefreshTokens::SessionId).string().not_null())
+          .col(ColumnDef::new(RefreshTokens::CreatedAt).timestamp().not_null())
+          .col(ColumnDef::new(RefreshTokens::UsedAt).timestamp().null())
+          .foreign_key(
+            ForeignKey::create()
+              .name("fk_refresh_tokens_session")
+              .from(RefreshTokens::Table, RefreshTokens::SessionId)
+              .to(AuthSessions::Table, AuthSessions::Id)
+              .on_delete(ForeignKeyAction::Cascade),
+          )
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_refresh_tokens_session")
+          .table(RefreshTokens::Table)
+          .col(RefreshTokens::SessionId)
+          .to_owned(),
+      )
+      .await?;
+
+    Ok(())
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(
+        Table::drop()
+          .table(RefreshTokens::Table)
+          .if_exists()
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .drop_table(
+        Table::drop()
+          .table(AuthSessions::Table)
+          .if_exists()
+          .to_owned(),
+      )
+      .await
+  }
+}
+
+#[derive(DeriveIden)]
+enum Users {
+  Table,
+  Id,
+}
+
+#[derive(DeriveIden)]
+enum AuthSessions {
+  Table,
+  Id,
+  UserId,
+  Provider,
+  Subject,
+  IdToken,
+  UserAgent,
+  CreatedAt,
+  LastUsedAt,
+  ExpiresAt,
+  RevokedAt,
+}
+
+#[derive(DeriveIden)]
+enum RefreshTokens {
+  Table,
+  TokenHash,
+  SessionId,
+  CreatedAt,
+  UsedAt,
+}
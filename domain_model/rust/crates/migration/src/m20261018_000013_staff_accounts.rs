@@ -0,0 +1,125 @@
+use argon2::{
+  password_hash::{rand_core::OsRng, PasswordHasher, SaltString},
+  Argon2,
+};
+use sea_orm_migration::{prelude::*, sea_orm::ConnectionTrait};
+
+/// Password of users who cannot sign in with one (provider-only accounts).
+const NO_PASSWORD: &str = "!";
+/// Seeded by `m20260111_000010` with a password published in this repo.
+const SEED_USERNAME: &str = "synthetic_admin";
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    // SQLite alters one column per statement.
+    let columns = [
+      // TOTP secret, sealed by security-service; set on enrolment, in use
+      // once confirmed.
+      ColumnDef::new(Users::TotpSecret).string().null().to_owned(),
+      ColumnDef::new(Users::TotpEnabledAt).timestamp().null().to_owned(),
+      // Last accepted TOTP time step, so a code cannot be replayed.
+      ColumnDef::new(Users::TotpLastStep).big_integer().null().to_owned(),
+      ColumnDef::new(Users::FailedLoginAttempts)
+        .integer()
+        .not_null()
+        .default(0)
+        .to_owned(),
+      ColumnDef::new(Users::LockedUntil).timestamp().null().to_owned(),
+      // Volunteer accounts only work during their shift rota.
+      ColumnDef::new(Users::ActiveFrom).timestamp().null().to_owned(),
+      ColumnDef::new(Users::ExpiresAt).timestamp().null().to_owned(),
+    ];
+    for mut column in columns {
+      manager
+        .alter_table(
+          Table::alter()
+            .table(Users::Table)
+            .add_column(&mut column)
+            .to_owned(),
+        )
+        .await?;
+    }
+
+    hash_stored_passwords(manager).await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    // Hashed passwords stay hashed: there is no going back to plaintext.
+    for column in [
+      Users::TotpSecret,
+      Users::TotpEnabledAt,
+      Users::TotpLastStep,
+      Users::FailedLoginAttempts,
+      Users::LockedUntil,
+      Users::ActiveFrom,
+      Users::ExpiresAt,
+    ] {
+      manager
+        .alter_table(
+          Table::alter()
+            .table(Users::Table)
+            .drop_column(column)
+            .to_owned(),
+        )
+        .await?;
+    }
+    Ok(())
+  }
+}
+
+/// Replaces plaintext passwords with Argon2id hashes. The seeded account's
+/// password is public, so it is disabled instead of hashed.
+async fn hash_stored_passwords(manager: &SchemaManager<'_>) -> Result<(), DbErr> {
+  let db = manager.get_connection();
+  let select = Query::select()
+    .columns([Users::Id, Users::Username, Users::Password])
+    .from(Users::Table)
+    .to_owned();
+
+  for row in db.query_all(&select).await? {
+    let id: i32 = row.try_get("", "id")?;
+    let username: String = row.try_get("", "username")?;
+    let password: String = row.try_get("", "password")?;
+    if password == NO_PASSWORD || password.starts_with("$argon2") {
+      continue;
+    }
+
+    let replacement = if username == SEED_USERNAME {
+      NO_PASSWORD.to_string()
+    } else {
+      let salt = SaltString::generate(&mut OsRng);
+      Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| DbErr::Migration(format!("hashing password of user {id}: {e}")))?
+        .to_string()
+    };
+
+    let update = Query::update()
+      .table(Users::Table)
+      .value(Users::Password, replacement)
+      .and_where(Expr::col(Users::Id).eq(id))
+      .to_owned();
+    manager.exec_stmt(update).await?;
+  }
+
+  Ok(())
+}
+
+#[derive(DeriveIden)]
+enum Users {
+  Table,
+  Id,
+  Username,
+  Password,
+  TotpSecret,
+  TotpEnabledAt,
+  TotpLastStep,
+  FailedLoginAttempts,
+  LockedUntil,
+  ActiveFrom,
+  ExpiresAt,
+}
@@ -0,0 +1,65 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    // Keyed HMAC of the normalised document number, written by
+    // security-service next to the encrypted value so returning pilgrims can
+    // be found without decrypting every row.
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Pilgrims::Table)
+          .add_column(
+            ColumnDef::new(Pilgrims::DocumentNumberBlindIndex)
+              .string()
+              .null(),
+          )
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_pilgrims_document_number_blind_index")
+          .table(Pilgrims::Table)
+          .col(Pilgrims::DocumentNumberBlindIndex)
+          .to_owned(),
+      )
+      .await?;
+
+    Ok(())
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_index(
+        Index::drop()
+          .name("idx_pilgrims_document_number_blind_index")
+          .table(Pilgrims::Table)
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Pilgrims::Table)
+          .drop_column(Pilgrims::DocumentNumberBlindIndex)
+          .to_owned(),
+      )
+      .await?;
+
+    Ok(())
+  }
+}
+
+#[derive(DeriveIden)]
+enum Pilgrims {
+  Table,
+  DocumentNumberBlindIndex,
+}
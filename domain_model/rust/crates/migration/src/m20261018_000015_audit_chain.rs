@@ -0,0 +1,93 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    // Each entry carries the hash of the one before it and its own, so
+    // altered, removed or inserted rows show up when the chain is checked.
+    // Rows from before this migration stay unchained.
+    for column in [AuditLog::PrevHash, AuditLog::EntryHash] {
+      manager
+        .alter_table(
+          Table::alter()
+            .table(AuditLog::Table)
+            .add_column(ColumnDef::new(column).string().null())
+            .to_owned(),
+        )
+        .await?;
+    }
+
+    // Two writers appending after the same entry would fork the chain; the
+    // second insert fails and is retried on the new head.
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_audit_log_prev_hash")
+          .table(AuditLog::Table)
+          .col(AuditLog::PrevHash)
+          .unique()
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_audit_log_user_created")
+          .table(AuditLog::Table)
+          .col(AuditLog::UserId)
+          .col(AuditLog::CreatedAt)
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_audit_log_created_at")
+          .table(AuditLog::Table)
+          .col(AuditLog::CreatedAt)
+          .to_owned(),
+      )
+      .await?;
+
+    Ok(())
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    for index in [
+      "idx_audit_log_created_at",
+      "idx_audit_log_user_created",
+      "idx_audit_log_prev_hash",
+    ] {
+      manager
+        .drop_index(Index::drop().name(index).table(AuditLog::Table).to_owned())
+        .await?;
+    }
+
+    for column in [AuditLog::EntryHash, AuditLog::PrevHash] {
+      manager
+        .alter_table(
+          Table::alter()
+            .table(AuditLog::Table)
+            .drop_column(column)
+            .to_owned(),
+        )
+        .await?;
+    }
+
+    Ok(())
+  }
+}
+
+#[derive(DeriveIden)]
+enum AuditLog {
+  Table,
+  UserId,
+  CreatedAt,
+  PrevHash,
+  EntryHash,
+}
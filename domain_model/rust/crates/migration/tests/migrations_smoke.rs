@@ -1,6 +1,7 @@
 ﻿use albergue_migration::Migrator;
+use argon2::{Argon2, PasswordHash, PasswordVerifier};
 use sea_orm_migration::prelude::*;
-use sea_orm_migration::sea_orm::Database;
+use sea_orm_migration::sea_orm::{ConnectionTrait, Database};
 
 #[tokio::test]
 async fn migrations_apply_and_report_status() -> Result<(), DbErr> {
@@ -9,14 +10,48 @@ async fn migrations_apply_and_report_status() -> Result<(), DbErr> {
   Migrator::refresh(&db).await?;
   let applied = Migrator::get_applied_migrations(&db).await?;
   let pending = Migrator::get_pending_migrations(&db).await?;
-  assert_eq!(applied.len(), 10);
+  assert_eq!(applied.len(), 15);
   assert_eq!(pending.len(), 0);
 
   Migrator::reset(&db).await?;
   let applied = Migrator::get_applied_migrations(&db).await?;
   let pending = Migrator::get_pending_migrations(&db).await?;
   assert_eq!(applied.len(), 0);
-  assert_eq!(pending.len(), 10);
+  assert_eq!(pending.len(), 15);
+
+  Ok(())
+}
+
+/// The seeded admin's password is published in this repo, so the staff
+/// accounts migration must leave it matching no password at all.
+#[tokio::test]
+async fn seeded_admin_cannot_sign_in() -> Result<(), DbErr> {
+  let db = Database::connect("sqlite::memory:").await?;
+  Migrator::up(&db, None).await?;
+
+  let select = Query::select()
+    .column(Alias::new("password"))
+    .from(Alias::new("users"))
+    .and_where(Expr::col(Alias::new("username")).eq("synthetic_admin"))
+    .to_owned();
+  let row = db
+    .query_one(&select)
+    .await?
+    .expect("synthetic_admin is seeded");
+  let stored: String = row.try_get("", "password")?;
+  assert_eq!(stored, "!");
+
+  // The check auth-service signs staff in with.
+  let signs_in = |password: &str| {
+    PasswordHash::new(&stored).is_ok_and(|parsed| {
+      Argon2::default()
+        .verify_password(password.as_bytes(), &parsed)
+        .is_ok()
+    })
+  };
+  for password in ["synthetic_password", "!", "", "correct horse battery"] {
+    assert!(!signs_in(password), "{password:?} signed in");
+  }
 
   Ok(())
 }
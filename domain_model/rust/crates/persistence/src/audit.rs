@@ -0,0 +1,319 @@
+//! The hash-chained `audit_log`.
+//!
+//! Each row stores the previous row's hash (`prev_hash`) and its own
+//! (`entry_hash`): SHA-256 over the previous hash and the entry's fields.
+//! Altering, removing or inserting a row breaks the chain from there on,
+//! which [`verify`] finds. Hashing and checking come from the backend's
+//! `audit-chain` crate, shared with `shared::audit`, whose services append
+//! to the same chain through security-service.
+
+use audit_chain::{ChainedRow, EntryFields};
+use chrono::{DateTime, Utc};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter, QueryOrder,
+    QuerySelect, Set, TransactionSession, TransactionTrait,
+};
+use serde_json::Value as Json;
+
+use crate::entities::audit_log;
+
+pub use audit_chain::{canonical_json, ChainBreak, ChainReport, GENESIS_HASH};
+
+/// Concurrent writers can read the same head; the unique `prev_hash` index
+/// rejects all but one and the others retry on the new head.
+const MAX_ATTEMPTS: u32 = 5;
+const VERIFY_PAGE: u64 = 500;
+pub const DEFAULT_QUERY_LIMIT: u64 = 100;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditEntry {
+    pub table_name: String,
+    pub record_id: String,
+    pub action: String,
+    pub old_values: Option<Json>,
+    pub new_values: Option<Json>,
+    pub user_id: Option<i32>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl AuditEntry {
+    #[must_use]
+    pub fn new(table_name: &str, record_id: &str, action: &str, now: DateTime<Utc>) -> Self {
+        Self {
+            table_name: table_name.to_string(),
+            record_id: record_id.to_string(),
+            action: action.to_string(),
+            old_values: None,
+            new_values: None,
+            user_id: None,
+            ip_address: None,
+            user_agent: None,
+            created_at: now,
+        }
+    }
+
+    #[must_use]
+    pub fn values(mut self, old_values: Option<Json>, new_values: Option<Json>) -> Self {
+        self.old_values = old_values;
+        self.new_values = new_values;
+        self
+    }
+
+    #[must_use]
+    pub fn by(mut self, user_id: Option<i32>) -> Self {
+        self.user_id = user_id;
+        self
+    }
+
+    /// The hash of this entry when it follows `prev_hash`.
+    #[must_use]
+    pub fn hash(&self, prev_hash: &str) -> String {
+        EntryFields {
+            table_name: &self.table_name,
+            record_id: &self.record_id,
+            action: &self.action,
+            old_values: self.old_values.as_ref(),
+            new_values: self.new_values.as_ref(),
+            user_id: self.user_id.map(i64::from),
+            ip_address: self.ip_address.as_deref(),
+            user_agent: self.user_agent.as_deref(),
+            created_at: self.created_at,
+        }
+        .hash(prev_hash)
+    }
+}
+
+impl ChainedRow for audit_log::Model {
+    fn id(&self) -> i64 {
+        i64::from(self.id)
+    }
+
+    fn prev_hash(&self) -> Option<&str> {
+        self.prev_hash.as_deref()
+    }
+
+    fn entry_hash(&self) -> Option<&str> {
+        self.entry_hash.as_deref()
+    }
+
+    fn fields(&self) -> EntryFields<'_> {
+        EntryFields {
+            table_name: &self.table_name,
+            record_id: &self.record_id,
+            action: &self.action,
+            old_values: self.old_values.as_ref(),
+            new_values: self.new_values.as_ref(),
+            user_id: self.user_id.map(i64::from),
+            ip_address: self.ip_address.as_deref(),
+            user_agent: self.user_agent.as_deref(),
+            created_at: self.created_at.unwrap_or_default(),
+        }
+    }
+}
+
+/// Appends `entry` to the chain. Each attempt runs in its own (nested)
+/// transaction so a lost race does not abort the caller's.
+pub async fn record<C>(db: &C, entry: &AuditEntry) -> Result<audit_log::Model, DbErr>
+where
+    C: ConnectionTrait + TransactionTrait,
+{
+    let mut last_error = None;
+    for _ in 0..MAX_ATTEMPTS {
+        let txn = db.begin().await?;
+        let head: Option<Option<String>> = audit_log::Entity::find()
+            .select_only()
+            .column(audit_log::Column::EntryHash)
+            .filter(audit_log::Column::EntryHash.is_not_null())
+            .order_by_desc(audit_log::Column::Id)
+            .into_tuple()
+            .one(&txn)
+            .await?;
+        let prev_hash = head.flatten().unwrap_or_else(|| GENESIS_HASH.to_string());
+        let entry_hash = entry.hash(&prev_hash);
+        let inserted = audit_log::ActiveModel {
+            table_name: Set(entry.table_name.clone()),
+            record_id: Set(entry.record_id.clone()),
+            action: Set(entry.action.clone()),
+            old_values: Set(entry.old_values.clone()),
+            new_values: Set(entry.new_values.clone()),
+            user_id: Set(entry.user_id),
+            ip_address: Set(entry.ip_address.clone()),
+            user_agent: Set(entry.user_agent.clone()),
+            created_at: Set(Some(entry.created_at)),
+            prev_hash: Set(Some(prev_hash)),
+            entry_hash: Set(Some(entry_hash)),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await;
+        match inserted {
+            Ok(row) => {
+                txn.commit().await?;
+                return Ok(row);
+            }
+            Err(e) => {
+                txn.rollback().await?;
+                last_error = Some(e);
+            }
+        }
+    }
+    Err(last_error.unwrap_or_else(|| DbErr::Custom("audit log append failed".to_string())))
+}
+
+/// Walks the whole chain, a page at a time, stopping at the first break.
+pub async fn verify(db: &impl ConnectionTrait) -> Result<ChainReport, DbErr> {
+    let mut report = ChainReport::default();
+    let mut after = 0;
+    loop {
+        let page = audit_log::Entity::find()
+            .filter(audit_log::Column::Id.gt(after))
+            .order_by_asc(audit_log::Column::Id)
+            .limit(VERIFY_PAGE)
+            .all(db)
+            .await?;
+        let Some(last) = page.last() else {
+            return Ok(report);
+        };
+        after = last.id;
+        if !page.iter().all(|row| report.check(row)) {
+            return Ok(report);
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct AuditQuery {
+    pub user_id: Option<i32>,
+    pub table_name: Option<String>,
+    pub record_id: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub limit: Option<u64>,
+}
+
+/// Entries matching every filter given, newest first.
+pub async fn query(db: &impl ConnectionTrait, filter: &AuditQuery) -> Result<Vec<Json>, DbErr> {
+    let mut select = audit_log::Entity::find();
+    if let Some(user_id) = filter.user_id {
+        select = select.filter(audit_log::Column::UserId.eq(user_id));
+    }
+    if let Some(table) = &filter.table_name {
+        select = select.filter(audit_log::Column::TableName.eq(table.as_str()));
+    }
+    if let Some(record_id) = &filter.record_id {
+        select = select.filter(audit_log::Column::RecordId.eq(record_id.as_str()));
+    }
+    if let Some(from) = filter.from {
+        select = select.filter(audit_log::Column::CreatedAt.gte(from));
+    }
+    if let Some(to) = filter.to {
+        select = select.filter(audit_log::Column::CreatedAt.lt(to));
+    }
+    select
+        .order_by_desc(audit_log::Column::Id)
+        .limit(filter.limit.unwrap_or(DEFAULT_QUERY_LIMIT))
+        .into_json()
+        .all(db)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn entry() -> AuditEntry {
+        AuditEntry::new(
+            "pilgrims",
+            "42",
+            "update",
+            DateTime::parse_from_rfc3339("2026-10-18T09:30:00.123456Z")
+                .unwrap()
+                .with_timezone(&Utc),
+        )
+        .values(Some(json!({ "phone": "a" })), Some(json!({ "phone": "b" })))
+        .by(Some(3))
+    }
+
+    fn row(id: i32, entry: &AuditEntry, prev_hash: &str) -> audit_log::Model {
+        audit_log::Model {
+            id,
+            table_name: entry.table_name.clone(),
+            record_id: entry.record_id.clone(),
+            action: entry.action.clone(),
+            old_values: entry.old_values.clone(),
+            new_values: entry.new_values.clone(),
+            user_id: entry.user_id,
+            ip_address: entry.ip_address.clone(),
+            user_agent: entry.user_agent.clone(),
+            created_at: Some(entry.created_at),
+            prev_hash: Some(prev_hash.to_string()),
+            entry_hash: Some(entry.hash(prev_hash)),
+        }
+    }
+
+    /// The vectors of the backend's `audit-chain` crate, read as entries
+    /// are stored here.
+    #[test]
+    fn hashes_the_shared_vectors() {
+        let vectors: Json = serde_json::from_str(include_str!(
+            "../../../../../backend/audit-chain/tests/fixtures/audit_vectors.json"
+        ))
+        .unwrap();
+        for vector in vectors["entries"].as_array().unwrap() {
+            let text = |key: &str| vector[key].as_str().map(str::to_string);
+            let values = |key: &str| Some(vector[key].clone()).filter(|v| !v.is_null());
+            let created_at = DateTime::parse_from_rfc3339(&text("created_at").unwrap()).unwrap();
+            let mut entry = AuditEntry::new(
+                &text("table_name").unwrap(),
+                &text("record_id").unwrap(),
+                &text("action").unwrap(),
+                created_at.with_timezone(&Utc),
+            )
+            .values(values("old_values"), values("new_values"))
+            .by(vector["user_id"]
+                .as_i64()
+                .map(|id| i32::try_from(id).unwrap()));
+            entry.ip_address = text("ip_address");
+            entry.user_agent = text("user_agent");
+            let prev_hash = text("prev_hash").unwrap();
+            assert_eq!(Some(entry.hash(&prev_hash)), text("entry_hash"));
+        }
+    }
+
+    #[test]
+    fn finds_the_first_broken_link() {
+        let first = row(1, &entry(), GENESIS_HASH);
+        let second = row(2, &entry().by(None), first.entry_hash.as_deref().unwrap());
+        let third = row(3, &entry(), second.entry_hash.as_deref().unwrap());
+
+        let mut report = ChainReport::default();
+        assert!([&first, &second, &third].iter().all(|r| report.check(r)));
+        assert_eq!(
+            (report.verified, report.head.clone()),
+            (3, third.entry_hash.clone())
+        );
+
+        let mut altered = second.clone();
+        altered.action = "read".to_string();
+        let mut report = ChainReport::default();
+        assert!(![&first, &altered, &third].iter().all(|r| report.check(r)));
+        assert_eq!(report.broken.map(|b| b.id), Some(2));
+
+        let mut report = ChainReport::default();
+        assert!(![&first, &third].iter().all(|r| report.check(r)));
+        assert_eq!(report.broken.map(|b| b.id), Some(3));
+
+        let legacy = audit_log::Model {
+            prev_hash: None,
+            entry_hash: None,
+            ..row(0, &entry(), GENESIS_HASH)
+        };
+        let mut report = ChainReport::default();
+        assert!([&legacy, &first].iter().all(|r| report.check(r)));
+        assert_eq!(report.unchained, 1);
+        assert!(!report.check(&audit_log::Model { id: 4, ..legacy }));
+    }
+}
@@ -6,7 +6,7 @@ pub async fn connect(database_url: &str) -> Result<DatabaseConnection, sea_orm::
   opt.max_connections(10)
     .min_connections(1)
     .connect_timeout(Duration::from_secs(10))
-    .idle_timeout(Duration::from_secs(60))
+    .idle_timeout(Duration::from_mins(1))
     .sqlx_logging(false);
   Database::connect(opt).await
 }
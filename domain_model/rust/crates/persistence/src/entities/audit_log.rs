@@ -14,6 +14,10 @@ pub struct Model {
     pub ip_address: Option<String>,
     pub user_agent: Option<String>,
     pub created_at: Option<DateTimeUtc>,
+    /// `entry_hash` of the entry before, or all zeros for the first.
+    pub prev_hash: Option<String>,
+    /// SHA-256 over `prev_hash` and this entry; see [`crate::audit`].
+    pub entry_hash: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
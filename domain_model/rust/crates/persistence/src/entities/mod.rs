@@ -1,4 +1,5 @@
 pub mod audit_log;
+pub mod auth_sessions;
 pub mod beds;
 pub mod bookings;
 pub mod government_submissions;
@@ -6,4 +7,6 @@ pub mod notifications;
 pub mod payments;
 pub mod pilgrims;
 pub mod pricing;
+pub mod refresh_tokens;
+pub mod user_identities;
 pub mod users;
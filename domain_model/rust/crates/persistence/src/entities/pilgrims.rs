@@ -1,25 +1,56 @@
 use sea_orm::entity::prelude::*;
 
+use crate::pii::{pii_fields, Encrypted};
+
+pii_fields! {
+    FirstName => "pilgrims.first_name_encrypted",
+    LastName1 => "pilgrims.last_name1_encrypted",
+    LastName2 => "pilgrims.last_name2_encrypted",
+    BirthDate => "pilgrims.birth_date_encrypted",
+    DocumentNumber => "pilgrims.document_number_encrypted",
+    Phone => "pilgrims.phone_encrypted",
+    Email => "pilgrims.email_encrypted",
+    AddressStreet => "pilgrims.address_street_encrypted",
+    AddressStreet2 => "pilgrims.address_street2_encrypted",
+    AddressCity => "pilgrims.address_city_encrypted",
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
 #[sea_orm(table_name = "pilgrims")]
 pub struct Model {
     #[sea_orm(primary_key)]
     pub id: i32,
-    pub first_name_encrypted: String,
-    pub last_name_1_encrypted: String,
-    pub last_name_2_encrypted: Option<String>,
-    pub birth_date_encrypted: String,
+    #[sea_orm(column_type = "Text")]
+    pub first_name_encrypted: Encrypted<FirstName>,
+    #[sea_orm(column_name = "last_name1_encrypted", column_type = "Text")]
+    pub last_name_1_encrypted: Encrypted<LastName1>,
+    #[sea_orm(column_name = "last_name2_encrypted", column_type = "Text", nullable)]
+    pub last_name_2_encrypted: Option<Encrypted<LastName2>>,
+    #[sea_orm(column_type = "Text")]
+    pub birth_date_encrypted: Encrypted<BirthDate>,
     pub document_type: String,
-    pub document_number_encrypted: String,
+    #[sea_orm(column_type = "Text")]
+    pub document_number_encrypted: Encrypted<DocumentNumber>,
+    /// Keyed hash of the normalised document number, for lookups.
+    pub document_number_blind_index: Option<String>,
     pub document_support: Option<String>,
     pub gender: String,
     pub nationality: Option<String>,
-    pub phone_encrypted: String,
-    pub email_encrypted: Option<String>,
+    #[sea_orm(column_type = "Text")]
+    pub phone_encrypted: Encrypted<Phone>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub email_encrypted: Option<Encrypted<Email>>,
     pub address_country: String,
-    pub address_street_encrypted: String,
-    pub address_street_2_encrypted: Option<String>,
-    pub address_city_encrypted: String,
+    #[sea_orm(column_type = "Text")]
+    pub address_street_encrypted: Encrypted<AddressStreet>,
+    #[sea_orm(
+        column_name = "address_street2_encrypted",
+        column_type = "Text",
+        nullable
+    )]
+    pub address_street_2_encrypted: Option<Encrypted<AddressStreet2>>,
+    #[sea_orm(column_type = "Text")]
+    pub address_city_encrypted: Encrypted<AddressCity>,
     pub address_postal_code: String,
     pub address_province: Option<String>,
     pub address_municipality_code: Option<String>,
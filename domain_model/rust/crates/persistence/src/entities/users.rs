@@ -7,13 +7,28 @@ pub struct Model {
     pub id: i32,
     pub username: String,
     pub password: String,
+    /// Space-separated: `pilgrim`, `hospitalero`, `admin`.
+    pub roles: String,
     pub created_at: Option<DateTimeUtc>,
+    /// Sealed by security-service in the `users.totp_secret` context.
+    pub totp_secret: Option<String>,
+    pub totp_enabled_at: Option<DateTimeUtc>,
+    pub totp_last_step: Option<i64>,
+    pub failed_login_attempts: i32,
+    pub locked_until: Option<DateTimeUtc>,
+    /// Staff accounts only sign in between `active_from` and `expires_at`.
+    pub active_from: Option<DateTimeUtc>,
+    pub expires_at: Option<DateTimeUtc>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
     #[sea_orm(has_many = "super::audit_log::Entity")]
     AuditLog,
+    #[sea_orm(has_many = "super::user_identities::Entity")]
+    UserIdentities,
+    #[sea_orm(has_many = "super::auth_sessions::Entity")]
+    AuthSessions,
 }
 
 impl Related<super::audit_log::Entity> for Entity {
@@ -22,4 +37,16 @@ impl Related<super::audit_log::Entity> for Entity {
     }
 }
 
+impl Related<super::user_identities::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::UserIdentities.def()
+    }
+}
+
+impl Related<super::auth_sessions::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::AuthSessions.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -1,5 +1,9 @@
 #![deny(warnings)]
 #![warn(clippy::all, clippy::pedantic)]
+#![allow(clippy::missing_errors_doc)]
 
+pub mod audit;
 pub mod db;
 pub mod entities;
+pub mod pii;
+pub mod privacy;
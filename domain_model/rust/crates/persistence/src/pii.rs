@@ -0,0 +1,586 @@
+//! Field-level encryption of personal data.
+//!
+//! Columns such as `pilgrims.phone_encrypted` hold [`Encrypted`] values: the
+//! plaintext is sealed when the value is created, so entities never carry
+//! ciphertext callers have to produce by hand. Values use the AES-256-GCM
+//! envelope format of the `pii-envelope` crate, which security-service
+//! encrypts with too, with the column as context; either side reads what
+//! the other wrote and security-service's rotation job can re-wrap them.
+//!
+//! Rows are loaded with their values still sealed. Plaintext only comes out
+//! of [`privacy::read_pilgrim`](crate::privacy::read_pilgrim), which first
+//! appends the read to the audit log, as security-service's decrypt
+//! endpoint does.
+//!
+//! Values from before envelope encryption existed, base64 of `encrypted:`
+//! and the plaintext, still read: they come back marked
+//! [`legacy`](Encrypted::is_legacy) until the rotation job encrypts them.
+//!
+//! Keys come from a [`KeyProvider`], installed once at start-up with
+//! [`install_key_provider`]. [`StaticKeyProvider::from_env`] reads the same
+//! settings as security-service.
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::{Arc, OnceLock};
+
+use albergue_domain::identity::EncryptedString;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use pii_envelope::{BlindIndexKey, CryptoError, Envelope, MIN_BLIND_INDEX_KEY_BYTES};
+use sea_orm::sea_query::{ArrayType, ColumnType, Nullable, ValueType, ValueTypeErr};
+use sea_orm::{ColIdx, QueryResult, TryGetError, TryGetable, Value};
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+pub use pii_envelope::MasterKey;
+
+const DEFAULT_KEY_ID: &str = "k1";
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum PiiError {
+    #[error("no key provider installed")]
+    NoKeyProvider,
+    #[error("a key provider is already installed")]
+    KeyProviderInstalled,
+    #[error("the value has not been read through the audit log")]
+    Sealed,
+    #[error("master key {0} is not available")]
+    UnknownKey(String),
+    #[error("not an encrypted value")]
+    Malformed,
+    #[error("decryption failed: wrong key or column, or the value was altered")]
+    Decryption,
+    #[error("invalid key: {0}")]
+    InvalidKey(String),
+    #[error("no randomness available")]
+    Randomness,
+}
+
+impl From<CryptoError> for PiiError {
+    fn from(e: CryptoError) -> Self {
+        match e {
+            CryptoError::Malformed => Self::Malformed,
+            CryptoError::UnknownKey(id) => Self::UnknownKey(id),
+            CryptoError::Decryption => Self::Decryption,
+            CryptoError::InvalidKey(reason) => Self::InvalidKey(reason),
+            CryptoError::Randomness => Self::Randomness,
+        }
+    }
+}
+
+/// A column holding encrypted values. The context binds each value to its
+/// column: a value copied into another column does not decrypt.
+pub trait PiiField: 'static {
+    /// `{table}.{column}`, e.g. `pilgrims.phone_encrypted`.
+    const CONTEXT: &'static str;
+}
+
+/// Declares [`PiiField`] markers.
+macro_rules! pii_fields {
+    ($($(#[$meta:meta])* $name:ident => $context:literal,)+) => {
+        $(
+            $(#[$meta])*
+            #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+            pub struct $name;
+
+            impl $crate::pii::PiiField for $name {
+                const CONTEXT: &'static str = $context;
+            }
+        )+
+    };
+}
+pub(crate) use pii_fields;
+
+/// Where master keys come from: configuration, a KMS, a test.
+pub trait KeyProvider: Send + Sync {
+    /// The key new values are wrapped with.
+    fn current_key(&self) -> Result<MasterKey, PiiError>;
+    /// Any key stored values may name, current or retired.
+    fn key(&self, id: &str) -> Result<MasterKey, PiiError>;
+    /// Key of blind indexes, independent of the master keys.
+    fn blind_index_key(&self) -> Result<Zeroizing<Vec<u8>>, PiiError>;
+}
+
+/// Keys held in memory.
+pub struct StaticKeyProvider {
+    current: MasterKey,
+    previous: Vec<MasterKey>,
+    blind_index_key: Zeroizing<Vec<u8>>,
+}
+
+impl StaticKeyProvider {
+    pub fn new(
+        current: MasterKey,
+        previous: Vec<MasterKey>,
+        blind_index_key: &[u8],
+    ) -> Result<Self, PiiError> {
+        if blind_index_key.len() < MIN_BLIND_INDEX_KEY_BYTES {
+            return Err(PiiError::InvalidKey(format!(
+                "the blind-index key needs at least {MIN_BLIND_INDEX_KEY_BYTES} bytes"
+            )));
+        }
+        Ok(Self {
+            current,
+            previous,
+            blind_index_key: Zeroizing::new(blind_index_key.to_vec()),
+        })
+    }
+
+    /// Reads `ENCRYPTION_KEY`, `ENCRYPTION_KEY_ID`, `ENCRYPTION_PREVIOUS_KEYS`
+    /// and `BLIND_INDEX_KEY`, as security-service does.
+    pub fn from_env() -> Result<Self, PiiError> {
+        let var = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        let decode = |name: &str, value: &str| {
+            STANDARD
+                .decode(value.trim())
+                .map(Zeroizing::new)
+                .map_err(|_| PiiError::InvalidKey(format!("{name} is not valid base64")))
+        };
+        let missing = |name: &str| PiiError::InvalidKey(format!("{name} is not set"));
+
+        let id = var("ENCRYPTION_KEY_ID").unwrap_or_else(|| DEFAULT_KEY_ID.to_string());
+        let key = var("ENCRYPTION_KEY").ok_or_else(|| missing("ENCRYPTION_KEY"))?;
+        let current = MasterKey::new(&id, &decode("ENCRYPTION_KEY", &key)?)?;
+        let previous = var("ENCRYPTION_PREVIOUS_KEYS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let (id, key) = entry.split_once(':').ok_or_else(|| {
+                    PiiError::InvalidKey("ENCRYPTION_PREVIOUS_KEYS entries are id:base64".into())
+                })?;
+                Ok(MasterKey::new(
+                    id.trim(),
+                    &decode("ENCRYPTION_PREVIOUS_KEYS", key)?,
+                )?)
+            })
+            .collect::<Result<Vec<_>, PiiError>>()?;
+        let blind = var("BLIND_INDEX_KEY").ok_or_else(|| missing("BLIND_INDEX_KEY"))?;
+        Self::new(current, previous, &decode("BLIND_INDEX_KEY", &blind)?)
+    }
+}
+
+impl KeyProvider for StaticKeyProvider {
+    fn current_key(&self) -> Result<MasterKey, PiiError> {
+        Ok(self.current.clone())
+    }
+
+    fn key(&self, id: &str) -> Result<MasterKey, PiiError> {
+        std::iter::once(&self.current)
+            .chain(&self.previous)
+            .find(|k| k.id() == id)
+            .cloned()
+            .ok_or_else(|| PiiError::UnknownKey(id.to_string()))
+    }
+
+    fn blind_index_key(&self) -> Result<Zeroizing<Vec<u8>>, PiiError> {
+        Ok(self.blind_index_key.clone())
+    }
+}
+
+static KEY_PROVIDER: OnceLock<Arc<dyn KeyProvider>> = OnceLock::new();
+
+/// Sets the provider [`Encrypted`] values use. Call once, before touching
+/// any encrypted column.
+pub fn install_key_provider(provider: Arc<dyn KeyProvider>) -> Result<(), PiiError> {
+    KEY_PROVIDER
+        .set(provider)
+        .map_err(|_| PiiError::KeyProviderInstalled)
+}
+
+pub(crate) fn key_provider() -> Result<&'static dyn KeyProvider, PiiError> {
+    KEY_PROVIDER
+        .get()
+        .map(AsRef::as_ref)
+        .ok_or(PiiError::NoKeyProvider)
+}
+
+/// Personal data stored encrypted in the `F` column. The plaintext is wiped
+/// from memory when the value is dropped and never shows in `Debug` output.
+pub struct Encrypted<F: PiiField> {
+    /// `None` while the value is sealed, as loaded from the database.
+    plaintext: Option<Zeroizing<String>>,
+    sealed: EncryptedString,
+    legacy: bool,
+    field: PhantomData<fn() -> F>,
+}
+
+impl<F: PiiField> Encrypted<F> {
+    /// Encrypts `plaintext` with the installed key provider.
+    pub fn new(plaintext: impl Into<String>) -> Result<Self, PiiError> {
+        Self::new_with(key_provider()?, plaintext)
+    }
+
+    pub fn new_with(
+        provider: &dyn KeyProvider,
+        plaintext: impl Into<String>,
+    ) -> Result<Self, PiiError> {
+        let plaintext = Zeroizing::new(plaintext.into());
+        let sealed =
+            pii_envelope::seal(&provider.current_key()?, plaintext.as_bytes(), F::CONTEXT)?;
+        Ok(Self {
+            plaintext: Some(plaintext),
+            sealed: EncryptedString(sealed),
+            legacy: false,
+            field: PhantomData,
+        })
+    }
+
+    /// A stored value, not yet decrypted.
+    #[must_use]
+    pub fn sealed(sealed: EncryptedString) -> Self {
+        Self {
+            plaintext: None,
+            sealed,
+            legacy: false,
+            field: PhantomData,
+        }
+    }
+
+    /// The plaintext, once the value has been created here or revealed by
+    /// an audited read.
+    pub fn expose(&self) -> Result<&str, PiiError> {
+        self.plaintext
+            .as_deref()
+            .map(String::as_str)
+            .ok_or(PiiError::Sealed)
+    }
+
+    /// The value as stored.
+    #[must_use]
+    pub fn encrypted(&self) -> &EncryptedString {
+        &self.sealed
+    }
+
+    /// Whether the stored value predates envelope encryption and so is not
+    /// actually encrypted. security-service's rotation job encrypts it.
+    #[must_use]
+    pub fn is_legacy(&self) -> bool {
+        self.legacy
+    }
+
+    /// Decrypts the value in place. Legacy values are read as they are.
+    fn reveal_with(&mut self, provider: &dyn KeyProvider) -> Result<(), PiiError> {
+        if self.plaintext.is_some() {
+            return Ok(());
+        }
+        let (plaintext, legacy) = match open(provider, &self.sealed.0, F::CONTEXT) {
+            Ok(plaintext) => (plaintext, false),
+            Err(PiiError::Malformed) => (
+                legacy_plaintext(&self.sealed.0).ok_or(PiiError::Malformed)?,
+                true,
+            ),
+            Err(e) => return Err(e),
+        };
+        self.plaintext = Some(plaintext);
+        self.legacy = legacy;
+        Ok(())
+    }
+}
+
+/// An encrypted column of a loaded row, so a whole row can be revealed once
+/// its read is on record.
+pub(crate) trait SealedField {
+    /// The column, e.g. `phone_encrypted`.
+    fn column(&self) -> &'static str;
+    fn reveal_with(&mut self, provider: &dyn KeyProvider) -> Result<(), PiiError>;
+}
+
+impl<F: PiiField> SealedField for Encrypted<F> {
+    fn column(&self) -> &'static str {
+        F::CONTEXT
+            .split_once('.')
+            .map_or(F::CONTEXT, |(_, column)| column)
+    }
+
+    fn reveal_with(&mut self, provider: &dyn KeyProvider) -> Result<(), PiiError> {
+        Encrypted::reveal_with(self, provider)
+    }
+}
+
+impl<F: PiiField> Clone for Encrypted<F> {
+    fn clone(&self) -> Self {
+        Self {
+            plaintext: self.plaintext.clone(),
+            sealed: self.sealed.clone(),
+            legacy: self.legacy,
+            field: PhantomData,
+        }
+    }
+}
+
+impl<F: PiiField> fmt::Debug for Encrypted<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Encrypted<{}>(<redacted>)", F::CONTEXT)
+    }
+}
+
+/// Two values are equal when they are the same stored value; equal
+/// plaintexts encrypt differently every time.
+impl<F: PiiField> PartialEq for Encrypted<F> {
+    fn eq(&self, other: &Self) -> bool {
+        self.sealed == other.sealed
+    }
+}
+
+impl<F: PiiField> Eq for Encrypted<F> {}
+
+impl<F: PiiField> From<Encrypted<F>> for Value {
+    fn from(value: Encrypted<F>) -> Self {
+        Value::from(value.sealed.0.clone())
+    }
+}
+
+impl<F: PiiField> ValueType for Encrypted<F> {
+    fn try_from(v: Value) -> Result<Self, ValueTypeErr> {
+        let sealed = <String as ValueType>::try_from(v)?;
+        Ok(Self::sealed(EncryptedString(sealed)))
+    }
+
+    fn type_name() -> String {
+        format!("Encrypted<{}>", F::CONTEXT)
+    }
+
+    fn array_type() -> ArrayType {
+        <String as ValueType>::array_type()
+    }
+
+    fn column_type() -> ColumnType {
+        ColumnType::Text
+    }
+}
+
+impl<F: PiiField> Nullable for Encrypted<F> {
+    fn null() -> Value {
+        <String as Nullable>::null()
+    }
+}
+
+impl<F: PiiField> TryGetable for Encrypted<F> {
+    fn try_get_by<I: ColIdx>(res: &QueryResult, index: I) -> Result<Self, TryGetError> {
+        String::try_get_by(res, index).map(|sealed| Self::sealed(EncryptedString(sealed)))
+    }
+}
+
+/// Keyed HMAC-SHA256 of the normalised `value`, matching security-service's
+/// blind indexes: case, spaces and hyphens are ignored.
+pub fn blind_index(value: &str, context: &str) -> Result<String, PiiError> {
+    blind_index_with(key_provider()?, value, context)
+}
+
+pub fn blind_index_with(
+    provider: &dyn KeyProvider,
+    value: &str,
+    context: &str,
+) -> Result<String, PiiError> {
+    Ok(BlindIndexKey::new(&provider.blind_index_key()?)?.index(value, context))
+}
+
+/// The plaintext of a value written before envelopes existed, as
+/// security-service's rotation job reads it.
+fn legacy_plaintext(value: &str) -> Option<Zeroizing<String>> {
+    utf8(pii_envelope::legacy_plaintext(value)?).ok()
+}
+
+fn open(
+    provider: &dyn KeyProvider,
+    envelope: &str,
+    context: &str,
+) -> Result<Zeroizing<String>, PiiError> {
+    let envelope = Envelope::parse(envelope)?;
+    let master = provider.key(envelope.key_id())?;
+    utf8(envelope.open(&master, context)?)
+}
+
+fn utf8(mut bytes: Zeroizing<Vec<u8>>) -> Result<Zeroizing<String>, PiiError> {
+    let text = String::from_utf8(std::mem::take(&mut *bytes)).map_err(|e| {
+        let mut bytes = e.into_bytes();
+        zeroize::Zeroize::zeroize(&mut bytes);
+        PiiError::Malformed
+    })?;
+    Ok(Zeroizing::new(text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pii_envelope::KEY_BYTES;
+
+    pii_fields! {
+        Phone => "pilgrims.phone_encrypted",
+        Email => "pilgrims.email_encrypted",
+    }
+
+    fn provider(current: (&str, u8), previous: &[(&str, u8)]) -> StaticKeyProvider {
+        let key = |(id, byte): (&str, u8)| MasterKey::new(id, &[byte; KEY_BYTES]).unwrap();
+        StaticKeyProvider::new(
+            key(current),
+            previous.iter().copied().map(key).collect(),
+            &[9; 32],
+        )
+        .unwrap()
+    }
+
+    fn open_with<F: PiiField>(
+        provider: &dyn KeyProvider,
+        sealed: EncryptedString,
+    ) -> Result<Encrypted<F>, PiiError> {
+        let mut value = Encrypted::sealed(sealed);
+        value.reveal_with(provider)?;
+        Ok(value)
+    }
+
+    #[test]
+    fn round_trips_and_hides_the_plaintext() {
+        let keys = provider(("k1", 1), &[]);
+        let phone = Encrypted::<Phone>::new_with(&keys, "+34 600 000 000").unwrap();
+        assert_eq!(phone.expose().unwrap(), "+34 600 000 000");
+        assert!(phone.encrypted().0.starts_with("v1:k1:"));
+        assert!(!phone.encrypted().0.contains("600"));
+        assert_eq!(
+            format!("{phone:?}"),
+            "Encrypted<pilgrims.phone_encrypted>(<redacted>)"
+        );
+
+        let read = open_with::<Phone>(&keys, phone.encrypted().clone()).unwrap();
+        assert_eq!(read.expose().unwrap(), "+34 600 000 000");
+        assert_eq!(read, phone);
+        assert_ne!(
+            Encrypted::<Phone>::new_with(&keys, "+34 600 000 000").unwrap(),
+            phone
+        );
+    }
+
+    #[test]
+    fn loaded_values_stay_sealed() {
+        let keys = provider(("k1", 1), &[]);
+        let phone = Encrypted::<Phone>::new_with(&keys, "+34 600 000 000").unwrap();
+        let mut loaded = Encrypted::<Phone>::sealed(phone.encrypted().clone());
+        assert_eq!(loaded.expose(), Err(PiiError::Sealed));
+        assert_eq!(loaded, phone);
+        assert_eq!(SealedField::column(&loaded), "phone_encrypted");
+
+        SealedField::reveal_with(&mut loaded, &keys).unwrap();
+        assert_eq!(loaded.expose().unwrap(), "+34 600 000 000");
+    }
+
+    #[test]
+    fn values_are_bound_to_their_column() {
+        let keys = provider(("k1", 1), &[]);
+        let phone = Encrypted::<Phone>::new_with(&keys, "+34 600 000 000").unwrap();
+        assert_eq!(
+            open_with::<Email>(&keys, phone.encrypted().clone()).err(),
+            Some(PiiError::Decryption)
+        );
+    }
+
+    #[test]
+    fn reads_values_wrapped_by_retired_keys() {
+        let old = provider(("k1", 1), &[]);
+        let sealed = Encrypted::<Phone>::new_with(&old, "612345678").unwrap();
+
+        let rotated = provider(("k2", 2), &[("k1", 1)]);
+        let read = open_with::<Phone>(&rotated, sealed.encrypted().clone()).unwrap();
+        assert_eq!(read.expose().unwrap(), "612345678");
+        assert!(Encrypted::<Phone>::new_with(&rotated, "612345678")
+            .unwrap()
+            .encrypted()
+            .0
+            .starts_with("v1:k2:"));
+
+        let forgotten = provider(("k3", 3), &[]);
+        assert_eq!(
+            open_with::<Phone>(&forgotten, sealed.encrypted().clone()).err(),
+            Some(PiiError::UnknownKey("k1".to_string()))
+        );
+        for bad in ["", "QW5h", "v1:k1:AA", "v1:k1:!!:AA"] {
+            assert!(
+                open_with::<Phone>(&old, EncryptedString(bad.into())).is_err(),
+                "{bad}"
+            );
+        }
+    }
+
+    #[test]
+    fn reads_legacy_values_for_rotation() {
+        let keys = provider(("k1", 1), &[]);
+        let legacy = EncryptedString(STANDARD.encode("encrypted:Ana"));
+        let read = open_with::<Phone>(&keys, legacy.clone()).unwrap();
+        assert_eq!(read.expose().unwrap(), "Ana");
+        assert!(read.is_legacy());
+        assert_eq!(read.encrypted(), &legacy);
+        assert!(!Encrypted::<Phone>::new_with(&keys, "Ana")
+            .unwrap()
+            .is_legacy());
+    }
+
+    /// Values security-service must read too; see the `pii-envelope` tests.
+    #[test]
+    fn reads_the_shared_vectors() {
+        let vectors: serde_json::Value = serde_json::from_str(include_str!(
+            "../../../../../backend/pii-envelope/tests/fixtures/envelope_vectors.json"
+        ))
+        .unwrap();
+        let text = |value: &serde_json::Value| value.as_str().unwrap().to_string();
+        let decode = |value: &serde_json::Value| STANDARD.decode(text(value)).unwrap();
+        let keys = StaticKeyProvider::new(
+            MasterKey::new("k1", &decode(&vectors["master_keys"]["k1"])).unwrap(),
+            Vec::new(),
+            &decode(&vectors["blind_index_key"]),
+        )
+        .unwrap();
+
+        for vector in vectors["envelopes"].as_array().unwrap() {
+            let plaintext = open(&keys, &text(&vector["value"]), &text(&vector["context"]));
+            assert_eq!(*plaintext.unwrap(), text(&vector["plaintext"]));
+        }
+        for vector in vectors["blind_indexes"].as_array().unwrap() {
+            let index = blind_index_with(&keys, &text(&vector["value"]), &text(&vector["context"]));
+            assert_eq!(index.unwrap(), text(&vector["index"]));
+        }
+        for vector in vectors["legacy"].as_array().unwrap() {
+            let read = open_with::<Phone>(&keys, EncryptedString(text(&vector["value"])));
+            assert_eq!(read.unwrap().expose().unwrap(), text(&vector["plaintext"]));
+        }
+    }
+
+    #[test]
+    fn blind_indexes_ignore_formatting() {
+        let keys = provider(("k1", 1), &[]);
+        let index = blind_index_with(&keys, "12345678-z", "pilgrims.document_number").unwrap();
+        assert_eq!(
+            index,
+            blind_index_with(&keys, "12345678Z ", "pilgrims.document_number").unwrap()
+        );
+        assert_ne!(
+            index,
+            blind_index_with(&keys, "12345678Z", "pilgrims.passport").unwrap()
+        );
+    }
+
+    #[test]
+    fn converts_through_the_installed_provider() {
+        // The only test touching the global provider.
+        assert_eq!(
+            Encrypted::<Email>::new("ana@example.org").err(),
+            Some(PiiError::NoKeyProvider)
+        );
+        install_key_provider(Arc::new(provider(("k1", 1), &[]))).unwrap();
+        assert_eq!(
+            install_key_provider(Arc::new(provider(("k2", 2), &[]))),
+            Err(PiiError::KeyProviderInstalled)
+        );
+
+        let email = Encrypted::<Email>::new("ana@example.org").unwrap();
+        let value: Value = email.clone().into();
+        let loaded = <Encrypted<Email> as ValueType>::try_from(value).unwrap();
+        assert_eq!(loaded, email);
+        assert_eq!(loaded.expose(), Err(PiiError::Sealed));
+    }
+}
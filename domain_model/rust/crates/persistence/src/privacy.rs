@@ -0,0 +1,718 @@
+//! Data-subject rights and retention for pilgrim records.
+//!
+//! - [`read_pilgrim`] decrypts a pilgrim's personal data. It is the only
+//!   way to: rows load with their encrypted fields sealed.
+//! - [`export`] bundles everything held about a pilgrim (GDPR art. 15).
+//! - [`erase`] handles an erasure request (art. 17). Data the traveller
+//!   registry obliges us to keep (RD 933/2021: what was submitted to
+//!   SES.Hospedajes, for [`REGISTRY_RETENTION_YEARS`] after the stay) is
+//!   restricted until then rather than deleted; everything else is removed
+//!   straight away.
+//! - [`sweep`] anonymises pilgrims whose `data_retention_until` has passed
+//!   and clears registry submissions past their statutory period.
+//!
+//! Each action, reads included, appends an entry to the [`audit`] chain
+//! naming the fields touched, never their values.
+
+use chrono::{DateTime, Months, NaiveDate, NaiveTime, Utc};
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, DatabaseConnection, DbErr,
+    EntityTrait, IntoActiveModel, QueryFilter, QuerySelect, Set, TransactionTrait,
+};
+use serde::Serialize;
+use serde_json::{json, Value as Json};
+use thiserror::Error;
+
+use crate::audit::{self, AuditEntry};
+use crate::entities::{
+    audit_log, bookings, government_submissions, notifications, payments, pilgrims,
+};
+use crate::pii::{self, Encrypted, KeyProvider, PiiError, SealedField};
+
+/// Years the traveller registry must be kept after a stay (RD 933/2021,
+/// art. 5).
+pub const REGISTRY_RETENTION_YEARS: u32 = 3;
+
+/// Stands in for erased text in columns that cannot be null.
+pub const ERASED: &str = "[erased]";
+
+#[derive(Debug, Error)]
+pub enum PrivacyError {
+    #[error("pilgrim {0} not found")]
+    NotFound(i32),
+    #[error(transparent)]
+    Db(#[from] DbErr),
+    #[error(transparent)]
+    Pii(#[from] PiiError),
+}
+
+/// A pilgrim's personal data, decrypted.
+#[derive(Debug, Serialize)]
+pub struct PilgrimData {
+    pub id: i32,
+    pub first_name: String,
+    pub last_name1: String,
+    pub last_name2: Option<String>,
+    pub birth_date: String,
+    pub document_type: String,
+    pub document_number: String,
+    pub document_support: Option<String>,
+    pub gender: String,
+    pub nationality: Option<String>,
+    pub phone: String,
+    pub email: Option<String>,
+    pub address_country: String,
+    pub address_street: String,
+    pub address_street2: Option<String>,
+    pub address_city: String,
+    pub address_postal_code: String,
+    pub address_province: Option<String>,
+    pub address_municipality_code: Option<String>,
+    pub id_photo_url: Option<String>,
+    pub language: Option<String>,
+    pub consent_given: Option<bool>,
+    pub consent_date: Option<DateTime<Utc>>,
+    pub data_retention_until: Option<DateTime<Utc>>,
+    pub last_access_date: Option<DateTime<Utc>>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl TryFrom<&pilgrims::Model> for PilgrimData {
+    type Error = PiiError;
+
+    fn try_from(p: &pilgrims::Model) -> Result<Self, PiiError> {
+        fn opt<F: crate::pii::PiiField>(
+            value: Option<&Encrypted<F>>,
+        ) -> Result<Option<String>, PiiError> {
+            value.map(|v| v.expose().map(str::to_string)).transpose()
+        }
+        Ok(Self {
+            id: p.id,
+            first_name: p.first_name_encrypted.expose()?.to_string(),
+            last_name1: p.last_name_1_encrypted.expose()?.to_string(),
+            last_name2: opt(p.last_name_2_encrypted.as_ref())?,
+            birth_date: p.birth_date_encrypted.expose()?.to_string(),
+            document_type: p.document_type.clone(),
+            document_number: p.document_number_encrypted.expose()?.to_string(),
+            document_support: p.document_support.clone(),
+            gender: p.gender.clone(),
+            nationality: p.nationality.clone(),
+            phone: p.phone_encrypted.expose()?.to_string(),
+            email: opt(p.email_encrypted.as_ref())?,
+            address_country: p.address_country.clone(),
+            address_street: p.address_street_encrypted.expose()?.to_string(),
+            address_street2: opt(p.address_street_2_encrypted.as_ref())?,
+            address_city: p.address_city_encrypted.expose()?.to_string(),
+            address_postal_code: p.address_postal_code.clone(),
+            address_province: p.address_province.clone(),
+            address_municipality_code: p.address_municipality_code.clone(),
+            id_photo_url: p.id_photo_url.clone(),
+            language: p.language.clone(),
+            consent_given: p.consent_given,
+            consent_date: p.consent_date,
+            data_retention_until: p.data_retention_until,
+            last_access_date: p.last_access_date,
+            created_at: p.created_at,
+            updated_at: p.updated_at,
+        })
+    }
+}
+
+/// Subject-access export.
+#[derive(Debug, Serialize)]
+pub struct SubjectExport {
+    pub generated_at: DateTime<Utc>,
+    pub pilgrim: PilgrimData,
+    pub bookings: Vec<Json>,
+    pub payments: Vec<Json>,
+    pub notifications: Vec<Json>,
+    pub government_submissions: Vec<Json>,
+    pub audit_log: Vec<Json>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum Erasure {
+    /// Everything personal was removed.
+    Anonymised,
+    /// Registry data is kept, restricted, until `until`; the rest was
+    /// removed.
+    Restricted { until: DateTime<Utc> },
+}
+
+#[derive(Debug, Serialize)]
+pub struct ErasureReport {
+    pub pilgrim_id: i32,
+    #[serde(flatten)]
+    pub erasure: Erasure,
+    pub fields: Vec<&'static str>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct SweepReport {
+    pub anonymised: Vec<i32>,
+    /// Pilgrims due for deletion whose registry data must still be kept.
+    pub extended: Vec<i32>,
+    pub submissions_cleared: Vec<i32>,
+}
+
+/// Fields cleared whatever the registry requires: free text and data the
+/// registry does not ask for.
+const UNREGISTERED_FIELDS: [&str; 6] = [
+    "pilgrims.id_photo_url",
+    "pilgrims.language",
+    "bookings.notes",
+    "notifications.recipient",
+    "notifications.subject",
+    "notifications.message",
+];
+
+/// Registry fields, cleared once the statutory period is over.
+const REGISTRY_FIELDS: [&str; 16] = [
+    "pilgrims.first_name_encrypted",
+    "pilgrims.last_name1_encrypted",
+    "pilgrims.last_name2_encrypted",
+    "pilgrims.birth_date_encrypted",
+    "pilgrims.document_number_encrypted",
+    "pilgrims.document_number_blind_index",
+    "pilgrims.document_support",
+    "pilgrims.phone_encrypted",
+    "pilgrims.email_encrypted",
+    "pilgrims.address_street_encrypted",
+    "pilgrims.address_street2_encrypted",
+    "pilgrims.address_city_encrypted",
+    "pilgrims.address_postal_code",
+    "pilgrims.address_municipality_code",
+    "government_submissions.xml_content",
+    "government_submissions.response_data",
+];
+
+/// When the registry obligation for stays ending on `check_outs` ends, if
+/// there is one.
+#[must_use]
+pub fn registry_hold_until(
+    check_outs: impl IntoIterator<Item = NaiveDate>,
+) -> Option<DateTime<Utc>> {
+    let last = check_outs.into_iter().max()?;
+    let until = last
+        .checked_add_months(Months::new(REGISTRY_RETENTION_YEARS * 12))?
+        .succ_opt()?;
+    Some(until.and_time(NaiveTime::MIN).and_utc())
+}
+
+/// Latest check-out date whose registry period may be over by `now`.
+fn registry_cutoff(now: DateTime<Utc>) -> NaiveDate {
+    let today = now.date_naive();
+    today
+        .checked_sub_months(Months::new(REGISTRY_RETENTION_YEARS * 12))
+        .unwrap_or(today)
+}
+
+/// Loads and decrypts a pilgrim's personal data. The read is on the audit
+/// chain, naming the encrypted fields, before anything is decrypted.
+pub async fn read_pilgrim(
+    db: &DatabaseConnection,
+    pilgrim_id: i32,
+    reader: Option<i32>,
+    now: DateTime<Utc>,
+) -> Result<PilgrimData, PrivacyError> {
+    read_pilgrim_with(db, pii::key_provider()?, pilgrim_id, reader, now).await
+}
+
+pub async fn read_pilgrim_with(
+    db: &DatabaseConnection,
+    provider: &dyn KeyProvider,
+    pilgrim_id: i32,
+    reader: Option<i32>,
+    now: DateTime<Utc>,
+) -> Result<PilgrimData, PrivacyError> {
+    let mut pilgrim = pilgrims::Entity::find_by_id(pilgrim_id)
+        .one(db)
+        .await?
+        .ok_or(PrivacyError::NotFound(pilgrim_id))?;
+    let fields: Vec<&str> = sealed_fields(&mut pilgrim)
+        .iter()
+        .map(|f| f.column())
+        .collect();
+    record(
+        db,
+        pilgrim_id,
+        "read",
+        None,
+        Some(json!({ "fields": fields })),
+        reader,
+        now,
+    )
+    .await?;
+
+    for field in sealed_fields(&mut pilgrim) {
+        field.reveal_with(provider)?;
+    }
+    Ok(PilgrimData::try_from(&pilgrim)?)
+}
+
+/// The encrypted fields `pilgrim` has values for.
+fn sealed_fields(pilgrim: &mut pilgrims::Model) -> Vec<&mut dyn SealedField> {
+    let mut fields: Vec<&mut dyn SealedField> = vec![
+        &mut pilgrim.first_name_encrypted,
+        &mut pilgrim.last_name_1_encrypted,
+        &mut pilgrim.birth_date_encrypted,
+        &mut pilgrim.document_number_encrypted,
+        &mut pilgrim.phone_encrypted,
+        &mut pilgrim.address_street_encrypted,
+        &mut pilgrim.address_city_encrypted,
+    ];
+    if let Some(v) = pilgrim.last_name_2_encrypted.as_mut() {
+        fields.push(v);
+    }
+    if let Some(v) = pilgrim.email_encrypted.as_mut() {
+        fields.push(v);
+    }
+    if let Some(v) = pilgrim.address_street_2_encrypted.as_mut() {
+        fields.push(v);
+    }
+    fields
+}
+
+pub async fn export(
+    db: &DatabaseConnection,
+    pilgrim_id: i32,
+    actor: Option<i32>,
+    now: DateTime<Utc>,
+) -> Result<SubjectExport, PrivacyError> {
+    let pilgrim = read_pilgrim(db, pilgrim_id, actor, now).await?;
+    let booking_ids = booking_ids(db, pilgrim_id).await?;
+
+    let bookings = bookings::Entity::find()
+        .filter(bookings::Column::PilgrimId.eq(pilgrim_id))
+        .into_json()
+        .all(db)
+        .await?;
+    let payments = payments::Entity::find()
+        .filter(payments::Column::BookingId.is_in(booking_ids.clone()))
+        .into_json()
+        .all(db)
+        .await?;
+    let notifications = notifications::Entity::find()
+        .filter(
+            Condition::any()
+                .add(notifications::Column::PilgrimId.eq(pilgrim_id))
+                .add(notifications::Column::BookingId.is_in(booking_ids.clone())),
+        )
+        .into_json()
+        .all(db)
+        .await?;
+    let government_submissions = government_submissions::Entity::find()
+        .filter(government_submissions::Column::BookingId.is_in(booking_ids.clone()))
+        .into_json()
+        .all(db)
+        .await?;
+    let audit = audit_log::Entity::find()
+        .filter(
+            Condition::any()
+                .add(
+                    Condition::all()
+                        .add(audit_log::Column::TableName.eq("pilgrims"))
+                        .add(audit_log::Column::RecordId.eq(pilgrim_id.to_string())),
+                )
+                .add(
+                    Condition::all()
+                        .add(audit_log::Column::TableName.eq("bookings"))
+                        .add(
+                            audit_log::Column::RecordId
+                                .is_in(booking_ids.iter().map(ToString::to_string)),
+                        ),
+                ),
+        )
+        .into_json()
+        .all(db)
+        .await?;
+
+    record(
+        db,
+        pilgrim_id,
+        "gdpr_export",
+        None,
+        Some(json!({ "generated_at": now })),
+        actor,
+        now,
+    )
+    .await?;
+
+    Ok(SubjectExport {
+        generated_at: now,
+        pilgrim,
+        bookings,
+        payments,
+        notifications,
+        government_submissions,
+        audit_log: audit,
+    })
+}
+
+/// Acts on an erasure request. Registry data still within its statutory
+/// period is kept, `data_retention_until` set to the end of it so the
+/// sweeper erases it then.
+pub async fn erase(
+    db: &DatabaseConnection,
+    pilgrim_id: i32,
+    actor: Option<i32>,
+    now: DateTime<Utc>,
+) -> Result<ErasureReport, PrivacyError> {
+    let txn = db.begin().await?;
+    let pilgrim = pilgrims::Entity::find_by_id(pilgrim_id)
+        .one(&txn)
+        .await?
+        .ok_or(PrivacyError::NotFound(pilgrim_id))?;
+    let hold = registry_hold(&txn, pilgrim_id)
+        .await?
+        .filter(|until| *until > now);
+    let report = apply(&txn, pilgrim, hold, now).await?;
+    record(
+        &txn,
+        pilgrim_id,
+        "gdpr_erasure",
+        Some(json!({ "fields": report.fields })),
+        Some(json!(report.erasure)),
+        actor,
+        now,
+    )
+    .await?;
+    txn.commit().await?;
+    Ok(report)
+}
+
+/// Enforces retention: pilgrims past `data_retention_until` are
+/// anonymised, or kept restricted while the registry requires, and
+/// registry submissions past their statutory period are cleared.
+pub async fn sweep(
+    db: &DatabaseConnection,
+    now: DateTime<Utc>,
+) -> Result<SweepReport, PrivacyError> {
+    let mut report = SweepReport::default();
+    let due = pilgrims::Entity::find()
+        .filter(pilgrims::Column::DataRetentionUntil.lte(now))
+        .all(db)
+        .await?;
+    for pilgrim in due {
+        let txn = db.begin().await?;
+        let pilgrim_id = pilgrim.id;
+        let hold = registry_hold(&txn, pilgrim_id)
+            .await?
+            .filter(|until| *until > now);
+        let erasure = apply(&txn, pilgrim, hold, now).await?;
+        record(
+            &txn,
+            pilgrim_id,
+            "retention_purge",
+            Some(json!({ "fields": erasure.fields })),
+            Some(json!(erasure.erasure)),
+            None,
+            now,
+        )
+        .await?;
+        txn.commit().await?;
+        match erasure.erasure {
+            Erasure::Anonymised => report.anonymised.push(pilgrim_id),
+            Erasure::Restricted { .. } => report.extended.push(pilgrim_id),
+        }
+    }
+
+    let expired: Vec<(i32, NaiveDate)> = government_submissions::Entity::find()
+        .select_only()
+        .column(government_submissions::Column::Id)
+        .column(bookings::Column::CheckOutDate)
+        .inner_join(bookings::Entity)
+        .filter(government_submissions::Column::XmlContent.ne(ERASED))
+        .filter(bookings::Column::CheckOutDate.lte(registry_cutoff(now)))
+        .into_tuple()
+        .all(db)
+        .await?;
+    for (id, check_out) in expired {
+        if registry_hold_until([check_out]).is_some_and(|until| until > now) {
+            continue;
+        }
+        let txn = db.begin().await?;
+        clear_submissions(&txn, government_submissions::Column::Id.eq(id)).await?;
+        let fields = json!({
+            "fields": [
+                "government_submissions.xml_content",
+                "government_submissions.response_data",
+            ]
+        });
+        let entry = AuditEntry::new(
+            "government_submissions",
+            &id.to_string(),
+            "retention_purge",
+            now,
+        )
+        .values(Some(fields), None);
+        audit::record(&txn, &entry).await?;
+        txn.commit().await?;
+        report.submissions_cleared.push(id);
+    }
+    Ok(report)
+}
+
+async fn booking_ids(db: &impl ConnectionTrait, pilgrim_id: i32) -> Result<Vec<i32>, DbErr> {
+    bookings::Entity::find()
+        .select_only()
+        .column(bookings::Column::Id)
+        .filter(bookings::Column::PilgrimId.eq(pilgrim_id))
+        .into_tuple()
+        .all(db)
+        .await
+}
+
+/// End of the registry obligation for stays reported to SES.Hospedajes.
+async fn registry_hold(
+    db: &impl ConnectionTrait,
+    pilgrim_id: i32,
+) -> Result<Option<DateTime<Utc>>, DbErr> {
+    let check_outs: Vec<NaiveDate> = bookings::Entity::find()
+        .select_only()
+        .column(bookings::Column::CheckOutDate)
+        .inner_join(government_submissions::Entity)
+        .filter(bookings::Column::PilgrimId.eq(pilgrim_id))
+        .into_tuple()
+        .all(db)
+        .await?;
+    Ok(registry_hold_until(check_outs))
+}
+
+async fn apply(
+    db: &impl ConnectionTrait,
+    pilgrim: pilgrims::Model,
+    hold: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> Result<ErasureReport, PrivacyError> {
+    let pilgrim_id = pilgrim.id;
+    let mut fields = UNREGISTERED_FIELDS.to_vec();
+    let mut row = pilgrim.into_active_model();
+    row.id_photo_url = Set(None);
+    row.language = Set(None);
+    row.consent_given = Set(Some(false));
+    row.updated_at = Set(Some(now));
+
+    let erasure = if let Some(until) = hold {
+        row.data_retention_until = Set(Some(until));
+        Erasure::Restricted { until }
+    } else {
+        row.first_name_encrypted = Set(Encrypted::new(ERASED)?);
+        row.last_name_1_encrypted = Set(Encrypted::new(ERASED)?);
+        row.last_name_2_encrypted = Set(None);
+        row.birth_date_encrypted = Set(Encrypted::new(ERASED)?);
+        row.document_number_encrypted = Set(Encrypted::new(ERASED)?);
+        row.document_number_blind_index = Set(None);
+        row.document_support = Set(None);
+        row.phone_encrypted = Set(Encrypted::new(ERASED)?);
+        row.email_encrypted = Set(None);
+        row.address_street_encrypted = Set(Encrypted::new(ERASED)?);
+        row.address_street_2_encrypted = Set(None);
+        row.address_city_encrypted = Set(Encrypted::new(ERASED)?);
+        row.address_postal_code = Set(ERASED.to_string());
+        row.address_municipality_code = Set(None);
+        row.data_retention_until = Set(None);
+        row.last_access_date = Set(None);
+        fields.extend(REGISTRY_FIELDS);
+        Erasure::Anonymised
+    };
+    row.update(db).await?;
+
+    bookings::Entity::update_many()
+        .col_expr(bookings::Column::Notes, Expr::value(Option::<String>::None))
+        .filter(bookings::Column::PilgrimId.eq(pilgrim_id))
+        .exec(db)
+        .await?;
+    let booking_ids = booking_ids(db, pilgrim_id).await?;
+    notifications::Entity::update_many()
+        .col_expr(notifications::Column::Recipient, Expr::value(ERASED))
+        .col_expr(
+            notifications::Column::Subject,
+            Expr::value(Option::<String>::None),
+        )
+        .col_expr(notifications::Column::Message, Expr::value(ERASED))
+        .filter(
+            Condition::any()
+                .add(notifications::Column::PilgrimId.eq(pilgrim_id))
+                .add(notifications::Column::BookingId.is_in(booking_ids.clone())),
+        )
+        .exec(db)
+        .await?;
+    if erasure == Erasure::Anonymised {
+        clear_submissions(
+            db,
+            government_submissions::Column::BookingId.is_in(booking_ids),
+        )
+        .await?;
+    }
+
+    Ok(ErasureReport {
+        pilgrim_id,
+        erasure,
+        fields,
+    })
+}
+
+async fn clear_submissions(
+    db: &impl ConnectionTrait,
+    filter: sea_orm::sea_query::SimpleExpr,
+) -> Result<(), DbErr> {
+    government_submissions::Entity::update_many()
+        .col_expr(
+            government_submissions::Column::XmlContent,
+            Expr::value(ERASED),
+        )
+        .col_expr(
+            government_submissions::Column::ResponseData,
+            Expr::value(Option::<Json>::None),
+        )
+        .filter(filter)
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+async fn record(
+    db: &(impl ConnectionTrait + TransactionTrait),
+    pilgrim_id: i32,
+    action: &str,
+    old_values: Option<Json>,
+    new_values: Option<Json>,
+    actor: Option<i32>,
+    now: DateTime<Utc>,
+) -> Result<(), DbErr> {
+    let entry = AuditEntry::new("pilgrims", &pilgrim_id.to_string(), action, now)
+        .values(old_values, new_values)
+        .by(actor);
+    audit::record(db, &entry).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::{Database, DbBackend, Schema};
+
+    use super::*;
+    use crate::entities::users;
+    use crate::pii::{MasterKey, StaticKeyProvider};
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn registry_hold_runs_from_the_last_stay() {
+        assert_eq!(registry_hold_until([]), None);
+        let until = registry_hold_until([date(2024, 5, 2), date(2025, 9, 30), date(2023, 1, 1)]);
+        assert_eq!(
+            until,
+            Some(date(2028, 10, 1).and_time(NaiveTime::MIN).and_utc())
+        );
+        // A stay ending on 29 February is kept until the end of 28 February.
+        assert_eq!(
+            registry_hold_until([date(2024, 2, 29)]),
+            Some(date(2027, 3, 1).and_time(NaiveTime::MIN).and_utc())
+        );
+    }
+
+    #[test]
+    fn erasure_reports_fields_not_values() {
+        let report = ErasureReport {
+            pilgrim_id: 7,
+            erasure: Erasure::Restricted {
+                until: date(2028, 10, 1).and_time(NaiveTime::MIN).and_utc(),
+            },
+            fields: UNREGISTERED_FIELDS.to_vec(),
+        };
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["mode"], "restricted");
+        assert_eq!(json["until"], "2028-10-01T00:00:00Z");
+        assert_eq!(json["fields"][0], "pilgrims.id_photo_url");
+        assert!(REGISTRY_FIELDS
+            .iter()
+            .all(|f| !UNREGISTERED_FIELDS.contains(f)));
+    }
+
+    #[tokio::test]
+    async fn reads_are_audited_before_decrypting() -> Result<(), PrivacyError> {
+        let db = Database::connect("sqlite::memory:").await?;
+        let schema = Schema::new(DbBackend::Sqlite);
+        for table in [
+            schema.create_table_from_entity(pilgrims::Entity),
+            schema.create_table_from_entity(users::Entity),
+            schema.create_table_from_entity(audit_log::Entity),
+        ] {
+            db.execute(&table).await?;
+        }
+        let master = MasterKey::new("k1", &[1; 32]).map_err(PiiError::from)?;
+        let keys = StaticKeyProvider::new(master, Vec::new(), &[9; 32])?;
+        let now = date(2026, 10, 19).and_time(NaiveTime::MIN).and_utc();
+
+        let pilgrim = pilgrims::Model {
+            id: 1,
+            first_name_encrypted: Encrypted::new_with(&keys, "Ana")?,
+            last_name_1_encrypted: Encrypted::new_with(&keys, "García")?,
+            last_name_2_encrypted: None,
+            birth_date_encrypted: Encrypted::new_with(&keys, "1990-04-01")?,
+            document_type: "NIF".to_string(),
+            document_number_encrypted: Encrypted::new_with(&keys, "12345678Z")?,
+            document_number_blind_index: None,
+            document_support: None,
+            gender: "F".to_string(),
+            nationality: Some("ESP".to_string()),
+            phone_encrypted: Encrypted::new_with(&keys, "+34 600 000 000")?,
+            email_encrypted: Some(Encrypted::new_with(&keys, "ana@example.org")?),
+            address_country: "ESP".to_string(),
+            address_street_encrypted: Encrypted::new_with(&keys, "Rúa do Franco 1")?,
+            address_street_2_encrypted: None,
+            address_city_encrypted: Encrypted::new_with(&keys, "Santiago")?,
+            address_postal_code: "15705".to_string(),
+            address_province: None,
+            address_municipality_code: None,
+            id_photo_url: None,
+            language: None,
+            consent_given: None,
+            consent_date: None,
+            data_retention_until: None,
+            last_access_date: None,
+            created_at: None,
+            updated_at: None,
+        };
+        pilgrim.into_active_model().insert(&db).await?;
+
+        let loaded = pilgrims::Entity::find_by_id(1).one(&db).await?.unwrap();
+        assert_eq!(loaded.phone_encrypted.expose(), Err(PiiError::Sealed));
+        assert!(audit_log::Entity::find().all(&db).await?.is_empty());
+
+        let data = read_pilgrim_with(&db, &keys, 1, None, now).await?;
+        assert_eq!(data.phone, "+34 600 000 000");
+        assert_eq!(data.email.as_deref(), Some("ana@example.org"));
+        assert_eq!(data.last_name2, None);
+
+        let entries = audit_log::Entity::find().all(&db).await?;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].action, "read");
+        assert_eq!(entries[0].record_id, "1");
+        let fields = &entries[0].new_values.as_ref().unwrap()["fields"];
+        assert_eq!(fields.as_array().unwrap().len(), 8);
+        assert!(fields
+            .as_array()
+            .unwrap()
+            .contains(&Json::from("email_encrypted")));
+        assert!(!entries[0]
+            .new_values
+            .as_ref()
+            .unwrap()
+            .to_string()
+            .contains("600"));
+
+        assert!(matches!(
+            read_pilgrim_with(&db, &keys, 2, None, now).await,
+            Err(PrivacyError::NotFound(2))
+        ));
+        Ok(())
+    }
+}
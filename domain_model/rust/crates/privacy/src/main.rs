@@ -0,0 +1,56 @@
+#![deny(warnings)]
+#![warn(clippy::all, clippy::pedantic, clippy::nursery)]
+#![allow(clippy::missing_errors_doc)]
+
+//! Data-subject requests and retention enforcement over the pilgrim store.
+//!
+//! ```text
+//! albergue-privacy export <pilgrim-id> [--actor <user-id>]
+//! albergue-privacy erase <pilgrim-id> [--actor <user-id>]
+//! albergue-privacy sweep
+//! ```
+//!
+//! Results are printed as JSON. Keys are read from `ENCRYPTION_KEY` and
+//! friends, the database from `DATABASE_URL`.
+
+use std::sync::Arc;
+
+use albergue_persistence::{db, pii, privacy};
+use chrono::Utc;
+
+const USAGE: &str = "usage: albergue-privacy export|erase <pilgrim-id> [--actor <user-id>] | sweep";
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+  tracing_subscriber::fmt()
+    .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+    .init();
+
+  let mut args = std::env::args().skip(1);
+  let command = args.next().ok_or(USAGE)?;
+  let pilgrim_id = if command == "sweep" {
+    None
+  } else {
+    Some(args.next().ok_or(USAGE)?.parse::<i32>()?)
+  };
+  let mut actor = None;
+  while let Some(flag) = args.next() {
+    match flag.as_str() {
+      "--actor" => actor = Some(args.next().ok_or(USAGE)?.parse::<i32>()?),
+      _ => return Err(USAGE.into()),
+    }
+  }
+
+  pii::install_key_provider(Arc::new(pii::StaticKeyProvider::from_env()?))?;
+  let conn = db::connect_auto().await?;
+  let now = Utc::now();
+
+  let report = match (command.as_str(), pilgrim_id) {
+    ("export", Some(id)) => serde_json::to_value(privacy::export(&conn, id, actor, now).await?)?,
+    ("erase", Some(id)) => serde_json::to_value(privacy::erase(&conn, id, actor, now).await?)?,
+    ("sweep", None) => serde_json::to_value(privacy::sweep(&conn, now).await?)?,
+    _ => return Err(USAGE.into()),
+  };
+  println!("{}", serde_json::to_string_pretty(&report)?);
+  Ok(())
+}
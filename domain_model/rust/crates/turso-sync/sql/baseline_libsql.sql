@@ -4,9 +4,56 @@ CREATE TABLE IF NOT EXISTS users (
   id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
   username TEXT NOT NULL UNIQUE,
   password TEXT NOT NULL,
-  created_at TIMESTAMP NULL
+  created_at TIMESTAMP NULL,
+  roles TEXT NOT NULL DEFAULT 'pilgrim',
+  totp_secret TEXT NULL,
+  totp_enabled_at TIMESTAMP NULL,
+  totp_last_step INTEGER NULL,
+  failed_login_attempts INTEGER NOT NULL DEFAULT 0,
+  locked_until TIMESTAMP NULL,
+  active_from TIMESTAMP NULL,
+  expires_at TIMESTAMP NULL
+);
+
+CREATE TABLE IF NOT EXISTS user_identities (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  user_id INTEGER NOT NULL,
+  provider TEXT NOT NULL,
+  subject TEXT NOT NULL,
+  email TEXT NULL,
+  created_at TIMESTAMP NULL,
+  last_login_at TIMESTAMP NULL,
+  CONSTRAINT fk_user_identities_user FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_user_identities_provider_subject ON user_identities(provider, subject);
+
+CREATE TABLE IF NOT EXISTS auth_sessions (
+  id TEXT PRIMARY KEY NOT NULL,
+  user_id INTEGER NOT NULL,
+  provider TEXT NOT NULL,
+  subject TEXT NOT NULL,
+  id_token TEXT NULL,
+  user_agent TEXT NULL,
+  created_at TIMESTAMP NOT NULL,
+  last_used_at TIMESTAMP NULL,
+  expires_at TIMESTAMP NOT NULL,
+  revoked_at TIMESTAMP NULL,
+  CONSTRAINT fk_auth_sessions_user FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
 );
 
+CREATE INDEX IF NOT EXISTS idx_auth_sessions_user ON auth_sessions(user_id);
+
+CREATE TABLE IF NOT EXISTS refresh_tokens (
+  token_hash TEXT PRIMARY KEY NOT NULL,
+  session_id TEXT NOT NULL,
+  created_at TIMESTAMP NOT NULL,
+  used_at TIMESTAMP NULL,
+  CONSTRAINT fk_refresh_tokens_session FOREIGN KEY (session_id) REFERENCES auth_sessions(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_refresh_tokens_session ON refresh_tokens(session_id);
+
 CREATE TABLE IF NOT EXISTS pilgrims (
   id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
   first_name_encrypted TEXT NOT NULL,
@@ -15,6 +62,7 @@ CREATE TABLE IF NOT EXISTS pilgrims (
   birth_date_encrypted TEXT NOT NULL,
   document_type TEXT NOT NULL,
   document_number_encrypted TEXT NOT NULL,
+  document_number_blind_index TEXT NULL,
   document_support TEXT NULL,
   gender TEXT NOT NULL,
   nationality TEXT NULL,
@@ -37,6 +85,8 @@ CREATE TABLE IF NOT EXISTS pilgrims (
   updated_at TIMESTAMP NULL
 );
 
+CREATE INDEX IF NOT EXISTS idx_pilgrims_document_number_blind_index ON pilgrims(document_number_blind_index);
+
 CREATE TABLE IF NOT EXISTS beds (
   id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
   bed_number INTEGER NOT NULL,
@@ -158,13 +208,19 @@ CREATE TABLE IF NOT EXISTS audit_log (
   ip_address TEXT NULL,
   user_agent TEXT NULL,
   created_at TIMESTAMP NULL,
+  prev_hash TEXT NULL,
+  entry_hash TEXT NULL,
   CONSTRAINT fk_audit_log_user FOREIGN KEY (user_id) REFERENCES users(id)
 );
 
 CREATE INDEX IF NOT EXISTS idx_audit_log_record ON audit_log(table_name, record_id);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_audit_log_prev_hash ON audit_log(prev_hash);
+CREATE INDEX IF NOT EXISTS idx_audit_log_user_created ON audit_log(user_id, created_at);
+CREATE INDEX IF NOT EXISTS idx_audit_log_created_at ON audit_log(created_at);
 
+-- '!' matches no password hash: the seed user cannot sign in.
 INSERT INTO users (username, password)
-SELECT 'synthetic_admin', 'synthetic_password'
+SELECT 'synthetic_admin', '!'
 WHERE NOT EXISTS (
   SELECT 1 FROM users WHERE username = 'synthetic_admin' LIMIT 1
 );
@@ -222,6 +222,9 @@ mod tests {
 
     for expected in [
       "users",
+      "user_identities",
+      "auth_sessions",
+      "refresh_tokens",
       "pilgrims",
       "beds",
       "bookings",
@@ -3,11 +3,14 @@ import {
   text,
   serial,
   integer,
+  bigint,
   boolean,
   timestamp,
   decimal,
   date,
   jsonb,
+  uniqueIndex,
+  index,
 } from "drizzle-orm/pg-core";
 import { relations } from "drizzle-orm";
 import { createInsertSchema } from "drizzle-zod";
@@ -18,37 +21,119 @@ export const users = pgTable("users", {
   username: text("username").notNull().unique(),
   password: text("password").notNull(),
   createdAt: timestamp("created_at").defaultNow(),
+  // Space-separated: pilgrim, hospitalero, admin
+  roles: text("roles").notNull().default("pilgrim"),
+  // Base32 TOTP secret; in use once totpEnabledAt is set
+  totpSecret: text("totp_secret"),
+  totpEnabledAt: timestamp("totp_enabled_at"),
+  totpLastStep: bigint("totp_last_step", { mode: "number" }),
+  failedLoginAttempts: integer("failed_login_attempts").notNull().default(0),
+  lockedUntil: timestamp("locked_until"),
+  // Staff accounts only sign in during their shift rota
+  activeFrom: timestamp("active_from"),
+  expiresAt: timestamp("expires_at"),
 });
 
-export const pilgrims = pgTable("pilgrims", {
-  id: serial("id").primaryKey(),
-  firstName: text("first_name_encrypted").notNull(),
-  lastName1: text("last_name_1_encrypted").notNull(),
-  lastName2: text("last_name_2_encrypted"),
-  birthDate: text("birth_date_encrypted").notNull(),
-  documentType: text("document_type").notNull(),
-  documentNumber: text("document_number_encrypted").notNull(),
-  documentSupport: text("document_support"),
-  gender: text("gender").notNull(),
-  nationality: text("nationality"),
-  phone: text("phone_encrypted").notNull(),
-  email: text("email_encrypted"),
-  addressCountry: text("address_country").notNull(),
-  addressStreet: text("address_street_encrypted").notNull(),
-  addressStreet2: text("address_street_2_encrypted"),
-  addressCity: text("address_city_encrypted").notNull(),
-  addressPostalCode: text("address_postal_code").notNull(),
-  addressProvince: text("address_province"),
-  addressMunicipalityCode: text("address_municipality_code"),
-  idPhotoUrl: text("id_photo_url"),
-  language: text("language").default("es"),
-  consentGiven: boolean("consent_given").default(true),
-  consentDate: timestamp("consent_date").defaultNow(),
-  dataRetentionUntil: timestamp("data_retention_until"),
-  lastAccessDate: timestamp("last_access_date").defaultNow(),
-  createdAt: timestamp("created_at").defaultNow(),
-  updatedAt: timestamp("updated_at").defaultNow(),
-});
+// Identity-provider accounts (Logto, Zitadel, GitHub...) linked to a user
+export const userIdentities = pgTable(
+  "user_identities",
+  {
+    id: serial("id").primaryKey(),
+    userId: integer("user_id")
+      .notNull()
+      .references(() => users.id, { onDelete: "cascade" }),
+    provider: text("provider").notNull(),
+    subject: text("subject").notNull(),
+    email: text("email"),
+    createdAt: timestamp("created_at").defaultNow(),
+    lastLoginAt: timestamp("last_login_at"),
+  },
+  (table) => ({
+    providerSubject: uniqueIndex("idx_user_identities_provider_subject").on(
+      table.provider,
+      table.subject,
+    ),
+  }),
+);
+
+// One row per sign-in; refresh tokens rotate within it and its id is the
+// `sid` claim of the access tokens the auth-service issues for it
+export const authSessions = pgTable(
+  "auth_sessions",
+  {
+    id: text("id").primaryKey(),
+    userId: integer("user_id")
+      .notNull()
+      .references(() => users.id, { onDelete: "cascade" }),
+    provider: text("provider").notNull(),
+    subject: text("subject").notNull(),
+    idToken: text("id_token"),
+    userAgent: text("user_agent"),
+    createdAt: timestamp("created_at").notNull(),
+    lastUsedAt: timestamp("last_used_at"),
+    expiresAt: timestamp("expires_at").notNull(),
+    revokedAt: timestamp("revoked_at"),
+  },
+  (table) => ({
+    user: index("idx_auth_sessions_user").on(table.userId),
+  }),
+);
+
+// Refresh tokens, stored as SHA-256 hashes; `usedAt` is set on rotation
+export const refreshTokens = pgTable(
+  "refresh_tokens",
+  {
+    tokenHash: text("token_hash").primaryKey(),
+    sessionId: text("session_id")
+      .notNull()
+      .references(() => authSessions.id, { onDelete: "cascade" }),
+    createdAt: timestamp("created_at").notNull(),
+    usedAt: timestamp("used_at"),
+  },
+  (table) => ({
+    session: index("idx_refresh_tokens_session").on(table.sessionId),
+  }),
+);
+
+export const pilgrims = pgTable(
+  "pilgrims",
+  {
+    id: serial("id").primaryKey(),
+    firstName: text("first_name_encrypted").notNull(),
+    lastName1: text("last_name_1_encrypted").notNull(),
+    lastName2: text("last_name_2_encrypted"),
+    birthDate: text("birth_date_encrypted").notNull(),
+    documentType: text("document_type").notNull(),
+    documentNumber: text("document_number_encrypted").notNull(),
+    // Keyed HMAC of the normalised number, for finding returning pilgrims.
+    documentNumberBlindIndex: text("document_number_blind_index"),
+    documentSupport: text("document_support"),
+    gender: text("gender").notNull(),
+    nationality: text("nationality"),
+    phone: text("phone_encrypted").notNull(),
+    email: text("email_encrypted"),
+    addressCountry: text("address_country").notNull(),
+    addressStreet: text("address_street_encrypted").notNull(),
+    addressStreet2: text("address_street_2_encrypted"),
+    addressCity: text("address_city_encrypted").notNull(),
+    addressPostalCode: text("address_postal_code").notNull(),
+    addressProvince: text("address_province"),
+    addressMunicipalityCode: text("address_municipality_code"),
+    idPhotoUrl: text("id_photo_url"),
+    language: text("language").default("es"),
+    consentGiven: boolean("consent_given").default(true),
+    consentDate: timestamp("consent_date").defaultNow(),
+    dataRetentionUntil: timestamp("data_retention_until"),
+    lastAccessDate: timestamp("last_access_date").defaultNow(),
+    createdAt: timestamp("created_at").defaultNow(),
+    updatedAt: timestamp("updated_at").defaultNow(),
+  },
+  (table) => ({
+    documentNumberBlindIndex: index("idx_pilgrims_document_number_blind_index").on(
+      table.documentNumberBlindIndex,
+    ),
+  }),
+);
 
 export const beds = pgTable("beds", {
   id: serial("id").primaryKey(),
@@ -153,21 +238,62 @@ export const notifications = pgTable("notifications", {
   createdAt: timestamp("created_at").defaultNow(),
 });
 
-export const auditLog = pgTable("audit_log", {
-  id: serial("id").primaryKey(),
-  tableName: text("table_name").notNull(),
-  recordId: text("record_id").notNull(),
-  action: text("action").notNull(),
-  oldValues: jsonb("old_values"),
-  newValues: jsonb("new_values"),
-  userId: integer("user_id").references(() => users.id),
-  ipAddress: text("ip_address"),
-  userAgent: text("user_agent"),
-  createdAt: timestamp("created_at").defaultNow(),
-});
+export const auditLog = pgTable(
+  "audit_log",
+  {
+    id: serial("id").primaryKey(),
+    tableName: text("table_name").notNull(),
+    recordId: text("record_id").notNull(),
+    action: text("action").notNull(),
+    oldValues: jsonb("old_values"),
+    newValues: jsonb("new_values"),
+    userId: integer("user_id").references(() => users.id),
+    ipAddress: text("ip_address"),
+    userAgent: text("user_agent"),
+    createdAt: timestamp("created_at").defaultNow(),
+    // Hash chain: SHA-256 of the previous entry's hash and this entry.
+    prevHash: text("prev_hash"),
+    entryHash: text("entry_hash"),
+  },
+  (table) => ({
+    prevHash: uniqueIndex("idx_audit_log_prev_hash").on(table.prevHash),
+    userCreated: index("idx_audit_log_user_created").on(
+      table.userId,
+      table.createdAt,
+    ),
+    createdAt: index("idx_audit_log_created_at").on(table.createdAt),
+  }),
+);
 
 export const usersRelations = relations(users, ({ many }) => ({
   auditLog: many(auditLog),
+  identities: many(userIdentities),
+  sessions: many(authSessions),
+}));
+
+export const userIdentitiesRelations = relations(userIdentities, ({ one }) => ({
+  user: one(users, {
+    fields: [userIdentities.userId],
+    references: [users.id],
+  }),
+}));
+
+export const authSessionsRelations = relations(
+  authSessions,
+  ({ one, many }) => ({
+    user: one(users, {
+      fields: [authSessions.userId],
+      references: [users.id],
+    }),
+    refreshTokens: many(refreshTokens),
+  }),
+);
+
+export const refreshTokensRelations = relations(refreshTokens, ({ one }) => ({
+  session: one(authSessions, {
+    fields: [refreshTokens.sessionId],
+    references: [authSessions.id],
+  }),
 }));
 
 export const pilgrimsRelations = relations(pilgrims, ({ many }) => ({
@@ -282,6 +408,8 @@ export const insertAuditLogSchema = createInsertSchema(auditLog).omit({
 
 export type User = typeof users.$inferSelect;
 export type InsertUser = z.infer<typeof insertUserSchema>;
+export type UserIdentity = typeof userIdentities.$inferSelect;
+export type AuthSession = typeof authSessions.$inferSelect;
 export type Pilgrim = typeof pilgrims.$inferSelect;
 export type InsertPilgrim = z.infer<typeof insertPilgrimSchema>;
 export type Booking = typeof bookings.$inferSelect;
@@ -0,0 +1,127 @@
+use serde::{Deserialize, Serialize};
+
+/// Every key the gateway issues starts with this, so leaked keys are easy to
+/// spot in logs and by secret scanners.
+pub const API_KEY_PREFIX: &str = "amc_";
+
+/// `amc_{id}_{secret}`: the id is public and names the key's record, the
+/// secret is only ever seen by its holder.
+pub fn format_api_key(id: &str, secret: &str) -> String {
+    format!("{API_KEY_PREFIX}{id}_{secret}")
+}
+
+/// Splits a presented key into its id and secret, or `None` when it is not
+/// one of ours. Ids are lowercase hex, so the first `_` ends them.
+pub fn parse_api_key(key: &str) -> Option<(&str, &str)> {
+    let (id, secret) = key.trim().strip_prefix(API_KEY_PREFIX)?.split_once('_')?;
+    let hex = |s: &str| s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'));
+    (!id.is_empty() && hex(id) && secret.len() >= 32).then_some((id, secret))
+}
+
+/// A key as stored by the gateway. Only the SHA-256 of the full key is kept.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiKeyRecord {
+    pub id: String,
+    /// Who holds it, e.g. `reception-kiosk-1`.
+    pub name: String,
+    pub hash: String,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    #[serde(default)]
+    pub roles: Vec<String>,
+    /// Unix seconds.
+    pub created_at: u64,
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+    #[serde(default)]
+    pub revoked_at: Option<u64>,
+    #[serde(default)]
+    pub rotated_at: Option<u64>,
+    /// Hash of the key replaced by the last rotation, still accepted until
+    /// `previous_expires_at` so holders can switch over.
+    #[serde(default)]
+    pub previous_hash: Option<String>,
+    #[serde(default)]
+    pub previous_expires_at: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ApiKeyError {
+    Unknown,
+    Revoked,
+    Expired,
+}
+
+impl ApiKeyError {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Unknown => "Unknown API key",
+            Self::Revoked => "API key revoked",
+            Self::Expired => "API key expired",
+        }
+    }
+}
+
+/// Which of a record's keys was presented.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ApiKeyMatch {
+    Current,
+    /// The key replaced by the last rotation, inside its grace period.
+    Previous,
+}
+
+impl ApiKeyRecord {
+    /// Checks the hash of a presented key against the record at `now`.
+    pub fn check(&self, presented_hash: &str, now: u64) -> Result<ApiKeyMatch, ApiKeyError> {
+        let matched = if constant_time_eq(&self.hash, presented_hash) {
+            ApiKeyMatch::Current
+        } else if self.previous_hash.as_deref().is_some_and(|previous| {
+            constant_time_eq(previous, presented_hash)
+                && self.previous_expires_at.is_some_and(|until| now < until)
+        }) {
+            ApiKeyMatch::Previous
+        } else {
+            return Err(ApiKeyError::Unknown);
+        };
+
+        if self.revoked_at.is_some() {
+            return Err(ApiKeyError::Revoked);
+        }
+        if self.expires_at.is_some_and(|until| now >= until) {
+            return Err(ApiKeyError::Expired);
+        }
+        Ok(matched)
+    }
+
+    /// Replaces the key with `new_hash`, accepting the old one for another
+    /// `grace_seconds`. A rotation during a grace period ends it: only the
+    /// key being replaced stays valid.
+    pub fn rotate(&mut self, new_hash: String, now: u64, grace_seconds: u64) {
+        let previous = std::mem::replace(&mut self.hash, new_hash);
+        if grace_seconds > 0 {
+            self.previous_hash = Some(previous);
+            self.previous_expires_at = Some(now + grace_seconds);
+        } else {
+            self.previous_hash = None;
+            self.previous_expires_at = None;
+        }
+        self.rotated_at = Some(now);
+    }
+
+    pub fn revoke(&mut self, now: u64) {
+        self.revoked_at.get_or_insert(now);
+    }
+
+    pub fn is_active(&self, now: u64) -> bool {
+        self.revoked_at.is_none() && self.expires_at.is_none_or(|until| now < until)
+    }
+}
+
+/// Compares two strings without stopping at the first difference.
+pub fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0u8, |diff, (x, y)| diff | (x ^ y))
+            == 0
+}
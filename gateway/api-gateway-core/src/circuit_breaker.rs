@@ -0,0 +1,222 @@
+/// A circuit breaker's state, stored in Redis as its `code`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CircuitState {
+    Closed,
+    Open,
+    /// Letting up to `half_open_max` trial requests through.
+    HalfOpen,
+}
+
+impl CircuitState {
+    pub fn code(self) -> i64 {
+        match self {
+            Self::Closed => 0,
+            Self::Open => 1,
+            Self::HalfOpen => 2,
+        }
+    }
+
+    pub fn from_code(code: i64) -> Option<Self> {
+        match code {
+            0 => Some(Self::Closed),
+            1 => Some(Self::Open),
+            2 => Some(Self::HalfOpen),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Closed => "closed",
+            Self::Open => "open",
+            Self::HalfOpen => "half_open",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BreakerSettings {
+    /// Length of the rolling window the error rate is measured over.
+    pub window_seconds: u64,
+    /// Requests the window must hold before the error rate can trip it.
+    pub min_requests: u64,
+    pub failure_rate_percent: u64,
+    pub open_seconds: u64,
+    /// Trial requests let through while half-open; that many successes in a
+    /// row close the breaker.
+    pub half_open_max: u64,
+}
+
+impl BreakerSettings {
+    /// The window is kept as ten buckets so it rolls without storing every
+    /// request.
+    pub fn bucket_seconds(&self) -> u64 {
+        (self.window_seconds / 10).max(1)
+    }
+
+    /// `ARGV` for [`BREAKER_ADMIT_SCRIPT`].
+    pub fn admit_args(&self) -> Vec<String> {
+        vec![
+            self.open_seconds.to_string(),
+            self.half_open_max.to_string(),
+        ]
+    }
+
+    /// `ARGV` for [`BREAKER_RECORD_SCRIPT`].
+    pub fn record_args(&self, failed: bool) -> Vec<String> {
+        vec![
+            u8::from(failed).to_string(),
+            self.window_seconds.to_string(),
+            self.bucket_seconds().to_string(),
+            self.min_requests.to_string(),
+            self.failure_rate_percent.to_string(),
+            self.half_open_max.to_string(),
+        ]
+    }
+}
+
+/// Whether an upstream status counts against the breaker. Timeouts and
+/// connection errors reach it as the gateway's own 504 and 502.
+pub fn is_failure_status(status: u16) -> bool {
+    status >= 500
+}
+
+/// Whether a window holding `requests`, `failures` of them failed, trips a
+/// closed breaker. Mirrors the check in [`BREAKER_RECORD_SCRIPT`].
+pub fn should_trip(requests: u64, failures: u64, settings: &BreakerSettings) -> bool {
+    requests > 0
+        && requests >= settings.min_requests
+        && failures * 100 >= requests * settings.failure_rate_percent
+}
+
+/// A change of state, reported so it can be logged and published.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BreakerTransition {
+    pub from: CircuitState,
+    pub to: CircuitState,
+}
+
+impl BreakerTransition {
+    fn from_codes(from: i64, to: i64) -> Option<Self> {
+        let (from, to) = (CircuitState::from_code(from)?, CircuitState::from_code(to)?);
+        (from != to).then_some(Self { from, to })
+    }
+}
+
+/// Reply of [`BREAKER_ADMIT_SCRIPT`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BreakerAdmission {
+    pub allowed: bool,
+    pub state: CircuitState,
+    pub retry_after_seconds: u64,
+    pub transition: Option<BreakerTransition>,
+}
+
+impl BreakerAdmission {
+    pub fn from_reply(reply: &[i64]) -> Option<Self> {
+        let [allowed, previous, state, retry_after] = reply else {
+            return None;
+        };
+        Some(Self {
+            allowed: *allowed == 1,
+            state: CircuitState::from_code(*state)?,
+            retry_after_seconds: u64::try_from(*retry_after).unwrap_or(0),
+            transition: BreakerTransition::from_codes(*previous, *state),
+        })
+    }
+}
+
+/// Reply of [`BREAKER_RECORD_SCRIPT`]: the transition the outcome caused, if
+/// any.
+pub fn record_transition(reply: &[i64]) -> Option<BreakerTransition> {
+    let [previous, state] = reply else {
+        return None;
+    };
+    BreakerTransition::from_codes(*previous, *state)
+}
+
+/// Decides whether a request may go upstream. `KEYS[1]` is the breaker's
+/// state hash; `ARGV` is `{open_seconds, half_open_max}`. Replies
+/// `{allowed, previous_state, state, retry_after}`.
+///
+/// An open breaker turns half-open once `open_seconds` have passed. Trial
+/// slots whose requests never reported back are reclaimed after another
+/// `open_seconds`.
+pub const BREAKER_ADMIT_SCRIPT: &str = r"
+if redis.replicate_commands then redis.replicate_commands() end
+local now = tonumber(redis.call('TIME')[1])
+local open_seconds, half_open_max = tonumber(ARGV[1]), tonumber(ARGV[2])
+local state = tonumber(redis.call('HGET', KEYS[1], 'state') or '0')
+local previous = state
+local changed_at = tonumber(redis.call('HGET', KEYS[1], 'changed_at') or '0')
+if state == 1 then
+  if now < changed_at + open_seconds then
+    return {0, previous, 1, changed_at + open_seconds - now}
+  end
+  state = 2
+  changed_at = now
+  redis.call('HSET', KEYS[1], 'state', 2, 'changed_at', now, 'probes', 0, 'successes', 0)
+end
+if state == 2 then
+  local probes = tonumber(redis.call('HGET', KEYS[1], 'probes') or '0')
+  if probes >= half_open_max and now >= changed_at + open_seconds then
+    probes = 0
+    redis.call('HSET', KEYS[1], 'changed_at', now, 'probes', 0)
+  end
+  if probes >= half_open_max then return {0, previous, 2, 1} end
+  redis.call('HINCRBY', KEYS[1], 'probes', 1)
+end
+return {1, previous, state, 0}
+";
+
+/// Records an upstream outcome. `KEYS` are the breaker's state hash and its
+/// window hash; `ARGV` is `{failed, window_seconds, bucket_seconds,
+/// min_requests, failure_rate_percent, half_open_max}`. Replies
+/// `{previous_state, state}`.
+pub const BREAKER_RECORD_SCRIPT: &str = r"
+if redis.replicate_commands then redis.replicate_commands() end
+local now = tonumber(redis.call('TIME')[1])
+local failed = ARGV[1] == '1'
+local window, bucket_len = tonumber(ARGV[2]), tonumber(ARGV[3])
+local state = tonumber(redis.call('HGET', KEYS[1], 'state') or '0')
+local function trip()
+  redis.call('HSET', KEYS[1], 'state', 1, 'changed_at', now)
+  redis.call('HDEL', KEYS[1], 'probes', 'successes')
+  redis.call('DEL', KEYS[2])
+end
+if state == 1 then return {1, 1} end
+if state == 2 then
+  if failed then
+    trip()
+    return {2, 1}
+  end
+  if redis.call('HINCRBY', KEYS[1], 'successes', 1) >= tonumber(ARGV[6]) then
+    redis.call('HSET', KEYS[1], 'state', 0, 'changed_at', now)
+    redis.call('HDEL', KEYS[1], 'probes', 'successes')
+    return {2, 0}
+  end
+  return {2, 2}
+end
+local bucket = math.floor(now / bucket_len)
+redis.call('HINCRBY', KEYS[2], bucket .. ':n', 1)
+if failed then redis.call('HINCRBY', KEYS[2], bucket .. ':f', 1) end
+redis.call('EXPIRE', KEYS[2], window + bucket_len)
+local oldest = math.floor((now - window) / bucket_len)
+local requests, failures = 0, 0
+local fields = redis.call('HGETALL', KEYS[2])
+for i = 1, #fields, 2 do
+  local b, kind = string.match(fields[i], '^(%d+):(%a)$')
+  if b == nil or tonumber(b) <= oldest then
+    redis.call('HDEL', KEYS[2], fields[i])
+  elseif kind == 'n' then
+    requests = requests + tonumber(fields[i + 1])
+  else
+    failures = failures + tonumber(fields[i + 1])
+  end
+end
+if requests >= tonumber(ARGV[4]) and failures * 100 >= requests * tonumber(ARGV[5]) then
+  trip()
+  return {0, 1}
+end
+return {0, 0}
+";
@@ -0,0 +1,512 @@
+use anyhow::{bail, Result};
+use regex::Regex;
+use serde_json::{Map, Value};
+
+/// Keywords that only document the schema.
+const ANNOTATIONS: [&str; 7] = [
+    "$schema",
+    "$id",
+    "$comment",
+    "title",
+    "description",
+    "default",
+    "examples",
+];
+
+/// One failed assertion, located by a JSON Pointer (RFC 6901) into the
+/// instance.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SchemaViolation {
+    pub pointer: String,
+    pub keyword: &'static str,
+    pub message: String,
+}
+
+/// Refuses schemas [`validate`] cannot enforce: keywords outside the
+/// supported subset (`$ref` included), keywords of the wrong type and
+/// patterns that do not compile.
+pub fn check_schema(schema: &Value) -> Result<()> {
+    check_at(schema, "#")
+}
+
+fn check_at(schema: &Value, at: &str) -> Result<()> {
+    let Some(obj) = schema.as_object() else {
+        if schema.is_boolean() {
+            return Ok(());
+        }
+        bail!("{at}: a schema must be an object or a boolean");
+    };
+    for (keyword, value) in obj {
+        let here = format!("{at}/{}", escape_pointer(keyword));
+        match keyword.as_str() {
+            "properties" => {
+                let Some(props) = value.as_object() else {
+                    bail!("{here}: must be an object");
+                };
+                for (name, sub) in props {
+                    check_at(sub, &format!("{here}/{}", escape_pointer(name)))?;
+                }
+            }
+            "additionalProperties" | "items" => check_at(value, &here)?,
+            "allOf" => {
+                let Some(subs) = value.as_array() else {
+                    bail!("{here}: must be an array");
+                };
+                for (i, sub) in subs.iter().enumerate() {
+                    check_at(sub, &format!("{here}/{i}"))?;
+                }
+            }
+            "required" | "enum" => {
+                if !value.is_array() {
+                    bail!("{here}: must be an array");
+                }
+            }
+            "type" => {
+                let names: Vec<&Value> = match value {
+                    Value::Array(names) => names.iter().collect(),
+                    other => vec![other],
+                };
+                for name in names {
+                    if !name.as_str().is_some_and(is_type_name) {
+                        bail!("{here}: unknown type {name}");
+                    }
+                }
+            }
+            "pattern" => {
+                let Some(pattern) = value.as_str() else {
+                    bail!("{here}: must be a string");
+                };
+                if Regex::new(pattern).is_err() {
+                    bail!("{here}: invalid pattern {pattern:?}");
+                }
+            }
+            "minLength" | "maxLength" | "minItems" | "maxItems" | "minProperties"
+            | "maxProperties" => {
+                if !value.is_u64() {
+                    bail!("{here}: must be a non-negative integer");
+                }
+            }
+            "minimum" | "maximum" | "exclusiveMinimum" | "exclusiveMaximum" => {
+                if !value.is_number() {
+                    bail!("{here}: must be a number");
+                }
+            }
+            "format" | "const" => {}
+            other if ANNOTATIONS.contains(&other) => {}
+            other => bail!("{here}: unsupported keyword {other:?}"),
+        }
+    }
+    Ok(())
+}
+
+/// Every violation of `schema` by `instance`, in document order. Schemas are
+/// expected to have passed [`check_schema`].
+pub fn validate(schema: &Value, instance: &Value) -> Vec<SchemaViolation> {
+    let mut out = Vec::new();
+    validate_at(schema, instance, "", &mut out);
+    out
+}
+
+fn validate_at(schema: &Value, instance: &Value, pointer: &str, out: &mut Vec<SchemaViolation>) {
+    let fail = |keyword: &'static str, message: String| SchemaViolation {
+        pointer: pointer.to_string(),
+        keyword,
+        message,
+    };
+    let obj = match schema {
+        Value::Bool(false) => return out.push(fail("false", "is not allowed".to_string())),
+        Value::Object(obj) => obj,
+        _ => return,
+    };
+
+    if let Some(expected) = obj.get("type") {
+        let names: Vec<&str> = match expected {
+            Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+            other => other.as_str().into_iter().collect(),
+        };
+        if !names.iter().any(|name| has_type(instance, name)) {
+            // The remaining keywords would only repeat the mismatch.
+            return out.push(fail(
+                "type",
+                format!("must be {}, not {}", names.join(" or "), type_of(instance)),
+            ));
+        }
+    }
+    if let Some(allowed) = obj.get("enum").and_then(Value::as_array) {
+        if !allowed.contains(instance) {
+            let list: Vec<String> = allowed.iter().map(Value::to_string).collect();
+            out.push(fail("enum", format!("must be one of {}", list.join(", "))));
+        }
+    }
+    if let Some(expected) = obj.get("const") {
+        if expected != instance {
+            out.push(fail("const", format!("must be {expected}")));
+        }
+    }
+
+    match instance {
+        Value::String(s) => out.extend(check_string(obj, s).into_iter().map(|(k, m)| fail(k, m))),
+        Value::Number(n) => {
+            if let Some(n) = n.as_f64() {
+                out.extend(check_number(obj, n).into_iter().map(|(k, m)| fail(k, m)));
+            }
+        }
+        _ => {}
+    }
+
+    if let Value::Array(items) = instance {
+        let len = items.len() as u64;
+        if let Some(min) = obj.get("minItems").and_then(Value::as_u64) {
+            if len < min {
+                out.push(fail("minItems", format!("must have at least {min} items")));
+            }
+        }
+        if let Some(max) = obj.get("maxItems").and_then(Value::as_u64) {
+            if len > max {
+                out.push(fail("maxItems", format!("must have at most {max} items")));
+            }
+        }
+    }
+
+    if let Value::Object(fields) = instance {
+        let len = fields.len() as u64;
+        if let Some(min) = obj.get("minProperties").and_then(Value::as_u64) {
+            if len < min {
+                out.push(fail(
+                    "minProperties",
+                    format!("must have at least {min} properties"),
+                ));
+            }
+        }
+        if let Some(max) = obj.get("maxProperties").and_then(Value::as_u64) {
+            if len > max {
+                out.push(fail(
+                    "maxProperties",
+                    format!("must have at most {max} properties"),
+                ));
+            }
+        }
+        for name in obj
+            .get("required")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+        {
+            if !fields.contains_key(name) {
+                out.push(SchemaViolation {
+                    pointer: format!("{pointer}/{}", escape_pointer(name)),
+                    keyword: "required",
+                    message: "is required".to_string(),
+                });
+            }
+        }
+        validate_properties(obj, fields, pointer, out);
+    }
+
+    if let (Value::Array(items), Some(item_schema)) = (instance, obj.get("items")) {
+        for (i, item) in items.iter().enumerate() {
+            validate_at(item_schema, item, &format!("{pointer}/{i}"), out);
+        }
+    }
+    for sub in obj
+        .get("allOf")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        validate_at(sub, instance, pointer, out);
+    }
+}
+
+fn validate_properties(
+    schema: &Map<String, Value>,
+    fields: &Map<String, Value>,
+    pointer: &str,
+    out: &mut Vec<SchemaViolation>,
+) {
+    let properties = schema.get("properties").and_then(Value::as_object);
+    for (name, value) in fields {
+        let here = format!("{pointer}/{}", escape_pointer(name));
+        match (
+            properties.and_then(|p| p.get(name)),
+            schema.get("additionalProperties"),
+        ) {
+            (Some(sub), _) => validate_at(sub, value, &here, out),
+            (None, Some(Value::Bool(false))) => out.push(SchemaViolation {
+                pointer: here,
+                keyword: "additionalProperties",
+                message: "is not an allowed property".to_string(),
+            }),
+            (None, Some(sub)) => validate_at(sub, value, &here, out),
+            (None, None) => {}
+        }
+    }
+}
+
+fn check_string(obj: &Map<String, Value>, s: &str) -> Vec<(&'static str, String)> {
+    let mut failed = Vec::new();
+    let len = s.chars().count() as u64;
+    if let Some(min) = obj.get("minLength").and_then(Value::as_u64) {
+        if len < min {
+            failed.push(("minLength", format!("must be at least {min} characters")));
+        }
+    }
+    if let Some(max) = obj.get("maxLength").and_then(Value::as_u64) {
+        if len > max {
+            failed.push(("maxLength", format!("must be at most {max} characters")));
+        }
+    }
+    if let Some(pattern) = obj.get("pattern").and_then(Value::as_str) {
+        if Regex::new(pattern).is_ok_and(|re| !re.is_match(s)) {
+            failed.push(("pattern", format!("must match {pattern}")));
+        }
+    }
+    if let Some(format) = obj.get("format").and_then(Value::as_str) {
+        if !matches_format(format, s) {
+            failed.push(("format", format!("must be a valid {format}")));
+        }
+    }
+    failed
+}
+
+fn check_number(obj: &Map<String, Value>, n: f64) -> Vec<(&'static str, String)> {
+    let bound = |name: &str| obj.get(name).and_then(Value::as_f64);
+    let mut failed = Vec::new();
+    if let Some(min) = bound("minimum") {
+        if n < min {
+            failed.push(("minimum", format!("must be at least {min}")));
+        }
+    }
+    if let Some(max) = bound("maximum") {
+        if n > max {
+            failed.push(("maximum", format!("must be at most {max}")));
+        }
+    }
+    if let Some(min) = bound("exclusiveMinimum") {
+        if n <= min {
+            failed.push(("exclusiveMinimum", format!("must be greater than {min}")));
+        }
+    }
+    if let Some(max) = bound("exclusiveMaximum") {
+        if n >= max {
+            failed.push(("exclusiveMaximum", format!("must be less than {max}")));
+        }
+    }
+    failed
+}
+
+/// Formats worth checking at the edge. Others are annotations, as the spec
+/// allows, and always pass.
+fn matches_format(format: &str, s: &str) -> bool {
+    match format {
+        "date" => is_date(s),
+        "date-time" => s
+            .split_once(['T', 't'])
+            .is_some_and(|(date, time)| is_date(date) && is_time(time)),
+        "email" => s.split_once('@').is_some_and(|(local, domain)| {
+            !local.is_empty()
+                && !domain.starts_with('.')
+                && domain.contains('.')
+                && !domain.ends_with('.')
+                && !s.chars().any(|c| c.is_whitespace())
+                && !domain.contains('@')
+        }),
+        "uuid" => {
+            s.len() == 36
+                && s.char_indices().all(|(i, c)| match i {
+                    8 | 13 | 18 | 23 => c == '-',
+                    _ => c.is_ascii_hexdigit(),
+                })
+        }
+        _ => true,
+    }
+}
+
+fn is_date(s: &str) -> bool {
+    let parts: Vec<&str> = s.split('-').collect();
+    let [year, month, day] = parts.as_slice() else {
+        return false;
+    };
+    if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+        return false;
+    }
+    let (Ok(year), Ok(month), Ok(day)) = (
+        year.parse::<u32>(),
+        month.parse::<u32>(),
+        day.parse::<u32>(),
+    ) else {
+        return false;
+    };
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => return false,
+    };
+    (1..=days).contains(&day)
+}
+
+/// `hh:mm:ss[.frac](Z|±hh:mm)`
+fn is_time(s: &str) -> bool {
+    let (clock, offset) = if let Some(clock) = s.strip_suffix(['Z', 'z']) {
+        (clock, None)
+    } else if let Some(i) = s.rfind(['+', '-']) {
+        (&s[..i], Some(&s[i + 1..]))
+    } else {
+        return false;
+    };
+    let clock = clock.split_once('.').map_or(clock, |(hms, frac)| {
+        if !frac.is_empty() && frac.bytes().all(|b| b.is_ascii_digit()) {
+            hms
+        } else {
+            ""
+        }
+    });
+    let hms = |t: &str, parts: usize, maxima: [u32; 3]| {
+        let fields: Vec<&str> = t.split(':').collect();
+        fields.len() == parts
+            && fields.iter().zip(maxima).all(|(f, max)| {
+                f.len() == 2
+                    && f.bytes().all(|b| b.is_ascii_digit())
+                    && f.parse::<u32>().is_ok_and(|v| v <= max)
+            })
+    };
+    hms(clock, 3, [23, 59, 60]) && offset.is_none_or(|o| hms(o, 2, [23, 59, 0]))
+}
+
+fn is_type_name(name: &str) -> bool {
+    matches!(
+        name,
+        "null" | "boolean" | "object" | "array" | "number" | "integer" | "string"
+    )
+}
+
+fn has_type(instance: &Value, name: &str) -> bool {
+    match name {
+        "null" => instance.is_null(),
+        "boolean" => instance.is_boolean(),
+        "object" => instance.is_object(),
+        "array" => instance.is_array(),
+        "number" => instance.is_number(),
+        "integer" => {
+            instance.is_i64()
+                || instance.is_u64()
+                || instance.as_f64().is_some_and(|n| n.fract() == 0.0)
+        }
+        "string" => instance.is_string(),
+        _ => false,
+    }
+}
+
+fn type_of(instance: &Value) -> &'static str {
+    match instance {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Object(_) => "object",
+        Value::Array(_) => "array",
+        Value::Number(n) if n.is_i64() || n.is_u64() => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+    }
+}
+
+/// The query string as a JSON object to validate. Values are decoded and
+/// converted to the type the schema declares for their parameter, so
+/// `?limit=10` meets `"type": "integer"`; values that do not convert stay
+/// strings and fail validation. Repeated parameters become arrays.
+pub fn query_instance(query: &str, schema: &Value) -> Value {
+    let properties = schema.get("properties").and_then(Value::as_object);
+    let mut grouped: Vec<(String, Vec<String>)> = Vec::new();
+    for pair in query.split('&').filter(|p| !p.is_empty()) {
+        let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let (name, value) = (percent_decode(name), percent_decode(value));
+        match grouped.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, values)) => values.push(value),
+            None => grouped.push((name, vec![value])),
+        }
+    }
+
+    let mut out = Map::new();
+    for (name, values) in grouped {
+        let declared = properties.and_then(|p| p.get(&name));
+        let value = if declared.is_some_and(|s| has_declared_type(s, "array")) {
+            let items = declared.and_then(|s| s.get("items"));
+            Value::Array(values.iter().map(|v| coerce(v, items)).collect())
+        } else if let [single] = values.as_slice() {
+            coerce(single, declared)
+        } else {
+            Value::Array(values.iter().map(|v| coerce(v, declared)).collect())
+        };
+        out.insert(name, value);
+    }
+    Value::Object(out)
+}
+
+fn coerce(raw: &str, schema: Option<&Value>) -> Value {
+    let Some(schema) = schema else {
+        return Value::String(raw.to_string());
+    };
+    if has_declared_type(schema, "integer") {
+        if let Ok(n) = raw.parse::<i64>() {
+            return Value::from(n);
+        }
+    }
+    if has_declared_type(schema, "number") {
+        if let Some(n) = raw
+            .parse::<f64>()
+            .ok()
+            .and_then(serde_json::Number::from_f64)
+        {
+            return Value::Number(n);
+        }
+    }
+    if has_declared_type(schema, "boolean") {
+        match raw {
+            "true" => return Value::Bool(true),
+            "false" => return Value::Bool(false),
+            _ => {}
+        }
+    }
+    Value::String(raw.to_string())
+}
+
+fn has_declared_type(schema: &Value, name: &str) -> bool {
+    match schema.get("type") {
+        Some(Value::String(t)) => t == name,
+        Some(Value::Array(types)) => types.iter().any(|t| t == name),
+        _ => false,
+    }
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let hex = |b: u8| char::from(b).to_digit(16);
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' => {
+                let digit = |at: usize| bytes.get(at).copied().and_then(hex);
+                match (digit(i + 1), digit(i + 2)) {
+                    (Some(hi), Some(lo)) => {
+                        decoded.push((hi * 16 + lo) as u8);
+                        i += 2;
+                    }
+                    _ => decoded.push(b'%'),
+                }
+            }
+            b => decoded.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Escapes a property name for use as a JSON Pointer token.
+pub fn escape_pointer(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
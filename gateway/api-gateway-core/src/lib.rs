@@ -2,19 +2,58 @@
 use serde::Deserialize;
 use std::collections::BTreeMap;
 
-#[derive(Clone, Debug, serde::Deserialize)]
+mod api_key;
+mod circuit_breaker;
+mod json_schema;
+mod metrics;
+mod rate_limit;
+mod response_cache;
+mod retry;
+mod routes;
+mod trace_context;
+
+pub use api_key::{
+    constant_time_eq, format_api_key, parse_api_key, ApiKeyError, ApiKeyMatch, ApiKeyRecord,
+    API_KEY_PREFIX,
+};
+pub use circuit_breaker::{
+    is_failure_status, record_transition, should_trip, BreakerAdmission, BreakerSettings,
+    BreakerTransition, CircuitState, BREAKER_ADMIT_SCRIPT, BREAKER_RECORD_SCRIPT,
+};
+pub use json_schema::{check_schema, escape_pointer, query_instance, validate, SchemaViolation};
+pub use metrics::{
+    metric_increments, redact_headers, render_prometheus, DURATION_BUCKETS_MS,
+    METRICS_RECORD_SCRIPT,
+};
+pub use rate_limit::{
+    client_ip, RateLimitAlgorithm, RateLimitDecision, RateLimitInputs, RateLimitKey, RateLimitKeys,
+};
+pub use response_cache::{
+    cache_state, event_matches, freshness, if_none_match, vary_fragment, CacheControl, CacheState,
+    Freshness,
+};
+pub use retry::{
+    backoff_delay, is_idempotent, is_retryable_status, parse_retry_after, retry_budget_allows,
+    retry_delay, AttemptOutcome, RetrySettings, RETRY_BUDGET_SCRIPT,
+};
+pub use routes::{RewriteRule, RouteConfig, RouteMatch, RouteTable};
+pub use trace_context::{hex_id, parse_tracestate, TraceParent};
+
+#[derive(Clone, Debug, Deserialize)]
 pub struct GatewayConfig {
     pub defaults: DefaultsConfig,
     pub services: BTreeMap<String, ServiceConfig>,
+    #[serde(default)]
+    pub routes: Vec<RouteConfig<Option<toml::Value>>>,
 }
 
-#[derive(Clone, Debug, serde::Deserialize)]
+#[derive(Clone, Debug, Deserialize)]
 pub struct DefaultsConfig {
     #[serde(default)]
     pub policy: Option<toml::Value>,
 }
 
-#[derive(Clone, Debug, serde::Deserialize)]
+#[derive(Clone, Debug, Deserialize)]
 pub struct ServiceConfig {
     pub url: String,
     #[serde(default = "Option::default")]
@@ -24,23 +63,13 @@ pub struct ServiceConfig {
 pub fn gateway_config_for_test(bytes: &[u8]) -> Result<GatewayConfig> {
     let text = std::str::from_utf8(bytes).context("Gateway config must be UTF-8")?;
     let cfg: GatewayConfig = toml::from_str(text).context("Failed to parse gateway TOML config")?;
+    route_table(&cfg)?;
     Ok(cfg)
 }
 
-pub fn rewrite_upstream_path_for_test(path: &str, service: &str) -> String {
-    if service == "auth-service" {
-        if let Some(rest) = path.strip_prefix("/api/auth") {
-            return format!("/api/auth{rest}");
-        }
-    }
-
-    if service == "location-service" {
-        if let Some(rest) = path.strip_prefix("/api/countries") {
-            return format!("/api/countries{rest}");
-        }
-    }
-
-    path.to_string()
+pub fn route_table(cfg: &GatewayConfig) -> Result<RouteTable<Option<toml::Value>>> {
+    RouteTable::compile(cfg.routes.clone(), |service| {
+        cfg.services.contains_key(service)
+    })
+    .context("Invalid gateway route table")
 }
-
-
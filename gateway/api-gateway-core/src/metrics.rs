@@ -0,0 +1,138 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// Upper bounds, in milliseconds, of the request duration histogram.
+pub const DURATION_BUCKETS_MS: [u64; 11] = [5, 10, 25, 50, 100, 250, 500, 1000, 2500, 5000, 10000];
+
+/// Headers whose values never reach the logs.
+const SENSITIVE_HEADERS: [&str; 6] = [
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "set-cookie",
+    "x-api-key",
+    "x-user-claims",
+];
+
+/// Increments one request adds to its service's counters. Durations are
+/// counted in the first bucket they fit; [`render_prometheus`] makes the
+/// buckets cumulative.
+pub fn metric_increments(status: u16, duration_ms: u64) -> Vec<(String, u64)> {
+    let bucket = DURATION_BUCKETS_MS
+        .iter()
+        .find(|le| duration_ms <= **le)
+        .map_or_else(|| "+Inf".to_string(), u64::to_string);
+    let mut increments = vec![
+        (format!("requests:{}xx", status / 100), 1),
+        (format!("bucket:{bucket}"), 1),
+        ("duration_ms_sum".to_string(), duration_ms),
+    ];
+    if status >= 500 {
+        increments.push(("errors".to_string(), 1));
+    }
+    increments
+}
+
+/// Redis script adding [`metric_increments`] to a service's hash. `KEYS` are
+/// the set of known services and the service's hash; `ARGV` is the service
+/// name followed by field/increment pairs.
+pub const METRICS_RECORD_SCRIPT: &str = r"
+redis.call('SADD', KEYS[1], ARGV[1])
+for i = 2, #ARGV, 2 do
+  redis.call('HINCRBY', KEYS[2], ARGV[i], ARGV[i + 1])
+end
+return 1
+";
+
+/// Renders per-service counters, as stored by [`METRICS_RECORD_SCRIPT`], in
+/// the Prometheus text exposition format.
+pub fn render_prometheus(services: &BTreeMap<String, BTreeMap<String, u64>>) -> String {
+    let mut out = String::new();
+
+    out.push_str("# HELP gateway_requests_total Requests proxied, by status class.\n");
+    out.push_str("# TYPE gateway_requests_total counter\n");
+    for (service, fields) in services {
+        for (field, value) in fields {
+            if let Some(code) = field.strip_prefix("requests:") {
+                let _ = writeln!(
+                    out,
+                    "gateway_requests_total{{service=\"{}\",code=\"{}\"}} {value}",
+                    escape_label(service),
+                    escape_label(code)
+                );
+            }
+        }
+    }
+
+    out.push_str("# HELP gateway_request_errors_total Requests answered with a 5xx status.\n");
+    out.push_str("# TYPE gateway_request_errors_total counter\n");
+    for (service, fields) in services {
+        let _ = writeln!(
+            out,
+            "gateway_request_errors_total{{service=\"{}\"}} {}",
+            escape_label(service),
+            fields.get("errors").copied().unwrap_or(0)
+        );
+    }
+
+    out.push_str("# HELP gateway_request_duration_seconds Time to answer a request.\n");
+    out.push_str("# TYPE gateway_request_duration_seconds histogram\n");
+    for (service, fields) in services {
+        let service = escape_label(service);
+        let mut cumulative = 0;
+        for le in DURATION_BUCKETS_MS {
+            cumulative += fields.get(&format!("bucket:{le}")).copied().unwrap_or(0);
+            let _ = writeln!(
+                out,
+                "gateway_request_duration_seconds_bucket{{service=\"{service}\",le=\"{}\"}} {cumulative}",
+                seconds(le)
+            );
+        }
+        cumulative += fields.get("bucket:+Inf").copied().unwrap_or(0);
+        let _ = writeln!(
+            out,
+            "gateway_request_duration_seconds_bucket{{service=\"{service}\",le=\"+Inf\"}} {cumulative}"
+        );
+        let sum_ms = fields.get("duration_ms_sum").copied().unwrap_or(0);
+        let _ = writeln!(
+            out,
+            "gateway_request_duration_seconds_sum{{service=\"{service}\"}} {}",
+            seconds(sum_ms)
+        );
+        let _ = writeln!(
+            out,
+            "gateway_request_duration_seconds_count{{service=\"{service}\"}} {cumulative}"
+        );
+    }
+
+    out
+}
+
+/// Headers as they may be logged: names lowercased, values of credentials
+/// and of any name in `extra` replaced by `[REDACTED]`.
+pub fn redact_headers<'a>(
+    headers: impl IntoIterator<Item = (&'a str, &'a str)>,
+    extra: &[String],
+) -> Vec<(String, String)> {
+    headers
+        .into_iter()
+        .map(|(name, value)| {
+            let name = name.to_ascii_lowercase();
+            let sensitive = SENSITIVE_HEADERS.contains(&name.as_str())
+                || extra.iter().any(|e| e.eq_ignore_ascii_case(&name));
+            let value = if sensitive { "[REDACTED]" } else { value };
+            (name, value.to_string())
+        })
+        .collect()
+}
+
+fn seconds(ms: u64) -> String {
+    format!("{}", ms as f64 / 1000.0)
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
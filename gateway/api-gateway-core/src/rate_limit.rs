@@ -0,0 +1,263 @@
+use serde::{Deserialize, Serialize};
+
+/// How requests are counted against `max_requests` per `window_seconds`.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitAlgorithm {
+    /// One counter per window. Cheap, but a client can spend two windows'
+    /// worth of requests around a window boundary.
+    #[default]
+    Fixed,
+    /// Timestamps of the requests in the last window. Exact, at the cost of
+    /// one sorted-set entry per request.
+    SlidingLog,
+    /// Generic cell rate algorithm: a token bucket refilled at
+    /// `max_requests / window_seconds`, holding at most `burst` tokens.
+    Gcra,
+}
+
+impl RateLimitAlgorithm {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RateLimitAlgorithm::Fixed => "fixed",
+            RateLimitAlgorithm::SlidingLog => "sliding_log",
+            RateLimitAlgorithm::Gcra => "gcra",
+        }
+    }
+
+    /// Redis script implementing the algorithm. Each takes the bucket as
+    /// `KEYS[1]`, reads the clock from Redis so gateway instances agree, and
+    /// replies `{allowed, remaining, reset_seconds, retry_after_seconds}`.
+    pub fn script(self) -> &'static str {
+        match self {
+            RateLimitAlgorithm::Fixed => FIXED_WINDOW_SCRIPT,
+            RateLimitAlgorithm::SlidingLog => SLIDING_LOG_SCRIPT,
+            RateLimitAlgorithm::Gcra => GCRA_SCRIPT,
+        }
+    }
+
+    /// `ARGV` for [`Self::script`]. `member` must be unique per request.
+    pub fn script_args(
+        self,
+        limit: u64,
+        window_seconds: u64,
+        burst: u64,
+        member: &str,
+    ) -> Vec<String> {
+        match self {
+            RateLimitAlgorithm::Fixed => vec![window_seconds.to_string(), limit.to_string()],
+            RateLimitAlgorithm::SlidingLog => vec![
+                (window_seconds * 1000).to_string(),
+                limit.to_string(),
+                member.to_string(),
+            ],
+            RateLimitAlgorithm::Gcra => vec![
+                (window_seconds * 1000).to_string(),
+                limit.to_string(),
+                burst.max(1).to_string(),
+            ],
+        }
+    }
+}
+
+const FIXED_WINDOW_SCRIPT: &str = r"
+local current = redis.call('INCR', KEYS[1])
+if current == 1 then redis.call('EXPIRE', KEYS[1], ARGV[1]) end
+local limit = tonumber(ARGV[2])
+local ttl = redis.call('TTL', KEYS[1])
+if ttl < 0 then ttl = tonumber(ARGV[1]) end
+if current > limit then return {0, 0, ttl, ttl} end
+return {1, limit - current, ttl, 0}
+";
+
+const SLIDING_LOG_SCRIPT: &str = r"
+if redis.replicate_commands then redis.replicate_commands() end
+local t = redis.call('TIME')
+local now = tonumber(t[1]) * 1000 + math.floor(tonumber(t[2]) / 1000)
+local window, limit = tonumber(ARGV[1]), tonumber(ARGV[2])
+redis.call('ZREMRANGEBYSCORE', KEYS[1], '-inf', now - window)
+local count = redis.call('ZCARD', KEYS[1])
+local allowed = 0
+if count < limit then
+  redis.call('ZADD', KEYS[1], now, ARGV[3])
+  redis.call('PEXPIRE', KEYS[1], window)
+  count = count + 1
+  allowed = 1
+end
+local oldest = redis.call('ZRANGE', KEYS[1], 0, 0, 'WITHSCORES')
+local reset = 0
+if oldest[2] then reset = math.ceil((tonumber(oldest[2]) + window - now) / 1000) end
+if allowed == 1 then return {1, limit - count, reset, 0} end
+return {0, 0, reset, reset}
+";
+
+const GCRA_SCRIPT: &str = r"
+if redis.replicate_commands then redis.replicate_commands() end
+local t = redis.call('TIME')
+local now = tonumber(t[1]) * 1000 + math.floor(tonumber(t[2]) / 1000)
+local emission = tonumber(ARGV[1]) / tonumber(ARGV[2])
+local tolerance = emission * tonumber(ARGV[3])
+local tat = tonumber(redis.call('GET', KEYS[1])) or now
+if tat < now then tat = now end
+local new_tat = tat + emission
+local allow_at = new_tat - tolerance
+if allow_at > now then
+  return {0, 0, math.ceil((tat - now) / 1000), math.ceil((allow_at - now) / 1000)}
+end
+redis.call('SET', KEYS[1], math.ceil(new_tat), 'PX', math.ceil(new_tat - now))
+local remaining = math.floor((now + tolerance - new_tat) / emission)
+return {1, remaining, math.ceil((new_tat - now) / 1000), 0}
+";
+
+/// One component of a rate-limit bucket key.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitKey {
+    /// The token's subject; unauthenticated requests fall back to `ip`.
+    Sub,
+    /// The client address.
+    Ip,
+    /// The API key header, hashed before it reaches Redis.
+    ApiKey,
+    /// The matched route, so one route's traffic cannot exhaust another's.
+    Route,
+}
+
+/// `key = "sub"` or a composite such as `key = ["api_key", "route"]`.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(try_from = "OneOrMany")]
+pub struct RateLimitKeys(pub Vec<RateLimitKey>);
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(RateLimitKey),
+    Many(Vec<RateLimitKey>),
+}
+
+impl TryFrom<OneOrMany> for RateLimitKeys {
+    type Error = &'static str;
+
+    fn try_from(value: OneOrMany) -> Result<Self, Self::Error> {
+        match value {
+            OneOrMany::One(key) => Ok(Self(vec![key])),
+            OneOrMany::Many(keys) if keys.is_empty() => Err("rate limit key list is empty"),
+            OneOrMany::Many(keys) => Ok(Self(keys)),
+        }
+    }
+}
+
+impl Default for RateLimitKeys {
+    fn default() -> Self {
+        Self(vec![RateLimitKey::Sub])
+    }
+}
+
+/// What a request offers for building its bucket key.
+#[derive(Clone, Debug, Default)]
+pub struct RateLimitInputs<'a> {
+    pub subject: Option<&'a str>,
+    pub client_ip: Option<&'a str>,
+    /// Digest of the API key, never the key itself.
+    pub api_key_hash: Option<&'a str>,
+    pub route: &'a str,
+}
+
+impl RateLimitKeys {
+    /// The bucket a request is counted in, e.g. `sub=alice|route=POST /api/bookings`.
+    pub fn identity(&self, inputs: &RateLimitInputs<'_>) -> String {
+        let ip = || format!("ip={}", inputs.client_ip.unwrap_or("unknown"));
+        self.0
+            .iter()
+            .map(|key| match key {
+                RateLimitKey::Sub => inputs.subject.map_or_else(ip, |sub| format!("sub={sub}")),
+                RateLimitKey::Ip => ip(),
+                RateLimitKey::ApiKey => {
+                    format!("key={}", inputs.api_key_hash.unwrap_or("none"))
+                }
+                RateLimitKey::Route => format!("route={}", inputs.route),
+            })
+            .collect::<Vec<_>>()
+            .join("|")
+    }
+}
+
+/// The client address: the peer address with its port removed, or the first
+/// `X-Forwarded-For` hop when the gateway sits behind a trusted proxy.
+pub fn client_ip(
+    forwarded_for: Option<&str>,
+    peer_addr: Option<&str>,
+    trust_forwarded_for: bool,
+) -> Option<String> {
+    if trust_forwarded_for {
+        let first = forwarded_for
+            .and_then(|v| v.split(',').next())
+            .map(str::trim);
+        if let Some(ip) = first.filter(|ip| !ip.is_empty()) {
+            return Some(ip.to_string());
+        }
+    }
+
+    let peer = peer_addr?.trim();
+    let host = if let Some(rest) = peer.strip_prefix('[') {
+        rest.split(']').next().unwrap_or(rest)
+    } else if peer.matches(':').count() == 1 {
+        peer.split(':').next().unwrap_or(peer)
+    } else {
+        peer
+    };
+    (!host.is_empty()).then(|| host.to_string())
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RateLimitDecision {
+    pub allowed: bool,
+    pub limit: u64,
+    pub window_seconds: u64,
+    pub remaining: u64,
+    pub reset_seconds: u64,
+    pub retry_after_seconds: u64,
+}
+
+impl RateLimitDecision {
+    /// Read a script reply; `None` if it is not the four integers expected.
+    pub fn from_reply(reply: &[i64], limit: u64, window_seconds: u64) -> Option<Self> {
+        let [allowed, remaining, reset, retry_after] = reply else {
+            return None;
+        };
+        let non_negative = |v: &i64| u64::try_from(*v).unwrap_or(0);
+        Some(Self {
+            allowed: *allowed == 1,
+            limit,
+            window_seconds,
+            remaining: non_negative(remaining),
+            reset_seconds: non_negative(reset),
+            retry_after_seconds: non_negative(retry_after).max(u64::from(*allowed != 1)),
+        })
+    }
+
+    /// `RateLimit-Policy` and `RateLimit` as in the IETF httpapi
+    /// rate-limit headers draft, plus `Retry-After` when rejected.
+    pub fn headers(&self, policy_name: &str) -> Vec<(&'static str, String)> {
+        let mut headers = vec![
+            (
+                "ratelimit-policy",
+                format!(
+                    "\"{policy_name}\";q={};w={}",
+                    self.limit, self.window_seconds
+                ),
+            ),
+            (
+                "ratelimit",
+                format!(
+                    "\"{policy_name}\";r={};t={}",
+                    self.remaining, self.reset_seconds
+                ),
+            ),
+        ];
+        if !self.allowed {
+            headers.push(("retry-after", self.retry_after_seconds.to_string()));
+        }
+        headers
+    }
+}
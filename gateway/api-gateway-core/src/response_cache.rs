@@ -0,0 +1,124 @@
+/// The `Cache-Control` directives a shared cache acts on.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CacheControl {
+    pub no_store: bool,
+    pub no_cache: bool,
+    pub private: bool,
+    pub max_age: Option<u64>,
+    pub s_maxage: Option<u64>,
+    pub stale_while_revalidate: Option<u64>,
+}
+
+impl CacheControl {
+    pub fn parse(value: &str) -> Self {
+        let mut cc = Self::default();
+        for directive in value.split(',') {
+            let (name, arg) = match directive.split_once('=') {
+                Some((name, arg)) => (name, Some(arg.trim().trim_matches('"'))),
+                None => (directive, None),
+            };
+            let seconds = arg.and_then(|a| a.parse::<u64>().ok());
+            match name.trim().to_ascii_lowercase().as_str() {
+                "no-store" => cc.no_store = true,
+                "no-cache" => cc.no_cache = true,
+                // `private="set-cookie"` only restricts some fields, but a
+                // cache that keeps whole responses cannot honour that.
+                "private" => cc.private = true,
+                "max-age" => cc.max_age = seconds,
+                "s-maxage" => cc.s_maxage = seconds,
+                "stale-while-revalidate" => cc.stale_while_revalidate = seconds,
+                _ => {}
+            }
+        }
+        cc
+    }
+}
+
+/// How long a stored response is served.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Freshness {
+    pub ttl_seconds: u64,
+    /// After `ttl_seconds`, how long the response may still be served while
+    /// one request refreshes it.
+    pub stale_seconds: u64,
+}
+
+/// Whether a shared cache may store a response and for how long. The
+/// upstream's `s-maxage`/`max-age` win over the policy's `default_ttl`;
+/// `no-store`, `private` and `no-cache` responses are not stored.
+pub fn freshness(
+    status: u16,
+    cache_control: Option<&str>,
+    default_ttl: u64,
+    default_stale: u64,
+) -> Option<Freshness> {
+    if !(200..300).contains(&status) {
+        return None;
+    }
+    let cc = cache_control.map(CacheControl::parse).unwrap_or_default();
+    if cc.no_store || cc.private || cc.no_cache {
+        return None;
+    }
+    let ttl_seconds = cc.s_maxage.or(cc.max_age).unwrap_or(default_ttl);
+    if ttl_seconds == 0 {
+        return None;
+    }
+    Some(Freshness {
+        ttl_seconds,
+        stale_seconds: cc.stale_while_revalidate.unwrap_or(default_stale),
+    })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CacheState {
+    Fresh,
+    /// Past its TTL but inside the stale-while-revalidate window.
+    Stale,
+    Expired,
+}
+
+pub fn cache_state(stored_at: u64, freshness: Freshness, now: u64) -> CacheState {
+    let age = now.saturating_sub(stored_at);
+    if age < freshness.ttl_seconds {
+        CacheState::Fresh
+    } else if age < freshness.ttl_seconds + freshness.stale_seconds {
+        CacheState::Stale
+    } else {
+        CacheState::Expired
+    }
+}
+
+/// Whether `If-None-Match` matches `etag`, using the weak comparison
+/// RFC 9110 prescribes for it.
+pub fn if_none_match(header: &str, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    let etag = opaque(etag);
+    header.trim() == "*" || header.split(',').any(|candidate| opaque(candidate) == etag)
+}
+
+/// Key fragment for the request headers a policy varies on.
+pub fn vary_fragment<'a>(
+    vary_headers: &[String],
+    header: impl Fn(&str) -> Option<&'a str>,
+) -> String {
+    vary_headers
+        .iter()
+        .map(|name| {
+            let name = name.to_ascii_lowercase();
+            let value = header(&name).unwrap_or("").trim();
+            format!("{name}={value}")
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Whether a CloudEvent type matches a purge pattern such as
+/// `albergue.v1.booking.*`, where `*` matches one or more trailing segments.
+pub fn event_matches(pattern: &str, event_type: &str) -> bool {
+    match pattern.strip_suffix(".*") {
+        Some(prefix) => event_type
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.len() > 1 && rest.starts_with('.')),
+        None => pattern == event_type,
+    }
+}
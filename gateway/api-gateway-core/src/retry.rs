@@ -0,0 +1,135 @@
+use std::time::Duration;
+
+/// Methods a request may be sent more than once for (RFC 9110, 9.2.2).
+pub fn is_idempotent(method: &str) -> bool {
+    ["GET", "HEAD", "OPTIONS", "TRACE", "PUT", "DELETE"]
+        .iter()
+        .any(|m| m.eq_ignore_ascii_case(method))
+}
+
+/// Statuses meaning the upstream did not handle the request and another
+/// attempt may succeed.
+pub fn is_retryable_status(status: u16) -> bool {
+    matches!(status, 502..=504)
+}
+
+/// How an upstream attempt ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AttemptOutcome<'a> {
+    Response {
+        status: u16,
+        retry_after: Option<&'a str>,
+    },
+    /// No response: connection refused, reset, DNS failure.
+    ConnectError,
+    /// No response before the attempt's timeout.
+    Timeout,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetrySettings {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+/// How long to wait before retry number `retries_done + 1`, or `None` when
+/// the outcome is final. `jitter` is a random number in `[0, 1)`; `remaining`
+/// is what is left of the request's overall timeout, and a retry that could
+/// not start within it is not attempted.
+pub fn retry_delay(
+    settings: &RetrySettings,
+    retries_done: u32,
+    outcome: AttemptOutcome<'_>,
+    now_unix: u64,
+    remaining: Duration,
+    jitter: f64,
+) -> Option<Duration> {
+    if retries_done >= settings.max_retries {
+        return None;
+    }
+    let retry_after = match outcome {
+        AttemptOutcome::Response { status, .. } if !is_retryable_status(status) => return None,
+        AttemptOutcome::Response { retry_after, .. } => {
+            retry_after.and_then(|v| parse_retry_after(v, now_unix))
+        }
+        AttemptOutcome::ConnectError | AttemptOutcome::Timeout => None,
+    };
+
+    let delay = backoff_delay(
+        retries_done,
+        settings.base_delay,
+        settings.max_delay,
+        jitter,
+    )
+    .max(retry_after.unwrap_or_default());
+    (delay < remaining).then_some(delay)
+}
+
+/// Exponential backoff with full jitter: uniformly random up to
+/// `base * 2^attempt`, capped at `max`.
+pub fn backoff_delay(attempt: u32, base: Duration, max: Duration, jitter: f64) -> Duration {
+    let ceiling = base.saturating_mul(1u32 << attempt.min(16)).min(max);
+    ceiling.mul_f64(jitter.clamp(0.0, 1.0))
+}
+
+/// A `Retry-After` value, either delay-seconds or an IMF-fixdate such as
+/// `Sun, 06 Nov 1994 08:49:37 GMT`.
+pub fn parse_retry_after(value: &str, now_unix: u64) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let at = parse_imf_fixdate(value)?;
+    Some(Duration::from_secs(at.saturating_sub(now_unix)))
+}
+
+fn parse_imf_fixdate(value: &str) -> Option<u64> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let (_weekday, rest) = value.split_once(", ")?;
+    let parts: Vec<&str> = rest.split(' ').collect();
+    let [day, month, year, time, "GMT"] = parts.as_slice() else {
+        return None;
+    };
+    let day: u64 = day.parse().ok()?;
+    let month = MONTHS.iter().position(|m| m == month)? as u64 + 1;
+    let year: u64 = year.parse().ok()?;
+    let mut hms = time.split(':').map(|p| p.parse::<u64>().ok());
+    let (h, m, s) = (hms.next()??, hms.next()??, hms.next()??);
+    if year < 1970 || !(1..=31).contains(&day) || h > 23 || m > 59 || s > 60 {
+        return None;
+    }
+    Some(days_from_civil(year, month, day) * 86_400 + h * 3600 + m * 60 + s)
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date (year >= 1970).
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y / 400;
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Whether the retry budget has room: retries within the current window may
+/// reach `percent` of the requests seen, and always `min_retries`.
+pub fn retry_budget_allows(requests: u64, retries: u64, percent: u64, min_retries: u64) -> bool {
+    retries < (requests * percent / 100).max(min_retries)
+}
+
+/// Redis script applying [`retry_budget_allows`] and counting the retry if
+/// it is allowed. `KEYS` are the window's request and retry counters;
+/// `ARGV` is `{window_seconds, percent, min_retries}`. Replies 1 or 0.
+pub const RETRY_BUDGET_SCRIPT: &str = r"
+local requests = tonumber(redis.call('GET', KEYS[1]) or '0')
+local retries = tonumber(redis.call('GET', KEYS[2]) or '0')
+local allowed = math.max(tonumber(ARGV[3]), math.floor(requests * tonumber(ARGV[2]) / 100))
+if retries >= allowed then return 0 end
+redis.call('INCR', KEYS[2])
+redis.call('EXPIRE', KEYS[2], ARGV[1])
+return 1
+";
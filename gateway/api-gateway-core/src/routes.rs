@@ -0,0 +1,304 @@
+use anyhow::{bail, Result};
+use serde::Deserialize;
+
+const HTTP_METHODS: [&str; 7] = ["GET", "HEAD", "POST", "PUT", "PATCH", "DELETE", "OPTIONS"];
+
+/// One `[[routes]]` entry in `gateway.toml`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(bound(deserialize = "P: Deserialize<'de> + Default"))]
+pub struct RouteConfig<P> {
+    /// Key of the `[services.*]` table requests are forwarded to.
+    pub service: String,
+    /// Public path prefix, matched on whole segments; a `*` segment matches
+    /// any single segment.
+    pub prefix: String,
+    /// Methods the route accepts; empty accepts every method.
+    #[serde(default)]
+    pub methods: Vec<String>,
+    #[serde(default)]
+    pub rewrite: RewriteRule,
+    /// Applied on top of the service's policy.
+    #[serde(default)]
+    pub policy: P,
+}
+
+/// How the matched prefix is turned into the upstream path. With neither set
+/// the path is forwarded unchanged.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct RewriteRule {
+    /// Drop the matched prefix: `/api/reviews/stats` becomes `/stats`.
+    #[serde(default)]
+    pub strip_prefix: bool,
+    /// Swap the matched prefix for another: with `/api`,
+    /// `/api/reviews/stats` becomes `/api/stats`.
+    #[serde(default)]
+    pub replace_prefix: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Any,
+}
+
+#[derive(Clone, Debug)]
+struct CompiledRoute<P> {
+    id: String,
+    segments: Vec<Segment>,
+    methods: Vec<String>,
+    config: RouteConfig<P>,
+}
+
+/// Routes ordered so the first match is the most specific one: longest
+/// prefix, then fewest wildcards, then method-specific before catch-all.
+#[derive(Clone, Debug)]
+pub struct RouteTable<P> {
+    routes: Vec<CompiledRoute<P>>,
+}
+
+impl<P> Default for RouteTable<P> {
+    fn default() -> Self {
+        Self { routes: Vec::new() }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct RouteMatch<'a, P> {
+    pub service: String,
+    pub upstream_path: String,
+    /// Methods and prefix of the matched route, e.g. `POST /api/bookings`.
+    pub route: Option<&'a str>,
+    /// `None` when no configured route matched and the service was derived
+    /// from the path, as for services registered at runtime.
+    pub policy: Option<&'a P>,
+}
+
+impl<P> RouteTable<P> {
+    /// Validate and order the configured routes. `is_known_service` rejects
+    /// routes pointing at services missing from `[services.*]`.
+    pub fn compile(
+        routes: Vec<RouteConfig<P>>,
+        is_known_service: impl Fn(&str) -> bool,
+    ) -> Result<Self> {
+        let mut compiled = Vec::with_capacity(routes.len());
+        for config in routes {
+            if !is_known_service(&config.service) {
+                bail!(
+                    "route `{}` points at unknown service `{}`",
+                    config.prefix,
+                    config.service
+                );
+            }
+            let segments = parse_prefix(&config.prefix)?;
+            let methods = parse_methods(&config)?;
+            validate_rewrite(&config)?;
+            let id = if methods.is_empty() {
+                format!("* {}", config.prefix)
+            } else {
+                format!("{} {}", methods.join(","), config.prefix)
+            };
+            compiled.push(CompiledRoute {
+                id,
+                segments,
+                methods,
+                config,
+            });
+        }
+
+        for (i, a) in compiled.iter().enumerate() {
+            for b in &compiled[i + 1..] {
+                if overlaps(a, b) {
+                    bail!(
+                        "routes `{}` ({}) and `{}` ({}) overlap",
+                        a.config.prefix,
+                        a.config.service,
+                        b.config.prefix,
+                        b.config.service
+                    );
+                }
+            }
+        }
+
+        compiled.sort_by_key(|r| std::cmp::Reverse(specificity(r)));
+        Ok(Self { routes: compiled })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+    }
+
+    /// The compiled routes with their ids, most specific first.
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &RouteConfig<P>)> {
+        self.routes.iter().map(|r| (r.id.as_str(), &r.config))
+    }
+
+    /// Resolve a request to a service and upstream path. Paths no route
+    /// covers fall back to `/api/<service>/...`, forwarded unchanged.
+    pub fn route(&self, method: &str, path: &str) -> RouteMatch<'_, P> {
+        let path_segments: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+
+        for route in &self.routes {
+            if !route.accepts(method) {
+                continue;
+            }
+            if let Some(remainder) = route.match_path(path, &path_segments) {
+                return RouteMatch {
+                    service: route.config.service.clone(),
+                    upstream_path: route.config.rewrite.apply(path, remainder),
+                    route: Some(&route.id),
+                    policy: Some(&route.config.policy),
+                };
+            }
+        }
+
+        RouteMatch {
+            service: fallback_service(&path_segments),
+            upstream_path: path.to_string(),
+            route: None,
+            policy: None,
+        }
+    }
+}
+
+impl<P> CompiledRoute<P> {
+    fn accepts(&self, method: &str) -> bool {
+        // Preflights must reach the route's CORS policy whatever it accepts.
+        self.methods.is_empty()
+            || method.eq_ignore_ascii_case("OPTIONS")
+            || self.methods.iter().any(|m| m.eq_ignore_ascii_case(method))
+    }
+
+    /// The part of `path` after the matched prefix, if it matches.
+    fn match_path<'p>(&self, path: &'p str, path_segments: &[&str]) -> Option<&'p str> {
+        if path_segments.len() < self.segments.len() {
+            return None;
+        }
+        let matches =
+            self.segments
+                .iter()
+                .zip(path_segments)
+                .all(|(segment, actual)| match segment {
+                    Segment::Literal(expected) => expected == actual,
+                    Segment::Any => !actual.is_empty(),
+                });
+        if !matches {
+            return None;
+        }
+
+        let trimmed = path.trim_start_matches('/');
+        let consumed: usize = path_segments[..self.segments.len()]
+            .iter()
+            .map(|s| s.len())
+            .sum::<usize>()
+            + self.segments.len().saturating_sub(1);
+        Some(&trimmed[consumed..])
+    }
+}
+
+impl RewriteRule {
+    fn apply(&self, path: &str, remainder: &str) -> String {
+        let replacement = if self.strip_prefix {
+            ""
+        } else if let Some(prefix) = &self.replace_prefix {
+            prefix.trim_end_matches('/')
+        } else {
+            return path.to_string();
+        };
+
+        let rewritten = format!("{replacement}{remainder}");
+        if rewritten.is_empty() {
+            "/".to_string()
+        } else {
+            rewritten
+        }
+    }
+}
+
+fn parse_prefix(prefix: &str) -> Result<Vec<Segment>> {
+    let Some(rest) = prefix.strip_prefix('/') else {
+        bail!("route prefix `{prefix}` must start with `/`");
+    };
+    let rest = rest.trim_end_matches('/');
+    if rest.is_empty() {
+        bail!("route prefix `{prefix}` would match every path");
+    }
+
+    rest.split('/')
+        .map(|segment| match segment {
+            "" => bail!("route prefix `{prefix}` has an empty segment"),
+            "*" => Ok(Segment::Any),
+            s if s.contains('*') => {
+                bail!("route prefix `{prefix}`: `*` must be a whole segment")
+            }
+            s => Ok(Segment::Literal(s.to_string())),
+        })
+        .collect()
+}
+
+fn parse_methods<P>(config: &RouteConfig<P>) -> Result<Vec<String>> {
+    config
+        .methods
+        .iter()
+        .map(|m| {
+            let upper = m.to_ascii_uppercase();
+            if HTTP_METHODS.contains(&upper.as_str()) {
+                Ok(upper)
+            } else {
+                bail!("route `{}` has unknown method `{m}`", config.prefix)
+            }
+        })
+        .collect()
+}
+
+fn validate_rewrite<P>(config: &RouteConfig<P>) -> Result<()> {
+    match &config.rewrite {
+        RewriteRule {
+            strip_prefix: true,
+            replace_prefix: Some(_),
+        } => bail!(
+            "route `{}` sets both strip_prefix and replace_prefix",
+            config.prefix
+        ),
+        RewriteRule {
+            replace_prefix: Some(p),
+            ..
+        } if !p.starts_with('/') => bail!(
+            "route `{}`: replace_prefix `{p}` must start with `/`",
+            config.prefix
+        ),
+        _ => Ok(()),
+    }
+}
+
+fn specificity<P>(route: &CompiledRoute<P>) -> (usize, usize, bool) {
+    let literals = route
+        .segments
+        .iter()
+        .filter(|s| matches!(s, Segment::Literal(_)))
+        .count();
+    (route.segments.len(), literals, !route.methods.is_empty())
+}
+
+/// Two routes overlap when some request could match both and neither is more
+/// specific, so which one wins would depend on file order.
+fn overlaps<P>(a: &CompiledRoute<P>, b: &CompiledRoute<P>) -> bool {
+    if specificity(a) != specificity(b) {
+        return false;
+    }
+    let same_shape = a.segments.iter().zip(&b.segments).all(|pair| match pair {
+        (Segment::Literal(x), Segment::Literal(y)) => x == y,
+        _ => true,
+    });
+    let shared_method = a.methods.is_empty()
+        || b.methods.is_empty()
+        || a.methods.iter().any(|m| b.methods.contains(m));
+    same_shape && shared_method
+}
+
+fn fallback_service(path_segments: &[&str]) -> String {
+    match path_segments {
+        ["api", service, ..] if !service.is_empty() => (*service).to_string(),
+        _ => "unknown".to_string(),
+    }
+}
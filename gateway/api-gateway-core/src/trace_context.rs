@@ -0,0 +1,126 @@
+/// A W3C Trace Context `traceparent` (version 00).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceParent {
+    /// 32 lowercase hex digits.
+    pub trace_id: String,
+    /// The caller's span: 16 lowercase hex digits.
+    pub parent_id: String,
+    pub flags: u8,
+}
+
+impl TraceParent {
+    pub const SAMPLED: u8 = 0x01;
+
+    /// Parses a `traceparent` header. Unknown future versions are read as
+    /// version 00, as the spec asks; all-zero ids are rejected.
+    pub fn parse(header: &str) -> Option<Self> {
+        let header = header.trim();
+        let mut parts = header.split('-');
+        let version = parts.next()?;
+        let trace_id = parts.next()?;
+        let parent_id = parts.next()?;
+        let flags = parts.next()?;
+        let rest = parts.next();
+
+        if !is_lower_hex(version, 2) || version == "ff" {
+            return None;
+        }
+        if version == "00" && rest.is_some() {
+            return None;
+        }
+        if !is_lower_hex(trace_id, 32) || !is_lower_hex(parent_id, 16) || !is_lower_hex(flags, 2) {
+            return None;
+        }
+        if is_zero(trace_id) || is_zero(parent_id) {
+            return None;
+        }
+        Some(Self {
+            trace_id: trace_id.to_string(),
+            parent_id: parent_id.to_string(),
+            flags: u8::from_str_radix(flags, 16).ok()?,
+        })
+    }
+
+    /// A new sampled trace started by the gateway.
+    pub fn root(trace_id: [u8; 16], span_id: [u8; 8]) -> Self {
+        Self {
+            trace_id: hex_id(&trace_id),
+            parent_id: hex_id(&span_id),
+            flags: Self::SAMPLED,
+        }
+    }
+
+    /// The same trace with `span_id` as the parent, for the next hop.
+    pub fn child(&self, span_id: [u8; 8]) -> Self {
+        Self {
+            trace_id: self.trace_id.clone(),
+            parent_id: hex_id(&span_id),
+            flags: self.flags,
+        }
+    }
+
+    pub fn sampled(&self) -> bool {
+        self.flags & Self::SAMPLED != 0
+    }
+
+    pub fn header(&self) -> String {
+        format!("00-{}-{}-{:02x}", self.trace_id, self.parent_id, self.flags)
+    }
+}
+
+/// The `tracestate` to forward: at most 32 well-formed `key=value` members,
+/// or `None` when nothing valid is left. A malformed list must not be
+/// propagated, so any invalid member drops the whole header.
+pub fn parse_tracestate(header: &str) -> Option<String> {
+    let members: Vec<&str> = header
+        .split(',')
+        .map(str::trim)
+        .filter(|m| !m.is_empty())
+        .collect();
+    if members.is_empty() || members.len() > 32 {
+        return None;
+    }
+    let valid = members.iter().all(|member| {
+        member.split_once('=').is_some_and(|(key, value)| {
+            is_tracestate_key(key)
+                && !value.is_empty()
+                && value.len() <= 256
+                && !value.ends_with(' ')
+                && value
+                    .bytes()
+                    .all(|b| (0x20..=0x7e).contains(&b) && b != b',' && b != b'=')
+        })
+    });
+    valid.then(|| members.join(","))
+}
+
+pub fn hex_id(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn is_tracestate_key(key: &str) -> bool {
+    let allowed = |b: u8| {
+        b.is_ascii_lowercase() || b.is_ascii_digit() || matches!(b, b'_' | b'-' | b'*' | b'/')
+    };
+    let (tenant, system) = match key.split_once('@') {
+        Some((tenant, system)) => (tenant, Some(system)),
+        None => (key, None),
+    };
+    !tenant.is_empty()
+        && tenant.len() <= 256
+        && tenant.bytes().all(allowed)
+        && system.is_none_or(|s| {
+            !s.is_empty()
+                && s.len() <= 14
+                && s.as_bytes()[0].is_ascii_lowercase()
+                && s.bytes().all(allowed)
+        })
+}
+
+fn is_lower_hex(s: &str, len: usize) -> bool {
+    s.len() == len && s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+fn is_zero(s: &str) -> bool {
+    s.bytes().all(|b| b == b'0')
+}
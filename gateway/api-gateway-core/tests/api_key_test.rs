@@ -0,0 +1,99 @@
+use api_gateway_core::{
+    constant_time_eq, format_api_key, parse_api_key, ApiKeyError, ApiKeyMatch, ApiKeyRecord,
+};
+
+const SECRET: &str = "0123456789abcdef0123456789abcdef";
+
+fn record() -> ApiKeyRecord {
+    ApiKeyRecord {
+        id: "a1b2c3".to_string(),
+        name: "reception-kiosk-1".to_string(),
+        hash: "current".to_string(),
+        scopes: vec!["bookings:write".to_string()],
+        roles: vec!["reception".to_string()],
+        created_at: 1_000,
+        expires_at: Some(5_000),
+        revoked_at: None,
+        rotated_at: None,
+        previous_hash: None,
+        previous_expires_at: None,
+    }
+}
+
+#[test]
+fn formats_and_parses_keys() {
+    let key = format_api_key("a1b2c3", SECRET);
+    assert_eq!(key, format!("amc_a1b2c3_{SECRET}"));
+    assert_eq!(parse_api_key(&key), Some(("a1b2c3", SECRET)));
+    assert_eq!(
+        parse_api_key(&format!(" amc_a1b2c3_{SECRET}_x ")),
+        Some(("a1b2c3", &*format!("{SECRET}_x")))
+    );
+}
+
+#[test]
+fn rejects_foreign_or_short_keys() {
+    assert_eq!(parse_api_key(&format!("key_a1b2c3_{SECRET}")), None);
+    assert_eq!(parse_api_key(&format!("amc_A1B2C3_{SECRET}")), None);
+    assert_eq!(parse_api_key(&format!("amc__{SECRET}")), None);
+    assert_eq!(parse_api_key("amc_a1b2c3_short"), None);
+    assert_eq!(parse_api_key("amc_a1b2c3"), None);
+}
+
+#[test]
+fn accepts_the_current_key_until_it_expires() {
+    let rec = record();
+    assert_eq!(rec.check("current", 4_999), Ok(ApiKeyMatch::Current));
+    assert_eq!(rec.check("current", 5_000), Err(ApiKeyError::Expired));
+    assert_eq!(rec.check("other", 2_000), Err(ApiKeyError::Unknown));
+    assert!(rec.is_active(4_999));
+    assert!(!rec.is_active(5_000));
+}
+
+#[test]
+fn rotation_keeps_the_old_key_for_the_grace_period() {
+    let mut rec = record();
+    rec.rotate("next".to_string(), 2_000, 600);
+    assert_eq!(rec.rotated_at, Some(2_000));
+    assert_eq!(rec.check("next", 2_100), Ok(ApiKeyMatch::Current));
+    assert_eq!(rec.check("current", 2_599), Ok(ApiKeyMatch::Previous));
+    assert_eq!(rec.check("current", 2_600), Err(ApiKeyError::Unknown));
+
+    rec.rotate("third".to_string(), 2_100, 600);
+    assert_eq!(rec.check("current", 2_200), Err(ApiKeyError::Unknown));
+    assert_eq!(rec.check("next", 2_200), Ok(ApiKeyMatch::Previous));
+
+    rec.rotate("fourth".to_string(), 2_300, 0);
+    assert_eq!(rec.check("third", 2_300), Err(ApiKeyError::Unknown));
+    assert_eq!(rec.previous_expires_at, None);
+}
+
+#[test]
+fn revoked_keys_are_refused() {
+    let mut rec = record();
+    rec.rotate("next".to_string(), 2_000, 600);
+    rec.revoke(2_050);
+    rec.revoke(2_060);
+    assert_eq!(rec.revoked_at, Some(2_050));
+    assert_eq!(rec.check("next", 2_100), Err(ApiKeyError::Revoked));
+    assert_eq!(rec.check("current", 2_100), Err(ApiKeyError::Revoked));
+    assert_eq!(rec.check("other", 2_100), Err(ApiKeyError::Unknown));
+    assert!(!rec.is_active(2_100));
+}
+
+#[test]
+fn records_without_expiry_never_expire() {
+    let rec = ApiKeyRecord {
+        expires_at: None,
+        ..record()
+    };
+    assert_eq!(rec.check("current", u64::MAX), Ok(ApiKeyMatch::Current));
+}
+
+#[test]
+fn compares_in_constant_time() {
+    assert!(constant_time_eq("abc", "abc"));
+    assert!(!constant_time_eq("abc", "abd"));
+    assert!(!constant_time_eq("abc", "abcd"));
+    assert!(constant_time_eq("", ""));
+}
@@ -0,0 +1,90 @@
+use api_gateway_core::{
+    is_failure_status, record_transition, should_trip, BreakerAdmission, BreakerSettings,
+    BreakerTransition, CircuitState,
+};
+
+const SETTINGS: BreakerSettings = BreakerSettings {
+    window_seconds: 30,
+    min_requests: 10,
+    failure_rate_percent: 50,
+    open_seconds: 15,
+    half_open_max: 2,
+};
+
+#[test]
+fn trips_on_error_rate_once_volume_is_reached() {
+    assert!(!should_trip(9, 9, &SETTINGS), "below the minimum volume");
+    assert!(should_trip(10, 5, &SETTINGS));
+    assert!(!should_trip(10, 4, &SETTINGS));
+    assert!(should_trip(200, 100, &SETTINGS));
+    assert!(!should_trip(
+        0,
+        0,
+        &BreakerSettings {
+            min_requests: 0,
+            ..SETTINGS
+        }
+    ));
+}
+
+#[test]
+fn gateway_errors_count_as_failures() {
+    for status in [500, 502, 503, 504] {
+        assert!(is_failure_status(status), "{status}");
+    }
+    for status in [200, 304, 404, 429] {
+        assert!(!is_failure_status(status), "{status}");
+    }
+}
+
+#[test]
+fn window_is_split_into_buckets() {
+    assert_eq!(SETTINGS.bucket_seconds(), 3);
+    let short = BreakerSettings {
+        window_seconds: 5,
+        ..SETTINGS
+    };
+    assert_eq!(short.bucket_seconds(), 1);
+    assert_eq!(
+        SETTINGS.record_args(true),
+        vec!["1", "30", "3", "10", "50", "2"]
+    );
+}
+
+#[test]
+fn parses_admission_replies() {
+    let admitted = BreakerAdmission::from_reply(&[1, 1, 2, 0]).unwrap();
+    assert!(admitted.allowed);
+    assert_eq!(admitted.state, CircuitState::HalfOpen);
+    assert_eq!(
+        admitted.transition,
+        Some(BreakerTransition {
+            from: CircuitState::Open,
+            to: CircuitState::HalfOpen
+        })
+    );
+
+    let rejected = BreakerAdmission::from_reply(&[0, 1, 1, 7]).unwrap();
+    assert!(!rejected.allowed);
+    assert_eq!(rejected.retry_after_seconds, 7);
+    assert_eq!(rejected.transition, None);
+
+    assert_eq!(BreakerAdmission::from_reply(&[1, 0, 9, 0]), None);
+    assert_eq!(BreakerAdmission::from_reply(&[1, 0]), None);
+}
+
+#[test]
+fn parses_record_replies() {
+    assert_eq!(
+        record_transition(&[0, 1]),
+        Some(BreakerTransition {
+            from: CircuitState::Closed,
+            to: CircuitState::Open
+        })
+    );
+    assert_eq!(record_transition(&[2, 2]), None);
+    assert_eq!(
+        record_transition(&[2, 0]).map(|t| t.to.as_str()),
+        Some("closed")
+    );
+}
@@ -1,8 +1,31 @@
-use api_gateway_core::gateway_config_for_test;
+use api_gateway_core::{gateway_config_for_test, route_table};
 
 #[test]
 fn parses_gateway_toml() {
     let cfg =
         gateway_config_for_test(include_bytes!("../../api-gateway/config/gateway.toml")).unwrap();
     assert!(!cfg.services.is_empty());
+    assert!(!cfg.routes.is_empty());
+}
+
+#[test]
+fn gateway_toml_routes_every_service() {
+    let cfg =
+        gateway_config_for_test(include_bytes!("../../api-gateway/config/gateway.toml")).unwrap();
+    let table = route_table(&cfg).unwrap();
+
+    for service in cfg.services.keys() {
+        assert!(
+            cfg.routes.iter().any(|r| &r.service == service),
+            "no route for {service}"
+        );
+    }
+    assert_eq!(
+        table.route("GET", "/api/addresses/provinces").service,
+        "location-service"
+    );
+    assert_eq!(
+        table.route("GET", "/api/bookings/42").upstream_path,
+        "/api/42"
+    );
 }
@@ -0,0 +1,202 @@
+use api_gateway_core::{check_schema, escape_pointer, query_instance, validate, SchemaViolation};
+use serde_json::{json, Value};
+
+fn booking_schema() -> Value {
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "New booking",
+        "type": "object",
+        "required": ["guest_name", "guest_email", "check_in", "num_guests"],
+        "additionalProperties": false,
+        "properties": {
+            "guest_name": { "type": "string", "minLength": 1, "maxLength": 120 },
+            "guest_email": { "type": "string", "format": "email" },
+            "check_in": { "type": "string", "format": "date" },
+            "num_guests": { "type": "integer", "minimum": 1, "maximum": 12 },
+            "room_type": { "enum": ["dorm-a", "dorm-b", "private"] },
+            "tags": { "type": "array", "items": { "type": "string" }, "maxItems": 2 }
+        }
+    })
+}
+
+fn pointers(violations: &[SchemaViolation]) -> Vec<(&str, &str)> {
+    violations
+        .iter()
+        .map(|v| (v.pointer.as_str(), v.keyword))
+        .collect()
+}
+
+#[test]
+fn accepts_a_valid_instance() {
+    let schema = booking_schema();
+    check_schema(&schema).unwrap();
+    let body = json!({
+        "guest_name": "Ana",
+        "guest_email": "ana@example.org",
+        "check_in": "2024-02-29",
+        "num_guests": 2,
+        "room_type": "dorm-a",
+        "tags": ["bike"]
+    });
+    assert_eq!(validate(&schema, &body), Vec::new());
+}
+
+#[test]
+fn locates_every_violation_by_json_pointer() {
+    let body = json!({
+        "guest_email": "not-an-email",
+        "check_in": "2023-02-29",
+        "num_guests": 0,
+        "room_type": "suite",
+        "tags": ["a", 2, "c"],
+        "coupon": "FREE"
+    });
+    let violations = validate(&booking_schema(), &body);
+    assert_eq!(
+        pointers(&violations),
+        vec![
+            ("/guest_name", "required"),
+            ("/check_in", "format"),
+            ("/coupon", "additionalProperties"),
+            ("/guest_email", "format"),
+            ("/num_guests", "minimum"),
+            ("/room_type", "enum"),
+            ("/tags", "maxItems"),
+            ("/tags/1", "type"),
+        ]
+    );
+    assert_eq!(violations[7].message, "must be string, not integer");
+}
+
+#[test]
+fn type_mismatch_stops_at_the_first_error() {
+    let violations = validate(&booking_schema(), &json!(["not", "an", "object"]));
+    assert_eq!(pointers(&violations), vec![("", "type")]);
+}
+
+#[test]
+fn integers_accept_whole_floats_only() {
+    let schema = json!({ "type": "integer" });
+    assert!(validate(&schema, &json!(3.0)).is_empty());
+    assert_eq!(
+        pointers(&validate(&schema, &json!(3.5))),
+        vec![("", "type")]
+    );
+}
+
+#[test]
+fn checks_string_bounds_patterns_and_formats() {
+    let schema = json!({
+        "type": "object",
+        "properties": {
+            "code": { "type": "string", "pattern": "^[A-Z]{2}$" },
+            "name": { "type": "string", "minLength": 2, "maxLength": 3 },
+            "at": { "type": "string", "format": "date-time" },
+            "id": { "type": "string", "format": "uuid" }
+        }
+    });
+    assert!(validate(
+        &schema,
+        &json!({
+            "code": "ES",
+            "name": "Ñú",
+            "at": "2024-05-01T10:00:00.5+02:00",
+            "id": "6f1c2b1e-8a7d-4c1e-9f00-1a2b3c4d5e6f"
+        })
+    )
+    .is_empty());
+    assert_eq!(
+        pointers(&validate(
+            &schema,
+            &json!({ "code": "es", "name": "x", "at": "2024-05-01 10:00", "id": "nope" })
+        )),
+        vec![
+            ("/at", "format"),
+            ("/code", "pattern"),
+            ("/id", "format"),
+            ("/name", "minLength"),
+        ]
+    );
+}
+
+#[test]
+fn escapes_pointer_tokens() {
+    assert_eq!(escape_pointer("a/b~c"), "a~1b~0c");
+    let schema = json!({ "type": "object", "required": ["a/b"] });
+    assert_eq!(validate(&schema, &json!({}))[0].pointer, "/a~1b");
+}
+
+#[test]
+fn all_of_applies_every_subschema() {
+    let schema = json!({
+        "allOf": [
+            { "type": "object", "required": ["a"] },
+            { "required": ["b"] }
+        ]
+    });
+    assert_eq!(
+        pointers(&validate(&schema, &json!({}))),
+        vec![("/a", "required"), ("/b", "required")]
+    );
+}
+
+#[test]
+fn refuses_schemas_it_cannot_enforce() {
+    assert!(check_schema(&json!({ "$ref": "#/definitions/x" })).is_err());
+    assert!(check_schema(&json!({ "oneOf": [] })).is_err());
+    assert!(check_schema(&json!({ "type": "text" })).is_err());
+    assert!(check_schema(&json!({ "properties": { "a": { "pattern": "(" } } })).is_err());
+    assert!(check_schema(&json!({ "minLength": -1 })).is_err());
+    let err = check_schema(&json!({ "properties": { "a/b": { "if": {} } } })).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "#/properties/a~1b/if: unsupported keyword \"if\""
+    );
+}
+
+#[test]
+fn converts_query_parameters_to_declared_types() {
+    let schema = json!({
+        "type": "object",
+        "properties": {
+            "limit": { "type": "integer" },
+            "ratio": { "type": "number" },
+            "open": { "type": "boolean" },
+            "lang": { "type": "string" },
+            "ids": { "type": "array", "items": { "type": "integer" } }
+        }
+    });
+    let instance = query_instance(
+        "limit=10&ratio=0.5&open=true&lang=es%2DES&search=camino+de+plata&ids=1&ids=x",
+        &schema,
+    );
+    assert_eq!(
+        instance,
+        json!({
+            "limit": 10,
+            "ratio": 0.5,
+            "open": true,
+            "lang": "es-ES",
+            "search": "camino de plata",
+            "ids": [1, "x"]
+        })
+    );
+    assert_eq!(
+        pointers(&validate(&schema, &instance)),
+        vec![("/ids/1", "type")]
+    );
+}
+
+#[test]
+fn query_values_that_do_not_convert_fail_validation() {
+    let schema = json!({
+        "type": "object",
+        "properties": { "limit": { "type": "integer" }, "lang": { "type": "string" } }
+    });
+    let instance = query_instance("limit=ten&lang=es&lang=en&bad=%zz", &schema);
+    assert_eq!(instance["bad"], json!("%zz"));
+    assert_eq!(
+        pointers(&validate(&schema, &instance)),
+        vec![("/lang", "type"), ("/limit", "type")]
+    );
+}
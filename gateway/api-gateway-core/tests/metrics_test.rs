@@ -0,0 +1,68 @@
+use api_gateway_core::{metric_increments, redact_headers, render_prometheus};
+use std::collections::BTreeMap;
+
+fn apply(fields: &mut BTreeMap<String, u64>, status: u16, duration_ms: u64) {
+    for (field, by) in metric_increments(status, duration_ms) {
+        *fields.entry(field).or_default() += by;
+    }
+}
+
+#[test]
+fn increments_pick_status_class_and_bucket() {
+    assert_eq!(
+        metric_increments(201, 7),
+        vec![
+            ("requests:2xx".to_string(), 1),
+            ("bucket:10".to_string(), 1),
+            ("duration_ms_sum".to_string(), 7),
+        ]
+    );
+    let slow = metric_increments(503, 60_000);
+    assert!(slow.contains(&("bucket:+Inf".to_string(), 1)));
+    assert!(slow.contains(&("errors".to_string(), 1)));
+}
+
+#[test]
+fn renders_cumulative_histogram() {
+    let mut fields = BTreeMap::new();
+    apply(&mut fields, 200, 3);
+    apply(&mut fields, 200, 40);
+    apply(&mut fields, 502, 20_000);
+    let services = BTreeMap::from([("booking-service".to_string(), fields)]);
+
+    let text = render_prometheus(&services);
+    for line in [
+        "# TYPE gateway_requests_total counter",
+        "gateway_requests_total{service=\"booking-service\",code=\"2xx\"} 2",
+        "gateway_requests_total{service=\"booking-service\",code=\"5xx\"} 1",
+        "gateway_request_errors_total{service=\"booking-service\"} 1",
+        "gateway_request_duration_seconds_bucket{service=\"booking-service\",le=\"0.005\"} 1",
+        "gateway_request_duration_seconds_bucket{service=\"booking-service\",le=\"0.05\"} 2",
+        "gateway_request_duration_seconds_bucket{service=\"booking-service\",le=\"10\"} 2",
+        "gateway_request_duration_seconds_bucket{service=\"booking-service\",le=\"+Inf\"} 3",
+        "gateway_request_duration_seconds_sum{service=\"booking-service\"} 20.043",
+        "gateway_request_duration_seconds_count{service=\"booking-service\"} 3",
+    ] {
+        assert!(text.lines().any(|l| l == line), "missing {line}\n{text}");
+    }
+}
+
+#[test]
+fn redacts_credentials_and_configured_headers() {
+    let headers = [
+        ("Authorization", "Bearer secret"),
+        ("Cookie", "sid=1"),
+        ("X-Tenant-Key", "k"),
+        ("Accept", "application/json"),
+    ];
+    let logged = redact_headers(headers, &["x-tenant-key".to_string()]);
+    assert_eq!(
+        logged,
+        vec![
+            ("authorization".to_string(), "[REDACTED]".to_string()),
+            ("cookie".to_string(), "[REDACTED]".to_string()),
+            ("x-tenant-key".to_string(), "[REDACTED]".to_string()),
+            ("accept".to_string(), "application/json".to_string()),
+        ]
+    );
+}
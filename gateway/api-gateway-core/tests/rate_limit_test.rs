@@ -0,0 +1,147 @@
+use api_gateway_core::{
+    client_ip, gateway_config_for_test, route_table, RateLimitAlgorithm, RateLimitDecision,
+    RateLimitInputs, RateLimitKey, RateLimitKeys,
+};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct Policy {
+    #[serde(default)]
+    algorithm: RateLimitAlgorithm,
+    #[serde(default)]
+    key: RateLimitKeys,
+}
+
+fn policy(text: &str) -> Result<Policy, toml::de::Error> {
+    toml::from_str(text)
+}
+
+#[test]
+fn parses_single_and_composite_keys() {
+    let single = policy(r#"key = "ip""#).unwrap();
+    assert_eq!(single.key, RateLimitKeys(vec![RateLimitKey::Ip]));
+    assert_eq!(single.algorithm, RateLimitAlgorithm::Fixed);
+
+    let composite = policy(
+        r#"
+        algorithm = "gcra"
+        key = ["api_key", "route"]
+        "#,
+    )
+    .unwrap();
+    assert_eq!(
+        composite.key,
+        RateLimitKeys(vec![RateLimitKey::ApiKey, RateLimitKey::Route])
+    );
+    assert_eq!(composite.algorithm, RateLimitAlgorithm::Gcra);
+
+    assert!(policy("key = []").is_err());
+    assert!(policy(r#"key = "correlation_id""#).is_err());
+    assert!(policy(r#"algorithm = "leaky""#).is_err());
+}
+
+#[test]
+fn identity_composes_keys_and_falls_back_to_ip() {
+    let inputs = RateLimitInputs {
+        subject: None,
+        client_ip: Some("203.0.113.9"),
+        api_key_hash: Some("ab12"),
+        route: "POST /api/bookings",
+    };
+    assert_eq!(RateLimitKeys::default().identity(&inputs), "ip=203.0.113.9");
+
+    let keys = RateLimitKeys(vec![RateLimitKey::ApiKey, RateLimitKey::Route]);
+    assert_eq!(keys.identity(&inputs), "key=ab12|route=POST /api/bookings");
+
+    let signed_in = RateLimitInputs {
+        subject: Some("alice"),
+        ..inputs
+    };
+    assert_eq!(RateLimitKeys::default().identity(&signed_in), "sub=alice");
+}
+
+#[test]
+fn client_ip_strips_ports_and_only_trusts_forwarded_for_when_asked() {
+    assert_eq!(
+        client_ip(None, Some("198.51.100.7:51234"), false).as_deref(),
+        Some("198.51.100.7")
+    );
+    assert_eq!(
+        client_ip(None, Some("[2001:db8::1]:443"), false).as_deref(),
+        Some("2001:db8::1")
+    );
+    assert_eq!(
+        client_ip(None, Some("2001:db8::1"), false).as_deref(),
+        Some("2001:db8::1")
+    );
+
+    let xff = Some("203.0.113.9, 10.0.0.2");
+    let peer = Some("10.0.0.2:8080");
+    assert_eq!(client_ip(xff, peer, false).as_deref(), Some("10.0.0.2"));
+    assert_eq!(client_ip(xff, peer, true).as_deref(), Some("203.0.113.9"));
+    assert_eq!(client_ip(None, None, true), None);
+}
+
+#[test]
+fn decision_headers() {
+    let allowed = RateLimitDecision::from_reply(&[1, 9, 42, 0], 10, 60).unwrap();
+    assert!(allowed.allowed);
+    assert_eq!(
+        allowed.headers("booking-service"),
+        vec![
+            (
+                "ratelimit-policy",
+                "\"booking-service\";q=10;w=60".to_string()
+            ),
+            ("ratelimit", "\"booking-service\";r=9;t=42".to_string()),
+        ]
+    );
+
+    let denied = RateLimitDecision::from_reply(&[0, 0, 30, 0], 10, 60).unwrap();
+    assert!(!denied.allowed);
+    assert_eq!(denied.retry_after_seconds, 1, "Retry-After is never 0");
+    assert_eq!(
+        denied.headers("booking-service").last().unwrap(),
+        &("retry-after", "1".to_string())
+    );
+
+    assert!(RateLimitDecision::from_reply(&[1, 2], 10, 60).is_none());
+}
+
+#[test]
+fn script_args_match_each_algorithm() {
+    assert_eq!(
+        RateLimitAlgorithm::Fixed.script_args(10, 60, 10, "m"),
+        ["60", "10"]
+    );
+    assert_eq!(
+        RateLimitAlgorithm::SlidingLog.script_args(10, 60, 10, "m"),
+        ["60000", "10", "m"]
+    );
+    assert_eq!(
+        RateLimitAlgorithm::Gcra.script_args(10, 60, 0, "m"),
+        ["60000", "10", "1"]
+    );
+    for algorithm in [
+        RateLimitAlgorithm::Fixed,
+        RateLimitAlgorithm::SlidingLog,
+        RateLimitAlgorithm::Gcra,
+    ] {
+        assert!(algorithm.script().contains("KEYS[1]"));
+    }
+}
+
+#[test]
+fn gateway_toml_route_ids() {
+    let cfg =
+        gateway_config_for_test(include_bytes!("../../api-gateway/config/gateway.toml")).unwrap();
+    let table = route_table(&cfg).unwrap();
+    assert_eq!(
+        table.route("POST", "/api/bookings").route,
+        Some("POST /api/bookings")
+    );
+    let check_out = table.route("POST", "/api/bookings/42/check-out");
+    assert_eq!(check_out.route, Some("POST /api/bookings/*/check-out"));
+    assert_eq!(check_out.upstream_path, "/api/bookings/42/check-out");
+    assert_eq!(table.route("GET", "/unrouted").route, None);
+}
@@ -0,0 +1,99 @@
+use api_gateway_core::{
+    cache_state, event_matches, freshness, if_none_match, vary_fragment, CacheControl, CacheState,
+    Freshness,
+};
+
+#[test]
+fn parses_cache_control() {
+    let cc = CacheControl::parse("public, max-age=60, s-maxage=\"120\", stale-while-revalidate=30");
+    assert_eq!(cc.max_age, Some(60));
+    assert_eq!(cc.s_maxage, Some(120));
+    assert_eq!(cc.stale_while_revalidate, Some(30));
+    assert!(!cc.no_store && !cc.private);
+
+    assert!(CacheControl::parse("No-Store").no_store);
+    assert!(CacheControl::parse("private=\"set-cookie\"").private);
+}
+
+#[test]
+fn freshness_follows_upstream_directives() {
+    assert_eq!(
+        freshness(200, None, 15, 0),
+        Some(Freshness {
+            ttl_seconds: 15,
+            stale_seconds: 0
+        })
+    );
+    assert_eq!(
+        freshness(200, Some("max-age=60, stale-while-revalidate=30"), 15, 0),
+        Some(Freshness {
+            ttl_seconds: 60,
+            stale_seconds: 30
+        })
+    );
+    assert_eq!(
+        freshness(200, Some("max-age=60, s-maxage=5"), 15, 10)
+            .unwrap()
+            .ttl_seconds,
+        5
+    );
+    for cc in ["no-store", "private, max-age=60", "no-cache", "max-age=0"] {
+        assert_eq!(freshness(200, Some(cc), 15, 0), None, "{cc}");
+    }
+    assert_eq!(freshness(404, None, 15, 0), None);
+}
+
+#[test]
+fn state_moves_from_fresh_to_stale_to_expired() {
+    let f = Freshness {
+        ttl_seconds: 10,
+        stale_seconds: 5,
+    };
+    assert_eq!(cache_state(100, f, 105), CacheState::Fresh);
+    assert_eq!(cache_state(100, f, 112), CacheState::Stale);
+    assert_eq!(cache_state(100, f, 115), CacheState::Expired);
+}
+
+#[test]
+fn if_none_match_uses_weak_comparison() {
+    let etag = "\"abc\"";
+    assert!(if_none_match("\"abc\"", etag));
+    assert!(if_none_match("\"x\", W/\"abc\"", etag));
+    assert!(if_none_match("*", etag));
+    assert!(!if_none_match("\"abcd\"", etag));
+}
+
+#[test]
+fn vary_fragment_is_stable_and_case_insensitive() {
+    let vary = vec!["Accept-Language".to_string(), "x-tenant".to_string()];
+    let fragment = vary_fragment(&vary, |name| match name {
+        "accept-language" => Some("es "),
+        _ => None,
+    });
+    assert_eq!(fragment, "accept-language=es&x-tenant=");
+    assert_eq!(vary_fragment(&[], |_| None), "");
+}
+
+#[test]
+fn event_patterns() {
+    assert!(event_matches(
+        "albergue.v1.booking.*",
+        "albergue.v1.booking.reserved"
+    ));
+    assert!(event_matches(
+        "albergue.v1.booking.*",
+        "albergue.v1.booking.payment.failed"
+    ));
+    assert!(!event_matches(
+        "albergue.v1.booking.*",
+        "albergue.v1.booking"
+    ));
+    assert!(!event_matches(
+        "albergue.v1.booking.*",
+        "albergue.v1.bookings.reserved"
+    ));
+    assert!(event_matches(
+        "albergue.v1.review.created",
+        "albergue.v1.review.created"
+    ));
+}
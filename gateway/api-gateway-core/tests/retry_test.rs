@@ -0,0 +1,112 @@
+use api_gateway_core::{
+    backoff_delay, is_idempotent, parse_retry_after, retry_budget_allows, retry_delay,
+    AttemptOutcome, RetrySettings,
+};
+use std::time::Duration;
+
+const SETTINGS: RetrySettings = RetrySettings {
+    max_retries: 2,
+    base_delay: Duration::from_millis(100),
+    max_delay: Duration::from_secs(2),
+};
+
+fn status(status: u16) -> AttemptOutcome<'static> {
+    AttemptOutcome::Response {
+        status,
+        retry_after: None,
+    }
+}
+
+#[test]
+fn only_idempotent_methods_qualify() {
+    for method in ["GET", "head", "PUT", "DELETE", "OPTIONS"] {
+        assert!(is_idempotent(method), "{method}");
+    }
+    for method in ["POST", "PATCH"] {
+        assert!(!is_idempotent(method), "{method}");
+    }
+}
+
+#[test]
+fn retries_gateway_errors_and_transport_failures_only() {
+    let remaining = Duration::from_secs(10);
+    for outcome in [
+        status(502),
+        status(503),
+        status(504),
+        AttemptOutcome::ConnectError,
+        AttemptOutcome::Timeout,
+    ] {
+        assert!(
+            retry_delay(&SETTINGS, 0, outcome, 0, remaining, 0.5).is_some(),
+            "{outcome:?}"
+        );
+    }
+    for code in [200, 404, 429, 500] {
+        assert_eq!(
+            retry_delay(&SETTINGS, 0, status(code), 0, remaining, 0.5),
+            None
+        );
+    }
+    assert_eq!(
+        retry_delay(&SETTINGS, 2, status(503), 0, remaining, 0.5),
+        None
+    );
+}
+
+#[test]
+fn backoff_is_jittered_and_capped() {
+    let base = Duration::from_millis(100);
+    let max = Duration::from_secs(1);
+    assert_eq!(backoff_delay(0, base, max, 0.0), Duration::ZERO);
+    assert_eq!(backoff_delay(0, base, max, 0.5), Duration::from_millis(50));
+    assert_eq!(backoff_delay(2, base, max, 0.5), Duration::from_millis(200));
+    assert_eq!(backoff_delay(10, base, max, 0.99), max.mul_f64(0.99));
+    assert_eq!(backoff_delay(40, base, max, 1.0), max);
+}
+
+#[test]
+fn honours_retry_after_within_the_deadline() {
+    let outcome = AttemptOutcome::Response {
+        status: 503,
+        retry_after: Some("3"),
+    };
+    assert_eq!(
+        retry_delay(&SETTINGS, 0, outcome, 0, Duration::from_secs(10), 0.5),
+        Some(Duration::from_secs(3))
+    );
+    assert_eq!(
+        retry_delay(&SETTINGS, 0, outcome, 0, Duration::from_secs(2), 0.5),
+        None,
+        "waiting past the overall timeout is pointless"
+    );
+}
+
+#[test]
+fn parses_retry_after_forms() {
+    assert_eq!(parse_retry_after("120", 0), Some(Duration::from_secs(120)));
+    // 1994-11-06T08:49:37Z is 784111777.
+    assert_eq!(
+        parse_retry_after("Sun, 06 Nov 1994 08:49:37 GMT", 784_111_770),
+        Some(Duration::from_secs(7))
+    );
+    assert_eq!(
+        parse_retry_after("Sun, 06 Nov 1994 08:49:37 GMT", 784_111_800),
+        Some(Duration::ZERO)
+    );
+    assert_eq!(
+        parse_retry_after("Thu, 01 Jan 2026 00:00:00 GMT", 0),
+        Some(Duration::from_secs(1_767_225_600))
+    );
+    assert_eq!(parse_retry_after("soon", 0), None);
+    assert_eq!(parse_retry_after("Sun, 06 Nov 1994 08:49:37 CET", 0), None);
+}
+
+#[test]
+fn budget_caps_retries_to_a_share_of_traffic() {
+    assert!(retry_budget_allows(0, 0, 20, 3));
+    assert!(retry_budget_allows(0, 2, 20, 3));
+    assert!(!retry_budget_allows(0, 3, 20, 3));
+    assert!(retry_budget_allows(100, 19, 20, 3));
+    assert!(!retry_budget_allows(100, 20, 20, 3));
+}
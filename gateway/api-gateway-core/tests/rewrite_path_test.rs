@@ -1,13 +1,23 @@
-﻿use api_gateway_core::rewrite_upstream_path_for_test;
+use api_gateway_core::{gateway_config_for_test, route_table};
 use proptest::prelude::*;
 
 proptest! {
     #[test]
     fn rewrites_known_prefixes(rest in "(/[a-zA-Z0-9/_\\-]{0,64})?") {
+        let cfg = gateway_config_for_test(include_bytes!("fixtures/routes.toml")).unwrap();
+        let table = route_table(&cfg).unwrap();
+
         let p = format!("/api/auth{rest}");
-        prop_assert_eq!(rewrite_upstream_path_for_test(&p, "auth-service"), format!("/api/auth{}", rest));
+        let m = table.route("GET", &p);
+        prop_assert_eq!(m.service, "auth-service");
+        prop_assert_eq!(m.upstream_path, format!("/api/auth{}", rest));
 
         let p = format!("/api/countries{rest}");
-        prop_assert_eq!(rewrite_upstream_path_for_test(&p, "location-service"), format!("/api/countries{}", rest));
+        let m = table.route("GET", &p);
+        prop_assert_eq!(m.service, "location-service");
+        prop_assert_eq!(m.upstream_path, format!("/api/countries{}", rest));
+
+        let p = format!("/api/reviews{rest}");
+        prop_assert_eq!(table.route("GET", &p).upstream_path, format!("/api{}", rest));
     }
 }
@@ -0,0 +1,127 @@
+use api_gateway_core::{gateway_config_for_test, route_table};
+
+fn fixture() -> api_gateway_core::GatewayConfig {
+    gateway_config_for_test(include_bytes!("fixtures/routes.toml")).unwrap()
+}
+
+#[test]
+fn strips_prefix() {
+    let cfg = fixture();
+    let table = route_table(&cfg).unwrap();
+
+    let m = table.route("GET", "/api/bookings/42");
+    assert_eq!(m.service, "booking-service");
+    assert_eq!(m.upstream_path, "/42");
+    assert_eq!(table.route("GET", "/api/bookings").upstream_path, "/");
+}
+
+#[test]
+fn method_specific_route_wins() {
+    let cfg = fixture();
+    let table = route_table(&cfg).unwrap();
+
+    let post = table.route("POST", "/api/bookings");
+    assert_eq!(post.upstream_path, "/api");
+    assert!(post.policy.unwrap().is_some());
+
+    let get = table.route("GET", "/api/bookings");
+    assert_eq!(get.upstream_path, "/");
+    assert!(get.policy.unwrap().is_none());
+
+    let ids: Vec<&str> = table.entries().map(|(id, _)| id).collect();
+    let post_id = ids
+        .iter()
+        .position(|id| *id == "POST /api/bookings")
+        .unwrap();
+    let any_id = ids.iter().position(|id| *id == "* /api/bookings").unwrap();
+    assert!(post_id < any_id, "{ids:?}");
+}
+
+#[test]
+fn longest_prefix_and_wildcards() {
+    let cfg = fixture();
+    let table = route_table(&cfg).unwrap();
+
+    let m = table.route("GET", "/api/countries/health");
+    assert_eq!(m.service, "reviews-service");
+    assert_eq!(m.upstream_path, "/api/countries/health");
+
+    // Segment boundaries: `/api/authx` is not under `/api/auth`.
+    assert_eq!(table.route("GET", "/api/authx").service, "authx");
+}
+
+#[test]
+fn unmatched_paths_fall_back_to_path_segment() {
+    let cfg = fixture();
+    let table = route_table(&cfg).unwrap();
+
+    let m = table.route("GET", "/api/custom-service/items");
+    assert_eq!(m.service, "custom-service");
+    assert_eq!(m.upstream_path, "/api/custom-service/items");
+    assert!(m.policy.is_none());
+
+    assert_eq!(table.route("GET", "/health").service, "unknown");
+}
+
+#[test]
+fn rejects_overlapping_routes() {
+    let config = br#"
+[defaults]
+[services.a]
+url = "http://a"
+[services.b]
+url = "http://b"
+
+[[routes]]
+service = "a"
+prefix = "/api/*/items"
+
+[[routes]]
+service = "b"
+prefix = "/api/things/*"
+methods = ["GET"]
+
+[[routes]]
+service = "a"
+prefix = "/api/things/*"
+methods = ["GET", "POST"]
+"#;
+    let err = gateway_config_for_test(config).unwrap_err();
+    assert!(format!("{err:#}").contains("overlap"), "{err:#}");
+}
+
+#[test]
+fn rejects_unknown_service_and_bad_rules() {
+    let unknown = br#"
+[defaults]
+[services.a]
+url = "http://a"
+[[routes]]
+service = "missing"
+prefix = "/api/missing"
+"#;
+    let err = gateway_config_for_test(unknown).unwrap_err();
+    assert!(format!("{err:#}").contains("unknown service `missing`"));
+
+    let both = br#"
+[defaults]
+[services.a]
+url = "http://a"
+[[routes]]
+service = "a"
+prefix = "/api/a"
+rewrite = { strip_prefix = true, replace_prefix = "/v1" }
+"#;
+    assert!(gateway_config_for_test(both).is_err());
+
+    let method = br#"
+[defaults]
+[services.a]
+url = "http://a"
+[[routes]]
+service = "a"
+prefix = "/api/a"
+methods = ["FETCH"]
+"#;
+    assert!(gateway_config_for_test(method).is_err());
+}
@@ -0,0 +1,68 @@
+use api_gateway_core::{parse_tracestate, TraceParent};
+
+const VALID: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+#[test]
+fn parses_and_formats_traceparent() {
+    let tp = TraceParent::parse(VALID).unwrap();
+    assert_eq!(tp.trace_id, "4bf92f3577b34da6a3ce929d0e0e4736");
+    assert_eq!(tp.parent_id, "00f067aa0ba902b7");
+    assert!(tp.sampled());
+    assert_eq!(tp.header(), VALID);
+
+    let child = tp.child([0xab; 8]);
+    assert_eq!(
+        child.header(),
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-abababababababab-01"
+    );
+}
+
+#[test]
+fn rejects_malformed_traceparent() {
+    for header in [
+        "",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+        "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+        "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+        "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+        "00-4bf92f3577b34da6a3ce929d0e0e473-00f067aa0ba902b7-01",
+    ] {
+        assert_eq!(TraceParent::parse(header), None, "{header}");
+    }
+}
+
+#[test]
+fn accepts_future_versions_with_extra_fields() {
+    let tp =
+        TraceParent::parse("01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00-what").unwrap();
+    assert!(!tp.sampled());
+    assert!(tp.header().starts_with("00-"));
+}
+
+#[test]
+fn root_traces_are_sampled() {
+    let tp = TraceParent::root([1; 16], [2; 8]);
+    assert_eq!(
+        tp.header(),
+        "00-01010101010101010101010101010101-0202020202020202-01"
+    );
+}
+
+#[test]
+fn validates_tracestate() {
+    assert_eq!(
+        parse_tracestate(" rojo=00f067aa0ba902b7 , congo=t61rcWkgMzE,"),
+        Some("rojo=00f067aa0ba902b7,congo=t61rcWkgMzE".to_string())
+    );
+    assert_eq!(
+        parse_tracestate("tenant@vendor=abc"),
+        Some("tenant@vendor=abc".to_string())
+    );
+    assert_eq!(parse_tracestate("Upper=1"), None);
+    assert_eq!(parse_tracestate("novalue"), None);
+    assert_eq!(parse_tracestate(""), None);
+    let too_many: Vec<String> = (0..33).map(|i| format!("k{i}=v")).collect();
+    assert_eq!(parse_tracestate(&too_many.join(",")), None);
+}
@@ -0,0 +1,417 @@
+use crate::{
+    api_keys,
+    auth::authenticate_and_authorize,
+    circuit_breaker,
+    context::{
+        build_request_context, clear_stored_config, config_source, get_config, registered_services,
+        store_config, AuthContext, RequestContext, CORRELATION_ID_HEADER, REDIS_ADDRESS_VAR,
+    },
+    rejection::GatewayRejection,
+    security_headers::apply_security_headers,
+    upstream::{self, UpstreamError, UpstreamRequest},
+};
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use spin_sdk::{
+    http::{Params, Request, Response, ResponseBuilder},
+    redis::Connection,
+    variables,
+};
+use std::time::Instant;
+use tracing::{event, Level};
+
+/// Role a token must carry to use `/api/gateway/admin/*`.
+pub const ADMIN_ROLE: &str = "admin";
+
+/// Health probes get this long, without retries, so one hung service cannot
+/// stall the whole report.
+const PROBE_TIMEOUT_MS: u64 = 2000;
+
+/// How long a rotated key keeps working unless the rotation says otherwise.
+const DEFAULT_ROTATION_GRACE_SECONDS: u64 = 86_400;
+
+/// Body of `POST /api/gateway/admin/api-keys`.
+#[derive(Deserialize)]
+struct NewApiKey {
+    name: String,
+    #[serde(default)]
+    scopes: Vec<String>,
+    #[serde(default)]
+    roles: Vec<String>,
+    /// Unix seconds; keys without one never expire.
+    #[serde(default)]
+    expires_at: Option<u64>,
+}
+
+/// Body of `POST /api/gateway/admin/api-keys/:id/rotate`, which may be empty.
+#[derive(Deserialize, Default)]
+struct RotateApiKey {
+    #[serde(default)]
+    grace_seconds: Option<u64>,
+}
+
+/// Admin endpoints always require a valid token with [`ADMIN_ROLE`], whatever
+/// the auth policy of the path says.
+pub async fn authorize_admin(
+    req: &Request,
+    ctx: &RequestContext,
+) -> std::result::Result<AuthContext, GatewayRejection> {
+    let auth = authenticate_and_authorize(req, ctx).await?;
+    if !auth.roles.iter().any(|r| r == ADMIN_ROLE) {
+        return Err(GatewayRejection::Forbidden {
+            message: format!("Requires the {ADMIN_ROLE} role"),
+        });
+    }
+    Ok(auth)
+}
+
+/// `GET /api/gateway/admin/policies`: the effective policy of every
+/// configured service and of every route that overrides it.
+pub async fn handle_policies(req: Request, _params: Params) -> Result<Response> {
+    let ctx = build_request_context(&req)?;
+    if let Err(rej) = authorize_admin(&req, &ctx).await {
+        return Ok(rej.into_response(&ctx));
+    }
+
+    let cfg = get_config()?;
+    let services: serde_json::Map<String, Value> = cfg
+        .services
+        .iter()
+        .map(|(name, svc)| {
+            let policy = cfg.defaults.policy.apply(&svc.policy);
+            Ok((name.clone(), serde_json::to_value(policy)?))
+        })
+        .collect::<Result<_>>()?;
+
+    let mut routes = Vec::new();
+    for (id, route) in cfg.route_table.entries() {
+        let service_policy = match cfg.services.get(&route.service) {
+            Some(svc) => cfg.defaults.policy.apply(&svc.policy),
+            None => cfg.defaults.policy.clone(),
+        };
+        routes.push(json!({
+            "route": id,
+            "service": route.service,
+            "policy": service_policy.apply(&route.policy),
+        }));
+    }
+
+    json_response(
+        &ctx,
+        200,
+        &json!({
+            "source": config_source().as_str(),
+            "defaults": cfg.defaults.policy,
+            "services": services,
+            "routes": routes,
+        }),
+    )
+}
+
+/// `GET /api/gateway/admin/services`: configured and registered services,
+/// each probed at its health check URL.
+pub async fn handle_services(req: Request, _params: Params) -> Result<Response> {
+    let ctx = build_request_context(&req)?;
+    if let Err(rej) = authorize_admin(&req, &ctx).await {
+        return Ok(rej.into_response(&ctx));
+    }
+
+    let cfg = get_config()?;
+    let mut services = Vec::new();
+    for (name, svc) in &cfg.services {
+        let health_check = svc.health_check_url();
+        services.push(json!({
+            "name": name,
+            "source": "config",
+            "url": svc.url,
+            "health_check": health_check,
+            "health": probe(&ctx, name, &health_check),
+        }));
+    }
+    for registration in registered_services()? {
+        let health_check = if registration.health_check.starts_with("http") {
+            registration.health_check.clone()
+        } else {
+            format!(
+                "{}{}",
+                registration.url.trim_end_matches('/'),
+                registration.health_check
+            )
+        };
+        services.push(json!({
+            "name": registration.name,
+            "source": "registry",
+            "url": registration.url,
+            "registered_at": registration.registered_at,
+            "health_check": health_check,
+            "health": probe(&ctx, &registration.name, &health_check),
+        }));
+    }
+
+    json_response(&ctx, 200, &json!(services))
+}
+
+/// `GET /api/gateway/admin/config` describes the active configuration.
+/// `PUT` validates a `gateway.toml` body and stores it in the key-value
+/// store, from where the next request loads it; `DELETE` drops the stored
+/// copy so the deployed file applies again.
+pub async fn handle_config(req: Request, _params: Params) -> Result<Response> {
+    let ctx = build_request_context(&req)?;
+    let auth = match authorize_admin(&req, &ctx).await {
+        Ok(auth) => auth,
+        Err(rej) => return Ok(rej.into_response(&ctx)),
+    };
+
+    match req.method() {
+        spin_sdk::http::Method::Put => {
+            let Ok(text) = std::str::from_utf8(req.body()) else {
+                return Ok(GatewayRejection::UnprocessableEntity {
+                    message: "Gateway config must be UTF-8".to_string(),
+                }
+                .into_response(&ctx));
+            };
+            let cfg = match store_config(text) {
+                Ok(cfg) => cfg,
+                Err(err) => {
+                    return Ok(GatewayRejection::UnprocessableEntity {
+                        message: format!("{err:#}"),
+                    }
+                    .into_response(&ctx))
+                }
+            };
+            audit(&ctx, &auth, "config_stored");
+            json_response(
+                &ctx,
+                200,
+                &json!({
+                    "source": "store",
+                    "services": cfg.services.len(),
+                    "routes": cfg.routes.len(),
+                    "applies": "next_request",
+                }),
+            )
+        }
+        spin_sdk::http::Method::Delete => {
+            clear_stored_config()?;
+            audit(&ctx, &auth, "config_cleared");
+            json_response(
+                &ctx,
+                200,
+                &json!({ "source": "file", "applies": "next_request" }),
+            )
+        }
+        _ => {
+            let cfg = get_config()?;
+            json_response(
+                &ctx,
+                200,
+                &json!({
+                    "source": config_source().as_str(),
+                    "services": cfg.services.len(),
+                    "routes": cfg.routes.len(),
+                }),
+            )
+        }
+    }
+}
+
+/// `GET /api/gateway/admin/circuit-breakers`: the breaker of every service
+/// that has one enabled.
+pub async fn handle_circuit_breakers(req: Request, _params: Params) -> Result<Response> {
+    let ctx = build_request_context(&req)?;
+    if let Err(rej) = authorize_admin(&req, &ctx).await {
+        return Ok(rej.into_response(&ctx));
+    }
+
+    let conn = match variables::get(REDIS_ADDRESS_VAR)
+        .map_err(anyhow::Error::from)
+        .and_then(|address| Ok(Connection::open(&address)?))
+    {
+        Ok(conn) => conn,
+        Err(e) => {
+            return Ok(GatewayRejection::ServiceUnavailable {
+                message: format!("Circuit breaker backend unavailable: {e}"),
+            }
+            .into_response(&ctx))
+        }
+    };
+
+    let cfg = get_config()?;
+    let breakers: Vec<_> = cfg
+        .services
+        .iter()
+        .filter_map(|(name, svc)| {
+            let policy = cfg.defaults.policy.apply(&svc.policy).circuit_breaker;
+            policy
+                .enabled
+                .then(|| circuit_breaker::snapshot(&conn, name, &policy.settings()))
+        })
+        .collect();
+
+    json_response(&ctx, 200, &json!(breakers))
+}
+
+/// `GET /api/gateway/admin/api-keys` lists the keys; `POST` issues one and
+/// returns it, the only time the key itself is shown.
+pub async fn handle_api_keys(req: Request, _params: Params) -> Result<Response> {
+    let ctx = build_request_context(&req)?;
+    let auth = match authorize_admin(&req, &ctx).await {
+        Ok(auth) => auth,
+        Err(rej) => return Ok(rej.into_response(&ctx)),
+    };
+
+    if !matches!(req.method(), spin_sdk::http::Method::Post) {
+        let keys: Vec<_> = api_keys::list()?.iter().map(api_keys::summary).collect();
+        return json_response(&ctx, 200, &json!(keys));
+    }
+
+    let body: NewApiKey = match serde_json::from_slice(req.body()) {
+        Ok(body) => body,
+        Err(err) => {
+            return Ok(GatewayRejection::UnprocessableEntity {
+                message: format!("Invalid API key request: {err}"),
+            }
+            .into_response(&ctx))
+        }
+    };
+    if body.name.trim().is_empty() {
+        return Ok(GatewayRejection::UnprocessableEntity {
+            message: "API keys need a name".to_string(),
+        }
+        .into_response(&ctx));
+    }
+
+    let issued = api_keys::issue(body.name, body.scopes, body.roles, body.expires_at)?;
+    audit_api_key(&ctx, &auth, "api_key_issued", &issued.record.id);
+    let mut view = api_keys::summary(&issued.record);
+    view["key"] = json!(issued.key);
+    json_response(&ctx, 201, &view)
+}
+
+/// `POST /api/gateway/admin/api-keys/:id/rotate`: a new key for the same
+/// record; the old one is accepted for `grace_seconds` more.
+pub async fn handle_api_key_rotate(req: Request, params: Params) -> Result<Response> {
+    let ctx = build_request_context(&req)?;
+    let auth = match authorize_admin(&req, &ctx).await {
+        Ok(auth) => auth,
+        Err(rej) => return Ok(rej.into_response(&ctx)),
+    };
+    let id = params.get("id").unwrap_or_default();
+
+    let body: RotateApiKey = if req.body().is_empty() {
+        RotateApiKey::default()
+    } else {
+        match serde_json::from_slice(req.body()) {
+            Ok(body) => body,
+            Err(err) => {
+                return Ok(GatewayRejection::UnprocessableEntity {
+                    message: format!("Invalid rotation request: {err}"),
+                }
+                .into_response(&ctx))
+            }
+        }
+    };
+    let grace_seconds = body.grace_seconds.unwrap_or(DEFAULT_ROTATION_GRACE_SECONDS);
+
+    let Some(issued) = api_keys::rotate(id, grace_seconds)? else {
+        return Ok(GatewayRejection::NotFound {
+            message: "Unknown or revoked API key".to_string(),
+        }
+        .into_response(&ctx));
+    };
+    audit_api_key(&ctx, &auth, "api_key_rotated", id);
+    let mut view = api_keys::summary(&issued.record);
+    view["key"] = json!(issued.key);
+    json_response(&ctx, 200, &view)
+}
+
+/// `DELETE /api/gateway/admin/api-keys/:id` revokes a key, and any previous
+/// key still in its grace period, at once.
+pub async fn handle_api_key_revoke(req: Request, params: Params) -> Result<Response> {
+    let ctx = build_request_context(&req)?;
+    let auth = match authorize_admin(&req, &ctx).await {
+        Ok(auth) => auth,
+        Err(rej) => return Ok(rej.into_response(&ctx)),
+    };
+    let id = params.get("id").unwrap_or_default();
+
+    let Some(record) = api_keys::revoke(id)? else {
+        return Ok(GatewayRejection::NotFound {
+            message: "Unknown API key".to_string(),
+        }
+        .into_response(&ctx));
+    };
+    audit_api_key(&ctx, &auth, "api_key_revoked", id);
+    json_response(&ctx, 200, &api_keys::summary(&record))
+}
+
+fn probe(ctx: &RequestContext, service: &str, url: &str) -> Value {
+    let mut probe_ctx = ctx.clone();
+    probe_ctx.service = service.to_string();
+    let policy = &mut probe_ctx.policy.upstream;
+    policy.timeout_ms = PROBE_TIMEOUT_MS;
+    policy.connect_timeout_ms = policy.connect_timeout_ms.min(PROBE_TIMEOUT_MS);
+    policy.retries = 0;
+    policy.hedge_after_ms = None;
+
+    let request = UpstreamRequest {
+        method: "GET".to_string(),
+        url: url.to_string(),
+        headers: vec![(
+            CORRELATION_ID_HEADER.to_string(),
+            ctx.correlation_id.clone().into_bytes(),
+        )],
+        body: Vec::new(),
+    };
+    let started = Instant::now();
+    let result = upstream::send(&request, &probe_ctx);
+    let latency_ms = u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX);
+
+    match result {
+        Ok(response) => json!({
+            "status": if (200..300).contains(response.status()) { "up" } else { "down" },
+            "http_status": response.status(),
+            "latency_ms": latency_ms,
+        }),
+        Err(UpstreamError::Timeout) => json!({
+            "status": "down",
+            "error": "timeout",
+            "latency_ms": latency_ms,
+        }),
+        Err(UpstreamError::Failed(reason)) => json!({
+            "status": "down",
+            "error": reason,
+            "latency_ms": latency_ms,
+        }),
+    }
+}
+
+fn audit(ctx: &RequestContext, auth: &AuthContext, action: &str) {
+    event!(
+        Level::WARN,
+        correlation_id = ctx.correlation_id,
+        trace_id = ctx.trace_id,
+        admin = auth.subject.as_deref().unwrap_or("unknown"),
+        action = action
+    );
+}
+
+fn audit_api_key(ctx: &RequestContext, auth: &AuthContext, action: &str, key_id: &str) {
+    event!(
+        Level::WARN,
+        correlation_id = ctx.correlation_id,
+        trace_id = ctx.trace_id,
+        admin = auth.subject.as_deref().unwrap_or("unknown"),
+        action = action,
+        key_id = key_id
+    );
+}
+
+fn json_response(ctx: &RequestContext, status: u16, body: &Value) -> Result<Response> {
+    let resp = ResponseBuilder::new(status)
+        .header("content-type", "application/json")
+        .body(serde_json::to_string(body)?)
+        .build();
+    Ok(apply_security_headers(resp, &ctx.policy))
+}
@@ -0,0 +1,194 @@
+use crate::{
+    context::{AuthContext, RequestContext, SERVICE_REGISTRY_STORE},
+    rejection::GatewayRejection,
+};
+use anyhow::{Context, Result};
+use api_gateway_core::{format_api_key, hex_id, parse_api_key, ApiKeyMatch, ApiKeyRecord};
+use ring::{
+    digest::{digest, SHA256},
+    rand::{SecureRandom, SystemRandom},
+};
+use spin_sdk::key_value::Store;
+use std::collections::HashMap;
+use tracing::{event, Level};
+
+/// Header machine clients send their key in; already allowed by CORS.
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Each key's [`ApiKeyRecord`] is kept in the registry store under
+/// `apikey:{id}`.
+const API_KEY_PREFIX: &str = "apikey:";
+
+/// What a new or rotated key is handed out as. The key itself is only ever
+/// returned here; the store keeps its hash.
+pub struct IssuedKey {
+    pub key: String,
+    pub record: ApiKeyRecord,
+}
+
+/// Resolves an `X-API-Key` to the roles and scopes of its record.
+pub fn authenticate(key: &str, ctx: &RequestContext) -> Result<AuthContext, GatewayRejection> {
+    let unauthorized = |message: &str| GatewayRejection::Unauthorized {
+        message: message.to_string(),
+    };
+    let Some((id, _)) = parse_api_key(key) else {
+        return Err(unauthorized("Malformed API key"));
+    };
+    let record = load(id)
+        .map_err(|e| GatewayRejection::ServiceUnavailable {
+            message: format!("API key store unavailable: {e}"),
+        })?
+        .ok_or_else(|| unauthorized("Unknown API key"))?;
+
+    let matched = record
+        .check(&hash_key(key), now())
+        .map_err(|e| unauthorized(e.as_str()))?;
+    if matched == ApiKeyMatch::Previous {
+        event!(
+            Level::WARN,
+            correlation_id = ctx.correlation_id,
+            trace_id = ctx.trace_id,
+            service = ctx.service,
+            action = "api_key_previous_used",
+            key_id = record.id
+        );
+    }
+
+    let subject = format!("apikey:{}", record.id);
+    let mut claims_for_headers = HashMap::new();
+    claims_for_headers.insert("sub".to_string(), subject.clone());
+    claims_for_headers.insert("name".to_string(), record.name.clone());
+    claims_for_headers.insert("auth".to_string(), "api_key".to_string());
+    if !record.roles.is_empty() {
+        claims_for_headers.insert("roles".to_string(), record.roles.join(","));
+    }
+    if !record.scopes.is_empty() {
+        claims_for_headers.insert("scope".to_string(), record.scopes.join(" "));
+    }
+
+    Ok(AuthContext {
+        claims_for_headers,
+        subject: Some(subject),
+        issuer: None,
+        audiences: Vec::new(),
+        scopes: record.scopes,
+        roles: record.roles,
+    })
+}
+
+/// Creates a key; `expires_at` is in Unix seconds.
+pub fn issue(
+    name: String,
+    scopes: Vec<String>,
+    roles: Vec<String>,
+    expires_at: Option<u64>,
+) -> Result<IssuedKey> {
+    let id = hex_id(&random::<6>()?);
+    let key = format_api_key(&id, &hex_id(&random::<32>()?));
+    let record = ApiKeyRecord {
+        id,
+        name,
+        hash: hash_key(&key),
+        scopes,
+        roles,
+        created_at: now(),
+        expires_at,
+        revoked_at: None,
+        rotated_at: None,
+        previous_hash: None,
+        previous_expires_at: None,
+    };
+    save(&record)?;
+    Ok(IssuedKey { key, record })
+}
+
+/// Gives key `id` a new secret; the old one keeps working for
+/// `grace_seconds`. `None` when there is no such key, or it was revoked.
+pub fn rotate(id: &str, grace_seconds: u64) -> Result<Option<IssuedKey>> {
+    let Some(mut record) = load(id)? else {
+        return Ok(None);
+    };
+    if record.revoked_at.is_some() {
+        return Ok(None);
+    }
+    let key = format_api_key(&record.id, &hex_id(&random::<32>()?));
+    record.rotate(hash_key(&key), now(), grace_seconds);
+    save(&record)?;
+    Ok(Some(IssuedKey { key, record }))
+}
+
+/// Revokes key `id`. The record is kept so its use can still be audited.
+pub fn revoke(id: &str) -> Result<Option<ApiKeyRecord>> {
+    let Some(mut record) = load(id)? else {
+        return Ok(None);
+    };
+    record.revoke(now());
+    save(&record)?;
+    Ok(Some(record))
+}
+
+pub fn list() -> Result<Vec<ApiKeyRecord>> {
+    let store = Store::open(SERVICE_REGISTRY_STORE)?;
+    let mut records = Vec::new();
+    for key in store.get_keys()? {
+        let Some(id) = key.strip_prefix(API_KEY_PREFIX) else {
+            continue;
+        };
+        if let Some(record) = load(id)? {
+            records.push(record);
+        }
+    }
+    records.sort_by(|a, b| a.created_at.cmp(&b.created_at).then(a.id.cmp(&b.id)));
+    Ok(records)
+}
+
+/// A record as the admin API shows it: everything but the hashes.
+pub fn summary(record: &ApiKeyRecord) -> serde_json::Value {
+    serde_json::json!({
+        "id": record.id,
+        "name": record.name,
+        "scopes": record.scopes,
+        "roles": record.roles,
+        "created_at": record.created_at,
+        "expires_at": record.expires_at,
+        "revoked_at": record.revoked_at,
+        "rotated_at": record.rotated_at,
+        "previous_expires_at": record.previous_expires_at,
+        "active": record.is_active(now()),
+    })
+}
+
+fn load(id: &str) -> Result<Option<ApiKeyRecord>> {
+    let store = Store::open(SERVICE_REGISTRY_STORE)?;
+    let Some(bytes) = store.get(&format!("{API_KEY_PREFIX}{id}"))? else {
+        return Ok(None);
+    };
+    let record = serde_json::from_slice(&bytes).context("api_key_record_invalid")?;
+    Ok(Some(record))
+}
+
+fn save(record: &ApiKeyRecord) -> Result<()> {
+    let store = Store::open(SERVICE_REGISTRY_STORE)?;
+    store.set(
+        &format!("{API_KEY_PREFIX}{}", record.id),
+        &serde_json::to_vec(record)?,
+    )?;
+    Ok(())
+}
+
+fn hash_key(key: &str) -> String {
+    hex_id(digest(&SHA256, key.trim().as_bytes()).as_ref())
+}
+
+/// Key material must come from the system RNG; there is no fallback.
+fn random<const N: usize>() -> Result<[u8; N]> {
+    let mut bytes = [0u8; N];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| anyhow::anyhow!("rng_unavailable"))?;
+    Ok(bytes)
+}
+
+fn now() -> u64 {
+    u64::try_from(chrono::Utc::now().timestamp()).unwrap_or(0)
+}
@@ -1,14 +1,24 @@
 use crate::{
-    context::{AuthContext, RequestContext, REDIS_ADDRESS_VAR},
+    api_keys::{self, API_KEY_HEADER},
+    context::{AuthContext, RequestContext, REDIS_ADDRESS_VAR, SERVICE_REGISTRY_STORE},
     rejection::GatewayRejection,
 };
 use anyhow::{Context as AnyhowContext, Result};
 use serde::Deserialize;
-use spin_sdk::{http::Method, variables};
+use spin_sdk::{http::Method, key_value::Store, variables};
 use std::collections::HashMap;
 use tracing::{event, Level};
 
-use crate::jwks_client::keyset::KeyStore;
+use crate::jwks_client::{
+    cache::resolve_key_store,
+    keyset::token_kid,
+    remote::{HttpKeySetFetcher, RedisKeySetCache},
+};
+
+/// Sessions the auth-service has signed out are listed in the shared store as
+/// `revoked_session:{sid}` until the last access token they could carry has
+/// expired.
+const REVOKED_SESSION_PREFIX: &str = "revoked_session:";
 
 #[derive(Debug, Deserialize)]
 struct OpenIdConfiguration {
@@ -26,38 +36,20 @@ pub async fn authenticate_and_authorize(
     let mut parts = auth_header.split_whitespace();
     let scheme = parts.next().unwrap_or("");
     let token = parts.next().unwrap_or("");
-    if scheme.to_ascii_lowercase() != "bearer" || token.is_empty() {
+    let api_key = req
+        .header(API_KEY_HEADER)
+        .and_then(|h| h.as_str())
+        .filter(|k| !k.trim().is_empty());
+
+    let auth_ctx = if scheme.eq_ignore_ascii_case("bearer") && !token.is_empty() {
+        validate_bearer(token, ctx).await?
+    } else if let (true, Some(key)) = (ctx.policy.auth.api_keys, api_key) {
+        api_keys::authenticate(key, ctx)?
+    } else {
         return Err(GatewayRejection::Unauthorized {
             message: "Missing or invalid Authorization header".to_string(),
         });
-    }
-
-    let auth_ctx = validate_jwt(token, ctx, ctx.policy.auth.oidc_url.as_str())
-        .await
-        .map_err(|e| GatewayRejection::Unauthorized {
-            message: format!("JWT validation failed: {e}"),
-        })?;
-
-    if let Some(required_issuer) = ctx.policy.auth.required_issuer.as_ref() {
-        if auth_ctx
-            .issuer
-            .as_ref()
-            .map(|v| v != required_issuer)
-            .unwrap_or(true)
-        {
-            return Err(GatewayRejection::Forbidden {
-                message: "Issuer not allowed".to_string(),
-            });
-        }
-    }
-
-    if let Some(required_audience) = ctx.policy.auth.required_audience.as_ref() {
-        if !auth_ctx.audiences.iter().any(|a| a == required_audience) {
-            return Err(GatewayRejection::Forbidden {
-                message: "Audience not allowed".to_string(),
-            });
-        }
-    }
+    };
 
     for required in ctx.policy.auth.required_scopes.iter() {
         if !auth_ctx.scopes.iter().any(|s| s == required) {
@@ -90,6 +82,41 @@ pub async fn authenticate_and_authorize(
     Ok(auth_ctx)
 }
 
+/// Verifies an OIDC bearer token and the issuer and audience the policy
+/// requires of it.
+async fn validate_bearer(
+    token: &str,
+    ctx: &RequestContext,
+) -> std::result::Result<AuthContext, GatewayRejection> {
+    let auth_ctx = validate_jwt(token, ctx, ctx.policy.auth.oidc_url.as_str())
+        .await
+        .map_err(|e| GatewayRejection::Unauthorized {
+            message: format!("JWT validation failed: {e}"),
+        })?;
+
+    if let Some(required_issuer) = ctx.policy.auth.required_issuer.as_ref() {
+        if auth_ctx
+            .issuer
+            .as_ref()
+            .is_none_or(|v| v != required_issuer)
+        {
+            return Err(GatewayRejection::Forbidden {
+                message: "Issuer not allowed".to_string(),
+            });
+        }
+    }
+
+    if let Some(required_audience) = ctx.policy.auth.required_audience.as_ref() {
+        if !auth_ctx.audiences.iter().any(|a| a == required_audience) {
+            return Err(GatewayRejection::Forbidden {
+                message: "Audience not allowed".to_string(),
+            });
+        }
+    }
+
+    Ok(auth_ctx)
+}
+
 async fn validate_jwt(token: &str, ctx: &RequestContext, oidc_url: &str) -> Result<AuthContext> {
     let cache_key = format!("jwks:{}", oidc_url);
 
@@ -140,14 +167,27 @@ async fn verify_with_jwks(
     token: &str,
     ctx: &RequestContext,
 ) -> Result<AuthContext> {
-    let key_store = KeyStore::new_from(jwks_uri.to_string())
-        .await
-        .with_context(|| format!("Failed to load JWKS from {jwks_uri}"))?;
+    let kid = token_kid(token);
+    let key_store = resolve_key_store(
+        &RedisKeySetCache::open(),
+        &HttpKeySetFetcher,
+        jwks_uri,
+        kid.as_deref(),
+        u64::try_from(chrono::Utc::now().timestamp()).unwrap_or(0),
+    )
+    .await
+    .with_context(|| format!("Failed to load JWKS from {jwks_uri}"))?;
 
     let jwt = key_store
         .verify(token)
         .map_err(|e| anyhow::anyhow!("JWT verification failed: {:?}", e))?;
 
+    if let Some(sid) = jwt.payload().get_str("sid") {
+        if session_revoked(sid)? {
+            anyhow::bail!("Session has been revoked");
+        }
+    }
+
     event!(
         Level::INFO,
         correlation_id = ctx.correlation_id,
@@ -190,6 +230,9 @@ async fn verify_with_jwks(
     } else if let Some(role) = jwt.payload().get_str("role") {
         roles.push(role.to_string());
     }
+    if !roles.is_empty() {
+        claims_for_headers.insert("roles".to_string(), roles.join(","));
+    }
 
     let mut scopes = Vec::new();
     if let Some(scope) = jwt.payload().get_str("scope") {
@@ -211,3 +254,8 @@ async fn verify_with_jwks(
         roles,
     })
 }
+
+fn session_revoked(sid: &str) -> Result<bool> {
+    let store = Store::open(SERVICE_REGISTRY_STORE)?;
+    Ok(store.exists(&format!("{REVOKED_SESSION_PREFIX}{sid}"))?)
+}
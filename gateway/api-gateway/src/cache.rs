@@ -1,35 +1,68 @@
-use crate::context::{AuthContext, RequestContext, CORRELATION_ID_HEADER, TRACE_ID_HEADER};
+use crate::context::{
+    get_config, AuthContext, RequestContext, CORRELATION_ID_HEADER, REDIS_ADDRESS_VAR,
+    TRACE_ID_HEADER,
+};
 use anyhow::{Context, Result};
+use api_gateway_core::{
+    cache_state, event_matches, freshness, if_none_match, vary_fragment, CacheState, Freshness,
+};
+use ring::digest::{digest, SHA256};
 use serde::{Deserialize, Serialize};
-use spin_sdk::{http::Response, http::ResponseBuilder};
+use spin_sdk::{
+    http::{Request, Response, ResponseBuilder},
+    redis::{Connection, RedisParameter, RedisResult},
+    variables,
+};
+use std::fmt::Write;
+use tracing::{event, Level};
+
+/// How long one request may take to refresh a stale entry before another
+/// request is allowed to try.
+const REFRESH_LOCK_SECONDS: i64 = 10;
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 struct CachedResponse {
     status: u16,
-    content_type: String,
+    headers: Vec<(String, String)>,
     body: Vec<u8>,
+    etag: String,
+    stored_at: u64,
+    ttl_seconds: u64,
+    stale_seconds: u64,
+}
+
+impl CachedResponse {
+    fn freshness(&self) -> Freshness {
+        Freshness {
+            ttl_seconds: self.ttl_seconds,
+            stale_seconds: self.stale_seconds,
+        }
+    }
+
+    fn cache_control(&self) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(name, _)| name == "cache-control")
+            .map(|(_, value)| value.as_str())
+    }
 }
 
+/// Serves a request from the cache. A stale entry is still served while
+/// another request refreshes it; the request that wins the refresh lock gets
+/// `None` and goes upstream.
 #[allow(clippy::unused_async)]
 pub async fn try_cache_hit(
     redis_address: &str,
-    req: &spin_sdk::http::Request,
+    req: &Request,
     ctx: &RequestContext,
     auth: Option<&AuthContext>,
 ) -> Result<Option<Response>> {
-    let method = req.method().to_string();
-    if !ctx
-        .policy
-        .cache
-        .methods
-        .iter()
-        .any(|m| m.eq_ignore_ascii_case(&method))
-    {
+    if !is_cacheable_method(req, ctx) {
         return Ok(None);
     }
 
-    let conn = spin_sdk::redis::Connection::open(redis_address).context("redis_open_failed")?;
-    let key = cache_key(req, ctx, auth);
+    let conn = Connection::open(redis_address).context("redis_open_failed")?;
+    let key = cache_key(&conn, req, ctx, auth);
     let Some(bytes) = conn.get(&key).context("cache_get_failed")? else {
         return Ok(None);
     };
@@ -40,83 +73,279 @@ pub async fn try_cache_hit(
     let cached: CachedResponse =
         serde_json::from_slice(&bytes).context("cache_deserialize_failed")?;
 
-    let mut response = ResponseBuilder::new(cached.status)
-        .header("content-type", cached.content_type)
-        .header("x-cache", "HIT")
-        .body(cached.body)
-        .build();
+    let now = now_unix();
+    let label = match cache_state(cached.stored_at, cached.freshness(), now) {
+        CacheState::Fresh => "HIT",
+        CacheState::Stale => {
+            if claim_refresh(&conn, &key) {
+                event!(
+                    Level::INFO,
+                    correlation_id = ctx.correlation_id,
+                    trace_id = ctx.trace_id,
+                    service = ctx.service,
+                    action = "cache_revalidate"
+                );
+                return Ok(None);
+            }
+            "STALE"
+        }
+        CacheState::Expired => return Ok(None),
+    };
 
+    let mut response = if request_matches_etag(req, &cached.etag) {
+        not_modified(&cached.etag, cached.cache_control())
+    } else {
+        let mut builder = ResponseBuilder::new(cached.status);
+        for (name, value) in &cached.headers {
+            builder.header(name.as_str(), value.as_str());
+        }
+        builder
+            .header("etag", cached.etag.as_str())
+            .body(cached.body)
+            .build()
+    };
+    response.set_header("age", now.saturating_sub(cached.stored_at).to_string());
+    response.set_header("x-cache", label);
     response.set_header(CORRELATION_ID_HEADER, ctx.correlation_id.clone());
     response.set_header(TRACE_ID_HEADER, ctx.trace_id.clone());
 
     Ok(Some(response))
 }
 
+/// Stores a cacheable upstream response and returns it with a strong `ETag`,
+/// or a `304 Not Modified` when the request's `If-None-Match` already names
+/// that tag. Responses the policy or the upstream rule out are returned as
+/// they are.
 #[allow(clippy::unused_async)]
 pub async fn try_cache_store(
     redis_address: &str,
-    req: &spin_sdk::http::Request,
-    response: &Response,
+    req: &Request,
+    mut response: Response,
     ctx: &RequestContext,
     auth: Option<&AuthContext>,
-) -> Result<()> {
-    let method = req.method().to_string();
-    if !ctx
-        .policy
-        .cache
-        .methods
+) -> Response {
+    let policy = &ctx.policy.cache;
+    if !is_cacheable_method(req, ctx) {
+        return response;
+    }
+
+    let header = |name: &str| {
+        response
+            .header(name)
+            .and_then(|h| h.as_str())
+            .map(str::to_string)
+    };
+    let cache_control = header("cache-control");
+    let Some(fresh) = freshness(
+        *response.status(),
+        cache_control.as_deref(),
+        policy.ttl_seconds,
+        policy.stale_while_revalidate_seconds,
+    ) else {
+        return response;
+    };
+    if header("set-cookie").is_some() || response.body().len() > policy.max_body_bytes {
+        return response;
+    }
+    // The key only distinguishes the policy's `vary_headers`; anything else
+    // the upstream varies on would be served to the wrong clients.
+    if let Some(vary) = header("vary") {
+        let keyed = vary.split(',').map(str::trim).all(|name| {
+            policy
+                .vary_headers
+                .iter()
+                .any(|v| v.eq_ignore_ascii_case(name))
+        });
+        if !keyed {
+            return response;
+        }
+    }
+
+    let etag = header("etag")
+        .filter(|tag| !tag.starts_with("W/"))
+        .unwrap_or_else(|| strong_etag(response.body()));
+
+    let headers = policy
+        .stored_headers
         .iter()
-        .any(|m| m.eq_ignore_ascii_case(&method))
-    {
-        return Ok(());
+        .filter_map(|name| {
+            let name = name.to_ascii_lowercase();
+            header(&name).map(|value| (name, value))
+        })
+        .collect();
+    let cached = CachedResponse {
+        status: *response.status(),
+        headers,
+        body: response.body().to_vec(),
+        etag: etag.clone(),
+        stored_at: now_unix(),
+        ttl_seconds: fresh.ttl_seconds,
+        stale_seconds: fresh.stale_seconds,
+    };
+    if let Err(err) = store(redis_address, req, ctx, auth, &cached) {
+        event!(
+            Level::WARN,
+            correlation_id = ctx.correlation_id,
+            trace_id = ctx.trace_id,
+            service = ctx.service,
+            action = "cache_store_failed",
+            error = %err
+        );
     }
 
-    let status = *response.status();
-    if !(200..300).contains(&status) {
-        return Ok(());
+    if request_matches_etag(req, &etag) {
+        return not_modified(&etag, cache_control.as_deref());
     }
+    response.set_header("etag", etag);
+    response.set_header("x-cache", "MISS");
+    response
+}
 
-    let body = response.body().to_vec();
-    if body.len() > ctx.policy.cache.max_body_bytes {
-        return Ok(());
+/// Drops the cached responses of every service whose `purge_on` patterns
+/// match one of `events`. Entries are not deleted one by one: the service's
+/// generation counter, part of every key, is bumped so old entries are no
+/// longer found and expire on their own.
+pub fn purge_for_events(events: &[serde_json::Value]) {
+    let types: Vec<&str> = events
+        .iter()
+        .filter_map(|e| e.get("type")?.as_str())
+        .collect();
+    if types.is_empty() {
+        return;
     }
+    let Ok(cfg) = get_config() else {
+        return;
+    };
 
-    let content_type = response
-        .header("content-type")
-        .and_then(|h| h.as_str())
-        .unwrap_or("application/octet-stream")
-        .to_string();
+    let services: Vec<&String> = cfg
+        .services
+        .iter()
+        .filter(|(_, svc)| {
+            let policy = cfg.defaults.policy.apply(&svc.policy).cache;
+            policy.enabled
+                && policy
+                    .purge_on
+                    .iter()
+                    .any(|pattern| types.iter().any(|t| event_matches(pattern, t)))
+        })
+        .map(|(name, _)| name)
+        .collect();
+    if services.is_empty() {
+        return;
+    }
 
-    let cached = CachedResponse {
-        status,
-        content_type,
-        body,
+    let Ok(address) = variables::get(REDIS_ADDRESS_VAR) else {
+        return;
+    };
+    let Ok(conn) = Connection::open(&address) else {
+        return;
     };
+    for service in services {
+        if conn.incr(&generation_key(service)).is_ok() {
+            event!(Level::INFO, service = %service, action = "cache_purged");
+        }
+    }
+}
 
-    let conn = spin_sdk::redis::Connection::open(redis_address).context("redis_open_failed")?;
-    let key = cache_key(req, ctx, auth);
-    let bytes = serde_json::to_vec(&cached)?;
+fn store(
+    redis_address: &str,
+    req: &Request,
+    ctx: &RequestContext,
+    auth: Option<&AuthContext>,
+    cached: &CachedResponse,
+) -> Result<()> {
+    let conn = Connection::open(redis_address).context("redis_open_failed")?;
+    let key = cache_key(&conn, req, ctx, auth);
+    let bytes = serde_json::to_vec(cached)?;
     conn.set(&key, &bytes).context("cache_set_failed")?;
 
     let _ = conn.execute(
         "EXPIRE",
         &[
-            spin_sdk::redis::RedisParameter::Binary(key.as_bytes().to_vec()),
-            spin_sdk::redis::RedisParameter::Int64(ctx.policy.cache.ttl_seconds as i64),
+            RedisParameter::Binary(key.as_bytes().to_vec()),
+            RedisParameter::Int64(
+                i64::try_from(cached.ttl_seconds + cached.stale_seconds).unwrap_or(i64::MAX),
+            ),
         ],
     );
+    let _ = conn.del(&[refresh_key(&key)]);
 
     Ok(())
 }
 
+fn is_cacheable_method(req: &Request, ctx: &RequestContext) -> bool {
+    let method = req.method().to_string();
+    ctx.policy
+        .cache
+        .methods
+        .iter()
+        .any(|m| m.eq_ignore_ascii_case(&method))
+}
+
+fn request_matches_etag(req: &Request, etag: &str) -> bool {
+    req.header("if-none-match")
+        .and_then(|h| h.as_str())
+        .is_some_and(|h| if_none_match(h, etag))
+}
+
+fn not_modified(etag: &str, cache_control: Option<&str>) -> Response {
+    let mut builder = ResponseBuilder::new(304);
+    builder.header("etag", etag);
+    if let Some(cc) = cache_control {
+        builder.header("cache-control", cc);
+    }
+    builder.body(Vec::new()).build()
+}
+
+fn strong_etag(body: &[u8]) -> String {
+    let hash = digest(&SHA256, body).as_ref()[..16]
+        .iter()
+        .fold(String::new(), |mut hex, b| {
+            let _ = write!(hex, "{b:02x}");
+            hex
+        });
+    format!("\"{hash}\"")
+}
+
+fn claim_refresh(conn: &Connection, key: &str) -> bool {
+    let result = conn.execute(
+        "SET",
+        &[
+            RedisParameter::Binary(refresh_key(key).into_bytes()),
+            RedisParameter::Binary(b"1".to_vec()),
+            RedisParameter::Binary(b"NX".to_vec()),
+            RedisParameter::Binary(b"EX".to_vec()),
+            RedisParameter::Int64(REFRESH_LOCK_SECONDS),
+        ],
+    );
+    match result {
+        Ok(values) => !matches!(values.first(), Some(RedisResult::Nil) | None),
+        // Without Redis nobody can coordinate; refresh rather than serve stale.
+        Err(_) => true,
+    }
+}
+
+fn now_unix() -> u64 {
+    u64::try_from(chrono::Utc::now().timestamp()).unwrap_or(0)
+}
+
+fn generation_key(service: &str) -> String {
+    format!("cache:{service}:gen")
+}
+
+fn refresh_key(key: &str) -> String {
+    format!("{key}:refresh")
+}
+
 fn cache_key(
-    req: &spin_sdk::http::Request,
+    conn: &Connection,
+    req: &Request,
     ctx: &RequestContext,
     auth: Option<&AuthContext>,
 ) -> String {
     let query = req.query();
     let query = if query.is_empty() {
-        "".to_string()
+        String::new()
     } else {
         format!("?{query}")
     };
@@ -125,12 +354,25 @@ fn cache_key(
         .and_then(|a| a.subject.clone())
         .unwrap_or_else(|| "anon".to_string());
 
+    let generation = conn
+        .get(&generation_key(&ctx.service))
+        .ok()
+        .flatten()
+        .and_then(|v| String::from_utf8(v).ok()?.parse::<u64>().ok())
+        .unwrap_or(0);
+
+    let vary = vary_fragment(&ctx.policy.cache.vary_headers, |name| {
+        req.header(name).and_then(|h| h.as_str())
+    });
+
     format!(
-        "cache:{}:{}:{}{}:{}",
+        "cache:{}:{}:{}:{}{}:{}:{}",
         ctx.service,
+        generation,
         req.method(),
         req.path(),
         query,
-        sub
+        sub,
+        vary
     )
 }
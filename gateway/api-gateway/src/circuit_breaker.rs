@@ -1,128 +1,220 @@
-use crate::context::RequestContext;
+use crate::{
+    context::RequestContext, events::publish_events_async, rejection::GatewayRejection,
+    util::parse_redis_ints,
+};
 use anyhow::{Context, Result};
-use spin_sdk::{http::Response, http::ResponseBuilder};
-
+use api_gateway_core::{
+    is_failure_status, record_transition, BreakerAdmission, BreakerSettings, BreakerTransition,
+    CircuitState, BREAKER_ADMIT_SCRIPT, BREAKER_RECORD_SCRIPT,
+};
+use spin_sdk::{
+    http::Response,
+    redis::{Connection, RedisParameter, RedisResult},
+};
+use tracing::{event, Level};
+use uuid::Uuid;
+
+/// Rejects the request while the service's breaker is open, or half-open
+/// with all trial slots taken.
 #[allow(clippy::unused_async)]
 pub async fn precheck(redis_address: &str, ctx: &RequestContext) -> Result<Option<Response>> {
-    let conn = spin_sdk::redis::Connection::open(redis_address).context("redis_open_failed")?;
-
-    let state_key = format!("cb:{}:state", ctx.service);
-    let opened_at_key = format!("cb:{}:opened_at", ctx.service);
-    let probe_key = format!("cb:{}:probe", ctx.service);
-
-    let state = conn
-        .get(&state_key)
-        .context("cb_state_get_failed")?
-        .unwrap_or_default();
-
-    if state == b"open" {
-        let opened_at = conn
-            .get(&opened_at_key)
-            .context("cb_opened_at_get_failed")?
-            .and_then(|v| String::from_utf8(v).ok())
-            .and_then(|s| s.parse::<u64>().ok())
-            .unwrap_or(0);
-
-        let now = chrono::Utc::now().timestamp() as u64;
-        if now < opened_at.saturating_add(ctx.policy.circuit_breaker.open_seconds) {
-            let resp = ResponseBuilder::new(503)
-                .header("content-type", "application/json")
-                .header(
-                    "retry-after",
-                    ctx.policy.circuit_breaker.open_seconds.to_string(),
-                )
-                .body(
-                    serde_json::json!({
-                        "error": "Service Unavailable",
-                        "message": "Circuit open",
-                        "service": ctx.service,
-                        "correlation_id": ctx.correlation_id
-                    })
-                    .to_string(),
-                )
-                .build();
-            return Ok(Some(resp));
-        }
-
-        let _ = conn.set(&state_key, b"half_open");
-        let _ = conn.del(&[probe_key.clone()]);
+    let conn = Connection::open(redis_address).context("redis_open_failed")?;
+    let settings = ctx.policy.circuit_breaker.settings();
+
+    let reply = eval(
+        &conn,
+        BREAKER_ADMIT_SCRIPT,
+        &[state_key(&ctx.service)],
+        settings.admit_args(),
+    )
+    .context("cb_admit_eval_failed")?;
+    let admission = BreakerAdmission::from_reply(&reply).context("cb_admit_bad_reply")?;
+
+    if let Some(transition) = admission.transition {
+        announce(ctx, transition);
     }
-
-    let state = conn
-        .get(&state_key)
-        .context("cb_state_get_failed")?
-        .unwrap_or_default();
-
-    if state == b"half_open" {
-        let script = r"local ok=redis.call('SET', KEYS[1], '1', 'NX', 'EX', ARGV[1]); if ok then return 1 else return 0 end";
-        let res = conn
-            .execute(
-                "EVAL",
-                &[
-                    spin_sdk::redis::RedisParameter::Binary(script.as_bytes().to_vec()),
-                    spin_sdk::redis::RedisParameter::Binary(b"1".to_vec()),
-                    spin_sdk::redis::RedisParameter::Binary(probe_key.as_bytes().to_vec()),
-                    spin_sdk::redis::RedisParameter::Binary(b"5".to_vec()),
-                ],
-            )
-            .context("cb_probe_eval_failed")?;
-
-        let acquired = crate::util::parse_redis_int(&res).unwrap_or(0);
-        if acquired == 0 {
-            let resp = ResponseBuilder::new(503)
-                .header("content-type", "application/json")
-                .header("retry-after", "5")
-                .body(
-                    serde_json::json!({
-                        "error": "Service Unavailable",
-                        "message": "Circuit half-open",
-                        "service": ctx.service,
-                        "correlation_id": ctx.correlation_id
-                    })
-                    .to_string(),
-                )
-                .build();
-            return Ok(Some(resp));
-        }
+    if admission.allowed {
+        return Ok(None);
     }
 
-    Ok(None)
+    let message = match admission.state {
+        CircuitState::HalfOpen => "Circuit half-open",
+        _ => "Circuit open",
+    };
+    let mut resp = GatewayRejection::ServiceUnavailable {
+        message: message.to_string(),
+    }
+    .into_response(ctx);
+    resp.set_header(
+        "retry-after",
+        admission.retry_after_seconds.max(1).to_string(),
+    );
+    Ok(Some(resp))
 }
 
+/// Records the upstream's answer. Timeouts and connection errors arrive as
+/// the gateway's own 504 and 502 and count as failures.
 #[allow(clippy::unused_async)]
 pub async fn record(redis_address: &str, ctx: &RequestContext, status: u16) -> Result<()> {
-    let conn = spin_sdk::redis::Connection::open(redis_address).context("redis_open_failed")?;
-
-    let failures_key = format!("cb:{}:failures", ctx.service);
-    let state_key = format!("cb:{}:state", ctx.service);
-    let opened_at_key = format!("cb:{}:opened_at", ctx.service);
-    let probe_key = format!("cb:{}:probe", ctx.service);
-
-    let state = conn
-        .get(&state_key)
-        .context("cb_state_get_failed")?
-        .unwrap_or_default();
-
-    if status >= 500 {
-        if state == b"half_open" {
-            let now = chrono::Utc::now().timestamp() as u64;
-            let _ = conn.set(&state_key, b"open");
-            let _ = conn.set(&opened_at_key, now.to_string().as_bytes());
-            let _ = conn.del(&[failures_key, probe_key]);
-            return Ok(());
-        }
+    let conn = Connection::open(redis_address).context("redis_open_failed")?;
+    let settings = ctx.policy.circuit_breaker.settings();
+
+    let reply = eval(
+        &conn,
+        BREAKER_RECORD_SCRIPT,
+        &[state_key(&ctx.service), window_key(&ctx.service)],
+        settings.record_args(is_failure_status(status)),
+    )
+    .context("cb_record_eval_failed")?;
+
+    if let Some(transition) = record_transition(&reply) {
+        announce(ctx, transition);
+    }
+    Ok(())
+}
 
-        let failures = conn.incr(&failures_key).context("cb_incr_failed")? as u64;
-        if failures >= ctx.policy.circuit_breaker.failure_threshold {
-            let now = chrono::Utc::now().timestamp() as u64;
-            let _ = conn.set(&state_key, b"open");
-            let _ = conn.set(&opened_at_key, now.to_string().as_bytes());
-            let _ = conn.del(&[probe_key]);
+/// A breaker as reported by the admin API.
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct BreakerSnapshot {
+    pub service: String,
+    pub state: &'static str,
+    /// Unix seconds of the last transition, if the breaker ever changed.
+    pub changed_at: Option<u64>,
+    /// Requests and failures in the current window; only tracked while
+    /// closed.
+    pub window_requests: u64,
+    pub window_failures: u64,
+}
+
+pub fn snapshot(conn: &Connection, service: &str, settings: &BreakerSettings) -> BreakerSnapshot {
+    let state = hash_fields(conn, &state_key(service));
+    let field = |name: &str| {
+        state
+            .iter()
+            .find(|(k, _)| k == name)
+            .and_then(|(_, v)| v.parse::<u64>().ok())
+    };
+    let code = field("state")
+        .and_then(|c| i64::try_from(c).ok())
+        .unwrap_or(0);
+
+    let now = u64::try_from(chrono::Utc::now().timestamp()).unwrap_or(0);
+    let bucket_len = settings.bucket_seconds();
+    let oldest = now.saturating_sub(settings.window_seconds) / bucket_len;
+    let (mut requests, mut failures) = (0, 0);
+    for (name, value) in hash_fields(conn, &window_key(service)) {
+        let Some((bucket, kind)) = name.split_once(':') else {
+            continue;
+        };
+        if bucket.parse::<u64>().ok().is_none_or(|b| b <= oldest) {
+            continue;
+        }
+        let count = value.parse::<u64>().unwrap_or(0);
+        match kind {
+            "n" => requests += count,
+            "f" => failures += count,
+            _ => {}
         }
+    }
+
+    BreakerSnapshot {
+        service: service.to_string(),
+        state: CircuitState::from_code(code)
+            .unwrap_or(CircuitState::Closed)
+            .as_str(),
+        changed_at: field("changed_at"),
+        window_requests: requests,
+        window_failures: failures,
+    }
+}
+
+/// Logs a state change and publishes it as
+/// `albergue.v1.gateway.circuit_breaker.{opened,half_opened,closed}`.
+fn announce(ctx: &RequestContext, transition: BreakerTransition) {
+    if transition.to == CircuitState::Open {
+        event!(
+            Level::WARN,
+            correlation_id = ctx.correlation_id,
+            trace_id = ctx.trace_id,
+            service = ctx.service,
+            action = "circuit_breaker_transition",
+            from = transition.from.as_str(),
+            to = transition.to.as_str()
+        );
     } else {
-        let _ = conn.del(&[failures_key, opened_at_key, probe_key]);
-        let _ = conn.set(&state_key, b"closed");
+        event!(
+            Level::INFO,
+            correlation_id = ctx.correlation_id,
+            trace_id = ctx.trace_id,
+            service = ctx.service,
+            action = "circuit_breaker_transition",
+            from = transition.from.as_str(),
+            to = transition.to.as_str()
+        );
     }
 
-    Ok(())
+    let name = match transition.to {
+        CircuitState::Open => "opened",
+        CircuitState::HalfOpen => "half_opened",
+        CircuitState::Closed => "closed",
+    };
+    publish_events_async(vec![serde_json::json!({
+        "specversion": "1.0",
+        "type": format!("albergue.v1.gateway.circuit_breaker.{name}"),
+        "source": "api-gateway",
+        "id": Uuid::new_v4().to_string(),
+        "time": chrono::Utc::now().to_rfc3339(),
+        "datacontenttype": "application/json",
+        "data": {
+            "service": ctx.service,
+            "from": transition.from.as_str(),
+            "to": transition.to.as_str(),
+            "correlation_id": ctx.correlation_id
+        }
+    })]);
+}
+
+fn eval(conn: &Connection, script: &str, keys: &[String], args: Vec<String>) -> Result<Vec<i64>> {
+    let mut params = vec![
+        RedisParameter::Binary(script.as_bytes().to_vec()),
+        RedisParameter::Int64(i64::try_from(keys.len())?),
+    ];
+    params.extend(
+        keys.iter()
+            .map(|k| RedisParameter::Binary(k.as_bytes().to_vec())),
+    );
+    params.extend(
+        args.into_iter()
+            .map(|a| RedisParameter::Binary(a.into_bytes())),
+    );
+
+    let res = conn.execute("EVAL", &params)?;
+    parse_redis_ints(&res).context("cb_eval_non_integer_reply")
+}
+
+fn hash_fields(conn: &Connection, key: &str) -> Vec<(String, String)> {
+    let Ok(values) = conn.execute(
+        "HGETALL",
+        &[RedisParameter::Binary(key.as_bytes().to_vec())],
+    ) else {
+        return Vec::new();
+    };
+    let text = |v: &RedisResult| match v {
+        RedisResult::Binary(b) => String::from_utf8(b.clone()).ok(),
+        RedisResult::Status(s) => Some(s.clone()),
+        RedisResult::Int64(i) => Some(i.to_string()),
+        RedisResult::Nil => None,
+    };
+    values
+        .chunks_exact(2)
+        .filter_map(|pair| Some((text(&pair[0])?, text(&pair[1])?)))
+        .collect()
+}
+
+fn state_key(service: &str) -> String {
+    format!("cb:{service}:breaker")
+}
+
+fn window_key(service: &str) -> String {
+    format!("cb:{service}:window")
 }
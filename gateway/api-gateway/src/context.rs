@@ -1,20 +1,44 @@
-﻿use crate::gateway_config::{load_from_file, GatewayConfig, Policy, ServiceConfig};
+use crate::gateway_config::{
+    load_from_file, load_schemas, parse, GatewayConfig, Policy, ServiceConfig,
+};
 use anyhow::Result;
+use api_gateway_core::{parse_tracestate, TraceParent};
 use once_cell::sync::OnceCell;
+use ring::rand::{SecureRandom, SystemRandom};
 use spin_sdk::{key_value::Store, variables};
+use std::path::Path;
 use uuid::Uuid;
 
 pub const SERVICE_REGISTRY_STORE: &str = "default";
 pub const CORRELATION_ID_HEADER: &str = "x-correlation-id";
 pub const TRACE_ID_HEADER: &str = "x-trace-id";
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+pub const TRACESTATE_HEADER: &str = "tracestate";
 pub const DEFAULT_CONFIG_PATH: &str = "/config/gateway.toml";
 pub const REDIS_ADDRESS_VAR: &str = "redis_address";
+/// Key-value entry holding a `gateway.toml` uploaded through the admin API.
+/// When present it replaces the file baked into the deployment.
+pub const CONFIG_STORE_KEY: &str = "gateway:config";
+const SERVICE_KEY_PREFIX: &str = "service:";
 
 #[derive(Clone, Debug)]
 pub struct RequestContext {
     pub correlation_id: String,
+    /// W3C trace id, continued from the caller's `traceparent` when it sent
+    /// a valid one.
     pub trace_id: String,
+    /// The gateway's span for this request: `parent_id` is the gateway's own
+    /// span id, so the header can be forwarded upstream as it is.
+    pub trace: TraceParent,
+    /// The caller's span, if the request arrived with a `traceparent`.
+    pub parent_span_id: Option<String>,
+    pub tracestate: Option<String>,
     pub service: String,
+    /// Path to request from the service, after the route's rewrite rule.
+    pub upstream_path: String,
+    /// The matched route's methods and prefix, or the service for paths no
+    /// route covers.
+    pub route: String,
     pub policy: Policy,
 }
 
@@ -30,38 +54,88 @@ pub struct AuthContext {
 
 static CONFIG: OnceCell<GatewayConfig> = OnceCell::new();
 
+/// Where the active configuration came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfigSource {
+    File,
+    Store,
+}
+
+impl ConfigSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ConfigSource::File => "file",
+            ConfigSource::Store => "store",
+        }
+    }
+}
+
+static CONFIG_SOURCE: OnceCell<ConfigSource> = OnceCell::new();
+
+/// The configuration for this request: the admin-uploaded copy in the
+/// key-value store if there is one, otherwise `gateway_config_path`. A stored
+/// copy that no longer parses is skipped rather than taking the gateway down.
 pub fn get_config() -> Result<&'static GatewayConfig> {
     CONFIG.get_or_try_init(|| {
-        let path = variables::get("gateway_config_path")
-            .unwrap_or_else(|_| DEFAULT_CONFIG_PATH.to_string());
-        load_from_file(&path)
+        if let Some(cfg) = load_from_store() {
+            let _ = CONFIG_SOURCE.set(ConfigSource::Store);
+            return Ok(cfg);
+        }
+        let cfg = load_from_file(&config_path())?;
+        let _ = CONFIG_SOURCE.set(ConfigSource::File);
+        Ok(cfg)
     })
 }
 
-pub fn extract_service_name(path: &str) -> String {
-    let p = path.trim_start_matches('/');
-    let mut parts = p.split('/');
-    let first = parts.next().unwrap_or("");
-    let second = parts.next().unwrap_or("");
+pub fn config_source() -> ConfigSource {
+    CONFIG_SOURCE.get().copied().unwrap_or(ConfigSource::File)
+}
 
-    if first != "api" {
-        return "unknown".to_string();
+fn load_from_store() -> Option<GatewayConfig> {
+    let store = Store::open(SERVICE_REGISTRY_STORE).ok()?;
+    let bytes = store.get(CONFIG_STORE_KEY).ok()??;
+    let text = String::from_utf8(bytes).ok()?;
+    match parse_with_schemas(&text) {
+        Ok(cfg) => Some(cfg),
+        Err(err) => {
+            tracing::event!(
+                tracing::Level::ERROR,
+                action = "stored_config_invalid",
+                error = %err
+            );
+            None
+        }
     }
+}
 
-    match second {
-        "auth" => "auth-service".to_string(),
-        "countries" => "location-service".to_string(),
-        "redis" => "redis-service".to_string(),
-        "rate-limit" => "rate-limiter-service".to_string(),
-        "security" => "security-service".to_string(),
-        "reviews" => "reviews-service".to_string(),
-        "notifications" => "notification-service".to_string(),
-        "documents" => "document-validation-service".to_string(),
-        "info" => "info-on-arrival-service".to_string(),
-        "bookings" => "booking-service".to_string(),
-        other if !other.is_empty() => other.to_string(),
-        _ => "unknown".to_string(),
-    }
+/// Validates `text` as a gateway config and stores it, to take effect from
+/// the next request.
+pub fn store_config(text: &str) -> Result<GatewayConfig> {
+    let cfg = parse_with_schemas(text)?;
+    let store = Store::open(SERVICE_REGISTRY_STORE)?;
+    store.set(CONFIG_STORE_KEY, text.as_bytes())?;
+    Ok(cfg)
+}
+
+fn config_path() -> String {
+    variables::get("gateway_config_path").unwrap_or_else(|_| DEFAULT_CONFIG_PATH.to_string())
+}
+
+/// Stored configs name their schemas relative to the deployed config, whose
+/// directory holds the bundled `schemas/`.
+fn parse_with_schemas(text: &str) -> Result<GatewayConfig> {
+    let mut cfg = parse(text)?;
+    let path = config_path();
+    let dir = Path::new(&path).parent().unwrap_or(Path::new("."));
+    cfg.schemas = load_schemas(&cfg, dir)?;
+    Ok(cfg)
+}
+
+/// Drops the stored config so the deployed file applies again.
+pub fn clear_stored_config() -> Result<()> {
+    let store = Store::open(SERVICE_REGISTRY_STORE)?;
+    store.delete(CONFIG_STORE_KEY)?;
+    Ok(())
 }
 
 pub fn build_request_context(req: &spin_sdk::http::Request) -> Result<RequestContext> {
@@ -71,24 +145,43 @@ pub fn build_request_context(req: &spin_sdk::http::Request) -> Result<RequestCon
         .map(|s| s.to_string())
         .unwrap_or_else(|| Uuid::new_v4().to_string());
 
-    let trace_id = req
-        .header(TRACE_ID_HEADER)
+    let incoming = req
+        .header(TRACEPARENT_HEADER)
         .and_then(|h| h.as_str())
-        .map(|s| s.to_string())
-        .unwrap_or_else(|| Uuid::new_v4().to_string());
+        .and_then(TraceParent::parse);
+    let span_id = random_bytes::<8>();
+    let (trace, parent_span_id) = match incoming {
+        Some(parent) => (parent.child(span_id), Some(parent.parent_id)),
+        None => (TraceParent::root(random_bytes::<16>(), span_id), None),
+    };
+    let tracestate = parent_span_id
+        .as_ref()
+        .and_then(|_| req.header(TRACESTATE_HEADER))
+        .and_then(|h| h.as_str())
+        .and_then(parse_tracestate);
 
-    let service = extract_service_name(req.path());
     let cfg = get_config()?;
-    let service_cfg = cfg.services.get(&service);
-    let policy = match service_cfg {
+    let route = cfg.route_table.route(&req.method().to_string(), req.path());
+    let mut policy = match cfg.services.get(&route.service) {
         Some(svc) => cfg.defaults.policy.apply(&svc.policy),
         None => cfg.defaults.policy.clone(),
     };
+    if let Some(overrides) = route.policy {
+        policy = policy.apply(overrides);
+    }
+    let route_id = route
+        .route
+        .map_or_else(|| route.service.clone(), str::to_string);
 
     Ok(RequestContext {
         correlation_id,
-        trace_id,
-        service,
+        trace_id: trace.trace_id.clone(),
+        trace,
+        parent_span_id,
+        tracestate,
+        service: route.service,
+        upstream_path: route.upstream_path,
+        route: route_id,
         policy,
     })
 }
@@ -100,7 +193,7 @@ pub fn resolve_service_url(service: &str) -> Result<String> {
     }
 
     let store = Store::open(SERVICE_REGISTRY_STORE)?;
-    let service_key = format!("service:{service}");
+    let service_key = format!("{SERVICE_KEY_PREFIX}{service}");
     if let Ok(Some(data)) = store.get(&service_key) {
         let registration: crate::ServiceRegistration = serde_json::from_slice(&data)?;
         return Ok(registration.url);
@@ -108,3 +201,40 @@ pub fn resolve_service_url(service: &str) -> Result<String> {
 
     Err(anyhow::anyhow!("unknown_service"))
 }
+
+/// Services registered through `/api/services/register`. Names also in
+/// `[services.*]` are left out: the config always wins.
+pub fn registered_services() -> Result<Vec<crate::ServiceRegistration>> {
+    let cfg = get_config()?;
+    let store = Store::open(SERVICE_REGISTRY_STORE)?;
+    let mut registrations = Vec::new();
+    for key in store.get_keys()? {
+        let Some(name) = key.strip_prefix(SERVICE_KEY_PREFIX) else {
+            continue;
+        };
+        if cfg.services.contains_key(name) {
+            continue;
+        }
+        let Some(data) = store.get(&key)? else {
+            continue;
+        };
+        match serde_json::from_slice(&data) {
+            Ok(registration) => registrations.push(registration),
+            Err(err) => tracing::event!(
+                tracing::Level::WARN,
+                action = "service_registration_invalid",
+                key = key,
+                error = %err
+            ),
+        }
+    }
+    Ok(registrations)
+}
+
+fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut bytes = [0u8; N];
+    if SystemRandom::new().fill(&mut bytes).is_err() {
+        bytes[..].copy_from_slice(&Uuid::new_v4().as_bytes()[..N]);
+    }
+    bytes
+}
@@ -90,6 +90,7 @@ pub fn intercept_and_publish_events(response: Response) -> Response {
 
     // Publish events asynchronously (fire-and-forget)
     if !events.is_empty() {
+        crate::cache::purge_for_events(&events);
         publish_events_async(events);
     }
 
@@ -107,7 +108,7 @@ mod tests {
         let response = Response::builder()
             .status(200)
             .header("X-CloudEvents", events_json)
-            .body(b"{\"status\":\"ok\"}")
+            .body(b"{\"status\":\"ok\"}".to_vec())
             .build();
 
         let extracted = extract_events_from_response(&response);
@@ -119,7 +120,7 @@ mod tests {
         let body = r#"{"data":{"id":"123"},"events":[{"type":"albergue.v1.booking.reserved"}]}"#;
         let response = Response::builder()
             .status(200)
-            .body(body.as_bytes())
+            .body(body.as_bytes().to_vec())
             .build();
 
         let extracted = extract_events_from_response(&response);
@@ -130,7 +131,7 @@ mod tests {
     fn test_no_events() {
         let response = Response::builder()
             .status(200)
-            .body(b"{\"status\":\"ok\"}")
+            .body(b"{\"status\":\"ok\"}".to_vec())
             .build();
 
         let extracted = extract_events_from_response(&response);
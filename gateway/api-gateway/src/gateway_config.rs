@@ -1,6 +1,7 @@
 ﻿use anyhow::{Context, Result};
+use api_gateway_core::{RouteConfig, RouteTable};
 use serde::Deserialize;
-use std::{collections::BTreeMap, fs};
+use std::{collections::BTreeMap, fs, path::Path};
 
 pub mod auth;
 pub mod cache;
@@ -10,6 +11,8 @@ pub mod observability;
 pub mod policy;
 pub mod rate_limit;
 pub mod security_headers;
+pub mod upstream;
+pub mod validation;
 
 #[allow(unused_imports)]
 pub use auth::*;
@@ -25,11 +28,24 @@ pub use policy::*;
 pub use rate_limit::*;
 #[allow(unused_imports)]
 pub use security_headers::*;
+#[allow(unused_imports)]
+pub use upstream::*;
+#[allow(unused_imports)]
+pub use validation::*;
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct GatewayConfig {
     pub defaults: DefaultsConfig,
     pub services: BTreeMap<String, ServiceConfig>,
+    #[serde(default)]
+    pub routes: Vec<RouteConfig<PolicyOverride>>,
+    /// Compiled from `routes` when the config is loaded.
+    #[serde(skip)]
+    pub route_table: RouteTable<PolicyOverride>,
+    /// Request schemas named by validation policies, read by
+    /// [`load_schemas`].
+    #[serde(skip)]
+    pub schemas: BTreeMap<String, serde_json::Value>,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -41,15 +57,39 @@ pub struct DefaultsConfig {
 #[derive(Clone, Debug, Deserialize)]
 pub struct ServiceConfig {
     pub url: String,
+    /// Probed by the admin API; defaults to `{url}/health`.
+    #[serde(default)]
+    pub health_check: Option<String>,
     #[serde(default)]
     pub policy: PolicyOverride,
 }
 
+impl ServiceConfig {
+    pub fn health_check_url(&self) -> String {
+        self.health_check
+            .clone()
+            .unwrap_or_else(|| format!("{}/health", self.url.trim_end_matches('/')))
+    }
+}
+
 pub fn load_from_file(path: &str) -> Result<GatewayConfig> {
     let bytes =
         fs::read(path).with_context(|| format!("Failed to read gateway config at {path}"))?;
     let text = String::from_utf8(bytes).context("Gateway config must be UTF-8")?;
-    let cfg: GatewayConfig =
-        toml::from_str(&text).context("Failed to parse gateway TOML config")?;
+    let mut cfg = parse(&text)?;
+    let dir = Path::new(path).parent().unwrap_or(Path::new("."));
+    cfg.schemas = load_schemas(&cfg, dir)?;
+    Ok(cfg)
+}
+
+/// Parse a gateway config and compile its route table, rejecting overlapping
+/// routes and routes to services missing from `[services.*]`.
+pub fn parse(text: &str) -> Result<GatewayConfig> {
+    let mut cfg: GatewayConfig =
+        toml::from_str(text).context("Failed to parse gateway TOML config")?;
+    cfg.route_table = RouteTable::compile(cfg.routes.clone(), |service| {
+        cfg.services.contains_key(service)
+    })
+    .context("Invalid gateway route table")?;
     Ok(cfg)
 }
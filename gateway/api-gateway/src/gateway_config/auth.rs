@@ -1,7 +1,7 @@
 use crate::gateway_config::defaults::{default_google_oidc, default_true};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct AuthPolicy {
     #[serde(default = "default_true")]
     pub enabled: bool,
@@ -15,6 +15,10 @@ pub struct AuthPolicy {
     pub required_scopes: Vec<String>,
     #[serde(default)]
     pub required_roles: Vec<String>,
+    /// Also accept gateway-issued API keys, sent in `X-API-Key`, from
+    /// machines that cannot run an OIDC login.
+    #[serde(default)]
+    pub api_keys: bool,
 }
 
 #[derive(Clone, Debug, Deserialize, Default)]
@@ -25,6 +29,7 @@ pub struct AuthPolicyOverride {
     pub required_audience: Option<Option<String>>,
     pub required_scopes: Option<Vec<String>>,
     pub required_roles: Option<Vec<String>>,
+    pub api_keys: Option<bool>,
 }
 
 impl Default for AuthPolicy {
@@ -36,6 +41,7 @@ impl Default for AuthPolicy {
             required_audience: None,
             required_scopes: Vec::new(),
             required_roles: Vec::new(),
+            api_keys: false,
         }
     }
 }
@@ -64,6 +70,7 @@ impl AuthPolicy {
                 .required_roles
                 .clone()
                 .unwrap_or_else(|| self.required_roles.clone()),
+            api_keys: o.api_keys.unwrap_or(self.api_keys),
         }
     }
 }
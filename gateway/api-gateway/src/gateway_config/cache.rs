@@ -1,9 +1,10 @@
 use crate::gateway_config::defaults::{
-    default_cache_max_body_bytes, default_cache_methods, default_cache_ttl_seconds,
+    default_cache_max_body_bytes, default_cache_methods, default_cache_stored_headers,
+    default_cache_ttl_seconds,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct CachePolicy {
     #[serde(default)]
     pub enabled: bool,
@@ -15,6 +16,16 @@ pub struct CachePolicy {
     pub vary_headers: Vec<String>,
     #[serde(default = "default_cache_max_body_bytes")]
     pub max_body_bytes: usize,
+    /// Used when the upstream sends no `stale-while-revalidate`.
+    #[serde(default)]
+    pub stale_while_revalidate_seconds: u64,
+    /// Upstream response headers kept with a cached body.
+    #[serde(default = "default_cache_stored_headers")]
+    pub stored_headers: Vec<String>,
+    /// `CloudEvent` types that drop this service's cached responses, e.g.
+    /// `albergue.v1.booking.*`.
+    #[serde(default)]
+    pub purge_on: Vec<String>,
 }
 
 impl Default for CachePolicy {
@@ -25,6 +36,9 @@ impl Default for CachePolicy {
             methods: default_cache_methods(),
             vary_headers: Vec::new(),
             max_body_bytes: default_cache_max_body_bytes(),
+            stale_while_revalidate_seconds: 0,
+            stored_headers: default_cache_stored_headers(),
+            purge_on: Vec::new(),
         }
     }
 }
@@ -36,6 +50,9 @@ pub struct CachePolicyOverride {
     pub methods: Option<Vec<String>>,
     pub vary_headers: Option<Vec<String>>,
     pub max_body_bytes: Option<usize>,
+    pub stale_while_revalidate_seconds: Option<u64>,
+    pub stored_headers: Option<Vec<String>>,
+    pub purge_on: Option<Vec<String>>,
 }
 
 impl CachePolicy {
@@ -52,6 +69,14 @@ impl CachePolicy {
                 .clone()
                 .unwrap_or_else(|| self.vary_headers.clone()),
             max_body_bytes: o.max_body_bytes.unwrap_or(self.max_body_bytes),
+            stale_while_revalidate_seconds: o
+                .stale_while_revalidate_seconds
+                .unwrap_or(self.stale_while_revalidate_seconds),
+            stored_headers: o
+                .stored_headers
+                .clone()
+                .unwrap_or_else(|| self.stored_headers.clone()),
+            purge_on: o.purge_on.clone().unwrap_or_else(|| self.purge_on.clone()),
         }
     }
 }
@@ -1,16 +1,27 @@
 use crate::gateway_config::defaults::{
-    default_cb_failure_threshold, default_cb_half_open_max, default_cb_open_seconds,
+    default_cb_failure_rate_percent, default_cb_half_open_max, default_cb_min_requests,
+    default_cb_open_seconds, default_cb_window_seconds,
 };
-use serde::Deserialize;
+use api_gateway_core::BreakerSettings;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct CircuitBreakerPolicy {
     #[serde(default)]
     pub enabled: bool,
-    #[serde(default = "default_cb_failure_threshold")]
-    pub failure_threshold: u64,
+    /// Rolling window the error rate is measured over.
+    #[serde(default = "default_cb_window_seconds")]
+    pub window_seconds: u64,
+    /// Requests the window must hold before the breaker can open.
+    #[serde(default = "default_cb_min_requests")]
+    pub min_requests: u64,
+    /// Share of failed requests in the window that opens the breaker.
+    #[serde(default = "default_cb_failure_rate_percent")]
+    pub failure_rate_percent: u64,
     #[serde(default = "default_cb_open_seconds")]
     pub open_seconds: u64,
+    /// Trial requests let through while half-open; that many successes close
+    /// the breaker again.
     #[serde(default = "default_cb_half_open_max")]
     pub half_open_max: u64,
 }
@@ -19,7 +30,9 @@ impl Default for CircuitBreakerPolicy {
     fn default() -> Self {
         Self {
             enabled: false,
-            failure_threshold: default_cb_failure_threshold(),
+            window_seconds: default_cb_window_seconds(),
+            min_requests: default_cb_min_requests(),
+            failure_rate_percent: default_cb_failure_rate_percent(),
             open_seconds: default_cb_open_seconds(),
             half_open_max: default_cb_half_open_max(),
         }
@@ -29,7 +42,9 @@ impl Default for CircuitBreakerPolicy {
 #[derive(Clone, Debug, Deserialize)]
 pub struct CircuitBreakerPolicyOverride {
     pub enabled: Option<bool>,
-    pub failure_threshold: Option<u64>,
+    pub window_seconds: Option<u64>,
+    pub min_requests: Option<u64>,
+    pub failure_rate_percent: Option<u64>,
     pub open_seconds: Option<u64>,
     pub half_open_max: Option<u64>,
 }
@@ -41,9 +56,21 @@ impl CircuitBreakerPolicy {
         };
         CircuitBreakerPolicy {
             enabled: o.enabled.unwrap_or(self.enabled),
-            failure_threshold: o.failure_threshold.unwrap_or(self.failure_threshold),
+            window_seconds: o.window_seconds.unwrap_or(self.window_seconds),
+            min_requests: o.min_requests.unwrap_or(self.min_requests),
+            failure_rate_percent: o.failure_rate_percent.unwrap_or(self.failure_rate_percent),
             open_seconds: o.open_seconds.unwrap_or(self.open_seconds),
             half_open_max: o.half_open_max.unwrap_or(self.half_open_max),
         }
     }
+
+    pub fn settings(&self) -> BreakerSettings {
+        BreakerSettings {
+            window_seconds: self.window_seconds.max(1),
+            min_requests: self.min_requests,
+            failure_rate_percent: self.failure_rate_percent.min(100),
+            open_seconds: self.open_seconds,
+            half_open_max: self.half_open_max.max(1),
+        }
+    }
 }
@@ -14,6 +14,10 @@ pub fn default_rate_max_requests() -> u64 {
     120
 }
 
+pub fn default_rate_api_key_header() -> String {
+    "x-api-key".to_string()
+}
+
 pub fn default_cache_ttl_seconds() -> u64 {
     15
 }
@@ -26,6 +30,19 @@ pub fn default_cache_max_body_bytes() -> usize {
     262_144
 }
 
+pub fn default_cache_stored_headers() -> Vec<String> {
+    [
+        "content-type",
+        "content-language",
+        "content-encoding",
+        "cache-control",
+        "expires",
+        "last-modified",
+    ]
+    .map(String::from)
+    .to_vec()
+}
+
 pub fn default_cors_allow_origin() -> String {
     "*".to_string()
 }
@@ -38,8 +55,16 @@ pub fn default_cors_allow_headers() -> String {
     "authorization,content-type,x-correlation-id,x-trace-id".to_string()
 }
 
-pub fn default_cb_failure_threshold() -> u64 {
-    5
+pub fn default_cb_window_seconds() -> u64 {
+    30
+}
+
+pub fn default_cb_min_requests() -> u64 {
+    10
+}
+
+pub fn default_cb_failure_rate_percent() -> u64 {
+    50
 }
 
 pub fn default_cb_open_seconds() -> u64 {
@@ -49,3 +74,35 @@ pub fn default_cb_open_seconds() -> u64 {
 pub fn default_cb_half_open_max() -> u64 {
     1
 }
+
+pub fn default_upstream_connect_timeout_ms() -> u64 {
+    2_000
+}
+
+pub fn default_upstream_timeout_ms() -> u64 {
+    10_000
+}
+
+pub fn default_upstream_retries() -> u32 {
+    2
+}
+
+pub fn default_upstream_retry_base_delay_ms() -> u64 {
+    100
+}
+
+pub fn default_upstream_retry_max_delay_ms() -> u64 {
+    2_000
+}
+
+pub fn default_upstream_retry_budget_percent() -> u64 {
+    20
+}
+
+pub fn default_upstream_retry_budget_min() -> u64 {
+    10
+}
+
+pub fn default_max_body_bytes() -> usize {
+    1_048_576
+}
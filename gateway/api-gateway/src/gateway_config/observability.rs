@@ -1,12 +1,16 @@
 use crate::gateway_config::defaults::default_true;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct ObservabilityPolicy {
     #[serde(default = "default_true")]
     pub enabled: bool,
+    /// Log request headers, with credentials redacted.
     #[serde(default)]
     pub log_headers: bool,
+    /// Headers redacted from logs besides the built-in credential headers.
+    #[serde(default)]
+    pub redact_headers: Vec<String>,
 }
 
 impl Default for ObservabilityPolicy {
@@ -14,6 +18,7 @@ impl Default for ObservabilityPolicy {
         Self {
             enabled: true,
             log_headers: false,
+            redact_headers: Vec::new(),
         }
     }
 }
@@ -22,6 +27,7 @@ impl Default for ObservabilityPolicy {
 pub struct ObservabilityPolicyOverride {
     pub enabled: Option<bool>,
     pub log_headers: Option<bool>,
+    pub redact_headers: Option<Vec<String>>,
 }
 
 impl ObservabilityPolicy {
@@ -32,6 +38,10 @@ impl ObservabilityPolicy {
         ObservabilityPolicy {
             enabled: o.enabled.unwrap_or(self.enabled),
             log_headers: o.log_headers.unwrap_or(self.log_headers),
+            redact_headers: o
+                .redact_headers
+                .clone()
+                .unwrap_or_else(|| self.redact_headers.clone()),
         }
     }
 }
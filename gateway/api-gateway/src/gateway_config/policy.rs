@@ -5,10 +5,12 @@ use crate::gateway_config::{
     observability::{ObservabilityPolicy, ObservabilityPolicyOverride},
     rate_limit::{RateLimitPolicy, RateLimitPolicyOverride},
     security_headers::{SecurityHeadersPolicy, SecurityHeadersPolicyOverride},
+    upstream::{UpstreamPolicy, UpstreamPolicyOverride},
+    validation::{ValidationPolicy, ValidationPolicyOverride},
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, Deserialize, Default)]
+#[derive(Clone, Debug, Deserialize, Serialize, Default)]
 pub struct Policy {
     #[serde(default)]
     pub auth: AuthPolicy,
@@ -21,7 +23,11 @@ pub struct Policy {
     #[serde(default)]
     pub circuit_breaker: CircuitBreakerPolicy,
     #[serde(default)]
+    pub upstream: UpstreamPolicy,
+    #[serde(default)]
     pub observability: ObservabilityPolicy,
+    #[serde(default)]
+    pub validation: ValidationPolicy,
 }
 
 #[derive(Clone, Debug, Deserialize, Default)]
@@ -31,7 +37,9 @@ pub struct PolicyOverride {
     pub cache: Option<CachePolicyOverride>,
     pub security_headers: Option<SecurityHeadersPolicyOverride>,
     pub circuit_breaker: Option<CircuitBreakerPolicyOverride>,
+    pub upstream: Option<UpstreamPolicyOverride>,
     pub observability: Option<ObservabilityPolicyOverride>,
+    pub validation: Option<ValidationPolicyOverride>,
 }
 
 impl Policy {
@@ -46,7 +54,9 @@ impl Policy {
             circuit_breaker: self
                 .circuit_breaker
                 .apply(overrides.circuit_breaker.as_ref()),
+            upstream: self.upstream.apply(overrides.upstream.as_ref()),
             observability: self.observability.apply(overrides.observability.as_ref()),
+            validation: self.validation.apply(overrides.validation.as_ref()),
         }
     }
 }
@@ -1,7 +1,10 @@
-use crate::gateway_config::defaults::{default_rate_max_requests, default_rate_window_seconds};
-use serde::Deserialize;
+use crate::gateway_config::defaults::{
+    default_rate_api_key_header, default_rate_max_requests, default_rate_window_seconds,
+};
+use api_gateway_core::{RateLimitAlgorithm, RateLimitKeys};
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct RateLimitPolicy {
     #[serde(default)]
     pub enabled: bool,
@@ -9,8 +12,19 @@ pub struct RateLimitPolicy {
     pub window_seconds: u64,
     #[serde(default = "default_rate_max_requests")]
     pub max_requests: u64,
-    #[serde(default = "default_rate_key")]
-    pub key: RateLimitKey,
+    #[serde(default)]
+    pub algorithm: RateLimitAlgorithm,
+    /// Requests a `gcra` bucket absorbs at once; defaults to `max_requests`.
+    #[serde(default)]
+    pub burst: Option<u64>,
+    #[serde(default)]
+    pub key: RateLimitKeys,
+    #[serde(default = "default_rate_api_key_header")]
+    pub api_key_header: String,
+    /// Take the client address from `X-Forwarded-For`; only safe behind a
+    /// proxy that overwrites the header.
+    #[serde(default)]
+    pub trust_forwarded_for: bool,
 }
 
 impl Default for RateLimitPolicy {
@@ -19,7 +33,11 @@ impl Default for RateLimitPolicy {
             enabled: false,
             window_seconds: default_rate_window_seconds(),
             max_requests: default_rate_max_requests(),
-            key: default_rate_key(),
+            algorithm: RateLimitAlgorithm::default(),
+            burst: None,
+            key: RateLimitKeys::default(),
+            api_key_header: default_rate_api_key_header(),
+            trust_forwarded_for: false,
         }
     }
 }
@@ -29,7 +47,11 @@ pub struct RateLimitPolicyOverride {
     pub enabled: Option<bool>,
     pub window_seconds: Option<u64>,
     pub max_requests: Option<u64>,
-    pub key: Option<RateLimitKey>,
+    pub algorithm: Option<RateLimitAlgorithm>,
+    pub burst: Option<u64>,
+    pub key: Option<RateLimitKeys>,
+    pub api_key_header: Option<String>,
+    pub trust_forwarded_for: Option<bool>,
 }
 
 impl RateLimitPolicy {
@@ -41,18 +63,18 @@ impl RateLimitPolicy {
             enabled: o.enabled.unwrap_or(self.enabled),
             window_seconds: o.window_seconds.unwrap_or(self.window_seconds),
             max_requests: o.max_requests.unwrap_or(self.max_requests),
+            algorithm: o.algorithm.unwrap_or(self.algorithm),
+            burst: o.burst.or(self.burst),
             key: o.key.clone().unwrap_or_else(|| self.key.clone()),
+            api_key_header: o
+                .api_key_header
+                .clone()
+                .unwrap_or_else(|| self.api_key_header.clone()),
+            trust_forwarded_for: o.trust_forwarded_for.unwrap_or(self.trust_forwarded_for),
         }
     }
-}
 
-#[derive(Clone, Debug, Deserialize)]
-#[serde(rename_all = "snake_case")]
-pub enum RateLimitKey {
-    Sub,
-    CorrelationId,
-}
-
-fn default_rate_key() -> RateLimitKey {
-    RateLimitKey::Sub
+    pub fn burst(&self) -> u64 {
+        self.burst.unwrap_or(self.max_requests)
+    }
 }
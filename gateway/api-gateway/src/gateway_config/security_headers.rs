@@ -1,9 +1,9 @@
 use crate::gateway_config::defaults::{
     default_cors_allow_headers, default_cors_allow_methods, default_cors_allow_origin, default_true,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct SecurityHeadersPolicy {
     #[serde(default = "default_true")]
     pub enabled: bool,
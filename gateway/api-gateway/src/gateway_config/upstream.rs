@@ -0,0 +1,80 @@
+use crate::gateway_config::defaults::{
+    default_upstream_connect_timeout_ms, default_upstream_retries,
+    default_upstream_retry_base_delay_ms, default_upstream_retry_budget_min,
+    default_upstream_retry_budget_percent, default_upstream_retry_max_delay_ms,
+    default_upstream_timeout_ms,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct UpstreamPolicy {
+    #[serde(default = "default_upstream_connect_timeout_ms")]
+    pub connect_timeout_ms: u64,
+    /// Budget for the whole exchange, retries and backoff included.
+    #[serde(default = "default_upstream_timeout_ms")]
+    pub timeout_ms: u64,
+    /// Extra attempts for idempotent requests answered with 502/503/504 or
+    /// not answered at all.
+    #[serde(default = "default_upstream_retries")]
+    pub retries: u32,
+    #[serde(default = "default_upstream_retry_base_delay_ms")]
+    pub retry_base_delay_ms: u64,
+    #[serde(default = "default_upstream_retry_max_delay_ms")]
+    pub retry_max_delay_ms: u64,
+    /// Retries and hedges per service may reach this percentage of its
+    /// requests over a 10 second window.
+    #[serde(default = "default_upstream_retry_budget_percent")]
+    pub retry_budget_percent: u64,
+    /// Retries allowed per window however little traffic there is.
+    #[serde(default = "default_upstream_retry_budget_min")]
+    pub retry_budget_min: u64,
+    /// Send a second copy of an idempotent request when the first has not
+    /// answered after this long, and use whichever answers first.
+    #[serde(default)]
+    pub hedge_after_ms: Option<u64>,
+}
+
+impl Default for UpstreamPolicy {
+    fn default() -> Self {
+        Self {
+            connect_timeout_ms: default_upstream_connect_timeout_ms(),
+            timeout_ms: default_upstream_timeout_ms(),
+            retries: default_upstream_retries(),
+            retry_base_delay_ms: default_upstream_retry_base_delay_ms(),
+            retry_max_delay_ms: default_upstream_retry_max_delay_ms(),
+            retry_budget_percent: default_upstream_retry_budget_percent(),
+            retry_budget_min: default_upstream_retry_budget_min(),
+            hedge_after_ms: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct UpstreamPolicyOverride {
+    pub connect_timeout_ms: Option<u64>,
+    pub timeout_ms: Option<u64>,
+    pub retries: Option<u32>,
+    pub retry_base_delay_ms: Option<u64>,
+    pub retry_max_delay_ms: Option<u64>,
+    pub retry_budget_percent: Option<u64>,
+    pub retry_budget_min: Option<u64>,
+    pub hedge_after_ms: Option<u64>,
+}
+
+impl UpstreamPolicy {
+    pub fn apply(&self, o: Option<&UpstreamPolicyOverride>) -> UpstreamPolicy {
+        let Some(o) = o else {
+            return self.clone();
+        };
+        UpstreamPolicy {
+            connect_timeout_ms: o.connect_timeout_ms.unwrap_or(self.connect_timeout_ms),
+            timeout_ms: o.timeout_ms.unwrap_or(self.timeout_ms),
+            retries: o.retries.unwrap_or(self.retries),
+            retry_base_delay_ms: o.retry_base_delay_ms.unwrap_or(self.retry_base_delay_ms),
+            retry_max_delay_ms: o.retry_max_delay_ms.unwrap_or(self.retry_max_delay_ms),
+            retry_budget_percent: o.retry_budget_percent.unwrap_or(self.retry_budget_percent),
+            retry_budget_min: o.retry_budget_min.unwrap_or(self.retry_budget_min),
+            hedge_after_ms: o.hedge_after_ms.or(self.hedge_after_ms),
+        }
+    }
+}
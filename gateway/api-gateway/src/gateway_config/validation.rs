@@ -0,0 +1,153 @@
+use crate::gateway_config::{
+    defaults::{default_max_body_bytes, default_true},
+    GatewayConfig, PolicyOverride,
+};
+use anyhow::{bail, Context, Result};
+use api_gateway_core::check_schema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Component, Path},
+};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ValidationPolicy {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Larger bodies are refused with 413 before reaching the service.
+    #[serde(default = "default_max_body_bytes")]
+    pub max_body_bytes: usize,
+    /// JSON Schema for request bodies, relative to the directory of
+    /// `gateway.toml`, e.g. `schemas/booking-create.json`.
+    #[serde(default)]
+    pub body_schema: Option<String>,
+    /// JSON Schema for the query string, seen as an object of parameters.
+    #[serde(default)]
+    pub query_schema: Option<String>,
+}
+
+impl Default for ValidationPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_body_bytes: default_max_body_bytes(),
+            body_schema: None,
+            query_schema: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ValidationPolicyOverride {
+    pub enabled: Option<bool>,
+    pub max_body_bytes: Option<usize>,
+    #[serde(default)]
+    pub body_schema: SchemaOverride,
+    #[serde(default)]
+    pub query_schema: SchemaOverride,
+}
+
+/// A service or route's schema setting. Left out it inherits the default's
+/// schema; an empty path drops it, since TOML has no null.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(from = "String")]
+pub enum SchemaOverride {
+    #[default]
+    Inherit,
+    Disable,
+    Path(String),
+}
+
+impl From<String> for SchemaOverride {
+    fn from(path: String) -> Self {
+        if path.is_empty() {
+            Self::Disable
+        } else {
+            Self::Path(path)
+        }
+    }
+}
+
+impl SchemaOverride {
+    fn apply(&self, inherited: Option<&String>) -> Option<String> {
+        match self {
+            Self::Inherit => inherited.cloned(),
+            Self::Disable => None,
+            Self::Path(path) => Some(path.clone()),
+        }
+    }
+
+    const fn path(&self) -> Option<&String> {
+        match self {
+            Self::Path(path) => Some(path),
+            Self::Inherit | Self::Disable => None,
+        }
+    }
+}
+
+impl ValidationPolicy {
+    pub fn apply(&self, o: Option<&ValidationPolicyOverride>) -> ValidationPolicy {
+        let Some(o) = o else {
+            return self.clone();
+        };
+        ValidationPolicy {
+            enabled: o.enabled.unwrap_or(self.enabled),
+            max_body_bytes: o.max_body_bytes.unwrap_or(self.max_body_bytes),
+            body_schema: o.body_schema.apply(self.body_schema.as_ref()),
+            query_schema: o.query_schema.apply(self.query_schema.as_ref()),
+        }
+    }
+}
+
+/// Reads every schema the config refers to from `dir`, refusing the config
+/// when one is missing, escapes `dir` or uses keywords the gateway cannot
+/// enforce.
+pub fn load_schemas(cfg: &GatewayConfig, dir: &Path) -> Result<BTreeMap<String, Value>> {
+    let mut schemas = BTreeMap::new();
+    for name in schema_names(cfg) {
+        let relative = Path::new(&name);
+        if !relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+        {
+            bail!("Schema path {name} must stay inside the config directory");
+        }
+        let path = dir.join(relative);
+        let bytes =
+            fs::read(&path).with_context(|| format!("Failed to read schema {}", path.display()))?;
+        let schema: Value = serde_json::from_slice(&bytes)
+            .with_context(|| format!("Schema {name} is not valid JSON"))?;
+        check_schema(&schema).with_context(|| format!("Schema {name} is not supported"))?;
+        schemas.insert(name, schema);
+    }
+    Ok(schemas)
+}
+
+fn schema_names(cfg: &GatewayConfig) -> Vec<String> {
+    let overrides = cfg
+        .services
+        .values()
+        .map(|svc| &svc.policy)
+        .chain(cfg.routes.iter().map(|route| &route.policy))
+        .filter_map(|policy: &PolicyOverride| policy.validation.as_ref());
+
+    let defaults = &cfg.defaults.policy.validation;
+    let mut names: Vec<String> = [&defaults.body_schema, &defaults.query_schema]
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect();
+    for o in overrides {
+        names.extend(
+            [&o.body_schema, &o.query_schema]
+                .into_iter()
+                .filter_map(SchemaOverride::path)
+                .cloned(),
+        );
+    }
+    names.sort();
+    names.dedup();
+    names
+}
@@ -0,0 +1,61 @@
+use crate::context::RequestContext;
+use anyhow::{bail, Result};
+use api_gateway_core::hex_id;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ring::{
+    digest::{digest, SHA256},
+    hmac,
+};
+use serde_json::json;
+use spin_sdk::variables;
+
+/// Header carrying the token on every upstream request.
+pub const GATEWAY_TOKEN_HEADER: &str = "x-gateway-token";
+
+/// Header the caller's claims are forwarded in; the token signs its value.
+pub const USER_CLAIMS_HEADER: &str = "x-user-claims";
+
+/// Secret shared with the backends, which verify the token with
+/// `shared::gateway_token`.
+pub const INTERNAL_TOKEN_SECRET_VAR: &str = "internal_token_secret";
+
+/// Tokens only have to outlive one upstream call, retries and hedges
+/// included.
+const TOKEN_TTL_SECONDS: u64 = 60;
+
+/// Mints an HS256 JWT for the upstream call, addressed to `ctx.service` and
+/// binding the SHA-256 of the `x-user-claims` value sent along with it, so a
+/// backend can tell the claims came from the gateway. Fails when no secret
+/// is configured: backends refuse requests without a token.
+pub fn mint(
+    ctx: &RequestContext,
+    subject: Option<&str>,
+    user_claims: Option<&str>,
+) -> Result<String> {
+    let secret = variables::get(INTERNAL_TOKEN_SECRET_VAR).unwrap_or_default();
+    if secret.is_empty() {
+        bail!("{INTERNAL_TOKEN_SECRET_VAR} is not configured");
+    }
+
+    let iat = u64::try_from(chrono::Utc::now().timestamp()).unwrap_or(0);
+    let mut payload = json!({
+        "iss": "api-gateway",
+        "aud": ctx.service,
+        "iat": iat,
+        "exp": iat + TOKEN_TTL_SECONDS,
+        "jti": ctx.correlation_id,
+    });
+    if let Some(sub) = subject {
+        payload["sub"] = json!(sub);
+    }
+    if let Some(claims) = user_claims {
+        payload["claims_sha256"] = json!(hex_id(digest(&SHA256, claims.as_bytes()).as_ref()));
+    }
+
+    let header = URL_SAFE_NO_PAD.encode(br#"{"alg":"HS256","typ":"JWT"}"#);
+    let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&payload)?);
+    let signing_input = format!("{header}.{payload}");
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    let signature = URL_SAFE_NO_PAD.encode(hmac::sign(&key, signing_input.as_bytes()));
+    Ok(format!("{signing_input}.{signature}"))
+}
@@ -0,0 +1,302 @@
+//! Caching for JWKS documents, shared by every gateway instance through Redis.
+//!
+//! Keys are kept for the lifetime the identity provider advertises in
+//! `Cache-Control`. A token signed with a `kid` missing from the cached set
+//! triggers at most one refetch per [`REFETCH_COOLDOWN_SECONDS`], so tokens
+//! with made-up key ids cannot turn into a stream of requests to the provider.
+
+use serde::{Deserialize, Serialize};
+
+use crate::jwks_client::error::Error;
+use crate::jwks_client::keyset::{JwtKey, KeyStore};
+
+pub const DEFAULT_TTL_SECONDS: u64 = 3600;
+pub const MIN_TTL_SECONDS: u64 = 60;
+pub const MAX_TTL_SECONDS: u64 = 86_400;
+pub const REFETCH_COOLDOWN_SECONDS: u64 = 60;
+/// How long an expired set is kept to fall back on while the provider is down.
+pub const STALE_IF_ERROR_SECONDS: u64 = 3600;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CachedKeySet {
+    pub keys: Vec<JwtKey>,
+    /// Unix seconds after which the set must be fetched again.
+    pub expires_at: u64,
+}
+
+/// A freshly downloaded key set and the `max-age` it was served with.
+#[derive(Debug, Clone)]
+pub struct FetchedKeySet {
+    pub keys: Vec<JwtKey>,
+    pub max_age: Option<u64>,
+}
+
+pub trait KeySetCache {
+    fn get(&self, key: &str) -> Option<CachedKeySet>;
+    fn set(&self, key: &str, value: &CachedKeySet, ttl_seconds: u64);
+    /// Take a lock that expires on its own after `ttl_seconds`. Returns
+    /// `false` while someone else holds it.
+    fn claim(&self, key: &str, ttl_seconds: u64) -> bool;
+}
+
+#[allow(async_fn_in_trait)]
+pub trait KeySetFetcher {
+    async fn fetch(&self, jwks_uri: &str) -> Result<FetchedKeySet, Error>;
+}
+
+/// The `max-age` of a `Cache-Control` header. `no-store` and `no-cache`
+/// count as zero, which [`clamp_ttl`] raises to the minimum.
+pub fn parse_max_age(cache_control: &str) -> Option<u64> {
+    let mut max_age = None;
+    for directive in cache_control.split(',').map(str::trim) {
+        let lower = directive.to_ascii_lowercase();
+        if lower == "no-store" || lower == "no-cache" {
+            return Some(0);
+        }
+        if let Some(value) = lower.strip_prefix("max-age=") {
+            max_age = value.trim_matches('"').parse().ok();
+        }
+    }
+    max_age
+}
+
+pub fn clamp_ttl(max_age: Option<u64>) -> u64 {
+    max_age
+        .unwrap_or(DEFAULT_TTL_SECONDS)
+        .clamp(MIN_TTL_SECONDS, MAX_TTL_SECONDS)
+}
+
+fn keys_cache_key(jwks_uri: &str) -> String {
+    format!("jwks:keys:{jwks_uri}")
+}
+
+fn refetch_lock_key(jwks_uri: &str) -> String {
+    format!("jwks:refetch:{jwks_uri}")
+}
+
+/// Build a [`KeyStore`] for `jwks_uri` that knows `kid` if the provider
+/// publishes it, going to the network only when the cache is cold, stale,
+/// or missing `kid` and the cooldown allows it.
+pub async fn resolve_key_store(
+    cache: &impl KeySetCache,
+    fetcher: &impl KeySetFetcher,
+    jwks_uri: &str,
+    kid: Option<&str>,
+    now: u64,
+) -> Result<KeyStore, Error> {
+    let cache_key = keys_cache_key(jwks_uri);
+    let cached = cache.get(&cache_key);
+
+    if let Some(set) = cached.as_ref().filter(|set| set.expires_at > now) {
+        let knows_kid = kid.is_none_or(|kid| set.keys.iter().any(|k| k.kid == kid));
+        // An unknown kid may be a key the provider rotated in early, but only
+        // one caller per cooldown gets to check.
+        if knows_kid || !cache.claim(&refetch_lock_key(jwks_uri), REFETCH_COOLDOWN_SECONDS) {
+            return Ok(KeyStore::from_keys(set.keys.clone()));
+        }
+    }
+
+    match fetcher.fetch(jwks_uri).await {
+        Ok(downloaded) => {
+            let ttl = clamp_ttl(downloaded.max_age);
+            let set = CachedKeySet {
+                keys: downloaded.keys,
+                expires_at: now + ttl,
+            };
+            cache.set(&cache_key, &set, ttl + STALE_IF_ERROR_SECONDS);
+            Ok(KeyStore::from_keys(set.keys))
+        }
+        Err(e) => match cached {
+            Some(set) => Ok(KeyStore::from_keys(set.keys)),
+            None => Err(e),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jwks_client::error::{err_con, Type};
+    use crate::jwks_client::keyset::{parse_jwks, test_keys::*};
+    use std::cell::{Cell, RefCell};
+    use std::collections::HashMap;
+    use std::future::Future;
+    use std::pin::pin;
+    use std::task::{Context, Poll, Waker};
+
+    /// In-memory cache; `now` drives lock expiry.
+    #[derive(Default)]
+    struct MemoryCache {
+        values: RefCell<HashMap<String, (CachedKeySet, u64)>>,
+        locks: RefCell<HashMap<String, u64>>,
+        now: Cell<u64>,
+    }
+
+    impl KeySetCache for MemoryCache {
+        fn get(&self, key: &str) -> Option<CachedKeySet> {
+            let values = self.values.borrow();
+            let (value, until) = values.get(key)?;
+            (*until > self.now.get()).then(|| value.clone())
+        }
+
+        fn set(&self, key: &str, value: &CachedKeySet, ttl_seconds: u64) {
+            self.values.borrow_mut().insert(
+                key.to_string(),
+                (value.clone(), self.now.get() + ttl_seconds),
+            );
+        }
+
+        fn claim(&self, key: &str, ttl_seconds: u64) -> bool {
+            let mut locks = self.locks.borrow_mut();
+            let now = self.now.get();
+            if locks.get(key).is_some_and(|until| *until > now) {
+                return false;
+            }
+            locks.insert(key.to_string(), now + ttl_seconds);
+            true
+        }
+    }
+
+    /// Local stand-in for the provider's JWKS endpoint.
+    struct StubJwks {
+        body: RefCell<Vec<u8>>,
+        max_age: Option<u64>,
+        fail: Cell<bool>,
+        calls: Cell<usize>,
+    }
+
+    impl StubJwks {
+        fn serving(keys: &[&TestKey], max_age: Option<u64>) -> Self {
+            Self {
+                body: RefCell::new(jwks_body(keys)),
+                max_age,
+                fail: Cell::new(false),
+                calls: Cell::new(0),
+            }
+        }
+
+        fn publish(&self, keys: &[&TestKey]) {
+            *self.body.borrow_mut() = jwks_body(keys);
+        }
+    }
+
+    impl KeySetFetcher for StubJwks {
+        async fn fetch(&self, _jwks_uri: &str) -> Result<FetchedKeySet, Error> {
+            self.calls.set(self.calls.get() + 1);
+            if self.fail.get() {
+                return Err(err_con("Failed to fetch keys"));
+            }
+            Ok(FetchedKeySet {
+                keys: parse_jwks(&self.body.borrow())?,
+                max_age: self.max_age,
+            })
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    const URI: &str = "https://id.example/jwks";
+
+    fn resolve(
+        cache: &MemoryCache,
+        jwks: &StubJwks,
+        kid: &str,
+        now: u64,
+    ) -> Result<KeyStore, Error> {
+        cache.now.set(now);
+        block_on(resolve_key_store(cache, jwks, URI, Some(kid), now))
+    }
+
+    #[test]
+    fn parses_cache_control() {
+        assert_eq!(parse_max_age("public, max-age=600"), Some(600));
+        assert_eq!(parse_max_age("Max-Age=\"30\", must-revalidate"), Some(30));
+        assert_eq!(parse_max_age("no-store"), Some(0));
+        assert_eq!(parse_max_age("public"), None);
+        assert_eq!(clamp_ttl(None), DEFAULT_TTL_SECONDS);
+        assert_eq!(clamp_ttl(Some(0)), MIN_TTL_SECONDS);
+        assert_eq!(clamp_ttl(Some(10_000_000)), MAX_TTL_SECONDS);
+    }
+
+    #[test]
+    fn serves_known_kid_from_cache_until_max_age() {
+        let key = TestKey::rsa("r1");
+        let cache = MemoryCache::default();
+        let jwks = StubJwks::serving(&[&key], Some(600));
+
+        resolve(&cache, &jwks, "r1", 1_000).unwrap();
+        let store = resolve(&cache, &jwks, "r1", 1_500).unwrap();
+        assert_eq!(jwks.calls.get(), 1);
+        assert!(store.key_by_id("r1").is_some());
+
+        resolve(&cache, &jwks, "r1", 1_600).unwrap();
+        assert_eq!(jwks.calls.get(), 2);
+    }
+
+    #[test]
+    fn refetches_once_for_rotated_key() {
+        let old = TestKey::ec("2026-01");
+        let new = TestKey::ed25519("2026-02");
+        let cache = MemoryCache::default();
+        let jwks = StubJwks::serving(&[&old], Some(3_600));
+
+        resolve(&cache, &jwks, "2026-01", 1_000).unwrap();
+        jwks.publish(&[&old, &new]);
+
+        let store = resolve(&cache, &jwks, "2026-02", 1_010).unwrap();
+        assert_eq!(jwks.calls.get(), 2);
+        store
+            .verify_time(
+                &new.sign(&serde_json::json!({ "sub": "a" })),
+                std::time::UNIX_EPOCH,
+            )
+            .unwrap();
+
+        // The refreshed set is cached, so the new kid no longer refetches.
+        resolve(&cache, &jwks, "2026-02", 1_020).unwrap();
+        assert_eq!(jwks.calls.get(), 2);
+    }
+
+    #[test]
+    fn unknown_kids_are_rate_limited_by_cooldown() {
+        let key = TestKey::rsa("r1");
+        let cache = MemoryCache::default();
+        let jwks = StubJwks::serving(&[&key], Some(3_600));
+
+        resolve(&cache, &jwks, "r1", 1_000).unwrap();
+        for (i, kid) in ["bogus-1", "bogus-2", "bogus-3"].iter().enumerate() {
+            let store = resolve(&cache, &jwks, kid, 1_001 + i as u64).unwrap();
+            assert!(store.key_by_id(kid).is_none());
+        }
+        assert_eq!(jwks.calls.get(), 2);
+
+        resolve(&cache, &jwks, "bogus-4", 1_001 + REFETCH_COOLDOWN_SECONDS).unwrap();
+        assert_eq!(jwks.calls.get(), 3);
+    }
+
+    #[test]
+    fn keeps_cached_keys_when_provider_is_down() {
+        let key = TestKey::rsa("r1");
+        let cache = MemoryCache::default();
+        let jwks = StubJwks::serving(&[&key], Some(600));
+
+        resolve(&cache, &jwks, "r1", 1_000).unwrap();
+        jwks.fail.set(true);
+        let store = resolve(&cache, &jwks, "r2", 1_100).unwrap();
+        assert!(store.key_by_id("r1").is_some());
+        let stale = resolve(&cache, &jwks, "r1", 1_700).unwrap();
+        assert!(stale.key_by_id("r1").is_some());
+
+        let cold = MemoryCache::default();
+        let err = resolve(&cold, &jwks, "r1", 1_000).err().unwrap();
+        assert_eq!(err.typ, Type::Connection);
+    }
+}
@@ -1,8 +1,10 @@
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
-use ring::signature::{RsaPublicKeyComponents, RSA_PKCS1_2048_8192_SHA256};
+use ring::signature::{
+    RsaPublicKeyComponents, UnparsedPublicKey, ECDSA_P256_SHA256_FIXED, ED25519,
+    RSA_PKCS1_2048_8192_SHA256,
+};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::Value;
-use spin_sdk::http;
 use std::time::SystemTime;
 
 use crate::jwks_client::error::*;
@@ -11,62 +13,35 @@ use crate::jwks_client::jwt::*;
 type HeaderBody = String;
 pub type Signature = String;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// One entry of a JWKS document. RSA keys carry `n`/`e`, EC and OKP keys
+/// carry `crv`/`x` (and `y` for EC).
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct JwtKey {
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub e: String,
     pub kty: String,
     pub alg: Option<String>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub n: String,
+    #[serde(default)]
     pub kid: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crv: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub x: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub y: Option<String>,
+    #[serde(default, rename = "use", skip_serializing_if = "Option::is_none")]
+    pub key_use: Option<String>,
 }
 
 pub struct KeyStore {
-    key_url: String,
     keys: Vec<JwtKey>,
 }
 
 impl KeyStore {
-    pub fn new() -> KeyStore {
-        KeyStore {
-            key_url: String::new(),
-            keys: Vec::new(),
-        }
-    }
-
-    pub async fn new_from(jwks_url: String) -> Result<KeyStore, Error> {
-        let mut keystore = KeyStore::new();
-        keystore.load_keys_from(jwks_url).await?;
-        Ok(keystore)
-    }
-
-    pub async fn load_keys_from(&mut self, url: String) -> Result<(), Error> {
-        self.key_url = url;
-        self.load_keys().await
-    }
-
-    pub async fn load_keys(&mut self) -> Result<(), Error> {
-        #[derive(Deserialize)]
-        pub struct JwtKeys {
-            pub keys: Vec<JwtKey>,
-        }
-
-        let req = http::Request::builder()
-            .method(http::Method::Get)
-            .uri(&self.key_url)
-            .body(())
-            .build();
-
-        let response: http::Response = http::send(req)
-            .await
-            .map_err(|_| err_con("Failed to fetch keys"))?;
-
-        let jwt_keys: JwtKeys =
-            serde_json::from_slice(response.body()).map_err(|_| err_con("Failed to parse keys"))?;
-
-        self.keys = jwt_keys.keys;
-        Ok(())
+    pub fn from_keys(keys: Vec<JwtKey>) -> KeyStore {
+        KeyStore { keys }
     }
 
     pub fn key_by_id(&self, kid: &str) -> Option<&JwtKey> {
@@ -103,7 +78,8 @@ impl KeyStore {
     pub fn verify_time(&self, token: &str, time: SystemTime) -> Result<Jwt, Error> {
         let (header, payload, signature, body) = self.decode_segments(token)?;
 
-        if header.alg() != Some("RS256") {
+        let alg = header.alg().ok_or(err_inv("No algorithm"))?;
+        if !matches!(alg, "RS256" | "ES256" | "EdDSA") {
             return Err(err_inv("Unsupported algorithm"));
         }
 
@@ -113,14 +89,12 @@ impl KeyStore {
             .key_by_id(kid)
             .ok_or(err_key("JWT key does not exist"))?;
 
-        let e = URL_SAFE_NO_PAD
-            .decode(&key.e)
-            .map_err(|_| err_cer("Failed to decode exponent"))?;
-        let n = URL_SAFE_NO_PAD
-            .decode(&key.n)
-            .map_err(|_| err_cer("Failed to decode modulus"))?;
+        // A key pinned to one algorithm must not verify tokens claiming another.
+        if key.alg.as_deref().is_some_and(|key_alg| key_alg != alg) {
+            return Err(err_key("Key algorithm does not match token"));
+        }
 
-        verify_signature(&e, &n, &body, &signature)?;
+        verify_signature(key, alg, &body, &signature)?;
 
         let jwt = Jwt::new(header, payload, signature);
 
@@ -139,18 +113,80 @@ impl KeyStore {
     }
 }
 
-fn verify_signature(e: &[u8], n: &[u8], message: &str, signature: &str) -> Result<(), Error> {
+/// Parse a JWKS document, dropping keys published for encryption only.
+pub fn parse_jwks(body: &[u8]) -> Result<Vec<JwtKey>, Error> {
+    #[derive(Deserialize)]
+    struct JwtKeys {
+        keys: Vec<JwtKey>,
+    }
+
+    let jwt_keys: JwtKeys =
+        serde_json::from_slice(body).map_err(|_| err_con("Failed to parse keys"))?;
+
+    Ok(jwt_keys
+        .keys
+        .into_iter()
+        .filter(|key| key.key_use.as_deref() != Some("enc"))
+        .collect())
+}
+
+/// The `kid` from a token's header, read without verifying anything.
+pub fn token_kid(token: &str) -> Option<String> {
+    let header_segment = token.split('.').next()?;
+    let header = decode_segment::<Value>(header_segment).ok()?;
+    header.get("kid")?.as_str().map(str::to_string)
+}
+
+fn verify_signature(key: &JwtKey, alg: &str, message: &str, signature: &str) -> Result<(), Error> {
     let sig_bytes = URL_SAFE_NO_PAD
         .decode(signature)
         .map_err(|_| err_sig("Failed to decode signature"))?;
 
-    let public_key = RsaPublicKeyComponents { n, e };
+    let verified = match (alg, key.kty.as_str()) {
+        ("RS256", "RSA") => {
+            let e = decode_key_part(&key.e, "Failed to decode exponent")?;
+            let n = decode_key_part(&key.n, "Failed to decode modulus")?;
+            RsaPublicKeyComponents { n: &n, e: &e }.verify(
+                &RSA_PKCS1_2048_8192_SHA256,
+                message.as_bytes(),
+                &sig_bytes,
+            )
+        }
+        ("ES256", "EC") => {
+            if key.crv.as_deref() != Some("P-256") {
+                return Err(err_cer("Unsupported EC curve"));
+            }
+            let x = decode_coordinate(key.x.as_deref(), "Failed to decode x coordinate")?;
+            let y = decode_coordinate(key.y.as_deref(), "Failed to decode y coordinate")?;
+            if x.len() != 32 || y.len() != 32 {
+                return Err(err_cer("Invalid P-256 coordinates"));
+            }
+            let mut point = Vec::with_capacity(65);
+            point.push(0x04);
+            point.extend_from_slice(&x);
+            point.extend_from_slice(&y);
+            UnparsedPublicKey::new(&ECDSA_P256_SHA256_FIXED, point)
+                .verify(message.as_bytes(), &sig_bytes)
+        }
+        ("EdDSA", "OKP") => {
+            if key.crv.as_deref() != Some("Ed25519") {
+                return Err(err_cer("Unsupported OKP curve"));
+            }
+            let x = decode_coordinate(key.x.as_deref(), "Failed to decode public key")?;
+            UnparsedPublicKey::new(&ED25519, x).verify(message.as_bytes(), &sig_bytes)
+        }
+        _ => return Err(err_key("Key type does not match algorithm")),
+    };
+
+    verified.map_err(|_| err_sig("Signature verification failed"))
+}
 
-    public_key
-        .verify(&RSA_PKCS1_2048_8192_SHA256, message.as_bytes(), &sig_bytes)
-        .map_err(|_| err_sig("Signature verification failed"))?;
+fn decode_key_part(part: &str, msg: &'static str) -> Result<Vec<u8>, Error> {
+    URL_SAFE_NO_PAD.decode(part).map_err(|_| err_cer(msg))
+}
 
-    Ok(())
+fn decode_coordinate(part: Option<&str>, msg: &'static str) -> Result<Vec<u8>, Error> {
+    decode_key_part(part.ok_or(err_cer(msg))?, msg)
 }
 
 fn decode_segment<T: DeserializeOwned>(segment: &str) -> Result<T, Error> {
@@ -160,3 +196,221 @@ fn decode_segment<T: DeserializeOwned>(segment: &str) -> Result<T, Error> {
 
     serde_json::from_slice(&decoded).map_err(|_| err_inv("Failed to parse segment"))
 }
+
+#[cfg(test)]
+pub(crate) mod test_keys {
+    //! Signing keys standing in for an identity provider's JWKS.
+
+    use super::*;
+    use ring::rand::SystemRandom;
+    use ring::signature::{
+        EcdsaKeyPair, Ed25519KeyPair, KeyPair, RsaKeyPair, ECDSA_P256_SHA256_FIXED_SIGNING,
+        RSA_PKCS1_SHA256,
+    };
+    use serde_json::json;
+
+    const RSA_PKCS8: &[u8] = include_bytes!("testdata/rsa2048.pk8");
+
+    pub enum Signer {
+        Rsa(RsaKeyPair),
+        Ec(EcdsaKeyPair),
+        Ed(Ed25519KeyPair),
+    }
+
+    pub struct TestKey {
+        pub kid: String,
+        signer: Signer,
+    }
+
+    impl TestKey {
+        pub fn rsa(kid: &str) -> Self {
+            let pair = RsaKeyPair::from_pkcs8(RSA_PKCS8).expect("rsa test key");
+            Self {
+                kid: kid.to_string(),
+                signer: Signer::Rsa(pair),
+            }
+        }
+
+        pub fn ec(kid: &str) -> Self {
+            let rng = SystemRandom::new();
+            let pkcs8 =
+                EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng).unwrap();
+            let pair =
+                EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8.as_ref(), &rng)
+                    .unwrap();
+            Self {
+                kid: kid.to_string(),
+                signer: Signer::Ec(pair),
+            }
+        }
+
+        pub fn ed25519(kid: &str) -> Self {
+            let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+            let pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+            Self {
+                kid: kid.to_string(),
+                signer: Signer::Ed(pair),
+            }
+        }
+
+        pub fn alg(&self) -> &'static str {
+            match self.signer {
+                Signer::Rsa(_) => "RS256",
+                Signer::Ec(_) => "ES256",
+                Signer::Ed(_) => "EdDSA",
+            }
+        }
+
+        pub fn jwk(&self) -> JwtKey {
+            let b64 = |bytes: &[u8]| URL_SAFE_NO_PAD.encode(bytes);
+            let mut key = JwtKey {
+                e: String::new(),
+                kty: String::new(),
+                alg: Some(self.alg().to_string()),
+                n: String::new(),
+                kid: self.kid.clone(),
+                crv: None,
+                x: None,
+                y: None,
+                key_use: Some("sig".to_string()),
+            };
+            match &self.signer {
+                Signer::Rsa(pair) => {
+                    let components = pair.public().into();
+                    let ring::rsa::PublicKeyComponents::<Vec<u8>> { n, e } = components;
+                    key.kty = "RSA".to_string();
+                    key.n = b64(&n);
+                    key.e = b64(&e);
+                }
+                Signer::Ec(pair) => {
+                    let point = pair.public_key().as_ref();
+                    key.kty = "EC".to_string();
+                    key.crv = Some("P-256".to_string());
+                    key.x = Some(b64(&point[1..33]));
+                    key.y = Some(b64(&point[33..65]));
+                }
+                Signer::Ed(pair) => {
+                    key.kty = "OKP".to_string();
+                    key.crv = Some("Ed25519".to_string());
+                    key.x = Some(b64(pair.public_key().as_ref()));
+                }
+            }
+            key
+        }
+
+        pub fn sign(&self, claims: &Value) -> String {
+            let b64 = |bytes: &[u8]| URL_SAFE_NO_PAD.encode(bytes);
+            let header = json!({ "alg": self.alg(), "typ": "JWT", "kid": self.kid });
+            let body = format!(
+                "{}.{}",
+                b64(header.to_string().as_bytes()),
+                b64(claims.to_string().as_bytes())
+            );
+            let rng = SystemRandom::new();
+            let signature = match &self.signer {
+                Signer::Rsa(pair) => {
+                    let mut sig = vec![0; pair.public().modulus_len()];
+                    pair.sign(&RSA_PKCS1_SHA256, &rng, body.as_bytes(), &mut sig)
+                        .unwrap();
+                    sig
+                }
+                Signer::Ec(pair) => pair.sign(&rng, body.as_bytes()).unwrap().as_ref().to_vec(),
+                Signer::Ed(pair) => pair.sign(body.as_bytes()).as_ref().to_vec(),
+            };
+            format!("{body}.{}", b64(&signature))
+        }
+    }
+
+    pub fn jwks_body(keys: &[&TestKey]) -> Vec<u8> {
+        let keys: Vec<JwtKey> = keys.iter().map(|k| k.jwk()).collect();
+        json!({ "keys": keys }).to_string().into_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_keys::*;
+    use super::*;
+    use serde_json::json;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn claims() -> Value {
+        json!({ "sub": "pilgrim-1", "iss": "https://id.example", "iat": 1_000, "exp": 2_000 })
+    }
+
+    fn store(keys: &[&TestKey]) -> KeyStore {
+        KeyStore::from_keys(parse_jwks(&jwks_body(keys)).unwrap())
+    }
+
+    #[test]
+    fn verifies_each_supported_algorithm() {
+        for key in [
+            TestKey::rsa("r1"),
+            TestKey::ec("e1"),
+            TestKey::ed25519("d1"),
+        ] {
+            let token = key.sign(&claims());
+            let jwt = store(&[&key]).verify_time(&token, at(1_500)).unwrap();
+            assert_eq!(jwt.payload().sub(), Some("pilgrim-1"), "{}", key.alg());
+        }
+    }
+
+    #[test]
+    fn rejects_signature_from_another_key_with_same_kid() {
+        let published = TestKey::ec("shared");
+        let forger = TestKey::ec("shared");
+        let token = forger.sign(&claims());
+        let err = store(&[&published])
+            .verify_time(&token, at(1_500))
+            .unwrap_err();
+        assert_eq!(err.typ, Type::Signature);
+    }
+
+    #[test]
+    fn rejects_key_type_that_does_not_match_algorithm() {
+        let ec = TestKey::ec("k1");
+        let mut jwk = ec.jwk();
+        jwk.alg = None;
+        jwk.kty = "RSA".to_string();
+        let token = ec.sign(&claims());
+        let err = KeyStore::from_keys(vec![jwk])
+            .verify_time(&token, at(1_500))
+            .unwrap_err();
+        assert_eq!(err.typ, Type::Key);
+    }
+
+    #[test]
+    fn rejects_expired_and_unknown_kid() {
+        let key = TestKey::ed25519("d1");
+        let token = key.sign(&claims());
+        let keys = store(&[&key]);
+        assert_eq!(
+            keys.verify_time(&token, at(2_500)).unwrap_err().typ,
+            Type::Expired
+        );
+
+        let other = TestKey::ed25519("d2").sign(&claims());
+        assert_eq!(
+            keys.verify_time(&other, at(1_500)).unwrap_err().typ,
+            Type::Key
+        );
+    }
+
+    #[test]
+    fn parse_jwks_skips_encryption_keys_and_reads_kid() {
+        let key = TestKey::rsa("r1");
+        let mut enc = TestKey::ec("enc-1").jwk();
+        enc.key_use = Some("enc".to_string());
+        let body = json!({ "keys": [key.jwk(), enc] }).to_string();
+
+        let keys = parse_jwks(body.as_bytes()).unwrap();
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0].kid, "r1");
+        assert_eq!(token_kid(&key.sign(&claims())).as_deref(), Some("r1"));
+        assert_eq!(token_kid("not-a-token"), None);
+    }
+}
@@ -1,3 +1,5 @@
+pub mod cache;
 pub mod error;
 pub mod jwt;
 pub mod keyset;
+pub mod remote;
@@ -0,0 +1,122 @@
+use once_cell::sync::OnceCell;
+use spin_sdk::{
+    http,
+    redis::{Connection, RedisParameter, RedisResult},
+    variables,
+};
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::context::REDIS_ADDRESS_VAR;
+use crate::jwks_client::cache::{
+    parse_max_age, CachedKeySet, FetchedKeySet, KeySetCache, KeySetFetcher,
+};
+use crate::jwks_client::error::{err_con, Error};
+use crate::jwks_client::keyset::parse_jwks;
+
+/// Downloads JWKS documents with the outbound HTTP the component is allowed.
+pub struct HttpKeySetFetcher;
+
+impl KeySetFetcher for HttpKeySetFetcher {
+    async fn fetch(&self, jwks_uri: &str) -> Result<FetchedKeySet, Error> {
+        let req = http::Request::builder()
+            .method(http::Method::Get)
+            .uri(jwks_uri)
+            .header("accept", "application/json")
+            .body(())
+            .build();
+
+        let response: http::Response = http::send(req)
+            .await
+            .map_err(|_| err_con("Failed to fetch keys"))?;
+        if !(200..300).contains(response.status()) {
+            return Err(err_con("JWKS endpoint returned an error status"));
+        }
+
+        let max_age = response
+            .header("cache-control")
+            .and_then(|h| h.as_str())
+            .and_then(parse_max_age);
+
+        Ok(FetchedKeySet {
+            keys: parse_jwks(response.body())?,
+            max_age,
+        })
+    }
+}
+
+/// Key sets in Redis. Without a reachable Redis every lookup misses and
+/// claims fall back to a cooldown kept in this process, so verification
+/// degrades to fetching when the cache is cold, not on every unknown `kid`.
+pub struct RedisKeySetCache {
+    conn: Option<Connection>,
+}
+
+impl RedisKeySetCache {
+    pub fn open() -> Self {
+        let conn = variables::get(REDIS_ADDRESS_VAR)
+            .ok()
+            .and_then(|address| Connection::open(&address).ok());
+        Self { conn }
+    }
+}
+
+impl KeySetCache for RedisKeySetCache {
+    fn get(&self, key: &str) -> Option<CachedKeySet> {
+        let bytes = self.conn.as_ref()?.get(key).ok()??;
+        serde_json::from_slice(&bytes).ok()
+    }
+
+    fn set(&self, key: &str, value: &CachedKeySet, ttl_seconds: u64) {
+        let (Some(conn), Ok(bytes)) = (self.conn.as_ref(), serde_json::to_vec(value)) else {
+            return;
+        };
+        if conn.set(key, &bytes).is_ok() {
+            let _ = conn.execute(
+                "EXPIRE",
+                &[
+                    RedisParameter::Binary(key.as_bytes().to_vec()),
+                    RedisParameter::Int64(i64::try_from(ttl_seconds).unwrap_or(i64::MAX)),
+                ],
+            );
+        }
+    }
+
+    fn claim(&self, key: &str, ttl_seconds: u64) -> bool {
+        let Some(conn) = self.conn.as_ref() else {
+            return claim_locally(key, ttl_seconds);
+        };
+        let result = conn.execute(
+            "SET",
+            &[
+                RedisParameter::Binary(key.as_bytes().to_vec()),
+                RedisParameter::Binary(b"1".to_vec()),
+                RedisParameter::Binary(b"NX".to_vec()),
+                RedisParameter::Binary(b"EX".to_vec()),
+                RedisParameter::Int64(i64::try_from(ttl_seconds).unwrap_or(i64::MAX)),
+            ],
+        );
+        match result {
+            Ok(values) => !matches!(values.first(), Some(RedisResult::Nil) | None),
+            Err(_) => claim_locally(key, ttl_seconds),
+        }
+    }
+}
+
+/// [`KeySetCache::claim`] for when Redis is unreachable: the lock only holds
+/// within this process.
+fn claim_locally(key: &str, ttl_seconds: u64) -> bool {
+    static CLAIMS: OnceCell<Mutex<HashMap<String, Instant>>> = OnceCell::new();
+    let now = Instant::now();
+    let mut claims = CLAIMS
+        .get_or_init(Mutex::default)
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    claims.retain(|_, until| *until > now);
+    if claims.contains_key(key) {
+        return false;
+    }
+    claims.insert(key.to_string(), now + Duration::from_secs(ttl_seconds));
+    true
+}
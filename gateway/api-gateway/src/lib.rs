@@ -15,33 +15,37 @@ use spin_sdk::{
 };
 use tracing::{event, Level};
 
+mod admin;
+mod api_keys;
 mod auth;
 mod cache;
 mod circuit_breaker;
 mod context;
 mod events;
 mod gateway_config;
+mod internal_token;
 mod jwks_client;
 mod rate_limit;
 mod rejection;
 mod security_headers;
 mod telemetry;
+mod upstream;
 mod util;
-
-pub fn rewrite_upstream_path_for_test(path: &str, service: &str) -> String {
-    rewrite_upstream_path(path, service)
-}
+mod validation;
 
 pub fn gateway_config_for_test(bytes: &[u8]) -> anyhow::Result<gateway_config::GatewayConfig> {
     let text = std::str::from_utf8(bytes)?;
-    let cfg: gateway_config::GatewayConfig = toml::from_str(text)?;
-    Ok(cfg)
+    gateway_config::parse(text)
 }
 
+use api_keys::API_KEY_HEADER;
 use context::{
-    build_request_context, get_config, resolve_service_url, AuthContext, RequestContext,
-    CORRELATION_ID_HEADER, REDIS_ADDRESS_VAR, SERVICE_REGISTRY_STORE, TRACE_ID_HEADER,
+    build_request_context, get_config, registered_services, resolve_service_url, AuthContext,
+    RequestContext, CORRELATION_ID_HEADER, REDIS_ADDRESS_VAR, SERVICE_REGISTRY_STORE,
+    TRACEPARENT_HEADER, TRACESTATE_HEADER, TRACE_ID_HEADER,
 };
+use internal_token::{GATEWAY_TOKEN_HEADER, USER_CLAIMS_HEADER};
+use rate_limit::apply_rate_limit_headers;
 use rejection::GatewayRejection;
 use security_headers::apply_security_headers;
 
@@ -64,6 +68,26 @@ fn handle_gateway(req: Request) -> Result<impl IntoResponse> {
 
     router.get_async("/api/services", handle_list_services);
     router.post_async("/api/services/register", handle_register_service);
+    router.get_async("/api/gateway/metrics", telemetry::handle_metrics);
+    router.get_async("/api/gateway/admin/policies", admin::handle_policies);
+    router.get_async("/api/gateway/admin/services", admin::handle_services);
+    router.get_async("/api/gateway/admin/config", admin::handle_config);
+    router.put_async("/api/gateway/admin/config", admin::handle_config);
+    router.delete_async("/api/gateway/admin/config", admin::handle_config);
+    router.get_async(
+        "/api/gateway/admin/circuit-breakers",
+        admin::handle_circuit_breakers,
+    );
+    router.get_async("/api/gateway/admin/api-keys", admin::handle_api_keys);
+    router.post_async("/api/gateway/admin/api-keys", admin::handle_api_keys);
+    router.post_async(
+        "/api/gateway/admin/api-keys/:id/rotate",
+        admin::handle_api_key_rotate,
+    );
+    router.delete_async(
+        "/api/gateway/admin/api-keys/:id",
+        admin::handle_api_key_revoke,
+    );
 
     router.any_async("/api/*", handle_protected_route);
 
@@ -130,7 +154,14 @@ async fn handle_list_services(req: Request, _params: Params) -> Result<Response>
     let cfg = get_config()?;
     let mut services = Vec::new();
     for (name, svc) in cfg.services.iter() {
-        services.push(serde_json::json!({ "name": name, "url": svc.url }));
+        services.push(serde_json::json!({ "name": name, "url": svc.url, "source": "config" }));
+    }
+    for registration in registered_services()? {
+        services.push(serde_json::json!({
+            "name": registration.name,
+            "url": registration.url,
+            "source": "registry"
+        }));
     }
 
     let resp = ResponseBuilder::new(200)
@@ -175,15 +206,16 @@ async fn handle_register_service(req: Request, _params: Params) -> Result<Respon
 
 async fn handle_protected_route(req: Request, _params: Params) -> Result<Response> {
     let ctx = build_request_context(&req)?;
+    let timer = telemetry::RequestTimer::start();
+    telemetry::log_request_headers(&req, &ctx);
 
-    let span = tracing::info_span!(
-        "gateway_request",
-        correlation_id = %ctx.correlation_id,
-        trace_id = %ctx.trace_id,
-        service = %ctx.service,
-        method = %req.method(),
-        path = %req.path()
-    );
+    let response = proxy_request(&req, &ctx).await?;
+    telemetry::finish_request(&req, &ctx, *response.status(), &timer);
+    Ok(response)
+}
+
+async fn proxy_request(req: &Request, ctx: &RequestContext) -> Result<Response> {
+    let span = telemetry::request_span(req, ctx);
     let _enter = span.enter();
 
     if *req.method() == Method::Options {
@@ -204,29 +236,48 @@ async fn handle_protected_route(req: Request, _params: Params) -> Result<Respons
     );
 
     let auth_ctx: Option<AuthContext> = if ctx.policy.auth.enabled {
-        match auth::authenticate_and_authorize(&req, &ctx).await {
+        match auth::authenticate_and_authorize(req, ctx).await {
             Ok(a) => Some(a),
-            Err(rejection) => return Ok(rejection.into_response(&ctx)),
+            Err(rejection) => return Ok(rejection.into_response(ctx)),
         }
     } else {
         None
     };
 
+    let mut rate_limit_decision = None;
     if ctx.policy.rate_limit.enabled {
         if let Ok(redis_address) = variables::get(REDIS_ADDRESS_VAR) {
-            if let Err(rej) =
-                rate_limit::enforce_rate_limit(&redis_address, &ctx, auth_ctx.as_ref()).await
+            match rate_limit::enforce_rate_limit(&redis_address, req, ctx, auth_ctx.as_ref()).await
             {
-                return Ok(rej.into_response(&ctx));
+                Ok(decision) if !decision.allowed => {
+                    let rejection = GatewayRejection::TooManyRequests {
+                        message: "Rate limit exceeded".to_string(),
+                    }
+                    .into_response(ctx);
+                    return Ok(apply_rate_limit_headers(rejection, Some(&decision), ctx));
+                }
+                Ok(decision) => rate_limit_decision = Some(decision),
+                Err(rej) => return Ok(rej.into_response(ctx)),
             }
         }
     }
+    let rate_limit_decision = rate_limit_decision.as_ref();
+
+    if let Err(rejection) = validation::check_request(req, ctx) {
+        let rejection = rejection.into_response(ctx);
+        return Ok(apply_rate_limit_headers(
+            rejection,
+            rate_limit_decision,
+            ctx,
+        ));
+    }
 
     if ctx.policy.cache.enabled {
         if let Ok(redis_address) = variables::get(REDIS_ADDRESS_VAR) {
             if let Ok(Some(hit)) =
-                cache::try_cache_hit(&redis_address, &req, &ctx, auth_ctx.as_ref()).await
+                cache::try_cache_hit(&redis_address, req, ctx, auth_ctx.as_ref()).await
             {
+                let hit = apply_rate_limit_headers(hit, rate_limit_decision, ctx);
                 return Ok(apply_security_headers(hit, &ctx.policy));
             }
         }
@@ -234,46 +285,51 @@ async fn handle_protected_route(req: Request, _params: Params) -> Result<Respons
 
     if ctx.policy.circuit_breaker.enabled {
         if let Ok(redis_address) = variables::get(REDIS_ADDRESS_VAR) {
-            if let Some(resp) = circuit_breaker::precheck(&redis_address, &ctx).await? {
+            if let Some(resp) = circuit_breaker::precheck(&redis_address, ctx).await? {
+                let resp = apply_rate_limit_headers(resp, rate_limit_decision, ctx);
                 return Ok(apply_security_headers(resp, &ctx.policy));
             }
         }
     }
 
-    let mut response = match forward_to_service(&req, &ctx, auth_ctx.as_ref()).await {
-        Ok(r) => r,
-        Err(_) => {
-            return Ok(GatewayRejection::BadGateway {
-                message: "Upstream request failed".to_string(),
-            }
-            .into_response(&ctx))
+    let Ok(mut response) = forward_to_service(req, ctx, auth_ctx.as_ref()) else {
+        let rejection = GatewayRejection::BadGateway {
+            message: "Upstream request failed".to_string(),
         }
+        .into_response(ctx);
+        return Ok(apply_rate_limit_headers(
+            rejection,
+            rate_limit_decision,
+            ctx,
+        ));
     };
 
     if ctx.policy.circuit_breaker.enabled {
         if let Ok(redis_address) = variables::get(REDIS_ADDRESS_VAR) {
-            let _ = circuit_breaker::record(&redis_address, &ctx, *response.status()).await;
+            let _ = circuit_breaker::record(&redis_address, ctx, *response.status()).await;
         }
     }
 
+    // Intercept and publish domain events (fire-and-forget). This runs before
+    // the cache so a 304 answer cannot swallow the events of the response.
+    response = events::intercept_and_publish_events(response);
+
     if ctx.policy.cache.enabled {
         if let Ok(redis_address) = variables::get(REDIS_ADDRESS_VAR) {
-            let _ =
-                cache::try_cache_store(&redis_address, &req, &response, &ctx, auth_ctx.as_ref())
-                    .await;
+            response =
+                cache::try_cache_store(&redis_address, req, response, ctx, auth_ctx.as_ref()).await;
         }
     }
 
     response.set_header(CORRELATION_ID_HEADER, ctx.correlation_id.clone());
     response.set_header(TRACE_ID_HEADER, ctx.trace_id.clone());
 
-    // Intercept and publish domain events (fire-and-forget)
-    let response = events::intercept_and_publish_events(response);
+    let response = apply_rate_limit_headers(response, rate_limit_decision, ctx);
 
     Ok(apply_security_headers(response, &ctx.policy))
 }
 
-async fn forward_to_service(
+fn forward_to_service(
     req: &Request,
     ctx: &RequestContext,
     auth_ctx: Option<&AuthContext>,
@@ -283,41 +339,66 @@ async fn forward_to_service(
         Err(_) => return Ok(GatewayRejection::UnknownService.into_response(ctx)),
     };
 
-    let upstream_path = rewrite_upstream_path(req.path(), &ctx.service);
     let upstream_path_and_query = match req.query() {
-        q if !q.is_empty() => format!("{}?{}", upstream_path, q),
-        _ => upstream_path,
+        q if !q.is_empty() => format!("{}?{}", ctx.upstream_path, q),
+        _ => ctx.upstream_path.clone(),
     };
 
-    let mut forward_req = spin_sdk::http::Request::new(
-        req.method().clone(),
-        format!("{}{}", service_url, upstream_path_and_query),
-    );
-    *forward_req.body_mut() = req.body().to_vec();
-
-    for (name, value) in req.headers() {
-        if name.starts_with("spin-") || name == "host" {
-            continue;
-        }
-        if let Some(v) = value.as_str() {
-            forward_req.set_header(name, v);
-        }
+    let mut headers: Vec<(String, Vec<u8>)> = req
+        .headers()
+        .map(|(name, value)| (name.to_string(), value.as_bytes().to_vec()))
+        .filter(|(name, _)| {
+            ![
+                CORRELATION_ID_HEADER,
+                TRACE_ID_HEADER,
+                TRACEPARENT_HEADER,
+                TRACESTATE_HEADER,
+                USER_CLAIMS_HEADER,
+                "x-user-sub",
+                GATEWAY_TOKEN_HEADER,
+                API_KEY_HEADER,
+            ]
+            .iter()
+            .any(|h| name.eq_ignore_ascii_case(h))
+        })
+        .collect();
+    headers.push((
+        CORRELATION_ID_HEADER.to_string(),
+        ctx.correlation_id.clone().into_bytes(),
+    ));
+    headers.push((
+        TRACE_ID_HEADER.to_string(),
+        ctx.trace_id.clone().into_bytes(),
+    ));
+    headers.push((
+        TRACEPARENT_HEADER.to_string(),
+        ctx.trace.header().into_bytes(),
+    ));
+    if let Some(state) = ctx.tracestate.as_ref() {
+        headers.push((TRACESTATE_HEADER.to_string(), state.clone().into_bytes()));
     }
 
-    forward_req.set_header(CORRELATION_ID_HEADER, ctx.correlation_id.clone());
-    forward_req.set_header(TRACE_ID_HEADER, ctx.trace_id.clone());
-
-    if let Some(auth) = auth_ctx {
-        forward_req.set_header(
-            "x-user-claims",
-            serde_json::to_string(&auth.claims_for_headers)?,
-        );
-        if let Some(sub) = auth.subject.as_ref() {
-            forward_req.set_header("x-user-sub", sub.clone());
-        }
+    let user_claims = auth_ctx
+        .map(|auth| serde_json::to_string(&auth.claims_for_headers))
+        .transpose()?;
+    let subject = auth_ctx.and_then(|auth| auth.subject.as_deref());
+    if let Some(claims) = user_claims.as_ref() {
+        headers.push((USER_CLAIMS_HEADER.to_string(), claims.clone().into_bytes()));
+    }
+    if let Some(sub) = subject {
+        headers.push(("x-user-sub".to_string(), sub.as_bytes().to_vec()));
     }
+    let token = internal_token::mint(ctx, subject, user_claims.as_deref())?;
+    headers.push((GATEWAY_TOKEN_HEADER.to_string(), token.into_bytes()));
+
+    let upstream_req = upstream::UpstreamRequest {
+        method: req.method().to_string(),
+        url: format!("{service_url}{upstream_path_and_query}"),
+        headers,
+        body: req.body().to_vec(),
+    };
 
-    match spin_sdk::http::send::<_, Response>(forward_req).await {
+    match upstream::send(&upstream_req, ctx) {
         Ok(mut response) => {
             event!(
                 Level::INFO,
@@ -333,40 +414,23 @@ async fn forward_to_service(
 
             Ok(response)
         }
-        Err(_) => Ok(GatewayRejection::BadGateway {
-            message: "Service unavailable".to_string(),
+        Err(upstream::UpstreamError::Timeout) => Ok(GatewayRejection::GatewayTimeout {
+            message: "Upstream timed out".to_string(),
         }
         .into_response(ctx)),
-    }
-}
-
-fn rewrite_upstream_path(path: &str, service: &str) -> String {
-    let trimmed = path.trim_start_matches('/');
-    let mut parts = trimmed.split('/');
-    let first = parts.next().unwrap_or("");
-    let second = parts.next().unwrap_or("");
-    if first != "api" {
-        return path.to_string();
-    }
-
-    let rest: Vec<&str> = parts.collect();
-    let rest_path = if rest.is_empty() {
-        String::new()
-    } else {
-        format!("/{}", rest.join("/"))
-    };
-
-    match service {
-        "auth-service" if second == "auth" => format!("/api/auth{rest_path}"),
-        "location-service" if second == "countries" => format!("/api/countries{rest_path}"),
-        "redis-service" if second == "redis" => format!("/api/redis{rest_path}"),
-        "rate-limiter-service"
-        | "security-service"
-        | "reviews-service"
-        | "notification-service"
-        | "document-validation-service"
-        | "info-on-arrival-service"
-        | "booking-service" => format!("/api{rest_path}"),
-        _ => path.to_string(),
+        Err(upstream::UpstreamError::Failed(reason)) => {
+            event!(
+                Level::WARN,
+                correlation_id = ctx.correlation_id,
+                trace_id = ctx.trace_id,
+                service = ctx.service,
+                action = "upstream_failed",
+                reason = reason
+            );
+            Ok(GatewayRejection::BadGateway {
+                message: "Service unavailable".to_string(),
+            }
+            .into_response(ctx))
+        }
     }
 }
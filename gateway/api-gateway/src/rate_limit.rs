@@ -1,67 +1,127 @@
 use crate::{
     context::{AuthContext, RequestContext},
     rejection::GatewayRejection,
-    util::parse_redis_int,
+    util::parse_redis_ints,
 };
 use anyhow::Context;
+use api_gateway_core::{client_ip, RateLimitDecision, RateLimitInputs};
+use ring::digest::{digest, SHA256};
+use spin_sdk::{http::Response, redis::RedisParameter};
+use std::fmt::Write;
 use tracing::{event, Level};
+use uuid::Uuid;
 
+/// Header Spin sets to the address of the connecting peer.
+const CLIENT_ADDR_HEADER: &str = "spin-client-addr";
+
+/// Count the request against its bucket. Rejected requests come back as a
+/// decision with `allowed == false`, so their headers can still be sent.
 #[allow(clippy::unused_async)]
 pub async fn enforce_rate_limit(
     redis_address: &str,
+    req: &spin_sdk::http::Request,
     ctx: &RequestContext,
     auth: Option<&AuthContext>,
-) -> std::result::Result<(), GatewayRejection> {
-    let identity = match ctx.policy.rate_limit.key {
-        crate::gateway_config::RateLimitKey::Sub => auth
-            .and_then(|a| a.subject.clone())
-            .unwrap_or_else(|| "anon".to_string()),
-        crate::gateway_config::RateLimitKey::CorrelationId => ctx.correlation_id.clone(),
-    };
+) -> std::result::Result<RateLimitDecision, GatewayRejection> {
+    let policy = &ctx.policy.rate_limit;
+
+    let header = |name: &str| req.header(name).and_then(|h| h.as_str());
+    let ip = client_ip(
+        header("x-forwarded-for"),
+        header(CLIENT_ADDR_HEADER),
+        policy.trust_forwarded_for,
+    );
+    let api_key_hash = header(&policy.api_key_header).map(hash_api_key);
+    let identity = policy.key.identity(&RateLimitInputs {
+        subject: auth.and_then(|a| a.subject.as_deref()),
+        client_ip: ip.as_deref(),
+        api_key_hash: api_key_hash.as_deref(),
+        route: &ctx.route,
+    });
+    let key = format!(
+        "rl:{}:{}:{}",
+        policy.algorithm.as_str(),
+        ctx.service,
+        identity
+    );
 
-    let window = ctx.policy.rate_limit.window_seconds;
-    let now = chrono::Utc::now().timestamp() as u64;
-    let window_start = now - (now % window);
-    let key = format!("rl:{}:{}:{}", ctx.service, identity, window_start);
+    let unavailable = |e: anyhow::Error| GatewayRejection::ServiceUnavailable {
+        message: format!("Rate limit backend unavailable: {e}"),
+    };
 
     let conn = spin_sdk::redis::Connection::open(redis_address)
         .context("rate_limit_redis_open_failed")
-        .map_err(|e| GatewayRejection::ServiceUnavailable {
-            message: format!("Rate limit backend unavailable: {e}"),
-        })?;
+        .map_err(unavailable)?;
 
-    let script = r"local current=redis.call('INCR', KEYS[1]); if current==1 then redis.call('EXPIRE', KEYS[1], ARGV[1]); end; return current";
+    // Sliding-log entries need a member no client can repeat, so not the
+    // correlation id.
+    let member = Uuid::new_v4().to_string();
+    let mut params = vec![
+        RedisParameter::Binary(policy.algorithm.script().as_bytes().to_vec()),
+        RedisParameter::Binary(b"1".to_vec()),
+        RedisParameter::Binary(key.into_bytes()),
+    ];
+    params.extend(
+        policy
+            .algorithm
+            .script_args(
+                policy.max_requests,
+                policy.window_seconds,
+                policy.burst(),
+                &member,
+            )
+            .into_iter()
+            .map(|arg| RedisParameter::Binary(arg.into_bytes())),
+    );
 
     let res = conn
-        .execute(
-            "EVAL",
-            &[
-                spin_sdk::redis::RedisParameter::Binary(script.as_bytes().to_vec()),
-                spin_sdk::redis::RedisParameter::Binary(b"1".to_vec()),
-                spin_sdk::redis::RedisParameter::Binary(key.as_bytes().to_vec()),
-                spin_sdk::redis::RedisParameter::Int64(window as i64),
-            ],
-        )
+        .execute("EVAL", &params)
         .context("rate_limit_redis_eval_failed")
-        .map_err(|e| GatewayRejection::ServiceUnavailable {
-            message: format!("Rate limit backend unavailable: {e}"),
-        })?;
+        .map_err(unavailable)?;
+
+    let decision = parse_redis_ints(&res)
+        .and_then(|reply| {
+            RateLimitDecision::from_reply(&reply, policy.max_requests, policy.window_seconds)
+        })
+        .context("rate_limit_unexpected_reply")
+        .map_err(unavailable)?;
 
-    let current = parse_redis_int(&res).unwrap_or(0);
-    if current > ctx.policy.rate_limit.max_requests as i64 {
+    if !decision.allowed {
         event!(
             Level::WARN,
             correlation_id = ctx.correlation_id,
             trace_id = ctx.trace_id,
             service = ctx.service,
+            route = ctx.route,
             action = "rate_limited",
-            current = current,
-            max = ctx.policy.rate_limit.max_requests
+            algorithm = policy.algorithm.as_str(),
+            max = policy.max_requests,
+            retry_after = decision.retry_after_seconds
         );
-        return Err(GatewayRejection::TooManyRequests {
-            message: "Rate limit exceeded".to_string(),
-        });
     }
 
-    Ok(())
+    Ok(decision)
+}
+
+/// Add the decision's `RateLimit-*` (and on rejection `Retry-After`) headers.
+pub fn apply_rate_limit_headers(
+    mut response: Response,
+    decision: Option<&RateLimitDecision>,
+    ctx: &RequestContext,
+) -> Response {
+    if let Some(decision) = decision {
+        for (name, value) in decision.headers(&ctx.service) {
+            response.set_header(name, value);
+        }
+    }
+    response
+}
+
+fn hash_api_key(key: &str) -> String {
+    digest(&SHA256, key.as_bytes()).as_ref()[..16]
+        .iter()
+        .fold(String::new(), |mut hex, b| {
+            let _ = write!(hex, "{b:02x}");
+            hex
+        })
 }
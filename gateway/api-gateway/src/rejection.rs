@@ -1,12 +1,36 @@
-use crate::{context::RequestContext, security_headers::apply_security_headers};
+use crate::{
+    context::RequestContext, security_headers::apply_security_headers, validation::RequestProblem,
+};
 use spin_sdk::http::{Response, ResponseBuilder};
 
 pub enum GatewayRejection {
-    Unauthorized { message: String },
-    Forbidden { message: String },
-    TooManyRequests { message: String },
-    BadGateway { message: String },
-    ServiceUnavailable { message: String },
+    Unauthorized {
+        message: String,
+    },
+    Forbidden {
+        message: String,
+    },
+    NotFound {
+        message: String,
+    },
+    UnprocessableEntity {
+        message: String,
+    },
+    /// Refused by the route's validation policy; answered as RFC 7807
+    /// `application/problem+json` rather than the gateway's usual body.
+    InvalidRequest(RequestProblem),
+    TooManyRequests {
+        message: String,
+    },
+    BadGateway {
+        message: String,
+    },
+    GatewayTimeout {
+        message: String,
+    },
+    ServiceUnavailable {
+        message: String,
+    },
     UnknownService,
 }
 
@@ -15,15 +39,27 @@ impl GatewayRejection {
         let (status, error, message) = match self {
             GatewayRejection::Unauthorized { message } => (401, "Unauthorized", message),
             GatewayRejection::Forbidden { message } => (403, "Forbidden", message),
+            GatewayRejection::NotFound { message } => (404, "Not Found", message),
+            GatewayRejection::UnprocessableEntity { message } => {
+                (422, "Unprocessable Entity", message)
+            }
+            GatewayRejection::InvalidRequest(problem) => {
+                let resp = ResponseBuilder::new(problem.status)
+                    .header("content-type", "application/problem+json")
+                    .body(problem.to_json(ctx).to_string())
+                    .build();
+                return finish(resp, ctx);
+            }
             GatewayRejection::TooManyRequests { message } => (429, "Too Many Requests", message),
             GatewayRejection::BadGateway { message } => (502, "Bad Gateway", message),
+            GatewayRejection::GatewayTimeout { message } => (504, "Gateway Timeout", message),
             GatewayRejection::ServiceUnavailable { message } => {
                 (503, "Service Unavailable", message)
             }
             GatewayRejection::UnknownService => (404, "Not Found", "Unknown service".to_string()),
         };
 
-        let mut resp = ResponseBuilder::new(status)
+        let resp = ResponseBuilder::new(status)
             .header("content-type", "application/json")
             .body(
                 serde_json::json!({
@@ -35,13 +71,16 @@ impl GatewayRejection {
                 .to_string(),
             )
             .build();
+        finish(resp, ctx)
+    }
+}
 
-        resp.set_header(
-            crate::context::CORRELATION_ID_HEADER,
-            ctx.correlation_id.clone(),
-        );
-        resp.set_header(crate::context::TRACE_ID_HEADER, ctx.trace_id.clone());
+fn finish(mut resp: Response, ctx: &RequestContext) -> Response {
+    resp.set_header(
+        crate::context::CORRELATION_ID_HEADER,
+        ctx.correlation_id.clone(),
+    );
+    resp.set_header(crate::context::TRACE_ID_HEADER, ctx.trace_id.clone());
 
-        apply_security_headers(resp, &ctx.policy)
-    }
+    apply_security_headers(resp, &ctx.policy)
 }
@@ -1,4 +1,34 @@
+use crate::{
+    admin::authorize_admin,
+    context::{build_request_context, RequestContext, REDIS_ADDRESS_VAR},
+    security_headers::apply_security_headers,
+    upstream::{self, UpstreamError, UpstreamRequest},
+};
+use anyhow::{Context, Result};
+use api_gateway_core::{
+    metric_increments, redact_headers, render_prometheus, METRICS_RECORD_SCRIPT,
+};
 use once_cell::sync::OnceCell;
+use serde_json::{json, Value};
+use spin_sdk::{
+    http::{Params, Request, Response, ResponseBuilder},
+    redis::{Connection, RedisParameter, RedisResult},
+    variables,
+};
+use std::collections::BTreeMap;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tracing::{event, Level, Span};
+
+/// Base URL of an OTLP/HTTP collector, e.g. `http://localhost:4318`. Spans
+/// are not exported when it is empty.
+pub const OTLP_ENDPOINT_VAR: &str = "otlp_endpoint";
+
+/// The collector gets this long, without retries, so a slow or unreachable
+/// one adds little to the request it reports on.
+const OTLP_EXPORT_TIMEOUT_MS: u64 = 200;
+
+/// Redis set of the services that have metrics, each kept in `metrics:{service}`.
+const METRICS_SERVICES_KEY: &str = "metrics:services";
 
 pub fn init_tracing() {
     static INIT: OnceCell<()> = OnceCell::new();
@@ -9,3 +39,236 @@ pub fn init_tracing() {
             .try_init();
     });
 }
+
+/// When a proxied request started, for its span and duration metric.
+pub struct RequestTimer {
+    started: Instant,
+    started_unix_nanos: u128,
+}
+
+impl RequestTimer {
+    pub fn start() -> Self {
+        Self {
+            started: Instant::now(),
+            started_unix_nanos: unix_nanos(),
+        }
+    }
+}
+
+/// The span every log line of a proxied request is recorded under.
+pub fn request_span(req: &Request, ctx: &RequestContext) -> Span {
+    tracing::info_span!(
+        "gateway_request",
+        correlation_id = %ctx.correlation_id,
+        trace_id = %ctx.trace_id,
+        service = %ctx.service,
+        method = %req.method(),
+        path = %req.path()
+    )
+}
+
+/// Logs the request's headers when the policy's `log_headers` is on.
+/// Credentials, the rate limiter's API key header and `redact_headers` are
+/// never written out.
+pub fn log_request_headers(req: &Request, ctx: &RequestContext) {
+    let policy = &ctx.policy.observability;
+    if !policy.log_headers {
+        return;
+    }
+    let mut redacted = policy.redact_headers.clone();
+    redacted.push(ctx.policy.rate_limit.api_key_header.clone());
+
+    let headers = redact_headers(
+        req.headers()
+            .filter_map(|(name, value)| Some((name, value.as_str()?))),
+        &redacted,
+    );
+    let headers: BTreeMap<String, String> = headers.into_iter().collect();
+    event!(
+        Level::INFO,
+        correlation_id = ctx.correlation_id,
+        trace_id = ctx.trace_id,
+        service = ctx.service,
+        action = "request_headers",
+        headers = %json!(headers)
+    );
+}
+
+/// Records the request's rate, error and duration metrics and exports its
+/// span. Failures are logged, never surfaced to the client.
+pub fn finish_request(req: &Request, ctx: &RequestContext, status: u16, timer: &RequestTimer) {
+    if !ctx.policy.observability.enabled {
+        return;
+    }
+    let duration_ms = u64::try_from(timer.started.elapsed().as_millis()).unwrap_or(u64::MAX);
+
+    if let Err(err) = record_metrics(ctx, status, duration_ms) {
+        event!(
+            Level::WARN,
+            correlation_id = ctx.correlation_id,
+            trace_id = ctx.trace_id,
+            service = ctx.service,
+            action = "metrics_record_failed",
+            error = %err
+        );
+    }
+    if let Err(err) = export_span(req, ctx, status, timer.started_unix_nanos) {
+        event!(
+            Level::WARN,
+            correlation_id = ctx.correlation_id,
+            trace_id = ctx.trace_id,
+            service = ctx.service,
+            action = "otlp_export_failed",
+            error = %err
+        );
+    }
+}
+
+/// `GET /api/gateway/metrics` in the Prometheus text format, for admins.
+pub async fn handle_metrics(req: Request, _params: Params) -> Result<Response> {
+    let ctx = build_request_context(&req)?;
+    if let Err(rej) = authorize_admin(&req, &ctx).await {
+        return Ok(rej.into_response(&ctx));
+    }
+    let address = variables::get(REDIS_ADDRESS_VAR)?;
+    let conn = Connection::open(&address).context("redis_open_failed")?;
+
+    let mut services = BTreeMap::new();
+    for service in conn
+        .smembers(METRICS_SERVICES_KEY)
+        .context("metrics_services_failed")?
+    {
+        let values = conn
+            .execute(
+                "HGETALL",
+                &[RedisParameter::Binary(metrics_key(&service).into_bytes())],
+            )
+            .context("metrics_get_failed")?;
+        let fields: BTreeMap<String, u64> = values
+            .chunks_exact(2)
+            .filter_map(|pair| {
+                let value = redis_text(&pair[1])?.parse().ok()?;
+                Some((redis_text(&pair[0])?, value))
+            })
+            .collect();
+        services.insert(service, fields);
+    }
+
+    let resp = ResponseBuilder::new(200)
+        .header("content-type", "text/plain; version=0.0.4; charset=utf-8")
+        .body(render_prometheus(&services))
+        .build();
+    Ok(apply_security_headers(resp, &ctx.policy))
+}
+
+fn record_metrics(ctx: &RequestContext, status: u16, duration_ms: u64) -> Result<()> {
+    let address = variables::get(REDIS_ADDRESS_VAR)?;
+    let conn = Connection::open(&address).context("redis_open_failed")?;
+
+    let mut params = vec![
+        RedisParameter::Binary(METRICS_RECORD_SCRIPT.as_bytes().to_vec()),
+        RedisParameter::Int64(2),
+        RedisParameter::Binary(METRICS_SERVICES_KEY.as_bytes().to_vec()),
+        RedisParameter::Binary(metrics_key(&ctx.service).into_bytes()),
+        RedisParameter::Binary(ctx.service.as_bytes().to_vec()),
+    ];
+    for (field, by) in metric_increments(status, duration_ms) {
+        params.push(RedisParameter::Binary(field.into_bytes()));
+        params.push(RedisParameter::Binary(by.to_string().into_bytes()));
+    }
+    conn.execute("EVAL", &params)
+        .context("metrics_eval_failed")?;
+    Ok(())
+}
+
+/// Sends the gateway's server span to the collector as OTLP/HTTP JSON.
+fn export_span(req: &Request, ctx: &RequestContext, status: u16, start: u128) -> Result<()> {
+    if !ctx.trace.sampled() {
+        return Ok(());
+    }
+    let endpoint = variables::get(OTLP_ENDPOINT_VAR).unwrap_or_default();
+    if endpoint.trim().is_empty() {
+        return Ok(());
+    }
+
+    let attribute = |key: &str, value: Value| json!({ "key": key, "value": value });
+    let mut span = json!({
+        "traceId": ctx.trace.trace_id,
+        "spanId": ctx.trace.parent_id,
+        "parentSpanId": ctx.parent_span_id.clone().unwrap_or_default(),
+        "name": format!("{} {}", req.method(), ctx.route),
+        // SPAN_KIND_SERVER
+        "kind": 2,
+        "startTimeUnixNano": start.to_string(),
+        "endTimeUnixNano": unix_nanos().to_string(),
+        "attributes": [
+            attribute("http.request.method", json!({ "stringValue": req.method().to_string() })),
+            attribute("url.path", json!({ "stringValue": req.path() })),
+            attribute("http.route", json!({ "stringValue": ctx.route })),
+            attribute("http.response.status_code", json!({ "intValue": status.to_string() })),
+            attribute("gateway.service", json!({ "stringValue": ctx.service })),
+            attribute("gateway.correlation_id", json!({ "stringValue": ctx.correlation_id })),
+        ],
+        // STATUS_CODE_ERROR for server errors, otherwise left unset.
+        "status": { "code": if status >= 500 { 2 } else { 0 } },
+    });
+    if let Some(state) = ctx.tracestate.as_ref() {
+        span["traceState"] = json!(state);
+    }
+
+    let body = json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [attribute("service.name", json!({ "stringValue": "api-gateway" }))]
+            },
+            "scopeSpans": [{
+                "scope": { "name": "api-gateway", "version": env!("CARGO_PKG_VERSION") },
+                "spans": [span]
+            }]
+        }]
+    });
+
+    let mut export_ctx = ctx.clone();
+    let policy = &mut export_ctx.policy.upstream;
+    policy.timeout_ms = OTLP_EXPORT_TIMEOUT_MS;
+    policy.connect_timeout_ms = policy.connect_timeout_ms.min(OTLP_EXPORT_TIMEOUT_MS);
+    policy.retries = 0;
+    policy.hedge_after_ms = None;
+
+    let request = UpstreamRequest {
+        method: "POST".to_string(),
+        url: format!("{}/v1/traces", endpoint.trim().trim_end_matches('/')),
+        headers: vec![("content-type".to_string(), b"application/json".to_vec())],
+        body: serde_json::to_vec(&body)?,
+    };
+    let response = match upstream::send(&request, &export_ctx) {
+        Ok(response) => response,
+        Err(UpstreamError::Timeout) => anyhow::bail!("otlp_send_timeout"),
+        Err(UpstreamError::Failed(reason)) => anyhow::bail!("otlp_send_failed: {reason}"),
+    };
+    anyhow::ensure!(
+        (200..300).contains(response.status()),
+        "collector answered {}",
+        response.status()
+    );
+    Ok(())
+}
+
+fn metrics_key(service: &str) -> String {
+    format!("metrics:{service}")
+}
+
+fn redis_text(value: &RedisResult) -> Option<String> {
+    match value {
+        RedisResult::Binary(b) => String::from_utf8(b.clone()).ok(),
+        RedisResult::Status(s) => Some(s.clone()),
+        RedisResult::Int64(i) => Some(i.to_string()),
+        RedisResult::Nil => None,
+    }
+}
+
+fn unix_nanos() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos())
+}
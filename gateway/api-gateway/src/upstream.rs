@@ -0,0 +1,424 @@
+//! Calls to upstream services.
+//!
+//! Requests go out through wasi-http directly instead of
+//! `spin_sdk::http::send`, which takes no request options. That gives each
+//! attempt connect and first-byte timeouts, and lets a hedged copy of a
+//! request race the original.
+
+use crate::{
+    context::{RequestContext, REDIS_ADDRESS_VAR},
+    util::parse_redis_int,
+};
+use api_gateway_core::{
+    is_idempotent, retry_delay, AttemptOutcome, RetrySettings, RETRY_BUDGET_SCRIPT,
+};
+use ring::rand::{SecureRandom, SystemRandom};
+use spin_sdk::{
+    http::{Response, ResponseBuilder},
+    redis::{Connection, RedisParameter},
+    variables,
+};
+use std::time::Duration;
+use tracing::{event, Level};
+use wasi::{
+    clocks::monotonic_clock::{self, Instant},
+    http::{
+        outgoing_handler,
+        types::{
+            ErrorCode, Fields, FutureIncomingResponse, IncomingResponse, Method, OutgoingBody,
+            OutgoingRequest, RequestOptions, Scheme,
+        },
+    },
+    io::{poll, streams::StreamError},
+};
+
+const RETRY_BUDGET_WINDOW_SECONDS: i64 = 10;
+/// `blocking-write-and-flush` accepts at most 4096 bytes per call.
+const WRITE_CHUNK: usize = 4096;
+const READ_CHUNK: u64 = 64 * 1024;
+
+/// Headers that describe the client's connection rather than the request.
+const HOP_BY_HOP: [&str; 8] = [
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+    "http2-settings",
+];
+
+pub struct UpstreamRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, Vec<u8>)>,
+    pub body: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub enum UpstreamError {
+    /// The overall timeout passed before a response arrived.
+    Timeout,
+    /// The request could not be sent or the connection failed.
+    Failed(String),
+}
+
+fn is_forwardable_header(name: &str) -> bool {
+    !name.starts_with("spin-")
+        && name != "host"
+        && !HOP_BY_HOP.iter().any(|h| name.eq_ignore_ascii_case(h))
+}
+
+/// Send `req`, retrying idempotent requests per the route's upstream policy.
+pub fn send(req: &UpstreamRequest, ctx: &RequestContext) -> Result<Response, UpstreamError> {
+    let policy = &ctx.policy.upstream;
+    let deadline = monotonic_clock::now().saturating_add(millis(policy.timeout_ms));
+    let idempotent = is_idempotent(&req.method);
+    let settings = RetrySettings {
+        max_retries: if idempotent { policy.retries } else { 0 },
+        base_delay: Duration::from_millis(policy.retry_base_delay_ms),
+        max_delay: Duration::from_millis(policy.retry_max_delay_ms),
+    };
+    let hedge_after = policy.hedge_after_ms.filter(|_| idempotent);
+
+    let budget = RetryBudget::open(ctx);
+    if settings.max_retries > 0 || hedge_after.is_some() {
+        budget.record_request();
+    }
+
+    let mut retries_done = 0;
+    loop {
+        let result = attempt(req, ctx, deadline, hedge_after, &budget);
+        let reason = match &result {
+            Ok(response) => response.status().to_string(),
+            Err(UpstreamError::Timeout) => "timeout".to_string(),
+            Err(UpstreamError::Failed(e)) => e.clone(),
+        };
+        let outcome = match &result {
+            Ok(response) => AttemptOutcome::Response {
+                status: *response.status(),
+                retry_after: response.header("retry-after").and_then(|h| h.as_str()),
+            },
+            Err(UpstreamError::Timeout) => AttemptOutcome::Timeout,
+            Err(UpstreamError::Failed(_)) => AttemptOutcome::ConnectError,
+        };
+        let Some(delay) = retry_delay(
+            &settings,
+            retries_done,
+            outcome,
+            u64::try_from(chrono::Utc::now().timestamp()).unwrap_or(0),
+            remaining(deadline),
+            jitter(),
+        ) else {
+            return result;
+        };
+
+        if !budget.try_acquire() {
+            event!(
+                Level::WARN,
+                correlation_id = ctx.correlation_id,
+                trace_id = ctx.trace_id,
+                service = ctx.service,
+                action = "upstream_retry_budget_exhausted",
+                reason = reason
+            );
+            return result;
+        }
+
+        retries_done += 1;
+        event!(
+            Level::WARN,
+            correlation_id = ctx.correlation_id,
+            trace_id = ctx.trace_id,
+            service = ctx.service,
+            action = "upstream_retry",
+            retry = retries_done,
+            max_retries = settings.max_retries,
+            reason = reason,
+            delay_ms = u64::try_from(delay.as_millis()).unwrap_or(u64::MAX)
+        );
+        monotonic_clock::subscribe_duration(u64::try_from(delay.as_nanos()).unwrap_or(u64::MAX))
+            .block();
+    }
+}
+
+/// One attempt, plus its hedged copy if the original is slow to answer.
+fn attempt(
+    req: &UpstreamRequest,
+    ctx: &RequestContext,
+    deadline: Instant,
+    hedge_after_ms: Option<u64>,
+    budget: &RetryBudget,
+) -> Result<Response, UpstreamError> {
+    let mut in_flight = vec![start(req, ctx, deadline)?];
+    let mut hedge_at = hedge_after_ms
+        .map(|ms| monotonic_clock::now().saturating_add(millis(ms)))
+        .filter(|at| *at < deadline);
+    let mut last_error = None;
+
+    loop {
+        let timer_index = u32::try_from(in_flight.len()).unwrap_or(u32::MAX);
+        let ready = {
+            let timer = monotonic_clock::subscribe_instant(hedge_at.unwrap_or(deadline));
+            let pending: Vec<_> = in_flight
+                .iter()
+                .map(FutureIncomingResponse::subscribe)
+                .collect();
+            let mut pollables: Vec<_> = pending.iter().collect();
+            pollables.push(&timer);
+            poll::poll(&pollables)
+        };
+
+        let mut answered: Vec<usize> = ready
+            .iter()
+            .filter(|&&i| i < timer_index)
+            .map(|&i| i as usize)
+            .collect();
+        answered.sort_unstable_by(|a, b| b.cmp(a));
+        for index in answered {
+            match in_flight.remove(index).get() {
+                Some(Ok(Ok(response))) => return read_response(&response),
+                Some(Ok(Err(code))) => last_error = Some(classify(code)),
+                _ => last_error = Some(UpstreamError::Failed("response lost".to_string())),
+            }
+        }
+
+        if ready.contains(&timer_index) {
+            if hedge_at.take().is_none() {
+                return Err(UpstreamError::Timeout);
+            }
+            if !in_flight.is_empty() && budget.try_acquire() {
+                event!(
+                    Level::INFO,
+                    correlation_id = ctx.correlation_id,
+                    trace_id = ctx.trace_id,
+                    service = ctx.service,
+                    action = "upstream_hedge",
+                    after_ms = hedge_after_ms.unwrap_or_default()
+                );
+                match start(req, ctx, deadline) {
+                    Ok(future) => in_flight.push(future),
+                    Err(e) => last_error = Some(e),
+                }
+            }
+        }
+
+        if in_flight.is_empty() {
+            return Err(last_error.unwrap_or(UpstreamError::Timeout));
+        }
+    }
+}
+
+fn start(
+    req: &UpstreamRequest,
+    ctx: &RequestContext,
+    deadline: Instant,
+) -> Result<FutureIncomingResponse, UpstreamError> {
+    let failed = |what: &str| UpstreamError::Failed(what.to_string());
+    let (scheme, authority, path) =
+        split_url(&req.url).ok_or_else(|| failed("invalid upstream url"))?;
+
+    let headers: Vec<(String, Vec<u8>)> = req
+        .headers
+        .iter()
+        .filter(|(name, _)| is_forwardable_header(name))
+        .cloned()
+        .collect();
+    let fields =
+        Fields::from_list(&headers).map_err(|e| UpstreamError::Failed(format!("{e:?}")))?;
+
+    let request = OutgoingRequest::new(fields);
+    request
+        .set_method(&method(&req.method))
+        .map_err(|()| failed("invalid method"))?;
+    request
+        .set_scheme(Some(&scheme))
+        .map_err(|()| failed("invalid scheme"))?;
+    request
+        .set_authority(Some(authority))
+        .map_err(|()| failed("invalid authority"))?;
+    request
+        .set_path_with_query(Some(path))
+        .map_err(|()| failed("invalid path"))?;
+    let body = request.body().map_err(|()| failed("request body taken"))?;
+
+    let left = remaining_nanos(deadline);
+    let options = RequestOptions::new();
+    let _ = options.set_connect_timeout(Some(
+        millis(ctx.policy.upstream.connect_timeout_ms).min(left),
+    ));
+    let _ = options.set_first_byte_timeout(Some(left));
+    let _ = options.set_between_bytes_timeout(Some(left));
+
+    let future = outgoing_handler::handle(request, Some(options)).map_err(classify)?;
+    {
+        let stream = body
+            .write()
+            .map_err(|()| failed("request body stream taken"))?;
+        for chunk in req.body.chunks(WRITE_CHUNK) {
+            stream
+                .blocking_write_and_flush(chunk)
+                .map_err(|e| UpstreamError::Failed(format!("{e:?}")))?;
+        }
+    }
+    OutgoingBody::finish(body, None).map_err(classify)?;
+    Ok(future)
+}
+
+fn read_response(response: &IncomingResponse) -> Result<Response, UpstreamError> {
+    let status = response.status();
+    let headers = response.headers().entries();
+    let body = response
+        .consume()
+        .map_err(|()| UpstreamError::Failed("response body taken".to_string()))?;
+
+    let mut bytes = Vec::new();
+    {
+        let stream = body
+            .stream()
+            .map_err(|()| UpstreamError::Failed("response stream taken".to_string()))?;
+        loop {
+            match stream.blocking_read(READ_CHUNK) {
+                Ok(chunk) => bytes.extend_from_slice(&chunk),
+                Err(StreamError::Closed) => break,
+                Err(StreamError::LastOperationFailed(e)) => {
+                    return Err(UpstreamError::Failed(e.to_debug_string()))
+                }
+            }
+        }
+    }
+    drop(body);
+
+    let mut builder = ResponseBuilder::new(status);
+    for (name, value) in headers {
+        if is_forwardable_header(&name) {
+            builder.header(name, String::from_utf8_lossy(&value).into_owned());
+        }
+    }
+    Ok(builder.body(bytes).build())
+}
+
+fn classify(code: ErrorCode) -> UpstreamError {
+    match code {
+        ErrorCode::HttpResponseTimeout | ErrorCode::ConnectionReadTimeout => UpstreamError::Timeout,
+        other => UpstreamError::Failed(format!("{other:?}")),
+    }
+}
+
+fn split_url(url: &str) -> Option<(Scheme, &str, &str)> {
+    let (scheme, rest) = url.split_once("://")?;
+    let scheme = match scheme {
+        "http" => Scheme::Http,
+        "https" => Scheme::Https,
+        other => Scheme::Other(other.to_string()),
+    };
+    let (authority, path) = match rest.find('/') {
+        Some(i) => rest.split_at(i),
+        None => (rest, "/"),
+    };
+    (!authority.is_empty()).then_some((scheme, authority, path))
+}
+
+fn method(name: &str) -> Method {
+    match name.to_ascii_uppercase().as_str() {
+        "GET" => Method::Get,
+        "HEAD" => Method::Head,
+        "POST" => Method::Post,
+        "PUT" => Method::Put,
+        "DELETE" => Method::Delete,
+        "CONNECT" => Method::Connect,
+        "OPTIONS" => Method::Options,
+        "TRACE" => Method::Trace,
+        "PATCH" => Method::Patch,
+        other => Method::Other(other.to_string()),
+    }
+}
+
+fn millis(ms: u64) -> u64 {
+    ms.saturating_mul(1_000_000)
+}
+
+fn remaining_nanos(deadline: Instant) -> u64 {
+    deadline.saturating_sub(monotonic_clock::now())
+}
+
+fn remaining(deadline: Instant) -> Duration {
+    Duration::from_nanos(remaining_nanos(deadline))
+}
+
+/// Uniform in `[0, 1)`.
+fn jitter() -> f64 {
+    let mut bytes = [0u8; 4];
+    if SystemRandom::new().fill(&mut bytes).is_err() {
+        return 0.5;
+    }
+    f64::from(u32::from_le_bytes(bytes)) / (f64::from(u32::MAX) + 1.0)
+}
+
+/// Shared count of requests and retries per service, so that retries and
+/// hedges stay a small share of traffic however many gateway instances
+/// are running. Without Redis only `retries` bounds them.
+struct RetryBudget<'a> {
+    conn: Option<Connection>,
+    ctx: &'a RequestContext,
+    window: i64,
+}
+
+impl<'a> RetryBudget<'a> {
+    fn open(ctx: &'a RequestContext) -> Self {
+        let conn = variables::get(REDIS_ADDRESS_VAR)
+            .ok()
+            .and_then(|address| Connection::open(&address).ok());
+        let window = chrono::Utc::now().timestamp() / RETRY_BUDGET_WINDOW_SECONDS;
+        Self { conn, ctx, window }
+    }
+
+    fn key(&self, counter: &str) -> String {
+        format!(
+            "retry_budget:{}:{}:{counter}",
+            self.ctx.service, self.window
+        )
+    }
+
+    fn record_request(&self) {
+        let Some(conn) = self.conn.as_ref() else {
+            return;
+        };
+        let key = self.key("requests");
+        if conn.incr(&key).is_ok_and(|count| count == 1) {
+            let _ = conn.execute(
+                "EXPIRE",
+                &[
+                    RedisParameter::Binary(key.into_bytes()),
+                    RedisParameter::Int64(RETRY_BUDGET_WINDOW_SECONDS),
+                ],
+            );
+        }
+    }
+
+    fn try_acquire(&self) -> bool {
+        let Some(conn) = self.conn.as_ref() else {
+            return true;
+        };
+        let policy = &self.ctx.policy.upstream;
+        let result = conn.execute(
+            "EVAL",
+            &[
+                RedisParameter::Binary(RETRY_BUDGET_SCRIPT.as_bytes().to_vec()),
+                RedisParameter::Binary(b"2".to_vec()),
+                RedisParameter::Binary(self.key("requests").into_bytes()),
+                RedisParameter::Binary(self.key("retries").into_bytes()),
+                RedisParameter::Int64(RETRY_BUDGET_WINDOW_SECONDS),
+                RedisParameter::Int64(
+                    i64::try_from(policy.retry_budget_percent).unwrap_or(i64::MAX),
+                ),
+                RedisParameter::Int64(i64::try_from(policy.retry_budget_min).unwrap_or(i64::MAX)),
+            ],
+        );
+        match result {
+            Ok(reply) => parse_redis_int(&reply) == Some(1),
+            Err(_) => true,
+        }
+    }
+}
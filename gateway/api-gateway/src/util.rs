@@ -8,3 +8,10 @@
         spin_sdk::redis::RedisResult::Nil => None,
     }
 }
+
+/// Every element of a reply as an integer, as returned for a script's array.
+pub fn parse_redis_ints(res: &[spin_sdk::redis::RedisResult]) -> Option<Vec<i64>> {
+    res.iter()
+        .map(|item| parse_redis_int(std::slice::from_ref(item)))
+        .collect()
+}
@@ -0,0 +1,158 @@
+use crate::{
+    context::{get_config, RequestContext},
+    rejection::GatewayRejection,
+};
+use api_gateway_core::{query_instance, validate, SchemaViolation};
+use serde::Serialize;
+use serde_json::{json, Value};
+use spin_sdk::http::{Method, Request};
+use tracing::{event, Level};
+
+/// Why a request was refused, as an RFC 7807 problem.
+pub struct RequestProblem {
+    pub status: u16,
+    /// Last segment of the problem's `type` URI.
+    pub kind: &'static str,
+    pub title: &'static str,
+    pub detail: String,
+    pub errors: Vec<ProblemError>,
+}
+
+#[derive(Serialize)]
+pub struct ProblemError {
+    /// `body` or `query`.
+    #[serde(rename = "in")]
+    pub location: &'static str,
+    /// JSON Pointer into the body, or into the query seen as an object of
+    /// parameters; empty for the document as a whole.
+    pub pointer: String,
+    pub detail: String,
+}
+
+impl RequestProblem {
+    pub fn to_json(&self, ctx: &RequestContext) -> Value {
+        json!({
+            "type": format!("/problems/{}", self.kind),
+            "title": self.title,
+            "status": self.status,
+            "detail": self.detail,
+            "errors": self.errors,
+            "service": ctx.service,
+            "correlation_id": ctx.correlation_id,
+        })
+    }
+}
+
+/// Enforces the route's body size limit and its body and query schemas.
+pub fn check_request(req: &Request, ctx: &RequestContext) -> Result<(), GatewayRejection> {
+    let policy = &ctx.policy.validation;
+    if !policy.enabled {
+        return Ok(());
+    }
+
+    let declared = req
+        .header("content-length")
+        .and_then(|h| h.as_str())
+        .and_then(|v| v.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+    if req.body().len().max(declared) > policy.max_body_bytes {
+        return Err(reject(
+            ctx,
+            RequestProblem {
+                status: 413,
+                kind: "payload-too-large",
+                title: "Payload Too Large",
+                detail: format!(
+                    "Request bodies on this route are limited to {} bytes",
+                    policy.max_body_bytes
+                ),
+                errors: Vec::new(),
+            },
+        ));
+    }
+
+    let mut errors = Vec::new();
+    if let Some(name) = policy.query_schema.as_deref() {
+        let schema = schema(name)?;
+        let instance = query_instance(req.query(), schema);
+        errors.extend(located("query", validate(schema, &instance)));
+    }
+    if let (Some(name), true) = (
+        policy.body_schema.as_deref(),
+        matches!(req.method(), Method::Post | Method::Put | Method::Patch),
+    ) {
+        match body_json(req) {
+            Ok(body) => errors.extend(located("body", validate(schema(name)?, &body))),
+            Err(detail) => errors.push(ProblemError {
+                location: "body",
+                pointer: String::new(),
+                detail,
+            }),
+        }
+    }
+
+    if errors.is_empty() {
+        return Ok(());
+    }
+    Err(reject(
+        ctx,
+        RequestProblem {
+            status: 400,
+            kind: "invalid-request",
+            title: "Bad Request",
+            detail: "The request does not match the schema of this route".to_string(),
+            errors,
+        },
+    ))
+}
+
+fn body_json(req: &Request) -> Result<Value, String> {
+    let content_type = req
+        .header("content-type")
+        .and_then(|h| h.as_str())
+        .unwrap_or("")
+        .to_ascii_lowercase();
+    let media_type = content_type.split(';').next().unwrap_or("").trim();
+    if media_type != "application/json" && !media_type.ends_with("+json") {
+        return Err("must be sent as application/json".to_string());
+    }
+    if req.body().is_empty() {
+        return Err("is required".to_string());
+    }
+    serde_json::from_slice(req.body()).map_err(|e| format!("is not valid JSON: {e}"))
+}
+
+/// Schemas are read and checked with the config, so one named by a policy
+/// is always there.
+fn schema(name: &str) -> Result<&'static Value, GatewayRejection> {
+    get_config()
+        .ok()
+        .and_then(|cfg| cfg.schemas.get(name))
+        .ok_or_else(|| GatewayRejection::ServiceUnavailable {
+            message: format!("Schema {name} is not loaded"),
+        })
+}
+
+fn located(location: &'static str, violations: Vec<SchemaViolation>) -> Vec<ProblemError> {
+    violations
+        .into_iter()
+        .map(|v| ProblemError {
+            location,
+            pointer: v.pointer,
+            detail: v.message,
+        })
+        .collect()
+}
+
+fn reject(ctx: &RequestContext, problem: RequestProblem) -> GatewayRejection {
+    event!(
+        Level::INFO,
+        correlation_id = ctx.correlation_id,
+        trace_id = ctx.trace_id,
+        service = ctx.service,
+        action = "request_invalid",
+        status = problem.status,
+        errors = problem.errors.len()
+    );
+    GatewayRejection::InvalidRequest(problem)
+}